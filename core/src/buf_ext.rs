@@ -0,0 +1,179 @@
+// Reads of the integers, length-encoded integers and strings the protocol is made of, bounds
+// checked, on top of `bytes::Buf`, and their writes on top of `bytes::BufMut`.
+#![cfg_attr(
+  not(test),
+  deny(
+    clippy::unwrap_used,
+    clippy::expect_used,
+    clippy::panic,
+    clippy::todo,
+    clippy::unimplemented
+  )
+)]
+
+use super::io;
+use super::util::{unexpected_eof, unexpected_err};
+use alloc::string::String;
+use alloc::vec::Vec;
+use alloc::{format, vec};
+use bytes::{Buf, BufMut};
+
+// Every read is bounds checked and returns an UnexpectedEof error instead of panicking, since
+// the buffers are filled with whatever the server sent us.
+pub trait BufExt: Buf {
+  fn peek_u8(&self) -> Option<u8> {
+    self.bytes().first().copied()
+  }
+
+  fn safe_get_lenc_bytes(&mut self) -> io::Result<Vec<u8>> {
+    let len = self.safe_get_lenc_uint()?;
+    self.safe_get_bytes(safe_len(len)?)
+  }
+
+  fn safe_get_eof_string(&mut self) -> io::Result<String> {
+    self.safe_get_fixed_length_string(self.remaining())
+  }
+
+  // Returns a utf-8 encoded string terminated by \0.
+  fn safe_null_terminated_string(&mut self) -> io::Result<String> {
+    let len = self
+      .bytes()
+      .iter()
+      .position(|x| *x == 0x00)
+      .unwrap_or(self.remaining());
+
+    self.safe_get_fixed_length_string(len)
+  }
+
+  // Returns a utf-8 encoded string of length N, where N are in bytes.
+  fn safe_get_fixed_length_string(&mut self, len: usize) -> io::Result<String> {
+    let bytes = self.safe_get_bytes(len)?;
+    String::from_utf8(bytes).map_err(unexpected_err)
+  }
+
+  // Returns a utf-8 encoded string of variable length. See `BufExt::safe_get_lenc_uint`.
+  fn safe_get_lenc_string(&mut self) -> io::Result<String> {
+    let len = self.safe_get_lenc_uint()?;
+    self.safe_get_fixed_length_string(safe_len(len)?)
+  }
+
+  // Same as get_u8, but returns an UnexpectedEof error instead of panicking when remaining < 1;
+  fn safe_get_u8(&mut self) -> io::Result<u8> {
+    self.safe_get_uint_le(1).map(|x| x as u8)
+  }
+
+  fn safe_get_u16_le(&mut self) -> io::Result<u16> {
+    self.safe_get_uint_le(2).map(|x| x as u16)
+  }
+
+  fn safe_get_u32_le(&mut self) -> io::Result<u32> {
+    self.safe_get_uint_le(4).map(|x| x as u32)
+  }
+
+  fn safe_get_u64_le(&mut self) -> io::Result<u64> {
+    self.safe_get_uint_le(8)
+  }
+
+  // Same as get_uint_le, but returns an error instead of panicking when remaining < nbytes, or
+  // when nbytes does not fit in a u64.
+  fn safe_get_uint_le(&mut self, nbytes: usize) -> io::Result<u64> {
+    check_uint_len(nbytes)?;
+    self.ensure_remaining(nbytes)?;
+    Ok(self.get_uint_le(nbytes))
+  }
+
+  // Same as get_uint, but big-endian values are rare enough in the protocol that only the safe
+  // version exists. Used for the binlog temporal and decimal types.
+  fn safe_get_uint_be(&mut self, nbytes: usize) -> io::Result<u64> {
+    check_uint_len(nbytes)?;
+    self.ensure_remaining(nbytes)?;
+    Ok(self.get_uint(nbytes))
+  }
+
+  fn safe_get_bytes(&mut self, len: usize) -> io::Result<Vec<u8>> {
+    self.ensure_remaining(len)?;
+    let mut bytes = vec![0; len];
+    self.copy_to_slice(bytes.as_mut_slice());
+    Ok(bytes)
+  }
+
+  // Same as advance, but returns an UnexpectedEof error instead of panicking when
+  // remaining < cnt.
+  fn safe_advance(&mut self, cnt: usize) -> io::Result<()> {
+    self.ensure_remaining(cnt)?;
+    self.advance(cnt);
+    Ok(())
+  }
+
+  fn safe_get_lenc_uint(&mut self) -> io::Result<u64> {
+    match self.safe_get_u8()? {
+      0xfc => self.safe_get_uint_le(2),
+      0xfd => self.safe_get_uint_le(3),
+      0xfe => self.safe_get_uint_le(8),
+      0xff => Err(unexpected_err("Invalid length-encoded integer value")),
+      x => Ok(x as u64),
+    }
+  }
+
+  fn ensure_remaining(&self, len: usize) -> io::Result<()> {
+    if self.remaining() >= len {
+      Ok(())
+    } else {
+      Err(unexpected_eof(format!(
+        "expected {}, got {}",
+        len,
+        self.remaining()
+      )))
+    }
+  }
+}
+
+fn check_uint_len(nbytes: usize) -> io::Result<()> {
+  if nbytes <= 8 {
+    Ok(())
+  } else {
+    Err(unexpected_err(format!(
+      "integers are at most 8 bytes, got {}",
+      nbytes
+    )))
+  }
+}
+
+// Lengths read off the wire are u64, but anything that does not fit in memory is necessarily
+// invalid.
+fn safe_len(len: u64) -> io::Result<usize> {
+  use core::convert::TryFrom;
+  usize::try_from(len).map_err(unexpected_err)
+}
+
+// Blanket implementations
+impl<T> BufExt for T where T: Buf {}
+
+// Writes the length-encoded integers and strings of the protocol, e.g. of the parameters of
+// prepared statements.
+pub trait BufMutExt: BufMut {
+  fn put_lenc_uint(&mut self, v: u64) {
+    match v {
+      0..=0xfa => self.put_u8(v as u8),
+      0xfb..=0xffff => {
+        self.put_u8(0xfc);
+        self.put_uint_le(v, 2);
+      }
+      0x1_0000..=0xff_ffff => {
+        self.put_u8(0xfd);
+        self.put_uint_le(v, 3);
+      }
+      _ => {
+        self.put_u8(0xfe);
+        self.put_u64_le(v);
+      }
+    }
+  }
+
+  fn put_lenc_bytes(&mut self, bytes: &[u8]) {
+    self.put_lenc_uint(bytes.len() as u64);
+    self.put_slice(bytes);
+  }
+}
+
+impl<T> BufMutExt for T where T: BufMut {}
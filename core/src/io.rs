@@ -0,0 +1,59 @@
+//! `std::io` errors when built with the standard library. Without it, a stand-in with the subset
+//! of the API used by the parsers, so that they can be written once.
+
+#[cfg(feature = "std")]
+pub use std::io::{Error, ErrorKind, Result};
+
+#[cfg(not(feature = "std"))]
+pub use self::no_std::{Error, ErrorKind, Result};
+
+#[cfg(not(feature = "std"))]
+mod no_std {
+  use alloc::boxed::Box;
+  use core::fmt;
+
+  type BoxError = Box<dyn core::error::Error + Send + Sync>;
+
+  pub type Result<T> = core::result::Result<T, Error>;
+
+  #[derive(Clone, Copy, Eq, PartialEq, Debug)]
+  pub enum ErrorKind {
+    UnexpectedEof,
+    Other,
+  }
+
+  #[derive(Debug)]
+  pub struct Error {
+    kind: ErrorKind,
+    error: BoxError,
+  }
+
+  impl Error {
+    pub fn new<E: Into<BoxError>>(kind: ErrorKind, error: E) -> Self {
+      Self {
+        kind,
+        error: error.into(),
+      }
+    }
+
+    pub fn other<E: Into<BoxError>>(error: E) -> Self {
+      Self::new(ErrorKind::Other, error)
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+      self.kind
+    }
+  }
+
+  impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+      self.error.fmt(f)
+    }
+  }
+
+  impl core::error::Error for Error {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+      self.error.source()
+    }
+  }
+}
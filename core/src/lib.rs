@@ -0,0 +1,29 @@
+//! Parsers for the MYSQL client/server protocol and binlog events, without any network IO.
+//!
+//! This is the part of tail_mysql that browser or edge tools need to parse binlog dumps (see
+//! `protocol_binlog::BinlogFile`). Disable the default `std` feature to build it without the
+//! standard library, e.g. for wasm32.
+//!
+//! The parsers take bytes sent by the server, which can not be trusted: malformed input is reported
+//! as an error, never as a panic. `protocol`, `protocol_binlog`, `value` and `buf_ext` deny the
+//! clippy lints of the calls that panic (`unwrap`, `expect`, ...) outside of their tests, and the
+//! fuzz targets under fuzz/ of the repository exercise them.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+#![allow(dead_code)]
+#![allow(unused_variables)]
+#![allow(unused_imports)]
+#![allow(unused_assignments)]
+#![allow(unused_mut)]
+
+extern crate alloc;
+
+#[doc(hidden)]
+pub mod buf_ext;
+pub mod io;
+pub mod protocol;
+pub mod protocol_binlog;
+pub mod time_zone;
+#[doc(hidden)]
+pub mod util;
+pub mod value;
@@ -1,10 +1,26 @@
+// Packets of the client/server protocol: framing, the handshake, the authentication responses, and
+// the responses to commands, i.e. OK and ERR packets, column definitions and rows.
+#![cfg_attr(
+  not(test),
+  deny(
+    clippy::unwrap_used,
+    clippy::expect_used,
+    clippy::panic,
+    clippy::todo,
+    clippy::unimplemented
+  )
+)]
+
 use super::buf_ext::BufExt;
-use super::util::{null_terminated_pos, unexpected_eof};
+use super::io;
+use super::util::{unexpected_eof, unexpected_err};
 use super::value::Value;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
 use bitflags::bitflags;
 use bytes::{Buf, Bytes};
-use std::cmp::max;
-use std::io;
+use core::convert::TryFrom;
 
 pub const MYSQL_NATIVE_PASSWORD_PLUGIN_NAME: &str = "mysql_native_password";
 pub const CACHING_SHA2_PASSWORD_PLUGIN_NAME: &str = "caching_sha2_password";
@@ -64,6 +80,7 @@ bitflags! {
       const CLIENT_CAN_HANDLE_EXPIRED_PASSWORDS = 0x00400000;
       const CLIENT_SESSION_TRACK = 0x00800000;
       const CLIENT_DEPRECATE_EOF = 0x01000000;
+      const CLIENT_ZSTD_COMPRESSION_ALGORITHM = 0x04000000;
       const CLIENT_PROGRESS_OBSOLETE = 0x20000000;
       const CLIENT_SSL_VERIFY_SERVER_CERT = 0x40000000;
       const CLIENT_REMEMBER_OPTIONS = 0x80000000;
@@ -90,9 +107,9 @@ bitflags! {
 }
 
 // https://dev.mysql.com/doc/internals/en/character-set.html
-#[allow(non_camel_case_types)]
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
 #[repr(u8)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum CharacterSet {
   BIG5 = 0x01_u8,
   DEC8 = 0x03_u8,
@@ -138,7 +155,7 @@ pub enum CharacterSet {
 }
 
 // https://dev.mysql.com/doc/internals/en/character-set.html
-#[allow(non_camel_case_types)]
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
 #[repr(u8)]
 #[derive(Debug, Clone, Copy)]
 pub enum Collation {
@@ -182,12 +199,67 @@ pub enum Collation {
   CP932_JAPANESE_CI = 0x5F_u8,
   EUCJPMS_JAPANESE_CI = 0x61_u8,
   GB18030_CHINESE_CI = 0xF8_u8,
+  UTF8MB4_GENERAL_CI = 0x2D_u8,
   UTF8MB4_0900_AI_CI = 0xFF_u8,
 }
 
-impl From<u8> for CharacterSet {
-  fn from(id: u8) -> Self {
-    match id {
+impl CharacterSet {
+  /// Character set of a MYSQL name, e.g. `utf8mb4`, case insensitive.
+  pub fn from_name(name: &str) -> Option<Self> {
+    let character_set = match name.to_ascii_lowercase().as_str() {
+      "big5" => CharacterSet::BIG5,
+      "dec8" => CharacterSet::DEC8,
+      "cp850" => CharacterSet::CP850,
+      "hp8" => CharacterSet::HP8,
+      "koi8r" => CharacterSet::KOI8R,
+      "latin1" => CharacterSet::LATIN1,
+      "latin2" => CharacterSet::LATIN2,
+      "swe7" => CharacterSet::SWE7,
+      "ascii" => CharacterSet::ASCII,
+      "ujis" => CharacterSet::UJIS,
+      "sjis" => CharacterSet::SJIS,
+      "hebrew" => CharacterSet::HEBREW,
+      "tis620" => CharacterSet::TIS620,
+      "euckr" => CharacterSet::EUCKR,
+      "koi8u" => CharacterSet::KOI8U,
+      "gb2312" => CharacterSet::GB2312,
+      "greek" => CharacterSet::GREEK,
+      "cp1250" => CharacterSet::CP1250,
+      "gbk" => CharacterSet::GBK,
+      "latin5" => CharacterSet::LATIN5,
+      "armscii8" => CharacterSet::ARMSCII8,
+      "utf8" => CharacterSet::UTF8,
+      "ucs2" => CharacterSet::UCS2,
+      "cp866" => CharacterSet::CP866,
+      "keybcs2" => CharacterSet::KEYBCS2,
+      "macce" => CharacterSet::MACCE,
+      "macroman" => CharacterSet::MACROMAN,
+      "cp852" => CharacterSet::CP852,
+      "latin7" => CharacterSet::LATIN7,
+      "cp1251" => CharacterSet::CP1251,
+      "utf16" => CharacterSet::UTF16,
+      "utf16le" => CharacterSet::UTF16LE,
+      "cp1256" => CharacterSet::CP1256,
+      "cp1257" => CharacterSet::CP1257,
+      "utf32" => CharacterSet::UTF32,
+      "binary" => CharacterSet::BINARY,
+      "geostd8" => CharacterSet::GEOSTD8,
+      "cp932" => CharacterSet::CP932,
+      "eucjpms" => CharacterSet::EUCJPMS,
+      "gb18030" => CharacterSet::GB18030,
+      "utf8mb4" => CharacterSet::UTF8MB4,
+      "utf8mb3" => CharacterSet::UTF8,
+      _ => return None,
+    };
+    Some(character_set)
+  }
+}
+
+impl TryFrom<u8> for CharacterSet {
+  type Error = io::Error;
+
+  fn try_from(id: u8) -> io::Result<Self> {
+    let character_set = match id {
       0x01_u8 => CharacterSet::BIG5,
       0x03_u8 => CharacterSet::DEC8,
       0x04_u8 => CharacterSet::CP850,
@@ -228,15 +300,19 @@ impl From<u8> for CharacterSet {
       0x5F_u8 => CharacterSet::CP932,
       0x61_u8 => CharacterSet::EUCJPMS,
       0xF8_u8 => CharacterSet::GB18030,
-      0xFF_u8 => CharacterSet::UTF8MB4,
-      invalid => panic!("invalid character set {}", invalid),
-    }
+      // utf8mb4_general_ci, utf8mb4_bin, the utf8mb4_unicode_ci family and utf8mb4_0900_ai_ci.
+      0x2D_u8 | 0x2E_u8 | 0xE0_u8..=0xF7_u8 | 0xFF_u8 => CharacterSet::UTF8MB4,
+      invalid => return Err(unexpected_err(format!("invalid character set {}", invalid))),
+    };
+    Ok(character_set)
   }
 }
 
-impl From<u8> for Collation {
-  fn from(id: u8) -> Self {
-    match id {
+impl TryFrom<u8> for Collation {
+  type Error = io::Error;
+
+  fn try_from(id: u8) -> io::Result<Self> {
+    let collation = match id {
       0x01_u8 => Collation::BIG5_CHINESE_CI,
       0x03_u8 => Collation::DEC8_SWEDISH_CI,
       0x04_u8 => Collation::CP850_GENERAL_CI,
@@ -277,9 +353,11 @@ impl From<u8> for Collation {
       0x5F_u8 => Collation::CP932_JAPANESE_CI,
       0x61_u8 => Collation::EUCJPMS_JAPANESE_CI,
       0xF8_u8 => Collation::GB18030_CHINESE_CI,
+      0x2D_u8 => Collation::UTF8MB4_GENERAL_CI,
       0xFF_u8 => Collation::UTF8MB4_0900_AI_CI,
-      invalid => panic!("invalid collation {}", invalid),
-    }
+      invalid => return Err(unexpected_err(format!("invalid collation {}", invalid))),
+    };
+    Ok(collation)
   }
 }
 
@@ -374,7 +452,7 @@ impl From<Collation> for CharacterSet {
       Collation::CP932_JAPANESE_CI => CharacterSet::CP932,
       Collation::EUCJPMS_JAPANESE_CI => CharacterSet::EUCJPMS,
       Collation::GB18030_CHINESE_CI => CharacterSet::GB18030,
-      Collation::UTF8MB4_0900_AI_CI => CharacterSet::UTF8MB4,
+      Collation::UTF8MB4_GENERAL_CI | Collation::UTF8MB4_0900_AI_CI => CharacterSet::UTF8MB4,
     }
   }
 }
@@ -419,7 +497,7 @@ pub enum Command {
 
 /// Type of MySql column field
 #[allow(non_camel_case_types)]
-#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
 #[repr(u8)]
 pub enum ColumnType {
   MYSQL_TYPE_DECIMAL = 0,
@@ -455,9 +533,11 @@ pub enum ColumnType {
   MYSQL_TYPE_GEOMETRY = 255,
 }
 
-impl From<u8> for ColumnType {
-  fn from(x: u8) -> ColumnType {
-    match x {
+impl TryFrom<u8> for ColumnType {
+  type Error = io::Error;
+
+  fn try_from(x: u8) -> io::Result<Self> {
+    let column_type = match x {
       0x00_u8 => ColumnType::MYSQL_TYPE_DECIMAL,
       0x01_u8 => ColumnType::MYSQL_TYPE_TINY,
       0x02_u8 => ColumnType::MYSQL_TYPE_SHORT,
@@ -488,8 +568,9 @@ impl From<u8> for ColumnType {
       0xfd_u8 => ColumnType::MYSQL_TYPE_VAR_STRING,
       0xfe_u8 => ColumnType::MYSQL_TYPE_STRING,
       0xff_u8 => ColumnType::MYSQL_TYPE_GEOMETRY,
-      _ => panic!("Unknown column type {}", x),
-    }
+      _ => return Err(unexpected_err(format!("unknown column type {}", x))),
+    };
+    Ok(column_type)
   }
 }
 
@@ -497,6 +578,7 @@ impl From<u8> for ColumnType {
 pub struct Handshake {
   capabilities: CapabilityFlags,
   protocol_version: u8,
+  server_version: String,
   scramble_1: Vec<u8>,
   scramble_2: Option<Vec<u8>>,
   auth_plugin_name: Option<String>,
@@ -507,39 +589,37 @@ pub struct Handshake {
 impl Handshake {
   fn parse(buffer: impl Into<Bytes>) -> io::Result<Self> {
     let mut b = buffer.into();
-    let protocol_version = b.get_u8();
-    let server_version = b.split_to(null_terminated_pos(b.bytes()));
-    b.advance(1);
-    let connection_id = b.get_u32_le();
-    let scramble_1 = b.split_to(8).to_vec();
-    b.advance(1);
-    let capabilities_1 = b.get_u16_le();
-    let character_set = b.get_u8().into();
-    let status_flags = StatusFlags::from_bits_truncate(b.get_u16_le());
-    let capabilities_2 = b.get_u16_le();
-    let scramble_len = b.get_u8();
-    b.advance(10);
+    let protocol_version = b.safe_get_u8()?;
+    let server_version = b.safe_null_terminated_string()?;
+    b.safe_advance(1)?;
+    let connection_id = b.safe_get_u32_le()?;
+    let scramble_1 = b.safe_get_bytes(8)?;
+    b.safe_advance(1)?;
+    let capabilities_1 = b.safe_get_u16_le()?;
+    let character_set = CharacterSet::try_from(b.safe_get_u8()?)?;
+    let status_flags = StatusFlags::from_bits_truncate(b.safe_get_u16_le()?);
+    let capabilities_2 = b.safe_get_u16_le()?;
+    let scramble_len = b.safe_get_u8()?;
+    b.safe_advance(10)?;
 
     let capabilities =
       CapabilityFlags::from_bits_truncate(capabilities_1 as u32 | ((capabilities_2 as u32) << 16));
 
     let mut scramble_2 = None;
     if capabilities.contains(CapabilityFlags::CLIENT_SECURE_CONNECTION) {
-      scramble_2 = Some(
-        b.split_to(max(12, scramble_len as i8 - 9) as usize)
-          .to_vec(),
-      );
-      b.advance(1);
+      scramble_2 = Some(b.safe_get_bytes((scramble_len as usize).saturating_sub(9).max(12))?);
+      b.safe_advance(1)?;
     }
 
     let mut auth_plugin_name = None;
     if capabilities.contains(CapabilityFlags::CLIENT_PLUGIN_AUTH) {
-      auth_plugin_name = Some(b.get_null_terminated_string());
+      auth_plugin_name = Some(b.safe_null_terminated_string()?);
     }
 
     Ok(Self {
       capabilities,
       protocol_version,
+      server_version,
       scramble_1,
       scramble_2,
       auth_plugin_name,
@@ -560,6 +640,11 @@ impl Handshake {
     self.protocol_version
   }
 
+  /// Version reported by the server, e.g. `8.0.22`. Proxies usually report one of their own.
+  pub fn server_version_str(&self) -> &str {
+    self.server_version.as_str()
+  }
+
   pub fn capabilities(&self) -> CapabilityFlags {
     self.capabilities
   }
@@ -575,11 +660,51 @@ impl Handshake {
   }
 
   pub fn auth_plugin_name(&self) -> &str {
+    // Servers without CLIENT_PLUGIN_AUTH, or proxies filtering it out, only do native passwords.
     self
       .auth_plugin_name
-      .as_ref()
-      .map(String::as_str)
-      .unwrap_or("") // TODO: potentially have a saner default here...
+      .as_deref()
+      .unwrap_or(MYSQL_NATIVE_PASSWORD_PLUGIN_NAME)
+  }
+}
+
+// https://dev.mysql.com/doc/internals/en/connection-phase-packets.html#packet-Protocol::AuthSwitchRequest
+#[derive(Debug)]
+pub struct AuthSwitchRequest {
+  auth_plugin_name: String,
+  nonce: Vec<u8>,
+}
+
+impl AuthSwitchRequest {
+  fn parse(buffer: impl Into<Bytes>) -> io::Result<Self> {
+    let mut b = buffer.into();
+    let _header = b.safe_get_u8()?;
+    // A bare 0xFE asks for mysql_old_password, removed from the servers since 5.7.
+    if !b.has_remaining() {
+      return Err(unexpected_err(
+        "mysql_old_password auth switch is not supported",
+      ));
+    }
+    let auth_plugin_name = b.safe_null_terminated_string()?;
+    b.safe_advance(1)?;
+    let mut nonce = b.to_vec();
+    // The nonce is sent as a null terminated string.
+    if nonce.last() == Some(&0x00) {
+      nonce.pop();
+    }
+
+    Ok(Self {
+      auth_plugin_name,
+      nonce,
+    })
+  }
+
+  pub fn auth_plugin_name(&self) -> &str {
+    self.auth_plugin_name.as_str()
+  }
+
+  pub fn nonce(&self) -> &[u8] {
+    self.nonce.as_slice()
   }
 }
 
@@ -601,11 +726,9 @@ impl Packet {
   }
 
   pub fn parse(b: &mut impl Buf) -> io::Result<Self> {
-    let payload_len = b.get_uint_le(3) as usize;
-    let sequence_id = b.get_u8();
-
-    let mut payload = vec![0; payload_len];
-    b.copy_to_slice(payload.as_mut_slice());
+    let payload_len = b.safe_get_uint_le(3)? as usize;
+    let sequence_id = b.safe_get_u8()?;
+    let payload = b.safe_get_bytes(payload_len)?;
 
     Ok(Self {
       sequence_id,
@@ -617,6 +740,7 @@ impl Packet {
     self.sequence_id
   }
 
+  #[allow(clippy::wrong_self_convention)]
   pub fn as_payload(self) -> Payload {
     Payload(self.payload)
   }
@@ -629,13 +753,28 @@ pub enum GenericResponse {
 
 pub struct Payload(Vec<u8>);
 
+#[allow(clippy::wrong_self_convention)]
 impl Payload {
   pub fn as_bytes(&self) -> &[u8] {
     self.0.as_slice()
   }
 
+  // Used by the fault injection of tail_mysql's `testing` feature.
+  #[doc(hidden)]
+  pub fn truncate(&mut self, len: usize) {
+    self.0.truncate(len);
+  }
+
+  fn header(&self) -> io::Result<u8> {
+    self
+      .0
+      .first()
+      .copied()
+      .ok_or_else(|| unexpected_eof("expected a packet header, got an empty payload"))
+  }
+
   pub fn as_generic_response(self, capabilities: CapabilityFlags) -> io::Result<GenericResponse> {
-    match self.0[0] {
+    match self.header()? {
       0x00 => Ok(GenericResponse::ServerOk(ServerOk::parse(
         self.0,
         capabilities,
@@ -644,21 +783,29 @@ impl Payload {
         self.0,
         capabilities,
       )?)),
-      _ => todo!(),
+      header => Err(unexpected_header(header)),
     }
   }
 
   pub fn as_server_ok(self, capabilities: CapabilityFlags) -> io::Result<ServerOk> {
-    match self.0[0] {
+    match self.header()? {
       0x00 => ServerOk::parse(self.0, capabilities),
-      _ => todo!(),
+      header => Err(unexpected_header(header)),
     }
   }
 
   pub fn as_server_err(self, capabilities: CapabilityFlags) -> io::Result<ServerError> {
-    match self.0[0] {
+    match self.header()? {
       0xFF => ServerError::parse(self.0, capabilities),
-      _ => todo!(),
+      header => Err(unexpected_header(header)),
+    }
+  }
+
+  /// EOF packet ending the column definitions of a result set, without `CLIENT_DEPRECATE_EOF`.
+  pub fn as_eof(self) -> io::Result<ServerOk> {
+    match self.header()? {
+      0xFE => ServerOk::parse_eof(self.0),
+      header => Err(unexpected_header(header)),
     }
   }
 
@@ -666,7 +813,7 @@ impl Payload {
     self,
     capabilities: CapabilityFlags,
   ) -> io::Result<HandshakeResponse> {
-    match self.0[0] {
+    match self.header()? {
       0xFF => Ok(HandshakeResponse::Failure(ServerError::parse(
         self.0,
         capabilities,
@@ -676,7 +823,7 @@ impl Payload {
   }
 
   pub fn as_auth_response(self, capabilities: CapabilityFlags) -> io::Result<AuthResponse> {
-    match self.0[0] {
+    match self.header()? {
       0xFF => Ok(AuthResponse::Failure(ServerError::parse(
         self.0,
         capabilities,
@@ -685,12 +832,16 @@ impl Payload {
         self.0,
         capabilities,
       )?)),
-      _ => todo!(),
+      0x01 => Ok(AuthResponse::AuthMoreData(
+        self.0.into_iter().skip(1).collect(),
+      )),
+      0xFE => Ok(AuthResponse::AuthSwitch(AuthSwitchRequest::parse(self.0)?)),
+      header => Err(unexpected_header(header)),
     }
   }
 
   pub fn as_query_response(self, capabilities: CapabilityFlags) -> io::Result<QueryResponse> {
-    match self.0[0] {
+    match self.header()? {
       0x00 => Ok(QueryResponse::Success(ServerOk::parse(
         self.0,
         capabilities,
@@ -701,7 +852,7 @@ impl Payload {
       )?)),
       0xFB => Ok(QueryResponse::LocalInfile(LocalInfile {})),
       _ => {
-        let column_count = self.0.as_slice().get_lenc_uint();
+        let column_count = self.0.as_slice().safe_get_lenc_uint()?;
         Ok(QueryResponse::ResultSet(column_count))
       }
     }
@@ -711,7 +862,7 @@ impl Payload {
     self,
     capabilities: CapabilityFlags,
   ) -> io::Result<ColumnDefinitionResponse> {
-    match self.0[0] {
+    match self.header()? {
       0x00 => Ok(ColumnDefinitionResponse::Success(ServerOk::parse(
         self.0,
         capabilities,
@@ -725,16 +876,20 @@ impl Payload {
   pub fn as_row_response(
     self,
     capabilities: CapabilityFlags,
-    columns: &Vec<Column>,
+    columns: &[Column],
   ) -> io::Result<RowResponse> {
-    match self.0[0] {
-      // TODO: I think i would have to check for lenght here according to https://dev.mysql.com/doc/internals/en/packet-EOF_Packet.html.
+    match self.header()? {
+      // Without CLIENT_DEPRECATE_EOF, the rows end with an EOF packet rather than an OK packet.
+      // https://dev.mysql.com/doc/internals/en/packet-EOF_Packet.html
+      0xFE if !capabilities.contains(CapabilityFlags::CLIENT_DEPRECATE_EOF) => {
+        Ok(RowResponse::Success(ServerOk::parse_eof(self.0)?))
+      }
       0x00 | 0xFE => Ok(RowResponse::Success(ServerOk::parse(self.0, capabilities)?)),
       _ => {
         let mut values = Vec::with_capacity(columns.len());
         let mut b = self.0.as_slice();
-        for i in 0..columns.len() {
-          let value = Value::parse_from_text(&mut b, &columns[i])?;
+        for column in columns {
+          let value = Value::parse_from_text(&mut b, column)?;
           values.push(value);
         }
 
@@ -744,6 +899,10 @@ impl Payload {
   }
 }
 
+fn unexpected_header(header: u8) -> io::Error {
+  unexpected_err(format!("unexpected packet header 0x{:02X}", header))
+}
+
 #[derive(Debug)]
 pub struct Row(Vec<Value>);
 
@@ -751,6 +910,27 @@ impl Row {
   pub fn values(&self) -> &[Value] {
     self.0.as_slice()
   }
+
+  /// Value of the column at `index`, `None` when out of bounds.
+  pub fn get(&self, index: usize) -> Option<&Value> {
+    self.0.get(index)
+  }
+
+  pub fn len(&self) -> usize {
+    self.0.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.0.is_empty()
+  }
+}
+
+impl core::ops::Index<usize> for Row {
+  type Output = Value;
+
+  fn index(&self, index: usize) -> &Value {
+    &self.0[index]
+  }
 }
 
 #[derive(Debug)]
@@ -763,8 +943,12 @@ pub enum RowResponse {
 pub enum AuthResponse {
   Success(ServerOk),
   Failure(ServerError),
-  AuthSwitch,
-  AuthMoreData,
+  /// The server asks to authenticate again with another plugin, e.g. the plugin of the account
+  /// differs from the default plugin announced in the handshake.
+  AuthSwitch(AuthSwitchRequest),
+  /// Data of the auth plugin, e.g. the result of the fast authentication of
+  /// caching_sha2_password, or the public key of the server.
+  AuthMoreData(Vec<u8>),
 }
 
 #[derive(Debug)]
@@ -794,7 +978,8 @@ pub struct Column {
   table: String,
   name: String,
   org_table: String,
-  character_set: CharacterSet,
+  org_name: String,
+  collation: u16,
   column_length: u32,
   column_type: ColumnType,
   flags: ColumnFlags,
@@ -804,20 +989,30 @@ pub struct Column {
 impl Column {
   fn parse(buffer: impl Into<Bytes>) -> io::Result<Self> {
     let mut b = buffer.into();
-    let catalog = b.get_lenc_string();
-    assert_eq!("def", catalog.as_str());
-    let schema = b.get_lenc_string();
-    let table = b.get_lenc_string();
-    let org_table = b.get_lenc_string();
-    let name = b.get_lenc_string();
-    let org_name = b.get_lenc_string();
-    let fixed_len = b.get_lenc_uint();
-    assert_eq!(0x0C, fixed_len);
-    let character_set = (b.get_u16_le() as u8).into();
-    let column_length = b.get_u32_le();
-    let column_type = b.get_u8().into();
-    let flags = ColumnFlags::from_bits_truncate(b.get_u16_le());
-    let decimals = b.get_u8();
+    let catalog = b.safe_get_lenc_string()?;
+    if catalog != "def" {
+      return Err(unexpected_err(format!(
+        "expected catalog def, got {}",
+        catalog
+      )));
+    }
+    let schema = b.safe_get_lenc_string()?;
+    let table = b.safe_get_lenc_string()?;
+    let org_table = b.safe_get_lenc_string()?;
+    let name = b.safe_get_lenc_string()?;
+    let org_name = b.safe_get_lenc_string()?;
+    let fixed_len = b.safe_get_lenc_uint()?;
+    if fixed_len != 0x0C {
+      return Err(unexpected_err(format!(
+        "expected 12 bytes of fixed length fields, got {}",
+        fixed_len
+      )));
+    }
+    let collation = b.safe_get_u16_le()?;
+    let column_length = b.safe_get_u32_le()?;
+    let column_type = ColumnType::try_from(b.safe_get_u8()?)?;
+    let flags = ColumnFlags::from_bits_truncate(b.safe_get_u16_le()?);
+    let decimals = b.safe_get_u8()?;
 
     Ok(Self {
       catalog,
@@ -825,7 +1020,8 @@ impl Column {
       table,
       name,
       org_table,
-      character_set,
+      org_name,
+      collation,
       column_length,
       column_type,
       flags,
@@ -833,6 +1029,54 @@ impl Column {
     })
   }
 
+  /// Name of the column in the result set, i.e. its alias when it has one.
+  pub fn name_str(&self) -> &str {
+    self.name.as_str()
+  }
+
+  /// Name of the column in its table, empty for expressions.
+  pub fn org_name_str(&self) -> &str {
+    self.org_name.as_str()
+  }
+
+  /// Schema of the table of the column, empty for expressions.
+  pub fn schema_str(&self) -> &str {
+    self.schema.as_str()
+  }
+
+  /// Table of the column in the query, i.e. its alias when it has one.
+  pub fn table_str(&self) -> &str {
+    self.table.as_str()
+  }
+
+  /// Name of the table of the column.
+  pub fn org_table_str(&self) -> &str {
+    self.org_table.as_str()
+  }
+
+  /// Maximum length of the values of the column, in bytes.
+  pub fn column_length(&self) -> u32 {
+    self.column_length
+  }
+
+  /// Number of digits after the decimal point, of DECIMAL, floating point and temporal columns.
+  pub fn decimals(&self) -> u8 {
+    self.decimals
+  }
+
+  /// Id of the collation of the values, see `character_set`.
+  pub fn collation_id(&self) -> u16 {
+    self.collation
+  }
+
+  /// Character set of the values, `None` for the collations this client does not know about.
+  /// Binary values have the `BINARY` character set.
+  pub fn character_set(&self) -> Option<CharacterSet> {
+    u8::try_from(self.collation)
+      .ok()
+      .and_then(|collation| CharacterSet::try_from(collation).ok())
+  }
+
   pub fn column_type(&self) -> ColumnType {
     self.column_type
   }
@@ -857,18 +1101,18 @@ pub struct ServerError {
 impl ServerError {
   fn parse(buffer: impl Into<Bytes>, capability_flags: CapabilityFlags) -> io::Result<Self> {
     let mut b = buffer.into();
-    let _header = b.get_u8();
-    let error_code = b.get_u16_le();
+    let _header = b.safe_get_u8()?;
+    let error_code = b.safe_get_u16_le()?;
 
     let mut state_marker = None;
     let mut state = None;
 
     if capability_flags.contains(CapabilityFlags::CLIENT_PROTOCOL_41) {
-      let state_marker = Some(b.get_fixed_length_string(1));
-      let state = Some(b.get_fixed_length_string(5));
+      state_marker = Some(b.safe_get_fixed_length_string(1)?);
+      state = Some(b.safe_get_fixed_length_string(5)?);
     }
 
-    let error_message = b.get_eof_string();
+    let error_message = b.safe_get_eof_string()?;
     Ok(Self {
       error_code,
       state_marker,
@@ -876,6 +1120,19 @@ impl ServerError {
       error_message,
     })
   }
+
+  pub fn error_code(&self) -> u16 {
+    self.error_code
+  }
+
+  /// SQLSTATE of the error, only sent with `CLIENT_PROTOCOL_41`.
+  pub fn state(&self) -> Option<&str> {
+    self.state.as_deref()
+  }
+
+  pub fn error_message(&self) -> &str {
+    self.error_message.as_str()
+  }
 }
 
 // https://dev.mysql.com/doc/internals/en/packet-OK_Packet.html
@@ -886,42 +1143,47 @@ pub struct ServerOk {
   status_flags: Option<StatusFlags>,
   warnings: Option<u16>,
   info: String,
-  session_state_changes: Option<String>,
+  session_state_changes: Vec<SessionStateChange>,
 }
 
 impl ServerOk {
   fn parse(buffer: impl Into<Bytes>, capability_flags: CapabilityFlags) -> io::Result<Self> {
     let mut b = buffer.into();
-    let _header = b.get_u8();
-    let affected_rows = b.get_lenc_uint();
-    let last_inserted_id = b.get_lenc_uint();
+    let _header = b.safe_get_u8()?;
+    let affected_rows = b.safe_get_lenc_uint()?;
+    let last_inserted_id = b.safe_get_lenc_uint()?;
 
     let mut status_flags = None;
     let mut warnings = None;
     if capability_flags.contains(CapabilityFlags::CLIENT_PROTOCOL_41) {
-      status_flags = Some(StatusFlags::from_bits_truncate(b.get_u16_le()));
-      warnings = Some(b.get_u16_le());
+      status_flags = Some(StatusFlags::from_bits_truncate(b.safe_get_u16_le()?));
+      warnings = Some(b.safe_get_u16_le()?);
     } else if capability_flags.contains(CapabilityFlags::CLIENT_TRANSACTIONS) {
-      status_flags = Some(StatusFlags::from_bits_truncate(b.get_u16_le()));
+      status_flags = Some(StatusFlags::from_bits_truncate(b.safe_get_u16_le()?));
     }
 
     let (info, session_state_changes) =
       if capability_flags.contains(CapabilityFlags::CLIENT_SESSION_TRACK) {
-        let info = b.get_lenc_string();
+        // Servers omit the info when it is empty and nothing follows it.
+        let info = if b.has_remaining() {
+          b.safe_get_lenc_string()?
+        } else {
+          String::new()
+        };
 
         let has_session_state_changes = status_flags
           .map(|f| f.contains(StatusFlags::SERVER_SESSION_STATE_CHANGED))
           .unwrap_or(false);
 
-        let mut session_state_changes = None;
+        let mut session_state_changes = Vec::new();
         if has_session_state_changes {
-          session_state_changes = Some(b.get_lenc_string())
+          session_state_changes = SessionStateChange::parse_all(b.safe_get_lenc_bytes()?)?;
         }
 
         (info, session_state_changes)
       } else {
-        let info = b.get_eof_string();
-        (info, None)
+        let info = b.safe_get_eof_string()?;
+        (info, Vec::new())
       };
 
     Ok(Self {
@@ -934,6 +1196,23 @@ impl ServerOk {
     })
   }
 
+  // EOF packets carry the warnings and status flags of an OK packet, and nothing else.
+  fn parse_eof(buffer: impl Into<Bytes>) -> io::Result<Self> {
+    let mut b = buffer.into();
+    let _header = b.safe_get_u8()?;
+    let warnings = Some(b.safe_get_u16_le()?);
+    let status_flags = Some(StatusFlags::from_bits_truncate(b.safe_get_u16_le()?));
+
+    Ok(Self {
+      affected_rows: 0,
+      last_inserted_id: 0,
+      status_flags,
+      warnings,
+      info: String::new(),
+      session_state_changes: Vec::new(),
+    })
+  }
+
   pub fn affected_rows(&self) -> u64 {
     self.affected_rows
   }
@@ -946,4 +1225,68 @@ impl ServerOk {
   pub fn warnings(&self) -> Option<u16> {
     self.warnings
   }
+  /// Changes of the session reported by the session trackers, with `CLIENT_SESSION_TRACK`.
+  pub fn session_state_changes(&self) -> &[SessionStateChange] {
+    self.session_state_changes.as_slice()
+  }
+}
+
+// https://dev.mysql.com/doc/dev/mysql-server/latest/group__group__cs__session__state__type.html
+const SESSION_TRACK_SYSTEM_VARIABLES: u8 = 0x00;
+const SESSION_TRACK_SCHEMA: u8 = 0x01;
+const SESSION_TRACK_STATE_CHANGE: u8 = 0x02;
+const SESSION_TRACK_GTIDS: u8 = 0x03;
+const SESSION_TRACK_TRANSACTION_CHARACTERISTICS: u8 = 0x04;
+const SESSION_TRACK_TRANSACTION_STATE: u8 = 0x05;
+
+/// Change of the session, as tracked by the `session_track_*` system variables.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SessionStateChange {
+  /// A system variable listed in `session_track_system_variables` was set, e.g. `autocommit`.
+  SystemVariable { name: String, value: String },
+  /// The default schema changed, e.g. by `USE`.
+  Schema(String),
+  /// Some state of the session changed, with `session_track_state_change=ON`.
+  StateChange,
+  /// GTIDs of the transaction, with `session_track_gtids`.
+  Gtids(String),
+  /// Statements that restore the characteristics of the transaction, e.g. `START TRANSACTION READ
+  /// ONLY;`, with `session_track_transaction_info=CHARACTERISTICS`.
+  TransactionCharacteristics(String),
+  /// State of the transaction, e.g. `T_______`, with `session_track_transaction_info`.
+  TransactionState(String),
+  /// Trackers this client does not know about, skipped.
+  Other(u8),
+}
+
+impl SessionStateChange {
+  fn parse_all(buffer: Vec<u8>) -> io::Result<Vec<Self>> {
+    let mut b = Bytes::from(buffer);
+    let mut changes = Vec::new();
+    while b.has_remaining() {
+      let kind = b.safe_get_u8()?;
+      let mut data = Bytes::from(b.safe_get_lenc_bytes()?);
+      let change = match kind {
+        SESSION_TRACK_SYSTEM_VARIABLES => SessionStateChange::SystemVariable {
+          name: data.safe_get_lenc_string()?,
+          value: data.safe_get_lenc_string()?,
+        },
+        SESSION_TRACK_SCHEMA => SessionStateChange::Schema(data.safe_get_lenc_string()?),
+        SESSION_TRACK_STATE_CHANGE => SessionStateChange::StateChange,
+        SESSION_TRACK_GTIDS => {
+          let _encoding_specification = data.safe_get_u8()?;
+          SessionStateChange::Gtids(data.safe_get_lenc_string()?)
+        }
+        SESSION_TRACK_TRANSACTION_CHARACTERISTICS => {
+          SessionStateChange::TransactionCharacteristics(data.safe_get_lenc_string()?)
+        }
+        SESSION_TRACK_TRANSACTION_STATE => {
+          SessionStateChange::TransactionState(data.safe_get_lenc_string()?)
+        }
+        kind => SessionStateChange::Other(kind),
+      };
+      changes.push(change);
+    }
+    Ok(changes)
+  }
 }
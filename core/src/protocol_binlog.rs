@@ -0,0 +1,2263 @@
+// Binlog events: their header, then the events the decoder understands, e.g. FORMAT_DESCRIPTION,
+// TABLE_MAP and rows events, from the packets of a binlog stream or from binlog files.
+#![cfg_attr(
+  not(test),
+  deny(
+    clippy::unwrap_used,
+    clippy::expect_used,
+    clippy::panic,
+    clippy::todo,
+    clippy::unimplemented
+  )
+)]
+
+// TODO: user specified server_id OR randomly generate one that does not already exists
+
+// byte 1 = OK packet
+// byte 2-5] = all zero packets
+// byte 6 = mysql version (4) https://dev.mysql.com/doc/internals/en/binlog-version.html
+//
+
+// 00000000  00 00 00 00 00 04 01 00  00 00 32 00 00 00 00 00  |..........2.....|
+// 00000010  00 00 20 00 9e 02 00 00  00 00 00 00 70 61 79 6d  |.. .........paym|
+// 00000020  65 6e 74 2d 61 70 70 73  2d 62 69 6e 2e 30 30 30  |ent-apps-bin.000|
+// 00000030  30 30 33 00 1c 88 43 5d  0f 01 00 00 00 77 00 00  |003...C].....w..|
+// 00000040  00 00 00 00 00 00 00 04  00 35 2e 37 2e 31 38 2d  |.........5.7.18-|
+// 00000050  31 36 2d 6c 6f 67 00 00  00 00 00 00 00 00 00 00  |16-log..........|
+// 00000060  00 00 00 00 00 00 00 00  00 00 00 00 00 00 00 00  |................|
+// 00000070  00 00 00 00 00 00 00 00  00 00 00 00 00 00 00 13  |................|
+// 00000080  38 0d 00 08 00 12 00 04  04 04 04 12 00 00 5f 00  |8............._.|
+// 00000090  04 1a 08 00 00 00 08 08  08 02 00 00 00 0a 0a 0a  |................|
+
+use super::buf_ext::BufExt;
+use super::protocol::ColumnType;
+use super::util::{null_terminated_pos, server_version_triple, unexpected_err};
+use super::value::{Row, Value};
+// use crate::io::ReadMysqlExt;
+// use byteorder::{LittleEndian as LE, ReadBytesExt};
+use super::io;
+use alloc::string::String;
+use alloc::vec::Vec;
+use alloc::{format, vec};
+// use std::fs::OpenOptions;
+// use std::collections::BTreeMap;
+use alloc::borrow::Cow;
+use bytes::{Buf, Bytes};
+use core::convert::TryFrom;
+use core::fmt;
+
+use core::iter::Iterator;
+
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[repr(u8)]
+#[non_exhaustive]
+pub enum EventType {
+  UNKNOWN_EVENT,
+  START_EVENT_V3,
+  QUERY_EVENT,
+  STOP_EVENT,
+  ROTATE_EVENT,
+  INTVAR_EVENT,
+  LOAD_EVENT,
+  SLAVE_EVENT,
+  CREATE_FILE_EVENT,
+  APPEND_BLOCK_EVENT,
+  EXEC_LOAD_EVENT,
+  DELETE_FILE_EVENT,
+  NEW_LOAD_EVENT,
+  RAND_EVENT,
+  USER_VAR_EVENT,
+  FORMAT_DESCRIPTION_EVENT,
+  XID_EVENT,
+  BEGIN_LOAD_QUERY_EVENT,
+  EXECUTE_LOAD_QUERY_EVENT,
+  TABLE_MAP_EVENT,
+  WRITE_ROWS_EVENTV0,
+  UPDATE_ROWS_EVENTV0,
+  DELETE_ROWS_EVENTV0,
+  WRITE_ROWS_EVENTV1,
+  UPDATE_ROWS_EVENTV1,
+  DELETE_ROWS_EVENTV1,
+  INCIDENT_EVENT,
+  HEARTBEAT_EVENT,
+  IGNORABLE_EVENT,
+  ROWS_QUERY_EVENT,
+  WRITE_ROWS_EVENTV2,
+  UPDATE_ROWS_EVENTV2,
+  DELETE_ROWS_EVENTV2,
+  GTID_EVENT,
+  ANONYMOUS_GTID_EVENT,
+  PREVIOUS_GTIDS_EVENT,
+  TRANSACTION_CONTEXT_EVENT,
+  VIEW_CHANGE_EVENT,
+  XA_PREPARE_LOG_EVENT,
+  PARTIAL_UPDATE_ROWS_EVENT,
+  TRANSACTION_PAYLOAD_EVENT,
+  // MariaDB, with encrypt_binlog=ON.
+  START_ENCRYPTION_EVENT = 0xa4,
+}
+
+impl From<u8> for EventType {
+  fn from(x: u8) -> EventType {
+    match x {
+      0x00_u8 => EventType::UNKNOWN_EVENT,
+      0x01_u8 => EventType::START_EVENT_V3,
+      0x02_u8 => EventType::QUERY_EVENT,
+      0x03_u8 => EventType::STOP_EVENT,
+      0x04_u8 => EventType::ROTATE_EVENT,
+      0x05_u8 => EventType::INTVAR_EVENT,
+      0x06_u8 => EventType::LOAD_EVENT,
+      0x07_u8 => EventType::SLAVE_EVENT,
+      0x08_u8 => EventType::CREATE_FILE_EVENT,
+      0x09_u8 => EventType::APPEND_BLOCK_EVENT,
+      0x0a_u8 => EventType::EXEC_LOAD_EVENT,
+      0x0b_u8 => EventType::DELETE_FILE_EVENT,
+      0x0c_u8 => EventType::NEW_LOAD_EVENT,
+      0x0d_u8 => EventType::RAND_EVENT,
+      0x0e_u8 => EventType::USER_VAR_EVENT,
+      0x0f_u8 => EventType::FORMAT_DESCRIPTION_EVENT,
+      0x10_u8 => EventType::XID_EVENT,
+      0x11_u8 => EventType::BEGIN_LOAD_QUERY_EVENT,
+      0x12_u8 => EventType::EXECUTE_LOAD_QUERY_EVENT,
+      0x13_u8 => EventType::TABLE_MAP_EVENT,
+      0x14_u8 => EventType::WRITE_ROWS_EVENTV0,
+      0x15_u8 => EventType::UPDATE_ROWS_EVENTV0,
+      0x16_u8 => EventType::DELETE_ROWS_EVENTV0,
+      0x17_u8 => EventType::WRITE_ROWS_EVENTV1,
+      0x18_u8 => EventType::UPDATE_ROWS_EVENTV1,
+      0x19_u8 => EventType::DELETE_ROWS_EVENTV1,
+      0x1a_u8 => EventType::INCIDENT_EVENT,
+      0x1b_u8 => EventType::HEARTBEAT_EVENT,
+      0x1c_u8 => EventType::IGNORABLE_EVENT,
+      0x1d_u8 => EventType::ROWS_QUERY_EVENT,
+      0x1e_u8 => EventType::WRITE_ROWS_EVENTV2,
+      0x1f_u8 => EventType::UPDATE_ROWS_EVENTV2,
+      0x20_u8 => EventType::DELETE_ROWS_EVENTV2,
+      0x21_u8 => EventType::GTID_EVENT,
+      0x22_u8 => EventType::ANONYMOUS_GTID_EVENT,
+      0x23_u8 => EventType::PREVIOUS_GTIDS_EVENT,
+      0x24_u8 => EventType::TRANSACTION_CONTEXT_EVENT,
+      0x25_u8 => EventType::VIEW_CHANGE_EVENT,
+      0x26_u8 => EventType::XA_PREPARE_LOG_EVENT,
+      0x27_u8 => EventType::PARTIAL_UPDATE_ROWS_EVENT,
+      0x28_u8 => EventType::TRANSACTION_PAYLOAD_EVENT,
+      0xa4_u8 => EventType::START_ENCRYPTION_EVENT,
+      _ => EventType::UNKNOWN_EVENT,
+    }
+  }
+}
+
+// pub fn parse_event2(mut payload: &[u8]) -> io::Result<()> {
+//     // skip OK byte
+//     payload = &payload[1..];
+
+//     // parse rotate event
+//     let rotate_event = &payload[..45];
+//     println!("{:02x?}", rotate_event);
+//     payload = &payload[45..];
+//     EventPacket::parse(rotate_event);
+
+//     // skip OK byte
+//     payload = &payload[1..];
+
+//     let format_event = &payload[..119];
+//     println!("{:02x?}", format_event);
+//     payload = &payload[119..];
+//     EventPacket::parse(format_event);
+
+//     // skip OK byte
+//     payload = &payload[1..];
+
+//     let anonymous_gtid_event = &payload[..61];
+//     println!("{:02x?}", anonymous_gtid_event);
+//     payload = &payload[61..];
+//     EventPacket::parse(anonymous_gtid_event);
+
+//     // skip OK byte
+//     payload = &payload[1..];
+
+//     let query_event = &payload[..68];
+//     println!("{:02x?}", query_event);
+//     payload = &payload[68..];
+//     EventPacket::parse(query_event);
+
+//     // skip OK byte
+//     payload = &payload[1..];
+
+//     let table_map_event = &payload[..50];
+//     println!("{:02x?}", table_map_event);
+//     payload = &payload[50..];
+//     EventPacket::parse(table_map_event);
+
+//     // skip OK byte
+//     payload = &payload[1..];
+
+//     let row_event = &payload[..55];
+//     println!("{:02x?}", row_event);
+//     payload = &payload[55..];
+//     EventPacket::parse(row_event);
+
+//     // skip OK byte
+//     payload = &payload[1..];
+
+//     let xid_event = &payload[..27];
+//     println!("{:02x?}", xid_event);
+//     payload = &payload[27..];
+//     EventPacket::parse(xid_event);
+
+//     Ok(())
+// }
+
+#[derive(Debug)]
+pub struct BinlogEventPacket {
+  timestamp: u32,
+  server_id: u32,
+  event_size: u32,
+  log_pos: u32,
+  flags: u16,
+  event_type: EventType,
+  payload: Vec<u8>,
+}
+
+// https://dev.mysql.com/doc/internals/en/binlog-event-flag.html
+const LOG_EVENT_ARTIFICIAL_F: u16 = 0x0020;
+const LOG_EVENT_RELAY_LOG_F: u16 = 0x0040;
+
+// https://dev.mysql.com/doc/internals/en/binlog-event-header.html
+#[derive(Debug, Clone, Copy)]
+pub struct EventHeader {
+  timestamp: u32,
+  server_id: u32,
+  event_size: u32,
+  log_pos: u32,
+  flags: u16,
+}
+
+impl EventHeader {
+  pub fn timestamp(&self) -> u32 {
+    self.timestamp
+  }
+
+  pub fn server_id(&self) -> u32 {
+    self.server_id
+  }
+
+  /// Size of the event in bytes, header included.
+  pub fn event_size(&self) -> u32 {
+    self.event_size
+  }
+
+  /// Position of the next event in the binlog file.
+  pub fn log_pos(&self) -> u32 {
+    self.log_pos
+  }
+
+  pub fn flags(&self) -> u16 {
+    self.flags
+  }
+
+  /// Returns true for events that were not logged by the source, e.g. the ROTATE_EVENT sent at
+  /// the beginning of a binlog dump.
+  pub fn is_artificial(&self) -> bool {
+    self.flags & LOG_EVENT_ARTIFICIAL_F != 0
+  }
+
+  /// Returns true for the events written by a replica to its own relay log, rather than
+  /// replicated from the source.
+  pub fn is_relay_log(&self) -> bool {
+    self.flags & LOG_EVENT_RELAY_LOG_F != 0
+  }
+}
+
+impl BinlogEventPacket {
+  pub fn parse(buffer: impl Into<Bytes>) -> io::Result<BinlogEventPacket> {
+    let mut b = buffer.into();
+    // assume version > 1 = 19 bytes header.
+    // if payload.len() < 19 {
+    //     return Err(io::Error::new(
+    //         io::ErrorKind::InvalidData,
+    //         format!("expected len(event header) >= 19, got={}", payload.len()),
+    //     ));
+    // }
+
+    // skip OK byte
+    b.safe_advance(1)?;
+    Self::parse_event(b)
+  }
+
+  /// Parses an event as stored in binlog files, without the OK byte of the network protocol.
+  pub fn parse_event(buffer: impl Into<Bytes>) -> io::Result<BinlogEventPacket> {
+    let mut b = buffer.into();
+    let timestamp = b.safe_get_u32_le()?;
+    let event_type = b.safe_get_u8()?.into();
+    let server_id = b.safe_get_u32_le()?;
+    let event_size = b.safe_get_u32_le()?;
+    let log_pos = b.safe_get_u32_le()?;
+    let flags = b.safe_get_u16_le()?;
+    let payload = b.to_vec();
+
+    Ok(BinlogEventPacket {
+      timestamp,
+      server_id,
+      event_size,
+      log_pos,
+      flags,
+      event_type,
+      payload,
+    })
+  }
+
+  // Removes the checksum trailing the event, see `FormatDescriptionEvent::has_checksums`.
+  fn strip_checksum(&mut self) -> io::Result<()> {
+    let len = self
+      .payload
+      .len()
+      .checked_sub(BINLOG_CHECKSUM_LEN)
+      .ok_or_else(|| unexpected_err("expected a checksum at the end of the event"))?;
+    self.payload.truncate(len);
+    Ok(())
+  }
+
+  pub fn header(&self) -> EventHeader {
+    EventHeader {
+      timestamp: self.timestamp,
+      server_id: self.server_id,
+      event_size: self.event_size,
+      log_pos: self.log_pos,
+      flags: self.flags,
+    }
+  }
+
+  pub fn event_type(&self) -> EventType {
+    self.event_type
+  }
+
+  /// Body of the event, after the header.
+  pub fn payload(&self) -> &[u8] {
+    self.payload.as_slice()
+  }
+
+  pub fn into_binlog_event(self) -> io::Result<BinlogEvent> {
+    match self.event_type {
+      EventType::TABLE_MAP_EVENT => Ok(BinlogEvent::TableMap(TableMapEvent::parse(self.payload)?)),
+      EventType::ROTATE_EVENT => Ok(BinlogEvent::Rotate(RotateEvent::parse(self.payload)?)),
+      EventType::FORMAT_DESCRIPTION_EVENT => Ok(BinlogEvent::Format(
+        FormatDescriptionEvent::parse(self.payload)?,
+      )),
+      EventType::WRITE_ROWS_EVENTV0 => Ok(BinlogEvent::Insert(RowEvent::parse(
+        self.payload,
+        false,
+        false,
+      )?)),
+      EventType::WRITE_ROWS_EVENTV1 => Ok(BinlogEvent::Insert(RowEvent::parse(
+        self.payload,
+        false,
+        false,
+      )?)),
+      EventType::WRITE_ROWS_EVENTV2 => Ok(BinlogEvent::Insert(RowEvent::parse(
+        self.payload,
+        true,
+        false,
+      )?)),
+      EventType::UPDATE_ROWS_EVENTV0 => Ok(BinlogEvent::Update(RowEvent::parse(
+        self.payload,
+        false,
+        false,
+      )?)),
+      EventType::UPDATE_ROWS_EVENTV1 => Ok(BinlogEvent::Update(RowEvent::parse(
+        self.payload,
+        false,
+        true,
+      )?)),
+      EventType::UPDATE_ROWS_EVENTV2 => Ok(BinlogEvent::Update(RowEvent::parse(
+        self.payload,
+        true,
+        true,
+      )?)),
+      EventType::DELETE_ROWS_EVENTV0 => Ok(BinlogEvent::Delete(RowEvent::parse(
+        self.payload,
+        false,
+        false,
+      )?)),
+      EventType::DELETE_ROWS_EVENTV1 => Ok(BinlogEvent::Delete(RowEvent::parse(
+        self.payload,
+        false,
+        false,
+      )?)),
+      EventType::DELETE_ROWS_EVENTV2 => Ok(BinlogEvent::Delete(RowEvent::parse(
+        self.payload,
+        true,
+        false,
+      )?)),
+      EventType::QUERY_EVENT => Ok(BinlogEvent::Query(QueryEvent::parse(self.payload)?)),
+      EventType::XID_EVENT => Ok(BinlogEvent::Xid(XidEvent::parse(self.payload)?)),
+      EventType::GTID_EVENT => Ok(BinlogEvent::Gtid(GtidEvent::parse(self.payload, false)?)),
+      EventType::ANONYMOUS_GTID_EVENT => {
+        Ok(BinlogEvent::Gtid(GtidEvent::parse(self.payload, true)?))
+      }
+      EventType::PREVIOUS_GTIDS_EVENT => Ok(BinlogEvent::PreviousGtids(PreviousGtidsEvent::parse(
+        self.payload,
+      )?)),
+      EventType::TRANSACTION_PAYLOAD_EVENT => Ok(BinlogEvent::TransactionPayload(
+        TransactionPayloadEvent::parse(self.payload)?,
+      )),
+      // Has no fields.
+      EventType::STOP_EVENT => Ok(BinlogEvent::Stop),
+      unhandled_event_type => Err(unexpected_err(format!(
+        "{:?} is not supported",
+        unhandled_event_type
+      ))),
+    }
+  }
+}
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum BinlogEvent {
+  Query(QueryEvent),
+  Xid(XidEvent),
+  Gtid(GtidEvent),
+  PreviousGtids(PreviousGtidsEvent),
+  TableMap(TableMapEvent),
+  Rotate(RotateEvent),
+  Format(FormatDescriptionEvent),
+  Insert(RowEvent),
+  Update(RowEvent),
+  Delete(RowEvent),
+  /// The events of a transaction, compressed with `binlog_transaction_compression=ON`. See
+  /// `TransactionPayloadEvent::events`.
+  TransactionPayload(TransactionPayloadEvent),
+  /// Last event of a binlog file closed by a shutdown of the server. The server starts a new file
+  /// when it starts again.
+  Stop,
+}
+
+/// Formats as a one-liner naming the event and its main fields, e.g. `ROTATE mysql-bin.000002:4`
+/// or `TABLE_MAP 42 pets.cats (3 columns)`. Rows events are formatted without their rows, which
+/// can only be decoded with their TABLE_MAP_EVENT.
+impl fmt::Display for BinlogEvent {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      BinlogEvent::Query(query) => {
+        f.write_str("QUERY ")?;
+        if !query.schema_str().is_empty() {
+          write!(f, "{}: ", query.schema_str())?;
+        }
+        // Statements span several lines more often than not.
+        for (i, word) in query.query_str().split_whitespace().enumerate() {
+          if i > 0 {
+            f.write_str(" ")?;
+          }
+          f.write_str(word)?;
+        }
+        Ok(())
+      }
+      BinlogEvent::Xid(xid) => write!(f, "XID {}", xid.xid()),
+      BinlogEvent::Gtid(gtid) => match gtid.gtid() {
+        Some(gtid) => write!(f, "GTID {}", gtid),
+        None => f.write_str("GTID anonymous"),
+      },
+      BinlogEvent::PreviousGtids(previous_gtids) => {
+        write!(f, "PREVIOUS_GTIDS {}", previous_gtids.gtid_set())
+      }
+      BinlogEvent::TableMap(table_map) => write!(
+        f,
+        "TABLE_MAP {} {}.{} ({} columns)",
+        table_map.table_id(),
+        table_map.schema_str(),
+        table_map.table_str(),
+        table_map.column_count()
+      ),
+      BinlogEvent::Rotate(rotate) => write!(
+        f,
+        "ROTATE {}:{}",
+        rotate.next_log_name_str(),
+        rotate.position()
+      ),
+      BinlogEvent::Format(format) => write!(
+        f,
+        "FORMAT_DESCRIPTION v{} {}",
+        format.version(),
+        format.server_version_str()
+      ),
+      BinlogEvent::Insert(rows) => write!(
+        f,
+        "WRITE_ROWS {}, {} bytes",
+        rows.table_id(),
+        rows.rows().len()
+      ),
+      BinlogEvent::Update(rows) => write!(
+        f,
+        "UPDATE_ROWS {}, {} bytes",
+        rows.table_id(),
+        rows.rows().len()
+      ),
+      BinlogEvent::Delete(rows) => write!(
+        f,
+        "DELETE_ROWS {}, {} bytes",
+        rows.table_id(),
+        rows.rows().len()
+      ),
+      BinlogEvent::TransactionPayload(payload) => write!(
+        f,
+        "TRANSACTION_PAYLOAD {:?}, {} bytes ({} uncompressed)",
+        payload.compression(),
+        payload.payload().len(),
+        payload.uncompressed_size()
+      ),
+      BinlogEvent::Stop => f.write_str("STOP"),
+    }
+  }
+}
+
+// https://dev.mysql.com/doc/internals/en/query-event.html
+#[derive(Debug)]
+pub struct QueryEvent {
+  thread_id: u32,
+  exec_time: u32,
+  error_code: u16,
+  status_vars: Vec<u8>,
+  time_zone: Option<String>,
+  schema: String,
+  query: String,
+}
+
+impl QueryEvent {
+  fn parse(buffer: impl Into<Bytes>) -> io::Result<Self> {
+    let mut b = buffer.into();
+    let thread_id = b.safe_get_u32_le()?;
+    let exec_time = b.safe_get_u32_le()?;
+    let schema_len = b.safe_get_u8()? as usize;
+    let error_code = b.safe_get_u16_le()?;
+    let status_vars_len = b.safe_get_u16_le()? as usize;
+    let status_vars = b.safe_get_bytes(status_vars_len)?;
+    let time_zone = status_var_time_zone(&status_vars);
+    let schema = b.safe_get_fixed_length_string(schema_len)?;
+
+    // skip 0x00
+    b.safe_advance(1)?;
+
+    let query = String::from_utf8(b.to_vec()).map_err(unexpected_err)?;
+
+    Ok(Self {
+      thread_id,
+      exec_time,
+      error_code,
+      status_vars,
+      time_zone,
+      schema,
+      query,
+    })
+  }
+
+  pub fn thread_id(&self) -> u32 {
+    self.thread_id
+  }
+
+  pub fn exec_time(&self) -> u32 {
+    self.exec_time
+  }
+
+  pub fn error_code(&self) -> u16 {
+    self.error_code
+  }
+
+  pub fn schema_str(&self) -> &str {
+    self.schema.as_str()
+  }
+
+  pub fn query_str(&self) -> &str {
+    self.query.as_str()
+  }
+
+  /// Session time zone of the statement (e.g. `+02:00` or `Europe/Paris`), which MYSQL converts
+  /// TIMESTAMP literals and functions like NOW() from. `None` when it is the server's time zone.
+  pub fn time_zone_str(&self) -> Option<&str> {
+    self.time_zone.as_deref()
+  }
+}
+
+// https://github.com/mysql/mysql-server/blob/8.0/libbinlogevents/include/statement_events.h
+//
+// Status variables are (code, value) pairs, where the size of the value depends on the code. The
+// walk stops at the first unknown code, like the server does, since the rest can not be skipped.
+fn status_var_time_zone(mut b: &[u8]) -> Option<String> {
+  const Q_TIME_ZONE_CODE: u8 = 5;
+
+  fn len_prefixed(b: &[u8]) -> Option<usize> {
+    b.first().map(|len| 1 + *len as usize)
+  }
+
+  while let Some((&code, rest)) = b.split_first() {
+    let len = match code {
+      // Q_FLAGS2_CODE, Q_MASTER_DATA_WRITTEN_CODE
+      0 | 10 => 4,
+      // Q_SQL_MODE_CODE, Q_TABLE_MAP_FOR_UPDATE_CODE, Q_DDL_LOGGED_WITH_XID
+      1 | 9 | 17 => 8,
+      // Q_CATALOG_CODE, null terminated
+      2 => len_prefixed(rest)? + 1,
+      // Q_AUTO_INCREMENT
+      3 => 4,
+      // Q_CHARSET_CODE
+      4 => 6,
+      Q_TIME_ZONE_CODE => {
+        let len = len_prefixed(rest)?;
+        let time_zone = rest.get(1..len)?;
+        return core::str::from_utf8(time_zone).ok().map(String::from);
+      }
+      // Q_CATALOG_NZ_CODE
+      6 => len_prefixed(rest)?,
+      // Q_LC_TIME_NAMES_CODE, Q_CHARSET_DATABASE_CODE, Q_DEFAULT_COLLATION_FOR_UTF8MB4
+      7 | 8 | 18 => 2,
+      // Q_INVOKER, user then host
+      11 => {
+        let user = len_prefixed(rest)?;
+        user + len_prefixed(rest.get(user..)?)?
+      }
+      // Q_UPDATED_DB_NAMES, null terminated names unless there are too many to be logged.
+      12 => {
+        let count = *rest.first()?;
+        let mut len = 1;
+        if count != 254 {
+          for _ in 0..count {
+            let name = rest.get(len..)?;
+            len += name.iter().position(|b| *b == 0)? + 1;
+          }
+        }
+        len
+      }
+      // Q_MICROSECONDS
+      13 => 3,
+      // Q_EXPLICIT_DEFAULTS_FOR_TIMESTAMP, Q_SQL_REQUIRE_PRIMARY_KEY, Q_DEFAULT_TABLE_ENCRYPTION
+      16 | 19 | 20 => 1,
+      _ => return None,
+    };
+    b = rest.get(len..)?;
+  }
+  None
+}
+
+// https://dev.mysql.com/doc/internals/en/xid-event.html
+#[derive(Debug)]
+pub struct XidEvent {
+  xid: u64,
+}
+
+impl XidEvent {
+  fn parse(buffer: impl Into<Bytes>) -> io::Result<Self> {
+    let mut b = buffer.into();
+    let xid = b.safe_get_u64_le()?;
+    Ok(Self { xid })
+  }
+
+  pub fn xid(&self) -> u64 {
+    self.xid
+  }
+}
+
+// https://dev.mysql.com/doc/dev/mysql-server/latest/classbinary__log_1_1Gtid__event.html
+#[derive(Debug)]
+pub struct GtidEvent {
+  anonymous: bool,
+  flags: u8,
+  sid: [u8; 16],
+  gno: u64,
+  last_committed: Option<u64>,
+  sequence_number: Option<u64>,
+  immediate_commit_timestamp: Option<u64>,
+  original_commit_timestamp: Option<u64>,
+  transaction_length: Option<u64>,
+}
+
+// Set on the immediate commit timestamp when the original commit timestamp follows it.
+const ORIGINAL_COMMIT_TIMESTAMP_FLAG: u64 = 1 << 55;
+
+impl GtidEvent {
+  fn parse(buffer: impl Into<Bytes>, anonymous: bool) -> io::Result<Self> {
+    let mut b = buffer.into();
+    let flags = b.safe_get_u8()?;
+    let mut sid = [0; 16];
+    b.ensure_remaining(sid.len())?;
+    b.copy_to_slice(&mut sid);
+    let gno = b.safe_get_u64_le()?;
+
+    // logical timestamps are only available from 5.7.
+    let mut last_committed = None;
+    let mut sequence_number = None;
+    // commit timestamps are only available from 8.0.1.
+    let mut immediate_commit_timestamp = None;
+    let mut original_commit_timestamp = None;
+    // transaction length is only available from 8.0.2.
+    let mut transaction_length = None;
+    if b.remaining() >= 17 && b.safe_get_u8()? == 0x02 {
+      last_committed = Some(b.safe_get_u64_le()?);
+      sequence_number = Some(b.safe_get_u64_le()?);
+
+      if b.remaining() >= 7 {
+        let immediate = b.safe_get_uint_le(7)?;
+        let original = if immediate & ORIGINAL_COMMIT_TIMESTAMP_FLAG != 0 {
+          b.safe_get_uint_le(7)?
+        } else {
+          immediate
+        };
+        immediate_commit_timestamp = Some(immediate & !ORIGINAL_COMMIT_TIMESTAMP_FLAG);
+        original_commit_timestamp = Some(original);
+      }
+
+      if b.has_remaining() {
+        transaction_length = Some(b.safe_get_lenc_uint()?);
+      }
+    }
+
+    Ok(Self {
+      anonymous,
+      flags,
+      sid,
+      gno,
+      last_committed,
+      sequence_number,
+      immediate_commit_timestamp,
+      original_commit_timestamp,
+      transaction_length,
+    })
+  }
+
+  /// Returns true for ANONYMOUS_GTID_EVENT, logged when `gtid_mode` is OFF.
+  pub fn is_anonymous(&self) -> bool {
+    self.anonymous
+  }
+
+  pub fn flags(&self) -> u8 {
+    self.flags
+  }
+
+  pub fn sid(&self) -> &[u8; 16] {
+    &self.sid
+  }
+
+  pub fn gno(&self) -> u64 {
+    self.gno
+  }
+
+  pub fn last_committed(&self) -> Option<u64> {
+    self.last_committed
+  }
+
+  pub fn sequence_number(&self) -> Option<u64> {
+    self.sequence_number
+  }
+
+  /// Time the transaction was committed on the server that logged this event, in microseconds
+  /// since the epoch. Only logged from MYSQL 8.0.1.
+  pub fn immediate_commit_timestamp(&self) -> Option<u64> {
+    self.immediate_commit_timestamp
+  }
+
+  /// Time the transaction was committed on the server it originated from, in microseconds since
+  /// the epoch. Same as the immediate commit timestamp on the source itself, unlike the timestamp
+  /// of the event header, which can be the time an intermediate server of the replication chain
+  /// applied it.
+  pub fn original_commit_timestamp(&self) -> Option<u64> {
+    self.original_commit_timestamp
+  }
+
+  /// Size of the transaction in the binlog, in bytes, this event included. Only logged from MYSQL
+  /// 8.0.2.
+  pub fn transaction_length(&self) -> Option<u64> {
+    self.transaction_length
+  }
+
+  /// Returns the GTID formatted as `uuid:gno`, or `None` for anonymous transactions.
+  pub fn gtid(&self) -> Option<String> {
+    if self.anonymous {
+      return None;
+    }
+
+    Some(format!("{}:{}", format_sid(&self.sid), self.gno))
+  }
+}
+
+// Formats a server UUID, e.g. `3e11fa47-71ca-11e1-9e33-c80aa9429562`.
+fn format_sid(sid: &[u8; 16]) -> String {
+  let hex: Vec<String> = sid.iter().map(|b| format!("{:02x}", b)).collect();
+  format!(
+    "{}-{}-{}-{}-{}",
+    hex[0..4].concat(),
+    hex[4..6].concat(),
+    hex[6..8].concat(),
+    hex[8..10].concat(),
+    hex[10..16].concat(),
+  )
+}
+
+// Logged at the beginning of every binlog file, with the GTIDs of the transactions of the previous
+// files.
+// https://dev.mysql.com/doc/dev/mysql-server/latest/classbinary__log_1_1Previous__gtids__event.html
+#[derive(Debug)]
+pub struct PreviousGtidsEvent {
+  sids: Vec<([u8; 16], GnoIntervals)>,
+}
+
+// Intervals of transaction numbers of a server, the end is exclusive.
+type GnoIntervals = Vec<(u64, u64)>;
+
+impl PreviousGtidsEvent {
+  fn parse(buffer: impl Into<Bytes>) -> io::Result<Self> {
+    let mut b = buffer.into();
+    let sid_count = b.safe_get_u64_le()?;
+    let mut sids = Vec::new();
+    for _ in 0..sid_count {
+      let mut sid = [0; 16];
+      b.ensure_remaining(sid.len())?;
+      b.copy_to_slice(&mut sid);
+      let interval_count = b.safe_get_u64_le()?;
+      let mut intervals = Vec::new();
+      for _ in 0..interval_count {
+        let start = b.safe_get_u64_le()?;
+        let end = b.safe_get_u64_le()?;
+        intervals.push((start, end));
+      }
+      sids.push((sid, intervals));
+    }
+    Ok(Self { sids })
+  }
+
+  /// Returns the GTID set formatted as `uuid:1-5:7,uuid:1-3`, empty when `gtid_mode` is OFF.
+  pub fn gtid_set(&self) -> String {
+    self
+      .sids
+      .iter()
+      .map(|(sid, intervals)| {
+        let mut gtids = format_sid(sid);
+        for (start, end) in intervals {
+          match end.saturating_sub(1) {
+            last if last <= *start => gtids.push_str(&format!(":{}", start)),
+            last => gtids.push_str(&format!(":{}-{}", start, last)),
+          }
+        }
+        gtids
+      })
+      .collect::<Vec<_>>()
+      .join(",")
+  }
+}
+
+#[derive(Debug)]
+pub struct RotateEvent {
+  position: u64,
+  next_log_name: String,
+}
+
+impl RotateEvent {
+  fn parse(buffer: impl Into<Bytes>) -> io::Result<Self> {
+    let mut b = buffer.into();
+    let position = b.safe_get_u64_le()?;
+    let next_log_name = b.safe_get_eof_string()?;
+
+    Ok(Self {
+      position,
+      next_log_name,
+    })
+  }
+
+  pub fn position(&self) -> u64 {
+    self.position
+  }
+
+  pub fn next_log_name_str(&self) -> &str {
+    self.next_log_name.as_str()
+  }
+}
+
+#[derive(Debug)]
+pub struct TableMapEvent {
+  table_id: u64,
+  flags: u16,
+  schema: String,
+  table: String,
+  column_count: u64,
+  column_types: Vec<ColumnType>,
+  column_metas: Vec<u16>,
+  null_bitmap: Vec<u8>,
+  column_names: Vec<String>,
+  unsigned_columns: Vec<bool>,
+}
+
+// https://dev.mysql.com/doc/dev/mysql-server/latest/classbinary__log_1_1Table__map__event.html
+// Optional metadata fields, the signedness of the numeric columns (one bit each, most significant
+// first), logged by default, and their names, with `binlog_row_metadata=FULL`.
+const TABLE_MAP_SIGNEDNESS: u8 = 1;
+const TABLE_MAP_COLUMN_NAME: u8 = 4;
+
+// Columns with a bit in the SIGNEDNESS field.
+fn is_numeric(column_type: ColumnType) -> bool {
+  matches!(
+    column_type,
+    ColumnType::MYSQL_TYPE_TINY
+      | ColumnType::MYSQL_TYPE_SHORT
+      | ColumnType::MYSQL_TYPE_INT24
+      | ColumnType::MYSQL_TYPE_LONG
+      | ColumnType::MYSQL_TYPE_LONGLONG
+      | ColumnType::MYSQL_TYPE_NEWDECIMAL
+      | ColumnType::MYSQL_TYPE_FLOAT
+      | ColumnType::MYSQL_TYPE_DOUBLE
+  )
+}
+
+impl TableMapEvent {
+  fn parse(buffer: impl Into<Bytes>) -> io::Result<Self> {
+    let mut b = buffer.into();
+    let table_id = b.safe_get_uint_le(6)?; // this is actually a fixed length (either 4 or 6 bytes)
+    let flags = b.safe_get_u16_le()?;
+
+    let schema_len = b.safe_get_u8()? as usize;
+    let schema = b.safe_get_fixed_length_string(schema_len)?;
+
+    // skip 0x00
+    b.safe_advance(1)?;
+
+    let table_len = b.safe_get_u8()? as usize;
+    let table = b.safe_get_fixed_length_string(table_len)?;
+
+    // skip 0x00
+    b.safe_advance(1)?;
+
+    let column_count = b.safe_get_lenc_uint()? as usize;
+    let column_types = b
+      .safe_get_bytes(column_count)?
+      .into_iter()
+      .map(ColumnType::try_from)
+      .collect::<io::Result<Vec<_>>>()?;
+
+    let mut column_metas = vec![0; column_count];
+
+    let column_meta_reader_len = b.safe_get_lenc_uint()? as usize;
+    let column_meta = b.safe_get_bytes(column_meta_reader_len)?;
+    let mut column_meta_reader = column_meta.as_slice();
+
+    for (i, t) in column_types.iter().enumerate() {
+      match t {
+        // 2 bytes, the max length in bytes
+        ColumnType::MYSQL_TYPE_VAR_STRING | ColumnType::MYSQL_TYPE_VARCHAR => {
+          column_metas[i] = column_meta_reader.safe_get_u16_le()?;
+        }
+
+        // 2 bytes, (real type, length), (precision, scale) and (bits, bytes) respectively
+        ColumnType::MYSQL_TYPE_STRING
+        | ColumnType::MYSQL_TYPE_NEWDECIMAL
+        | ColumnType::MYSQL_TYPE_BIT => {
+          column_metas[i] = column_meta_reader.safe_get_uint_be(2)? as u16;
+        }
+
+        // 1 byte
+        ColumnType::MYSQL_TYPE_BLOB
+        | ColumnType::MYSQL_TYPE_DOUBLE
+        | ColumnType::MYSQL_TYPE_FLOAT
+        | ColumnType::MYSQL_TYPE_GEOMETRY
+        | ColumnType::MYSQL_TYPE_JSON => {
+          // println!("b {:?}", t);
+          column_metas[i] = column_meta_reader.safe_get_u8()? as u16;
+        }
+
+        // maybe 1 byte?
+        ColumnType::MYSQL_TYPE_TIME2
+        | ColumnType::MYSQL_TYPE_DATETIME2
+        | ColumnType::MYSQL_TYPE_TIMESTAMP2 => {
+          // println!("c {:?}", t);
+          column_metas[i] = column_meta_reader.safe_get_u8()? as u16;
+        }
+
+        // 0 byte
+        ColumnType::MYSQL_TYPE_DECIMAL
+        | ColumnType::MYSQL_TYPE_TINY
+        | ColumnType::MYSQL_TYPE_SHORT
+        | ColumnType::MYSQL_TYPE_LONG
+        | ColumnType::MYSQL_TYPE_NULL
+        | ColumnType::MYSQL_TYPE_TIMESTAMP
+        | ColumnType::MYSQL_TYPE_LONGLONG
+        | ColumnType::MYSQL_TYPE_INT24
+        | ColumnType::MYSQL_TYPE_DATE
+        | ColumnType::MYSQL_TYPE_TIME
+        | ColumnType::MYSQL_TYPE_DATETIME
+        | ColumnType::MYSQL_TYPE_YEAR => {
+          // println!("d {:?}", t);
+          column_metas[i] = 0_u16;
+        }
+
+        _ => return Err(unexpected_err(format!("{:?} not supported", t))),
+      }
+    }
+
+    let null_bitmap_len = column_count.div_ceil(8);
+    let (null_bitmap, column_names, unsigned_columns) = if b.len() >= null_bitmap_len {
+      let null_bitmap = b.split_to(null_bitmap_len).to_vec();
+      // The optional metadata is best effort, e.g. events parsed on their own may still end with a
+      // checksum.
+      let column_names = Self::parse_column_names(b.clone(), column_count).unwrap_or_default();
+      let unsigned_columns = Self::parse_signedness(b, &column_types).unwrap_or_default();
+      (null_bitmap, column_names, unsigned_columns)
+    } else {
+      (Vec::new(), Vec::new(), Vec::new())
+    };
+
+    Ok(Self {
+      table_id,
+      flags,
+      schema,
+      table,
+      column_count: column_count as u64,
+      column_types,
+      column_metas,
+      null_bitmap,
+      column_names,
+      unsigned_columns,
+    })
+  }
+
+  // Walks the type, length and value fields of the optional metadata, up to the value of the field
+  // of `wanted_type`.
+  fn optional_metadata_field(mut b: Bytes, wanted_type: u8) -> Option<Bytes> {
+    while b.has_remaining() {
+      let field_type = b.safe_get_u8().ok()?;
+      let len = b.safe_get_lenc_uint().ok()? as usize;
+      let value = Bytes::from(b.safe_get_bytes(len).ok()?);
+      if field_type == wanted_type {
+        return Some(value);
+      }
+    }
+    None
+  }
+
+  fn parse_signedness(b: Bytes, column_types: &[ColumnType]) -> Option<Vec<bool>> {
+    let bits = Self::optional_metadata_field(b, TABLE_MAP_SIGNEDNESS)?;
+    let mut nth_numeric = 0;
+    let mut unsigned_columns = Vec::with_capacity(column_types.len());
+    for column_type in column_types {
+      if !is_numeric(*column_type) {
+        unsigned_columns.push(false);
+        continue;
+      }
+      let byte = bits.get(nth_numeric / 8)?;
+      unsigned_columns.push(byte & (0x80 >> (nth_numeric % 8)) != 0);
+      nth_numeric += 1;
+    }
+    Some(unsigned_columns)
+  }
+
+  fn parse_column_names(b: Bytes, column_count: usize) -> Option<Vec<String>> {
+    let mut value = Self::optional_metadata_field(b, TABLE_MAP_COLUMN_NAME)?;
+    let mut names = Vec::with_capacity(column_count);
+    while value.has_remaining() {
+      let len = value.safe_get_lenc_uint().ok()? as usize;
+      names.push(value.safe_get_fixed_length_string(len).ok()?);
+    }
+    Some(names).filter(|names| names.len() == column_count)
+  }
+
+  pub fn table_id(&self) -> u64 {
+    self.table_id
+  }
+
+  pub fn flags(&self) -> u16 {
+    self.flags
+  }
+
+  pub fn schema_str(&self) -> &str {
+    self.schema.as_str()
+  }
+
+  pub fn table_str(&self) -> &str {
+    self.table.as_str()
+  }
+
+  pub fn column_count(&self) -> u64 {
+    self.column_count
+  }
+
+  pub fn column_types(&self) -> &[ColumnType] {
+    self.column_types.as_slice()
+  }
+
+  pub fn column_metas(&self) -> &[u16] {
+    self.column_metas.as_slice()
+  }
+
+  /// Names of the columns, only logged with `binlog_row_metadata=FULL` (MYSQL >= 8.0.1). Empty
+  /// otherwise.
+  pub fn column_names(&self) -> &[String] {
+    self.column_names.as_slice()
+  }
+
+  /// Whether the `i`th column is an UNSIGNED numeric column, per the signedness logged in the
+  /// optional metadata (MYSQL >= 8.0.1). `false` when it was not logged.
+  pub fn is_unsigned(&self, i: usize) -> bool {
+    self.unsigned_columns.get(i).copied().unwrap_or(false)
+  }
+}
+
+// https://dev.mysql.com/doc/refman/8.0/en/replication-options-binary-log.html#sysvar_binlog_checksum
+const BINLOG_CHECKSUM_ALG_CRC32: u8 = 1;
+const BINLOG_CHECKSUM_LEN: usize = 4;
+// Servers >= 5.6.1 append the checksum algorithm and the checksum of the event itself to the
+// FORMAT_DESCRIPTION_EVENT.
+const BINLOG_CHECKSUM_SERVER_VERSION: (u32, u32, u32) = (5, 6, 1);
+
+#[derive(Debug)]
+pub struct FormatDescriptionEvent {
+  version: u16,
+  server_version: String,
+  create_timestamp: u32,
+  event_header_length: u8,
+  event_type_header_lengths: Vec<u8>,
+  checksum_alg: Option<u8>,
+}
+
+impl FormatDescriptionEvent {
+  fn parse(buffer: impl Into<Bytes>) -> io::Result<Self> {
+    let mut b = buffer.into();
+    let version = b.safe_get_u16_le()?;
+
+    let server_version = b.safe_get_bytes(50)?;
+    let server_version = server_version
+      .as_slice()
+      .safe_get_fixed_length_string(null_terminated_pos(&server_version))?;
+
+    let create_timestamp = b.safe_get_u32_le()?;
+    let event_header_length = b.safe_get_u8()?;
+
+    let mut event_type_header_lengths = b.to_vec();
+    let checksum_alg = match event_type_header_lengths
+      .len()
+      .checked_sub(1 + BINLOG_CHECKSUM_LEN)
+    {
+      Some(pos) if server_version_triple(&server_version) >= BINLOG_CHECKSUM_SERVER_VERSION => {
+        let checksum_alg = event_type_header_lengths.get(pos).copied();
+        event_type_header_lengths.truncate(pos);
+        checksum_alg
+      }
+      _ => None,
+    };
+
+    Ok(Self {
+      version,
+      server_version,
+      create_timestamp,
+      event_header_length,
+      event_type_header_lengths,
+      checksum_alg,
+    })
+  }
+
+  /// Returns true when the events that follow end with a CRC32 checksum.
+  pub fn has_checksums(&self) -> bool {
+    self.checksum_alg == Some(BINLOG_CHECKSUM_ALG_CRC32)
+  }
+
+  pub fn version(&self) -> u16 {
+    self.version
+  }
+
+  pub fn server_version_str(&self) -> &str {
+    self.server_version.as_str()
+  }
+
+  pub fn create_timestamp(&self) -> u32 {
+    self.create_timestamp
+  }
+}
+
+// https://dev.mysql.com/doc/internals/en/binlog-file.html
+const BINLOG_MAGIC: &[u8] = b"\xfebin";
+// Files written with binlog_encryption=ON start with a header holding the id of the keyring key.
+const ENCRYPTED_BINLOG_MAGIC: &[u8] = b"\xfdbin";
+
+/// Length of the header of encrypted binlog files, the encrypted events follow it.
+pub const ENCRYPTED_BINLOG_HEADER_LEN: usize = 512;
+
+// https://dev.mysql.com/doc/dev/mysql-server/latest/classRpl__encryption__header__v1.html
+const ENCRYPTION_HEADER_END_MARK: u8 = 0;
+const ENCRYPTION_HEADER_KEY_ID: u8 = 1;
+const ENCRYPTION_HEADER_ENCRYPTED_PASSWORD: u8 = 2;
+const ENCRYPTION_HEADER_IV: u8 = 3;
+const ENCRYPTION_HEADER_PASSWORD_LEN: usize = 32;
+const ENCRYPTION_HEADER_IV_LEN: usize = 16;
+
+/// Header of the binlog files written with `binlog_encryption=ON`. The events are encrypted with
+/// AES-256-CTR by a per-file password, which is itself encrypted with AES-256-CBC by the
+/// replication master key `key_id` of the server's keyring.
+#[derive(Debug)]
+pub struct EncryptedBinlogHeader {
+  version: u8,
+  key_id: String,
+  encrypted_password: Vec<u8>,
+  iv: Vec<u8>,
+}
+
+impl EncryptedBinlogHeader {
+  pub fn parse(buffer: impl Into<Bytes>) -> io::Result<Self> {
+    let mut b = buffer.into();
+    if b.safe_get_bytes(ENCRYPTED_BINLOG_MAGIC.len())? != ENCRYPTED_BINLOG_MAGIC {
+      return Err(unexpected_err(
+        "not an encrypted binlog file, the magic number does not match",
+      ));
+    }
+    let version = b.safe_get_u8()?;
+    if version != 1 {
+      return Err(unexpected_err(format!(
+        "binlog encryption version {} is not supported",
+        version
+      )));
+    }
+
+    let mut key_id = None;
+    let mut encrypted_password = None;
+    let mut iv = None;
+    loop {
+      match b.safe_get_u8()? {
+        ENCRYPTION_HEADER_END_MARK => break,
+        ENCRYPTION_HEADER_KEY_ID => {
+          let len = b.safe_get_u8()? as usize;
+          key_id = Some(b.safe_get_fixed_length_string(len)?);
+        }
+        ENCRYPTION_HEADER_ENCRYPTED_PASSWORD => {
+          encrypted_password = Some(b.safe_get_bytes(ENCRYPTION_HEADER_PASSWORD_LEN)?)
+        }
+        ENCRYPTION_HEADER_IV => iv = Some(b.safe_get_bytes(ENCRYPTION_HEADER_IV_LEN)?),
+        field => {
+          return Err(unexpected_err(format!(
+            "unknown binlog encryption header field {}",
+            field
+          )))
+        }
+      }
+    }
+
+    let missing = |field| unexpected_err(format!("binlog encryption header without {}", field));
+    Ok(Self {
+      version,
+      key_id: key_id.ok_or_else(|| missing("key id"))?,
+      encrypted_password: encrypted_password.ok_or_else(|| missing("password"))?,
+      iv: iv.ok_or_else(|| missing("iv"))?,
+    })
+  }
+
+  /// Id of the replication master key in the server's keyring, e.g.
+  /// `MySQLReplicationKey_<server_uuid>_1`.
+  pub fn key_id_str(&self) -> &str {
+    self.key_id.as_str()
+  }
+
+  /// Password of the file, encrypted by the replication master key.
+  pub fn encrypted_password(&self) -> &[u8] {
+    self.encrypted_password.as_slice()
+  }
+
+  /// IV of the encryption of the password.
+  pub fn iv(&self) -> &[u8] {
+    self.iv.as_slice()
+  }
+}
+
+// https://mariadb.com/kb/en/start_encryption_event/
+#[derive(Debug)]
+pub struct StartEncryptionEvent {
+  scheme: u8,
+  key_version: u32,
+  nonce: Vec<u8>,
+}
+
+impl StartEncryptionEvent {
+  fn parse(buffer: impl Into<Bytes>) -> io::Result<Self> {
+    let mut b = buffer.into();
+    let scheme = b.safe_get_u8()?;
+    let key_version = b.safe_get_u32_le()?;
+    let nonce = b.safe_get_bytes(12)?;
+    Ok(Self {
+      scheme,
+      key_version,
+      nonce,
+    })
+  }
+
+  pub fn scheme(&self) -> u8 {
+    self.scheme
+  }
+
+  /// Version of the binlog key (key id 1) of the key management plugin.
+  pub fn key_version(&self) -> u32 {
+    self.key_version
+  }
+}
+
+/// Events of a binlog file (e.g. `mysql-bin.000001`), as written by the server or downloaded with
+/// `mysqlbinlog --read-from-remote-server --raw`. Iteration stops after the first error, as the
+/// boundaries of the events that follow can not be trusted anymore.
+pub struct BinlogFile {
+  b: Bytes,
+  checksums: bool,
+}
+
+impl BinlogFile {
+  pub fn parse(buffer: impl Into<Bytes>) -> io::Result<Self> {
+    let mut b = buffer.into();
+    if b.as_ref().starts_with(ENCRYPTED_BINLOG_MAGIC) {
+      let header = EncryptedBinlogHeader::parse(b)?;
+      return Err(unexpected_err(format!(
+        "binlog file is encrypted (binlog_encryption=ON) with the keyring key `{}`, decrypt it \
+         first or read it through the server",
+        header.key_id_str()
+      )));
+    }
+    let magic = b.safe_get_bytes(BINLOG_MAGIC.len())?;
+    if magic != BINLOG_MAGIC {
+      return Err(unexpected_err(
+        "not a binlog file, the magic number does not match",
+      ));
+    }
+    Ok(Self {
+      b,
+      checksums: false,
+    })
+  }
+
+  fn next_packet(&mut self) -> io::Result<BinlogEventPacket> {
+    // event_size is at offset 9 of the header.
+    let mut header = self.b.as_ref();
+    header.safe_advance(9)?;
+    let event_size = header.safe_get_u32_le()? as usize;
+    self.b.ensure_remaining(event_size)?;
+
+    let mut packet = BinlogEventPacket::parse_event(self.b.split_to(event_size))?;
+    if packet.event_type == EventType::START_ENCRYPTION_EVENT {
+      // The events that follow can not be parsed, their headers are encrypted too.
+      if self.checksums {
+        packet.strip_checksum()?;
+      }
+      let start_encryption = StartEncryptionEvent::parse(packet.payload)?;
+      return Err(unexpected_err(format!(
+        "binlog file is encrypted (encrypt_binlog=ON) with version {} of the binlog key, read it \
+         through the server instead",
+        start_encryption.key_version()
+      )));
+    }
+    if packet.event_type == EventType::FORMAT_DESCRIPTION_EVENT {
+      self.checksums = FormatDescriptionEvent::parse(packet.payload.clone())?.has_checksums();
+    } else if self.checksums {
+      packet.strip_checksum()?;
+    }
+    Ok(packet)
+  }
+}
+
+impl Iterator for BinlogFile {
+  type Item = io::Result<BinlogEventPacket>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if !self.b.has_remaining() {
+      return None;
+    }
+
+    let packet = self.next_packet();
+    if packet.is_err() {
+      self.b.clear();
+    }
+    Some(packet)
+  }
+}
+
+// https://dev.mysql.com/doc/dev/mysql-server/latest/classbinary__log_1_1Transaction__payload__event.html
+const PAYLOAD_HEADER_END_MARK: u64 = 0;
+const PAYLOAD_SIZE_FIELD: u64 = 1;
+const PAYLOAD_COMPRESSION_TYPE_FIELD: u64 = 2;
+const PAYLOAD_UNCOMPRESSED_SIZE_FIELD: u64 = 3;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PayloadCompression {
+  Zstd,
+  None,
+}
+
+impl TryFrom<u64> for PayloadCompression {
+  type Error = io::Error;
+
+  fn try_from(x: u64) -> io::Result<Self> {
+    match x {
+      0 => Ok(PayloadCompression::Zstd),
+      255 => Ok(PayloadCompression::None),
+      x => Err(unexpected_err(format!(
+        "unknown transaction payload compression {}",
+        x
+      ))),
+    }
+  }
+}
+
+#[derive(Debug)]
+pub struct TransactionPayloadEvent {
+  compression: PayloadCompression,
+  uncompressed_size: u64,
+  payload: Vec<u8>,
+}
+
+impl TransactionPayloadEvent {
+  fn parse(buffer: impl Into<Bytes>) -> io::Result<Self> {
+    let mut b = buffer.into();
+    let mut compression = PayloadCompression::None;
+    let mut uncompressed_size = 0;
+    let mut payload_size = None;
+
+    // Fields are (type, length, value) triplets, up to the end mark.
+    loop {
+      let field_type = b.safe_get_lenc_uint()?;
+      if field_type == PAYLOAD_HEADER_END_MARK {
+        break;
+      }
+      let len = b.safe_get_lenc_uint()? as usize;
+      b.ensure_remaining(len)?;
+      let mut value = b.split_to(len);
+      match field_type {
+        PAYLOAD_SIZE_FIELD => payload_size = Some(value.safe_get_lenc_uint()? as usize),
+        PAYLOAD_COMPRESSION_TYPE_FIELD => {
+          compression = PayloadCompression::try_from(value.safe_get_lenc_uint()?)?
+        }
+        PAYLOAD_UNCOMPRESSED_SIZE_FIELD => uncompressed_size = value.safe_get_lenc_uint()?,
+        // Fields added by newer servers.
+        _ => {}
+      }
+    }
+
+    let payload = match payload_size {
+      Some(payload_size) => b.safe_get_bytes(payload_size)?,
+      None => b.to_vec(),
+    };
+
+    Ok(Self {
+      compression,
+      uncompressed_size,
+      payload,
+    })
+  }
+
+  pub fn compression(&self) -> PayloadCompression {
+    self.compression
+  }
+
+  /// Size of the events once decompressed, as reported by the server.
+  pub fn uncompressed_size(&self) -> u64 {
+    self.uncompressed_size
+  }
+
+  /// The events, compressed with `compression`.
+  pub fn payload(&self) -> &[u8] {
+    self.payload.as_slice()
+  }
+
+  /// Events of the decompressed `payload`, which have no checksums. They all report the position
+  /// of the TRANSACTION_PAYLOAD_EVENT (`header`), the only one the transaction can be resumed from.
+  pub fn events(
+    uncompressed: impl Into<Bytes>,
+    header: &EventHeader,
+  ) -> impl Iterator<Item = io::Result<BinlogEventPacket>> {
+    let log_pos = header.log_pos();
+    let events = BinlogFile {
+      b: uncompressed.into(),
+      checksums: false,
+    };
+    events.map(move |packet| {
+      packet.map(|mut packet| {
+        packet.log_pos = log_pos;
+        packet
+      })
+    })
+  }
+}
+
+/// Events of a relay log file (e.g. `relay-bin.000002`), i.e. the events a replica received from
+/// its source. The events the replica wrote itself (its FORMAT_DESCRIPTION_EVENT,
+/// PREVIOUS_GTIDS_EVENT, and the ROTATE_EVENT to its next relay log) are skipped.
+///
+/// A relay log may span several binlog files of the source, each introduced by a ROTATE_EVENT
+/// and the FORMAT_DESCRIPTION_EVENT of the source. `log_pos` of the events are positions in the
+/// binlog file of the source named by `source_log_name_str`.
+pub struct RelayLogFile {
+  events: BinlogFile,
+  source_log_name: Option<String>,
+}
+
+impl RelayLogFile {
+  pub fn parse(buffer: impl Into<Bytes>) -> io::Result<Self> {
+    let events = BinlogFile::parse(buffer)?;
+    Ok(Self {
+      events,
+      source_log_name: None,
+    })
+  }
+
+  /// Binlog file of the source of the last event returned, `None` before the first ROTATE_EVENT.
+  pub fn source_log_name_str(&self) -> Option<&str> {
+    self.source_log_name.as_deref()
+  }
+}
+
+impl Iterator for RelayLogFile {
+  type Item = io::Result<BinlogEventPacket>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    loop {
+      let packet = match self.events.next()? {
+        Ok(packet) => packet,
+        Err(err) => return Some(Err(err)),
+      };
+      if packet.header().is_relay_log() {
+        continue;
+      }
+
+      if packet.event_type == EventType::ROTATE_EVENT {
+        match RotateEvent::parse(packet.payload.clone()) {
+          Ok(rotate) => self.source_log_name = Some(rotate.next_log_name),
+          Err(err) => return Some(Err(err)),
+        }
+      }
+      return Some(Ok(packet));
+    }
+  }
+}
+
+#[derive(Debug)]
+pub struct RowEvent {
+  table_id: u64,
+  flags: u16,
+  extras: Vec<u8>,
+  column_count: u64,
+  column_bitmap1: Vec<u8>,
+  column_bitmap2: Vec<u8>,
+  rows: Vec<u8>,
+}
+
+impl RowEvent {
+  fn parse(buffer: impl Into<Bytes>, use_extras: bool, use_bitmap2: bool) -> io::Result<Self> {
+    let mut b = buffer.into();
+    let table_id = b.safe_get_uint_le(6)?;
+    let flags = b.safe_get_u16_le()?;
+
+    let extras = if use_extras {
+      // the length includes the 2 bytes of the length itself.
+      let extras_len = b.safe_get_u16_le()?.checked_sub(2).ok_or_else(|| {
+        unexpected_err("rows event extra data length is smaller than its own header")
+      })?;
+
+      b.safe_get_bytes(extras_len as usize)?
+    } else {
+      Vec::new()
+    };
+
+    let column_count = b.safe_get_lenc_uint()?;
+
+    let bitmap_len = column_count.div_ceil(8) as usize;
+
+    let column_bitmap1 = b.safe_get_bytes(bitmap_len)?;
+
+    let column_bitmap2 = if use_bitmap2 {
+      b.safe_get_bytes(bitmap_len)?
+    } else {
+      Vec::new()
+    };
+
+    let rows = b.bytes().to_vec();
+
+    Ok(Self {
+      table_id,
+      flags,
+      extras,
+      column_count,
+      column_bitmap1,
+      column_bitmap2,
+      rows,
+    })
+  }
+
+  pub fn table_id(&self) -> u64 {
+    self.table_id
+  }
+
+  pub fn flags(&self) -> u16 {
+    self.flags
+  }
+
+  pub fn column_count(&self) -> u64 {
+    self.column_count
+  }
+
+  /// Raw rows images, as logged by MYSQL.
+  pub fn rows(&self) -> &[u8] {
+    self.rows.as_slice()
+  }
+
+  // https://dev.mysql.com/doc/internals/en/rows-event.html
+  //
+  // Decodes the rows images using the column definitions of the table map. For UPDATE_ROWS_EVENTs
+  // the before and after images alternate.
+  pub fn decode_rows(&self, table_map: &TableMapEvent) -> io::Result<Vec<Row>> {
+    if table_map.table_id() != self.table_id {
+      return Err(unexpected_err(format!(
+        "table map {} does not match rows event table {}",
+        table_map.table_id(),
+        self.table_id
+      )));
+    }
+
+    let mut b = self.rows.as_slice();
+    let mut rows = Vec::new();
+    while b.has_remaining() {
+      let bitmap = if !self.column_bitmap2.is_empty() && rows.len() % 2 == 1 {
+        &self.column_bitmap2
+      } else {
+        &self.column_bitmap1
+      };
+      rows.push(self.decode_row(&mut b, bitmap, table_map)?);
+    }
+
+    Ok(rows)
+  }
+
+  fn decode_row(&self, b: &mut &[u8], bitmap: &[u8], table_map: &TableMapEvent) -> io::Result<Row> {
+    let is_set = |bitmap: &[u8], i: usize| bitmap[i / 8] & (1 << (i % 8)) != 0;
+
+    let column_count = self.column_count as usize;
+    let present = (0..column_count).filter(|i| is_set(bitmap, *i)).count();
+    let null_bitmap = b.safe_get_bytes(present.div_ceil(8))?;
+
+    let mut values = Vec::with_capacity(column_count);
+    let mut nth_present = 0;
+    for i in 0..column_count {
+      if !is_set(bitmap, i) {
+        values.push(None);
+        continue;
+      }
+
+      let is_null = is_set(&null_bitmap, nth_present);
+      nth_present += 1;
+      if is_null {
+        values.push(Some(Value::Null));
+        continue;
+      }
+
+      let column_type = table_map.column_types().get(i).cloned();
+      let column_type =
+        column_type.ok_or_else(|| unexpected_err("column is missing from table map"))?;
+      let meta = table_map.column_metas()[i];
+      let unsigned = table_map.is_unsigned(i);
+      values.push(Some(Value::parse_from_binlog(
+        b,
+        column_type,
+        meta,
+        unsigned,
+      )?));
+    }
+
+    Ok(Row::new(values))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{
+    status_var_time_zone, BinlogEvent, BinlogEventPacket, BinlogFile, ColumnType,
+    EncryptedBinlogHeader, EventType, GtidEvent, PayloadCompression, RelayLogFile, TableMapEvent,
+    TransactionPayloadEvent, ENCRYPTED_BINLOG_HEADER_LEN, ORIGINAL_COMMIT_TIMESTAMP_FLAG,
+  };
+
+  #[test]
+  fn parses_rotate() {
+    const ROTATE_EVENT : &[u8] = b"\x00\x00\x00\x00\x00\x04\x01\x00\x00\x00\x2d\x00\x00\x00\x00\x00\x00\
+                                       \x00\x20\x00\x96\x00\x00\x00\x00\x00\x00\x00\x73\x68\x6f\x70\x69\x66\
+                                       \x79\x2d\x62\x69\x6e\x2e\x30\x30\x30\x30\x30\x35";
+
+    let event = BinlogEventPacket::parse(ROTATE_EVENT).unwrap();
+    match event.into_binlog_event().unwrap() {
+      BinlogEvent::Rotate(packet) => {
+        assert_eq!(150, packet.position());
+        assert_eq!("shopify-bin.000005", packet.next_log_name_str());
+        assert_eq!(
+          "ROTATE shopify-bin.000005:150",
+          BinlogEvent::Rotate(packet).to_string()
+        );
+      }
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+  }
+
+  #[test]
+  fn parses_stop() {
+    const STOP_EVENT: &[u8] =
+      b"\x00\xfc\x5a\x5d\x5d\x03\x01\x00\x00\x00\x13\x00\x00\x00\x2e\x02\x00\
+        \x00\x00\x00";
+
+    let event = BinlogEventPacket::parse(STOP_EVENT).unwrap();
+    assert_eq!(EventType::STOP_EVENT, event.event_type());
+    assert_eq!(558, event.header().log_pos());
+    match event.into_binlog_event().unwrap() {
+      BinlogEvent::Stop => {}
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+  }
+
+  #[test]
+  fn parses_query_time_zone() {
+    // Q_FLAGS2_CODE, Q_SQL_MODE_CODE, Q_CATALOG_NZ_CODE "std", Q_CHARSET_CODE
+    let status_vars = b"\x00\x00\x00\x00\x00\x01\x00\x00\x00\x40\x00\x00\x00\x00\x06\x03std\x04\
+                        \x21\x00\x21\x00\x2d\x00";
+    assert_eq!(None, status_var_time_zone(status_vars));
+
+    let mut status_vars = status_vars.to_vec();
+    status_vars.extend_from_slice(b"\x05\x06+02:00\x0c\x01pets\x00");
+    assert_eq!(
+      Some("+02:00"),
+      status_var_time_zone(&status_vars).as_deref()
+    );
+
+    // Truncated, or after an unknown code.
+    assert_eq!(None, status_var_time_zone(b"\x05\x06+02"));
+    assert_eq!(None, status_var_time_zone(b"\x0e\x05\x06+02:00"));
+  }
+
+  #[test]
+  fn parses_format_description() {
+    const FORMAT_DESCRIPTION_EVENT : &[u8] = b"\x00\xf2\x43\x5d\x5d\x0f\x01\x00\x00\x00\x77\x00\x00\x00\x00\x00\x00\
+                                                   \x00\x00\x00\x04\x00\x35\x2e\x37\x2e\x31\x38\x2d\x31\x36\x2d\x6c\x6f\
+                                                   \x67\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+                                                   \x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+                                                   \x00\x00\x00\x00\x00\x00\x00\x00\x13\x38\x0d\x00\x08\x00\x12\x00\x04\
+                                                   \x04\x04\x04\x12\x00\x00\x5f\x00\x04\x1a\x08\x00\x00\x00\x08\x08\x08\
+                                                   \x02\x00\x00\x00\x0a\x0a\x0a\x2a\x2a\x00\x12\x34\x00\x00\xc2\x36\x0c\
+                                                   \xdf";
+
+    let event = BinlogEventPacket::parse(FORMAT_DESCRIPTION_EVENT).unwrap();
+    match event.into_binlog_event().unwrap() {
+      BinlogEvent::Format(packet) => {
+        assert_eq!(4, packet.version());
+        assert_eq!("5.7.18-16-log", packet.server_version_str());
+        assert_eq!(0, packet.create_timestamp());
+      }
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+  }
+
+  #[test]
+  fn parses_anonymous_gtid() {
+    const ANONYMOUS_GTID_EVENT : &[u8] = b"\x00\xfc\x5a\x5d\x5d\x22\x01\x00\x00\x00\x3d\x00\x00\x00\xd3\x00\x00\
+                                               \x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+                                               \x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x02\x00\x00\x00\x00\x00\
+                                               \x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00";
+
+    let event = BinlogEventPacket::parse(ANONYMOUS_GTID_EVENT).unwrap();
+    assert_eq!(event.event_type, EventType::ANONYMOUS_GTID_EVENT);
+    assert_eq!(61, event.header().event_size());
+    assert_eq!(211, event.header().log_pos());
+    match event.into_binlog_event().unwrap() {
+      BinlogEvent::Gtid(packet) => {
+        assert!(packet.is_anonymous());
+        assert_eq!(None, packet.gtid());
+        assert_eq!(Some(0), packet.last_committed());
+        assert_eq!(Some(1), packet.sequence_number());
+      }
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+  }
+
+  #[test]
+  fn parses_commit_timestamps_of_gtids() {
+    const IMMEDIATE: u64 = 1_600_000_000_000_002;
+    const ORIGINAL: u64 = 1_600_000_000_000_001;
+
+    let mut b = vec![0x01];
+    b.extend_from_slice(&[0x3e; 16]);
+    b.extend_from_slice(&5u64.to_le_bytes());
+    b.push(0x02);
+    b.extend_from_slice(&4u64.to_le_bytes());
+    b.extend_from_slice(&5u64.to_le_bytes());
+    // Logged by the source: the original commit timestamp is the immediate one.
+    let mut source = b.clone();
+    source.extend_from_slice(&ORIGINAL.to_le_bytes()[..7]);
+    let event = GtidEvent::parse(source, false).unwrap();
+    assert_eq!(Some(ORIGINAL), event.immediate_commit_timestamp());
+    assert_eq!(Some(ORIGINAL), event.original_commit_timestamp());
+
+    // Logged by a replica.
+    let mut replica = b.clone();
+    replica.extend_from_slice(&(IMMEDIATE | ORIGINAL_COMMIT_TIMESTAMP_FLAG).to_le_bytes()[..7]);
+    replica.extend_from_slice(&ORIGINAL.to_le_bytes()[..7]);
+    let event = GtidEvent::parse(replica.clone(), false).unwrap();
+    assert_eq!(Some(IMMEDIATE), event.immediate_commit_timestamp());
+    assert_eq!(Some(ORIGINAL), event.original_commit_timestamp());
+    assert_eq!(None, event.transaction_length());
+
+    replica.extend_from_slice(&[0xfc, 0x2c, 0x01]);
+    let event = GtidEvent::parse(replica, false).unwrap();
+    assert_eq!(Some(300), event.transaction_length());
+
+    let event = GtidEvent::parse(b, false).unwrap();
+    assert_eq!(Some(5), event.sequence_number());
+    assert_eq!(None, event.original_commit_timestamp());
+  }
+
+  #[test]
+  fn parses_previous_gtids() {
+    const PREVIOUS_GTIDS_EVENT : &[u8] = b"\x00\xfc\x5a\x5d\x5d\x23\x01\x00\x00\x00\x53\x00\x00\x00\xae\x00\x00\
+                                               \x00\x80\x00\x01\x00\x00\x00\x00\x00\x00\x00\x3e\x11\xfa\x47\x71\xca\
+                                               \x11\xe1\x9e\x33\xc8\x0a\xa9\x42\x95\x62\x02\x00\x00\x00\x00\x00\x00\
+                                               \x00\x01\x00\x00\x00\x00\x00\x00\x00\x06\x00\x00\x00\x00\x00\x00\x00\
+                                               \x07\x00\x00\x00\x00\x00\x00\x00\x08\x00\x00\x00\x00\x00\x00\x00";
+
+    let event = BinlogEventPacket::parse(PREVIOUS_GTIDS_EVENT).unwrap();
+    assert_eq!(event.event_type, EventType::PREVIOUS_GTIDS_EVENT);
+    match event.into_binlog_event().unwrap() {
+      BinlogEvent::PreviousGtids(packet) => {
+        assert_eq!(
+          "3e11fa47-71ca-11e1-9e33-c80aa9429562:1-5:7",
+          packet.gtid_set()
+        );
+      }
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+  }
+
+  #[test]
+  fn parses_query() {
+    const QUERY_EVENT : &[u8] = b"\x00\xfc\x5a\x5d\x5d\x02\x01\x00\x00\x00\x44\x00\x00\x00\x17\x01\x00\
+                                      \x00\x08\x00\x3b\x18\x00\x00\x00\x00\x00\x00\x04\x00\x00\x1a\x00\x00\
+                                      \x00\x00\x00\x00\x01\x00\x00\x00\x40\x00\x00\x00\x00\x06\x03\x73\x74\
+                                      \x64\x04\x21\x00\x21\x00\x2d\x00\x70\x65\x74\x73\x00\x42\x45\x47\x49\
+                                      \x4e";
+
+    let event = BinlogEventPacket::parse(QUERY_EVENT).unwrap();
+    assert_eq!(event.event_type, EventType::QUERY_EVENT);
+    match event.into_binlog_event().unwrap() {
+      BinlogEvent::Query(packet) => {
+        assert_eq!(6203, packet.thread_id());
+        assert_eq!(0, packet.error_code());
+        assert_eq!("pets", packet.schema_str());
+        assert_eq!("BEGIN", packet.query_str());
+      }
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+  }
+
+  #[test]
+  fn parses_table_map() {
+    const TABLE_MAP_EVENT : &[u8] = b"\x00\xfc\x5a\x5d\x5d\x13\x01\x00\x00\x00\x32\x00\x00\x00\x49\x01\x00\
+                                          \x00\x00\x00\x2d\x0a\x00\x00\x00\x00\x01\x00\x04\x70\x65\x74\x73\x00\
+                                          \x04\x63\x61\x74\x73\x00\x04\x03\x0f\x0f\x0a\x04\x58\x02\x58\x02\x00";
+
+    let event = BinlogEventPacket::parse(TABLE_MAP_EVENT).unwrap();
+    match event.into_binlog_event().unwrap() {
+      BinlogEvent::TableMap(packet) => {
+        assert_eq!(2605, packet.table_id());
+        assert_eq!(1, packet.flags());
+        assert_eq!(4, packet.column_count());
+        assert_eq!("pets", packet.schema_str());
+        assert_eq!("cats", packet.table_str());
+        assert_eq!(
+          &[
+            ColumnType::MYSQL_TYPE_LONG,
+            ColumnType::MYSQL_TYPE_VARCHAR,
+            ColumnType::MYSQL_TYPE_VARCHAR,
+            ColumnType::MYSQL_TYPE_DATE
+          ],
+          packet.column_types()
+        );
+        assert_eq!(&[0, 600, 600, 0], packet.column_metas());
+        assert!(packet.column_names().is_empty());
+        assert_eq!(
+          "TABLE_MAP 2605 pets.cats (4 columns)",
+          BinlogEvent::TableMap(packet).to_string()
+        );
+      }
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+
+    // With binlog_row_metadata=FULL, followed by the SIGNEDNESS and COLUMN_NAME fields.
+    let payload = [
+      &TABLE_MAP_EVENT[20..],
+      b"\x01\x01\x00",
+      b"\x04\x16\x02id\x04name\x05owner\x07born_on",
+    ]
+    .concat();
+    let table_map = TableMapEvent::parse(payload).unwrap();
+    assert_eq!(
+      &["id", "name", "owner", "born_on"],
+      table_map.column_names()
+    );
+    assert!(!table_map.is_unsigned(0));
+
+    // id INT UNSIGNED, the first numeric column.
+    let payload = [&TABLE_MAP_EVENT[20..], b"\x01\x01\x80"].concat();
+    let table_map = TableMapEvent::parse(payload).unwrap();
+    assert!(table_map.is_unsigned(0));
+    assert!(!table_map.is_unsigned(1));
+  }
+
+  #[test]
+  fn parses_insert_row() {
+    const INSERT_ROW_EVENT : &[u8] = b"\x00\xfc\x5a\x5d\x5d\x1e\x01\x00\x00\x00\x37\x00\x00\x00\x80\x01\x00\
+                                           \x00\x00\x00\x2d\x0a\x00\x00\x00\x00\x01\x00\x02\x00\x04\xff\xf0\x04\
+                                           \x00\x00\x00\x07\x00\x43\x68\x61\x72\x6c\x69\x65\x05\x00\x52\x69\x76\
+                                           \x65\x72\xb5\xc0\x0f";
+
+    let event = BinlogEventPacket::parse(INSERT_ROW_EVENT).unwrap();
+    match event.into_binlog_event().unwrap() {
+      BinlogEvent::Insert(packet) => {
+        assert_eq!(2605, packet.table_id());
+        assert_eq!(1, packet.flags());
+      }
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+  }
+
+  #[test]
+  fn parses_delete_row() {
+    // TODO
+  }
+
+  #[test]
+  fn parses_update_row() {
+    // TODO
+  }
+
+  #[test]
+  fn parses_xid_event() {
+    const XID_EVENT: &[u8] =
+      b"\x00\xfc\x5a\x5d\x5d\x10\x01\x00\x00\x00\x1b\x00\x00\x00\x9b\x01\x00\
+                                    \x00\x00\x00\x72\x0e\x00\x00\x00\x00\x00\x00";
+
+    let event = BinlogEventPacket::parse(XID_EVENT).unwrap();
+    assert_eq!(event.event_type, EventType::XID_EVENT);
+    match event.into_binlog_event().unwrap() {
+      BinlogEvent::Xid(packet) => assert_eq!(3698, packet.xid()),
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+  }
+
+  // #[test]
+  // fn parses_row_event() {
+  //     // 00000000  00 00 00 00 00 04 01 00  00 00 2d 00 00 00 00 00  |..........-.....|
+  //     // 00000010  00 00 20 00 96 00 00 00  00 00 00 00 73 68 6f 70  |.. .........shop|
+  //     // 00000020  69 66 79 2d 62 69 6e 2e  30 30 30 30 30 35 00 f2  |ify-bin.000005..|
+  //     // 00000030  43 5d 5d 0f 01 00 00 00  77 00 00 00 00 00 00 00  |C]].....w.......|
+  //     // 00000040  00 00 04 00 35 2e 37 2e  31 38 2d 31 36 2d 6c 6f  |....5.7.18-16-lo|
+  //     // 00000050  67 00 00 00 00 00 00 00  00 00 00 00 00 00 00 00  |g...............|
+  //     // 00000060  00 00 00 00 00 00 00 00  00 00 00 00 00 00 00 00  |................|
+  //     // 00000070  00 00 00 00 00 00 00 00  00 00 13 38 0d 00 08 00  |...........8....|
+  //     // 00000080  12 00 04 04 04 04 12 00  00 5f 00 04 1a 08 00 00  |........._......|
+  //     // 00000090  00 08 08 08 02 00 00 00  0a 0a 0a 2a 2a 00 12 34  |...........**..4|
+  //     // 000000a0  00 00 c2 36 0c df 00 fc  5a 5d 5d 22 01 00 00 00  |...6....Z]]"....|
+  //     // 000000b0  3d 00 00 00 d3 00 00 00  00 00 01 00 00 00 00 00  |=...............|
+  //     // 000000c0  00 00 00 00 00 00 00 00  00 00 00 00 00 00 00 00  |................|
+  //     // 000000d0  00 00 00 02 00 00 00 00  00 00 00 00 01 00 00 00  |................|
+  //     // 000000e0  00 00 00 00 00 fc 5a 5d  5d 02 01 00 00 00 44 00  |......Z]].....D.|
+  //     // 000000f0  00 00 17 01 00 00 08 00  3b 18 00 00 00 00 00 00  |........;.......|
+  //     // 00000100  04 00 00 1a 00 00 00 00  00 00 01 00 00 00 40 00  |..............@.|
+  //     // 00000110  00 00 00 06 03 73 74 64  04 21 00 21 00 2d 00 70  |.....std.!.!.-.p|
+  //     // 00000120  65 74 73 00 42 45 47 49  4e 00 fc 5a 5d 5d 13 01  |ets.BEGIN..Z]]..|
+  //     // 00000130  00 00 00 32 00 00 00 49  01 00 00 00 00 2d 0a 00  |...2...I.....-..|
+  //     // 00000140  00 00 00 01 00 04 70 65  74 73 00 04 63 61 74 73  |......pets..cats|
+  //     // 00000150  00 04 03 0f 0f 0a 04 58  02 58 02 00 00 fc 5a 5d  |.......X.X....Z]|
+  //     // 00000160  5d 1e 01 00 00 00 37 00  00 00 80 01 00 00 00 00  |].....7.........|
+  //     // 00000170  2d 0a 00 00 00 00 01 00  02 00 04 ff f0 04 00 00  |-...............|
+  //     // 00000180  00 07 00 43 68 61 72 6c  69 65 05 00 52 69 76 65  |...Charlie..Rive|
+  //     // 00000190  72 b5 c0 0f 00 fc 5a 5d  5d 10 01 00 00 00 1b 00  |r.....Z]].......|
+  //     // 000001a0  00 00 9b 01 00 00 00 00  72 0e 00 00 00 00 00 00  |........r.......|
+
+  //     // const A : &[u8] = b"\x00\x00\x00\x00\x00\x04\x01\x00\x00\x00\x2d\x00\x00\x00\x00\x00\
+  //     //                     \x00\x00\x20\x00\x96\x00\x00\x00\x00\x00\x00\x00\x73\x68\x6f\x70\
+  //     //                     \x69\x66\x79\x2d\x62\x69\x6e\x2e\x30\x30\x30\x30\x30\x35";
+
+  //     // const B : &[u8] = b"\x00\xf2\
+  //     //     \x43\x5d\x5d\x0f\x01\x00\x00\x00\x77\x00\x00\x00\x00\x00\x00\x00\
+  //     //     \x00\x00\x04\x00\x35\x2e\x37\x2e\x31\x38\x2d\x31\x36\x2d\x6c\x6f\
+  //     //     \x67\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+  //     //     \x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+  //     //     \x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x13\x38\x0d\x00\x08\x00\
+  //     //     \x12\x00\x04\x04\x04\x04\x12\x00\x00\x5f\x00\x04\x1a\x08\x00\x00\
+  //     //     \x00\x08\x08\x08\x02\x00\x00\x00\x0a\x0a\x0a\x2a\x2a\x00\x12\x34\
+  //     //     \x00\x00\xc2\x36\x0c\xdf";
+
+  //     // const ANONYMOUS_GTID_EVENT : &[u8] = b"\x00\xfc\x5a\x5d\x5d\x22\x01\x00\x00\x00\x3d\x00\x00\x00\xd3\x00\x00\
+  //     //                                        \x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+  //     //                                        \x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x02\x00\x00\x00\x00\x00\
+  //     //                                        \x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00";
+
+  //     // const QUERY_EVENT : &[u8] = b"\x00\xfc\x5a\x5d\x5d\x02\x01\x00\x00\x00\x44\x00\x00\x00\x17\x01\x00\
+  //     //                               \x00\x08\x00\x3b\x18\x00\x00\x00\x00\x00\x00\x04\x00\x00\x1a\x00\x00\
+  //     //                               \x00\x00\x00\x00\x01\x00\x00\x00\x40\x00\x00\x00\x00\x06\x03\x73\x74\
+  //     //                               \x64\x04\x21\x00\x21\x00\x2d\x00\x70\x65\x74\x73\x00\x42\x45\x47\x49\
+  //     //                               \x4e";
+
+  //     // const TABLE_MAP_EVENT : &[u8] = b"\x00\xfc\x5a\x5d\x5d\x13\x01\x00\x00\x00\x32\x00\x00\x00\x49\x01\x00\
+  //     //                                   \x00\x00\x00\x2d\x0a\x00\x00\x00\x00\x01\x00\x04\x70\x65\x74\x73\x00\
+  //     //                                   \x04\x63\x61\x74\x73\x00\x04\x03\x0f\x0f\x0a\x04\x58\x02\x58\x02\x00";
+
+  //     // const INSERT_ROW_EVENT : &[u8] = b"\x00\xfc\x5a\x5d\x5d\x1e\x01\x00\x00\x00\x37\x00\x00\x00\x80\x01\x00\
+  //     //                                    \x00\x00\x00\x2d\x0a\x00\x00\x00\x00\x01\x00\x02\x00\x04\xff\xf0\x04\
+  //     //                                    \x00\x00\x00\x07\x00\x43\x68\x61\x72\x6c\x69\x65\x05\x00\x52\x69\x76\
+  //     //                                    \x65\x72\xb5\xc0\x0f";
+
+  //     // const XID_EVENT : &[u8] = b"\x00\xfc\x5a\x5d\x5d\x10\x01\x00\x00\x00\x1b\x00\x00\x00\x9b\x01\x00\
+  //     //                             \x00\x00\x00\x72\x0e\x00\x00\x00\x00\x00\x00";
+
+  //     // let events = vec![
+  //     //     ROTATE_EVENT.to_vec(),
+  //     //     FORMAT_DESCRIPTION_EVENT.to_vec(),
+  //     //     ANONYMOUS_GTID_EVENT.to_vec(),
+  //     //     QUERY_EVENT.to_vec(),
+  //     //     TABLE_MAP_EVENT.to_vec(),
+  //     //     INSERT_ROW_EVENT.to_vec(),
+  //     //     XID_EVENT.to_vec(),
+  //     // ];
+
+  //     // let mut reader = Reader::new();
+  //     // for e in events {
+  //     //     reader.next_row_event(&e);
+  //     // }
+  //     // for lol in Reader::new(events.iter()) {
+  //     //     println!("{:?}", lol);
+  //     // }
+  //     // let mut reader = ;
+  //     // reader.next();
+  //     // reader.next();
+  //     // reader.next();
+  //     // reader.next();
+  //     // reader.next();
+  //     // reader.next();
+  //     // reader.next();
+
+  //     // const EVENT : &[u8] = b"\x00\x00\x00\x00\x00\x04\x01\x00\x00\x00\x2d\x00\x00\x00\x00\x00\
+  //     //                         \x00\x00\x20\x00\x96\x00\x00\x00\x00\x00\x00\x00\x73\x68\x6f\x70\
+  //     //                         \x69\x66\x79\x2d\x62\x69\x6e\x2e\x30\x30\x30\x30\x30\x35\x00\xf2\
+  //     //                         \x43\x5d\x5d\x0f\x01\x00\x00\x00\x77\x00\x00\x00\x00\x00\x00\x00\
+  //     //                         \x00\x00\x04\x00\x35\x2e\x37\x2e\x31\x38\x2d\x31\x36\x2d\x6c\x6f\
+  //     //                         \x67\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+  //     //                         \x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+  //     //                         \x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x13\x38\x0d\x00\x08\x00\
+  //     //                         \x12\x00\x04\x04\x04\x04\x12\x00\x00\x5f\x00\x04\x1a\x08\x00\x00\
+  //     //                         \x00\x08\x08\x08\x02\x00\x00\x00\x0a\x0a\x0a\x2a\x2a\x00\x12\x34\
+  //     //                         \x00\x00\xc2\x36\x0c\xdf\x00\xfc\x5a\x5d\x5d\x22\x01\x00\x00\x00\
+  //     //                         \x3d\x00\x00\x00\xd3\x00\x00\x00\x00\x00\x01\x00\x00\x00\x00\x00\
+  //     //                         \x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+  //     //                         \x00\x00\x00\x02\x00\x00\x00\x00\x00\x00\x00\x00\x01\x00\x00\x00\
+  //     //                         \x00\x00\x00\x00\x00\xfc\x5a\x5d\x5d\x02\x01\x00\x00\x00\x44\x00\
+  //     //                         \x00\x00\x17\x01\x00\x00\x08\x00\x3b\x18\x00\x00\x00\x00\x00\x00\
+  //     //                         \x04\x00\x00\x1a\x00\x00\x00\x00\x00\x00\x01\x00\x00\x00\x40\x00\
+  //     //                         \x00\x00\x00\x06\x03\x73\x74\x64\x04\x21\x00\x21\x00\x2d\x00\x70\
+  //     //                         \x65\x74\x73\x00\x42\x45\x47\x49\x4e\x00\xfc\x5a\x5d\x5d\x13\x01\
+  //     //                         \x00\x00\x00\x32\x00\x00\x00\x49\x01\x00\x00\x00\x00\x2d\x0a\x00\
+  //     //                         \x00\x00\x00\x01\x00\x04\x70\x65\x74\x73\x00\x04\x63\x61\x74\x73\
+  //     //                         \x00\x04\x03\x0f\x0f\x0a\x04\x58\x02\x58\x02\x00\x00\xfc\x5a\x5d\
+  //     //                         \x5d\x1e\x01\x00\x00\x00\x37\x00\x00\x00\x80\x01\x00\x00\x00\x00\
+  //     //                         \x2d\x0a\x00\x00\x00\x00\x01\x00\x02\x00\x04\xff\xf0\x04\x00\x00\
+  //     //                         \x00\x07\x00\x43\x68\x61\x72\x6c\x69\x65\x05\x00\x52\x69\x76\x65\
+  //     //                         \x72\xb5\xc0\x0f\x00\xfc\x5a\x5d\x5d\x10\x01\x00\x00\x00\x1b\x00\
+  //     //                         \x00\x00\x9b\x01\x00\x00\x00\x00\x72\x0e\x00\x00\x00\x00\x00\x00";
+
+  //     // const EVENT2 : &[u8] = b"\x00\xf2\x43\x5d\x5d\x0f\x01\x00\x00\x00\x77\x00\x00\x00\x00\x00\
+  //     //                         \x00\x00\x00\x00\x04\x00\x35\x2e\x37\x2e\x31\x38\x2d\x31\x36\x2d\
+  //     //                         \x6c\x6f\x67\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+  //     //                         \x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+  //     //                         \x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x13\x38\x0d\x00\
+  //     //                         \x08\x00\x12\x00\x04\x04\x04\x04\x12\x00\x00\x5f\x00\x04\x1a\x08\
+  //     //                         \x00\x00\x00\x08\x08\x08\x02\x00\x00\x00\x0a\x0a\x0a\x2a\x2a\x00";
+
+  //     // parse_event2(EVENT).unwrap();
+  // }
+  // Network packets, the events of a binlog file are the same minus the leading OK byte.
+  const FORMAT_DESCRIPTION_EVENT: &[u8] =
+    include_bytes!("../../fuzz/corpus/binlog_event/format_description_event");
+  const QUERY_EVENT: &[u8] = include_bytes!("../../fuzz/corpus/binlog_event/query_event");
+
+  fn binlog_file(events: &[Vec<u8>]) -> Vec<u8> {
+    let mut file = b"\xfebin".to_vec();
+    for event in events {
+      file.extend_from_slice(event);
+    }
+    file
+  }
+
+  fn with_checksum(packet: &[u8]) -> Vec<u8> {
+    let mut event = packet[1..].to_vec();
+    let event_size = u32::from_le_bytes([event[9], event[10], event[11], event[12]]) + 4;
+    event[9..13].copy_from_slice(&event_size.to_le_bytes());
+    event.extend_from_slice(&[0xde, 0xad, 0xbe, 0xef]);
+    event
+  }
+
+  #[test]
+  fn parses_binlog_file() {
+    let file = binlog_file(&[
+      FORMAT_DESCRIPTION_EVENT[1..].to_vec(),
+      QUERY_EVENT[1..].to_vec(),
+    ]);
+    let events: Vec<BinlogEvent> = BinlogFile::parse(file)
+      .unwrap()
+      .map(|packet| packet.unwrap().into_binlog_event().unwrap())
+      .collect();
+
+    match events.as_slice() {
+      [BinlogEvent::Format(format), BinlogEvent::Query(query)] => {
+        assert!(!format.has_checksums());
+        assert_eq!("BEGIN", query.query_str());
+      }
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+  }
+
+  #[test]
+  fn strips_binlog_file_checksums() {
+    // The checksum algorithm sits right before the checksum of the FORMAT_DESCRIPTION_EVENT.
+    let mut format = FORMAT_DESCRIPTION_EVENT[1..].to_vec();
+    let alg = format.len() - 5;
+    format[alg] = 1;
+
+    let file = binlog_file(&[format, with_checksum(QUERY_EVENT)]);
+    let mut events = BinlogFile::parse(file).unwrap();
+    match events.nth(1).unwrap().unwrap().into_binlog_event().unwrap() {
+      BinlogEvent::Query(query) => assert_eq!("BEGIN", query.query_str()),
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+    assert!(events.next().is_none());
+  }
+
+  #[test]
+  fn stops_at_truncated_binlog_file() {
+    assert!(BinlogFile::parse(&b"\xfebi"[..]).is_err());
+    assert!(BinlogFile::parse(&b"\xfe\x00\x00\x00"[..]).is_err());
+
+    let mut file = binlog_file(&[QUERY_EVENT[1..].to_vec(), QUERY_EVENT[1..].to_vec()]);
+    file.truncate(file.len() - 1);
+    let mut events = BinlogFile::parse(file).unwrap();
+    assert!(events.next().unwrap().is_ok());
+    assert!(events.next().unwrap().is_err());
+    assert!(events.next().is_none());
+  }
+
+  #[test]
+  fn parses_transaction_payload() {
+    // XID_EVENT, without a checksum.
+    let xid_event = b"\x00\x00\x00\x00\x10\x01\x00\x00\x00\x1b\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+                      \x2a\x00\x00\x00\x00\x00\x00\x00";
+    // Compression NONE (255), uncompressed size and size of 27 bytes, end mark.
+    let mut payload_event = b"\x00\x00\x00\x00\x00\x28\x01\x00\x00\x00\x00\x00\x00\x00\x9a\x02\x00\
+                              \x00\x00\x00\x02\x03\xfc\xff\x00\x03\x01\x1b\x01\x01\x1b\x00"
+      .to_vec();
+    payload_event.extend_from_slice(xid_event);
+
+    let packet = BinlogEventPacket::parse(payload_event).unwrap();
+    let header = packet.header();
+    let payload = match packet.into_binlog_event().unwrap() {
+      BinlogEvent::TransactionPayload(payload) => payload,
+      unexpected => panic!("unexpected {:?}", unexpected),
+    };
+    assert_eq!(PayloadCompression::None, payload.compression());
+    assert_eq!(27, payload.uncompressed_size());
+
+    let packets: Vec<_> = TransactionPayloadEvent::events(payload.payload().to_vec(), &header)
+      .map(Result::unwrap)
+      .collect();
+    assert_eq!(1, packets.len());
+    assert_eq!(666, packets[0].header().log_pos());
+    match packets
+      .into_iter()
+      .next()
+      .unwrap()
+      .into_binlog_event()
+      .unwrap()
+    {
+      BinlogEvent::Xid(xid) => assert_eq!(42, xid.xid()),
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+  }
+
+  fn encrypted_binlog_header(key_id: &str) -> Vec<u8> {
+    let mut header = b"\xfdbin\x01\x01".to_vec();
+    header.push(key_id.len() as u8);
+    header.extend_from_slice(key_id.as_bytes());
+    header.push(0x02);
+    header.extend_from_slice(&[0xaa; 32]);
+    header.push(0x03);
+    header.extend_from_slice(&[0xbb; 16]);
+    header.resize(ENCRYPTED_BINLOG_HEADER_LEN, 0);
+    header
+  }
+
+  #[test]
+  fn rejects_encrypted_binlog_file() {
+    let key_id = "MySQLReplicationKey_7ad5a1f8-a36b-11eb-8b21-0242ac110002_1";
+    let header = EncryptedBinlogHeader::parse(encrypted_binlog_header(key_id)).unwrap();
+    assert_eq!(key_id, header.key_id_str());
+    assert_eq!(&[0xaa; 32][..], header.encrypted_password());
+    assert_eq!(&[0xbb; 16][..], header.iv());
+
+    let err = BinlogFile::parse(encrypted_binlog_header(key_id))
+      .err()
+      .unwrap();
+    assert!(err.to_string().contains(key_id));
+    assert!(BinlogFile::parse(&b"\xfdbin\x01"[..]).is_err());
+  }
+
+  #[test]
+  fn stops_at_start_encryption() {
+    // START_ENCRYPTION_EVENT, scheme 1, key version 3.
+    let mut start_encryption =
+      b"\x00\x00\x00\x00\xa4\x01\x00\x00\x00\x24\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01\x03\x00\
+        \x00\x00"
+        .to_vec();
+    start_encryption.extend_from_slice(&[0xcc; 12]);
+    let file = binlog_file(&[QUERY_EVENT[1..].to_vec(), start_encryption]);
+
+    let mut events = BinlogFile::parse(file).unwrap();
+    assert!(events.next().unwrap().is_ok());
+    let err = events.next().unwrap().err().unwrap();
+    assert!(err.to_string().contains("version 3"));
+    assert!(events.next().is_none());
+  }
+
+  const ROTATE_EVENT: &[u8] = include_bytes!("../../fuzz/corpus/binlog_event/rotate_event");
+
+  // Sets the flags of an event, as stored in binlog files.
+  fn with_flags(packet: &[u8], flags: u16) -> Vec<u8> {
+    let mut event = packet[1..].to_vec();
+    event[17..19].copy_from_slice(&flags.to_le_bytes());
+    event
+  }
+
+  #[test]
+  fn parses_relay_log_file() {
+    let file = binlog_file(&[
+      with_flags(FORMAT_DESCRIPTION_EVENT, 0x0040),
+      with_flags(ROTATE_EVENT, 0x0020),
+      FORMAT_DESCRIPTION_EVENT[1..].to_vec(),
+      QUERY_EVENT[1..].to_vec(),
+      with_flags(ROTATE_EVENT, 0x0040),
+    ]);
+
+    let mut relay_log = RelayLogFile::parse(file).unwrap();
+    assert_eq!(None, relay_log.source_log_name_str());
+
+    let rotate = relay_log.next().unwrap().unwrap();
+    assert!(rotate.header().is_artificial());
+    let source_log_name = relay_log.source_log_name_str().unwrap().to_string();
+
+    let events: Vec<BinlogEvent> = relay_log
+      .by_ref()
+      .map(|packet| packet.unwrap().into_binlog_event().unwrap())
+      .collect();
+    match events.as_slice() {
+      [BinlogEvent::Format(_), BinlogEvent::Query(query)] => assert_eq!("BEGIN", query.query_str()),
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+    assert_eq!(
+      Some(source_log_name.as_str()),
+      relay_log.source_log_name_str()
+    );
+  }
+}
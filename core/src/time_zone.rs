@@ -0,0 +1,90 @@
+// Fixed UTC offsets, to render TIMESTAMP columns in a time zone other than UTC.
+//
+// Named time zones (e.g. `Europe/Paris`) need the tz database and its daylight saving rules, and
+// are not supported.
+
+use core::fmt;
+
+/// Offset from UTC, between -13:59 and +14:00 like MYSQL's `time_zone` variable.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Default)]
+pub struct UtcOffset {
+  seconds: i32,
+}
+
+impl UtcOffset {
+  pub const UTC: Self = Self { seconds: 0 };
+
+  const MIN_SECONDS: i32 = -(13 * 3_600 + 59 * 60);
+  const MAX_SECONDS: i32 = 14 * 3_600;
+
+  /// Returns `None` when `seconds` is out of range.
+  pub fn from_seconds(seconds: i32) -> Option<Self> {
+    if (Self::MIN_SECONDS..=Self::MAX_SECONDS).contains(&seconds) {
+      Some(Self { seconds })
+    } else {
+      None
+    }
+  }
+
+  /// Parses `+HH:MM`, `-HH:MM`, `UTC` or `Z`. Returns `None` for anything else, including named
+  /// time zones and `SYSTEM`.
+  pub fn parse(s: &str) -> Option<Self> {
+    if s == "Z" || s.eq_ignore_ascii_case("UTC") {
+      return Some(Self::UTC);
+    }
+
+    let (sign, offset) = match s.as_bytes().first()? {
+      b'+' => (1, &s[1..]),
+      b'-' => (-1, &s[1..]),
+      _ => return None,
+    };
+    let (hours, minutes) = offset.split_once(':')?;
+    if hours.is_empty() || hours.len() > 2 || minutes.len() != 2 {
+      return None;
+    }
+    let hours: i32 = hours.parse().ok()?;
+    let minutes: i32 = minutes.parse().ok()?;
+    if minutes >= 60 {
+      return None;
+    }
+    Self::from_seconds(sign * (hours * 3_600 + minutes * 60))
+  }
+
+  pub fn seconds(&self) -> i32 {
+    self.seconds
+  }
+
+  pub fn is_utc(&self) -> bool {
+    self.seconds == 0
+  }
+}
+
+/// Formats as `+HH:MM`.
+impl fmt::Display for UtcOffset {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let sign = if self.seconds < 0 { '-' } else { '+' };
+    let minutes = self.seconds.unsigned_abs() / 60;
+    write!(f, "{}{:02}:{:02}", sign, minutes / 60, minutes % 60)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use alloc::string::ToString;
+
+  #[test]
+  fn parses_offsets() {
+    assert_eq!(Some(UtcOffset::UTC), UtcOffset::parse("UTC"));
+    assert_eq!(Some(UtcOffset::UTC), UtcOffset::parse("+00:00"));
+    assert_eq!(UtcOffset::from_seconds(19_800), UtcOffset::parse("+05:30"));
+    assert_eq!(UtcOffset::from_seconds(-28_800), UtcOffset::parse("-8:00"));
+    assert_eq!(None, UtcOffset::parse("SYSTEM"));
+    assert_eq!(None, UtcOffset::parse("Europe/Paris"));
+    assert_eq!(None, UtcOffset::parse("+14:01"));
+    assert_eq!(None, UtcOffset::parse("+01:60"));
+
+    assert_eq!("-08:00", UtcOffset::parse("-8:00").unwrap().to_string());
+    assert_eq!("+00:00", UtcOffset::UTC.to_string());
+  }
+}
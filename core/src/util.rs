@@ -0,0 +1,38 @@
+use crate::io;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+
+pub fn unexpected_eof<E>(e: E) -> io::Error
+where
+  E: Into<Box<dyn core::error::Error + Send + Sync>>,
+{
+  io::Error::new(io::ErrorKind::UnexpectedEof, e)
+}
+
+pub fn unexpected_err<E>(e: E) -> io::Error
+where
+  E: Into<Box<dyn core::error::Error + Send + Sync>>,
+{
+  io::Error::other(e)
+}
+
+pub fn null_terminated_pos(b: &[u8]) -> usize {
+  b.iter().position(|b| *b == 0x00).unwrap_or(b.len())
+}
+
+pub fn hex(b: &[u8]) -> String {
+  b.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Major, minor and patch versions of a server version, e.g. 5.7.18-16-log => (5, 7, 18).
+pub fn server_version_triple(server_version: &str) -> (u32, u32, u32) {
+  let mut parts = server_version.split('.').map(|part| {
+    let digits = part
+      .find(|c: char| !c.is_ascii_digit())
+      .unwrap_or(part.len());
+    part[..digits].parse().unwrap_or(0)
+  });
+  let mut next = || parts.next().unwrap_or(0);
+  (next(), next(), next())
+}
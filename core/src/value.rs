@@ -0,0 +1,1656 @@
+// Values of the columns, decoded from the text and binary rows of result sets, and from the row
+// images of binlog rows events, whose encoding depends on the column type and its metadata.
+#![cfg_attr(
+  not(test),
+  deny(
+    clippy::unwrap_used,
+    clippy::expect_used,
+    clippy::panic,
+    clippy::todo,
+    clippy::unimplemented
+  )
+)]
+
+use super::buf_ext::{BufExt, BufMutExt};
+use super::io;
+use super::protocol::{CharacterSet, Column, ColumnFlags, ColumnType};
+use super::time_zone::UtcOffset;
+use super::util::{hex, unexpected_err};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use bytes::{Buf, BufMut, Bytes};
+use core::convert::TryFrom;
+use core::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+  Null,
+  Bytes(Vec<u8>),
+  Int(i64),
+  Uint(u64),
+  Float(f64),
+  /// DATE and DATETIME columns, which are wall clock times without a time zone.
+  Date {
+    year: u16,
+    month: u8,
+    day: u8,
+    hour: u8,
+    minute: u8,
+    second: u8,
+    micro: u32,
+  },
+  /// TIMESTAMP columns, as seconds since the epoch in UTC. MYSQL converts them from and to the
+  /// session time zone, see `Value::local_date`. Zero is the zero date, `0000-00-00 00:00:00`.
+  Timestamp {
+    seconds: u32,
+    micro: u32,
+  },
+  Time {
+    negative: bool,
+    days: u32,
+    hours: u8,
+    minutes: u8,
+    seconds: u8,
+    micros: u32,
+  },
+  /// Leading bytes of a value that went over its column size limit, see `ValueLimits`.
+  Truncated {
+    prefix: Vec<u8>,
+    size: usize,
+  },
+  /// SHA-256 digest of a value that went over its column size limit, see `ValueLimits`.
+  Digest {
+    sha256: [u8; 32],
+    size: usize,
+  },
+  /// Pointer to a value that went over its column size limit, and was offloaded to a
+  /// `BlobStore`.
+  Reference {
+    url: String,
+    sha256: [u8; 32],
+    size: usize,
+  },
+  /// Text representation of a sensitive value (see `Value::to_text`), encrypted with AES-256-GCM
+  /// by a data key. The data key is itself encrypted by the key `key_id` of a key provider.
+  Encrypted {
+    key_id: String,
+    wrapped_key: Vec<u8>,
+    nonce: [u8; 12],
+    ciphertext: Vec<u8>,
+  },
+}
+
+// Characters of the strings and bytes of the blobs printed by `Display`, past which they are cut.
+const DISPLAY_LEN: usize = 64;
+
+/// Values of a single row image, in the order of the table columns. Columns that are not part of
+/// the image (e.g. with `binlog_row_image=MINIMAL`) are `None`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+  values: Vec<Option<Value>>,
+}
+
+impl Row {
+  pub fn new(values: Vec<Option<Value>>) -> Self {
+    Self { values }
+  }
+
+  pub fn values(&self) -> &[Option<Value>] {
+    self.values.as_slice()
+  }
+
+  pub fn values_mut(&mut self) -> &mut [Option<Value>] {
+    self.values.as_mut_slice()
+  }
+
+  /// Appends a value after the last column, e.g. one looked up elsewhere.
+  pub fn push(&mut self, value: Option<Value>) {
+    self.values.push(value);
+  }
+
+  pub fn get(&self, index: usize) -> Option<&Value> {
+    self.values.get(index).and_then(Option::as_ref)
+  }
+
+  /// Approximate number of bytes held in memory by the row.
+  pub fn byte_size(&self) -> usize {
+    self.values.iter().flatten().map(Value::byte_size).sum()
+  }
+}
+
+/// Formats as `(1, 'Charlie', NULL)`, with `_` for the columns missing from the image.
+impl fmt::Display for Row {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str("(")?;
+    for (i, value) in self.values.iter().enumerate() {
+      if i > 0 {
+        f.write_str(", ")?;
+      }
+      match value {
+        Some(value) => write!(f, "{}", value)?,
+        None => f.write_str("_")?,
+      }
+    }
+    f.write_str(")")
+  }
+}
+
+/// Formats as a SQL literal: numbers as is, strings and temporal values quoted, and bytes that are
+/// not UTF-8 in hex, e.g. `0x89504e47`. Long strings and bytes are cut, and the values that
+/// replaced the original one are summarized, e.g. `<digest 9f86d081..., 2048 bytes>`.
+impl fmt::Display for Value {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Value::Null => f.write_str("NULL"),
+      Value::Int(v) => write!(f, "{}", v),
+      Value::Uint(v) => write!(f, "{}", v),
+      Value::Float(v) => write!(f, "{}", v),
+      Value::Bytes(bytes) => write_literal(f, bytes, bytes.len()),
+      Value::Truncated { prefix, size } => write_literal(f, prefix, *size),
+      Value::Digest { sha256, size } => {
+        write!(f, "<digest {}..., {} bytes>", hex(&sha256[..4]), size)
+      }
+      Value::Reference { url, size, .. } => write!(f, "<{}, {} bytes>", url, size),
+      Value::Encrypted { key_id, .. } => write!(f, "<encrypted with {}>", key_id),
+      value => {
+        let text = value.to_text().unwrap_or_default();
+        write!(f, "'{}'", String::from_utf8_lossy(&text))
+      }
+    }
+  }
+}
+
+// Quotes `bytes`, the first bytes of a value of `size` bytes, or writes them in hex when they are
+// not UTF-8.
+fn write_literal(f: &mut fmt::Formatter<'_>, bytes: &[u8], size: usize) -> fmt::Result {
+  let text = match core::str::from_utf8(bytes) {
+    Ok(text) => text,
+    // The prefix of a truncated value can end in the middle of a character.
+    Err(err) if err.error_len().is_none() && size > bytes.len() => {
+      core::str::from_utf8(&bytes[..err.valid_up_to()]).unwrap_or_default()
+    }
+    Err(_) => {
+      let len = bytes.len().min(DISPLAY_LEN);
+      write!(f, "0x{}", hex(&bytes[..len]))?;
+      return write_cut(f, len < size, size);
+    }
+  };
+
+  f.write_str("'")?;
+  let mut written = 0;
+  for c in text.chars().take(DISPLAY_LEN) {
+    match c {
+      '\\' => f.write_str("\\\\")?,
+      '\'' => f.write_str("\\'")?,
+      '\n' => f.write_str("\\n")?,
+      c => write!(f, "{}", c)?,
+    }
+    written += c.len_utf8();
+  }
+  f.write_str("'")?;
+  write_cut(f, written < size, size)
+}
+
+fn write_cut(f: &mut fmt::Formatter<'_>, cut: bool, size: usize) -> fmt::Result {
+  if cut {
+    write!(f, "... ({} bytes)", size)?;
+  }
+  Ok(())
+}
+
+impl Value {
+  pub fn parse_from_text(b: &mut impl Buf, column: &Column) -> io::Result<Self> {
+    // TODO: I HAVE NO IDEA HOW TO HANDLE THIS CLEANLY JUST YET...
+    // IF MYSQL ALWAYS RETURNS THE VALUES INTO THE CLIENT FORMATTED COLLATION, THEN WE CAN LAZILY CONVERT IT TO UTF8 AND SUPPORT METHODS TO TRANSCODE FROM ONE FORMAT TO THE OTHER
+    // OTHERWISE, WE HAVE TO DO THE CONVERSION OURSELVES BASED ON THE COLUMN COLLATION.
+    //
+    // ALWAYS ASSUME UTF-8, but in theory, the client could have a completely different charset
+    // We should convert it into a value based off of the column's charset, back into our client charset, and so on.
+    if let Some(0xFB) = b.peek_u8() {
+      b.advance(1);
+      Ok(Value::Null)
+    } else {
+      let bytes = b.safe_get_lenc_bytes()?;
+      Ok(Value::Bytes(bytes))
+    }
+  }
+
+  // https://dev.mysql.com/doc/internals/en/binary-protocol-value.html
+  // https://github.com/mysql/mysql-server/blob/8.0/libbinlogevents/src/binary_log_funcs.cpp
+  //
+  // Parses a single value of a binlog row image, where `meta` is the column metadata found in the
+  // TABLE_MAP_EVENT, and `unsigned` whether the column is UNSIGNED, per the signedness of its
+  // optional metadata (see `TableMapEvent::is_unsigned`). Integers of unsigned columns are returned
+  // as `Value::Uint`, the others as `Value::Int`.
+  pub fn parse_from_binlog(
+    b: &mut impl Buf,
+    column_type: ColumnType,
+    meta: u16,
+    unsigned: bool,
+  ) -> io::Result<Self> {
+    let value = match column_type {
+      ColumnType::MYSQL_TYPE_NULL => Value::Null,
+      ColumnType::MYSQL_TYPE_TINY
+      | ColumnType::MYSQL_TYPE_SHORT
+      | ColumnType::MYSQL_TYPE_INT24
+      | ColumnType::MYSQL_TYPE_LONG
+      | ColumnType::MYSQL_TYPE_LONGLONG => {
+        let len = match column_type {
+          ColumnType::MYSQL_TYPE_TINY => 1,
+          ColumnType::MYSQL_TYPE_SHORT => 2,
+          ColumnType::MYSQL_TYPE_INT24 => 3,
+          ColumnType::MYSQL_TYPE_LONG => 4,
+          _ => 8,
+        };
+        let v = b.safe_get_uint_le(len)?;
+        if unsigned {
+          Value::Uint(v)
+        } else {
+          // Sign extends the `len` bytes.
+          let shift = 64 - 8 * len as u32;
+          Value::Int((v << shift) as i64 >> shift)
+        }
+      }
+      ColumnType::MYSQL_TYPE_FLOAT => {
+        Value::Float(f32::from_bits(b.safe_get_uint_le(4)? as u32) as f64)
+      }
+      ColumnType::MYSQL_TYPE_DOUBLE => Value::Float(f64::from_bits(b.safe_get_uint_le(8)?)),
+      ColumnType::MYSQL_TYPE_YEAR => match b.safe_get_u8()? {
+        0 => Value::Uint(0),
+        year => Value::Uint(1900 + year as u64),
+      },
+      ColumnType::MYSQL_TYPE_DATE => {
+        let v = b.safe_get_uint_le(3)?;
+        Value::Date {
+          year: (v >> 9) as u16,
+          month: ((v >> 5) & 0x0f) as u8,
+          day: (v & 0x1f) as u8,
+          hour: 0,
+          minute: 0,
+          second: 0,
+          micro: 0,
+        }
+      }
+      ColumnType::MYSQL_TYPE_DATETIME => {
+        // YYYYMMDDHHMMSS, as an integer.
+        let v = b.safe_get_uint_le(8)?;
+        let (date, time) = (v / 1_000_000, v % 1_000_000);
+        Value::Date {
+          year: (date / 10_000) as u16,
+          month: (date / 100 % 100) as u8,
+          day: (date % 100) as u8,
+          hour: (time / 10_000) as u8,
+          minute: (time / 100 % 100) as u8,
+          second: (time % 100) as u8,
+          micro: 0,
+        }
+      }
+      ColumnType::MYSQL_TYPE_DATETIME2 => {
+        let v = b.safe_get_uint_be(5)?.wrapping_sub(0x80_0000_0000);
+        let micro = parse_fractional_seconds(b, meta)?;
+        let year_month = (v >> 22) & 0x1_ffff;
+        Value::Date {
+          year: (year_month / 13) as u16,
+          month: (year_month % 13) as u8,
+          day: ((v >> 17) & 0x1f) as u8,
+          hour: ((v >> 12) & 0x1f) as u8,
+          minute: ((v >> 6) & 0x3f) as u8,
+          second: (v & 0x3f) as u8,
+          micro,
+        }
+      }
+      ColumnType::MYSQL_TYPE_TIMESTAMP => Value::Timestamp {
+        seconds: b.safe_get_uint_le(4)? as u32,
+        micro: 0,
+      },
+      ColumnType::MYSQL_TYPE_TIMESTAMP2 => Value::Timestamp {
+        seconds: b.safe_get_uint_be(4)? as u32,
+        micro: parse_fractional_seconds(b, meta)?,
+      },
+      ColumnType::MYSQL_TYPE_TIME => {
+        // HHMMSS, as a signed integer.
+        let v = (b.safe_get_uint_le(3)? as i32) << 8 >> 8;
+        let abs = v.unsigned_abs();
+        let hours = abs / 10_000;
+        Value::Time {
+          negative: v < 0,
+          days: hours / 24,
+          hours: (hours % 24) as u8,
+          minutes: (abs / 100 % 100) as u8,
+          seconds: (abs % 100) as u8,
+          micros: 0,
+        }
+      }
+      ColumnType::MYSQL_TYPE_TIME2 => {
+        // The fractional part is packed with the rest of the value, which is then offset to
+        // sort correctly as a signed value.
+        let frac_len = checked_fractional_seconds_len(meta)?;
+        let offset = 0x80_0000_i64 << (8 * frac_len);
+        let v = b.safe_get_uint_be(3 + frac_len)? as i64 - offset;
+        let abs = v.unsigned_abs();
+        let (int, frac) = (abs >> (8 * frac_len), abs & ((1 << (8 * frac_len)) - 1));
+        let hours = ((int >> 12) & 0x3ff) as u32;
+        Value::Time {
+          negative: v < 0,
+          days: hours / 24,
+          hours: (hours % 24) as u8,
+          minutes: ((int >> 6) & 0x3f) as u8,
+          seconds: (int & 0x3f) as u8,
+          micros: scale_fractional_seconds(frac as u32, frac_len),
+        }
+      }
+      ColumnType::MYSQL_TYPE_NEWDECIMAL => Value::Bytes(parse_decimal(
+        b,
+        (meta >> 8) as usize,
+        (meta & 0xff) as usize,
+      )?),
+      ColumnType::MYSQL_TYPE_VARCHAR | ColumnType::MYSQL_TYPE_VAR_STRING => {
+        let len_size = if meta < 256 { 1 } else { 2 };
+        let len = b.safe_get_uint_le(len_size)? as usize;
+        Value::Bytes(b.safe_get_bytes(len)?)
+      }
+      ColumnType::MYSQL_TYPE_STRING => {
+        let (real_type, max_len) = string_meta(meta)?;
+        match real_type {
+          ColumnType::MYSQL_TYPE_ENUM | ColumnType::MYSQL_TYPE_SET => {
+            Value::Uint(b.safe_get_uint_le(max_len)?)
+          }
+          _ => {
+            let len_size = if max_len < 256 { 1 } else { 2 };
+            let len = b.safe_get_uint_le(len_size)? as usize;
+            Value::Bytes(b.safe_get_bytes(len)?)
+          }
+        }
+      }
+      ColumnType::MYSQL_TYPE_BIT => {
+        let (bits, bytes) = ((meta >> 8) as usize, (meta & 0xff) as usize);
+        let len = bytes + if bits > 0 { 1 } else { 0 };
+        Value::Bytes(b.safe_get_bytes(len)?)
+      }
+      // JSON columns are returned in the MYSQL binary JSON format.
+      ColumnType::MYSQL_TYPE_BLOB
+      | ColumnType::MYSQL_TYPE_TINY_BLOB
+      | ColumnType::MYSQL_TYPE_MEDIUM_BLOB
+      | ColumnType::MYSQL_TYPE_LONG_BLOB
+      | ColumnType::MYSQL_TYPE_GEOMETRY
+      | ColumnType::MYSQL_TYPE_JSON => {
+        let len = b.safe_get_uint_le(meta as usize)? as usize;
+        Value::Bytes(b.safe_get_bytes(len)?)
+      }
+      unsupported => {
+        return Err(unexpected_err(format!(
+          "{:?} is not supported in row images",
+          unsupported
+        )))
+      }
+    };
+
+    Ok(value)
+  }
+
+  /// Writes the value in the format of a binlog row image, the inverse of `parse_from_binlog`.
+  /// Fails when the value can not be represented by the column type.
+  pub fn write_to_binlog(
+    &self,
+    b: &mut impl BufMut,
+    column_type: ColumnType,
+    meta: u16,
+  ) -> io::Result<()> {
+    let mismatch = || {
+      unexpected_err(format!(
+        "{:?} can not be written as {:?}",
+        self, column_type
+      ))
+    };
+
+    match (column_type, self) {
+      (ColumnType::MYSQL_TYPE_NULL, Value::Null) => {}
+      (ColumnType::MYSQL_TYPE_TINY, Value::Int(v)) => b.put_uint_le(*v as u64 & 0xff, 1),
+      (ColumnType::MYSQL_TYPE_SHORT, Value::Int(v)) => b.put_uint_le(*v as u64 & 0xffff, 2),
+      (ColumnType::MYSQL_TYPE_INT24, Value::Int(v)) => b.put_uint_le(*v as u64 & 0xff_ffff, 3),
+      (ColumnType::MYSQL_TYPE_LONG, Value::Int(v)) => b.put_uint_le(*v as u64 & 0xffff_ffff, 4),
+      (ColumnType::MYSQL_TYPE_LONGLONG, Value::Int(v)) => b.put_u64_le(*v as u64),
+      (ColumnType::MYSQL_TYPE_TINY, Value::Uint(v)) if *v <= 0xff => b.put_uint_le(*v, 1),
+      (ColumnType::MYSQL_TYPE_SHORT, Value::Uint(v)) if *v <= 0xffff => b.put_uint_le(*v, 2),
+      (ColumnType::MYSQL_TYPE_INT24, Value::Uint(v)) if *v <= 0xff_ffff => b.put_uint_le(*v, 3),
+      (ColumnType::MYSQL_TYPE_LONG, Value::Uint(v)) if *v <= 0xffff_ffff => b.put_uint_le(*v, 4),
+      (ColumnType::MYSQL_TYPE_LONGLONG, Value::Uint(v)) => b.put_u64_le(*v),
+      (ColumnType::MYSQL_TYPE_FLOAT, Value::Float(v)) => b.put_f32_le(*v as f32),
+      (ColumnType::MYSQL_TYPE_DOUBLE, Value::Float(v)) => b.put_f64_le(*v),
+      (ColumnType::MYSQL_TYPE_YEAR, Value::Uint(0)) => b.put_u8(0),
+      (ColumnType::MYSQL_TYPE_YEAR, Value::Uint(year)) if (1901..=2155).contains(year) => {
+        b.put_u8((year - 1900) as u8)
+      }
+      (
+        ColumnType::MYSQL_TYPE_DATE,
+        Value::Date {
+          year, month, day, ..
+        },
+      ) => {
+        let v = ((*year as u64) << 9) | ((*month as u64) << 5) | *day as u64;
+        b.put_uint_le(v, 3);
+      }
+      (
+        ColumnType::MYSQL_TYPE_DATETIME,
+        Value::Date {
+          year,
+          month,
+          day,
+          hour,
+          minute,
+          second,
+          ..
+        },
+      ) => {
+        let date = *year as u64 * 10_000 + *month as u64 * 100 + *day as u64;
+        let time = *hour as u64 * 10_000 + *minute as u64 * 100 + *second as u64;
+        b.put_u64_le(date * 1_000_000 + time);
+      }
+      (
+        ColumnType::MYSQL_TYPE_DATETIME2,
+        Value::Date {
+          year,
+          month,
+          day,
+          hour,
+          minute,
+          second,
+          micro,
+        },
+      ) => {
+        let year_month = *year as u64 * 13 + *month as u64;
+        let v = (year_month << 22)
+          | ((*day as u64) << 17)
+          | ((*hour as u64) << 12)
+          | ((*minute as u64) << 6)
+          | *second as u64;
+        b.put_uint(v + 0x80_0000_0000, 5);
+        write_fractional_seconds(b, *micro, meta);
+      }
+      (ColumnType::MYSQL_TYPE_TIMESTAMP, Value::Timestamp { seconds, micro: 0 }) => {
+        b.put_u32_le(*seconds)
+      }
+      (ColumnType::MYSQL_TYPE_TIMESTAMP2, Value::Timestamp { seconds, micro }) => {
+        b.put_u32(*seconds);
+        write_fractional_seconds(b, *micro, meta);
+      }
+      (
+        ColumnType::MYSQL_TYPE_TIME,
+        Value::Time {
+          negative,
+          days,
+          hours,
+          minutes,
+          seconds,
+          micros: 0,
+        },
+      ) => {
+        let hours = *days as i64 * 24 + *hours as i64;
+        let v = hours * 10_000 + *minutes as i64 * 100 + *seconds as i64;
+        let v = if *negative { -v } else { v };
+        b.put_uint_le(v as u64 & 0xff_ffff, 3);
+      }
+      (
+        ColumnType::MYSQL_TYPE_TIME2,
+        Value::Time {
+          negative,
+          days,
+          hours,
+          minutes,
+          seconds,
+          micros,
+        },
+      ) => {
+        let frac_len = fractional_seconds_len(meta);
+        let hours = *days as i64 * 24 + *hours as i64;
+        let int = (hours << 12) | ((*minutes as i64) << 6) | *seconds as i64;
+        let frac = unscale_fractional_seconds(*micros, frac_len) as i64;
+        let v = (int << (8 * frac_len)) | frac;
+        let v = if *negative { -v } else { v };
+        let offset = 0x80_0000_i64 << (8 * frac_len);
+        b.put_uint((v + offset) as u64, 3 + frac_len);
+      }
+      (ColumnType::MYSQL_TYPE_NEWDECIMAL, Value::Bytes(text)) => {
+        let text = core::str::from_utf8(text).map_err(|_| mismatch())?;
+        let bytes =
+          write_decimal(text, (meta >> 8) as usize, (meta & 0xff) as usize).ok_or_else(mismatch)?;
+        b.put_slice(&bytes);
+      }
+      (ColumnType::MYSQL_TYPE_VARCHAR, Value::Bytes(bytes))
+      | (ColumnType::MYSQL_TYPE_VAR_STRING, Value::Bytes(bytes)) => {
+        let len_size = if meta < 256 { 1 } else { 2 };
+        b.put_uint_le(bytes.len() as u64, len_size);
+        b.put_slice(bytes);
+      }
+      (ColumnType::MYSQL_TYPE_STRING, value) => match (string_meta(meta)?, value) {
+        ((ColumnType::MYSQL_TYPE_ENUM, len), Value::Uint(v))
+        | ((ColumnType::MYSQL_TYPE_SET, len), Value::Uint(v)) => b.put_uint_le(*v, len),
+        ((_, max_len), Value::Bytes(bytes)) => {
+          let len_size = if max_len < 256 { 1 } else { 2 };
+          b.put_uint_le(bytes.len() as u64, len_size);
+          b.put_slice(bytes);
+        }
+        _ => return Err(mismatch()),
+      },
+      (ColumnType::MYSQL_TYPE_BIT, Value::Bytes(bytes)) => b.put_slice(bytes),
+      (ColumnType::MYSQL_TYPE_BLOB, Value::Bytes(bytes))
+      | (ColumnType::MYSQL_TYPE_TINY_BLOB, Value::Bytes(bytes))
+      | (ColumnType::MYSQL_TYPE_MEDIUM_BLOB, Value::Bytes(bytes))
+      | (ColumnType::MYSQL_TYPE_LONG_BLOB, Value::Bytes(bytes))
+      | (ColumnType::MYSQL_TYPE_GEOMETRY, Value::Bytes(bytes))
+      | (ColumnType::MYSQL_TYPE_JSON, Value::Bytes(bytes)) => {
+        b.put_uint_le(bytes.len() as u64, meta as usize);
+        b.put_slice(bytes);
+      }
+      _ => return Err(mismatch()),
+    }
+
+    Ok(())
+  }
+
+  /// Type the value is sent as in the binary protocol, e.g. as a parameter of COM_STMT_EXECUTE,
+  /// and whether it is unsigned. `None` for the values that replaced the original value (see
+  /// `Value::Truncated`).
+  pub fn binary_type(&self) -> Option<(ColumnType, bool)> {
+    let binary_type = match self {
+      Value::Null => (ColumnType::MYSQL_TYPE_NULL, false),
+      Value::Bytes(_) => (ColumnType::MYSQL_TYPE_VAR_STRING, false),
+      Value::Int(_) => (ColumnType::MYSQL_TYPE_LONGLONG, false),
+      Value::Uint(_) => (ColumnType::MYSQL_TYPE_LONGLONG, true),
+      Value::Float(_) => (ColumnType::MYSQL_TYPE_DOUBLE, false),
+      Value::Date { .. } => (ColumnType::MYSQL_TYPE_DATETIME, false),
+      Value::Timestamp { .. } => (ColumnType::MYSQL_TYPE_TIMESTAMP, false),
+      Value::Time { .. } => (ColumnType::MYSQL_TYPE_TIME, false),
+      Value::Truncated { .. }
+      | Value::Digest { .. }
+      | Value::Reference { .. }
+      | Value::Encrypted { .. } => return None,
+    };
+    Some(binary_type)
+  }
+
+  /// Writes the value in the binary protocol, as typed by `Value::binary_type`. NULL is only set in
+  /// the NULL bitmap, and writes nothing. Dates and times are written in as few bytes as their
+  /// fields allow, microseconds included, and TIMESTAMPs in UTC: the session time zone has to be
+  /// UTC for them to be read back as written.
+  pub fn write_binary(&self, b: &mut impl BufMut) -> io::Result<()> {
+    match self {
+      Value::Null => {}
+      Value::Bytes(bytes) => b.put_lenc_bytes(bytes),
+      Value::Int(v) => b.put_i64_le(*v),
+      Value::Uint(v) => b.put_u64_le(*v),
+      Value::Float(v) => b.put_f64_le(*v),
+      Value::Date {
+        year,
+        month,
+        day,
+        hour,
+        minute,
+        second,
+        micro,
+      } => {
+        let len = match (*hour, *minute, *second, *micro) {
+          _ if *micro > 0 => 11,
+          (0, 0, 0, 0) if (*year, *month, *day) == (0, 0, 0) => 0,
+          (0, 0, 0, 0) => 4,
+          _ => 7,
+        };
+        b.put_u8(len);
+        if len >= 4 {
+          b.put_u16_le(*year);
+          b.put_u8(*month);
+          b.put_u8(*day);
+        }
+        if len >= 7 {
+          b.put_u8(*hour);
+          b.put_u8(*minute);
+          b.put_u8(*second);
+        }
+        if len == 11 {
+          b.put_u32_le(*micro);
+        }
+      }
+      Value::Timestamp { .. } => {
+        if let Some(date) = self.local_date(UtcOffset::UTC) {
+          date.write_binary(b)?;
+        }
+      }
+      Value::Time {
+        negative,
+        days,
+        hours,
+        minutes,
+        seconds,
+        micros,
+      } => {
+        let len = match (*days, *hours, *minutes, *seconds, *micros) {
+          (0, 0, 0, 0, 0) => 0,
+          (_, _, _, _, 0) => 8,
+          _ => 12,
+        };
+        b.put_u8(len);
+        if len >= 8 {
+          b.put_u8(*negative as u8);
+          b.put_u32_le(*days);
+          b.put_u8(*hours);
+          b.put_u8(*minutes);
+          b.put_u8(*seconds);
+        }
+        if len == 12 {
+          b.put_u32_le(*micros);
+        }
+      }
+      Value::Truncated { .. }
+      | Value::Digest { .. }
+      | Value::Reference { .. }
+      | Value::Encrypted { .. } => {
+        return Err(unexpected_err(format!(
+          "{:?} can not be written in the binary protocol",
+          self
+        )))
+      }
+    }
+    Ok(())
+  }
+
+  /// Parses a value of the binary protocol, e.g. of the rows of COM_STMT_EXECUTE, the inverse of
+  /// `write_binary`. DATE, DATETIME and TIMESTAMP values are all wall clock dates, the latter in
+  /// the session time zone.
+  pub fn parse_from_binary(
+    b: &mut impl Buf,
+    column_type: ColumnType,
+    unsigned: bool,
+  ) -> io::Result<Self> {
+    let int = |v: u64, len: u32| {
+      if unsigned {
+        Value::Uint(v)
+      } else {
+        let shift = 64 - 8 * len;
+        Value::Int((v as i64) << shift >> shift)
+      }
+    };
+
+    let value = match column_type {
+      ColumnType::MYSQL_TYPE_NULL => Value::Null,
+      ColumnType::MYSQL_TYPE_TINY => int(b.safe_get_uint_le(1)?, 1),
+      ColumnType::MYSQL_TYPE_SHORT | ColumnType::MYSQL_TYPE_YEAR => int(b.safe_get_uint_le(2)?, 2),
+      ColumnType::MYSQL_TYPE_LONG | ColumnType::MYSQL_TYPE_INT24 => int(b.safe_get_uint_le(4)?, 4),
+      ColumnType::MYSQL_TYPE_LONGLONG => int(b.safe_get_uint_le(8)?, 8),
+      ColumnType::MYSQL_TYPE_FLOAT => {
+        Value::Float(f32::from_bits(b.safe_get_uint_le(4)? as u32) as f64)
+      }
+      ColumnType::MYSQL_TYPE_DOUBLE => Value::Float(f64::from_bits(b.safe_get_uint_le(8)?)),
+      ColumnType::MYSQL_TYPE_DATE
+      | ColumnType::MYSQL_TYPE_DATETIME
+      | ColumnType::MYSQL_TYPE_TIMESTAMP => {
+        let len = b.safe_get_u8()?;
+        if !matches!(len, 0 | 4 | 7 | 11) {
+          return Err(unexpected_err(format!("invalid length {} of a date", len)));
+        }
+        let (mut year, mut month, mut day) = (0, 0, 0);
+        let (mut hour, mut minute, mut second, mut micro) = (0, 0, 0, 0);
+        if len >= 4 {
+          year = b.safe_get_u16_le()?;
+          month = b.safe_get_u8()?;
+          day = b.safe_get_u8()?;
+        }
+        if len >= 7 {
+          hour = b.safe_get_u8()?;
+          minute = b.safe_get_u8()?;
+          second = b.safe_get_u8()?;
+        }
+        if len == 11 {
+          micro = b.safe_get_u32_le()?;
+        }
+        Value::Date {
+          year,
+          month,
+          day,
+          hour,
+          minute,
+          second,
+          micro,
+        }
+      }
+      ColumnType::MYSQL_TYPE_TIME => {
+        let len = b.safe_get_u8()?;
+        if !matches!(len, 0 | 8 | 12) {
+          return Err(unexpected_err(format!("invalid length {} of a time", len)));
+        }
+        let (mut negative, mut days, mut hours, mut minutes, mut seconds) = (false, 0, 0, 0, 0);
+        let mut micros = 0;
+        if len >= 8 {
+          negative = b.safe_get_u8()? == 1;
+          days = b.safe_get_u32_le()?;
+          hours = b.safe_get_u8()?;
+          minutes = b.safe_get_u8()?;
+          seconds = b.safe_get_u8()?;
+        }
+        if len == 12 {
+          micros = b.safe_get_u32_le()?;
+        }
+        Value::Time {
+          negative,
+          days,
+          hours,
+          minutes,
+          seconds,
+          micros,
+        }
+      }
+      ColumnType::MYSQL_TYPE_STRING
+      | ColumnType::MYSQL_TYPE_VAR_STRING
+      | ColumnType::MYSQL_TYPE_VARCHAR
+      | ColumnType::MYSQL_TYPE_BLOB
+      | ColumnType::MYSQL_TYPE_TINY_BLOB
+      | ColumnType::MYSQL_TYPE_MEDIUM_BLOB
+      | ColumnType::MYSQL_TYPE_LONG_BLOB
+      | ColumnType::MYSQL_TYPE_ENUM
+      | ColumnType::MYSQL_TYPE_SET
+      | ColumnType::MYSQL_TYPE_DECIMAL
+      | ColumnType::MYSQL_TYPE_NEWDECIMAL
+      | ColumnType::MYSQL_TYPE_BIT
+      | ColumnType::MYSQL_TYPE_GEOMETRY
+      | ColumnType::MYSQL_TYPE_JSON => Value::Bytes(b.safe_get_lenc_bytes()?),
+      other => {
+        return Err(unexpected_err(format!(
+          "{:?} is not a type of the binary protocol",
+          other
+        )))
+      }
+    };
+    Ok(value)
+  }
+
+  /// Approximate number of bytes held in memory by the value.
+  pub fn byte_size(&self) -> usize {
+    match self {
+      Value::Bytes(bytes) => bytes.len(),
+      Value::Truncated { prefix, .. } => prefix.len(),
+      Value::Reference { url, .. } => url.len() + 32,
+      Value::Encrypted {
+        key_id,
+        wrapped_key,
+        ciphertext,
+        ..
+      } => key_id.len() + wrapped_key.len() + ciphertext.len() + 12,
+      _ => core::mem::size_of::<Self>(),
+    }
+  }
+
+  /// MYSQL text representation of the value, e.g. `2016-05-21 00:00:00` for a DATETIME, with
+  /// TIMESTAMPs in UTC. `None` for NULL and for the values that replaced the original value (see
+  /// `Value::Truncated`).
+  pub fn to_text(&self) -> Option<Vec<u8>> {
+    let text = match self {
+      Value::Bytes(bytes) => return Some(bytes.clone()),
+      Value::Int(v) => v.to_string(),
+      Value::Uint(v) => v.to_string(),
+      Value::Float(v) => v.to_string(),
+      Value::Date {
+        year,
+        month,
+        day,
+        hour,
+        minute,
+        second,
+        micro,
+      } => {
+        let mut date = format!(
+          "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+          year, month, day, hour, minute, second
+        );
+        if *micro > 0 {
+          date.push_str(&format!(".{:06}", micro));
+        }
+        date
+      }
+      Value::Timestamp { .. } => return self.local_date(UtcOffset::UTC)?.to_text(),
+      Value::Time {
+        negative,
+        days,
+        hours,
+        minutes,
+        seconds,
+        micros,
+      } => {
+        let hours = u64::from(*days) * 24 + u64::from(*hours);
+        let mut time = format!(
+          "{}{:02}:{:02}:{:02}",
+          if *negative { "-" } else { "" },
+          hours,
+          minutes,
+          seconds
+        );
+        if *micros > 0 {
+          time.push_str(&format!(".{:06}", micros));
+        }
+        time
+      }
+      Value::Null
+      | Value::Truncated { .. }
+      | Value::Digest { .. }
+      | Value::Reference { .. }
+      | Value::Encrypted { .. } => return None,
+    };
+    Some(text.into_bytes())
+  }
+
+  /// Wall clock date of a `Timestamp` in the time zone at `offset`, `None` for other values.
+  pub fn local_date(&self, offset: UtcOffset) -> Option<Value> {
+    let (seconds, micro) = match *self {
+      Value::Timestamp {
+        seconds: 0,
+        micro: 0,
+      } => {
+        return Some(Value::Date {
+          year: 0,
+          month: 0,
+          day: 0,
+          hour: 0,
+          minute: 0,
+          second: 0,
+          micro: 0,
+        })
+      }
+      Value::Timestamp { seconds, micro } => (i64::from(seconds), micro),
+      _ => return None,
+    };
+
+    let seconds = seconds + i64::from(offset.seconds());
+    let (year, month, day) = civil_from_days(seconds.div_euclid(86_400));
+    let time = seconds.rem_euclid(86_400);
+    Some(Value::Date {
+      year,
+      month,
+      day,
+      hour: (time / 3_600) as u8,
+      minute: (time / 60 % 60) as u8,
+      second: (time % 60) as u8,
+      micro,
+    })
+  }
+
+  pub fn as_str(&self) -> Option<&str> {
+    // works because we assume utf-8
+    // this is definitely not the right way to do this kind of conversion.
+    match self {
+      Value::Bytes(bytes) => core::str::from_utf8(bytes.as_slice()).ok(),
+      _ => None,
+    }
+  }
+
+  /// Integer value of `Int`, `Uint` and the text of integers (results of text queries), `None` for
+  /// the other values and on overflow.
+  pub fn as_i64(&self) -> Option<i64> {
+    match *self {
+      Value::Int(v) => Some(v),
+      Value::Uint(v) => i64::try_from(v).ok(),
+      Value::Bytes(ref bytes) => core::str::from_utf8(bytes).ok()?.parse().ok(),
+      _ => None,
+    }
+  }
+
+  /// Same as `as_i64`, for unsigned integers: `None` for negative values. The values of UNSIGNED
+  /// columns are decoded from the binlog as `Uint`s when the table map logs their signedness (see
+  /// `parse_from_binlog`).
+  pub fn as_u64(&self) -> Option<u64> {
+    match *self {
+      Value::Int(v) => u64::try_from(v).ok(),
+      Value::Uint(v) => Some(v),
+      Value::Bytes(ref bytes) => core::str::from_utf8(bytes).ok()?.parse().ok(),
+      _ => None,
+    }
+  }
+
+  pub fn as_u32(&self) -> Option<u32> {
+    u32::try_from(self.as_u64()?).ok()
+  }
+
+  /// Floating point value of `Float`, of the integers that an f64 holds exactly (up to 2^53), and
+  /// of the text of numbers, e.g. DECIMALs, which is rounded to the nearest f64.
+  pub fn as_f64(&self) -> Option<f64> {
+    match *self {
+      Value::Float(v) => Some(v),
+      Value::Int(v) if v.unsigned_abs() <= MAX_EXACT_F64_INTEGER => Some(v as f64),
+      Value::Uint(v) if v <= MAX_EXACT_F64_INTEGER => Some(v as f64),
+      Value::Bytes(ref bytes) => core::str::from_utf8(bytes)
+        .ok()?
+        .parse::<f64>()
+        .ok()
+        .filter(|v| v.is_finite()),
+      _ => None,
+    }
+  }
+}
+
+// Largest integer below which every integer is exactly representable as an f64.
+const MAX_EXACT_F64_INTEGER: u64 = 1 << 53;
+
+// The real type of CHAR, ENUM and SET columns is packed in the metadata, along with the upper
+// bits of the max length.
+fn string_meta(meta: u16) -> io::Result<(ColumnType, usize)> {
+  let (mut real_type, mut max_len) = ((meta >> 8) as u8, (meta & 0xff) as usize);
+  if real_type & 0x30 != 0x30 {
+    max_len |= (((real_type & 0x30) ^ 0x30) as usize) << 4;
+    real_type |= 0x30;
+  }
+  Ok((ColumnType::try_from(real_type)?, max_len))
+}
+
+fn fractional_seconds_len(fsp: u16) -> usize {
+  (fsp as usize).div_ceil(2)
+}
+
+// Same as `fractional_seconds_len`, for metadata that comes from the server.
+fn checked_fractional_seconds_len(fsp: u16) -> io::Result<usize> {
+  if fsp <= 6 {
+    Ok(fractional_seconds_len(fsp))
+  } else {
+    Err(unexpected_err(format!(
+      "fractional seconds precision is at most 6, got {}",
+      fsp
+    )))
+  }
+}
+
+fn scale_fractional_seconds(frac: u32, len: usize) -> u32 {
+  match len {
+    1 => frac * 10_000,
+    2 => frac * 100,
+    _ => frac,
+  }
+}
+
+fn unscale_fractional_seconds(micros: u32, len: usize) -> u32 {
+  match len {
+    0 => 0,
+    1 => micros / 10_000,
+    2 => micros / 100,
+    _ => micros,
+  }
+}
+
+fn write_fractional_seconds(b: &mut impl BufMut, micros: u32, fsp: u16) {
+  let len = fractional_seconds_len(fsp);
+  b.put_uint(unscale_fractional_seconds(micros, len) as u64, len);
+}
+
+fn parse_fractional_seconds(b: &mut impl Buf, fsp: u16) -> io::Result<u32> {
+  let len = checked_fractional_seconds_len(fsp)?;
+  let frac = b.safe_get_uint_be(len)? as u32;
+  Ok(scale_fractional_seconds(frac, len))
+}
+
+// http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i64) -> (u16, u8, u8) {
+  let days = days + 719_468;
+  let era = days.div_euclid(146_097);
+  let doe = days - era * 146_097;
+  let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let day = (doy - (153 * mp + 2) / 5 + 1) as u8;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 } as u8;
+  let year = (yoe + era * 400 + if month <= 2 { 1 } else { 0 }) as u16;
+  (year, month, day)
+}
+
+// https://github.com/mysql/mysql-server/blob/8.0/strings/decimal.cc (decimal2bin)
+//
+// Decimals are stored as groups of 9 digits packed in 4 bytes, with the leftover digits of the
+// integral and fractional parts packed in as few bytes as possible. Returns the decimal as text,
+// like the text protocol does.
+fn parse_decimal(b: &mut impl Buf, precision: usize, scale: usize) -> io::Result<Vec<u8>> {
+  const DIGITS_PER_GROUP: usize = 9;
+  const LEFTOVER_BYTES: [usize; 10] = [0, 1, 1, 2, 2, 3, 3, 4, 4, 4];
+
+  let integral = precision.saturating_sub(scale);
+  let (integral_groups, integral_leftover) =
+    (integral / DIGITS_PER_GROUP, integral % DIGITS_PER_GROUP);
+  let (fractional_groups, fractional_leftover) =
+    (scale / DIGITS_PER_GROUP, scale % DIGITS_PER_GROUP);
+  let len = integral_groups * 4
+    + LEFTOVER_BYTES[integral_leftover]
+    + fractional_groups * 4
+    + LEFTOVER_BYTES[fractional_leftover];
+
+  let mut bytes = b.safe_get_bytes(len)?;
+  if bytes.is_empty() {
+    return Ok(b"0".to_vec());
+  }
+
+  // The sign is stored in the highest bit, and negative values have all their bits inverted.
+  let negative = bytes[0] & 0x80 == 0;
+  bytes[0] ^= 0x80;
+  if negative {
+    bytes.iter_mut().for_each(|byte| *byte = !*byte);
+  }
+
+  let mut digits = bytes.as_slice();
+  let mut group = |len: usize, width: usize| {
+    let value = digits[..len]
+      .iter()
+      .fold(0_u64, |acc, byte| (acc << 8) | *byte as u64);
+    digits = &digits[len..];
+    format!("{:0width$}", value, width = width)
+  };
+
+  let mut integral_digits = String::new();
+  if integral_leftover > 0 {
+    integral_digits.push_str(&group(LEFTOVER_BYTES[integral_leftover], integral_leftover));
+  }
+  for _ in 0..integral_groups {
+    integral_digits.push_str(&group(4, DIGITS_PER_GROUP));
+  }
+
+  let mut fractional_digits = String::new();
+  for _ in 0..fractional_groups {
+    fractional_digits.push_str(&group(4, DIGITS_PER_GROUP));
+  }
+  if fractional_leftover > 0 {
+    fractional_digits.push_str(&group(
+      LEFTOVER_BYTES[fractional_leftover],
+      fractional_leftover,
+    ));
+  }
+
+  let mut text = String::new();
+  if negative {
+    text.push('-');
+  }
+  match integral_digits.trim_start_matches('0') {
+    "" => text.push('0'),
+    digits => text.push_str(digits),
+  }
+  if !fractional_digits.is_empty() {
+    text.push('.');
+    text.push_str(&fractional_digits);
+  }
+
+  Ok(text.into_bytes())
+}
+
+// Inverse of `parse_decimal`, `None` when the text is not a decimal that fits in
+// DECIMAL(precision, scale).
+fn write_decimal(text: &str, precision: usize, scale: usize) -> Option<Vec<u8>> {
+  const DIGITS_PER_GROUP: usize = 9;
+  const LEFTOVER_BYTES: [usize; 10] = [0, 1, 1, 2, 2, 3, 3, 4, 4, 4];
+
+  let (negative, text) = match text.strip_prefix('-') {
+    Some(text) => (true, text),
+    None => (false, text),
+  };
+  let (integral_digits, fractional_digits) = match text.find('.') {
+    Some(pos) => (&text[..pos], &text[pos + 1..]),
+    None => (text, ""),
+  };
+
+  let integral = precision.saturating_sub(scale);
+  let integral_digits = integral_digits.trim_start_matches('0');
+  let all_digits = |digits: &str| digits.bytes().all(|b| b.is_ascii_digit());
+  if !all_digits(integral_digits)
+    || !all_digits(fractional_digits)
+    || integral_digits.len() > integral
+    || fractional_digits.len() > scale
+  {
+    return None;
+  }
+
+  let integral_digits = format!("{:0>width$}", integral_digits, width = integral);
+  let fractional_digits = format!("{:0<width$}", fractional_digits, width = scale);
+
+  let mut bytes = Vec::new();
+  let mut group = |digits: &[u8], len: usize| {
+    let value = digits
+      .iter()
+      .fold(0_u64, |acc, digit| acc * 10 + (digit - b'0') as u64);
+    bytes.extend_from_slice(&value.to_be_bytes()[8 - len..]);
+  };
+
+  let integral_digits = integral_digits.as_bytes();
+  let integral_leftover = integral % DIGITS_PER_GROUP;
+  group(
+    &integral_digits[..integral_leftover],
+    LEFTOVER_BYTES[integral_leftover],
+  );
+  for chunk in integral_digits[integral_leftover..].chunks(DIGITS_PER_GROUP) {
+    group(chunk, 4);
+  }
+
+  let fractional_digits = fractional_digits.as_bytes();
+  let fractional_leftover = scale % DIGITS_PER_GROUP;
+  let fractional_groups_len = scale - fractional_leftover;
+  for chunk in fractional_digits[..fractional_groups_len].chunks(DIGITS_PER_GROUP) {
+    group(chunk, 4);
+  }
+  group(
+    &fractional_digits[fractional_groups_len..],
+    LEFTOVER_BYTES[fractional_leftover],
+  );
+
+  if negative {
+    bytes.iter_mut().for_each(|byte| *byte = !*byte);
+  }
+  if let Some(first) = bytes.first_mut() {
+    *first ^= 0x80;
+  }
+  Some(bytes)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn formats_values() {
+    let text = |s: &str| Value::Bytes(s.as_bytes().to_vec());
+
+    assert_eq!("NULL", Value::Null.to_string());
+    assert_eq!("-1", Value::Int(-1).to_string());
+    assert_eq!("'it\\'s'", text("it's").to_string());
+    assert_eq!(
+      "0x89504e47",
+      Value::Bytes(vec![0x89, 0x50, 0x4e, 0x47]).to_string()
+    );
+    assert_eq!(
+      "'1970-01-01 00:00:01.000001'",
+      Value::Timestamp {
+        seconds: 1,
+        micro: 1
+      }
+      .to_string()
+    );
+    let long = text(&"a".repeat(100)).to_string();
+    assert!(long.ends_with("aaa'... (100 bytes)"));
+    assert_eq!(DISPLAY_LEN + 2, long.find("...").unwrap());
+    // Cut in the middle of the `é`.
+    let truncated = Value::Truncated {
+      prefix: vec![b'a', 0xc3],
+      size: 10,
+    };
+    assert_eq!("'a'... (10 bytes)", truncated.to_string());
+    let digest = Value::Digest {
+      sha256: [0xab; 32],
+      size: 2048,
+    };
+    assert_eq!("<digest abababab..., 2048 bytes>", digest.to_string());
+
+    let row = Row::new(vec![Some(Value::Uint(1)), None, Some(Value::Null)]);
+    assert_eq!("(1, _, NULL)", row.to_string());
+  }
+
+  #[test]
+  fn converts_integers_at_their_boundaries() {
+    let text = |s: &str| Value::Bytes(s.as_bytes().to_vec());
+
+    assert_eq!(Some(i64::MIN), Value::Int(i64::MIN).as_i64());
+    assert_eq!(None, Value::Int(i64::MIN).as_u64());
+    assert_eq!(None, Value::Int(-1).as_u64());
+    assert_eq!(Some(0), Value::Int(0).as_u64());
+    assert_eq!(Some(i64::MAX as u64), Value::Int(i64::MAX).as_u64());
+    assert_eq!(Some(i64::MAX), Value::Uint(i64::MAX as u64).as_i64());
+    assert_eq!(None, Value::Uint(i64::MAX as u64 + 1).as_i64());
+    assert_eq!(None, Value::Uint(u64::MAX).as_i64());
+    assert_eq!(Some(u64::MAX), Value::Uint(u64::MAX).as_u64());
+
+    assert_eq!(Some(u32::MAX), Value::Uint(u32::MAX as u64).as_u32());
+    assert_eq!(None, Value::Uint(u32::MAX as u64 + 1).as_u32());
+    assert_eq!(None, Value::Int(-1).as_u32());
+
+    assert_eq!(Some(u64::MAX), text("18446744073709551615").as_u64());
+    assert_eq!(None, text("18446744073709551616").as_u64());
+    assert_eq!(None, text("18446744073709551615").as_i64());
+    assert_eq!(Some(i64::MIN), text("-9223372036854775808").as_i64());
+    assert_eq!(None, text("-9223372036854775809").as_i64());
+    assert_eq!(None, text("-1").as_u64());
+    assert_eq!(None, text("1.5").as_i64());
+    assert_eq!(None, Value::Bytes(vec![0xff]).as_u64());
+    assert_eq!(None, Value::Null.as_i64());
+    assert_eq!(None, Value::Float(1.0).as_i64());
+
+    let exact = 1_i64 << 53;
+    assert_eq!(Some(exact as f64), Value::Int(exact).as_f64());
+    assert_eq!(Some(-exact as f64), Value::Int(-exact).as_f64());
+    assert_eq!(None, Value::Int(exact + 1).as_f64());
+    assert_eq!(None, Value::Int(i64::MIN).as_f64());
+    assert_eq!(Some(exact as f64), Value::Uint(exact as u64).as_f64());
+    assert_eq!(None, Value::Uint(u64::MAX).as_f64());
+    assert_eq!(Some(1234.5678), text("1234.5678").as_f64());
+    assert_eq!(None, text("1e400").as_f64());
+    assert_eq!(Some(f64::MAX), Value::Float(f64::MAX).as_f64());
+  }
+
+  #[test]
+  fn parses_binlog_decimals() {
+    // DECIMAL(10, 4)
+    let mut b = &b"\x80\x04\xd2\x16\x2e"[..];
+    let value = Value::parse_from_binlog(&mut b, ColumnType::MYSQL_TYPE_NEWDECIMAL, 0x0a04, false);
+    assert_eq!(Value::Bytes(b"1234.5678".to_vec()), value.unwrap());
+
+    let mut b = &b"\x7f\xfb\x2d\xe9\xd1"[..];
+    let value = Value::parse_from_binlog(&mut b, ColumnType::MYSQL_TYPE_NEWDECIMAL, 0x0a04, false);
+    assert_eq!(Value::Bytes(b"-1234.5678".to_vec()), value.unwrap());
+  }
+
+  #[test]
+  fn parses_binlog_temporals() {
+    let mut b = &b"\x00\x00\x00\x00"[..];
+    let value =
+      Value::parse_from_binlog(&mut b, ColumnType::MYSQL_TYPE_TIMESTAMP, 0, false).unwrap();
+    assert_eq!(
+      Some(date(0, 0, 0, 0, 0, 0, 0)),
+      value.local_date(UtcOffset::UTC)
+    );
+
+    // 2019-08-21 14:32:05.5 UTC
+    let mut b = &b"\x5d\x5d\x55\xe5\x32"[..];
+    let value =
+      Value::parse_from_binlog(&mut b, ColumnType::MYSQL_TYPE_TIMESTAMP2, 1, false).unwrap();
+    assert_eq!(
+      Value::Timestamp {
+        seconds: 1_566_397_925,
+        micro: 500_000
+      },
+      value
+    );
+    assert_eq!(
+      Some(date(2019, 8, 21, 14, 32, 5, 500_000)),
+      value.local_date(UtcOffset::UTC)
+    );
+    assert_eq!(
+      Some(date(2019, 8, 21, 6, 32, 5, 500_000)),
+      value.local_date(UtcOffset::parse("-08:00").unwrap())
+    );
+
+    // 2019-08-21 14:32:05
+    let mut b = &b"\x99\xa3\xea\xe8\x05"[..];
+    let value = Value::parse_from_binlog(&mut b, ColumnType::MYSQL_TYPE_DATETIME2, 0, false);
+    assert_eq!(date(2019, 8, 21, 14, 32, 5, 0), value.unwrap());
+
+    // -01:00:00
+    let mut b = &b"\x7f\xf0\x00"[..];
+    let value = Value::parse_from_binlog(&mut b, ColumnType::MYSQL_TYPE_TIME2, 0, false);
+    assert_eq!(
+      Value::Time {
+        negative: true,
+        days: 0,
+        hours: 1,
+        minutes: 0,
+        seconds: 0,
+        micros: 0,
+      },
+      value.unwrap()
+    );
+  }
+
+  #[test]
+  fn parses_binlog_integers_by_signedness() {
+    let max = &b"\xff\xff\xff\xff\xff\xff\xff\xff"[..];
+    let value = Value::parse_from_binlog(&mut &max[..], ColumnType::MYSQL_TYPE_LONGLONG, 0, true);
+    let value = value.unwrap();
+    assert_eq!(Value::Uint(u64::MAX), value);
+    assert_eq!(Some(u64::MAX), value.as_u64());
+    let value = Value::parse_from_binlog(&mut &max[..], ColumnType::MYSQL_TYPE_LONGLONG, 0, false);
+    assert_eq!(Value::Int(-1), value.unwrap());
+
+    let value = Value::parse_from_binlog(&mut &max[..3], ColumnType::MYSQL_TYPE_INT24, 0, false);
+    assert_eq!(Value::Int(-1), value.unwrap());
+    let value = Value::parse_from_binlog(&mut &max[..3], ColumnType::MYSQL_TYPE_INT24, 0, true);
+    assert_eq!(Value::Uint(0xff_ffff), value.unwrap());
+
+    let mut written = Vec::new();
+    Value::Uint(u64::MAX)
+      .write_to_binlog(&mut written, ColumnType::MYSQL_TYPE_LONGLONG, 0)
+      .unwrap();
+    assert_eq!(max, &written[..]);
+    assert!(Value::Uint(256)
+      .write_to_binlog(&mut written, ColumnType::MYSQL_TYPE_TINY, 0)
+      .is_err());
+  }
+
+  #[test]
+  fn fails_on_truncated_binlog_value() {
+    let mut b = &b"\x05\x00Riv"[..];
+    assert!(Value::parse_from_binlog(&mut b, ColumnType::MYSQL_TYPE_VARCHAR, 600, false).is_err());
+  }
+
+  fn date(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8, micro: u32) -> Value {
+    Value::Date {
+      year,
+      month,
+      day,
+      hour,
+      minute,
+      second,
+      micro,
+    }
+  }
+
+  #[test]
+  fn normalizes_negative_zero_times() {
+    let negative_zero = Value::Time {
+      negative: true,
+      days: 0,
+      hours: 0,
+      minutes: 0,
+      seconds: 0,
+      micros: 0,
+    };
+    let mut b = Vec::new();
+    negative_zero
+      .write_to_binlog(&mut b, ColumnType::MYSQL_TYPE_TIME2, 0)
+      .unwrap();
+    match Value::parse_from_binlog(&mut b.as_slice(), ColumnType::MYSQL_TYPE_TIME2, 0, false) {
+      Ok(Value::Time { negative, .. }) => assert!(!negative),
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+  }
+
+  #[test]
+  fn writes_binary_temporals() {
+    let binary = |value: Value| {
+      let mut b = Vec::new();
+      value.write_binary(&mut b).unwrap();
+      b
+    };
+    assert_eq!(vec![0], binary(date(0, 0, 0, 0, 0, 0, 0)));
+    assert_eq!(
+      vec![4, 0xe0, 0x07, 5, 21],
+      binary(date(2016, 5, 21, 0, 0, 0, 0))
+    );
+    assert_eq!(
+      vec![7, 0xe0, 0x07, 5, 21, 13, 14, 15],
+      binary(date(2016, 5, 21, 13, 14, 15, 0))
+    );
+    assert_eq!(
+      vec![11, 0xe0, 0x07, 5, 21, 13, 14, 15, 0x7b, 0, 0, 0],
+      binary(date(2016, 5, 21, 13, 14, 15, 123))
+    );
+    // 2016-05-21 13:14:15.5 UTC
+    let timestamp = Value::Timestamp {
+      seconds: 1_463_836_455,
+      micro: 500_000,
+    };
+    assert_eq!(
+      vec![11, 0xe0, 0x07, 5, 21, 13, 14, 15, 0x20, 0xa1, 0x07, 0],
+      binary(timestamp)
+    );
+    let time = Value::Time {
+      negative: true,
+      days: 1,
+      hours: 2,
+      minutes: 3,
+      seconds: 4,
+      micros: 0,
+    };
+    assert_eq!(vec![8, 1, 1, 0, 0, 0, 2, 3, 4], binary(time));
+    let digest = Value::Digest {
+      sha256: [0; 32],
+      size: 1,
+    };
+    assert!(digest.write_binary(&mut Vec::new()).is_err());
+  }
+
+  mod round_trip {
+    use super::super::*;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+
+    fn round_trip(column_type: ColumnType, meta: u16, value: Value) -> Result<(), TestCaseError> {
+      let mut b = Vec::new();
+      value.write_to_binlog(&mut b, column_type, meta).unwrap();
+
+      let mut r = b.as_slice();
+      let unsigned = matches!(value, Value::Uint(_));
+      let parsed = Value::parse_from_binlog(&mut r, column_type, meta, unsigned).unwrap();
+      prop_assert_eq!(value, parsed);
+      prop_assert!(r.is_empty(), "{} trailing bytes", r.len());
+      Ok(())
+    }
+
+    fn binary_round_trip(value: Value) -> Result<(), TestCaseError> {
+      let mut b = Vec::new();
+      value.write_binary(&mut b).unwrap();
+
+      let (column_type, unsigned) = value.binary_type().unwrap();
+      let mut r = b.as_slice();
+      let parsed = Value::parse_from_binary(&mut r, column_type, unsigned).unwrap();
+      prop_assert_eq!(value, parsed);
+      prop_assert!(r.is_empty(), "{} trailing bytes", r.len());
+      Ok(())
+    }
+
+    // Fractional seconds representable with `fsp` digits.
+    fn micros(fsp: u16) -> impl Strategy<Value = u32> {
+      let len = fractional_seconds_len(fsp);
+      let max: u32 = [1, 100, 10_000, 1_000_000][len];
+      (0..max).prop_map(move |frac| scale_fractional_seconds(frac, len))
+    }
+
+    fn digits(len: usize) -> impl Strategy<Value = String> {
+      vec(0..10_u8, len).prop_map(|digits| digits.iter().map(|d| (b'0' + d) as char).collect())
+    }
+
+    fn decimal() -> impl Strategy<Value = (u16, String)> {
+      (1..=65_usize)
+        .prop_flat_map(|precision| (Just(precision), 0..=precision.min(30)))
+        .prop_flat_map(|(precision, scale)| {
+          let integral = precision - scale;
+          (
+            Just((precision, scale)),
+            any::<bool>(),
+            (0..=integral).prop_flat_map(digits),
+            digits(scale),
+          )
+        })
+        .prop_map(|((precision, scale), negative, integral, fractional)| {
+          let mut text = String::new();
+          if negative {
+            text.push('-');
+          }
+          match integral.trim_start_matches('0') {
+            "" => text.push('0'),
+            integral => text.push_str(integral),
+          }
+          if scale > 0 {
+            text.push('.');
+            text.push_str(&fractional);
+          }
+          (((precision as u16) << 8) | scale as u16, text)
+        })
+    }
+
+    proptest! {
+      #[test]
+      fn integers(v in any::<i64>()) {
+        round_trip(ColumnType::MYSQL_TYPE_TINY, 0, Value::Int(v as i8 as i64))?;
+        round_trip(ColumnType::MYSQL_TYPE_SHORT, 0, Value::Int(v as i16 as i64))?;
+        round_trip(ColumnType::MYSQL_TYPE_INT24, 0, Value::Int(((v as i32) << 8 >> 8) as i64))?;
+        round_trip(ColumnType::MYSQL_TYPE_LONG, 0, Value::Int(v as i32 as i64))?;
+        round_trip(ColumnType::MYSQL_TYPE_LONGLONG, 0, Value::Int(v))?;
+      }
+
+      #[test]
+      fn unsigned_integers(u in any::<u64>()) {
+        round_trip(ColumnType::MYSQL_TYPE_TINY, 0, Value::Uint(u & 0xff))?;
+        round_trip(ColumnType::MYSQL_TYPE_SHORT, 0, Value::Uint(u & 0xffff))?;
+        round_trip(ColumnType::MYSQL_TYPE_INT24, 0, Value::Uint(u & 0xff_ffff))?;
+        round_trip(ColumnType::MYSQL_TYPE_LONG, 0, Value::Uint(u & 0xffff_ffff))?;
+        round_trip(ColumnType::MYSQL_TYPE_LONGLONG, 0, Value::Uint(u))?;
+      }
+
+      #[test]
+      fn converts_integers(v in any::<i64>(), u in any::<u64>()) {
+        let text = |s: String| Value::Bytes(s.into_bytes());
+        prop_assert_eq!(Some(v), Value::Int(v).as_i64());
+        prop_assert_eq!(Some(v), text(v.to_string()).as_i64());
+        prop_assert_eq!(u64::try_from(v).ok(), Value::Int(v).as_u64());
+        prop_assert_eq!(Some(u), Value::Uint(u).as_u64());
+        prop_assert_eq!(Some(u), text(u.to_string()).as_u64());
+        prop_assert_eq!(i64::try_from(u).ok(), Value::Uint(u).as_i64());
+        if let Some(f) = Value::Int(v).as_f64() {
+          prop_assert_eq!(v, f as i64);
+        }
+      }
+
+      #[test]
+      fn floats(v in any::<f64>().prop_filter("NaN", |v| !v.is_nan())) {
+        round_trip(ColumnType::MYSQL_TYPE_FLOAT, 0, Value::Float(v as f32 as f64))?;
+        round_trip(ColumnType::MYSQL_TYPE_DOUBLE, 0, Value::Float(v))?;
+      }
+
+      #[test]
+      fn years(year in prop_oneof![Just(0_u64), 1901..=2155_u64]) {
+        round_trip(ColumnType::MYSQL_TYPE_YEAR, 0, Value::Uint(year))?;
+      }
+
+      #[test]
+      fn dates(year in 0..=9999_u16, month in 0..=12_u8, day in 0..=31_u8) {
+        let date = Value::Date { year, month, day, hour: 0, minute: 0, second: 0, micro: 0 };
+        round_trip(ColumnType::MYSQL_TYPE_DATE, 0, date)?;
+      }
+
+      #[test]
+      fn datetimes(
+        (fsp, micro) in (0..=6_u16).prop_flat_map(|fsp| (Just(fsp), micros(fsp))),
+        year in 0..=9999_u16,
+        month in 0..=12_u8,
+        day in 0..=31_u8,
+        hour in 0..24_u8,
+        minute in 0..60_u8,
+        second in 0..60_u8,
+      ) {
+        let datetime = Value::Date { year, month, day, hour, minute, second, micro };
+        round_trip(ColumnType::MYSQL_TYPE_DATETIME2, fsp, datetime)?;
+
+        let datetime = Value::Date { year, month, day, hour, minute, second, micro: 0 };
+        round_trip(ColumnType::MYSQL_TYPE_DATETIME, 0, datetime)?;
+      }
+
+      #[test]
+      fn timestamps(
+        (fsp, micro) in (0..=6_u16).prop_flat_map(|fsp| (Just(fsp), micros(fsp))),
+        seconds in any::<u32>(),
+      ) {
+        round_trip(ColumnType::MYSQL_TYPE_TIMESTAMP2, fsp, Value::Timestamp { seconds, micro })?;
+        round_trip(ColumnType::MYSQL_TYPE_TIMESTAMP, 0, Value::Timestamp { seconds, micro: 0 })?;
+      }
+
+      #[test]
+      fn times(
+        (fsp, micros) in (0..=6_u16).prop_flat_map(|fsp| (Just(fsp), micros(fsp))),
+        negative in any::<bool>(),
+        hours in 0..=838_u32,
+        minutes in 0..60_u8,
+        seconds in 0..60_u8,
+      ) {
+        // Negative zero is normalized, see normalizes_negative_zero_times.
+        let negative = negative && (hours, minutes, seconds, micros) != (0, 0, 0, 0);
+        let time = Value::Time {
+          negative,
+          days: hours / 24,
+          hours: (hours % 24) as u8,
+          minutes,
+          seconds,
+          micros,
+        };
+        round_trip(ColumnType::MYSQL_TYPE_TIME2, fsp, time)?;
+
+        let negative = negative && (hours, minutes, seconds) != (0, 0, 0);
+        let time = Value::Time {
+          negative,
+          days: hours / 24,
+          hours: (hours % 24) as u8,
+          minutes,
+          seconds,
+          micros: 0,
+        };
+        round_trip(ColumnType::MYSQL_TYPE_TIME, 0, time)?;
+      }
+
+      #[test]
+      fn binary_values(
+        v in any::<i64>(),
+        f in any::<f64>().prop_filter("NaN", |v| !v.is_nan()),
+        bytes in vec(any::<u8>(), 0..300),
+      ) {
+        binary_round_trip(Value::Null)?;
+        binary_round_trip(Value::Int(v))?;
+        binary_round_trip(Value::Uint(v as u64))?;
+        binary_round_trip(Value::Float(f))?;
+        binary_round_trip(Value::Bytes(bytes))?;
+      }
+
+      #[test]
+      fn binary_temporals(
+        micro in 0..1_000_000_u32,
+        year in 0..=9999_u16,
+        month in 0..=12_u8,
+        day in 0..=31_u8,
+        hour in prop_oneof![Just(0_u8), 0..24_u8],
+        minute in prop_oneof![Just(0_u8), 0..60_u8],
+        second in prop_oneof![Just(0_u8), 0..60_u8],
+        micro_or_zero in prop_oneof![Just(0_u32), 0..1_000_000_u32],
+        negative in any::<bool>(),
+        hours in 0..=838_u32,
+      ) {
+        binary_round_trip(Value::Date { year, month, day, hour, minute, second, micro })?;
+        let micro = micro_or_zero;
+        binary_round_trip(Value::Date { year, month, day, hour, minute, second, micro })?;
+
+        // Zero is written without a sign.
+        let micros = micro_or_zero;
+        let negative = negative && (hours, minute, second, micros) != (0, 0, 0, 0);
+        let (days, hours) = (hours / 24, (hours % 24) as u8);
+        let (minutes, seconds) = (minute, second);
+        binary_round_trip(Value::Time { negative, days, hours, minutes, seconds, micros })?;
+      }
+
+      #[test]
+      fn decimals((meta, text) in decimal()) {
+        round_trip(ColumnType::MYSQL_TYPE_NEWDECIMAL, meta, Value::Bytes(text.into_bytes()))?;
+      }
+
+      #[test]
+      fn strings(meta in 1..=u16::MAX, bytes in vec(any::<u8>(), 0..300)) {
+        let mut bytes = bytes;
+        bytes.truncate(meta as usize);
+        round_trip(ColumnType::MYSQL_TYPE_VARCHAR, meta, Value::Bytes(bytes.clone()))?;
+
+        bytes.truncate(255);
+        let meta = 0xfe00 | bytes.len() as u16;
+        round_trip(ColumnType::MYSQL_TYPE_STRING, meta, Value::Bytes(bytes))?;
+      }
+
+      #[test]
+      fn enums_and_sets(len in 1..=8_usize, v in any::<u64>()) {
+        let v = if len == 8 { v } else { v & ((1 << (8 * len)) - 1) };
+        round_trip(ColumnType::MYSQL_TYPE_STRING, 0xf800 | len as u16, Value::Uint(v))?;
+        if len <= 2 {
+          round_trip(ColumnType::MYSQL_TYPE_STRING, 0xf700 | len as u16, Value::Uint(v))?;
+        }
+      }
+
+      #[test]
+      fn blobs(meta in 1..=4_u16, bytes in vec(any::<u8>(), 0..300)) {
+        let mut bytes = bytes;
+        if meta == 1 {
+          bytes.truncate(255);
+        }
+        round_trip(ColumnType::MYSQL_TYPE_BLOB, meta, Value::Bytes(bytes.clone()))?;
+        round_trip(ColumnType::MYSQL_TYPE_JSON, meta, Value::Bytes(bytes.clone()))?;
+        round_trip(ColumnType::MYSQL_TYPE_GEOMETRY, meta, Value::Bytes(bytes))?;
+      }
+
+      #[test]
+      fn bits(bits in 0..8_u16, len in 0..=8_usize, bytes in vec(any::<u8>(), 9)) {
+        prop_assume!(bits > 0 || len > 0);
+        let meta = (bits << 8) | len as u16;
+        let len = len + if bits > 0 { 1 } else { 0 };
+        round_trip(ColumnType::MYSQL_TYPE_BIT, meta, Value::Bytes(bytes[..len].to_vec()))?;
+      }
+    }
+  }
+}
@@ -0,0 +1,5 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+// FORMAT_DESCRIPTION_EVENT
+fuzz_target!(|data: &[u8]| tail_mysql::fuzzing::event(0x0f, data));
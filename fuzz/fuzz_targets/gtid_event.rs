@@ -0,0 +1,10 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+// GTID_EVENT and ANONYMOUS_GTID_EVENT, picked by the first byte.
+fuzz_target!(|data: &[u8]| {
+  if let Some((kind, body)) = data.split_first() {
+    let event_type = if kind % 2 == 0 { 0x21 } else { 0x22 };
+    tail_mysql::fuzzing::event(event_type, body);
+  }
+});
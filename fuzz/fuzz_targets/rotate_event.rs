@@ -0,0 +1,5 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+// ROTATE_EVENT
+fuzz_target!(|data: &[u8]| tail_mysql::fuzzing::event(0x04, data));
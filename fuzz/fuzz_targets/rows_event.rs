@@ -0,0 +1,19 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+// WRITE_ROWS_EVENT, UPDATE_ROWS_EVENT and DELETE_ROWS_EVENT (v0 to v2), decoded with a
+// TABLE_MAP_EVENT.
+//
+// Input layout: [rows event type] [table map body len] [table map body] [rows event body]
+const ROWS_EVENT_TYPES: &[u8] = &[0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1e, 0x1f, 0x20];
+
+fuzz_target!(|data: &[u8]| {
+  if data.len() < 2 {
+    return;
+  }
+
+  let event_type = ROWS_EVENT_TYPES[data[0] as usize % ROWS_EVENT_TYPES.len()];
+  let table_map_len = (data[1] as usize).min(data.len() - 2);
+  let (table_map, body) = data[2..].split_at(table_map_len);
+  tail_mysql::fuzzing::rows(table_map, event_type, body);
+});
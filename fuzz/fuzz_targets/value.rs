@@ -0,0 +1,14 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+// A single value of a binlog row image.
+//
+// Input layout: [column type] [meta, 2 bytes little-endian] [value]
+fuzz_target!(|data: &[u8]| {
+  if data.len() < 3 {
+    return;
+  }
+
+  let meta = u16::from_le_bytes([data[1], data[2]]);
+  tail_mysql::fuzzing::value(data[0], meta, &data[3..]);
+});
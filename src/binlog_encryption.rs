@@ -0,0 +1,87 @@
+// Decryption of the binlog files written with binlog_encryption=ON (MYSQL >= 8.0.14), e.g. copied
+// off the server or restored from a backup. Events read through the server are already decrypted.
+//
+// Each file has its own password, stored in the header encrypted with AES-256-CBC by the
+// replication master key, which lives in the server's keyring (see
+// `EncryptedBinlogHeader::key_id_str`). The events are encrypted with AES-256-CTR, keyed by the
+// SHA-512 digest of the password.
+//
+// https://dev.mysql.com/doc/refman/8.0/en/replication-binlog-encryption.html
+
+use super::protocol_binlog::{EncryptedBinlogHeader, ENCRYPTED_BINLOG_HEADER_LEN};
+use super::util::unexpected_err;
+use aes::cipher::block_padding::NoPadding;
+use aes::cipher::{BlockDecryptMut, KeyIvInit, StreamCipher};
+use aes::Aes256;
+use sha2::{Digest, Sha512};
+use std::io;
+
+type Aes256CbcDec = cbc::Decryptor<Aes256>;
+type Aes256Ctr = ctr::Ctr128BE<Aes256>;
+
+/// Decrypts an encrypted binlog file with the replication master key named in its header. The
+/// result can be read with `BinlogFile`.
+pub fn decrypt_binlog_file(file: &[u8], master_key: &[u8]) -> io::Result<Vec<u8>> {
+  let header = EncryptedBinlogHeader::parse(file.to_vec())?;
+  let events = file
+    .get(ENCRYPTED_BINLOG_HEADER_LEN..)
+    .ok_or_else(|| unexpected_err("encrypted binlog file is shorter than its header"))?;
+
+  let mut password = header.encrypted_password().to_vec();
+  Aes256CbcDec::new_from_slices(master_key, header.iv())
+    .map_err(|_| unexpected_err("replication master keys must be 32 bytes long"))?
+    .decrypt_padded_mut::<NoPadding>(&mut password)
+    .map_err(|_| unexpected_err("failed to decrypt the password of the binlog file"))?;
+
+  let mut decrypted = events.to_vec();
+  file_cipher(&password).apply_keystream(&mut decrypted);
+  Ok(decrypted)
+}
+
+// The key and the IV of the events are the first 32 and the next 16 bytes of the digest.
+fn file_cipher(password: &[u8]) -> Aes256Ctr {
+  let digest = Sha512::digest(password);
+  Aes256Ctr::new(digest[..32].into(), digest[32..48].into())
+}
+
+#[cfg(test)]
+mod test {
+  use super::{decrypt_binlog_file, file_cipher, Aes256};
+  use crate::protocol_binlog::{BinlogFile, ENCRYPTED_BINLOG_HEADER_LEN};
+  use aes::cipher::block_padding::NoPadding;
+  use aes::cipher::{BlockEncryptMut, KeyIvInit, StreamCipher};
+
+  #[test]
+  fn decrypts_binlog_file() {
+    let master_key = [0x11; 32];
+    let password = [0x22; 32];
+    let iv = [0x33; 16];
+    let key_id = "MySQLReplicationKey_7ad5a1f8-a36b-11eb-8b21-0242ac110002_1";
+
+    let mut encrypted_password = password;
+    cbc::Encryptor::<Aes256>::new(&master_key.into(), &iv.into())
+      .encrypt_padded_mut::<NoPadding>(&mut encrypted_password, 32)
+      .unwrap();
+
+    let mut file = b"\xfdbin\x01\x01".to_vec();
+    file.push(key_id.len() as u8);
+    file.extend_from_slice(key_id.as_bytes());
+    file.push(0x02);
+    file.extend_from_slice(&encrypted_password);
+    file.push(0x03);
+    file.extend_from_slice(&iv);
+    file.resize(ENCRYPTED_BINLOG_HEADER_LEN, 0);
+
+    let mut events = b"\xfebin".to_vec();
+    file_cipher(&password).apply_keystream(&mut events);
+    file.extend_from_slice(&events);
+
+    let decrypted = decrypt_binlog_file(&file, &master_key).unwrap();
+    assert_eq!(b"\xfebin", decrypted.as_slice());
+    assert_eq!(0, BinlogFile::parse(decrypted).unwrap().count());
+
+    let decrypted = decrypt_binlog_file(&file, &[0x44; 32]).unwrap();
+    assert!(BinlogFile::parse(decrypted).is_err());
+    assert!(decrypt_binlog_file(&file, &[0x11; 16]).is_err());
+  }
+}
@@ -0,0 +1,117 @@
+// Synchronous facade over `conn::Connection`, for scripts and tools that would rather not deal
+// with async. Every connection owns a single threaded tokio runtime, and blocks on it for the
+// duration of each call.
+
+use super::conn::{
+  self, ConnectionOptions, DriverError, QueryResult, QueryResults, ReplicationOptions,
+};
+use super::protocol_binlog::{BinlogEvent, EventHeader};
+use super::runtime;
+use futures::stream::{Stream, StreamExt};
+use std::convert::TryInto;
+use std::pin::Pin;
+use tokio::runtime::{Builder, Runtime};
+
+type BlockingResult<T> = Result<T, DriverError>;
+
+pub struct Connection {
+  runtime: Runtime,
+  inner: conn::Connection<runtime::tokio::Stream>,
+}
+
+impl Connection {
+  /// Establish a connection to MYSQL.
+  pub fn connect<O>(opts: O) -> BlockingResult<Self>
+  where
+    O: TryInto<ConnectionOptions>,
+    DriverError: From<O::Error>,
+  {
+    let mut runtime = Builder::new().basic_scheduler().enable_all().build()?;
+    let inner = runtime.block_on(conn::Connection::connect(opts))?;
+    Ok(Self { runtime, inner })
+  }
+
+  /// Send a text query to MYSQL and returns a result set.
+  pub fn query(&mut self, query: impl AsRef<str>) -> BlockingResult<QueryResults> {
+    let Self { runtime, inner } = self;
+    runtime.block_on(inner.query(query))
+  }
+
+  /// Send a text query to MYSQL and yield only the first result.
+  pub fn pop(&mut self, query: impl AsRef<str>) -> BlockingResult<Option<QueryResult>> {
+    let Self { runtime, inner } = self;
+    runtime.block_on(inner.pop(query))
+  }
+
+  pub fn ping(&mut self) -> BlockingResult<()> {
+    let Self { runtime, inner } = self;
+    runtime.block_on(inner.ping())
+  }
+
+  /// Returns an iterator that yields binlog events, starting from the very beginning of the
+  /// current log.
+  pub fn binlog_stream(
+    &mut self,
+    replication_opts: impl Into<ReplicationOptions>,
+  ) -> BlockingResult<BinlogStream<'_>> {
+    let Self { runtime, inner } = self;
+    let stream = runtime.block_on(inner.binlog_stream(replication_opts))?;
+    let stream = Box::pin(stream);
+    Ok(BinlogStream { runtime, stream })
+  }
+
+  /// Returns an iterator that yields binlog events, starting from a given position and binlog
+  /// file.
+  pub fn resume_binlog_stream(
+    &mut self,
+    replication_opts: impl Into<ReplicationOptions>,
+    file: impl AsRef<str>,
+    position: u32,
+  ) -> BlockingResult<BinlogStream<'_>> {
+    let Self { runtime, inner } = self;
+    let stream = runtime.block_on(inner.resume_binlog_stream(replication_opts, file, position))?;
+    let stream = Box::pin(stream);
+    Ok(BinlogStream { runtime, stream })
+  }
+
+  /// Returns the underlying async connection.
+  pub fn into_inner(self) -> conn::Connection<runtime::tokio::Stream> {
+    self.inner
+  }
+}
+
+type EventStream<'a> = Pin<Box<dyn Stream<Item = BlockingResult<(EventHeader, BinlogEvent)>> + 'a>>;
+
+/// Blocks until the next binlog event is received. Ends when the server has no more events to
+/// send.
+pub struct BinlogStream<'a> {
+  runtime: &'a mut Runtime,
+  stream: EventStream<'a>,
+}
+
+impl<'a> Iterator for BinlogStream<'a> {
+  type Item = BlockingResult<(EventHeader, BinlogEvent)>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let Self { runtime, stream } = self;
+    runtime.block_on(stream.next())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::Connection;
+  use crate::conn::DriverError;
+  use url::Url;
+
+  #[test]
+  fn reports_connection_errors() {
+    // nothing listens on port 1.
+    let url = Url::parse("mysql://root@127.0.0.1:1").unwrap();
+    match Connection::connect(url) {
+      Err(DriverError::Io(_)) => {}
+      Err(unexpected) => panic!("unexpected {:?}", unexpected),
+      Ok(_) => panic!("unexpected connection"),
+    }
+  }
+}
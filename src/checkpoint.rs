@@ -0,0 +1,744 @@
+// Checkpoints of a pipeline: the binlog position it can resume from, saved where a restarted (or
+// rescheduled) tailer finds it again.
+//
+// `Checkpointer` saves the checkpoint of the watermarks of a pipeline to a `CheckpointStore` every
+// few seconds, and once more when the pipeline stops. Saving is at least once: a tailer that
+// crashes between two saves pushes the changes since the last one again. A save that fails is
+// retried, the next one that succeeds catches up, and only consecutive failures stop the pipeline.
+//
+// The stores keep the position as text, `FILE:POSITION`:
+//
+// - `FileCheckpointStore`, a local file, replaced rather than written in place.
+// - `RedisCheckpointStore`, a key of Redis, `redis://[:PASSWORD@]HOST[:PORT]/KEY`.
+// - `ConsulCheckpointStore`, a key of the KV store of Consul, `consul://HOST[:PORT]/KEY?token=T`.
+// - `sink::kafka::KafkaCheckpointStore`, a compacted topic next to the topics of the changes,
+//   `kafka://BROKER[:PORT]/TOPIC?name=NAME`. The Kafka sink publishes the checkpoints in the
+//   same Kafka transactions as the changes, see `KafkaSink::checkpoint_topic`.
+//
+// `open` picks one of them from a URL, e.g. the `checkpoint` option of `main`. The
+// Redis and Consul stores open a connection per operation, checkpoints are too far apart to keep
+// one.
+//
+// A pipeline routing its changes to several destinations, see `watermark::DestinationWatermarks`,
+// saves the checkpoint of each destination to a store of its own, and resumes from the oldest of
+// them, see `resume_from`: the destinations that were ahead get the changes since then again.
+
+use super::retry::RetryPolicy;
+use super::watermark::{BinlogPosition, Watermarks};
+use futures::future::{BoxFuture, Future, FutureExt};
+use futures::select;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use url::Url;
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(5);
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+const REDIS_PORT: u16 = 6379;
+const CONSUL_PORT: u16 = 8500;
+// Characters of the segments of Consul keys sent as is in request paths, the unreserved ones.
+const CONSUL_KEY_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+  .remove(b'-')
+  .remove(b'.')
+  .remove(b'_')
+  .remove(b'~');
+
+pub type CheckpointError = Box<dyn std::error::Error + Send + Sync>;
+
+type CheckpointResult<T> = Result<T, CheckpointError>;
+
+/// Where the checkpoints of a pipeline are kept.
+pub trait CheckpointStore: fmt::Debug + Send + Sync {
+  /// Position saved last, `None` before the first save.
+  fn load(&self) -> BoxFuture<'_, CheckpointResult<Option<BinlogPosition>>>;
+
+  fn save<'a>(&'a self, position: &'a BinlogPosition) -> BoxFuture<'a, CheckpointResult<()>>;
+}
+
+/// Store picked by the scheme of `url`: `file:`, `redis:`, `consul:` or, with the `sinks-kafka`
+/// feature, `kafka:`.
+pub fn open(url: &str) -> Result<Arc<dyn CheckpointStore>, String> {
+  let url = Url::parse(url).map_err(|err| format!("invalid URL: {}", err))?;
+  let key = percent_decode_str(url.path().trim_start_matches('/')).decode_utf8_lossy();
+  let addr = |port: u16| {
+    let host = url.host_str().filter(|host| !host.is_empty());
+    let host = host.ok_or_else(|| "expected a host".to_string())?;
+    Ok::<_, String>(format!("{}:{}", host, url.port().unwrap_or(port)))
+  };
+  if key.is_empty() && url.scheme() != "file" {
+    return Err("expected a key, as the path of the URL".to_string());
+  }
+  match url.scheme() {
+    "file" => match url.to_file_path() {
+      Ok(path) => Ok(Arc::new(FileCheckpointStore::new(path))),
+      Err(()) => Err("expected an absolute path, e.g. file:///var/lib/tail_mysql".to_string()),
+    },
+    "redis" => {
+      let mut store = RedisCheckpointStore::new(addr(REDIS_PORT)?, key.as_ref());
+      if let Some(password) = url.password() {
+        store = store.password(percent_decode_str(password).decode_utf8_lossy());
+      }
+      Ok(Arc::new(store))
+    }
+    "consul" => {
+      let mut store = ConsulCheckpointStore::new(addr(CONSUL_PORT)?, key.as_ref());
+      if let Some((_, token)) = url.query_pairs().find(|(name, _)| name == "token") {
+        if !is_header_value(&token) {
+          return Err("invalid token, expected no line breaks".to_string());
+        }
+        store = store.token(token);
+      }
+      Ok(Arc::new(store))
+    }
+    #[cfg(feature = "sinks-kafka")]
+    "kafka" => {
+      use crate::sink::kafka::{ClientConfig, KafkaCheckpointStore};
+      let broker = url
+        .host_str()
+        .ok_or_else(|| "expected a broker".to_string())?;
+      let broker = match url.port() {
+        Some(port) => format!("{}:{}", broker, port),
+        None => broker.to_string(),
+      };
+      let name = url.query_pairs().find(|(name, _)| name == "name");
+      let (_, name) =
+        name.ok_or_else(|| "expected the name of the pipeline, ?name=".to_string())?;
+      let mut config = ClientConfig::new();
+      config.set("bootstrap.servers", broker);
+      let store = KafkaCheckpointStore::new(&config, key, name).map_err(|err| err.to_string())?;
+      Ok(Arc::new(store))
+    }
+    scheme => Err(format!(
+      "unsupported scheme `{}`, expected file, redis or consul",
+      scheme
+    )),
+  }
+}
+
+/// Oldest position saved to `stores`, the checkpoints of the destinations of a pipeline, which it
+/// resumes from. `None` once any of them has none.
+pub async fn resume_from(
+  stores: &[Arc<dyn CheckpointStore>],
+) -> CheckpointResult<Option<BinlogPosition>> {
+  let mut oldest: Option<BinlogPosition> = None;
+  for store in stores {
+    let position = match store.load().await? {
+      Some(position) => position,
+      None => return Ok(None),
+    };
+    oldest = Some(oldest.map_or(position.clone(), |oldest| oldest.min(position)));
+  }
+  Ok(oldest)
+}
+
+/// Saves the checkpoints of `Watermarks` to a store, see `run`.
+pub struct Checkpointer {
+  store: Arc<dyn CheckpointStore>,
+  watermarks: Watermarks,
+  interval: Duration,
+  retry: RetryPolicy,
+}
+
+impl Checkpointer {
+  /// Saves the checkpoints of `watermarks`, which must be the ones of the pipeline, or of one of
+  /// its destinations.
+  pub fn new(store: Arc<dyn CheckpointStore>, watermarks: Watermarks) -> Self {
+    Self {
+      store,
+      watermarks,
+      interval: DEFAULT_INTERVAL,
+      retry: RetryPolicy::new()
+        .max_attempts(5)
+        .backoff(Duration::from_secs(1), Duration::from_secs(30)),
+    }
+  }
+
+  pub(crate) fn watermarks(&self) -> &Watermarks {
+    &self.watermarks
+  }
+
+  /// How often the checkpoint is saved, when it moved, 5 seconds by default.
+  pub fn interval(mut self, interval: Duration) -> Self {
+    self.interval = interval;
+    self
+  }
+
+  /// How failed saves are retried, 5 attempts spaced by 1s to 30s by default. Retries save the
+  /// checkpoint as of then, which may have moved since the save that failed.
+  pub fn retry(mut self, retry: RetryPolicy) -> Self {
+    self.retry = retry;
+    self
+  }
+
+  /// Saves the checkpoint until `shutdown` resolves, and a last time then, e.g. once the pipeline
+  /// stopped. Fails once a save failed as many times in a row as the retry policy allows, rather
+  /// than stream on without persisting the position, or as soon as the last save fails.
+  pub async fn run(self, shutdown: impl Future<Output = ()>) -> CheckpointResult<()> {
+    let shutdown = shutdown.fuse();
+    futures::pin_mut!(shutdown);
+    let mut saved = None;
+    let mut failures = 0;
+    loop {
+      let delay = match failures {
+        0 => self.interval,
+        failures => self.retry.backoff_after(failures),
+      };
+      let stopping = select! {
+        _ = shutdown => true,
+        _ = futures_timer::Delay::new(delay).fuse() => false,
+      };
+      let checkpoint = self.watermarks.checkpoint();
+      if let Some(position) = checkpoint.filter(|checkpoint| Some(checkpoint) != saved.as_ref()) {
+        match self.store.save(&position).await {
+          Ok(()) => {
+            saved = Some(position);
+            failures = 0;
+          }
+          Err(err) => {
+            failures += 1;
+            if stopping || !self.retry.allows(failures) {
+              return Err(err);
+            }
+            eprintln!(
+              "warning: failed to save checkpoint {}, retrying in {:?}: {}",
+              position,
+              self.retry.backoff_after(failures),
+              err
+            );
+          }
+        }
+      }
+      if stopping {
+        return Ok(());
+      }
+    }
+  }
+}
+
+/// Keeps the checkpoint in a local file.
+#[derive(Debug)]
+pub struct FileCheckpointStore {
+  path: PathBuf,
+}
+
+impl FileCheckpointStore {
+  pub fn new(path: impl Into<PathBuf>) -> Self {
+    Self { path: path.into() }
+  }
+}
+
+impl CheckpointStore for FileCheckpointStore {
+  fn load(&self) -> BoxFuture<'_, CheckpointResult<Option<BinlogPosition>>> {
+    async move {
+      match fs::read_to_string(&self.path) {
+        Ok(position) => Ok(Some(position.trim().parse()?)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+      }
+    }
+    .boxed()
+  }
+
+  // Replaced, so that a crash never leaves a partial position behind.
+  fn save<'a>(&'a self, position: &'a BinlogPosition) -> BoxFuture<'a, CheckpointResult<()>> {
+    async move {
+      let mut temporary = self.path.clone().into_os_string();
+      temporary.push(".tmp");
+      fs::write(&temporary, format!("{}\n", position))?;
+      fs::rename(&temporary, &self.path)?;
+      Ok(())
+    }
+    .boxed()
+  }
+}
+
+/// Keeps the checkpoint in a key of Redis.
+pub struct RedisCheckpointStore {
+  addr: String,
+  key: String,
+  password: Option<String>,
+  timeout: Duration,
+}
+
+impl RedisCheckpointStore {
+  /// Keeps the checkpoint in `key`, of the server listening on `addr` (`HOST:PORT`). Commands carry
+  /// the length of their arguments, any key is sent as is.
+  pub fn new(addr: impl Into<String>, key: impl Into<String>) -> Self {
+    Self {
+      addr: addr.into(),
+      key: key.into(),
+      password: None,
+      timeout: DEFAULT_TIMEOUT,
+    }
+  }
+
+  /// Authenticates with `password` (`requirepass`).
+  pub fn password(mut self, password: impl Into<String>) -> Self {
+    self.password = Some(password.into());
+    self
+  }
+
+  async fn command(&self, args: &[&[u8]]) -> CheckpointResult<Option<Vec<u8>>> {
+    let command = async {
+      let mut stream = TcpStream::connect(self.addr.as_str()).await?;
+      if let Some(ref password) = self.password {
+        redis_request(&mut stream, &[b"AUTH", password.as_bytes()]).await?;
+      }
+      redis_request(&mut stream, args).await
+    };
+    match tokio::time::timeout(self.timeout, command).await {
+      Ok(result) => result,
+      Err(_) => Err(format!("timed out waiting for redis at {}", self.addr).into()),
+    }
+  }
+}
+
+// The password is left out of the logs.
+impl fmt::Debug for RedisCheckpointStore {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("RedisCheckpointStore")
+      .field("addr", &self.addr)
+      .field("key", &self.key)
+      .finish()
+  }
+}
+
+impl CheckpointStore for RedisCheckpointStore {
+  fn load(&self) -> BoxFuture<'_, CheckpointResult<Option<BinlogPosition>>> {
+    async move {
+      match self.command(&[b"GET", self.key.as_bytes()]).await? {
+        Some(position) => Ok(Some(String::from_utf8_lossy(&position).parse()?)),
+        None => Ok(None),
+      }
+    }
+    .boxed()
+  }
+
+  fn save<'a>(&'a self, position: &'a BinlogPosition) -> BoxFuture<'a, CheckpointResult<()>> {
+    async move {
+      let position = position.to_string();
+      let args: &[&[u8]] = &[b"SET", self.key.as_bytes(), position.as_bytes()];
+      self.command(args).await.map(drop)
+    }
+    .boxed()
+  }
+}
+
+// Sends a command, and returns the bulk string it replied with, if any.
+async fn redis_request(
+  stream: &mut TcpStream,
+  args: &[&[u8]],
+) -> CheckpointResult<Option<Vec<u8>>> {
+  stream.write_all(&redis_command(args)).await?;
+  let mut reply = Vec::new();
+  let mut buf = [0; 512];
+  loop {
+    if let Some(parsed) = parse_redis_reply(&reply) {
+      return parsed.map_err(Into::into);
+    }
+    let read = stream.read(&mut buf).await?;
+    if read == 0 {
+      return Err("connection closed by redis".into());
+    }
+    reply.extend_from_slice(&buf[..read]);
+  }
+}
+
+// Array of bulk strings, as RESP clients send commands.
+fn redis_command(args: &[&[u8]]) -> Vec<u8> {
+  let mut command = format!("*{}\r\n", args.len()).into_bytes();
+  for arg in args {
+    command.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+    command.extend_from_slice(arg);
+    command.extend_from_slice(b"\r\n");
+  }
+  command
+}
+
+// Reply to a command, `None` until it is complete.
+fn parse_redis_reply(reply: &[u8]) -> Option<Result<Option<Vec<u8>>, String>> {
+  let end = reply.windows(2).position(|crlf| crlf == b"\r\n")?;
+  if end == 0 {
+    return Some(Err("empty reply from redis".to_string()));
+  }
+  let line = String::from_utf8_lossy(&reply[1..end]);
+  match reply[0] {
+    b'+' | b':' => Some(Ok(None)),
+    b'-' => Some(Err(format!("redis replied {}", line))),
+    b'$' => {
+      let len: i64 = match line.parse() {
+        Ok(len) => len,
+        Err(_) => return Some(Err(format!("invalid bulk string length `{}`", line))),
+      };
+      // The null bulk string, a missing key.
+      if len == -1 {
+        return Some(Ok(None));
+      }
+      let start = end + 2;
+      let stop = usize::try_from(len)
+        .ok()
+        .and_then(|len| start.checked_add(len))
+        .filter(|stop| stop.checked_add(2).is_some());
+      let stop = match stop {
+        Some(stop) => stop,
+        None => return Some(Err(format!("invalid bulk string length `{}`", line))),
+      };
+      if reply.len() < stop + 2 {
+        return None;
+      }
+      Some(Ok(Some(reply[start..stop].to_vec())))
+    }
+    _ => Some(Err(format!("unexpected reply from redis `{}`", line))),
+  }
+}
+
+/// Keeps the checkpoint in a key of the KV store of Consul, over its HTTP API.
+pub struct ConsulCheckpointStore {
+  addr: String,
+  key: String,
+  token: Option<String>,
+  timeout: Duration,
+}
+
+impl ConsulCheckpointStore {
+  /// Keeps the checkpoint in `key`, e.g. `tail_mysql/shop/checkpoint`, of the agent listening on
+  /// `addr` (`HOST:PORT`). The segments of the key are percent-encoded in the path of requests.
+  pub fn new(addr: impl Into<String>, key: impl Into<String>) -> Self {
+    Self {
+      addr: addr.into(),
+      key: key.into(),
+      token: None,
+      timeout: DEFAULT_TIMEOUT,
+    }
+  }
+
+  /// ACL token, sent as `X-Consul-Token`. Requests fail if it has line breaks.
+  pub fn token(mut self, token: impl Into<String>) -> Self {
+    self.token = Some(token.into());
+    self
+  }
+
+  async fn request(
+    &self,
+    method: &str,
+    query: &str,
+    body: &[u8],
+  ) -> CheckpointResult<(u16, Vec<u8>)> {
+    if !is_header_value(&self.addr) {
+      return Err("invalid consul address, expected no line breaks".into());
+    }
+    let mut request = format!(
+      "{} /v1/kv/{}{} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+      method,
+      consul_key_path(&self.key),
+      query,
+      self.addr,
+      body.len()
+    );
+    if let Some(ref token) = self.token {
+      if !is_header_value(token) {
+        return Err(
+          "invalid consul token, expected no line breaks"
+            .to_string()
+            .into(),
+        );
+      }
+      request.push_str(&format!("X-Consul-Token: {}\r\n", token));
+    }
+    request.push_str("\r\n");
+
+    let exchange = async {
+      let mut stream = TcpStream::connect(self.addr.as_str()).await?;
+      stream.write_all(request.as_bytes()).await?;
+      stream.write_all(body).await?;
+      let mut response = Vec::new();
+      stream.read_to_end(&mut response).await?;
+      parse_http_response(&response).map_err(CheckpointError::from)
+    };
+    match tokio::time::timeout(self.timeout, exchange).await {
+      Ok(result) => result,
+      Err(_) => Err(format!("timed out waiting for consul at {}", self.addr).into()),
+    }
+  }
+}
+
+// The token is left out of the logs.
+impl fmt::Debug for ConsulCheckpointStore {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("ConsulCheckpointStore")
+      .field("addr", &self.addr)
+      .field("key", &self.key)
+      .finish()
+  }
+}
+
+impl CheckpointStore for ConsulCheckpointStore {
+  fn load(&self) -> BoxFuture<'_, CheckpointResult<Option<BinlogPosition>>> {
+    async move {
+      match self.request("GET", "?raw", b"").await? {
+        (200, position) => Ok(Some(String::from_utf8_lossy(&position).trim().parse()?)),
+        (404, _) => Ok(None),
+        (status, body) => Err(consul_err(status, &body)),
+      }
+    }
+    .boxed()
+  }
+
+  fn save<'a>(&'a self, position: &'a BinlogPosition) -> BoxFuture<'a, CheckpointResult<()>> {
+    async move {
+      let position = position.to_string();
+      match self.request("PUT", "", position.as_bytes()).await? {
+        (200, _) => Ok(()),
+        (status, body) => Err(consul_err(status, &body)),
+      }
+    }
+    .boxed()
+  }
+}
+
+// `key` in the path of a request, e.g. `tail_mysql/shop%20eu` for `tail_mysql/shop eu`.
+fn consul_key_path(key: &str) -> String {
+  let segments = key
+    .split('/')
+    .map(|segment| utf8_percent_encode(segment, CONSUL_KEY_SEGMENT).to_string());
+  segments.collect::<Vec<_>>().join("/")
+}
+
+// Whether `value` fits in a header without starting another one.
+fn is_header_value(value: &str) -> bool {
+  !value.contains(['\r', '\n'])
+}
+
+fn consul_err(status: u16, body: &[u8]) -> CheckpointError {
+  format!(
+    "consul replied {}: {}",
+    status,
+    String::from_utf8_lossy(body).trim()
+  )
+  .into()
+}
+
+// Status and body of a complete HTTP/1.1 response.
+fn parse_http_response(response: &[u8]) -> Result<(u16, Vec<u8>), String> {
+  let invalid = || "invalid HTTP response".to_string();
+  let headers_end = response
+    .windows(4)
+    .position(|separator| separator == b"\r\n\r\n")
+    .ok_or_else(invalid)?;
+  let headers = String::from_utf8_lossy(&response[..headers_end]);
+  let mut lines = headers.split("\r\n");
+  let status = lines
+    .next()
+    .and_then(|line| line.split(' ').nth(1))
+    .and_then(|status| status.parse().ok())
+    .ok_or_else(invalid)?;
+  let chunked = lines.any(|line| {
+    let line = line.to_ascii_lowercase();
+    line.starts_with("transfer-encoding:") && line.contains("chunked")
+  });
+
+  let mut body = &response[headers_end + 4..];
+  if !chunked {
+    return Ok((status, body.to_vec()));
+  }
+  let mut decoded = Vec::new();
+  loop {
+    let line_end = body
+      .windows(2)
+      .position(|crlf| crlf == b"\r\n")
+      .ok_or_else(invalid)?;
+    let size = String::from_utf8_lossy(&body[..line_end]);
+    let size = size.split(';').next().unwrap_or_default().trim();
+    let size = usize::from_str_radix(size, 16).map_err(|_| invalid())?;
+    if size == 0 {
+      return Ok((status, decoded));
+    }
+    let start = line_end + 2;
+    let stop = start.checked_add(size).ok_or_else(invalid)?;
+    let chunk = body.get(start..stop).ok_or_else(invalid)?;
+    decoded.extend_from_slice(chunk);
+    body = body.get(stop + 2..).ok_or_else(invalid)?;
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use futures::executor::block_on;
+
+  #[test]
+  fn saves_checkpoints_to_files() {
+    let path = std::env::temp_dir().join(format!("tail_mysql_checkpoint_{}", std::process::id()));
+    let store = open(&format!("file://{}", path.display())).unwrap();
+    assert_eq!(None, block_on(store.load()).unwrap());
+
+    let watermarks = Watermarks::new();
+    let position = BinlogPosition::new("mysql-bin.000002", 120);
+    watermarks.seen(position.clone());
+    watermarks.ack(&position);
+    let checkpointer = Checkpointer::new(store.clone(), watermarks);
+    block_on(checkpointer.run(futures::future::ready(()))).unwrap();
+    assert_eq!(Some(position.clone()), block_on(store.load()).unwrap());
+    assert_eq!(Some(position), block_on(resume_from(&[store])).unwrap());
+    fs::remove_file(&path).unwrap();
+  }
+
+  // Fails the first `failures` saves, then saves in memory.
+  #[derive(Debug, Default)]
+  struct FailingStore {
+    failures: std::sync::Mutex<u32>,
+    saved: std::sync::Mutex<Option<BinlogPosition>>,
+  }
+
+  impl FailingStore {
+    fn new(failures: u32) -> Self {
+      let failures = std::sync::Mutex::new(failures);
+      Self {
+        failures,
+        ..Self::default()
+      }
+    }
+  }
+
+  impl CheckpointStore for FailingStore {
+    fn load(&self) -> BoxFuture<'_, CheckpointResult<Option<BinlogPosition>>> {
+      futures::future::ok(self.saved.lock().unwrap().clone()).boxed()
+    }
+
+    fn save<'a>(&'a self, position: &'a BinlogPosition) -> BoxFuture<'a, CheckpointResult<()>> {
+      let mut failures = self.failures.lock().unwrap();
+      if *failures > 0 {
+        *failures -= 1;
+        return futures::future::err("disk full".into()).boxed();
+      }
+      *self.saved.lock().unwrap() = Some(position.clone());
+      futures::future::ok(()).boxed()
+    }
+  }
+
+  fn retry() -> RetryPolicy {
+    RetryPolicy::new()
+      .max_attempts(3)
+      .backoff(Duration::from_millis(1), Duration::from_millis(1))
+  }
+
+  #[test]
+  fn fails_once_a_checkpoint_fails_to_save_in_a_row() {
+    let watermarks = Watermarks::new();
+    let position = BinlogPosition::new("mysql-bin.000002", 120);
+    watermarks.skip(position);
+    let store = Arc::new(FailingStore::new(u32::MAX));
+    let checkpointer = Checkpointer::new(store, watermarks)
+      .interval(Duration::from_millis(1))
+      .retry(retry());
+    // Does not wait for the pipeline to stop.
+    let saved = block_on(checkpointer.run(futures::future::pending()));
+    assert_eq!("disk full", saved.unwrap_err().to_string());
+  }
+
+  #[test]
+  fn retries_checkpoints_that_failed_to_save() {
+    let watermarks = Watermarks::new();
+    let position = BinlogPosition::new("mysql-bin.000002", 120);
+    watermarks.skip(position.clone());
+    let store = Arc::new(FailingStore::new(1));
+    let checkpointer = Checkpointer::new(store.clone(), watermarks)
+      .interval(Duration::from_millis(1))
+      .retry(retry());
+    let stopped = futures_timer::Delay::new(Duration::from_millis(50));
+    block_on(checkpointer.run(stopped)).unwrap();
+    assert_eq!(Some(position), block_on(store.load()).unwrap());
+
+    // Unless the last save fails.
+    let watermarks = Watermarks::new();
+    watermarks.skip(BinlogPosition::new("mysql-bin.000002", 120));
+    let checkpointer = Checkpointer::new(Arc::new(FailingStore::new(1)), watermarks).retry(retry());
+    assert!(block_on(checkpointer.run(futures::future::ready(()))).is_err());
+  }
+
+  #[test]
+  fn opens_stores_by_url() {
+    let store = open("redis://:hunter2@10.0.0.1/tail_mysql:shop").unwrap();
+    let debug = format!("{:?}", store);
+    assert_eq!(
+      "RedisCheckpointStore { addr: \"10.0.0.1:6379\", key: \"tail_mysql:shop\" }",
+      debug
+    );
+    assert!(open("consul://10.0.0.1/tail_mysql/shop?token=secret%0D%0AX-Injected:%201").is_err());
+    let store = open("consul://10.0.0.1/tail_mysql/shop?token=secret").unwrap();
+    assert_eq!(
+      "ConsulCheckpointStore { addr: \"10.0.0.1:8500\", key: \"tail_mysql/shop\" }",
+      format!("{:?}", store)
+    );
+    #[cfg(feature = "sinks-kafka")]
+    assert_eq!(
+      "KafkaCheckpointStore { topic: \"cdc.checkpoints\", name: \"shop\" }",
+      format!(
+        "{:?}",
+        open("kafka://10.0.0.1:9092/cdc.checkpoints?name=shop").unwrap()
+      )
+    );
+    // Keys are percent-decoded from URLs, and encoded again in the requests to Consul.
+    let store = open("redis://10.0.0.1/tail_mysql%20shop").unwrap();
+    assert!(format!("{:?}", store).contains("key: \"tail_mysql shop\""));
+    assert_eq!(
+      "tail_mysql/shop%20eu%3Fraw%0D%0A",
+      consul_key_path("tail_mysql/shop eu?raw\r\n")
+    );
+    assert!(open("redis://10.0.0.1").is_err());
+    assert!(open("etcd://10.0.0.1/shop").is_err());
+  }
+
+  #[test]
+  fn speaks_resp() {
+    assert_eq!(
+      b"*3\r\n$3\r\nSET\r\n$3\r\nkey\r\n$20\r\nmysql-bin.000002:120\r\n".to_vec(),
+      redis_command(&[b"SET", b"key", b"mysql-bin.000002:120"])
+    );
+    assert_eq!(Some(Ok(None)), parse_redis_reply(b"+OK\r\n"));
+    assert_eq!(None, parse_redis_reply(b"$20\r\nmysql-bin"));
+    assert_eq!(
+      Some(Ok(Some(b"mysql-bin.000002:120".to_vec()))),
+      parse_redis_reply(b"$20\r\nmysql-bin.000002:120\r\n")
+    );
+    assert_eq!(Some(Ok(None)), parse_redis_reply(b"$-1\r\n"));
+    assert!(matches!(parse_redis_reply(b"$-5\r\n"), Some(Err(_))));
+    assert!(matches!(
+      parse_redis_reply(b"$18446744073709551615\r\n"),
+      Some(Err(_))
+    ));
+    assert_eq!(
+      Some(Err("redis replied ERR invalid password".to_string())),
+      parse_redis_reply(b"-ERR invalid password\r\n")
+    );
+  }
+
+  #[test]
+  fn reads_http_responses() {
+    let response = b"HTTP/1.1 200 OK\r\nContent-Length: 20\r\n\r\nmysql-bin.000002:120";
+    assert_eq!(
+      Ok((200, b"mysql-bin.000002:120".to_vec())),
+      parse_http_response(response)
+    );
+    let response = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+                     9\r\nmysql-bin\r\nb\r\n.000002:120\r\n0\r\n\r\n";
+    assert_eq!(
+      Ok((200, b"mysql-bin.000002:120".to_vec())),
+      parse_http_response(response)
+    );
+    assert_eq!(
+      Ok((404, Vec::new())),
+      parse_http_response(b"HTTP/1.1 404 Not Found\r\n\r\n")
+    );
+    assert!(parse_http_response(b"HTTP/1.1 200").is_err());
+    let response = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nffffffffffffffff\r\n";
+    assert!(parse_http_response(response).is_err());
+  }
+}
@@ -0,0 +1,425 @@
+// Lightweight classification of the SQL found in QUERY_EVENTs.
+//
+// Without the `sqlparse` feature only the operation is extracted, by looking at the leading
+// keyword. With it, statements are parsed with sqlparser-rs, which also gives us the tables they
+// touch. Anything the parser does not understand falls back to the keyword classification.
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Operation {
+  Insert,
+  Update,
+  Delete,
+  Replace,
+  Ddl,
+  Begin,
+  Commit,
+  Rollback,
+  Other,
+}
+
+impl Operation {
+  /// Returns true when the statement modifies rows.
+  pub fn is_dml(self) -> bool {
+    matches!(
+      self,
+      Operation::Insert | Operation::Update | Operation::Delete | Operation::Replace
+    )
+  }
+
+  /// Returns true when the statement modifies the schema of one or more tables.
+  pub fn is_ddl(self) -> bool {
+    self == Operation::Ddl
+  }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct TableName {
+  schema: Option<String>,
+  table: String,
+}
+
+impl TableName {
+  pub fn new(schema: Option<String>, table: impl Into<String>) -> Self {
+    let table = table.into();
+    Self { schema, table }
+  }
+
+  pub fn schema_str(&self) -> Option<&str> {
+    self.schema.as_deref()
+  }
+
+  pub fn table_str(&self) -> &str {
+    self.table.as_str()
+  }
+
+  pub(crate) fn table_mut(&mut self) -> &mut String {
+    &mut self.table
+  }
+
+  /// Fills in the schema for unqualified table names, e.g. with the default schema of the
+  /// session that executed the query.
+  pub fn or_schema(self, schema: &str) -> Self {
+    let schema = self.schema.or_else(|| Some(schema.to_string()));
+    Self { schema, ..self }
+  }
+}
+
+#[derive(Debug)]
+pub struct Classification {
+  operation: Operation,
+  tables: Vec<TableName>,
+}
+
+impl Classification {
+  pub fn operation(&self) -> Operation {
+    self.operation
+  }
+
+  /// Tables touched by the statement. Always empty when the `sqlparse` feature is disabled, or
+  /// when the statement could not be parsed.
+  pub fn tables(&self) -> &[TableName] {
+    self.tables.as_slice()
+  }
+
+  pub fn into_tables(self) -> Vec<TableName> {
+    self.tables
+  }
+}
+
+/// Classifies a single SQL statement.
+pub fn classify(sql: &str) -> Classification {
+  #[cfg(feature = "sqlparse")]
+  {
+    if let Some(classification) = parser::classify(sql) {
+      return classification;
+    }
+  }
+
+  let operation = classify_keyword(sql);
+  let tables = Vec::new();
+  Classification { operation, tables }
+}
+
+fn classify_keyword(sql: &str) -> Operation {
+  let mut sql = sql.trim_start();
+  while let Some(rest) = sql.strip_prefix("/*") {
+    sql = rest
+      .find("*/")
+      .map(|end| rest[end + 2..].trim_start())
+      .unwrap_or("");
+  }
+
+  let keyword = sql
+    .split(|c: char| !c.is_ascii_alphabetic())
+    .next()
+    .unwrap_or("")
+    .to_ascii_uppercase();
+
+  match keyword.as_str() {
+    "INSERT" => Operation::Insert,
+    "UPDATE" => Operation::Update,
+    "DELETE" => Operation::Delete,
+    "REPLACE" => Operation::Replace,
+    "CREATE" | "ALTER" | "DROP" | "RENAME" | "TRUNCATE" => Operation::Ddl,
+    "BEGIN" | "START" => Operation::Begin,
+    "COMMIT" => Operation::Commit,
+    "ROLLBACK" => Operation::Rollback,
+    _ => Operation::Other,
+  }
+}
+
+/// Tables renamed by `RENAME TABLE a TO b[, c TO d]...` or `ALTER TABLE a ... RENAME [TO] b`,
+/// as (from, to) pairs. `None` when the statement does not rename tables.
+pub fn renames(sql: &str) -> Option<Vec<(TableName, TableName)>> {
+  let mut tokens = Tokens::new(sql);
+  if tokens.keyword("ALTER") {
+    return alter_table_rename(tokens).map(|rename| vec![rename]);
+  }
+  if !tokens.keyword("RENAME") || !tokens.keyword("TABLE") {
+    return None;
+  }
+
+  let mut renames = Vec::new();
+  loop {
+    let from = tokens.table_name()?;
+    if !tokens.keyword("TO") {
+      return None;
+    }
+    let to = tokens.table_name()?;
+    renames.push((from, to));
+    if !tokens.punct(',') {
+      break;
+    }
+  }
+  Some(renames)
+}
+
+fn alter_table_rename(mut tokens: Tokens<'_>) -> Option<(TableName, TableName)> {
+  if !tokens.keyword("TABLE") {
+    return None;
+  }
+  let from = tokens.table_name()?;
+
+  // Looks for a RENAME clause among the alter specifications.
+  while !tokens.is_empty() {
+    if tokens.keyword("RENAME") {
+      if tokens.keyword("COLUMN") || tokens.keyword("INDEX") || tokens.keyword("KEY") {
+        continue;
+      }
+      let _ = tokens.keyword("TO") || tokens.keyword("AS");
+      return Some((from, tokens.table_name()?));
+    }
+    tokens.any();
+  }
+  None
+}
+
+struct Tokens<'a> {
+  sql: &'a str,
+}
+
+impl<'a> Tokens<'a> {
+  fn new(sql: &'a str) -> Self {
+    Self { sql }
+  }
+
+  // Skips whitespace and comments.
+  fn skip(&mut self) {
+    loop {
+      self.sql = self.sql.trim_start();
+      match self.sql.strip_prefix("/*") {
+        Some(rest) => self.sql = rest.find("*/").map_or("", |end| &rest[end + 2..]),
+        None => return,
+      }
+    }
+  }
+
+  fn keyword(&mut self, keyword: &str) -> bool {
+    self.skip();
+    let len = self
+      .sql
+      .find(|c: char| !c.is_ascii_alphabetic())
+      .unwrap_or(self.sql.len());
+    if self.sql[..len].eq_ignore_ascii_case(keyword) {
+      self.sql = &self.sql[len..];
+      true
+    } else {
+      false
+    }
+  }
+
+  fn is_empty(&mut self) -> bool {
+    self.skip();
+    self.sql.is_empty()
+  }
+
+  // Skips a single token: a word, a quoted string or identifier, or a punctuation.
+  fn any(&mut self) {
+    self.skip();
+    let mut chars = self.sql.char_indices();
+    let len = match chars.next() {
+      Some((_, quote)) if quote == '\'' || quote == '"' || quote == '`' => chars
+        .find(|(_, c)| *c == quote)
+        .map_or(self.sql.len(), |(i, _)| i + 1),
+      Some((_, c)) if c.is_ascii_alphanumeric() || c == '_' => self
+        .sql
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '$'))
+        .unwrap_or(self.sql.len()),
+      Some((_, c)) => c.len_utf8(),
+      None => 0,
+    };
+    self.sql = &self.sql[len..];
+  }
+
+  fn punct(&mut self, punct: char) -> bool {
+    self.skip();
+    match self.sql.strip_prefix(punct) {
+      Some(rest) => {
+        self.sql = rest;
+        true
+      }
+      None => false,
+    }
+  }
+
+  fn ident(&mut self) -> Option<String> {
+    self.skip();
+    if let Some(rest) = self.sql.strip_prefix('`') {
+      // Backticks are escaped by doubling them.
+      let mut ident = String::new();
+      let mut chars = rest.char_indices();
+      while let Some((i, c)) = chars.next() {
+        if c == '`' {
+          if rest[i + 1..].starts_with('`') {
+            chars.next();
+          } else {
+            self.sql = &rest[i + 1..];
+            return Some(ident);
+          }
+        }
+        ident.push(c);
+      }
+      return None;
+    }
+
+    let len = self
+      .sql
+      .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '$'))
+      .unwrap_or(self.sql.len());
+    if len == 0 {
+      return None;
+    }
+    let ident = self.sql[..len].to_string();
+    self.sql = &self.sql[len..];
+    Some(ident)
+  }
+
+  fn table_name(&mut self) -> Option<TableName> {
+    let first = self.ident()?;
+    if self.sql.starts_with('.') {
+      self.sql = &self.sql[1..];
+      let table = self.ident()?;
+      Some(TableName::new(Some(first), table))
+    } else {
+      Some(TableName::new(None, first))
+    }
+  }
+}
+
+#[cfg(feature = "sqlparse")]
+mod parser {
+  use super::{Classification, Operation, TableName};
+  use sqlparser::ast::{ObjectName, Statement, TableFactor, TableWithJoins};
+  use sqlparser::dialect::MySqlDialect;
+  use sqlparser::parser::Parser;
+
+  pub fn classify(sql: &str) -> Option<Classification> {
+    let mut statements = Parser::parse_sql(&MySqlDialect {}, sql).ok()?;
+    if statements.len() != 1 {
+      return None;
+    }
+
+    let (operation, tables) = match statements.remove(0) {
+      Statement::Insert { table_name, .. } => (Operation::Insert, vec![table_name]),
+      Statement::Update { table, from, .. } => {
+        let mut tables = relations(&table);
+        tables.extend(from.iter().flat_map(relations));
+        (Operation::Update, tables)
+      }
+      Statement::Delete { tables, from, .. } => {
+        let tables = if tables.is_empty() {
+          from.iter().flat_map(relations).collect()
+        } else {
+          tables
+        };
+        (Operation::Delete, tables)
+      }
+      Statement::CreateTable { name, .. } => (Operation::Ddl, vec![name]),
+      Statement::CreateIndex { table_name, .. } => (Operation::Ddl, vec![table_name]),
+      Statement::AlterTable { name, .. } => (Operation::Ddl, vec![name]),
+      Statement::Drop { names, .. } => (Operation::Ddl, names),
+      Statement::Truncate { table_name, .. } => (Operation::Ddl, vec![table_name]),
+      Statement::StartTransaction { .. } => (Operation::Begin, Vec::new()),
+      Statement::Commit { .. } => (Operation::Commit, Vec::new()),
+      Statement::Rollback { .. } => (Operation::Rollback, Vec::new()),
+      _ => return None,
+    };
+
+    let tables = tables.into_iter().filter_map(table_name).collect();
+    Some(Classification { operation, tables })
+  }
+
+  fn relations(table: &TableWithJoins) -> Vec<ObjectName> {
+    std::iter::once(&table.relation)
+      .chain(table.joins.iter().map(|join| &join.relation))
+      .filter_map(|relation| match relation {
+        TableFactor::Table { name, .. } => Some(name.clone()),
+        _ => None,
+      })
+      .collect()
+  }
+
+  fn table_name(name: ObjectName) -> Option<TableName> {
+    let mut idents = name.0.into_iter().map(|ident| ident.value);
+    match (idents.next(), idents.next(), idents.next()) {
+      (Some(table), None, None) => Some(TableName::new(None, table)),
+      (Some(schema), Some(table), None) => Some(TableName::new(Some(schema), table)),
+      _ => None,
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn classifies_by_leading_keyword() {
+    assert_eq!(
+      Operation::Insert,
+      classify_keyword("INSERT INTO cats VALUES (1)")
+    );
+    assert_eq!(
+      Operation::Update,
+      classify_keyword("  update cats set name = 'River'")
+    );
+    assert_eq!(
+      Operation::Delete,
+      classify_keyword("/* app:web */ DELETE FROM cats")
+    );
+    assert_eq!(
+      Operation::Replace,
+      classify_keyword("REPLACE INTO cats VALUES (1)")
+    );
+    assert_eq!(
+      Operation::Ddl,
+      classify_keyword("ALTER TABLE cats ADD COLUMN age INT")
+    );
+    assert_eq!(
+      Operation::Ddl,
+      classify_keyword("RENAME TABLE cats TO dogs")
+    );
+    assert_eq!(Operation::Begin, classify_keyword("BEGIN"));
+    assert_eq!(Operation::Commit, classify_keyword("COMMIT"));
+    assert_eq!(Operation::Other, classify_keyword("/* unterminated"));
+  }
+
+  #[test]
+  fn extracts_renamed_tables() {
+    let table = |schema: Option<&str>, table: &str| TableName::new(schema.map(Into::into), table);
+    assert_eq!(
+      Some(vec![
+        (table(Some("pets"), "cats"), table(Some("pets"), "felines")),
+        (table(None, "dogs"), table(Some("zoo"), "dog`s")),
+      ]),
+      renames("RENAME TABLE `pets`.`cats` TO pets.felines, dogs TO /* moved */ zoo.`dog``s`")
+    );
+    assert_eq!(
+      Some(vec![(table(None, "cats"), table(None, "felines"))]),
+      renames("alter table cats add column age int comment 'rename to x', rename as felines")
+    );
+    assert_eq!(
+      None,
+      renames("ALTER TABLE cats RENAME COLUMN name TO nickname")
+    );
+    assert_eq!(None, renames("DROP TABLE cats"));
+  }
+
+  #[cfg(feature = "sqlparse")]
+  #[test]
+  fn extracts_touched_tables() {
+    let classification = classify("UPDATE pets.cats SET name = 'River' WHERE id = 1");
+    assert_eq!(Operation::Update, classification.operation());
+    assert_eq!(
+      &[TableName::new(Some("pets".into()), "cats")],
+      classification.tables()
+    );
+
+    let classification = classify("DROP TABLE cats, dogs");
+    assert_eq!(Operation::Ddl, classification.operation());
+    assert_eq!(
+      &[TableName::new(None, "cats"), TableName::new(None, "dogs")],
+      classification.tables()
+    );
+  }
+}
@@ -0,0 +1,216 @@
+// Compressed client/server protocol, negotiated with CLIENT_COMPRESS (zlib) or
+// CLIENT_ZSTD_COMPRESSION_ALGORITHM (zstd, from MYSQL 8.0.18). Once authenticated, packets travel
+// in frames: a 7 bytes header (length of the frame, sequence id, length of the packets before
+// compression or 0 when they are sent as is), then the packets themselves.
+// https://dev.mysql.com/doc/dev/mysql-server/latest/page_protocol_basic_compression.html
+
+use super::protocol::CapabilityFlags;
+use bytes::{Buf, BufMut, BytesMut};
+use std::io;
+use std::str::FromStr;
+
+const FRAME_HEADER_LEN: usize = 7;
+// Frames are as large as packets.
+const MAX_FRAME_LEN: usize = 0xFF_FF_FF;
+// Smaller frames are sent as is, as libmysqlclient does.
+const MIN_COMPRESS_LEN: usize = 50;
+// Level of zstd when none is set, as `protocol_compression_level` of the server.
+pub const DEFAULT_ZSTD_LEVEL: u8 = 3;
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Compression {
+  Zlib,
+  /// zstd, at a level between 1 and 22.
+  Zstd(u8),
+}
+
+impl Compression {
+  pub(crate) fn capability(self) -> CapabilityFlags {
+    match self {
+      Compression::Zlib => CapabilityFlags::CLIENT_COMPRESS,
+      Compression::Zstd(_) => CapabilityFlags::CLIENT_ZSTD_COMPRESSION_ALGORITHM,
+    }
+  }
+
+  #[cfg(feature = "compression")]
+  fn compress(self, data: &[u8]) -> io::Result<Vec<u8>> {
+    use std::io::Write;
+
+    match self {
+      Compression::Zlib => {
+        let mut encoder =
+          flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data)?;
+        encoder.finish()
+      }
+      Compression::Zstd(level) => zstd::bulk::compress(data, i32::from(level)),
+    }
+  }
+
+  #[cfg(feature = "compression")]
+  fn decompress(self, data: &[u8], len: usize) -> io::Result<Vec<u8>> {
+    use std::io::Read;
+
+    let decompressed = match self {
+      Compression::Zlib => {
+        let mut decompressed = Vec::with_capacity(len);
+        // A byte past `len` is enough to tell the frame is invalid.
+        flate2::read::ZlibDecoder::new(data)
+          .take(len as u64 + 1)
+          .read_to_end(&mut decompressed)?;
+        decompressed
+      }
+      Compression::Zstd(_) => zstd::bulk::decompress(data, len)?,
+    };
+    if decompressed.len() != len {
+      return Err(invalid_data(format!(
+        "frame of {} bytes decompressed to {}",
+        len,
+        decompressed.len()
+      )));
+    }
+    Ok(decompressed)
+  }
+
+  #[cfg(not(feature = "compression"))]
+  fn compress(self, _data: &[u8]) -> io::Result<Vec<u8>> {
+    Err(unsupported())
+  }
+
+  #[cfg(not(feature = "compression"))]
+  fn decompress(self, _data: &[u8], _len: usize) -> io::Result<Vec<u8>> {
+    Err(unsupported())
+  }
+
+  /// Frames `packets` (headers included), starting at `sequence_id`, which is left at the sequence
+  /// id of the next frame.
+  pub(crate) fn write_frames(self, packets: &[u8], sequence_id: &mut u8) -> io::Result<BytesMut> {
+    let mut b = BytesMut::with_capacity(packets.len() + FRAME_HEADER_LEN);
+    for chunk in packets.chunks(MAX_FRAME_LEN) {
+      let compressed = if chunk.len() >= MIN_COMPRESS_LEN {
+        Some(self.compress(chunk)?).filter(|compressed| compressed.len() < chunk.len())
+      } else {
+        None
+      };
+      let (payload, uncompressed_len) = match compressed {
+        Some(ref compressed) => (&compressed[..], chunk.len()),
+        None => (chunk, 0),
+      };
+
+      b.put_uint_le(payload.len() as u64, 3);
+      b.put_u8(*sequence_id);
+      b.put_uint_le(uncompressed_len as u64, 3);
+      b.put(payload);
+      *sequence_id = sequence_id.wrapping_add(1);
+    }
+    Ok(b)
+  }
+
+  /// Takes the next frame out of `buffer`, returns its sequence id and packets. `None` until the
+  /// whole frame was buffered.
+  pub(crate) fn read_frame(self, buffer: &mut BytesMut) -> io::Result<Option<(u8, Vec<u8>)>> {
+    if buffer.len() < FRAME_HEADER_LEN {
+      return Ok(None);
+    }
+    let mut header = &buffer[..FRAME_HEADER_LEN];
+    let len = header.get_uint_le(3) as usize;
+    let sequence_id = header.get_u8();
+    let uncompressed_len = header.get_uint_le(3) as usize;
+    if buffer.len() < FRAME_HEADER_LEN + len {
+      return Ok(None);
+    }
+
+    buffer.advance(FRAME_HEADER_LEN);
+    let payload = buffer.split_to(len);
+    let packets = match uncompressed_len {
+      0 => payload.to_vec(),
+      len => self.decompress(&payload, len)?,
+    };
+    Ok(Some((sequence_id, packets)))
+  }
+}
+
+impl Default for Compression {
+  fn default() -> Self {
+    Compression::Zstd(DEFAULT_ZSTD_LEVEL)
+  }
+}
+
+impl FromStr for Compression {
+  type Err = io::Error;
+
+  /// Parses `zlib` or `zstd`, the names of `protocol_compression_algorithms`.
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_ascii_lowercase().as_str() {
+      "zlib" => Ok(Compression::Zlib),
+      "zstd" => Ok(Compression::default()),
+      _ => Err(invalid_data(format!(
+        "unknown compression algorithm `{}`",
+        s
+      ))),
+    }
+  }
+}
+
+fn invalid_data(err: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+#[cfg(not(feature = "compression"))]
+fn unsupported() -> io::Error {
+  io::Error::other("compression without the compression feature")
+}
+
+#[cfg(all(test, feature = "compression"))]
+mod test {
+  use super::*;
+
+  #[test]
+  fn frames_packets() {
+    let small = b"\x01\x00\x00\x00\x0e".to_vec();
+    let mut large = vec![0xfc, 0x00, 0x00, 0x00];
+    large.resize(4 + 0xfc, b'a');
+
+    for compression in &[Compression::Zlib, Compression::Zstd(DEFAULT_ZSTD_LEVEL)] {
+      let mut sequence_id = 0;
+      let mut frames = compression.write_frames(&small, &mut sequence_id).unwrap();
+      frames.extend_from_slice(&compression.write_frames(&large, &mut sequence_id).unwrap());
+      assert_eq!(2, sequence_id);
+      // Sent as is.
+      assert_eq!(&[5, 0, 0, 0, 0, 0, 0], &frames[..FRAME_HEADER_LEN]);
+      assert!(frames.len() < small.len() + large.len());
+
+      let mut partial = BytesMut::from(&frames[..frames.len() - 1]);
+      assert_eq!(
+        Some((0, small.clone())),
+        compression.read_frame(&mut partial).unwrap()
+      );
+      assert_eq!(None, compression.read_frame(&mut partial).unwrap());
+      assert_eq!(
+        Some((1, large.clone())),
+        compression
+          .read_frame(&mut frames.split_off(FRAME_HEADER_LEN + small.len()))
+          .unwrap()
+      );
+    }
+  }
+
+  #[test]
+  fn refuses_frames_expanding_past_their_length() {
+    for compression in &[Compression::Zlib, Compression::Zstd(DEFAULT_ZSTD_LEVEL)] {
+      let compressed = compression.compress(&[b'a'; 4096]).unwrap();
+      assert!(compression.decompress(&compressed, 16).is_err());
+      assert_eq!(
+        4096,
+        compression.decompress(&compressed, 4096).unwrap().len()
+      );
+    }
+  }
+
+  #[test]
+  fn parses_algorithms() {
+    assert_eq!(Compression::Zstd(3), "ZSTD".parse().unwrap());
+    assert_eq!(Compression::Zlib, "zlib".parse().unwrap());
+    assert!("lz4".parse::<Compression>().is_err());
+  }
+}
@@ -0,0 +1,138 @@
+// Keeps the last events read from the binlog, as sent by the server, and writes them to a file when
+// one fails to parse or decode, so that the parser bugs triggered by rare events in production can
+// be reproduced from the dump. Off unless set with `ConnectionOptions::crash_dump`.
+//
+// The dump is a binlog file: the magic number, the last FORMAT_DESCRIPTION_EVENT read, then the
+// last events up to the one that failed, oldest first. `protocol_binlog::BinlogFile` and
+// `main replay` read it back, `fixture::test_case` turns its events into tests of the parsers. The
+// positions in the headers of the events are the ones of the original binlog file.
+
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+const BINLOG_MAGIC: &[u8] = b"\xfebin";
+// Offset of the type code in the header of an event.
+const EVENT_TYPE_OFFSET: usize = 4;
+const FORMAT_DESCRIPTION_EVENT: u8 = 0x0f;
+
+/// Ring buffer of the last events read from the binlog. Clones share their events.
+#[derive(Clone, Debug)]
+pub struct CrashDump {
+  path: PathBuf,
+  capacity: usize,
+  events: Arc<Mutex<RecentEvents>>,
+}
+
+#[derive(Debug, Default)]
+struct RecentEvents {
+  // Kept aside, the events of a file can not be parsed without it.
+  format_description: Option<Vec<u8>>,
+  events: VecDeque<Vec<u8>>,
+}
+
+impl CrashDump {
+  /// Keeps the last `capacity` events, and writes them to `path` when an event fails.
+  pub fn new(path: impl Into<PathBuf>, capacity: usize) -> Self {
+    Self {
+      path: path.into(),
+      capacity: capacity.max(1),
+      events: Arc::default(),
+    }
+  }
+
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+
+  /// The events kept, as a binlog file, see the module documentation.
+  pub fn to_binlog_file(&self) -> Vec<u8> {
+    let recent = self.events.lock().unwrap();
+    let mut file = BINLOG_MAGIC.to_vec();
+    // Unless it is still in the buffer.
+    if let Some(ref format_description) = recent.format_description {
+      if !recent.events.contains(format_description) {
+        file.extend_from_slice(format_description);
+      }
+    }
+    for event in &recent.events {
+      file.extend_from_slice(event);
+    }
+    file
+  }
+
+  /// Writes the events kept to the file of the dump, replacing it.
+  pub fn write(&self) -> io::Result<()> {
+    fs::write(&self.path, self.to_binlog_file())
+  }
+
+  // Keeps `event`, without the OK byte of the network protocol, evicting the oldest one when full.
+  pub(crate) fn record(&self, event: &[u8]) {
+    let mut recent = self.events.lock().unwrap();
+    if event.get(EVENT_TYPE_OFFSET) == Some(&FORMAT_DESCRIPTION_EVENT) {
+      recent.format_description = Some(event.to_vec());
+    }
+    if recent.events.len() == self.capacity {
+      recent.events.pop_front();
+    }
+    recent.events.push_back(event.to_vec());
+  }
+
+  // Writes the dump once the stream failed. Failing to is only reported, the error of the stream
+  // matters more.
+  pub(crate) fn dump(&self) {
+    let count = self.events.lock().unwrap().events.len();
+    match self.write() {
+      Ok(()) => eprintln!(
+        "dumped the last {} events read to {}",
+        count,
+        self.path.display()
+      ),
+      Err(err) => eprintln!(
+        "warning: failed to dump the last events read to {}: {}",
+        self.path.display(),
+        err
+      ),
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  // Header of an event of `event_type`, 19 bytes followed by `len` bytes of payload.
+  fn event(event_type: u8, len: usize) -> Vec<u8> {
+    let mut event = vec![0; 19 + len];
+    event[EVENT_TYPE_OFFSET] = event_type;
+    event[9..13].copy_from_slice(&(19 + len as u32).to_le_bytes());
+    event
+  }
+
+  #[test]
+  fn keeps_the_last_events_and_the_format_description() {
+    let dump = CrashDump::new("/dev/null", 2);
+    let format_description = event(FORMAT_DESCRIPTION_EVENT, 1);
+    dump.record(&format_description);
+    dump.record(&event(0x04, 2));
+    assert_eq!(
+      [BINLOG_MAGIC, &format_description, &event(0x04, 2)].concat(),
+      dump.to_binlog_file()
+    );
+
+    dump.record(&event(0x1e, 3));
+    dump.record(&event(0x1f, 4));
+    assert_eq!(
+      [
+        BINLOG_MAGIC,
+        &format_description,
+        &event(0x1e, 3),
+        &event(0x1f, 4)
+      ]
+      .concat(),
+      dump.to_binlog_file()
+    );
+  }
+}
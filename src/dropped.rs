@@ -0,0 +1,188 @@
+// Accounting of the changes dropped on purpose, by table and reason, so that a stream that loses
+// data silently shows it in its metrics: a filter that matches more than intended, a sampling rule
+// that keeps nothing, or a stream started in the middle of a transaction.
+//
+// The counts are kept in `Metrics::dropped`, shared by the decoder and the filters of the pipeline.
+// A debug sink can be handed one of every N dropped changes, e.g. to log them while checking a
+// filter against production traffic.
+//
+// Changes that can not be decoded are not dropped, they fail the stream, unless the pipeline skips
+// them (`pipeline::UndecodablePolicy::Skip`).
+
+use super::event::ChangeEvent;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum DropReason {
+  /// Dropped by a filter of the pipeline, e.g. a `ReplicationFilter`.
+  Filter,
+  /// Every row of the change was sampled out, see `Sampling`. The rows sampled out of the changes
+  /// that are kept are only counted in `Metrics::sampled_out_rows`.
+  Sampling,
+  /// Older than `EventDecoder::max_event_age`.
+  Expired,
+  /// A change of the shadow table of an online schema migration, see `ShadowTablePolicy`.
+  ShadowTable,
+  /// A rows event whose TABLE_MAP_EVENT was not seen, e.g. when the stream started in the middle of
+  /// a transaction. Its schema and table are unknown, and counted as empty.
+  UnknownTable,
+  /// An event that failed to decode, or a change a transform panicked on, skipped per
+  /// `UndecodablePolicy::Skip`. The schema and table of the events are unknown, and counted as
+  /// empty.
+  Undecodable,
+}
+
+/// A dropped change, as handed to the debug sink.
+#[derive(Debug)]
+pub struct DroppedEvent<'a> {
+  pub schema: &'a str,
+  pub table: &'a str,
+  pub reason: DropReason,
+  /// The change, unless it was dropped before its rows were decoded, e.g. `DropReason::Expired`.
+  pub change: Option<&'a ChangeEvent>,
+}
+
+type DebugSink = Box<dyn Fn(&DroppedEvent<'_>) + Send + Sync>;
+
+/// Number of changes dropped, by schema, table and reason.
+#[derive(Default)]
+pub struct DroppedEvents {
+  counts: Mutex<BTreeMap<(String, String, DropReason), u64>>,
+  total: AtomicU64,
+  // Hands one of every so many dropped changes to the sink.
+  debug_sink: Mutex<Option<(u64, DebugSink)>>,
+}
+
+impl fmt::Debug for DroppedEvents {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("DroppedEvents")
+      .field("counts", &self.counts.lock().unwrap())
+      .finish()
+  }
+}
+
+impl DroppedEvents {
+  pub fn count(&self, schema: &str, table: &str, reason: DropReason) -> u64 {
+    let key = (schema.to_string(), table.to_string(), reason);
+    let counts = self.counts.lock().unwrap();
+    counts.get(&key).copied().unwrap_or_default()
+  }
+
+  /// Number of changes dropped, every table and reason included.
+  pub fn total(&self) -> u64 {
+    self.total.load(Ordering::Relaxed)
+  }
+
+  /// Counts by schema, table and reason, of the tables that had changes dropped.
+  pub fn counts(&self) -> BTreeMap<(String, String, DropReason), u64> {
+    self.counts.lock().unwrap().clone()
+  }
+
+  /// Hands one of every `every` dropped changes to `sink`, the first one included. The sink runs
+  /// in the task that dropped the change, and should not block.
+  pub fn debug_sink(&self, every: u64, sink: impl Fn(&DroppedEvent<'_>) + Send + Sync + 'static) {
+    *self.debug_sink.lock().unwrap() = Some((every.max(1), Box::new(sink)));
+  }
+
+  /// Records that `change` was dropped.
+  pub(crate) fn record(&self, reason: DropReason, change: &ChangeEvent) {
+    let (schema, table) = table_of(change);
+    self.record_table(schema, table, reason, Some(change));
+  }
+
+  /// Records that a change of `schema`.`table` was dropped, `change` if it was decoded.
+  pub(crate) fn record_table(
+    &self,
+    schema: &str,
+    table: &str,
+    reason: DropReason,
+    change: Option<&ChangeEvent>,
+  ) {
+    let dropped = self.total.fetch_add(1, Ordering::Relaxed);
+    *self
+      .counts
+      .lock()
+      .unwrap()
+      .entry((schema.to_string(), table.to_string(), reason))
+      .or_default() += 1;
+
+    if let Some((every, ref sink)) = *self.debug_sink.lock().unwrap() {
+      if dropped.is_multiple_of(every) {
+        sink(&DroppedEvent {
+          schema,
+          table,
+          reason,
+          change,
+        });
+      }
+    }
+  }
+}
+
+// Schema and table `change` is counted under: the first table of statements, none for markers.
+pub(crate) fn table_of(change: &ChangeEvent) -> (&str, &str) {
+  match change {
+    ChangeEvent::Insert { schema, table, .. }
+    | ChangeEvent::Update { schema, table, .. }
+    | ChangeEvent::Delete { schema, table, .. }
+    | ChangeEvent::SchemaDrift { schema, table, .. } => (schema, table),
+    ChangeEvent::Statement { schema, tables, .. } => match tables.first() {
+      Some(table) => (table.schema_str().unwrap_or(schema), table.table_str()),
+      None => (schema, ""),
+    },
+    ChangeEvent::Begin(_) | ChangeEvent::End(_) | ChangeEvent::SourceRestart { .. } => ("", ""),
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::protocol::ColumnType;
+  use std::sync::Arc;
+
+  fn delete(table: &str) -> ChangeEvent {
+    ChangeEvent::Delete {
+      schema: "pets".to_string(),
+      table: table.to_string(),
+      column_types: vec![ColumnType::MYSQL_TYPE_LONG],
+      rows: Vec::new(),
+    }
+  }
+
+  #[test]
+  fn counts_by_table_and_reason() {
+    let dropped = DroppedEvents::default();
+    let sampled = Arc::new(Mutex::new(Vec::new()));
+    let sink = sampled.clone();
+    dropped.debug_sink(2, move |event| {
+      let sampled = (
+        event.table.to_string(),
+        event.reason,
+        event.change.is_some(),
+      );
+      sink.lock().unwrap().push(sampled);
+    });
+
+    dropped.record(DropReason::Filter, &delete("cats"));
+    dropped.record(DropReason::Filter, &delete("cats"));
+    dropped.record(DropReason::Filter, &delete("dogs"));
+    dropped.record_table("pets", "cats", DropReason::Expired, None);
+
+    assert_eq!(4, dropped.total());
+    assert_eq!(2, dropped.count("pets", "cats", DropReason::Filter));
+    assert_eq!(1, dropped.count("pets", "cats", DropReason::Expired));
+    assert_eq!(0, dropped.count("pets", "dogs", DropReason::Sampling));
+    assert_eq!(3, dropped.counts().len());
+
+    assert_eq!(
+      vec![
+        ("cats".to_string(), DropReason::Filter, true),
+        ("dogs".to_string(), DropReason::Filter, true),
+      ],
+      *sampled.lock().unwrap()
+    );
+  }
+}
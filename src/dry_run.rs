@@ -0,0 +1,147 @@
+// Dry runs of a pipeline, to validate a rollout end to end without side effects: the changes are
+// read, decoded, transformed and serialized to JSON as usual, then counted and dropped instead of
+// being pushed onto the sinks. No position is acked, so checkpoints do not move.
+//
+// The pipeline reads a bounded number of events, see `PipelineBuilder::max_events`.
+
+use super::event::ChangeEvent;
+use super::json;
+use super::transaction::Group;
+use super::watermark::BinlogPosition;
+use std::fmt;
+
+/// What a dry run read, and would have pushed onto the sinks.
+#[derive(Clone, Debug, Default)]
+pub struct DryRunSummary {
+  transactions: u64,
+  changes: u64,
+  rows: u64,
+  serialized_bytes: u64,
+  first_position: Option<BinlogPosition>,
+  last_position: Option<BinlogPosition>,
+}
+
+impl DryRunSummary {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Serializes the changes of `group`, and counts them.
+  pub fn record(&mut self, group: &Group) {
+    let (events, metadata) = match group {
+      Group::Transaction { metadata, events } => {
+        self.transactions += 1;
+        (events.as_slice(), Some(metadata))
+      }
+      Group::Chunk(chunk) => (chunk.events(), Some(chunk.metadata())),
+      Group::Event(event) => (std::slice::from_ref(event), None),
+    };
+
+    for event in events {
+      self.changes += 1;
+      self.rows += match event {
+        ChangeEvent::Insert { rows, .. } | ChangeEvent::Delete { rows, .. } => rows.len() as u64,
+        ChangeEvent::Update { rows, .. } => rows.len() as u64,
+        _ => 0,
+      };
+      self.serialized_bytes += json::to_string(event).len() as u64;
+    }
+
+    if let Some(position) = metadata.and_then(|metadata| metadata.position()) {
+      if self.first_position.is_none() {
+        self.first_position = Some(position.clone());
+      }
+      self.last_position = Some(position.clone());
+    }
+  }
+
+  /// Number of complete transactions.
+  pub fn transactions(&self) -> u64 {
+    self.transactions
+  }
+
+  /// Number of changes, transaction markers excluded.
+  pub fn changes(&self) -> u64 {
+    self.changes
+  }
+
+  /// Number of rows inserted, updated or deleted.
+  pub fn rows(&self) -> u64 {
+    self.rows
+  }
+
+  /// Size of the changes serialized to JSON.
+  pub fn serialized_bytes(&self) -> u64 {
+    self.serialized_bytes
+  }
+
+  /// Positions of the first and the last transactions.
+  pub fn positions(&self) -> Option<(&BinlogPosition, &BinlogPosition)> {
+    self
+      .first_position
+      .as_ref()
+      .zip(self.last_position.as_ref())
+  }
+}
+
+impl fmt::Display for DryRunSummary {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "dry run: {} transactions, {} changes, {} rows, {} bytes of JSON",
+      self.transactions, self.changes, self.rows, self.serialized_bytes
+    )?;
+    if let Some((first, last)) = self.positions() {
+      write!(f, ", from {} to {}", first, last)?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::event::TransactionMetadata;
+  use crate::protocol::ColumnType;
+  use crate::value::{Row, Value};
+
+  fn insert(rows: usize) -> ChangeEvent {
+    ChangeEvent::Insert {
+      schema: "pets".into(),
+      table: "cats".into(),
+      column_types: vec![ColumnType::MYSQL_TYPE_LONG],
+      rows: vec![Row::new(vec![Some(Value::Int(1))]); rows],
+    }
+  }
+
+  fn transaction(events: Vec<ChangeEvent>) -> Group {
+    let metadata = TransactionMetadata::default();
+    Group::Transaction { metadata, events }
+  }
+
+  #[test]
+  fn summarizes_changes() {
+    let mut summary = DryRunSummary::new();
+    summary.record(&transaction(vec![insert(2), insert(1)]));
+    summary.record(&Group::Event(insert(1)));
+    summary.record(&transaction(vec![insert(3)]));
+
+    assert_eq!(2, summary.transactions());
+    assert_eq!(4, summary.changes());
+    assert_eq!(7, summary.rows());
+    let serialized_bytes = [2, 1, 1, 3]
+      .iter()
+      .map(|rows| json::to_string(&insert(*rows)).len() as u64)
+      .sum::<u64>();
+    assert_eq!(serialized_bytes, summary.serialized_bytes());
+    // Positions are only known for the transactions read from a binlog.
+    assert_eq!(None, summary.positions());
+    assert_eq!(
+      format!(
+        "dry run: 2 transactions, 4 changes, 7 rows, {} bytes of JSON",
+        serialized_bytes
+      ),
+      summary.to_string()
+    );
+  }
+}
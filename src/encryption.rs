@@ -0,0 +1,348 @@
+// Encryption of sensitive columns (e.g. PII) while decoding rows images, so that their values never
+// leave the database host in clear, not even to a blob store (see `ValueLimits`).
+//
+// Values are encrypted with envelope encryption: a random AES-256-GCM data key encrypts the text
+// representation of the values, and is itself encrypted ("wrapped") by a master key that never
+// leaves the `KeyProvider` (e.g. a KMS). Every `Value::Encrypted` carries its wrapped data key, so
+// that consumers holding access to the master key can decrypt values on their own.
+
+use super::util::unexpected_err;
+use super::value::{Row, Value};
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+// Encryptions with one data key, before it is rotated: with random 96 bits nonces, the bound of
+// NIST SP 800-38D that keeps the odds of a nonce reused under 2^-32.
+const MAX_ENCRYPTIONS_PER_DATA_KEY: u64 = 1 << 32;
+
+/// Holds the master keys that wrap data keys.
+///
+/// Called synchronously while decoding rows images, but only when a data key is generated.
+pub trait KeyProvider: fmt::Debug + Send + Sync {
+  /// Identifier of the master key used to wrap new data keys.
+  fn key_id(&self) -> &str;
+
+  /// Encrypts `data_key` with the master key `key_id`.
+  fn wrap_key(&self, data_key: &[u8]) -> io::Result<Vec<u8>>;
+
+  /// Decrypts a data key wrapped by the master key `key_id`.
+  fn unwrap_key(&self, key_id: &str, wrapped_key: &[u8]) -> io::Result<Vec<u8>>;
+}
+
+/// Wraps data keys with a local AES-256-GCM master key, e.g. read from a file or an environment
+/// variable. Wrapped keys are the nonce followed by the ciphertext.
+pub struct LocalKeyProvider {
+  key_id: String,
+  cipher: Aes256Gcm,
+}
+
+impl LocalKeyProvider {
+  /// Fails when `master_key` is not 32 bytes long.
+  pub fn new(key_id: impl Into<String>, master_key: &[u8]) -> io::Result<Self> {
+    let cipher = Aes256Gcm::new_from_slice(master_key)
+      .map_err(|_| unexpected_err("master keys must be 32 bytes long"))?;
+    Ok(Self {
+      key_id: key_id.into(),
+      cipher,
+    })
+  }
+}
+
+impl fmt::Debug for LocalKeyProvider {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("LocalKeyProvider")
+      .field("key_id", &self.key_id)
+      .finish()
+  }
+}
+
+impl KeyProvider for LocalKeyProvider {
+  fn key_id(&self) -> &str {
+    &self.key_id
+  }
+
+  fn wrap_key(&self, data_key: &[u8]) -> io::Result<Vec<u8>> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = self
+      .cipher
+      .encrypt(&nonce, data_key)
+      .map_err(|_| unexpected_err("failed to wrap the data key"))?;
+    Ok([nonce.as_slice(), &ciphertext].concat())
+  }
+
+  fn unwrap_key(&self, key_id: &str, wrapped_key: &[u8]) -> io::Result<Vec<u8>> {
+    if key_id != self.key_id {
+      return Err(unexpected_err(format!("unknown master key {}", key_id)));
+    }
+    if wrapped_key.len() < 12 {
+      return Err(unexpected_err("wrapped data key is too short"));
+    }
+
+    let (nonce, ciphertext) = wrapped_key.split_at(12);
+    self
+      .cipher
+      .decrypt(Nonce::from_slice(nonce), ciphertext)
+      .map_err(|_| unexpected_err("failed to unwrap the data key"))
+  }
+}
+
+struct DataKey {
+  cipher: Aes256Gcm,
+  key_id: String,
+  wrapped_key: Vec<u8>,
+  // Values encrypted with the key so far.
+  encryptions: u64,
+}
+
+/// Columns to encrypt, identified by their position in the table like `ValueLimits`.
+///
+/// The data key is rotated after 2^32 encryptions, past which AES-GCM with random nonces is no
+/// longer safe. Clones share their data key.
+#[derive(Clone)]
+pub struct ColumnEncryption {
+  provider: Arc<dyn KeyProvider>,
+  tables: HashMap<(String, String), HashSet<usize>>,
+  data_key: Arc<Mutex<DataKey>>,
+  max_encryptions: u64,
+}
+
+impl ColumnEncryption {
+  /// Generates a data key, and wraps it with `provider`.
+  pub fn new(provider: Arc<dyn KeyProvider>) -> io::Result<Self> {
+    let data_key = Self::generate_data_key(provider.as_ref())?;
+    Ok(Self {
+      provider,
+      tables: HashMap::new(),
+      data_key: Arc::new(Mutex::new(data_key)),
+      max_encryptions: MAX_ENCRYPTIONS_PER_DATA_KEY,
+    })
+  }
+
+  pub fn column(
+    mut self,
+    schema: impl Into<String>,
+    table: impl Into<String>,
+    column: usize,
+  ) -> Self {
+    self
+      .tables
+      .entry((schema.into(), table.into()))
+      .or_default()
+      .insert(column);
+    self
+  }
+
+  /// Replaces the data key, e.g. periodically or after rotating the master key. Values encrypted
+  /// before keep their previous data key.
+  pub fn rotate_data_key(&mut self) -> io::Result<()> {
+    *self.lock_data_key()? = Self::generate_data_key(self.provider.as_ref())?;
+    Ok(())
+  }
+
+  fn lock_data_key(&self) -> io::Result<MutexGuard<'_, DataKey>> {
+    self
+      .data_key
+      .lock()
+      .map_err(|_| unexpected_err("the data key is poisoned"))
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.tables.is_empty()
+  }
+
+  /// Encrypts the configured columns of the rows of `schema`.`table`, returns the number of
+  /// values encrypted. NULLs are left as is.
+  pub fn apply<'a>(
+    &self,
+    schema: &str,
+    table: &str,
+    rows: impl IntoIterator<Item = &'a mut Row>,
+  ) -> io::Result<u64> {
+    let columns = match self.tables.get(&(schema.to_string(), table.to_string())) {
+      Some(columns) => columns,
+      None => return Ok(0),
+    };
+
+    let mut encrypted = 0;
+    for row in rows {
+      for (i, value) in row.values_mut().iter_mut().enumerate() {
+        if !columns.contains(&i) {
+          continue;
+        }
+        if let Some(value) = value {
+          if let Some(text) = value.to_text() {
+            *value = self.encrypt(&text)?;
+            encrypted += 1;
+          }
+        }
+      }
+    }
+
+    Ok(encrypted)
+  }
+
+  fn encrypt(&self, plaintext: &[u8]) -> io::Result<Value> {
+    let mut data_key = self.lock_data_key()?;
+    if data_key.encryptions >= self.max_encryptions {
+      *data_key = Self::generate_data_key(self.provider.as_ref())?;
+    }
+    data_key.encryptions += 1;
+
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = data_key
+      .cipher
+      .encrypt(&nonce, plaintext)
+      .map_err(|_| unexpected_err("failed to encrypt the value"))?;
+
+    let mut nonce_bytes = [0; 12];
+    nonce_bytes.copy_from_slice(&nonce);
+    Ok(Value::Encrypted {
+      key_id: data_key.key_id.clone(),
+      wrapped_key: data_key.wrapped_key.clone(),
+      nonce: nonce_bytes,
+      ciphertext,
+    })
+  }
+
+  fn generate_data_key(provider: &dyn KeyProvider) -> io::Result<DataKey> {
+    let key = Aes256Gcm::generate_key(&mut OsRng);
+    Ok(DataKey {
+      cipher: Aes256Gcm::new(&key),
+      key_id: provider.key_id().to_string(),
+      wrapped_key: provider.wrap_key(&key)?,
+      encryptions: 0,
+    })
+  }
+}
+
+impl fmt::Debug for ColumnEncryption {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("ColumnEncryption")
+      .field("provider", &self.provider)
+      .field("tables", &self.tables)
+      .finish()
+  }
+}
+
+/// Decrypts a `Value::Encrypted` back to the text representation of the original value.
+pub fn decrypt(value: &Value, provider: &dyn KeyProvider) -> io::Result<Vec<u8>> {
+  let (key_id, wrapped_key, nonce, ciphertext) = match value {
+    Value::Encrypted {
+      key_id,
+      wrapped_key,
+      nonce,
+      ciphertext,
+    } => (key_id, wrapped_key, nonce, ciphertext),
+    _ => return Err(unexpected_err("the value is not encrypted")),
+  };
+
+  let data_key = provider.unwrap_key(key_id, wrapped_key)?;
+  let cipher = Aes256Gcm::new_from_slice(&data_key)
+    .map_err(|_| unexpected_err("data keys must be 32 bytes long"))?;
+  cipher
+    .decrypt(Nonce::from_slice(nonce), ciphertext.as_slice())
+    .map_err(|_| unexpected_err("failed to decrypt the value"))
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn provider() -> Arc<LocalKeyProvider> {
+    Arc::new(LocalKeyProvider::new("local/1", &[42; 32]).unwrap())
+  }
+
+  fn row() -> Row {
+    Row::new(vec![
+      Some(Value::Int(1)),
+      Some(Value::Bytes(b"charlie@example.com".to_vec())),
+      Some(Value::Null),
+      None,
+    ])
+  }
+
+  #[test]
+  fn encrypts_configured_columns() {
+    let provider = provider();
+    let encryption = ColumnEncryption::new(provider.clone())
+      .unwrap()
+      .column("pets", "owners", 0)
+      .column("pets", "owners", 1)
+      .column("pets", "owners", 2);
+
+    let mut rows = [row()];
+    assert_eq!(
+      2,
+      encryption.apply("pets", "owners", rows.iter_mut()).unwrap()
+    );
+    assert_eq!(Some(&Value::Null), rows[0].get(2));
+    assert_eq!(
+      b"1".to_vec(),
+      decrypt(rows[0].get(0).unwrap(), provider.as_ref()).unwrap()
+    );
+    match rows[0].get(1) {
+      Some(value @ Value::Encrypted { key_id, .. }) => {
+        assert_eq!("local/1", key_id);
+        assert_eq!(
+          b"charlie@example.com".to_vec(),
+          decrypt(value, provider.as_ref()).unwrap()
+        );
+      }
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+
+    let mut rows = [row()];
+    assert_eq!(
+      0,
+      encryption.apply("pets", "cats", rows.iter_mut()).unwrap()
+    );
+  }
+
+  #[test]
+  fn fails_to_decrypt_with_another_master_key() {
+    let encryption = ColumnEncryption::new(provider())
+      .unwrap()
+      .column("pets", "owners", 1);
+    let mut rows = [row()];
+    encryption.apply("pets", "owners", rows.iter_mut()).unwrap();
+
+    let other = LocalKeyProvider::new("local/1", &[7; 32]).unwrap();
+    assert!(decrypt(rows[0].get(1).unwrap(), &other).is_err());
+    assert!(decrypt(&Value::Int(1), &other).is_err());
+    assert!(LocalKeyProvider::new("local/2", &[7; 16]).is_err());
+  }
+
+  #[test]
+  fn rotates_data_keys_after_max_encryptions() {
+    let provider = provider();
+    let mut encryption = ColumnEncryption::new(provider.clone())
+      .unwrap()
+      .column("pets", "owners", 1);
+    encryption.max_encryptions = 2;
+
+    let wrapped_keys = (0..5)
+      .map(|_| {
+        let mut rows = [row()];
+        encryption.apply("pets", "owners", rows.iter_mut()).unwrap();
+        match rows[0].get(1) {
+          Some(value @ Value::Encrypted { wrapped_key, .. }) => {
+            assert_eq!(
+              b"charlie@example.com".to_vec(),
+              decrypt(value, provider.as_ref()).unwrap()
+            );
+            wrapped_key.clone()
+          }
+          unexpected => panic!("unexpected {:?}", unexpected),
+        }
+      })
+      .collect::<Vec<_>>();
+    assert_eq!(wrapped_keys[0], wrapped_keys[1]);
+    assert_ne!(wrapped_keys[1], wrapped_keys[2]);
+    assert_eq!(wrapped_keys[2], wrapped_keys[3]);
+    assert_ne!(wrapped_keys[3], wrapped_keys[4]);
+  }
+}
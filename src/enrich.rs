@@ -0,0 +1,368 @@
+// Denormalizes the changes of a table with values looked up elsewhere, e.g. the row a foreign key
+// refers to, read from a replica with `Connection::query_at_position`, or a document fetched from
+// an HTTP service, for the consumers that need self-contained payloads.
+//
+// The value looked up for the key of every row image is appended to the image, as a column after
+// the last one of the table, and its type to the column types of the change. The lookups of a
+// change run concurrently, up to `Enricher::concurrency` of them; with `transform_workers`, the
+// pipeline runs the lookups of that many changes at once.
+//
+// Values found are cached, for `cache_ttl`. Lookups that fail or time out leave the value absent
+// (`None`, as the columns missing from minimal row images) rather than stop the stream, and are
+// counted in `EnricherStats`.
+
+use super::conn::ColumnType;
+use super::event::ChangeEvent;
+use super::value::{Row, Value};
+use futures::future::{self, BoxFuture, Either, Future, FutureExt};
+use futures::stream::{self, StreamExt};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const DEFAULT_CONCURRENCY: usize = 16;
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+const DEFAULT_CACHE_CAPACITY: usize = 10_000;
+const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(60);
+
+pub type LookupError = Box<dyn std::error::Error + Send + Sync>;
+
+type Lookup = dyn Fn(Value) -> BoxFuture<'static, Result<Option<Value>, LookupError>> + Send + Sync;
+
+/// Counters of the lookups of an `Enricher`.
+#[derive(Debug, Default)]
+pub struct EnricherStats {
+  lookups: AtomicU64,
+  cache_hits: AtomicU64,
+  timeouts: AtomicU64,
+  errors: AtomicU64,
+}
+
+impl EnricherStats {
+  /// Number of lookups sent, the cache hits excluded.
+  pub fn lookups(&self) -> u64 {
+    self.lookups.load(Ordering::Relaxed)
+  }
+
+  pub fn cache_hits(&self) -> u64 {
+    self.cache_hits.load(Ordering::Relaxed)
+  }
+
+  /// Number of lookups that took longer than `Enricher::timeout`.
+  pub fn timeouts(&self) -> u64 {
+    self.timeouts.load(Ordering::Relaxed)
+  }
+
+  pub fn errors(&self) -> u64 {
+    self.errors.load(Ordering::Relaxed)
+  }
+}
+
+// Values by key, evicted in the order they were inserted.
+struct Cache {
+  capacity: usize,
+  ttl: Duration,
+  entries: HashMap<Vec<u8>, (Value, Instant)>,
+  order: VecDeque<Vec<u8>>,
+}
+
+impl Cache {
+  fn get(&self, key: &[u8], now: Instant) -> Option<Value> {
+    let (value, inserted) = self.entries.get(key)?;
+    if now.duration_since(*inserted) >= self.ttl {
+      return None;
+    }
+    Some(value.clone())
+  }
+
+  fn insert(&mut self, key: Vec<u8>, value: Value, now: Instant) {
+    if self.capacity == 0 {
+      return;
+    }
+    if self.entries.insert(key.clone(), (value, now)).is_none() {
+      self.order.push_back(key);
+    }
+    while self.entries.len() > self.capacity {
+      match self.order.pop_front() {
+        Some(oldest) => self.entries.remove(&oldest),
+        None => break,
+      };
+    }
+  }
+}
+
+/// Appends a value looked up by key to the rows of a table, see `PipelineBuilder::enrich`. Cheap
+/// to clone, the clones share their cache and stats.
+#[derive(Clone)]
+pub struct Enricher {
+  schema: String,
+  table: String,
+  key_column: usize,
+  column_type: ColumnType,
+  concurrency: usize,
+  timeout: Duration,
+  lookup: Arc<Lookup>,
+  cache: Arc<Mutex<Cache>>,
+  stats: Arc<EnricherStats>,
+}
+
+impl Enricher {
+  /// Enriches the rows of `schema`.`table` with the value `lookup` returns for their
+  /// `key_column`, the index of the column in the row. `Ok(None)` means that there is no value
+  /// for the key, which is appended as NULL.
+  pub fn new<F>(
+    schema: impl Into<String>,
+    table: impl Into<String>,
+    key_column: usize,
+    lookup: impl Fn(Value) -> F + Send + Sync + 'static,
+  ) -> Self
+  where
+    F: Future<Output = Result<Option<Value>, LookupError>> + Send + 'static,
+  {
+    Self {
+      schema: schema.into(),
+      table: table.into(),
+      key_column,
+      column_type: ColumnType::MYSQL_TYPE_VAR_STRING,
+      concurrency: DEFAULT_CONCURRENCY,
+      timeout: DEFAULT_TIMEOUT,
+      lookup: Arc::new(move |key| lookup(key).boxed()),
+      cache: Arc::new(Mutex::new(Cache {
+        capacity: DEFAULT_CACHE_CAPACITY,
+        ttl: DEFAULT_CACHE_TTL,
+        entries: HashMap::new(),
+        order: VecDeque::new(),
+      })),
+      stats: Arc::new(EnricherStats::default()),
+    }
+  }
+
+  /// Type of the values looked up, VAR_STRING by default.
+  pub fn column_type(mut self, column_type: ColumnType) -> Self {
+    self.column_type = column_type;
+    self
+  }
+
+  /// Number of lookups of a change sent at once, 16 by default.
+  pub fn concurrency(mut self, concurrency: usize) -> Self {
+    self.concurrency = concurrency.max(1);
+    self
+  }
+
+  /// How long a lookup is waited for, 1 second by default.
+  pub fn timeout(mut self, timeout: Duration) -> Self {
+    self.timeout = timeout;
+    self
+  }
+
+  /// Number of values cached, 10000 by default, 0 disables the cache.
+  pub fn cache_capacity(self, capacity: usize) -> Self {
+    self.cache.lock().unwrap().capacity = capacity;
+    self
+  }
+
+  /// How long a value is cached, 60 seconds by default. Changes to the data looked up only show
+  /// once the value expires.
+  pub fn cache_ttl(self, ttl: Duration) -> Self {
+    self.cache.lock().unwrap().ttl = ttl;
+    self
+  }
+
+  pub fn stats(&self) -> &EnricherStats {
+    &self.stats
+  }
+
+  /// Enriches `change` when it is a rows change of the table, passes it through otherwise.
+  pub fn enrich(&self, change: ChangeEvent) -> impl Future<Output = Option<ChangeEvent>> {
+    let enricher = self.clone();
+    async move {
+      let mut change = change;
+      let (column_types, mut images) = match &mut change {
+        ChangeEvent::Insert {
+          schema,
+          table,
+          column_types,
+          rows,
+        }
+        | ChangeEvent::Delete {
+          schema,
+          table,
+          column_types,
+          rows,
+        } if enricher.is_enriched(schema, table) => (column_types, rows.iter_mut().collect()),
+        ChangeEvent::Update {
+          schema,
+          table,
+          column_types,
+          rows,
+        } if enricher.is_enriched(schema, table) => {
+          let images: Vec<&mut Row> = rows
+            .iter_mut()
+            .flat_map(|(before, after)| vec![before, after])
+            .collect();
+          (column_types, images)
+        }
+        _ => return Some(change),
+      };
+
+      let keys: Vec<Option<Value>> = images
+        .iter()
+        .map(|row| row.get(enricher.key_column).cloned())
+        .collect();
+      let values: Vec<Option<Value>> = stream::iter(keys)
+        .map(|key| enricher.lookup(key))
+        .buffered(enricher.concurrency)
+        .collect()
+        .await;
+      for (row, value) in images.iter_mut().zip(values) {
+        row.push(value);
+      }
+      column_types.push(enricher.column_type);
+      Some(change)
+    }
+  }
+
+  fn is_enriched(&self, schema: &str, table: &str) -> bool {
+    self.schema == schema && self.table == table
+  }
+
+  // Rows without a key (NULL, or missing from a minimal image) are not looked up.
+  async fn lookup(&self, key: Option<Value>) -> Option<Value> {
+    let key = match key {
+      Some(Value::Null) | None => return None,
+      Some(key) => key,
+    };
+    let cache_key = key
+      .to_text()
+      .unwrap_or_else(|| format!("{:?}", key).into_bytes());
+    if let Some(value) = self.cache.lock().unwrap().get(&cache_key, Instant::now()) {
+      self.stats.cache_hits.fetch_add(1, Ordering::Relaxed);
+      return Some(value);
+    }
+
+    self.stats.lookups.fetch_add(1, Ordering::Relaxed);
+    let timeout = futures_timer::Delay::new(self.timeout);
+    let result = match future::select((self.lookup)(key), timeout).await {
+      Either::Left((result, _)) => result,
+      Either::Right(_) => {
+        self.stats.timeouts.fetch_add(1, Ordering::Relaxed);
+        return None;
+      }
+    };
+    match result {
+      Ok(value) => {
+        let value = value.unwrap_or(Value::Null);
+        let mut cache = self.cache.lock().unwrap();
+        cache.insert(cache_key, value.clone(), Instant::now());
+        Some(value)
+      }
+      Err(_) => {
+        self.stats.errors.fetch_add(1, Ordering::Relaxed);
+        None
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use futures::executor::block_on;
+
+  fn insert(table: &str, ids: &[i64]) -> ChangeEvent {
+    ChangeEvent::Insert {
+      schema: "pets".into(),
+      table: table.into(),
+      column_types: vec![ColumnType::MYSQL_TYPE_LONG, ColumnType::MYSQL_TYPE_LONG],
+      rows: ids
+        .iter()
+        .map(|id| Row::new(vec![Some(Value::Int(1)), Some(Value::Int(*id))]))
+        .collect(),
+    }
+  }
+
+  // Names of the owners, by id.
+  fn enricher() -> Enricher {
+    Enricher::new("pets", "cats", 1, |owner| async move {
+      match owner {
+        Value::Int(1) => Ok(Some(Value::Bytes(b"Alice".to_vec()))),
+        Value::Int(2) => Err("connection lost".into()),
+        Value::Int(3) => {
+          futures_timer::Delay::new(Duration::from_secs(5)).await;
+          Ok(None)
+        }
+        _ => Ok(None),
+      }
+    })
+    .timeout(Duration::from_millis(50))
+  }
+
+  fn appended(change: Option<ChangeEvent>) -> Vec<Option<Value>> {
+    match change {
+      Some(ChangeEvent::Insert {
+        column_types, rows, ..
+      }) => {
+        assert_eq!(3, column_types.len());
+        rows.iter().map(|row| row.values()[2].clone()).collect()
+      }
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+  }
+
+  #[test]
+  fn appends_looked_up_values() {
+    let enricher = enricher();
+    let values = appended(block_on(enricher.enrich(insert("cats", &[1, 4, 1]))));
+    assert_eq!(
+      vec![
+        Some(Value::Bytes(b"Alice".to_vec())),
+        Some(Value::Null),
+        Some(Value::Bytes(b"Alice".to_vec())),
+      ],
+      values
+    );
+    // Owner 1 was looked up once, unless its second lookup went out before the first was cached.
+    let lookups = enricher.stats().lookups();
+    assert_eq!(3, lookups + enricher.stats().cache_hits());
+
+    appended(block_on(enricher.enrich(insert("cats", &[1]))));
+    assert_eq!(lookups, enricher.stats().lookups());
+    assert_eq!(4, lookups + enricher.stats().cache_hits());
+
+    match block_on(enricher.enrich(insert("dogs", &[1]))) {
+      Some(ChangeEvent::Insert { column_types, .. }) => assert_eq!(2, column_types.len()),
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+  }
+
+  #[test]
+  fn leaves_failed_lookups_absent() {
+    let enricher = enricher();
+    let values = appended(block_on(enricher.enrich(insert("cats", &[2, 3]))));
+    assert_eq!(vec![None, None], values);
+    assert_eq!(1, enricher.stats().errors());
+    assert_eq!(1, enricher.stats().timeouts());
+
+    // Failures are not cached.
+    appended(block_on(enricher.enrich(insert("cats", &[2]))));
+    assert_eq!(2, enricher.stats().errors());
+  }
+
+  #[test]
+  fn evicts_cached_values() {
+    let mut cache = Cache {
+      capacity: 2,
+      ttl: Duration::from_secs(60),
+      entries: HashMap::new(),
+      order: VecDeque::new(),
+    };
+    let now = Instant::now();
+    for key in &[b"1", b"2", b"3"] {
+      cache.insert(key.to_vec(), Value::Int(1), now);
+    }
+    assert_eq!(None, cache.get(b"1", now));
+    assert_eq!(Some(Value::Int(1)), cache.get(b"3", now));
+    assert_eq!(None, cache.get(b"3", now + Duration::from_secs(60)));
+  }
+}
@@ -0,0 +1,1450 @@
+use super::classify::{classify, Operation, TableName};
+use super::dropped::DropReason;
+#[cfg(feature = "encryption")]
+use super::encryption::ColumnEncryption;
+use super::limits::ValueLimits;
+use super::metrics::Metrics;
+use super::migration::{self, ShadowKind, ShadowTablePolicy};
+// Types of the public API of `EventDecoder` and its change events. The binlog events are read
+// through their accessors.
+pub use super::protocol::ColumnType;
+pub use super::protocol_binlog::{BinlogEvent, BinlogFile, EventHeader, EventType, RelayLogFile};
+use super::protocol_binlog::{QueryEvent, RowEvent, TableMapEvent};
+#[cfg(feature = "redaction")]
+use super::redaction::Redaction;
+use super::routing::TableRouter;
+use super::sampling::Sampling;
+use super::temporal::InvalidTemporalPolicy;
+use super::util::unexpected_err;
+pub use super::value::{Row, Value};
+use super::watermark::{BinlogPosition, Watermarks};
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A change applied to MYSQL, as observed in the binlog.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ChangeEvent {
+  /// `column_types` are the types of the columns of the table, as found in its TABLE_MAP_EVENT,
+  /// in the order of the row values.
+  #[non_exhaustive]
+  Insert {
+    schema: String,
+    table: String,
+    column_types: Vec<ColumnType>,
+    rows: Vec<Row>,
+  },
+  /// Before and after images of the updated rows.
+  #[non_exhaustive]
+  Update {
+    schema: String,
+    table: String,
+    column_types: Vec<ColumnType>,
+    rows: Vec<(Row, Row)>,
+  },
+  #[non_exhaustive]
+  Delete {
+    schema: String,
+    table: String,
+    column_types: Vec<ColumnType>,
+    rows: Vec<Row>,
+  },
+  /// A DML statement logged with `binlog_format=STATEMENT` (or `MIXED`), or a DDL statement
+  /// when enabled with `EventDecoder::emit_ddl`. Row images are not available, only the SQL that
+  /// was executed against `schema`. `time_zone` is the session time
+  /// zone the statement ran with, when it is not the server's, which TIMESTAMP literals and
+  /// functions like NOW() in `sql` are relative to.
+  #[non_exhaustive]
+  Statement {
+    schema: String,
+    sql: String,
+    operation: Operation,
+    tables: Vec<TableName>,
+    time_zone: Option<String>,
+  },
+  /// Marks the beginning of a transaction. Only emitted when enabled with
+  /// `EventDecoder::emit_transaction_markers`.
+  Begin(TransactionMetadata),
+  /// Marks the end of a committed (or rolled back) transaction. Only emitted when enabled with
+  /// `EventDecoder::emit_transaction_markers`.
+  End(TransactionMetadata),
+  /// The number of columns of a table changed since its previous TABLE_MAP_EVENT, e.g. after an
+  /// INSTANT `ADD COLUMN`, or a DDL that was not seen. Consumers that resolve columns by their
+  /// position (e.g. from information_schema) must refresh them before the next rows of the table.
+  /// `column_names` are only known with `binlog_row_metadata=FULL`, empty otherwise. Only emitted
+  /// when enabled with `EventDecoder::emit_schema_drift`.
+  #[non_exhaustive]
+  SchemaDrift {
+    schema: String,
+    table: String,
+    previous_column_count: u64,
+    column_count: u64,
+    column_names: Vec<String>,
+  },
+  /// The source shut down (STOP_EVENT), usually to restart: the stream goes on from the next
+  /// binlog file, if any. `position` is the end of the binlog file that was closed. Transactions
+  /// in progress at the shutdown were rolled back, but consumers sensitive to gaps (e.g. across a
+  /// crash recovery, or a failover to another source) may want to check them.
+  #[non_exhaustive]
+  SourceRestart { position: Option<BinlogPosition> },
+}
+
+impl ChangeEvent {
+  /// Approximate number of bytes held in memory by the event.
+  pub fn byte_size(&self) -> usize {
+    match self {
+      ChangeEvent::Insert {
+        schema,
+        table,
+        rows,
+        ..
+      }
+      | ChangeEvent::Delete {
+        schema,
+        table,
+        rows,
+        ..
+      } => schema.len() + table.len() + rows.iter().map(Row::byte_size).sum::<usize>(),
+      ChangeEvent::Update {
+        schema,
+        table,
+        rows,
+        ..
+      } => {
+        let rows_size: usize = rows
+          .iter()
+          .map(|(before, after)| before.byte_size() + after.byte_size())
+          .sum();
+        schema.len() + table.len() + rows_size
+      }
+      ChangeEvent::Statement { schema, sql, .. } => schema.len() + sql.len(),
+      ChangeEvent::SchemaDrift {
+        schema,
+        table,
+        column_names,
+        ..
+      } => schema.len() + table.len() + column_names.iter().map(String::len).sum::<usize>(),
+      ChangeEvent::Begin(_) | ChangeEvent::End(_) | ChangeEvent::SourceRestart { .. } => 0,
+    }
+  }
+}
+
+/// Formats as a one-liner, e.g. `INSERT pets.cats (1, 'Charlie'), (2, 'Tom')` or
+/// `COMMIT 3e11fa47-71ca-11e1-9e33-c80aa9429562:23 at mysql-bin.000002:1201 (2 events)`.
+impl fmt::Display for ChangeEvent {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ChangeEvent::Insert {
+        schema,
+        table,
+        rows,
+        ..
+      } => {
+        write!(f, "INSERT {}.{} ", schema, table)?;
+        write_list(f, rows.iter())
+      }
+      ChangeEvent::Update {
+        schema,
+        table,
+        rows,
+        ..
+      } => {
+        write!(f, "UPDATE {}.{} ", schema, table)?;
+        let rows = rows
+          .iter()
+          .map(|(before, after)| format!("{} -> {}", before, after));
+        write_list(f, rows)
+      }
+      ChangeEvent::Delete {
+        schema,
+        table,
+        rows,
+        ..
+      } => {
+        write!(f, "DELETE {}.{} ", schema, table)?;
+        write_list(f, rows.iter())
+      }
+      ChangeEvent::Statement {
+        schema,
+        sql,
+        time_zone,
+        ..
+      } => {
+        let sql = sql.split_whitespace().collect::<Vec<_>>().join(" ");
+        write!(f, "STATEMENT {}: {}", schema, sql)?;
+        match time_zone {
+          Some(time_zone) => write!(f, " (time zone {})", time_zone),
+          None => Ok(()),
+        }
+      }
+      ChangeEvent::Begin(metadata) => {
+        f.write_str("BEGIN")?;
+        if let Some(gtid) = metadata.gtid() {
+          write!(f, " {}", gtid)?;
+        }
+        Ok(())
+      }
+      ChangeEvent::End(metadata) => write!(f, "COMMIT {}", metadata),
+      ChangeEvent::SchemaDrift {
+        schema,
+        table,
+        previous_column_count,
+        column_count,
+        ..
+      } => write!(
+        f,
+        "SCHEMA DRIFT {}.{}: {} -> {} columns",
+        schema, table, previous_column_count, column_count
+      ),
+      ChangeEvent::SourceRestart { position } => {
+        f.write_str("SOURCE RESTART")?;
+        if let Some(position) = position {
+          write!(f, " at {}", position)?;
+        }
+        Ok(())
+      }
+    }
+  }
+}
+
+fn write_list<T: fmt::Display>(
+  f: &mut fmt::Formatter<'_>,
+  items: impl Iterator<Item = T>,
+) -> fmt::Result {
+  for (i, item) in items.enumerate() {
+    if i > 0 {
+      f.write_str(", ")?;
+    }
+    write!(f, "{}", item)?;
+  }
+  Ok(())
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TransactionMetadata {
+  gtid: Option<String>,
+  event_count: u64,
+  byte_size: u64,
+  start_position: Option<BinlogPosition>,
+  position: Option<BinlogPosition>,
+  original_commit_timestamp: Option<u64>,
+  transaction_length: Option<u64>,
+}
+
+impl TransactionMetadata {
+  /// GTID of the transaction, `None` when `gtid_mode` is OFF.
+  pub fn gtid(&self) -> Option<&str> {
+    self.gtid.as_deref()
+  }
+
+  /// Number of change events in the transaction so far. Once grouped by a
+  /// `transaction::TransactionGrouper`, only the changes that made it through the filters and
+  /// transforms are counted.
+  pub fn event_count(&self) -> u64 {
+    self.event_count
+  }
+
+  pub(crate) fn set_event_count(&mut self, event_count: u64) {
+    self.event_count = event_count;
+  }
+
+  #[cfg(test)]
+  pub(crate) fn set_transaction_length(&mut self, transaction_length: u64) {
+    self.transaction_length = Some(transaction_length);
+  }
+
+  pub(crate) fn set_position(&mut self, position: BinlogPosition) {
+    self.position = Some(position);
+  }
+
+  #[cfg(test)]
+  pub(crate) fn set_start_position(&mut self, position: BinlogPosition) {
+    self.start_position = Some(position);
+  }
+
+  /// Size of the binlog events of the transaction so far, in bytes.
+  pub fn byte_size(&self) -> u64 {
+    self.byte_size
+  }
+
+  /// Position of the first event of the transaction, its GTID_EVENT or BEGIN, once a ROTATE_EVENT
+  /// named the binlog file.
+  pub fn start_position(&self) -> Option<&BinlogPosition> {
+    self.start_position.as_ref()
+  }
+
+  /// Position right after the transaction, which the stream resumes from. Only known for
+  /// `ChangeEvent::End`, once a ROTATE_EVENT named the binlog file.
+  pub fn position(&self) -> Option<&BinlogPosition> {
+    self.position.as_ref()
+  }
+
+  /// Time the transaction was committed on the server it originated from, in microseconds since
+  /// the epoch. Only logged from MYSQL 8.0.1.
+  pub fn original_commit_timestamp(&self) -> Option<u64> {
+    self.original_commit_timestamp
+  }
+
+  /// Size of the whole transaction in the binlog, in bytes, known upfront from its GTID_EVENT.
+  /// Only logged from MYSQL 8.0.2.
+  pub fn transaction_length(&self) -> Option<u64> {
+    self.transaction_length
+  }
+
+  // Time the transaction was committed on the server it originated from, or the timestamp of
+  // `header` logged by whichever server of the replication chain wrote it.
+  fn committed_at(&self, header: &EventHeader) -> SystemTime {
+    match self.original_commit_timestamp {
+      Some(micros) => UNIX_EPOCH + Duration::from_micros(micros),
+      None => UNIX_EPOCH + Duration::from_secs(u64::from(header.timestamp())),
+    }
+  }
+}
+
+/// Formats as `GTID at FILE:POSITION (N events)`, leaving out the GTID and position when unknown.
+impl fmt::Display for TransactionMetadata {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if let Some(gtid) = &self.gtid {
+      write!(f, "{} ", gtid)?;
+    }
+    if let Some(position) = &self.position {
+      write!(f, "at {} ", position)?;
+    }
+    write!(f, "({} events)", self.event_count)
+  }
+}
+
+/// Turns raw binlog events into `ChangeEvent`s.
+///
+/// Keeps track of the TABLE_MAP_EVENTs seen so far, so that rows events can be resolved to the
+/// schema and table they belong to.
+pub struct EventDecoder {
+  tables: HashMap<u64, TableMapEvent>,
+  // Number of columns of every table, as of its last TABLE_MAP_EVENT.
+  column_counts: HashMap<(String, String), u64>,
+  metrics: Arc<Metrics>,
+  value_limits: ValueLimits,
+  invalid_temporals: InvalidTemporalPolicy,
+  sampling: Sampling,
+  #[cfg(feature = "redaction")]
+  redaction: Redaction,
+  #[cfg(feature = "encryption")]
+  encryption: Option<ColumnEncryption>,
+  emit_transaction_markers: bool,
+  emit_ddl: bool,
+  emit_schema_drift: bool,
+  max_event_age: Option<Duration>,
+  shadow_tables: ShadowTablePolicy,
+  router: TableRouter,
+  watermarks: Option<Watermarks>,
+  // Binlog file, and position of the end of the current event.
+  log_file: Option<String>,
+  log_pos: u32,
+  // GTID_EVENT that precedes the next transaction, with its size.
+  next_transaction: Option<TransactionMetadata>,
+  transaction: Option<TransactionMetadata>,
+}
+
+impl EventDecoder {
+  pub fn new(metrics: Arc<Metrics>) -> Self {
+    let tables = HashMap::new();
+    Self {
+      tables,
+      column_counts: HashMap::new(),
+      metrics,
+      value_limits: ValueLimits::default(),
+      invalid_temporals: InvalidTemporalPolicy::default(),
+      sampling: Sampling::default(),
+      #[cfg(feature = "redaction")]
+      redaction: Redaction::default(),
+      #[cfg(feature = "encryption")]
+      encryption: None,
+      emit_transaction_markers: false,
+      emit_ddl: false,
+      emit_schema_drift: false,
+      max_event_age: None,
+      shadow_tables: ShadowTablePolicy::default(),
+      router: TableRouter::default(),
+      watermarks: None,
+      log_file: None,
+      log_pos: 0,
+      next_transaction: None,
+      transaction: None,
+    }
+  }
+
+  /// Emits `ChangeEvent::Begin` and `ChangeEvent::End` around the changes of every transaction,
+  /// Debezium-style, so that consumers can apply transactions atomically.
+  pub fn emit_transaction_markers(mut self, enabled: bool) -> Self {
+    self.emit_transaction_markers = enabled;
+    self
+  }
+
+  /// Emits DDL queries as `ChangeEvent::Statement` with `Operation::Ddl`, e.g. for sinks that keep
+  /// the schema of their tables in sync. The tables they touch are only known with the `sqlparse`
+  /// feature.
+  ///
+  /// Unless the shadow tables of online schema migrations are kept, their DDL is dropped along
+  /// with their changes, cut-overs included. `ShadowTablePolicy::Remap` keeps the ALTER TABLE of
+  /// the ghost table, under the name of the logical table.
+  pub fn emit_ddl(mut self, enabled: bool) -> Self {
+    self.emit_ddl = enabled;
+    self
+  }
+
+  /// Emits `ChangeEvent::SchemaDrift` when the number of columns of a table changes between two of
+  /// its TABLE_MAP_EVENTs. Drifts are counted in the metrics either way.
+  pub fn emit_schema_drift(mut self, enabled: bool) -> Self {
+    self.emit_schema_drift = enabled;
+    self
+  }
+
+  /// Drops the changes that are older than `max_age`, by the timestamp of their binlog event, e.g.
+  /// while catching up to rebuild a cache, where intermediate states are worthless. Their rows
+  /// images are not even decoded. DDL and transaction markers are still emitted.
+  pub fn max_event_age(mut self, max_age: Duration) -> Self {
+    self.max_event_age = Some(max_age);
+    self
+  }
+
+  /// Size limits applied to the column values of rows events.
+  pub fn value_limits(mut self, value_limits: ValueLimits) -> Self {
+    self.value_limits = value_limits;
+    self
+  }
+
+  /// What to do with the zero dates and the out of range temporal values of rows events, kept as
+  /// parsed by default.
+  pub fn invalid_temporals(mut self, policy: InvalidTemporalPolicy) -> Self {
+    self.invalid_temporals = policy;
+    self
+  }
+
+  /// Rows of high-volume tables to keep, the others are dropped. Events left without rows are not
+  /// emitted.
+  pub fn sampling(mut self, sampling: Sampling) -> Self {
+    self.sampling = sampling;
+    self
+  }
+
+  /// Personal information redacted while decoding rows images, before the columns are encrypted
+  /// and the value limits apply.
+  #[cfg(feature = "redaction")]
+  pub fn redaction(mut self, redaction: Redaction) -> Self {
+    self.redaction = redaction;
+    self
+  }
+
+  /// Columns encrypted while decoding rows images, before the value limits apply.
+  #[cfg(feature = "encryption")]
+  pub fn column_encryption(mut self, encryption: ColumnEncryption) -> Self {
+    self.encryption = Some(encryption);
+    self
+  }
+
+  /// What to do with the changes made to the shadow tables of online schema migrations (gh-ost
+  /// or pt-online-schema-change). They are kept by default.
+  pub fn shadow_tables(mut self, policy: ShadowTablePolicy) -> Self {
+    self.shadow_tables = policy;
+    self
+  }
+
+  /// Logical names the changes of tables are emitted under, which follow the tables across
+  /// renames.
+  pub fn table_router(mut self, router: TableRouter) -> Self {
+    self.router = router;
+    self
+  }
+
+  /// Reports the position of every `ChangeEvent::End` as seen, for the sinks to ack them once they
+  /// are done with the transaction. See `TransactionMetadata::position`.
+  pub fn watermarks(mut self, watermarks: Watermarks) -> Self {
+    self.watermarks = Some(watermarks);
+    self
+  }
+
+  /// The table router, with the renames observed so far.
+  pub fn router(&self) -> &TableRouter {
+    &self.router
+  }
+
+  pub fn metrics(&self) -> &Arc<Metrics> {
+    &self.metrics
+  }
+
+  /// Decodes the next event of the stream. Returns `None` for events that do not carry any
+  /// change on their own (e.g. ROTATE_EVENT, TABLE_MAP_EVENT, BEGIN or, unless `emit_ddl`, DDL
+  /// queries) or that expired, and fails when the rows images can not be decoded.
+  pub fn decode(
+    &mut self,
+    header: &EventHeader,
+    event: BinlogEvent,
+  ) -> io::Result<Option<ChangeEvent>> {
+    let event_size = header.event_size() as u64;
+    if let Some(ref mut transaction) = self.transaction {
+      transaction.byte_size += event_size;
+    }
+    self.log_pos = header.log_pos();
+
+    let expired = self.is_expired(header);
+    let change = match event {
+      BinlogEvent::Insert(ref rows)
+      | BinlogEvent::Update(ref rows)
+      | BinlogEvent::Delete(ref rows)
+        if expired =>
+      {
+        self.metrics.incr_expired_events();
+        let (schema, table) = match self.tables.get(&rows.table_id()) {
+          Some(table_map) => (table_map.schema_str(), table_map.table_str()),
+          None => ("", ""),
+        };
+        let dropped = self.metrics.dropped();
+        dropped.record_table(schema, table, DropReason::Expired, None);
+        return Ok(None);
+      }
+      BinlogEvent::Query(query) => return Ok(self.decode_query(query, event_size, expired)),
+      BinlogEvent::TableMap(table_map) => {
+        let drift = self.observe_column_count(&table_map);
+        self.tables.insert(table_map.table_id(), table_map);
+        drift
+      }
+      BinlogEvent::Insert(rows) => {
+        self
+          .decode_rows(&rows, false)?
+          .map(|(schema, table, column_types, rows)| ChangeEvent::Insert {
+            schema,
+            table,
+            column_types,
+            rows,
+          })
+      }
+      BinlogEvent::Update(rows) => {
+        self
+          .decode_rows(&rows, true)?
+          .map(|(schema, table, column_types, rows)| ChangeEvent::Update {
+            schema,
+            table,
+            column_types,
+            rows: pair_images(rows),
+          })
+      }
+      BinlogEvent::Delete(rows) => {
+        self
+          .decode_rows(&rows, false)?
+          .map(|(schema, table, column_types, rows)| ChangeEvent::Delete {
+            schema,
+            table,
+            column_types,
+            rows,
+          })
+      }
+      BinlogEvent::Gtid(gtid) => {
+        let transaction = TransactionMetadata {
+          gtid: gtid.gtid(),
+          event_count: 0,
+          byte_size: event_size,
+          start_position: self.event_start(event_size),
+          position: None,
+          original_commit_timestamp: gtid.original_commit_timestamp(),
+          transaction_length: gtid.transaction_length(),
+        };
+        if let Ok(lag) = SystemTime::now().duration_since(transaction.committed_at(header)) {
+          self.metrics.set_replication_lag(lag);
+        }
+        self.next_transaction = Some(transaction);
+        None
+      }
+      BinlogEvent::Xid(_) => return Ok(self.end_transaction()),
+      BinlogEvent::Rotate(rotate) => {
+        self.log_file = Some(rotate.next_log_name_str().to_string());
+        None
+      }
+      BinlogEvent::Format(_) | BinlogEvent::PreviousGtids(_) => None,
+      BinlogEvent::Stop => Some(ChangeEvent::SourceRestart {
+        position: self
+          .log_file
+          .as_ref()
+          .map(|file| BinlogPosition::new(file.clone(), self.log_pos)),
+      }),
+      // `Connection` expands them, but not `BinlogFile`.
+      BinlogEvent::TransactionPayload(_) => {
+        return Err(unexpected_err(
+          "TRANSACTION_PAYLOAD_EVENT must be expanded with TransactionPayloadEvent::events",
+        ))
+      }
+      // Events parsed by a newer tail_mysql_core, without changes to decode yet.
+      _ => None,
+    };
+    let change = change
+      .and_then(|change| self.apply_shadow_tables(change))
+      .map(|change| self.route(change));
+
+    if change
+      .as_ref()
+      .is_some_and(|change| !matches!(change, ChangeEvent::SchemaDrift { .. }))
+    {
+      if let Some(ref mut transaction) = self.transaction {
+        transaction.event_count += 1;
+      }
+    }
+
+    Ok(change)
+  }
+
+  // Records the number of columns of the table, and reports when it changed.
+  fn observe_column_count(&mut self, table_map: &TableMapEvent) -> Option<ChangeEvent> {
+    let name = (
+      table_map.schema_str().to_string(),
+      table_map.table_str().to_string(),
+    );
+    let column_count = table_map.column_count();
+    let previous_column_count = self.column_counts.insert(name.clone(), column_count)?;
+    if previous_column_count == column_count {
+      return None;
+    }
+
+    self.metrics.incr_schema_drifts();
+    if !self.emit_schema_drift {
+      return None;
+    }
+    Some(ChangeEvent::SchemaDrift {
+      schema: name.0,
+      table: name.1,
+      previous_column_count,
+      column_count,
+      column_names: table_map.column_names().to_vec(),
+    })
+  }
+
+  fn is_expired(&self, header: &EventHeader) -> bool {
+    let max_age = match self.max_event_age {
+      Some(max_age) => max_age,
+      None => return false,
+    };
+    // Ages by the original commit of the transaction, since the header of its events can be
+    // stamped by an intermediate server of the replication chain.
+    let logged_at = match self.transaction {
+      Some(ref transaction) => transaction.committed_at(header),
+      None => UNIX_EPOCH + Duration::from_secs(u64::from(header.timestamp())),
+    };
+    SystemTime::now()
+      .duration_since(logged_at)
+      .map(|age| age > max_age)
+      .unwrap_or(false)
+  }
+
+  fn begin_transaction(&mut self, event_size: u64) -> Option<ChangeEvent> {
+    let mut transaction = self.next_transaction.take().unwrap_or_default();
+    transaction.byte_size += event_size;
+    if transaction.start_position.is_none() {
+      transaction.start_position = self.event_start(event_size);
+    }
+    self.transaction = Some(transaction.clone());
+
+    if self.emit_transaction_markers {
+      Some(ChangeEvent::Begin(transaction))
+    } else {
+      None
+    }
+  }
+
+  // Position of the current event, of `event_size` bytes.
+  fn event_start(&self, event_size: u64) -> Option<BinlogPosition> {
+    let start = u64::from(self.log_pos).saturating_sub(event_size) as u32;
+    let file = self.log_file.as_ref()?;
+    Some(BinlogPosition::new(file.clone(), start))
+  }
+
+  fn end_transaction(&mut self) -> Option<ChangeEvent> {
+    let mut transaction = self.transaction.take()?;
+    transaction.position = self
+      .log_file
+      .as_ref()
+      .map(|file| BinlogPosition::new(file.clone(), self.log_pos));
+
+    if self.emit_transaction_markers {
+      if let (Some(watermarks), Some(position)) = (&self.watermarks, &transaction.position) {
+        watermarks.seen(position.clone());
+      }
+      Some(ChangeEvent::End(transaction))
+    } else {
+      None
+    }
+  }
+
+  // Resolves the table of the rows event and decodes its images, which come in before and after
+  // pairs when `updates` is set. Returns `None` when the table map is unknown, e.g. when the stream
+  // started in the middle of a transaction, or when every row was sampled out.
+  #[allow(clippy::type_complexity)]
+  fn decode_rows(
+    &self,
+    rows: &RowEvent,
+    updates: bool,
+  ) -> io::Result<Option<(String, String, Vec<ColumnType>, Vec<Row>)>> {
+    let table_map = match self.tables.get(&rows.table_id()) {
+      Some(table_map) => table_map,
+      None => {
+        let dropped = self.metrics.dropped();
+        dropped.record_table("", "", DropReason::UnknownTable, None);
+        return Ok(None);
+      }
+    };
+
+    let schema = table_map.schema_str();
+    let table = table_map.table_str();
+    // Rows are never decoded against the columns of another version of the table.
+    if rows.column_count() != table_map.column_count() {
+      return Err(unexpected_err(format!(
+        "rows of {}.{} have {} columns, its table map {}",
+        schema,
+        table,
+        rows.column_count(),
+        table_map.column_count()
+      )));
+    }
+    let mut images = rows.decode_rows(table_map)?;
+
+    // Sampled before the value limits, so that dropped values are not offloaded.
+    if let Some(rule) = self.sampling.rule(schema, table) {
+      let count = images.len();
+      images = if updates {
+        // Updates are sampled by their after image.
+        let mut kept = Vec::with_capacity(count);
+        for (before, after) in pair_images(images) {
+          if rule.keep(&after) {
+            kept.push(before);
+            kept.push(after);
+          }
+        }
+        kept
+      } else {
+        images.into_iter().filter(|row| rule.keep(row)).collect()
+      };
+
+      let sampled_out = count - images.len();
+      if sampled_out > 0 {
+        let sampled_out = if updates {
+          sampled_out / 2
+        } else {
+          sampled_out
+        };
+        self.metrics.add_sampled_out_rows(sampled_out as u64);
+      }
+      if images.is_empty() {
+        let dropped = self.metrics.dropped();
+        dropped.record_table(schema, table, DropReason::Sampling, None);
+        return Ok(None);
+      }
+    }
+
+    let invalid =
+      self
+        .invalid_temporals
+        .apply(schema, table, table_map.column_types(), images.iter_mut())?;
+    if invalid > 0 {
+      self.metrics.add_invalid_temporal_values(invalid);
+    }
+
+    #[cfg(feature = "redaction")]
+    {
+      let column_names = table_map.column_names();
+      let redacted = self
+        .redaction
+        .apply(schema, table, column_names, images.iter_mut());
+      if redacted > 0 {
+        self.metrics.add_redacted_values(redacted);
+      }
+    }
+
+    // Encrypted before the value limits, so that sensitive values are not offloaded in clear.
+    #[cfg(feature = "encryption")]
+    if let Some(ref encryption) = self.encryption {
+      let encrypted = encryption.apply(schema, table, images.iter_mut())?;
+      if encrypted > 0 {
+        self.metrics.add_encrypted_values(encrypted);
+      }
+    }
+
+    let limited = self.value_limits.apply(schema, table, images.iter_mut())?;
+    if limited > 0 {
+      self.metrics.add_limited_values(limited);
+    }
+
+    Ok(Some((
+      schema.to_string(),
+      table.to_string(),
+      table_map.column_types().to_vec(),
+      images,
+    )))
+  }
+
+  fn decode_query(
+    &mut self,
+    query: QueryEvent,
+    event_size: u64,
+    expired: bool,
+  ) -> Option<ChangeEvent> {
+    let schema = query.schema_str();
+    let classification = classify(query.query_str());
+    let operation = classification.operation();
+
+    match operation {
+      Operation::Begin => return self.begin_transaction(event_size),
+      Operation::Commit | Operation::Rollback => return self.end_transaction(),
+      _ => {}
+    }
+
+    let tables: Vec<TableName> = classification
+      .into_tables()
+      .into_iter()
+      .map(|table| table.or_schema(schema))
+      .collect();
+
+    if operation.is_ddl() {
+      let cut_over = migration::cut_over(query.query_str());
+      if let Some((ref table, tool)) = cut_over {
+        let table = table.clone().or_schema(schema);
+        eprintln!(
+          "online schema migration of {}.{} ({:?}) cut over",
+          table.schema_str().unwrap_or_default(),
+          table.table_str(),
+          tool
+        );
+        self.metrics.incr_migration_cut_overs();
+      }
+      self.router.observe_ddl(schema, query.query_str());
+      self.invalidate(&tables);
+      if !self.emit_ddl {
+        return None;
+      }
+      if cut_over.is_some() && self.shadow_tables != ShadowTablePolicy::Keep {
+        self.metrics.incr_shadow_table_events();
+        return None;
+      }
+    } else if !operation.is_dml() {
+      return None;
+    } else if expired {
+      self.metrics.incr_expired_events();
+      return None;
+    } else if self.metrics.incr_statement_events() == 0 {
+      eprintln!(
+        "warning: received a statement based DML event, binlog_format is not set to ROW. \
+         Row images will not be available for these changes."
+      );
+    }
+
+    let change = self
+      .apply_shadow_tables(ChangeEvent::Statement {
+        schema: schema.to_string(),
+        sql: query.query_str().to_string(),
+        operation,
+        tables,
+        time_zone: query.time_zone_str().map(Into::into),
+      })
+      .map(|change| self.route(change))?;
+
+    if let Some(ref mut transaction) = self.transaction {
+      transaction.event_count += 1;
+    }
+
+    Some(change)
+  }
+
+  // Drops or remaps the changes to shadow tables, according to `shadow_tables`. Statements are
+  // matched by the first table they touch, which is the one written to.
+  fn apply_shadow_tables(&self, mut change: ChangeEvent) -> Option<ChangeEvent> {
+    if self.shadow_tables == ShadowTablePolicy::Keep {
+      return Some(change);
+    }
+
+    let (table, statement) = match change {
+      ChangeEvent::Insert { ref mut table, .. }
+      | ChangeEvent::Update { ref mut table, .. }
+      | ChangeEvent::Delete { ref mut table, .. }
+      | ChangeEvent::SchemaDrift { ref mut table, .. } => (table, None),
+      ChangeEvent::Statement {
+        ref mut tables,
+        ref mut sql,
+        operation,
+        ..
+      } => match tables.first_mut() {
+        Some(table) => (table.table_mut(), Some((sql, operation))),
+        None => return Some(change),
+      },
+      ChangeEvent::Begin(_) | ChangeEvent::End(_) | ChangeEvent::SourceRestart { .. } => {
+        return Some(change)
+      }
+    };
+
+    let shadow = match migration::shadow_table(table) {
+      Some(shadow) => shadow,
+      None => return Some(change),
+    };
+    // Only the schema change of the migration, its rows duplicate the ones of the original table.
+    if let (ShadowTablePolicy::Remap, ShadowKind::Ghost, Some((sql, Operation::Ddl))) =
+      (self.shadow_tables, shadow.kind(), statement)
+    {
+      if is_alter_table(sql) {
+        let logical = shadow.table_str().to_string();
+        *sql = migration::rename_table(sql, table, &logical);
+        *table = logical;
+        return Some(change);
+      }
+    }
+
+    self.metrics.incr_shadow_table_events();
+    self
+      .metrics
+      .dropped()
+      .record(DropReason::ShadowTable, &change);
+    None
+  }
+
+  // Renames the tables of the change to their logical names, see `TableRouter`.
+  fn route(&self, mut change: ChangeEvent) -> ChangeEvent {
+    match change {
+      ChangeEvent::Insert {
+        ref mut schema,
+        ref mut table,
+        ..
+      }
+      | ChangeEvent::Update {
+        ref mut schema,
+        ref mut table,
+        ..
+      }
+      | ChangeEvent::Delete {
+        ref mut schema,
+        ref mut table,
+        ..
+      }
+      | ChangeEvent::SchemaDrift {
+        ref mut schema,
+        ref mut table,
+        ..
+      } => {
+        if let Some((logical_schema, logical_table)) = self.router.resolve(schema, table) {
+          *schema = logical_schema.to_string();
+          *table = logical_table.to_string();
+        }
+      }
+      ChangeEvent::Statement { ref mut tables, .. } => {
+        for table in tables.iter_mut() {
+          let logical = table
+            .schema_str()
+            .and_then(|schema| self.router.resolve(schema, table.table_str()));
+          if let Some((schema, name)) = logical {
+            *table = TableName::new(Some(schema.to_string()), name);
+          }
+        }
+      }
+      ChangeEvent::Begin(_) | ChangeEvent::End(_) | ChangeEvent::SourceRestart { .. } => {}
+    }
+    change
+  }
+
+  // Forgets the table maps of tables whose schema changed. When the touched tables are unknown,
+  // every table map is dropped.
+  fn invalidate(&mut self, tables: &[TableName]) {
+    if tables.is_empty() {
+      self.tables.clear();
+      return;
+    }
+
+    self.tables.retain(|_, table_map| {
+      !tables.iter().any(|table| {
+        table.schema_str() == Some(table_map.schema_str())
+          && table.table_str() == table_map.table_str()
+      })
+    });
+  }
+}
+
+// The ghost table of gh-ost and pt-osc is created, then altered to the new schema.
+fn is_alter_table(sql: &str) -> bool {
+  let mut words = sql.split_whitespace();
+  let alter = words
+    .next()
+    .is_some_and(|word| word.eq_ignore_ascii_case("ALTER"));
+  alter
+    && words
+      .next()
+      .is_some_and(|word| word.eq_ignore_ascii_case("TABLE"))
+}
+
+fn pair_images(images: Vec<Row>) -> Vec<(Row, Row)> {
+  let mut images = images.into_iter();
+  let mut rows = Vec::new();
+  while let (Some(before), Some(after)) = (images.next(), images.next()) {
+    rows.push((before, after));
+  }
+  rows
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::limits::ValueLimit;
+  use crate::protocol_binlog::BinlogEventPacket;
+  use crate::sampling::{ColumnPredicate, SampleRule};
+  use crate::value::Value;
+
+  const ANONYMOUS_GTID_EVENT : &[u8] = b"\x00\xfc\x5a\x5d\x5d\x22\x01\x00\x00\x00\x3d\x00\x00\x00\xd3\x00\x00\
+                                         \x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+                                         \x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x02\x00\x00\x00\x00\x00\
+                                         \x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00";
+
+  const QUERY_EVENT : &[u8] = b"\x00\xfc\x5a\x5d\x5d\x02\x01\x00\x00\x00\x44\x00\x00\x00\x17\x01\x00\
+                                \x00\x08\x00\x3b\x18\x00\x00\x00\x00\x00\x00\x04\x00\x00\x1a\x00\x00\
+                                \x00\x00\x00\x00\x01\x00\x00\x00\x40\x00\x00\x00\x00\x06\x03\x73\x74\
+                                \x64\x04\x21\x00\x21\x00\x2d\x00\x70\x65\x74\x73\x00\x42\x45\x47\x49\
+                                \x4e";
+
+  const TABLE_MAP_EVENT : &[u8] = b"\x00\xfc\x5a\x5d\x5d\x13\x01\x00\x00\x00\x32\x00\x00\x00\x49\x01\x00\
+                                    \x00\x00\x00\x2d\x0a\x00\x00\x00\x00\x01\x00\x04\x70\x65\x74\x73\x00\
+                                    \x04\x63\x61\x74\x73\x00\x04\x03\x0f\x0f\x0a\x04\x58\x02\x58\x02\x00";
+
+  const INSERT_ROW_EVENT : &[u8] = b"\x00\xfc\x5a\x5d\x5d\x1e\x01\x00\x00\x00\x37\x00\x00\x00\x80\x01\x00\
+                                     \x00\x00\x00\x2d\x0a\x00\x00\x00\x00\x01\x00\x02\x00\x04\xff\xf0\x04\
+                                     \x00\x00\x00\x07\x00\x43\x68\x61\x72\x6c\x69\x65\x05\x00\x52\x69\x76\
+                                     \x65\x72\xb5\xc0\x0f";
+
+  const ROTATE_EVENT : &[u8] = b"\x00\x00\x00\x00\x00\x04\x01\x00\x00\x00\x2d\x00\x00\x00\x00\x00\x00\
+                                 \x00\x20\x00\x96\x00\x00\x00\x00\x00\x00\x00\x73\x68\x6f\x70\x69\x66\
+                                 \x79\x2d\x62\x69\x6e\x2e\x30\x30\x30\x30\x30\x35";
+
+  const XID_EVENT: &[u8] = b"\x00\xfc\x5a\x5d\x5d\x10\x01\x00\x00\x00\x1b\x00\x00\x00\x9b\x01\x00\
+                              \x00\x00\x00\x72\x0e\x00\x00\x00\x00\x00\x00";
+
+  fn decode_all(decoder: &mut EventDecoder) -> Vec<ChangeEvent> {
+    let events = vec![
+      ANONYMOUS_GTID_EVENT,
+      QUERY_EVENT,
+      TABLE_MAP_EVENT,
+      INSERT_ROW_EVENT,
+      XID_EVENT,
+    ];
+
+    events
+      .into_iter()
+      .filter_map(|bytes| {
+        let packet = BinlogEventPacket::parse(bytes).unwrap();
+        let header = packet.header();
+        decoder
+          .decode(&header, packet.into_binlog_event().unwrap())
+          .unwrap()
+      })
+      .collect()
+  }
+
+  // QUERY_EVENT of `sql` against `pets`.
+  fn query_event(sql: &str) -> BinlogEvent {
+    let mut bytes = QUERY_EVENT[..QUERY_EVENT.len() - b"BEGIN".len()].to_vec();
+    bytes.extend_from_slice(sql.as_bytes());
+    let event_size = (bytes.len() - 1) as u32;
+    bytes[10..14].copy_from_slice(&event_size.to_le_bytes());
+    BinlogEventPacket::parse(bytes)
+      .unwrap()
+      .into_binlog_event()
+      .unwrap()
+  }
+
+  fn decode_query(decoder: &mut EventDecoder, sql: &str) -> Option<ChangeEvent> {
+    match query_event(sql) {
+      BinlogEvent::Query(query) => decoder.decode_query(query, 0, false),
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+  }
+
+  #[test]
+  fn decodes_rows_events() {
+    let mut decoder = EventDecoder::new(Arc::new(Metrics::default()));
+    match decode_all(&mut decoder).as_slice() {
+      [ChangeEvent::Insert {
+        schema,
+        table,
+        column_types,
+        rows,
+      }] => {
+        assert_eq!("pets", schema);
+        assert_eq!("cats", table);
+        assert_eq!(
+          &[
+            ColumnType::MYSQL_TYPE_LONG,
+            ColumnType::MYSQL_TYPE_VARCHAR,
+            ColumnType::MYSQL_TYPE_VARCHAR,
+            ColumnType::MYSQL_TYPE_DATE,
+          ],
+          column_types.as_slice()
+        );
+        assert_eq!(
+          &[Row::new(vec![
+            Some(Value::Int(4)),
+            Some(Value::Bytes(b"Charlie".to_vec())),
+            Some(Value::Bytes(b"River".to_vec())),
+            Some(Value::Date {
+              year: 2016,
+              month: 5,
+              day: 21,
+              hour: 0,
+              minute: 0,
+              second: 0,
+              micro: 0,
+            }),
+          ])],
+          rows.as_slice()
+        );
+      }
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+  }
+
+  #[test]
+  fn marks_source_restarts() {
+    const STOP_EVENT: &[u8] =
+      b"\x00\xfc\x5a\x5d\x5d\x03\x01\x00\x00\x00\x13\x00\x00\x00\x2e\x02\x00\
+        \x00\x00\x00";
+
+    let mut decoder = EventDecoder::new(Arc::new(Metrics::default()));
+    let changes: Vec<ChangeEvent> = [ROTATE_EVENT, STOP_EVENT]
+      .iter()
+      .filter_map(|bytes| {
+        let packet = BinlogEventPacket::parse(bytes.to_vec()).unwrap();
+        let header = packet.header();
+        decoder
+          .decode(&header, packet.into_binlog_event().unwrap())
+          .unwrap()
+      })
+      .collect();
+    match changes.as_slice() {
+      [ChangeEvent::SourceRestart {
+        position: Some(position),
+      }] => assert_eq!(&BinlogPosition::new("shopify-bin.000005", 558), position),
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+  }
+
+  #[test]
+  fn applies_value_limits() {
+    let metrics = Arc::new(Metrics::default());
+    let limits = ValueLimits::new().column_limit("pets", "cats", 1, ValueLimit::truncate(4));
+    let mut decoder = EventDecoder::new(metrics.clone()).value_limits(limits);
+    match decode_all(&mut decoder).as_slice() {
+      [ChangeEvent::Insert { rows, .. }] => assert_eq!(
+        Some(&Value::Truncated {
+          prefix: b"Char".to_vec(),
+          size: 7
+        }),
+        rows[0].get(1)
+      ),
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+    assert_eq!(1, metrics.limited_values());
+  }
+
+  #[test]
+  fn emits_transaction_markers() {
+    let mut decoder =
+      EventDecoder::new(Arc::new(Metrics::default())).emit_transaction_markers(true);
+    let rotate = BinlogEventPacket::parse(ROTATE_EVENT.to_vec()).unwrap();
+    let header = rotate.header();
+    decoder
+      .decode(&header, rotate.into_binlog_event().unwrap())
+      .unwrap();
+    match decode_all(&mut decoder).as_slice() {
+      [ChangeEvent::Begin(begin), ChangeEvent::Insert { .. }, ChangeEvent::End(end)] => {
+        assert_eq!(None, begin.gtid());
+        // At the anonymous GTID_EVENT.
+        assert_eq!(
+          Some(&BinlogPosition::new("shopify-bin.000005", 150)),
+          begin.start_position()
+        );
+        assert_eq!(0, begin.event_count());
+        assert_eq!(61 + 68, begin.byte_size());
+        assert_eq!(1, end.event_count());
+        assert_eq!(61 + 68 + 50 + 55 + 27, end.byte_size());
+      }
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+  }
+
+  #[test]
+  fn formats_changes() {
+    let mut decoder =
+      EventDecoder::new(Arc::new(Metrics::default())).emit_transaction_markers(true);
+    let lines: Vec<String> = decode_all(&mut decoder)
+      .iter()
+      .map(ChangeEvent::to_string)
+      .collect();
+    assert_eq!(
+      vec![
+        "BEGIN",
+        "INSERT pets.cats (4, 'Charlie', 'River', '2016-05-21 00:00:00')",
+        "COMMIT (1 events)",
+      ],
+      lines
+    );
+
+    let update = ChangeEvent::Update {
+      schema: "pets".to_string(),
+      table: "cats".to_string(),
+      column_types: vec![ColumnType::MYSQL_TYPE_LONG, ColumnType::MYSQL_TYPE_BLOB],
+      rows: vec![(
+        Row::new(vec![
+          Some(Value::Int(4)),
+          Some(Value::Bytes(vec![0xff, 0x00])),
+        ]),
+        Row::new(vec![Some(Value::Int(4)), None]),
+      )],
+    };
+    assert_eq!("UPDATE pets.cats (4, 0xff00) -> (4, _)", update.to_string());
+
+    let statement = ChangeEvent::Statement {
+      schema: "pets".to_string(),
+      sql: "UPDATE cats\n  SET name = 'Tom'".to_string(),
+      operation: Operation::Update,
+      tables: vec![],
+      time_zone: None,
+    };
+    assert_eq!(
+      "STATEMENT pets: UPDATE cats SET name = 'Tom'",
+      statement.to_string()
+    );
+
+    let restart = ChangeEvent::SourceRestart {
+      position: Some(BinlogPosition::new("mysql-bin.000002", 1201)),
+    };
+    assert_eq!(
+      "SOURCE RESTART at mysql-bin.000002:1201",
+      restart.to_string()
+    );
+  }
+
+  #[test]
+  fn applies_shadow_table_policy() {
+    let insert = |table: &str| ChangeEvent::Insert {
+      schema: "pets".to_string(),
+      table: table.to_string(),
+      column_types: Vec::new(),
+      rows: Vec::new(),
+    };
+    let table = |change: Option<ChangeEvent>| match change {
+      Some(ChangeEvent::Insert { table, .. }) => Some(table),
+      _ => None,
+    };
+
+    let metrics = Arc::new(Metrics::default());
+    let decoder = EventDecoder::new(metrics.clone());
+    assert_eq!(
+      Some("_cats_gho".to_string()),
+      table(decoder.apply_shadow_tables(insert("_cats_gho")))
+    );
+
+    // The copy of the rows, and the replay of the changes of the original table, are not emitted
+    // a second time.
+    let decoder = EventDecoder::new(metrics.clone()).shadow_tables(ShadowTablePolicy::Remap);
+    let changes: Vec<_> = ["cats", "_cats_gho", "_cats_ghc"]
+      .iter()
+      .filter_map(|name| table(decoder.apply_shadow_tables(insert(name))))
+      .collect();
+    assert_eq!(vec!["cats".to_string()], changes);
+
+    // Only the ALTER TABLE of the ghost table is remapped, statement included.
+    let statement = |sql: &str, operation| ChangeEvent::Statement {
+      schema: "pets".to_string(),
+      sql: sql.to_string(),
+      operation,
+      tables: vec![TableName::new(Some("pets".to_string()), "_cats_gho")],
+      time_zone: None,
+    };
+    let alter = "ALTER TABLE `_cats_gho` ADD COLUMN age INT";
+    match decoder.apply_shadow_tables(statement(alter, Operation::Ddl)) {
+      Some(ChangeEvent::Statement { sql, tables, .. }) => {
+        assert_eq!("ALTER TABLE `cats` ADD COLUMN age INT", sql);
+        assert_eq!("cats", tables[0].table_str());
+      }
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+    let copy = "INSERT INTO `_cats_gho` SELECT * FROM `cats`";
+    assert!(decoder
+      .apply_shadow_tables(statement(copy, Operation::Insert))
+      .is_none());
+
+    let decoder = EventDecoder::new(metrics.clone()).shadow_tables(ShadowTablePolicy::Suppress);
+    assert_eq!(
+      None,
+      table(decoder.apply_shadow_tables(insert("_cats_new")))
+    );
+    assert_eq!(
+      Some("cats".to_string()),
+      table(decoder.apply_shadow_tables(insert("cats")))
+    );
+    assert_eq!(4, metrics.shadow_table_events());
+  }
+
+  #[test]
+  fn drops_expired_events() {
+    let metrics = Arc::new(Metrics::default());
+    // The fixtures were logged in 2019.
+    let mut decoder = EventDecoder::new(metrics.clone())
+      .emit_transaction_markers(true)
+      .max_event_age(Duration::from_secs(24 * 60 * 60));
+    match decode_all(&mut decoder).as_slice() {
+      [ChangeEvent::Begin(_), ChangeEvent::End(end)] => assert_eq!(0, end.event_count()),
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+    assert_eq!(1, metrics.expired_events());
+    let dropped = metrics.dropped();
+    assert_eq!(1, dropped.count("pets", "cats", DropReason::Expired));
+
+    let mut decoder = EventDecoder::new(metrics.clone())
+      .max_event_age(Duration::from_secs(100 * 365 * 24 * 60 * 60));
+    assert_eq!(1, decode_all(&mut decoder).len());
+    assert_eq!(1, metrics.expired_events());
+  }
+
+  #[test]
+  fn ages_events_by_original_commit_timestamp() {
+    let metrics = Arc::new(Metrics::default());
+    let mut decoder = EventDecoder::new(metrics.clone());
+    decode_all(&mut decoder);
+    assert!(metrics.replication_lag() > Duration::from_secs(365 * 24 * 60 * 60));
+
+    // Committed just now on the source, logged in 2019 by an intermediate server.
+    let committed_at = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    let mut gtid = ANONYMOUS_GTID_EVENT.to_vec();
+    gtid.extend_from_slice(&(committed_at.as_micros() as u64).to_le_bytes()[..7]);
+    let event_size = (gtid.len() - 1) as u32;
+    gtid[10..14].copy_from_slice(&event_size.to_le_bytes());
+
+    let mut decoder = EventDecoder::new(metrics.clone()).max_event_age(Duration::from_secs(60));
+    let changes: Vec<_> = [&gtid[..], QUERY_EVENT, TABLE_MAP_EVENT, INSERT_ROW_EVENT]
+      .iter()
+      .filter_map(|bytes| {
+        let packet = BinlogEventPacket::parse(bytes.to_vec()).unwrap();
+        let header = packet.header();
+        decoder
+          .decode(&header, packet.into_binlog_event().unwrap())
+          .unwrap()
+      })
+      .collect();
+    assert_eq!(1, changes.len());
+    assert_eq!(0, metrics.expired_events());
+    assert!(metrics.replication_lag() < Duration::from_secs(60));
+  }
+
+  #[test]
+  fn emits_ddl() {
+    let metrics = Arc::new(Metrics::default());
+    let mut decoder = EventDecoder::new(metrics.clone());
+    assert!(decode_query(&mut decoder, "ALTER TABLE cats ADD COLUMN age INT").is_none());
+
+    let mut decoder = EventDecoder::new(metrics.clone()).emit_ddl(true);
+    match decode_query(&mut decoder, "ALTER TABLE cats ADD COLUMN age INT") {
+      Some(ChangeEvent::Statement {
+        schema,
+        sql,
+        operation,
+        ..
+      }) => {
+        assert_eq!("pets", schema);
+        assert_eq!("ALTER TABLE cats ADD COLUMN age INT", sql);
+        assert_eq!(Operation::Ddl, operation);
+      }
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+    assert_eq!(0, metrics.statement_events());
+
+    // The shadow tables of the migration were not replicated.
+    let cut_over = "RENAME TABLE `cats` TO `_cats_del`, `_cats_gho` TO `cats`";
+    assert!(decode_query(&mut decoder, cut_over).is_some());
+    let mut decoder = EventDecoder::new(metrics.clone())
+      .emit_ddl(true)
+      .shadow_tables(ShadowTablePolicy::Suppress);
+    assert!(decode_query(&mut decoder, cut_over).is_none());
+    assert_eq!(1, metrics.shadow_table_events());
+  }
+
+  #[test]
+  fn routes_renamed_tables() {
+    let router = TableRouter::new().route("pets", "cats", "pets", "felines");
+    let mut decoder = EventDecoder::new(Arc::new(Metrics::default())).table_router(router);
+    match decode_all(&mut decoder).as_slice() {
+      [ChangeEvent::Insert { schema, table, .. }] => {
+        assert_eq!("pets", schema);
+        assert_eq!("felines", table);
+      }
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+
+    let router = TableRouter::new().follow_renames(true);
+    let mut decoder = EventDecoder::new(Arc::new(Metrics::default())).table_router(router);
+    decoder
+      .router
+      .observe_ddl("pets", "ALTER TABLE kittens RENAME TO cats");
+    match decode_all(&mut decoder).as_slice() {
+      [ChangeEvent::Insert { table, .. }] => assert_eq!("kittens", table),
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+    assert_eq!(1, decoder.router().history().len());
+  }
+
+  #[test]
+  fn samples_rows() {
+    let metrics = Arc::new(Metrics::default());
+    let charlie = ColumnPredicate::Equals(Value::Bytes(b"Charlie".to_vec()));
+    let sampling = Sampling::new().table("pets", "cats", SampleRule::matching(1, charlie));
+    let mut decoder = EventDecoder::new(metrics.clone()).sampling(sampling);
+    assert_eq!(1, decode_all(&mut decoder).len());
+
+    let river = ColumnPredicate::Equals(Value::Bytes(b"River".to_vec()));
+    let sampling = Sampling::new().table("pets", "cats", SampleRule::matching(1, river));
+    let mut decoder = EventDecoder::new(metrics.clone()).sampling(sampling);
+    assert!(decode_all(&mut decoder).is_empty());
+    assert_eq!(1, metrics.sampled_out_rows());
+    let dropped = metrics.dropped();
+    assert_eq!(1, dropped.count("pets", "cats", DropReason::Sampling));
+  }
+
+  #[test]
+  fn reports_schema_drift() {
+    let decode = |decoder: &mut EventDecoder, bytes: Vec<u8>| {
+      let packet = BinlogEventPacket::parse(bytes).unwrap();
+      let header = packet.header();
+      decoder.decode(&header, packet.into_binlog_event().unwrap())
+    };
+    let metrics = Arc::new(Metrics::default());
+    let mut decoder = EventDecoder::new(metrics.clone()).emit_schema_drift(true);
+    assert!(decode(&mut decoder, TABLE_MAP_EVENT.to_vec())
+      .unwrap()
+      .is_none());
+
+    // pets.cats without its last column, the DATE.
+    let mut narrower = TABLE_MAP_EVENT.to_vec();
+    narrower[40] = 3;
+    narrower.remove(44);
+    let event_size = (narrower.len() - 1) as u32;
+    narrower[10..14].copy_from_slice(&event_size.to_le_bytes());
+    match decode(&mut decoder, narrower).unwrap() {
+      Some(ChangeEvent::SchemaDrift {
+        schema,
+        table,
+        previous_column_count,
+        column_count,
+        column_names,
+      }) => {
+        assert_eq!(("pets", "cats"), (schema.as_str(), table.as_str()));
+        assert_eq!((4, 3), (previous_column_count, column_count));
+        assert!(column_names.is_empty());
+      }
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+    assert_eq!(1, metrics.schema_drifts());
+
+    // Rows of the previous version of the table are not decoded against the new one.
+    assert!(decode(&mut decoder, INSERT_ROW_EVENT.to_vec()).is_err());
+  }
+}
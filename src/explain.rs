@@ -0,0 +1,222 @@
+// Typed output of `EXPLAIN`, in its traditional tabular format.
+//
+// https://dev.mysql.com/doc/refman/8.0/en/explain-output.html
+
+/// How MYSQL accesses the rows of a table, the `type` column. From the best to the worst.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum AccessType {
+  System,
+  Const,
+  EqRef,
+  Ref,
+  FullText,
+  RefOrNull,
+  IndexMerge,
+  UniqueSubquery,
+  IndexSubquery,
+  Range,
+  /// Full scan of an index.
+  Index,
+  /// Full table scan.
+  All,
+  /// No table is accessed, e.g. `SELECT 1` or an impossible WHERE.
+  None,
+  Other(String),
+}
+
+impl AccessType {
+  fn parse(access_type: Option<&str>) -> Self {
+    match access_type {
+      Some("system") => AccessType::System,
+      Some("const") => AccessType::Const,
+      Some("eq_ref") => AccessType::EqRef,
+      Some("ref") => AccessType::Ref,
+      Some("fulltext") => AccessType::FullText,
+      Some("ref_or_null") => AccessType::RefOrNull,
+      Some("index_merge") => AccessType::IndexMerge,
+      Some("unique_subquery") => AccessType::UniqueSubquery,
+      Some("index_subquery") => AccessType::IndexSubquery,
+      Some("range") => AccessType::Range,
+      Some("index") => AccessType::Index,
+      Some("ALL") => AccessType::All,
+      Some(other) => AccessType::Other(other.to_string()),
+      None => AccessType::None,
+    }
+  }
+}
+
+/// Access to a single table of the plan.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ExplainRow {
+  id: Option<u64>,
+  select_type: String,
+  table: Option<String>,
+  access_type: AccessType,
+  possible_keys: Vec<String>,
+  key: Option<String>,
+  rows: Option<u64>,
+  filtered: Option<f64>,
+  extra: Option<String>,
+}
+
+impl ExplainRow {
+  // `column` returns the value of a column of the EXPLAIN result set, `None` for NULLs and the
+  // columns missing in older versions (e.g. `filtered` before 5.7).
+  pub(crate) fn parse<'a>(column: impl Fn(&str) -> Option<&'a str>) -> Self {
+    let list = |value: Option<&str>| {
+      value
+        .map(|keys| keys.split(',').map(Into::into).collect())
+        .unwrap_or_default()
+    };
+    Self {
+      id: column("id").and_then(|id| id.parse().ok()),
+      select_type: column("select_type").unwrap_or_default().to_string(),
+      table: column("table").map(Into::into),
+      access_type: AccessType::parse(column("type")),
+      possible_keys: list(column("possible_keys")),
+      key: column("key").map(Into::into),
+      rows: column("rows").and_then(|rows| rows.parse().ok()),
+      filtered: column("filtered").and_then(|filtered| filtered.parse().ok()),
+      extra: column("Extra").map(Into::into),
+    }
+  }
+
+  /// Identifier of the SELECT the row belongs to, `None` for the result of a UNION.
+  pub fn id(&self) -> Option<u64> {
+    self.id
+  }
+
+  pub fn select_type_str(&self) -> &str {
+    self.select_type.as_str()
+  }
+
+  pub fn table_str(&self) -> Option<&str> {
+    self.table.as_deref()
+  }
+
+  pub fn access_type(&self) -> &AccessType {
+    &self.access_type
+  }
+
+  pub fn possible_keys(&self) -> &[String] {
+    self.possible_keys.as_slice()
+  }
+
+  /// Index actually used, `None` when the table is not accessed through an index.
+  pub fn key_str(&self) -> Option<&str> {
+    self.key.as_deref()
+  }
+
+  /// Estimated number of rows examined.
+  pub fn rows(&self) -> Option<u64> {
+    self.rows
+  }
+
+  /// Estimated percentage of the examined rows left once the conditions are applied.
+  pub fn filtered(&self) -> Option<f64> {
+    self.filtered
+  }
+
+  pub fn extra_str(&self) -> Option<&str> {
+    self.extra.as_deref()
+  }
+
+  pub fn is_full_scan(&self) -> bool {
+    self.access_type == AccessType::All
+  }
+}
+
+/// Execution plan of a query, see `Connection::explain`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Explain {
+  rows: Vec<ExplainRow>,
+}
+
+impl Explain {
+  pub(crate) fn new(rows: Vec<ExplainRow>) -> Self {
+    Self { rows }
+  }
+
+  pub fn rows(&self) -> &[ExplainRow] {
+    self.rows.as_slice()
+  }
+
+  /// Returns true when a table is fully scanned.
+  pub fn is_full_scan(&self) -> bool {
+    self.rows.iter().any(ExplainRow::is_full_scan)
+  }
+
+  /// Returns true when every table is accessed through `key`, e.g. `PRIMARY`.
+  pub fn uses_key(&self, key: &str) -> bool {
+    self
+      .rows
+      .iter()
+      .filter(|row| row.access_type != AccessType::None)
+      .all(|row| row.key_str() == Some(key))
+  }
+
+  /// Estimated number of rows examined, across every table.
+  pub fn estimated_rows(&self) -> u64 {
+    self.rows.iter().filter_map(ExplainRow::rows).sum()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn row(columns: &[(&str, &'static str)]) -> ExplainRow {
+    let columns = columns.to_vec();
+    ExplainRow::parse(move |name| {
+      columns
+        .iter()
+        .find(|(column, _)| *column == name)
+        .map(|(_, value)| *value)
+    })
+  }
+
+  #[test]
+  fn parses_plans() {
+    let range = row(&[
+      ("id", "1"),
+      ("select_type", "SIMPLE"),
+      ("table", "cats"),
+      ("type", "range"),
+      ("possible_keys", "PRIMARY,index_cats_on_name"),
+      ("key", "PRIMARY"),
+      ("rows", "998"),
+      ("filtered", "100.00"),
+      ("Extra", "Using where"),
+    ]);
+    assert_eq!(Some(1), range.id());
+    assert_eq!(&AccessType::Range, range.access_type());
+    assert_eq!(
+      &["PRIMARY".to_string(), "index_cats_on_name".to_string()],
+      range.possible_keys()
+    );
+    assert_eq!(Some(100.0), range.filtered());
+
+    let plan = Explain::new(vec![range.clone()]);
+    assert!(plan.uses_key("PRIMARY"));
+    assert!(!plan.is_full_scan());
+
+    // MYSQL 5.6 has no `filtered` column.
+    let scan = row(&[
+      ("id", "1"),
+      ("table", "dogs"),
+      ("type", "ALL"),
+      ("rows", "2000000"),
+    ]);
+    assert_eq!(None, scan.filtered());
+    assert_eq!(None, scan.key_str());
+
+    let plan = Explain::new(vec![range, scan]);
+    assert!(plan.is_full_scan());
+    assert!(!plan.uses_key("PRIMARY"));
+    assert_eq!(2_000_998, plan.estimated_rows());
+
+    let nothing = row(&[("id", "1"), ("Extra", "No tables used")]);
+    assert_eq!(&AccessType::None, nothing.access_type());
+    assert!(Explain::new(vec![nothing]).uses_key("PRIMARY"));
+  }
+}
@@ -0,0 +1,270 @@
+// Test cases of the binlog parsers generated from captured events, in the layout of the tests of
+// tail_mysql_core's protocol_binlog.rs: the event as an escaped byte string, parsed, and the
+// values of its accessors asserted. See `main inspect --fixture`.
+//
+// The assertions record what the parser returns today, they are worth a review before pasting:
+// an event that fails to decode gets a test that fails until the parser is fixed.
+
+use super::protocol_binlog::{BinlogEvent, BinlogEventPacket};
+use std::fmt::Write;
+use std::io;
+
+// As in the existing tests.
+const BYTES_PER_LINE: usize = 17;
+
+/// Generates a test case for `event`, as stored in binlog files. The byte string starts with the
+/// OK byte of the network protocol, as `BinlogEventPacket::parse` expects.
+pub fn test_case(event: &[u8]) -> io::Result<String> {
+  let packet = BinlogEventPacket::parse_event(event.to_vec())?;
+  let header = packet.header();
+  let event_type = format!("{:?}", packet.event_type());
+  let name = event_type
+    .to_lowercase()
+    .replace("_event", "_")
+    .trim_matches('_')
+    .to_string();
+
+  let mut out = String::new();
+  let _ = writeln!(out, "  #[test]\n  fn parses_{}() {{", name);
+  let prefix = format!("    const {} : &[u8] = b\"", event_type);
+  out.push_str(&prefix);
+  let bytes: Vec<u8> = std::iter::once(0).chain(event.iter().copied()).collect();
+  for (i, line) in bytes.chunks(BYTES_PER_LINE).enumerate() {
+    if i > 0 {
+      out.push_str("\\\n");
+      out.push_str(&" ".repeat(prefix.len()));
+    }
+    for b in line {
+      let _ = write!(out, "\\x{:02x}", b);
+    }
+  }
+  out.push_str("\";\n\n");
+
+  let _ = writeln!(
+    out,
+    "    let event = BinlogEventPacket::parse({}).unwrap();",
+    event_type
+  );
+  let _ = writeln!(
+    out,
+    "    assert_eq!(EventType::{}, event.event_type());",
+    event_type
+  );
+  let _ = writeln!(
+    out,
+    "    assert_eq!({}, event.header().event_size());",
+    header.event_size()
+  );
+  let _ = writeln!(
+    out,
+    "    assert_eq!({}, event.header().log_pos());",
+    header.log_pos()
+  );
+
+  match packet.into_binlog_event().map(|event| assertions(&event)) {
+    Ok(Some((variant, assertions))) => {
+      out.push_str("    match event.into_binlog_event().unwrap() {\n");
+      if assertions.is_empty() {
+        // Events without fields, e.g. STOP_EVENT.
+        let _ = writeln!(out, "      BinlogEvent::{} => {{}}", variant);
+      } else {
+        let _ = writeln!(out, "      BinlogEvent::{}(packet) => {{", variant);
+        for assertion in assertions {
+          let _ = writeln!(out, "        {}", assertion);
+        }
+        out.push_str("      }\n");
+      }
+      out.push_str("      unexpected => panic!(\"unexpected {:?}\", unexpected),\n");
+      out.push_str("    }\n");
+    }
+    Ok(None) => {
+      out
+        .push_str("    // No assertions on the variants parsed after the generator was written.\n");
+      out.push_str("    event.into_binlog_event().unwrap();\n");
+    }
+    Err(err) => {
+      let _ = writeln!(out, "    // Failed to decode when captured: {}", err);
+      out.push_str("    event.into_binlog_event().unwrap();\n");
+    }
+  }
+  out.push_str("  }\n");
+  Ok(out)
+}
+
+// Variant of `event`, and the assertions on the values of its accessors. `None` for the variants
+// that do not have any yet.
+fn assertions(event: &BinlogEvent) -> Option<(&'static str, Vec<String>)> {
+  let eq =
+    |expected: String, accessor: &str| format!("assert_eq!({}, packet.{}());", expected, accessor);
+  let assertions = match event {
+    BinlogEvent::Query(query) => (
+      "Query",
+      vec![
+        eq(query.thread_id().to_string(), "thread_id"),
+        eq(query.exec_time().to_string(), "exec_time"),
+        eq(query.error_code().to_string(), "error_code"),
+        eq(format!("{:?}", query.schema_str()), "schema_str"),
+        eq(format!("{:?}", query.query_str()), "query_str"),
+        eq(format!("{:?}", query.time_zone_str()), "time_zone_str"),
+      ],
+    ),
+    BinlogEvent::Xid(xid) => ("Xid", vec![eq(xid.xid().to_string(), "xid")]),
+    BinlogEvent::Gtid(gtid) => (
+      "Gtid",
+      vec![
+        eq(gtid.is_anonymous().to_string(), "is_anonymous"),
+        eq(format!("{:?}", gtid.gtid()), "gtid"),
+        eq(format!("{:?}", gtid.last_committed()), "last_committed"),
+        eq(format!("{:?}", gtid.sequence_number()), "sequence_number"),
+        eq(
+          format!("{:?}", gtid.immediate_commit_timestamp()),
+          "immediate_commit_timestamp",
+        ),
+        eq(
+          format!("{:?}", gtid.original_commit_timestamp()),
+          "original_commit_timestamp",
+        ),
+        eq(
+          format!("{:?}", gtid.transaction_length()),
+          "transaction_length",
+        ),
+      ],
+    ),
+    BinlogEvent::PreviousGtids(previous_gtids) => (
+      "PreviousGtids",
+      vec![eq(format!("{:?}", previous_gtids.gtid_set()), "gtid_set")],
+    ),
+    BinlogEvent::TableMap(table_map) => {
+      let column_types: Vec<String> = table_map
+        .column_types()
+        .iter()
+        .map(|column_type| format!("ColumnType::{:?}", column_type))
+        .collect();
+      let mut assertions = vec![
+        eq(table_map.table_id().to_string(), "table_id"),
+        eq(table_map.flags().to_string(), "flags"),
+        eq(table_map.column_count().to_string(), "column_count"),
+        eq(format!("{:?}", table_map.schema_str()), "schema_str"),
+        eq(format!("{:?}", table_map.table_str()), "table_str"),
+        eq(format!("&[{}]", column_types.join(", ")), "column_types"),
+        eq(format!("&{:?}", table_map.column_metas()), "column_metas"),
+      ];
+      // The type of an empty `&[]` can not be inferred.
+      assertions.push(match table_map.column_names() {
+        [] => "assert!(packet.column_names().is_empty());".to_string(),
+        names => eq(format!("&{:?}", names), "column_names"),
+      });
+      ("TableMap", assertions)
+    }
+    BinlogEvent::Rotate(rotate) => (
+      "Rotate",
+      vec![
+        eq(rotate.position().to_string(), "position"),
+        eq(
+          format!("{:?}", rotate.next_log_name_str()),
+          "next_log_name_str",
+        ),
+      ],
+    ),
+    BinlogEvent::Format(format) => (
+      "Format",
+      vec![
+        eq(format.version().to_string(), "version"),
+        eq(
+          format!("{:?}", format.server_version_str()),
+          "server_version_str",
+        ),
+        eq(format.create_timestamp().to_string(), "create_timestamp"),
+        eq(format.has_checksums().to_string(), "has_checksums"),
+      ],
+    ),
+    BinlogEvent::Insert(rows) | BinlogEvent::Update(rows) | BinlogEvent::Delete(rows) => {
+      let variant = match event {
+        BinlogEvent::Insert(_) => "Insert",
+        BinlogEvent::Update(_) => "Update",
+        _ => "Delete",
+      };
+      (
+        variant,
+        vec![
+          eq(rows.table_id().to_string(), "table_id"),
+          eq(rows.flags().to_string(), "flags"),
+          eq(rows.column_count().to_string(), "column_count"),
+        ],
+      )
+    }
+    BinlogEvent::TransactionPayload(payload) => (
+      "TransactionPayload",
+      vec![
+        eq(
+          format!("PayloadCompression::{:?}", payload.compression()),
+          "compression",
+        ),
+        eq(payload.uncompressed_size().to_string(), "uncompressed_size"),
+      ],
+    ),
+    BinlogEvent::Stop => ("Stop", Vec::new()),
+    _ => return None,
+  };
+  Some(assertions)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn generates_test_cases() {
+    // ROTATE_EVENT of the parser tests, without the OK byte.
+    let rotate = b"\x00\x00\x00\x00\x04\x01\x00\x00\x00\x2d\x00\x00\x00\x00\x00\x00\x00\x20\x00\
+                   \x96\x00\x00\x00\x00\x00\x00\x00\x73\x68\x6f\x70\x69\x66\x79\x2d\x62\x69\x6e\
+                   \x2e\x30\x30\x30\x30\x30\x35";
+    assert_eq!(
+      r#"  #[test]
+  fn parses_rotate() {
+    const ROTATE_EVENT : &[u8] = b"\x00\x00\x00\x00\x00\x04\x01\x00\x00\x00\x2d\x00\x00\x00\x00\x00\x00\
+                                   \x00\x20\x00\x96\x00\x00\x00\x00\x00\x00\x00\x73\x68\x6f\x70\x69\x66\
+                                   \x79\x2d\x62\x69\x6e\x2e\x30\x30\x30\x30\x30\x35";
+
+    let event = BinlogEventPacket::parse(ROTATE_EVENT).unwrap();
+    assert_eq!(EventType::ROTATE_EVENT, event.event_type());
+    assert_eq!(45, event.header().event_size());
+    assert_eq!(0, event.header().log_pos());
+    match event.into_binlog_event().unwrap() {
+      BinlogEvent::Rotate(packet) => {
+        assert_eq!(150, packet.position());
+        assert_eq!("shopify-bin.000005", packet.next_log_name_str());
+      }
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+  }
+"#,
+      test_case(rotate).unwrap()
+    );
+
+    // TABLE_MAP_EVENT of the parser tests, logged without the column names.
+    let table_map = b"\xfc\x5a\x5d\x5d\x13\x01\x00\x00\x00\x32\x00\x00\x00\x49\x01\x00\x00\x00\
+                      \x00\x2d\x0a\x00\x00\x00\x00\x01\x00\x04\x70\x65\x74\x73\x00\x04\x63\x61\
+                      \x74\x73\x00\x04\x03\x0f\x0f\x0a\x04\x58\x02\x58\x02\x00";
+    let test_case = test_case(table_map).unwrap();
+    assert!(test_case.contains("  fn parses_table_map() {\n"));
+    assert!(test_case.contains(
+      "        assert_eq!(&[ColumnType::MYSQL_TYPE_LONG, ColumnType::MYSQL_TYPE_VARCHAR, \
+       ColumnType::MYSQL_TYPE_VARCHAR, ColumnType::MYSQL_TYPE_DATE], packet.column_types());\n\
+       \x20       assert_eq!(&[0, 600, 600, 0], packet.column_metas());\n\
+       \x20       assert!(packet.column_names().is_empty());\n"
+    ));
+  }
+
+  #[test]
+  fn generates_failing_test_cases_for_undecodable_events() {
+    // A QUERY_EVENT cut short.
+    let query = b"\xfc\x5a\x5d\x5d\x02\x01\x00\x00\x00\x17\x00\x00\x00\x17\x01\x00\x00\x08\x00\x3b";
+    let test_case = test_case(query).unwrap();
+    assert!(test_case.contains("fn parses_query() {"));
+    assert!(test_case.contains("    // Failed to decode when captured: "));
+    assert!(test_case.ends_with("    event.into_binlog_event().unwrap();\n  }\n"));
+
+    assert!(super::test_case(b"\xfc\x5a").is_err());
+  }
+}
@@ -0,0 +1,269 @@
+// Entry points for the cargo-fuzz targets under fuzz/, which can only reach the public API. Only
+// compiled with the `fuzzing` feature, nothing here is meant to be used otherwise.
+//
+// Every function parses untrusted bytes and discards the result, the fuzzer is only interested
+// in panics, hangs and out of memory errors. The unit tests below audit the same entry points
+// against mutations of the fuzz corpus, so that the no-panic policy holds without a fuzzer.
+
+use super::protocol::{CapabilityFlags, ColumnDefinitionResponse, ColumnType, Packet};
+use super::protocol_binlog::{BinlogEvent, BinlogEventPacket, BinlogFile};
+use super::value::Value;
+use bytes::{BufMut, BytesMut};
+use std::convert::TryFrom;
+
+// Size of the binlog event header, with the leading OK byte.
+const EVENT_HEADER_LEN: usize = 20;
+
+/// Parses a framed MYSQL packet.
+pub fn packet(data: &[u8]) {
+  let _ = Packet::parse(&mut &data[..]);
+}
+
+/// Parses the initial handshake sent by the server.
+pub fn handshake(data: &[u8]) {
+  if let Some(packet) = frame(data) {
+    let _ = packet
+      .as_payload()
+      .as_handshake_response(CapabilityFlags::empty());
+  }
+}
+
+/// Parses the payload of a response to a command, as every kind of response the client expects.
+pub fn response(data: &[u8]) {
+  let capabilities = CapabilityFlags::CLIENT_PROTOCOL_41
+    | CapabilityFlags::CLIENT_TRANSACTIONS
+    | CapabilityFlags::CLIENT_SESSION_TRACK;
+  let parse = || frame(data).map(Packet::as_payload);
+
+  if let Some(payload) = parse() {
+    let _ = payload.as_generic_response(capabilities);
+  }
+  if let Some(payload) = parse() {
+    let _ = payload.as_auth_response(capabilities);
+  }
+  if let Some(payload) = parse() {
+    let _ = payload.as_query_response(capabilities);
+  }
+  if let Some(payload) = parse() {
+    let _ = payload.as_server_err(capabilities);
+  }
+  if let Some(payload) = parse() {
+    // the payload is both the column definition, and the row.
+    let column = match payload.as_column_definition_response(capabilities) {
+      Ok(ColumnDefinitionResponse::ColumnDefinition(column)) => column,
+      _ => return,
+    };
+    if let Some(payload) = parse() {
+      let _ = payload.as_row_response(capabilities, &[column]);
+    }
+  }
+}
+
+/// Parses a single value of a binlog row image and of the binary protocol, unsigned when the low
+/// bit of `meta` is set.
+pub fn value(column_type: u8, meta: u16, data: &[u8]) {
+  if let Ok(column_type) = ColumnType::try_from(column_type) {
+    let _ = Value::parse_from_binlog(&mut &data[..], column_type, meta, meta & 1 == 1);
+    let _ = Value::parse_from_binary(&mut &data[..], column_type, meta & 1 == 1);
+  }
+}
+
+/// Parses a binlog event, with its header.
+pub fn binlog_event(data: &[u8]) {
+  if let Ok(packet) = BinlogEventPacket::parse(data.to_vec()) {
+    let _ = packet.into_binlog_event();
+  }
+}
+
+/// Parses the events of a binlog file.
+pub fn binlog_file(data: &[u8]) {
+  if let Ok(file) = BinlogFile::parse(data.to_vec()) {
+    for packet in file.flatten() {
+      let _ = packet.into_binlog_event();
+    }
+  }
+}
+
+/// Parses the body of a binlog event of type `event_type`.
+pub fn event(event_type: u8, body: &[u8]) {
+  binlog_event(&with_header(event_type, body));
+}
+
+/// Parses a TABLE_MAP_EVENT body and a rows event of type `event_type`, then decodes the rows
+/// images of the latter with the former.
+pub fn rows(table_map: &[u8], event_type: u8, body: &[u8]) {
+  let table_map = match parse(&with_header(0x13, table_map)) {
+    Some(BinlogEvent::TableMap(table_map)) => table_map,
+    _ => return,
+  };
+
+  match parse(&with_header(event_type, body)) {
+    Some(BinlogEvent::Insert(rows))
+    | Some(BinlogEvent::Update(rows))
+    | Some(BinlogEvent::Delete(rows)) => {
+      let _ = rows.decode_rows(&table_map);
+    }
+    _ => {}
+  }
+}
+
+fn parse(data: &[u8]) -> Option<BinlogEvent> {
+  BinlogEventPacket::parse(data.to_vec())
+    .and_then(BinlogEventPacket::into_binlog_event)
+    .ok()
+}
+
+fn frame(payload: &[u8]) -> Option<Packet> {
+  let mut b = BytesMut::with_capacity(4 + payload.len());
+  b.put_uint_le(payload.len() as u64, 3);
+  b.put_u8(0);
+  b.put(payload);
+  Packet::parse(&mut &b[..]).ok()
+}
+
+fn with_header(event_type: u8, body: &[u8]) -> Vec<u8> {
+  let event_size = EVENT_HEADER_LEN - 1 + body.len();
+
+  let mut b = BytesMut::with_capacity(EVENT_HEADER_LEN + body.len());
+  b.put_u8(0x00); // OK
+  b.put_u32_le(0); // timestamp
+  b.put_u8(event_type);
+  b.put_u32_le(1); // server_id
+  b.put_u32_le(event_size as u32);
+  b.put_u32_le(0); // log_pos
+  b.put_u16_le(0); // flags
+  b.put(body);
+  b.to_vec()
+}
+
+#[cfg(test)]
+mod test {
+  use proptest::prelude::*;
+  use std::panic::{self, AssertUnwindSafe};
+
+  const PACKETS: &[&[u8]] = &[
+    include_bytes!("../fuzz/corpus/packet/anonymous_gtid_event"),
+    include_bytes!("../fuzz/corpus/packet/format_description_event"),
+    include_bytes!("../fuzz/corpus/packet/insert_row_event"),
+    include_bytes!("../fuzz/corpus/packet/query_event"),
+    include_bytes!("../fuzz/corpus/packet/rotate_event"),
+    include_bytes!("../fuzz/corpus/packet/table_map_event"),
+    include_bytes!("../fuzz/corpus/packet/xid_event"),
+  ];
+
+  const BINLOG_EVENTS: &[&[u8]] = &[
+    include_bytes!("../fuzz/corpus/binlog_event/anonymous_gtid_event"),
+    include_bytes!("../fuzz/corpus/binlog_event/format_description_event"),
+    include_bytes!("../fuzz/corpus/binlog_event/insert_row_event"),
+    include_bytes!("../fuzz/corpus/binlog_event/query_event"),
+    include_bytes!("../fuzz/corpus/binlog_event/rotate_event"),
+    include_bytes!("../fuzz/corpus/binlog_event/table_map_event"),
+    include_bytes!("../fuzz/corpus/binlog_event/xid_event"),
+  ];
+
+  const BINLOG_FILE: &[u8] = include_bytes!("../fuzz/corpus/binlog_file/mysql_5_7");
+  const HANDSHAKE: &[u8] = include_bytes!("../fuzz/corpus/handshake/mysql_5_7");
+  const TABLE_MAP: &[u8] = include_bytes!("../fuzz/corpus/table_map_event/table_map_event");
+  const INSERT_ROWS: &[u8] = include_bytes!("../fuzz/corpus/rows_event/insert_row_event");
+  const RESPONSES: &[&[u8]] = &[
+    include_bytes!("../fuzz/corpus/response/ok"),
+    include_bytes!("../fuzz/corpus/response/err"),
+    include_bytes!("../fuzz/corpus/response/column_definition"),
+  ];
+
+  // Every truncation of the input, and the input with each of its bytes replaced by values that
+  // tend to hit edge cases (lengths, signs, length-encoded integer markers).
+  fn mutations(input: &[u8]) -> impl Iterator<Item = Vec<u8>> + '_ {
+    let truncations = (0..input.len()).map(move |len| input[..len].to_vec());
+    let replacements = (0..input.len()).flat_map(move |i| {
+      [0x00, 0x01, 0x7f, 0x80, 0xfb, 0xfc, 0xfe, 0xff]
+        .iter()
+        .map(move |byte| {
+          let mut mutated = input.to_vec();
+          mutated[i] = *byte;
+          mutated
+        })
+    });
+    truncations.chain(replacements)
+  }
+
+  fn assert_no_panic(name: &str, input: &[u8], parse: impl Fn(&[u8])) {
+    let result = panic::catch_unwind(AssertUnwindSafe(|| parse(input)));
+    assert!(result.is_ok(), "{} panicked on {:02x?}", name, input);
+  }
+
+  fn audit(name: &str, input: &[u8], parse: impl Fn(&[u8])) {
+    for mutated in mutations(input) {
+      assert_no_panic(name, &mutated, &parse);
+    }
+  }
+
+  #[test]
+  fn packets_do_not_panic() {
+    for packet in PACKETS {
+      audit("packet", packet, super::packet);
+    }
+  }
+
+  #[test]
+  fn handshakes_do_not_panic() {
+    audit("handshake", HANDSHAKE, super::handshake);
+  }
+
+  #[test]
+  fn responses_do_not_panic() {
+    for response in RESPONSES {
+      audit("response", response, super::response);
+    }
+  }
+
+  #[test]
+  fn binlog_events_do_not_panic() {
+    for event in BINLOG_EVENTS {
+      audit("binlog_event", event, super::binlog_event);
+    }
+  }
+
+  #[test]
+  fn binlog_files_do_not_panic() {
+    audit("binlog_file", BINLOG_FILE, super::binlog_file);
+  }
+
+  #[test]
+  fn rows_do_not_panic() {
+    // WRITE_ROWS_EVENTV2 and UPDATE_ROWS_EVENTV2, the latter reads 2 bitmaps.
+    for event_type in &[0x1e, 0x1f] {
+      audit("rows", TABLE_MAP, |table_map| {
+        super::rows(table_map, *event_type, INSERT_ROWS)
+      });
+      audit("rows", INSERT_ROWS, |body| {
+        super::rows(TABLE_MAP, *event_type, body)
+      });
+    }
+  }
+
+  proptest! {
+    #[test]
+    fn arbitrary_bytes_do_not_panic(data in proptest::collection::vec(any::<u8>(), 0..256)) {
+      assert_no_panic("packet", &data, super::packet);
+      assert_no_panic("handshake", &data, super::handshake);
+      assert_no_panic("response", &data, super::response);
+      assert_no_panic("binlog_event", &data, super::binlog_event);
+      assert_no_panic("binlog_file", &data, super::binlog_file);
+      for event_type in 0..=0x23 {
+        assert_no_panic("event", &data, |body| super::event(event_type, body));
+      }
+    }
+
+    // Metadata is mostly small in practice, and that is also where widths and precisions are
+    // just slightly out of range.
+    #[test]
+    fn arbitrary_values_do_not_panic(
+      column_type in prop_oneof![0x00_u8..=0x13, 0xf5_u8..=0xff],
+      meta in prop_oneof![0_u16..=0x40, any::<u16>()],
+      data in proptest::collection::vec(any::<u8>(), 0..64),
+    ) {
+      assert_no_panic("value", &data, |data| super::value(column_type, meta, data));
+    }
+  }
+}
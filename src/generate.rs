@@ -0,0 +1,493 @@
+// Synthetic INSERT/UPDATE/DELETE traffic against the tables of a test database, to benchmark and
+// soak test pipelines. The statements are drawn from a seeded PRNG, so that a run can be repeated.
+//
+// Values are random within the type of every column, as listed in information_schema: integers
+// within their width, strings within their length (up to 32 characters), members of ENUMs and
+// SETs, dates within the range of TIMESTAMPs. Auto-increment and generated columns are left to
+// the server, as are the columns of types it does not know (e.g. spatial types), which must then
+// be nullable or have a default.
+//
+// UPDATEs and DELETEs pick a random row by its rank in the primary key, out of the rows counted
+// when the table was described plus the ones inserted since: the statements of other clients are
+// not accounted for, and a rank past the end of the table changes nothing.
+
+use super::conn::{Connection, DriverError};
+use super::util::{quote_ident, quote_literal, XorShift};
+use futures::io::{AsyncRead, AsyncWrite};
+
+type DriverResult<T> = Result<T, DriverError>;
+
+// Longest strings generated.
+const MAX_STRING_LEN: u64 = 32;
+// One value out of `NULL_RATE` of nullable columns is NULL.
+const NULL_RATE: u64 = 10;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Kind {
+  Integer { bits: u32, unsigned: bool },
+  Decimal { precision: u32, scale: u32 },
+  Float,
+  Date,
+  Datetime,
+  Timestamp,
+  Time,
+  Year,
+  String { max_len: u64 },
+  Members(Vec<String>),
+  Json,
+  Bit { bits: u32 },
+}
+
+#[derive(Clone, Debug)]
+struct Column {
+  name: String,
+  nullable: bool,
+  // `None` for the columns left to the server.
+  kind: Option<Kind>,
+}
+
+struct Table {
+  name: String,
+  columns: Vec<Column>,
+  primary_key: Vec<String>,
+  rows: u64,
+}
+
+/// Generates statements against the tables described with `describe_table`.
+pub struct LoadGenerator {
+  rng: XorShift,
+  // Weights of INSERTs, UPDATEs and DELETEs.
+  mix: [u32; 3],
+  tables: Vec<Table>,
+}
+
+impl LoadGenerator {
+  /// Generates as many INSERTs, UPDATEs and DELETEs unless `mix` is set.
+  pub fn new(seed: u64) -> Self {
+    Self {
+      rng: XorShift::new(seed),
+      mix: [1, 1, 1],
+      tables: Vec::new(),
+    }
+  }
+
+  /// Relative weights of INSERTs, UPDATEs and DELETEs, e.g. `(5, 3, 1)`. Statements fall back to
+  /// INSERTs while a table is empty.
+  pub fn mix(mut self, inserts: u32, updates: u32, deletes: u32) -> Self {
+    self.mix = [inserts, updates, deletes];
+    self
+  }
+
+  /// Reads the columns, primary key and number of rows of `schema`.`table`, which statements are
+  /// then generated against.
+  pub async fn describe_table<S: AsyncRead + AsyncWrite + Unpin>(
+    &mut self,
+    conn: &mut Connection<S>,
+    schema: &str,
+    table: &str,
+  ) -> DriverResult<()> {
+    let sql = format!(
+      "SELECT COLUMN_NAME, DATA_TYPE, COLUMN_TYPE, IS_NULLABLE, COLUMN_KEY, EXTRA, \
+       CHARACTER_MAXIMUM_LENGTH, NUMERIC_PRECISION, NUMERIC_SCALE FROM information_schema.COLUMNS \
+       WHERE TABLE_SCHEMA = {} AND TABLE_NAME = {} ORDER BY ORDINAL_POSITION",
+      quote_literal(schema),
+      quote_literal(table)
+    );
+    let results = conn.query(sql).await?;
+    let mut columns = Vec::new();
+    let mut primary_key = Vec::new();
+    for row in results.iter() {
+      let text = |index: usize| row.values().get(index).and_then(|value| value.as_str());
+      let number = |index: usize| text(index).and_then(|value| value.parse::<u64>().ok());
+      let name = text(0).ok_or(DriverError::UnexpectedPacket)?;
+      if text(4) == Some("PRI") {
+        primary_key.push(name.to_string());
+      }
+      let extra = text(5).unwrap_or_default().to_ascii_lowercase();
+      let kind = if extra.contains("auto_increment") || extra.contains("generated") {
+        None
+      } else {
+        column_kind(
+          text(1).unwrap_or_default(),
+          text(2).unwrap_or_default(),
+          number(6),
+          number(7),
+          number(8),
+        )
+      };
+      columns.push(Column {
+        name: name.to_string(),
+        nullable: text(3) == Some("YES"),
+        kind,
+      });
+    }
+    if columns.is_empty() {
+      return Err(DriverError::Unsupported(format!(
+        "generating changes to {}.{}, which does not exist",
+        schema, table
+      )));
+    }
+
+    let name = format!("{}.{}", quote_ident(schema), quote_ident(table));
+    let rows = conn
+      .pop(format!("SELECT COUNT(*) FROM {}", name))
+      .await?
+      .and_then(|row| row.values().first()?.as_str()?.parse().ok())
+      .ok_or(DriverError::UnexpectedPacket)?;
+    self.tables.push(Table {
+      name,
+      columns,
+      primary_key,
+      rows,
+    });
+    Ok(())
+  }
+
+  /// Next statement to run, against a random table. `None` until a table is described.
+  pub fn next_statement(&mut self) -> Option<String> {
+    if self.tables.is_empty() {
+      return None;
+    }
+    let index = self.rng.below(self.tables.len() as u64) as usize;
+    let total = self
+      .mix
+      .iter()
+      .map(|weight| u64::from(*weight))
+      .sum::<u64>()
+      .max(1);
+    let mut draw = self.rng.below(total);
+    let mut operation = 0;
+    for (i, weight) in self.mix.iter().enumerate() {
+      if draw < u64::from(*weight) {
+        operation = i;
+        break;
+      }
+      draw -= u64::from(*weight);
+    }
+
+    let rng = &mut self.rng;
+    let table = &mut self.tables[index];
+    let statement = match operation {
+      1 if table.rows > 0 => update(rng, table),
+      2 if table.rows > 0 => Some(delete(rng, table)),
+      _ => None,
+    };
+    statement.or_else(|| Some(insert(rng, table)))
+  }
+}
+
+fn insert(rng: &mut XorShift, table: &mut Table) -> String {
+  let (names, values): (Vec<String>, Vec<String>) = table
+    .columns
+    .iter()
+    .filter_map(|column| Some((quote_ident(&column.name), value(rng, column)?)))
+    .unzip();
+  table.rows += 1;
+  format!(
+    "INSERT INTO {} ({}) VALUES ({})",
+    table.name,
+    names.join(", "),
+    values.join(", ")
+  )
+}
+
+// Sets a random column, out of the primary key, of a random row. `None` when every column is part
+// of the primary key or left to the server.
+fn update(rng: &mut XorShift, table: &Table) -> Option<String> {
+  let columns: Vec<&Column> = table
+    .columns
+    .iter()
+    .filter(|column| column.kind.is_some() && !table.primary_key.contains(&column.name))
+    .collect();
+  if columns.is_empty() {
+    return None;
+  }
+  let column = columns[rng.below(columns.len() as u64) as usize];
+  let value = value(rng, column)?;
+  Some(format!(
+    "UPDATE {} SET {} = {} {}",
+    table.name,
+    quote_ident(&column.name),
+    value,
+    random_row(rng, table)
+  ))
+}
+
+fn delete(rng: &mut XorShift, table: &mut Table) -> String {
+  let statement = format!("DELETE FROM {} {}", table.name, random_row(rng, table));
+  table.rows -= 1;
+  statement
+}
+
+// WHERE clause of a random row. MYSQL supports neither OFFSET in UPDATEs and DELETEs nor LIMIT in
+// IN subqueries, but does in derived tables. Tables without a primary key lose an arbitrary row.
+fn random_row(rng: &mut XorShift, table: &Table) -> String {
+  if table.primary_key.is_empty() {
+    return "LIMIT 1".to_string();
+  }
+  let key: Vec<String> = table
+    .primary_key
+    .iter()
+    .map(|column| quote_ident(column))
+    .collect();
+  let key = key.join(", ");
+  format!(
+    "WHERE ({}) IN (SELECT {} FROM (SELECT {} FROM {} ORDER BY {} LIMIT {}, 1) AS target)",
+    key,
+    key,
+    key,
+    table.name,
+    key,
+    rng.below(table.rows)
+  )
+}
+
+// SQL literal of a random value of `column`, `None` for the columns left to the server.
+fn value(rng: &mut XorShift, column: &Column) -> Option<String> {
+  let kind = column.kind.as_ref()?;
+  if column.nullable && rng.below(NULL_RATE) == 0 {
+    return Some("NULL".to_string());
+  }
+
+  let literal = match kind {
+    Kind::Integer { bits: 64, unsigned } => {
+      let value = rng.next_u64();
+      if *unsigned {
+        value.to_string()
+      } else {
+        (value as i64).to_string()
+      }
+    }
+    Kind::Integer { bits, unsigned } => {
+      let value = rng.below(1 << bits);
+      if *unsigned {
+        value.to_string()
+      } else {
+        (value as i64 - (1 << (bits - 1))).to_string()
+      }
+    }
+    Kind::Decimal { precision, scale } => {
+      let digits = |rng: &mut XorShift, count: u32| -> String {
+        (0..count)
+          .map(|_| char::from(b'0' + rng.below(10) as u8))
+          .collect()
+      };
+      let integer = digits(rng, (precision - scale).clamp(1, 18));
+      match scale {
+        0 => integer,
+        scale => format!("{}.{}", integer, digits(rng, (*scale).min(18))),
+      }
+    }
+    Kind::Float => format!("{:.2}", (rng.below(2_000_000) as f64 - 1e6) / 100.0),
+    Kind::Date => quote_literal(&random_date(rng)),
+    Kind::Datetime | Kind::Timestamp => {
+      quote_literal(&format!("{} {}", random_date(rng), random_time(rng)))
+    }
+    Kind::Time => quote_literal(&random_time(rng)),
+    Kind::Year => (1901 + rng.below(255)).to_string(),
+    Kind::String { max_len } => {
+      let len = 1 + rng.below((*max_len).clamp(1, MAX_STRING_LEN));
+      let alphabet = b"abcdefghijklmnopqrstuvwxyz0123456789";
+      let string: String = (0..len)
+        .map(|_| char::from(alphabet[rng.below(alphabet.len() as u64) as usize]))
+        .collect();
+      quote_literal(&string)
+    }
+    Kind::Members(members) if members.is_empty() => quote_literal(""),
+    Kind::Members(members) => quote_literal(&members[rng.below(members.len() as u64) as usize]),
+    Kind::Json => quote_literal(&format!("{{\"n\": {}}}", rng.below(1_000_000))),
+    Kind::Bit { bits } => format!("b'{:b}'", rng.below(1 << (*bits).min(63))),
+  };
+  Some(literal)
+}
+
+// Between 2000 and 2029, TIMESTAMPs end in 2038. Days stop at 28, every month has them.
+fn random_date(rng: &mut XorShift) -> String {
+  format!(
+    "{:04}-{:02}-{:02}",
+    2000 + rng.below(30),
+    1 + rng.below(12),
+    1 + rng.below(28)
+  )
+}
+
+fn random_time(rng: &mut XorShift) -> String {
+  format!(
+    "{:02}:{:02}:{:02}",
+    rng.below(24),
+    rng.below(60),
+    rng.below(60)
+  )
+}
+
+// Kind of the values of a column, from the DATA_TYPE and COLUMN_TYPE of information_schema, e.g.
+// `int` and `int(10) unsigned`.
+fn column_kind(
+  data_type: &str,
+  column_type: &str,
+  max_len: Option<u64>,
+  precision: Option<u64>,
+  scale: Option<u64>,
+) -> Option<Kind> {
+  let unsigned = column_type.to_ascii_lowercase().contains("unsigned");
+  let integer = |bits| Some(Kind::Integer { bits, unsigned });
+  match data_type.to_ascii_lowercase().as_str() {
+    "tinyint" => integer(8),
+    "smallint" => integer(16),
+    "mediumint" => integer(24),
+    "int" | "integer" => integer(32),
+    "bigint" => integer(64),
+    "decimal" | "numeric" => Some(Kind::Decimal {
+      precision: precision.unwrap_or(10) as u32,
+      scale: scale.unwrap_or(0) as u32,
+    }),
+    "float" | "double" | "real" => Some(Kind::Float),
+    "date" => Some(Kind::Date),
+    "datetime" => Some(Kind::Datetime),
+    "timestamp" => Some(Kind::Timestamp),
+    "time" => Some(Kind::Time),
+    "year" => Some(Kind::Year),
+    "char" | "varchar" | "binary" | "varbinary" | "tinytext" | "text" | "mediumtext"
+    | "longtext" | "tinyblob" | "blob" | "mediumblob" | "longblob" => Some(Kind::String {
+      max_len: max_len.unwrap_or(MAX_STRING_LEN),
+    }),
+    "enum" | "set" => Some(Kind::Members(members(column_type))),
+    "json" => Some(Kind::Json),
+    "bit" => Some(Kind::Bit {
+      bits: precision.unwrap_or(1) as u32,
+    }),
+    _ => None,
+  }
+}
+
+// Members of `enum('a','b')` or `set('a','b')`, whose quotes are doubled.
+fn members(column_type: &str) -> Vec<String> {
+  let list = match (column_type.find('('), column_type.rfind(')')) {
+    (Some(start), Some(end)) if start < end => &column_type[start + 1..end],
+    _ => return Vec::new(),
+  };
+  let mut members = Vec::new();
+  let mut chars = list.chars().peekable();
+  while let Some(c) = chars.next() {
+    if c != '\'' {
+      continue;
+    }
+    let mut member = String::new();
+    while let Some(c) = chars.next() {
+      match c {
+        '\'' if chars.peek() == Some(&'\'') => {
+          chars.next();
+          member.push('\'');
+        }
+        '\'' => break,
+        c => member.push(c),
+      }
+    }
+    members.push(member);
+  }
+  members
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn column(name: &str, nullable: bool, kind: Option<Kind>) -> Column {
+    Column {
+      name: name.to_string(),
+      nullable,
+      kind,
+    }
+  }
+
+  fn cats(seed: u64, rows: u64) -> LoadGenerator {
+    let mut generator = LoadGenerator::new(seed);
+    generator.tables.push(Table {
+      name: "`pets`.`cats`".to_string(),
+      columns: vec![
+        column("id", false, None),
+        column("name", false, Some(Kind::String { max_len: 8 })),
+        column("born_on", true, Some(Kind::Date)),
+        column(
+          "mood",
+          false,
+          Some(Kind::Members(members("enum('calm','it''s')"))),
+        ),
+      ],
+      primary_key: vec!["id".to_string()],
+      rows,
+    });
+    generator
+  }
+
+  #[test]
+  fn generates_statements() {
+    let mut generator = cats(42, 0).mix(0, 1, 1);
+    // The table is empty, the first statement can only be an INSERT.
+    let insert = generator.next_statement().unwrap();
+    assert!(
+      insert.starts_with("INSERT INTO `pets`.`cats` (`name`, `born_on`, `mood`) VALUES ('"),
+      "{}",
+      insert
+    );
+    let statement = generator.next_statement().unwrap();
+    assert!(
+      statement.ends_with(
+        "WHERE (`id`) IN (SELECT `id` FROM (SELECT `id` FROM `pets`.`cats` ORDER BY `id` \
+         LIMIT 0, 1) AS target)"
+      ),
+      "{}",
+      statement
+    );
+
+    // The same seed generates the same statements.
+    let statements = |seed| {
+      let mut generator = cats(seed, 100);
+      (0..20)
+        .map(|_| generator.next_statement().unwrap())
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(statements(7), statements(7));
+    assert_ne!(statements(7), statements(8));
+    let statements = statements(7);
+    assert!(statements.iter().any(|sql| sql.starts_with("INSERT")));
+    assert!(statements.iter().any(|sql| sql.starts_with("UPDATE")));
+    assert!(statements.iter().any(|sql| sql.starts_with("DELETE")));
+    assert!(statements
+      .iter()
+      .filter(|sql| sql.starts_with("UPDATE"))
+      .all(|sql| !sql.contains("SET `id`")));
+  }
+
+  #[test]
+  fn generates_values_within_their_type() {
+    let mut rng = XorShift::new(1);
+    for _ in 0..100 {
+      let tinyint = column(
+        "n",
+        false,
+        column_kind("tinyint", "tinyint(4)", None, None, None),
+      );
+      let tinyint: i64 = value(&mut rng, &tinyint).unwrap().parse().unwrap();
+      assert!((-128..=127).contains(&tinyint));
+
+      let unsigned = column_kind("smallint", "smallint(5) unsigned", None, None, None);
+      let unsigned: i64 = value(&mut rng, &column("n", false, unsigned))
+        .unwrap()
+        .parse()
+        .unwrap();
+      assert!((0..=65535).contains(&unsigned));
+
+      let decimal = column_kind("decimal", "decimal(5,2)", None, Some(5), Some(2));
+      let decimal = value(&mut rng, &column("n", false, decimal)).unwrap();
+      assert_eq!(6, decimal.len(), "{}", decimal);
+
+      let varchar = column_kind("varchar", "varchar(3)", Some(3), None, None);
+      let varchar = value(&mut rng, &column("s", false, varchar)).unwrap();
+      assert!((3..=5).contains(&varchar.len()), "{}", varchar);
+    }
+
+    assert_eq!(None, column_kind("point", "point", None, None, None));
+    assert_eq!(vec!["calm", "it's", ""], members("enum('calm','it''s','')"));
+  }
+}
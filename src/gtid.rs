@@ -0,0 +1,299 @@
+// Translation between binlog positions, as a file and an offset, and GTID sets, e.g. to migrate
+// the checkpoints of a deployment that turns `gtid_mode` ON.
+//
+// Every binlog file starts with a PREVIOUS_GTIDS event, the GTIDs of the transactions of the
+// files before it. The GTIDs executed at a position are those, plus the GTIDs of the transactions
+// of the file up to the position: translating a position reads a single file. Translating a GTID
+// set reads the PREVIOUS_GTIDS of the files from the newest until the set is not contained anymore,
+// then the transactions of that file.
+//
+// Positions are expected to fall between transactions, as checkpoints do. Files purged from the
+// server can not be translated.
+
+use super::conn::{Connection, DriverError, ReplicationOptions};
+use super::protocol_binlog::BinlogEvent;
+use futures::io::{AsyncRead, AsyncWrite};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::future::Future;
+use std::str::FromStr;
+
+type DriverResult<T> = Result<T, DriverError>;
+
+#[derive(Debug, thiserror::Error)]
+#[error("Invalid GTID set `{0}`")]
+pub struct ParseGtidSetError(String);
+
+/// Set of GTIDs, e.g. `3e11fa47-71ca-11e1-9e33-c80aa9429562:1-5:7`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct GtidSet {
+  // Sorted and disjoint intervals of transaction numbers per server UUID, the end is inclusive.
+  intervals: BTreeMap<String, Vec<(u64, u64)>>,
+}
+
+impl GtidSet {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.intervals.is_empty()
+  }
+
+  /// Adds the transaction `gno` of the server `uuid`.
+  pub fn insert(&mut self, uuid: &str, gno: u64) {
+    self.insert_interval(uuid.to_lowercase(), gno, gno);
+  }
+
+  /// Adds every GTID of `other`.
+  pub fn extend(&mut self, other: &GtidSet) {
+    for (uuid, intervals) in &other.intervals {
+      for (start, end) in intervals {
+        self.insert_interval(uuid.clone(), *start, *end);
+      }
+    }
+  }
+
+  /// Returns true when every GTID of `other` is in the set.
+  pub fn contains(&self, other: &GtidSet) -> bool {
+    other.intervals.iter().all(|(uuid, intervals)| {
+      let own = match self.intervals.get(uuid) {
+        Some(own) => own,
+        None => return false,
+      };
+      intervals
+        .iter()
+        .all(|(start, end)| own.iter().any(|(s, e)| s <= start && end <= e))
+    })
+  }
+
+  fn insert_interval(&mut self, uuid: String, start: u64, end: u64) {
+    let intervals = self.intervals.entry(uuid).or_default();
+    intervals.push((start, end));
+    intervals.sort_unstable();
+
+    let mut merged: Vec<(u64, u64)> = Vec::with_capacity(intervals.len());
+    for (start, end) in intervals.drain(..) {
+      match merged.last_mut() {
+        Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+        _ => merged.push((start, end)),
+      }
+    }
+    *intervals = merged;
+  }
+}
+
+impl FromStr for GtidSet {
+  type Err = ParseGtidSetError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let invalid = || ParseGtidSetError(s.to_string());
+    let mut set = Self::new();
+    // Server UUIDs are separated by a comma, and a line break in the output of MYSQL.
+    for gtids in s
+      .split(',')
+      .map(str::trim)
+      .filter(|gtids| !gtids.is_empty())
+    {
+      let mut parts = gtids.split(':');
+      let uuid = parts.next().unwrap_or_default().to_lowercase();
+      if uuid.len() != 36 {
+        return Err(invalid());
+      }
+      let mut intervals = parts.peekable();
+      if intervals.peek().is_none() {
+        return Err(invalid());
+      }
+      for interval in intervals {
+        let (start, end) = interval.split_once('-').unwrap_or((interval, interval));
+        let start: u64 = start.parse().map_err(|_| invalid())?;
+        let end: u64 = end.parse().map_err(|_| invalid())?;
+        if start == 0 || end < start {
+          return Err(invalid());
+        }
+        set.insert_interval(uuid.clone(), start, end);
+      }
+    }
+    Ok(set)
+  }
+}
+
+impl fmt::Display for GtidSet {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for (i, (uuid, intervals)) in self.intervals.iter().enumerate() {
+      if i > 0 {
+        f.write_str(",")?;
+      }
+      f.write_str(uuid)?;
+      for (start, end) in intervals {
+        if start == end {
+          write!(f, ":{}", start)?;
+        } else {
+          write!(f, ":{}-{}", start, end)?;
+        }
+      }
+    }
+    Ok(())
+  }
+}
+
+/// GTIDs of the transactions executed before `file`:`position`. Each call reads the binlog with a
+/// new connection, opened by `connect`.
+pub async fn gtid_set_at<C, F, S>(
+  connect: C,
+  replication_opts: impl Into<ReplicationOptions>,
+  file: &str,
+  position: u32,
+) -> DriverResult<GtidSet>
+where
+  C: Fn() -> F,
+  F: Future<Output = DriverResult<Connection<S>>>,
+  S: AsyncRead + AsyncWrite + Unpin,
+{
+  let mut conn = connect().await?;
+  scan_file(&mut conn, replication_opts, file, |at, _| at >= position)
+    .await?
+    .map(|(_, executed)| executed)
+    .ok_or_else(|| DriverError::InvalidBinlogPosition(file.to_string(), position))
+}
+
+/// Earliest binlog position where every transaction of `gtid_set` was executed, as a file and an
+/// offset. Transactions before the position but not in `gtid_set`, if any, are not streamed from
+/// it. Each file read opens a new connection with `connect`.
+pub async fn position_of<C, F, S>(
+  connect: C,
+  replication_opts: impl Into<ReplicationOptions>,
+  gtid_set: &GtidSet,
+) -> DriverResult<(String, u32)>
+where
+  C: Fn() -> F,
+  F: Future<Output = DriverResult<Connection<S>>>,
+  S: AsyncRead + AsyncWrite + Unpin,
+{
+  let replication_opts = replication_opts.into();
+  let not_found = || DriverError::GtidSetNotFound(gtid_set.to_string());
+  let files = connect().await?.binary_logs().await?;
+
+  for (i, file) in files.iter().enumerate().rev() {
+    let mut conn = connect().await?;
+    let (_, previous) = scan_file(&mut conn, replication_opts.clone(), file, |_, _| true)
+      .await?
+      .ok_or_else(not_found)?;
+    drop(conn);
+
+    if !previous.contains(gtid_set) {
+      let mut conn = connect().await?;
+      return scan_file(&mut conn, replication_opts.clone(), file, |_, executed| {
+        executed.contains(gtid_set)
+      })
+      .await?
+      .map(|(position, _)| (file.clone(), position))
+      .ok_or_else(not_found);
+    }
+
+    // Transactions missing from `gtid_set` are in the purged files.
+    if i == 0 {
+      return match gtid_set.contains(&previous) {
+        true => Ok((file.clone(), 4)),
+        false => Err(not_found()),
+      };
+    }
+  }
+
+  Err(not_found())
+}
+
+// Reads the binlog file `file`, and calls `visit` with the GTIDs executed at every transaction
+// boundary, from its beginning to its end. Returns the first position `visit` accepts, `None` if it
+// accepts none.
+async fn scan_file<S>(
+  conn: &mut Connection<S>,
+  replication_opts: impl Into<ReplicationOptions>,
+  file: &str,
+  mut visit: impl FnMut(u32, &GtidSet) -> bool,
+) -> DriverResult<Option<(u32, GtidSet)>>
+where
+  S: AsyncRead + AsyncWrite + Unpin,
+{
+  conn.start_binlog_scan(replication_opts, file, 4).await?;
+
+  let mut executed = GtidSet::new();
+  let mut end = 4;
+  while let Some((header, event)) = conn.read_binlog_event().await? {
+    // Events generated by the server, e.g. the ROTATE sent first, have no position.
+    if header.log_pos() == 0 {
+      continue;
+    }
+    let start = header.log_pos().saturating_sub(header.event_size());
+    end = header.log_pos();
+
+    match event {
+      BinlogEvent::PreviousGtids(previous) => {
+        executed = previous
+          .gtid_set()
+          .parse()
+          .map_err(|_| DriverError::UnexpectedPacket)?;
+      }
+      BinlogEvent::Gtid(gtid) => {
+        if visit(start, &executed) {
+          return Ok(Some((start, executed)));
+        }
+        if let Some((uuid, gno)) = gtid
+          .gtid()
+          .as_deref()
+          .and_then(|gtid| gtid.rsplit_once(':'))
+        {
+          executed.insert(
+            uuid,
+            gno.parse().map_err(|_| DriverError::UnexpectedPacket)?,
+          );
+        }
+      }
+      // The server moves on to the next file.
+      BinlogEvent::Rotate(_) => {
+        return Ok(visit(start, &executed).then_some((start, executed)));
+      }
+      _ => {}
+    }
+  }
+
+  Ok(visit(end, &executed).then_some((end, executed)))
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  const UUID: &str = "3e11fa47-71ca-11e1-9e33-c80aa9429562";
+  const OTHER_UUID: &str = "4a2b3c4d-0000-11e1-9e33-c80aa9429562";
+
+  #[test]
+  fn parses_and_merges_gtid_sets() {
+    let set: GtidSet = format!("{}:1-5:7,\n{}:3", UUID.to_uppercase(), OTHER_UUID)
+      .parse()
+      .unwrap();
+    assert_eq!(format!("{}:1-5:7,{}:3", UUID, OTHER_UUID), set.to_string());
+
+    let mut merged = set.clone();
+    merged.insert(UUID, 6);
+    merged.extend(&format!("{}:1-2", OTHER_UUID).parse().unwrap());
+    assert_eq!(
+      format!("{}:1-7,{}:1-3", UUID, OTHER_UUID),
+      merged.to_string()
+    );
+
+    assert!(merged.contains(&set));
+    assert!(!set.contains(&merged));
+    assert!(set.contains(&GtidSet::new()));
+    assert!("".parse::<GtidSet>().unwrap().is_empty());
+
+    for invalid in &[
+      "uuid:1",
+      UUID,
+      &format!("{}:0", UUID),
+      &format!("{}:5-3", UUID),
+    ] {
+      assert!(invalid.parse::<GtidSet>().is_err(), "{}", invalid);
+    }
+  }
+}
@@ -0,0 +1,103 @@
+// Idempotency keys of change events, for consumers to drop the changes they already applied when
+// a pipeline pushes them again, e.g. after a crash between two checkpoints, or when it is re-driven
+// from an older position.
+//
+// The key of a change is `SOURCE:TRANSACTION:INDEX`: the name given to the source, the GTID of the
+// transaction of the change (or, with `gtid_mode` OFF, the binlog position of its first event),
+// and the index of the change in the transaction. The key of a row is the key of its change
+// followed by `:ROW`, the index of the row in the change (`row_key`).
+//
+// GTIDs are the same on every server of a replication topology, so their keys survive a failover
+// to a replica, while binlog positions are only stable on the server that logged them. Changes are
+// indexed as they reach the sink: keys are stable as long as the filters upstream of it are.
+//
+// Keys are only known within transaction markers (`EventDecoder::emit_transaction_markers`).
+
+use super::event::ChangeEvent;
+
+#[derive(Clone, Debug)]
+pub struct IdempotencyKeys {
+  source: String,
+  // Prefix of the keys of the current transaction, `SOURCE:TRANSACTION`.
+  transaction: Option<String>,
+  index: u64,
+}
+
+impl IdempotencyKeys {
+  /// Keys of the changes of `source`, a name that tells the sources of a sink apart, e.g. the
+  /// name of the cluster.
+  pub fn new(source: impl Into<String>) -> Self {
+    Self {
+      source: source.into(),
+      transaction: None,
+      index: 0,
+    }
+  }
+
+  /// Observes `change`, in the order of the stream, and returns its key. `None` for the
+  /// transaction markers and restarts of the source, and for the changes outside of a
+  /// transaction.
+  pub fn next(&mut self, change: &ChangeEvent) -> Option<String> {
+    match change {
+      ChangeEvent::Begin(metadata) => {
+        let transaction = metadata
+          .gtid()
+          .map(str::to_string)
+          .or_else(|| metadata.start_position().map(ToString::to_string));
+        self.transaction =
+          transaction.map(|transaction| format!("{}:{}", self.source, transaction));
+        self.index = 0;
+        None
+      }
+      ChangeEvent::End(_) | ChangeEvent::SourceRestart { .. } => {
+        self.transaction = None;
+        None
+      }
+      _ => {
+        let key = format!("{}:{}", self.transaction.as_ref()?, self.index);
+        self.index += 1;
+        Some(key)
+      }
+    }
+  }
+}
+
+/// Key of the row at `index` of the change with key `key`.
+pub fn row_key(key: &str, index: usize) -> String {
+  format!("{}:{}", key, index)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::event::TransactionMetadata;
+  use crate::watermark::BinlogPosition;
+
+  fn insert() -> ChangeEvent {
+    ChangeEvent::Insert {
+      schema: "pets".to_string(),
+      table: "cats".to_string(),
+      column_types: vec![],
+      rows: vec![],
+    }
+  }
+
+  #[test]
+  fn keys_changes_by_transaction() {
+    let mut keys = IdempotencyKeys::new("shop");
+    assert_eq!(None, keys.next(&insert()));
+
+    let mut metadata = TransactionMetadata::default();
+    metadata.set_start_position(BinlogPosition::new("mysql-bin.000002", 4));
+    assert_eq!(None, keys.next(&ChangeEvent::Begin(metadata.clone())));
+    assert_eq!(
+      Some("shop:mysql-bin.000002:4:0".to_string()),
+      keys.next(&insert())
+    );
+    let key = keys.next(&insert()).unwrap();
+    assert_eq!("shop:mysql-bin.000002:4:1", key);
+    assert_eq!("shop:mysql-bin.000002:4:1:3", row_key(&key, 3));
+    assert_eq!(None, keys.next(&ChangeEvent::End(metadata)));
+    assert_eq!(None, keys.next(&insert()));
+  }
+}
@@ -0,0 +1,340 @@
+// Pretty printer of raw binlog events, to step through them one by one when the decoder disagrees
+// with a server: the header, the decoded fields and values, and a hex dump of the event as it was
+// sent, in the format of `hexdump -C`.
+//
+// Rows events only carry the id of their table, the `Inspector` keeps the table maps it saw to
+// decode their values, and the rotations to tell the binlog coordinates of the events.
+
+use super::protocol_binlog::{BinlogEvent, BinlogEventPacket, EventType, TableMapEvent};
+use super::value::{Row, Value};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::io;
+
+/// Decodes and pretty prints raw binlog events, see `Connection::resume_raw_binlog_stream`.
+#[derive(Default)]
+pub struct Inspector {
+  event_types: Vec<String>,
+  tables: Vec<(String, String)>,
+  binlog_file: Option<String>,
+  table_maps: HashMap<u64, TableMapEvent>,
+}
+
+impl Inspector {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Only prints the events of this type, e.g. `TABLE_MAP_EVENT`. Can be called more than once.
+  pub fn event_type(mut self, name: impl Into<String>) -> Self {
+    self.event_types.push(name.into().to_uppercase());
+    self
+  }
+
+  /// Only prints the table maps and rows events of this table. Can be called more than once.
+  pub fn table(mut self, schema: impl Into<String>, table: impl Into<String>) -> Self {
+    self.tables.push((schema.into(), table.into()));
+    self
+  }
+
+  /// Decodes `event`, as stored in binlog files, and returns its pretty printed form, or `None`
+  /// when the filters skip it. Events that fail to decode are printed with the error, since they
+  /// are the ones worth looking at.
+  pub fn inspect(&mut self, event: &[u8]) -> Option<String> {
+    let packet = match BinlogEventPacket::parse_event(event.to_vec()) {
+      Ok(packet) => packet,
+      Err(err) => return Some(format!("malformed event: {}\n{}", err, hexdump(event))),
+    };
+    let header = packet.header();
+    let event_type = packet.event_type();
+    if !self.event_types.is_empty()
+      && !self
+        .event_types
+        .iter()
+        .any(|name| *name == format!("{:?}", event_type))
+    {
+      // Still follow the rotations and table maps, for the events that are printed.
+      self.track(packet);
+      return None;
+    }
+
+    let start = header.log_pos().saturating_sub(header.event_size());
+    let mut out = format!(
+      "{:?} at {}:{}, {} bytes, server {}, timestamp {}",
+      event_type,
+      self.binlog_file.as_deref().unwrap_or("?"),
+      start,
+      header.event_size(),
+      header.server_id(),
+      header.timestamp()
+    );
+    if header.is_artificial() {
+      out.push_str(", artificial");
+    }
+    out.push('\n');
+
+    match packet.into_binlog_event() {
+      Ok(event) => {
+        if !self.describe(&event, &mut out) {
+          self.remember(event);
+          return None;
+        }
+        self.remember(event);
+      }
+      // Events of tables filtered out can not be told apart from the others once they fail.
+      Err(err) => {
+        let _ = writeln!(out, "failed to decode: {}", err);
+      }
+    }
+    out.push_str(&hexdump(event));
+    Some(out)
+  }
+
+  fn track(&mut self, packet: BinlogEventPacket) {
+    if let EventType::ROTATE_EVENT | EventType::TABLE_MAP_EVENT = packet.event_type() {
+      if let Ok(event) = packet.into_binlog_event() {
+        self.remember(event);
+      }
+    }
+  }
+
+  fn remember(&mut self, event: BinlogEvent) {
+    match event {
+      BinlogEvent::Rotate(rotate) => {
+        self.binlog_file = Some(rotate.next_log_name_str().to_string());
+      }
+      BinlogEvent::TableMap(table_map) => {
+        self.table_maps.insert(table_map.table_id(), table_map);
+      }
+      _ => {}
+    }
+  }
+
+  fn is_selected(&self, schema: &str, table: &str) -> bool {
+    self.tables.is_empty() || self.tables.iter().any(|(s, t)| s == schema && t == table)
+  }
+
+  // Writes the fields of `event` to `out`, returns false when the table filters skip it.
+  fn describe(&self, event: &BinlogEvent, out: &mut String) -> bool {
+    match event {
+      BinlogEvent::TableMap(table_map) => {
+        if !self.is_selected(table_map.schema_str(), table_map.table_str()) {
+          return false;
+        }
+        let _ = writeln!(
+          out,
+          "  table {}: {}.{}",
+          table_map.table_id(),
+          table_map.schema_str(),
+          table_map.table_str()
+        );
+        for (i, column_type) in table_map.column_types().iter().enumerate() {
+          let _ = writeln!(out, "  {}: {:?}", column_name(table_map, i), column_type);
+        }
+      }
+      BinlogEvent::Insert(rows) | BinlogEvent::Update(rows) | BinlogEvent::Delete(rows) => {
+        let table_map = match self.table_maps.get(&rows.table_id()) {
+          Some(table_map) => table_map,
+          None if !self.tables.is_empty() => return false,
+          None => {
+            let _ = writeln!(out, "  table {}: no table map", rows.table_id());
+            return true;
+          }
+        };
+        if !self.is_selected(table_map.schema_str(), table_map.table_str()) {
+          return false;
+        }
+        let _ = writeln!(
+          out,
+          "  table {}: {}.{}",
+          rows.table_id(),
+          table_map.schema_str(),
+          table_map.table_str()
+        );
+        match rows.decode_rows(table_map) {
+          Ok(images) => {
+            let is_update = matches!(event, BinlogEvent::Update(_));
+            for (i, image) in images.iter().enumerate() {
+              let label = match (is_update, i % 2) {
+                (true, 0) => format!("row {} before", i / 2 + 1),
+                (true, _) => format!("row {} after", i / 2 + 1),
+                (false, _) => format!("row {}", i + 1),
+              };
+              let _ = writeln!(out, "  {}: {}", label, format_row(table_map, image));
+            }
+          }
+          Err(err) => {
+            let _ = writeln!(out, "  failed to decode the rows: {}", err);
+          }
+        }
+      }
+      _ if !self.tables.is_empty() => return false,
+      BinlogEvent::Query(query) => {
+        let _ = writeln!(out, "  schema: {}", query.schema_str());
+        let _ = writeln!(out, "  query: {}", query.query_str());
+      }
+      BinlogEvent::Rotate(rotate) => {
+        let _ = writeln!(
+          out,
+          "  next: {}:{}",
+          rotate.next_log_name_str(),
+          rotate.position()
+        );
+      }
+      BinlogEvent::Gtid(gtid) => {
+        let _ = writeln!(
+          out,
+          "  gtid: {}",
+          gtid.gtid().as_deref().unwrap_or("anonymous")
+        );
+      }
+      event => {
+        let _ = writeln!(out, "  {}", event);
+      }
+    }
+    true
+  }
+}
+
+// Columns are named `@1`, `@2`, ... like mysqlbinlog does, unless the server logs their names
+// (`binlog_row_metadata=FULL`).
+fn column_name(table_map: &TableMapEvent, index: usize) -> String {
+  match table_map.column_names().get(index) {
+    Some(name) => name.clone(),
+    None => format!("@{}", index + 1),
+  }
+}
+
+fn format_row(table_map: &TableMapEvent, row: &Row) -> String {
+  let values: Vec<String> = row
+    .values()
+    .iter()
+    .enumerate()
+    .filter_map(|(i, value)| {
+      let value = match value.as_ref()? {
+        Value::Null => "NULL".to_string(),
+        Value::Bytes(bytes) => format!("{:?}", String::from_utf8_lossy(bytes)),
+        value => match value.to_text() {
+          Some(text) => String::from_utf8_lossy(&text).into_owned(),
+          None => format!("{:?}", value),
+        },
+      };
+      Some(format!("{}={}", column_name(table_map, i), value))
+    })
+    .collect();
+  values.join(" ")
+}
+
+/// Formats `bytes` the way `hexdump -C` does: offsets, 16 bytes per line in hex, and the
+/// printable ones as ASCII.
+pub fn hexdump(bytes: &[u8]) -> String {
+  let mut out = String::new();
+  for (i, line) in bytes.chunks(16).enumerate() {
+    let _ = write!(out, "{:08x} ", i * 16);
+    for j in 0..16 {
+      if j == 8 {
+        out.push(' ');
+      }
+      match line.get(j) {
+        Some(b) => {
+          let _ = write!(out, " {:02x}", b);
+        }
+        None => out.push_str("   "),
+      }
+    }
+    out.push_str("  |");
+    for b in line {
+      out.push(if b.is_ascii_graphic() || *b == b' ' {
+        *b as char
+      } else {
+        '.'
+      });
+    }
+    out.push_str("|\n");
+  }
+  let _ = writeln!(out, "{:08x}", bytes.len());
+  out
+}
+
+/// Command typed after an event in interactive mode: Enter for the next one, `c` to print the rest
+/// without pausing, `q` to quit.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Step {
+  Next,
+  Continue,
+  Quit,
+}
+
+impl Step {
+  pub fn read(input: &mut impl io::BufRead) -> io::Result<Self> {
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+      return Ok(Step::Quit);
+    }
+    Ok(match line.trim() {
+      "c" | "continue" => Step::Continue,
+      "q" | "quit" => Step::Quit,
+      _ => Step::Next,
+    })
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  // See the parser tests of tail_mysql_core, without the OK byte.
+  const TABLE_MAP_EVENT: &[u8] = b"\xfc\x5a\x5d\x5d\x13\x01\x00\x00\x00\x32\x00\x00\x00\x49\x01\x00\
+                                   \x00\x00\x00\x2d\x0a\x00\x00\x00\x00\x01\x00\x04\x70\x65\x74\x73\x00\
+                                   \x04\x63\x61\x74\x73\x00\x04\x03\x0f\x0f\x0a\x04\x58\x02\x58\x02\x00";
+  const INSERT_ROW_EVENT: &[u8] = b"\xfc\x5a\x5d\x5d\x1e\x01\x00\x00\x00\x37\x00\x00\x00\x80\x01\x00\
+                                    \x00\x00\x00\x2d\x0a\x00\x00\x00\x00\x01\x00\x02\x00\x04\xff\xf0\x04\
+                                    \x00\x00\x00\x07\x00\x43\x68\x61\x72\x6c\x69\x65\x05\x00\x52\x69\x76\
+                                    \x65\x72\xb5\xc0\x0f";
+
+  #[test]
+  fn prints_rows_with_their_table_map() {
+    let mut inspector = Inspector::new();
+    let table_map = inspector.inspect(TABLE_MAP_EVENT).unwrap();
+    assert!(table_map.starts_with("TABLE_MAP_EVENT at ?:279, 50 bytes, server 1"));
+    assert!(table_map.contains("  table 2605: pets.cats\n  @1: MYSQL_TYPE_LONG\n"));
+
+    let insert = inspector.inspect(INSERT_ROW_EVENT).unwrap();
+    assert!(insert.contains("  row 1: @1=4 @2=\"Charlie\" @3=\"River\" @4=2016-05-21 00:00:00\n"));
+    assert!(insert.ends_with("  |iver...|\n00000037\n"));
+  }
+
+  #[test]
+  fn filters_events() {
+    let mut inspector = Inspector::new().event_type("write_rows_eventv2");
+    assert_eq!(None, inspector.inspect(TABLE_MAP_EVENT));
+    // The table map was still recorded.
+    assert!(inspector
+      .inspect(INSERT_ROW_EVENT)
+      .unwrap()
+      .contains("pets.cats"));
+
+    let mut inspector = Inspector::new().table("pets", "dogs");
+    assert_eq!(None, inspector.inspect(TABLE_MAP_EVENT));
+    assert_eq!(None, inspector.inspect(INSERT_ROW_EVENT));
+  }
+
+  #[test]
+  fn dumps_hex() {
+    assert_eq!(
+      "00000000  70 65 74 73 00 01 02 03  04 05 06 07 08 09 0a 0b  |pets............|\n\
+       00000010  63 61 74 73                                       |cats|\n\
+       00000014\n",
+      hexdump(b"pets\x00\x01\x02\x03\x04\x05\x06\x07\x08\x09\x0a\x0bcats")
+    );
+  }
+
+  #[test]
+  fn reads_steps() {
+    let mut input = &b"\nc\nq\n"[..];
+    assert_eq!(Step::Next, Step::read(&mut input).unwrap());
+    assert_eq!(Step::Continue, Step::read(&mut input).unwrap());
+    assert_eq!(Step::Quit, Step::read(&mut input).unwrap());
+    assert_eq!(Step::Quit, Step::read(&mut input).unwrap());
+  }
+}
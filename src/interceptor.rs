@@ -0,0 +1,24 @@
+// Hooks into the packets exchanged with MYSQL, e.g. to log or audit the queries, or to record
+// latency histograms, without wrapping `Connection`.
+//
+// Interceptors are registered on `ConnectionOptions`, so that they observe the handshake too, and
+// are called synchronously on the IO path of the connection: they must not block.
+
+pub use super::protocol::{Command, SessionStateChange};
+use std::fmt;
+use std::time::Duration;
+
+/// Callbacks invoked on every command sent to MYSQL and every packet received from it.
+pub trait Interceptor: fmt::Debug + Send + Sync {
+  /// Called before `command` is sent, with its payload, e.g. the SQL of a COM_QUERY.
+  fn on_command(&self, command: Command, payload: &[u8]) {}
+
+  /// Called for every packet received, with the time elapsed since the last command was sent (or
+  /// since the connection was opened, during the handshake). Binlog events are received long after
+  /// their COM_BINLOG_DUMP, consecutive packets tell how long the server took to send each of them.
+  fn on_packet(&self, payload: &[u8], elapsed: Duration) {}
+
+  /// Called for every change of the session reported by the session trackers, e.g. when the
+  /// default schema changed.
+  fn on_session_state_change(&self, change: &SessionStateChange) {}
+}
@@ -0,0 +1,746 @@
+// JSON envelope of `ChangeEvent`s, for consumers that are not written in Rust.
+//
+// Rows are objects keyed by column index rather than arrays, so that the columns missing from an
+// image (e.g. with `binlog_row_image=MINIMAL`) can be left out instead of being confused with NULL.
+//
+// How values are represented can be changed per column type or per column with a `TypeMapping`,
+// e.g. to emit DECIMALs as numbers for consumers that do not need their exact precision.
+//
+// TIMESTAMPs are instants, and are rendered in the time zone of the `TypeMapping` (UTC by
+// default). DATEs and DATETIMEs are wall clock times, and are rendered as is.
+//
+// Every envelope carries the `version` of its layout, `ENVELOPE_VERSION`, and the envelopes of
+// rows events the `schema_version` of their table: a hash of its column types, which changes when
+// a column is added, dropped or changes type, so that consumers can tell the rows of two versions
+// of a table apart without comparing them. Column lengths and names are not in the binlog events
+// of every server, and do not take part in the hash.
+//
+// Sinks that key the changes (see `tail_mysql::idempotency`) add the key of the change to its
+// envelope, as `idempotency_key`.
+//
+// Versions of the envelope:
+//
+// - 1: `version` and `schema_version` added, the envelope is otherwise unchanged.
+
+use super::classify::Operation;
+#[cfg(feature = "client")]
+use super::conn::QueryResults;
+use super::event::{ChangeEvent, TransactionMetadata};
+use super::protocol::ColumnType;
+use super::time_zone::UtcOffset;
+use super::util::hex;
+use super::value::{Row, Value};
+use super::watermark::BinlogPosition;
+use serde_json::{json, Map};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+/// Version of the layout of the envelope, bumped whenever a field changes or goes away. Fields
+/// are added without bumping it.
+pub const ENVELOPE_VERSION: u32 = 1;
+
+/// Representation of the values of a column in the JSON envelope. Values a representation does
+/// not apply to (e.g. `Bool` for a string, or values over their size limit) keep their default
+/// representation.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Representation {
+  /// The representation of `value_to_json`.
+  Default,
+  /// Numbers as strings, e.g. `"42"`.
+  String,
+  /// Numbers, e.g. DECIMALs as `12.5`. The precision of large DECIMALs is lost.
+  Float,
+  /// ISO 8601 dates, e.g. `2016-05-21T10:00:00`. TIMESTAMPs are suffixed with the offset of the
+  /// time zone, e.g. `2016-05-21T10:00:00Z`.
+  Iso8601,
+  /// Milliseconds since the epoch. DATEs and DATETIMEs are read as UTC. Zero dates are null.
+  EpochMillis,
+  /// Booleans, e.g. BIT(1) as `true`.
+  Bool,
+  /// Integers, e.g. BIT(n) as the unsigned integer of its bits.
+  Integer,
+}
+
+/// Representations of the column values, per column type or per column. Columns are identified
+/// by their position in the table, like in `ValueLimits`.
+///
+/// The schemas of `json_schema` describe the default representations.
+#[derive(Clone, Debug, Default)]
+pub struct TypeMapping {
+  types: HashMap<ColumnType, Representation>,
+  tables: HashMap<(String, String), HashMap<usize, Representation>>,
+  time_zone: UtcOffset,
+}
+
+impl TypeMapping {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Representation of the columns of `column_type` without a specific representation. DATETIME,
+  /// TIMESTAMP and TIME also apply to their fractional seconds variants, e.g. DATETIME2.
+  pub fn type_representation(
+    mut self,
+    column_type: ColumnType,
+    representation: Representation,
+  ) -> Self {
+    self.types.insert(base_type(column_type), representation);
+    self
+  }
+
+  /// Time zone TIMESTAMPs are rendered in, UTC by default.
+  pub fn time_zone(mut self, time_zone: UtcOffset) -> Self {
+    self.time_zone = time_zone;
+    self
+  }
+
+  pub fn column_representation(
+    mut self,
+    schema: impl Into<String>,
+    table: impl Into<String>,
+    column: usize,
+    representation: Representation,
+  ) -> Self {
+    self
+      .tables
+      .entry((schema.into(), table.into()))
+      .or_default()
+      .insert(column, representation);
+    self
+  }
+
+  pub fn representation(
+    &self,
+    schema: &str,
+    table: &str,
+    column: usize,
+    column_type: ColumnType,
+  ) -> Representation {
+    // Avoids allocating the key when no column is configured, which is the common case.
+    let column_representation = if self.tables.is_empty() {
+      None
+    } else {
+      self
+        .tables
+        .get(&(schema.to_string(), table.to_string()))
+        .and_then(|columns| columns.get(&column))
+    };
+
+    column_representation
+      .or_else(|| self.types.get(&base_type(column_type)))
+      .copied()
+      .unwrap_or(Representation::Default)
+  }
+}
+
+fn base_type(column_type: ColumnType) -> ColumnType {
+  match column_type {
+    ColumnType::MYSQL_TYPE_DATETIME2 => ColumnType::MYSQL_TYPE_DATETIME,
+    ColumnType::MYSQL_TYPE_TIMESTAMP2 => ColumnType::MYSQL_TYPE_TIMESTAMP,
+    ColumnType::MYSQL_TYPE_TIME2 => ColumnType::MYSQL_TYPE_TIME,
+    column_type => column_type,
+  }
+}
+
+/// Returns the JSON envelope of `event`.
+pub fn to_json(event: &ChangeEvent) -> serde_json::Value {
+  to_json_with(event, &TypeMapping::default())
+}
+
+/// Returns the JSON envelope of `event`, with the values represented according to `mapping`.
+pub fn to_json_with(event: &ChangeEvent, mapping: &TypeMapping) -> serde_json::Value {
+  let mut envelope = event_to_json(event, mapping);
+  envelope["version"] = json!(ENVELOPE_VERSION);
+  envelope
+}
+
+/// Returns the JSON envelope of `event`, as `to_json_with` does, with its idempotency key.
+pub fn to_json_with_key(
+  event: &ChangeEvent,
+  mapping: &TypeMapping,
+  idempotency_key: &str,
+) -> serde_json::Value {
+  let mut envelope = to_json_with(event, mapping);
+  envelope["idempotency_key"] = json!(idempotency_key);
+  envelope
+}
+
+/// Version of the schema of a table with `column_types`, as stamped on the envelopes of its rows
+/// events: the first 8 bytes of the SHA-256 of the types, in hex.
+pub fn schema_version(column_types: &[ColumnType]) -> String {
+  let types: Vec<u8> = column_types
+    .iter()
+    .map(|&column_type| column_type as u8)
+    .collect();
+  hex(&Sha256::digest(&types)[..8])
+}
+
+fn event_to_json(event: &ChangeEvent, mapping: &TypeMapping) -> serde_json::Value {
+  let row = |schema: &str, table: &str, column_types: &[ColumnType], row: &Row| {
+    mapped_row_to_json(row, schema, table, column_types, mapping)
+  };
+
+  match event {
+    ChangeEvent::Insert {
+      schema,
+      table,
+      column_types,
+      rows,
+    } => json!({
+      "type": "insert",
+      "schema": schema,
+      "table": table,
+      "schema_version": schema_version(column_types),
+      "rows": rows
+        .iter()
+        .map(|r| row(schema, table, column_types, r))
+        .collect::<Vec<_>>(),
+    }),
+    ChangeEvent::Update {
+      schema,
+      table,
+      column_types,
+      rows,
+    } => json!({
+      "type": "update",
+      "schema": schema,
+      "table": table,
+      "schema_version": schema_version(column_types),
+      "rows": rows
+        .iter()
+        .map(|(before, after)| json!({
+          "before": row(schema, table, column_types, before),
+          "after": row(schema, table, column_types, after),
+        }))
+        .collect::<Vec<_>>(),
+    }),
+    ChangeEvent::Delete {
+      schema,
+      table,
+      column_types,
+      rows,
+    } => json!({
+      "type": "delete",
+      "schema": schema,
+      "table": table,
+      "schema_version": schema_version(column_types),
+      "rows": rows
+        .iter()
+        .map(|r| row(schema, table, column_types, r))
+        .collect::<Vec<_>>(),
+    }),
+    ChangeEvent::Statement {
+      schema,
+      sql,
+      operation,
+      tables,
+      time_zone,
+    } => json!({
+      "type": "statement",
+      "schema": schema,
+      "sql": sql,
+      "operation": operation_str(*operation),
+      "time_zone": time_zone,
+      "tables": tables
+        .iter()
+        .map(|table| json!({ "schema": table.schema_str(), "table": table.table_str() }))
+        .collect::<Vec<_>>(),
+    }),
+    ChangeEvent::SchemaDrift {
+      schema,
+      table,
+      previous_column_count,
+      column_count,
+      column_names,
+    } => json!({
+      "type": "schema_drift",
+      "schema": schema,
+      "table": table,
+      "previous_column_count": previous_column_count,
+      "column_count": column_count,
+      "column_names": column_names,
+    }),
+    ChangeEvent::Begin(transaction) => transaction_to_json("begin", transaction),
+    ChangeEvent::End(transaction) => transaction_to_json("end", transaction),
+    ChangeEvent::SourceRestart { position } => json!({
+      "type": "source_restart",
+      "file": position.as_ref().map(BinlogPosition::file_str),
+      "position": position.as_ref().map(BinlogPosition::position),
+    }),
+  }
+}
+
+/// Returns the JSON envelope of `event`, serialized.
+pub fn to_string(event: &ChangeEvent) -> String {
+  to_json(event).to_string()
+}
+
+/// Returns the JSON envelope of `event`, serialized, with the values represented according to
+/// `mapping`.
+pub fn to_string_with(event: &ChangeEvent, mapping: &TypeMapping) -> String {
+  to_json_with(event, mapping).to_string()
+}
+
+pub fn row_to_json(row: &Row) -> serde_json::Value {
+  let columns: Map<String, serde_json::Value> = row
+    .values()
+    .iter()
+    .enumerate()
+    .filter_map(|(i, value)| {
+      value
+        .as_ref()
+        .map(|value| (i.to_string(), value_to_json(value)))
+    })
+    .collect();
+  serde_json::Value::Object(columns)
+}
+
+/// Results of a query, as an array of objects keyed by column name.
+#[cfg(feature = "client")]
+pub fn query_results_to_json(results: &QueryResults) -> serde_json::Value {
+  let rows = results
+    .iter()
+    .map(|row| {
+      let columns: Map<String, serde_json::Value> = results
+        .columns()
+        .iter()
+        .zip(row.values())
+        .map(|(column, value)| (column.name_str().to_string(), value_to_json(value)))
+        .collect();
+      serde_json::Value::Object(columns)
+    })
+    .collect();
+  serde_json::Value::Array(rows)
+}
+
+/// Strings are emitted as is when they are valid UTF-8, and as `{"hex": ".."}` otherwise.
+/// Dates and times use their MYSQL text representation, with TIMESTAMPs in UTC.
+pub fn value_to_json(value: &Value) -> serde_json::Value {
+  match value {
+    Value::Null => serde_json::Value::Null,
+    Value::Bytes(b) => bytes_to_json(b),
+    Value::Int(v) => json!(v),
+    Value::Uint(v) => json!(v),
+    // NaN and infinities are not representable, and serialize as null.
+    Value::Float(v) => json!(v),
+    Value::Date { .. } | Value::Time { .. } | Value::Timestamp { .. } => match value.to_text() {
+      Some(text) => json!(String::from_utf8_lossy(&text)),
+      None => serde_json::Value::Null,
+    },
+    Value::Truncated { prefix, size } => json!({
+      "truncated": bytes_to_json(prefix),
+      "size": size,
+    }),
+    Value::Digest { sha256, size } => json!({
+      "sha256": hex(sha256),
+      "size": size,
+    }),
+    Value::Reference { url, sha256, size } => json!({
+      "url": url,
+      "sha256": hex(sha256),
+      "size": size,
+    }),
+    Value::Encrypted {
+      key_id,
+      wrapped_key,
+      nonce,
+      ciphertext,
+    } => json!({
+      "key_id": key_id,
+      "wrapped_key": hex(wrapped_key),
+      "nonce": hex(nonce),
+      "ciphertext": hex(ciphertext),
+    }),
+  }
+}
+
+fn mapped_row_to_json(
+  row: &Row,
+  schema: &str,
+  table: &str,
+  column_types: &[ColumnType],
+  mapping: &TypeMapping,
+) -> serde_json::Value {
+  let columns: Map<String, serde_json::Value> = row
+    .values()
+    .iter()
+    .enumerate()
+    .filter_map(|(i, value)| {
+      let value = value.as_ref()?;
+      let value = match column_types.get(i) {
+        Some(&column_type) => {
+          let representation = mapping.representation(schema, table, i, column_type);
+          mapped_value_to_json(value, column_type, representation, mapping.time_zone)
+        }
+        None => value_to_json(value),
+      };
+      Some((i.to_string(), value))
+    })
+    .collect();
+  serde_json::Value::Object(columns)
+}
+
+fn mapped_value_to_json(
+  value: &Value,
+  column_type: ColumnType,
+  representation: Representation,
+  time_zone: UtcOffset,
+) -> serde_json::Value {
+  if let Value::Timestamp { seconds, micro } = *value {
+    let date = match value.local_date(time_zone) {
+      Some(date) => date,
+      None => return value_to_json(value),
+    };
+    return match representation {
+      Representation::EpochMillis if seconds == 0 && micro == 0 => serde_json::Value::Null,
+      Representation::EpochMillis => json!(i64::from(seconds) * 1_000 + i64::from(micro) / 1_000),
+      Representation::Iso8601 => {
+        let mut date = mapped_value_to_json(&date, column_type, representation, time_zone);
+        if let serde_json::Value::String(ref mut date) = date {
+          if time_zone.is_utc() {
+            date.push('Z');
+          } else {
+            date.push_str(&time_zone.to_string());
+          }
+        }
+        date
+      }
+      _ => value_to_json(&date),
+    };
+  }
+
+  let mapped = match (representation, value) {
+    (Representation::String, Value::Int(v)) => Some(json!(v.to_string())),
+    (Representation::String, Value::Uint(v)) => Some(json!(v.to_string())),
+    (Representation::String, Value::Float(v)) => Some(json!(v.to_string())),
+    // DECIMALs are decoded to their text representation.
+    (Representation::Float, Value::Bytes(b)) => std::str::from_utf8(b)
+      .ok()
+      .and_then(|s| s.parse::<f64>().ok())
+      .map(|v| json!(v)),
+    (Representation::Float, Value::Int(v)) => Some(json!(*v as f64)),
+    (Representation::Float, Value::Uint(v)) => Some(json!(*v as f64)),
+    (
+      Representation::Iso8601,
+      Value::Date {
+        year,
+        month,
+        day,
+        hour,
+        minute,
+        second,
+        micro,
+      },
+    ) => {
+      let mut date = format!("{:04}-{:02}-{:02}", year, month, day);
+      if base_type(column_type) != ColumnType::MYSQL_TYPE_DATE {
+        date.push_str(&format!("T{:02}:{:02}:{:02}", hour, minute, second));
+        if *micro > 0 {
+          date.push_str(&format!(".{:06}", micro));
+        }
+      }
+      Some(json!(date))
+    }
+    (Representation::EpochMillis, Value::Date { .. }) => Some(json!(epoch_millis(value))),
+    (Representation::Bool, Value::Bytes(b)) => Some(json!(b.iter().any(|b| *b != 0))),
+    (Representation::Bool, Value::Int(v)) => Some(json!(*v != 0)),
+    (Representation::Bool, Value::Uint(v)) => Some(json!(*v != 0)),
+    // BIT values are big endian.
+    (Representation::Integer, Value::Bytes(b)) if b.len() <= 8 => {
+      Some(json!(b.iter().fold(0_u64, |v, b| v << 8 | u64::from(*b))))
+    }
+    _ => None,
+  };
+
+  mapped.unwrap_or_else(|| value_to_json(value))
+}
+
+fn epoch_millis(value: &Value) -> Option<i64> {
+  let (year, month, day, hour, minute, second, micro) = match *value {
+    Value::Date {
+      year,
+      month,
+      day,
+      hour,
+      minute,
+      second,
+      micro,
+    } => (year, month, day, hour, minute, second, micro),
+    _ => return None,
+  };
+  if month == 0 || day == 0 {
+    return None;
+  }
+
+  // http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+  let (month, day) = (i64::from(month), i64::from(day));
+  let year = i64::from(year) - if month <= 2 { 1 } else { 0 };
+  let era = year.div_euclid(400);
+  let yoe = year - era * 400;
+  let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+  let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+  let days = era * 146_097 + doe - 719_468;
+
+  let seconds =
+    days * 86_400 + i64::from(hour) * 3_600 + i64::from(minute) * 60 + i64::from(second);
+  Some(seconds * 1_000 + i64::from(micro) / 1_000)
+}
+
+fn bytes_to_json(b: &[u8]) -> serde_json::Value {
+  match std::str::from_utf8(b) {
+    Ok(s) => json!(s),
+    Err(_) => json!({ "hex": hex(b) }),
+  }
+}
+
+fn transaction_to_json(kind: &str, transaction: &TransactionMetadata) -> serde_json::Value {
+  json!({
+    "type": kind,
+    "gtid": transaction.gtid(),
+    "event_count": transaction.event_count(),
+    "byte_size": transaction.byte_size(),
+  })
+}
+
+fn operation_str(operation: Operation) -> &'static str {
+  match operation {
+    Operation::Insert => "insert",
+    Operation::Update => "update",
+    Operation::Delete => "delete",
+    Operation::Replace => "replace",
+    Operation::Ddl => "ddl",
+    Operation::Begin => "begin",
+    Operation::Commit => "commit",
+    Operation::Rollback => "rollback",
+    Operation::Other => "other",
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::classify::TableName;
+  use crate::time_zone::UtcOffset;
+
+  #[test]
+  fn serializes_rows_events() {
+    let event = ChangeEvent::Update {
+      schema: "pets".to_string(),
+      table: "cats".to_string(),
+      column_types: vec![ColumnType::MYSQL_TYPE_LONG, ColumnType::MYSQL_TYPE_VARCHAR],
+      rows: vec![(
+        Row::new(vec![Some(Value::Int(4)), None]),
+        Row::new(vec![
+          Some(Value::Int(4)),
+          Some(Value::Bytes(b"Charlie".to_vec())),
+        ]),
+      )],
+    };
+
+    assert_eq!(
+      r#"{"rows":[{"after":{"0":4,"1":"Charlie"},"before":{"0":4}}],"schema":"pets","schema_version":"fa814cc22ad1c831","table":"cats","type":"update","version":1}"#,
+      to_string(&event)
+    );
+  }
+
+  #[test]
+  fn serializes_statements() {
+    let event = ChangeEvent::Statement {
+      schema: "pets".to_string(),
+      sql: "DELETE FROM cats".to_string(),
+      operation: Operation::Delete,
+      tables: vec![TableName::new(Some("pets".to_string()), "cats")],
+      time_zone: Some("+02:00".to_string()),
+    };
+
+    assert_eq!(
+      json!({
+        "type": "statement",
+        "schema": "pets",
+        "sql": "DELETE FROM cats",
+        "operation": "delete",
+        "time_zone": "+02:00",
+        "tables": [{ "schema": "pets", "table": "cats" }],
+        "version": 1,
+      }),
+      to_json(&event)
+    );
+  }
+
+  #[test]
+  fn versions_table_schemas() {
+    let cats = [ColumnType::MYSQL_TYPE_LONG, ColumnType::MYSQL_TYPE_VARCHAR];
+    assert_eq!("fa814cc22ad1c831", schema_version(&cats));
+    // A column added, or a column that changed type.
+    let added = [
+      ColumnType::MYSQL_TYPE_LONG,
+      ColumnType::MYSQL_TYPE_VARCHAR,
+      ColumnType::MYSQL_TYPE_DATE,
+    ];
+    assert_ne!(schema_version(&cats), schema_version(&added));
+    let changed = [
+      ColumnType::MYSQL_TYPE_LONGLONG,
+      ColumnType::MYSQL_TYPE_VARCHAR,
+    ];
+    assert_ne!(schema_version(&cats), schema_version(&changed));
+  }
+
+  #[test]
+  fn serializes_values() {
+    assert_eq!(json!(null), value_to_json(&Value::Null));
+    assert_eq!(json!(null), value_to_json(&Value::Float(f64::NAN)));
+    assert_eq!(
+      json!({ "hex": "ff00" }),
+      value_to_json(&Value::Bytes(vec![0xff, 0x00]))
+    );
+    assert_eq!(
+      json!("2016-05-21 00:00:00"),
+      value_to_json(&Value::Date {
+        year: 2016,
+        month: 5,
+        day: 21,
+        hour: 0,
+        minute: 0,
+        second: 0,
+        micro: 0,
+      })
+    );
+    assert_eq!(
+      json!("-49:02:03.000500"),
+      value_to_json(&Value::Time {
+        negative: true,
+        days: 2,
+        hours: 1,
+        minutes: 2,
+        seconds: 3,
+        micros: 500,
+      })
+    );
+  }
+
+  #[test]
+  fn applies_type_mapping() {
+    let event = ChangeEvent::Insert {
+      schema: "pets".to_string(),
+      table: "cats".to_string(),
+      column_types: vec![
+        ColumnType::MYSQL_TYPE_NEWDECIMAL,
+        ColumnType::MYSQL_TYPE_TIMESTAMP2,
+        ColumnType::MYSQL_TYPE_BIT,
+        ColumnType::MYSQL_TYPE_BIT,
+        ColumnType::MYSQL_TYPE_DATE,
+      ],
+      rows: vec![Row::new(vec![
+        Some(Value::Bytes(b"12.50".to_vec())),
+        // 2016-05-21 10:00:00.000500 UTC
+        Some(Value::Timestamp {
+          seconds: 1_463_824_800,
+          micro: 500,
+        }),
+        Some(Value::Bytes(vec![0x01])),
+        Some(Value::Bytes(vec![0x81, 0x02])),
+        Some(Value::Date {
+          year: 1969,
+          month: 12,
+          day: 31,
+          hour: 0,
+          minute: 0,
+          second: 0,
+          micro: 0,
+        }),
+      ])],
+    };
+
+    let mapping = TypeMapping::new()
+      .type_representation(ColumnType::MYSQL_TYPE_NEWDECIMAL, Representation::Float)
+      .type_representation(ColumnType::MYSQL_TYPE_TIMESTAMP, Representation::Iso8601)
+      .type_representation(ColumnType::MYSQL_TYPE_BIT, Representation::Bool)
+      .type_representation(ColumnType::MYSQL_TYPE_DATE, Representation::EpochMillis)
+      .column_representation("pets", "cats", 3, Representation::Integer);
+    assert_eq!(
+      json!({ "0": 12.5, "1": "2016-05-21T10:00:00.000500Z", "2": true, "3": 33_026, "4": -86_400_000 }),
+      to_json_with(&event, &mapping)["rows"][0]
+    );
+
+    let mapping = mapping.column_representation("pets", "cats", 0, Representation::Default);
+    assert_eq!(
+      json!("12.50"),
+      to_json_with(&event, &mapping)["rows"][0]["0"]
+    );
+    assert_eq!(json!({ "hex": "8102" }), to_json(&event)["rows"][0]["3"]);
+  }
+
+  #[test]
+  fn renders_timestamps_in_time_zone() {
+    let event = ChangeEvent::Insert {
+      schema: "pets".to_string(),
+      table: "cats".to_string(),
+      column_types: vec![
+        ColumnType::MYSQL_TYPE_TIMESTAMP2,
+        ColumnType::MYSQL_TYPE_DATETIME2,
+      ],
+      rows: vec![Row::new(vec![
+        Some(Value::Timestamp {
+          seconds: 1_463_824_800,
+          micro: 0,
+        }),
+        Some(Value::Date {
+          year: 2016,
+          month: 5,
+          day: 21,
+          hour: 10,
+          minute: 0,
+          second: 0,
+          micro: 0,
+        }),
+      ])],
+    };
+    let time_zone = UtcOffset::parse("-04:00").unwrap();
+
+    // DATETIMEs are wall clock times, and are never converted.
+    let mapping = TypeMapping::new().time_zone(time_zone);
+    assert_eq!(
+      json!({ "0": "2016-05-21 06:00:00", "1": "2016-05-21 10:00:00" }),
+      to_json_with(&event, &mapping)["rows"][0]
+    );
+
+    let mapping = mapping
+      .type_representation(ColumnType::MYSQL_TYPE_TIMESTAMP, Representation::Iso8601)
+      .type_representation(ColumnType::MYSQL_TYPE_DATETIME, Representation::Iso8601);
+    assert_eq!(
+      json!({ "0": "2016-05-21T06:00:00-04:00", "1": "2016-05-21T10:00:00" }),
+      to_json_with(&event, &mapping)["rows"][0]
+    );
+
+    let mapping = mapping.type_representation(
+      ColumnType::MYSQL_TYPE_TIMESTAMP,
+      Representation::EpochMillis,
+    );
+    assert_eq!(
+      json!(1_463_824_800_000_i64),
+      to_json_with(&event, &mapping)["rows"][0]["0"]
+    );
+
+    let zero = Value::Timestamp {
+      seconds: 0,
+      micro: 0,
+    };
+    assert_eq!(json!("0000-00-00 00:00:00"), value_to_json(&zero));
+  }
+
+  #[test]
+  fn converts_dates_to_epoch_millis() {
+    let date = |year, month, day| Value::Date {
+      year,
+      month,
+      day,
+      hour: 1,
+      minute: 2,
+      second: 3,
+      micro: 4_000,
+    };
+    assert_eq!(Some(3_723_004), epoch_millis(&date(1970, 1, 1)));
+    assert_eq!(Some(1_582_938_123_004), epoch_millis(&date(2020, 2, 29)));
+    assert_eq!(None, epoch_millis(&date(0, 0, 0)));
+  }
+}
@@ -0,0 +1,391 @@
+// JSON Schema (draft 2020-12) of the envelope emitted by `tail_mysql::json`, for consumers that
+// generate their types from it.
+//
+// `envelope_schema` describes every change event, with rows of any shape. `table_schema` narrows
+// the rows events of a single table to its columns, as listed in information_schema: each column
+// is a property named after its index (see `json::row_to_json`), titled after the column name.
+//
+// The schemas pin the `version` of the envelope they describe, see `json::ENVELOPE_VERSION`.
+
+use super::json::ENVELOPE_VERSION;
+use serde_json::{json, Map};
+
+const DRAFT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// Column of a table, as described by information_schema.COLUMNS.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ColumnSchema {
+  name: String,
+  data_type: String,
+  nullable: bool,
+}
+
+impl ColumnSchema {
+  /// `data_type` is the `DATA_TYPE` of information_schema.COLUMNS, e.g. `int` or `varchar`.
+  pub fn new(name: impl Into<String>, data_type: impl Into<String>, nullable: bool) -> Self {
+    Self {
+      name: name.into(),
+      data_type: data_type.into(),
+      nullable,
+    }
+  }
+
+  pub fn name_str(&self) -> &str {
+    &self.name
+  }
+
+  pub fn data_type_str(&self) -> &str {
+    &self.data_type
+  }
+
+  pub fn is_nullable(&self) -> bool {
+    self.nullable
+  }
+
+  fn value_schema(&self) -> serde_json::Value {
+    let value = match self.data_type.to_ascii_lowercase().as_str() {
+      // ENUM and SET are replicated as the index of the value and as a bitmask of the members.
+      "tinyint" | "smallint" | "mediumint" | "int" | "integer" | "bigint" | "year" | "enum"
+      | "set" => json!({ "type": "integer" }),
+      // NaN and infinities serialize as null.
+      "float" | "double" | "real" => json!({ "type": ["number", "null"] }),
+      "decimal" | "numeric" => json!({ "type": "string", "pattern": "^-?[0-9]+(\\.[0-9]+)?$" }),
+      // TIMESTAMPs are rendered in UTC.
+      "date" | "datetime" | "timestamp" => json!({ "$ref": "#/$defs/datetime" }),
+      "time" => json!({ "$ref": "#/$defs/time" }),
+      // Strings, blobs, JSON (in the MYSQL binary format), BIT and spatial types.
+      _ => json!({ "$ref": "#/$defs/large_bytes" }),
+    };
+
+    let mut schema = json!({ "title": self.name });
+    if self.nullable {
+      schema["anyOf"] = json!([value, { "type": "null" }]);
+    } else {
+      schema["allOf"] = json!([value]);
+    }
+    schema
+  }
+}
+
+/// Returns the JSON Schema of the envelope of every `ChangeEvent`.
+pub fn envelope_schema() -> serde_json::Value {
+  let row = json!({ "$ref": "#/$defs/row" });
+  json!({
+    "$schema": DRAFT,
+    "$id": "tail_mysql/change_event.json",
+    "title": "ChangeEvent",
+    "oneOf": events_schema(&row),
+    "$defs": defs(json!({
+      "type": "object",
+      "patternProperties": { "^[0-9]+$": { "$ref": "#/$defs/value" } },
+      "additionalProperties": false,
+    })),
+  })
+}
+
+/// Returns the JSON Schema of the rows events of `schema`.`table`, and of the statements and
+/// transaction markers, which are not specific to a table.
+pub fn table_schema(schema: &str, table: &str, columns: &[ColumnSchema]) -> serde_json::Value {
+  let properties: Map<String, serde_json::Value> = columns
+    .iter()
+    .enumerate()
+    .map(|(i, column)| (i.to_string(), column.value_schema()))
+    .collect();
+  let row = json!({ "$ref": "#/$defs/row" });
+  let mut events = events_schema(&row);
+  for event in events.iter_mut().take(3) {
+    event["properties"]["schema"] = json!({ "const": schema });
+    event["properties"]["table"] = json!({ "const": table });
+  }
+
+  json!({
+    "$schema": DRAFT,
+    "$id": format!("tail_mysql/{}.{}.json", schema, table),
+    "title": format!("{}.{}", schema, table),
+    "oneOf": events,
+    "$defs": defs(json!({
+      "type": "object",
+      "properties": properties,
+      "additionalProperties": false,
+    })),
+  })
+}
+
+// Rows events first, see `table_schema`.
+fn events_schema(row: &serde_json::Value) -> Vec<serde_json::Value> {
+  let rows_event = |kind: &str, row: serde_json::Value| {
+    json!({
+      "type": "object",
+      "properties": {
+        "type": { "const": kind },
+        "schema": { "type": "string" },
+        "table": { "type": "string" },
+        "schema_version": { "type": "string", "pattern": "^[0-9a-f]{16}$" },
+        "rows": { "type": "array", "items": row },
+        "idempotency_key": { "type": "string" },
+      },
+      "required": ["type", "schema", "table", "schema_version", "rows"],
+    })
+  };
+  let transaction_event = |kind: &str| {
+    json!({
+      "type": "object",
+      "properties": {
+        "type": { "const": kind },
+        "gtid": { "type": ["string", "null"] },
+        "event_count": { "type": "integer", "minimum": 0 },
+        "byte_size": { "type": "integer", "minimum": 0 },
+      },
+      "required": ["type", "gtid", "event_count", "byte_size"],
+    })
+  };
+
+  let mut events = vec![
+    rows_event("insert", row.clone()),
+    rows_event(
+      "update",
+      json!({
+        "type": "object",
+        "properties": { "before": row, "after": row },
+        "required": ["before", "after"],
+      }),
+    ),
+    rows_event("delete", row.clone()),
+    json!({
+      "type": "object",
+      "properties": {
+        "type": { "const": "statement" },
+        "schema": { "type": "string" },
+        "sql": { "type": "string" },
+        "time_zone": { "type": ["string", "null"] },
+        "idempotency_key": { "type": "string" },
+        "operation": {
+          "enum": [
+            "insert", "update", "delete", "replace", "ddl", "begin", "commit", "rollback", "other",
+          ],
+        },
+        "tables": {
+          "type": "array",
+          "items": {
+            "type": "object",
+            "properties": {
+              "schema": { "type": ["string", "null"] },
+              "table": { "type": "string" },
+            },
+            "required": ["schema", "table"],
+          },
+        },
+      },
+      "required": ["type", "schema", "sql", "operation", "time_zone", "tables"],
+    }),
+    transaction_event("begin"),
+    transaction_event("end"),
+    json!({
+      "type": "object",
+      "properties": {
+        "type": { "const": "schema_drift" },
+        "schema": { "type": "string" },
+        "table": { "type": "string" },
+        "previous_column_count": { "type": "integer", "minimum": 0 },
+        "column_count": { "type": "integer", "minimum": 0 },
+        "column_names": { "type": "array", "items": { "type": "string" } },
+      },
+      "required": [
+        "type", "schema", "table", "previous_column_count", "column_count", "column_names",
+      ],
+    }),
+    json!({
+      "type": "object",
+      "properties": {
+        "type": { "const": "source_restart" },
+        "file": { "type": ["string", "null"] },
+        "position": { "type": ["integer", "null"], "minimum": 0 },
+      },
+      "required": ["type", "file", "position"],
+    }),
+  ];
+  for event in events.iter_mut() {
+    event["properties"]["version"] = json!({ "const": ENVELOPE_VERSION });
+    event["required"]
+      .as_array_mut()
+      .unwrap()
+      .push(json!("version"));
+  }
+  events
+}
+
+fn defs(row: serde_json::Value) -> serde_json::Value {
+  let sha256 = json!({ "type": "string", "pattern": "^[0-9a-f]{64}$" });
+  let size = json!({ "type": "integer", "minimum": 0 });
+  let hex = json!({ "type": "string", "pattern": "^([0-9a-f]{2})*$" });
+  json!({
+    "row": row,
+    "bytes": {
+      "anyOf": [
+        { "type": "string" },
+        {
+          "type": "object",
+          "properties": { "hex": hex },
+          "required": ["hex"],
+          "additionalProperties": false,
+        },
+      ],
+    },
+    "datetime": {
+      "type": "string",
+      "pattern": "^[0-9]{4}-[0-9]{2}-[0-9]{2} [0-9]{2}:[0-9]{2}:[0-9]{2}(\\.[0-9]{6})?$",
+    },
+    "time": {
+      "type": "string",
+      "pattern": "^-?[0-9]{2,}:[0-9]{2}:[0-9]{2}(\\.[0-9]{6})?$",
+    },
+    // Values over their column size limit, see `ValueLimits`.
+    "large_bytes": {
+      "anyOf": [
+        { "$ref": "#/$defs/bytes" },
+        {
+          "type": "object",
+          "properties": { "truncated": { "$ref": "#/$defs/bytes" }, "size": size },
+          "required": ["truncated", "size"],
+          "additionalProperties": false,
+        },
+        {
+          "type": "object",
+          "properties": { "sha256": sha256, "size": size },
+          "required": ["sha256", "size"],
+          "additionalProperties": false,
+        },
+        {
+          "type": "object",
+          "properties": { "url": { "type": "string" }, "sha256": sha256, "size": size },
+          "required": ["url", "sha256", "size"],
+          "additionalProperties": false,
+        },
+        { "$ref": "#/$defs/encrypted" },
+      ],
+    },
+    // Values of encrypted columns, see `ColumnEncryption`.
+    "encrypted": {
+      "type": "object",
+      "properties": {
+        "key_id": { "type": "string" },
+        "wrapped_key": hex,
+        "nonce": { "type": "string", "pattern": "^[0-9a-f]{24}$" },
+        "ciphertext": hex,
+      },
+      "required": ["key_id", "wrapped_key", "nonce", "ciphertext"],
+      "additionalProperties": false,
+    },
+    "value": {
+      "anyOf": [
+        { "type": ["integer", "number", "null"] },
+        { "$ref": "#/$defs/large_bytes" },
+      ],
+    },
+  })
+}
+
+/// Lists the columns of `schema`.`table` from information_schema, in the order of the row images.
+#[cfg(feature = "client")]
+pub async fn table_columns<S>(
+  conn: &mut super::conn::Connection<S>,
+  schema: &str,
+  table: &str,
+) -> Result<Vec<ColumnSchema>, super::conn::DriverError>
+where
+  S: futures::io::AsyncRead + futures::io::AsyncWrite + Unpin,
+{
+  use super::util::quote_literal;
+
+  let sql = format!(
+    "SELECT COLUMN_NAME, DATA_TYPE, IS_NULLABLE FROM information_schema.COLUMNS \
+     WHERE TABLE_SCHEMA = {} AND TABLE_NAME = {} ORDER BY ORDINAL_POSITION",
+    quote_literal(schema),
+    quote_literal(table)
+  );
+  let results = conn.query(sql).await?;
+  let columns = results
+    .iter()
+    .filter_map(|row| match row.values() {
+      [name, data_type, nullable] => Some(ColumnSchema::new(
+        name.as_str()?,
+        data_type.as_str()?,
+        nullable.as_str()? == "YES",
+      )),
+      _ => None,
+    })
+    .collect();
+  Ok(columns)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn describes_the_envelope() {
+    let schema = envelope_schema();
+    let kinds: Vec<&str> = schema["oneOf"]
+      .as_array()
+      .unwrap()
+      .iter()
+      .map(|event| event["properties"]["type"]["const"].as_str().unwrap())
+      .collect();
+    assert_eq!(
+      vec![
+        "insert",
+        "update",
+        "delete",
+        "statement",
+        "begin",
+        "end",
+        "schema_drift",
+        "source_restart"
+      ],
+      kinds
+    );
+    assert_eq!(
+      json!({ "$ref": "#/$defs/row" }),
+      schema["oneOf"][1]["properties"]["rows"]["items"]["properties"]["after"]
+    );
+    assert_eq!(
+      json!({ "const": ENVELOPE_VERSION }),
+      schema["oneOf"][4]["properties"]["version"]
+    );
+  }
+
+  #[test]
+  fn describes_table_rows() {
+    let columns = vec![
+      ColumnSchema::new("id", "bigint", false),
+      ColumnSchema::new("name", "varchar", true),
+      ColumnSchema::new("born_at", "DATETIME", false),
+    ];
+    let schema = table_schema("pets", "cats", &columns);
+
+    assert_eq!("pets.cats", schema["title"]);
+    assert_eq!(
+      json!({ "const": "cats" }),
+      schema["oneOf"][0]["properties"]["table"]
+    );
+    assert_eq!(
+      json!({ "type": "string" }),
+      schema["oneOf"][3]["properties"]["schema"]
+    );
+    assert_eq!(
+      json!({
+        "type": "object",
+        "properties": {
+          "0": { "title": "id", "allOf": [{ "type": "integer" }] },
+          "1": {
+            "title": "name",
+            "anyOf": [{ "$ref": "#/$defs/large_bytes" }, { "type": "null" }],
+          },
+          "2": { "title": "born_at", "allOf": [{ "$ref": "#/$defs/datetime" }] },
+        },
+        "additionalProperties": false,
+      }),
+      schema["$defs"]["row"]
+    );
+  }
+}
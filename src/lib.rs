@@ -4,10 +4,119 @@
 #![allow(unused_assignments)]
 #![allow(unused_mut)]
 
-mod buf_ext;
+// The parsers live in tail_mysql_core, which builds without the network layer. Its modules are only
+// exposed with the `protocol` feature, `conn` and `event` re-export the types of their API, e.g.
+// `BinlogEvent` or `Value`.
+use tail_mysql_core::buf_ext;
+pub use tail_mysql_core::time_zone;
+#[cfg(not(feature = "protocol"))]
+use tail_mysql_core::{protocol, protocol_binlog, value};
+#[cfg(feature = "protocol")]
+pub use tail_mysql_core::{protocol, protocol_binlog, value};
+
+#[cfg(feature = "encryption")]
+pub mod binlog_encryption;
+#[cfg(feature = "tokio-runtime")]
+pub mod blocking;
+#[cfg(all(feature = "tokio-runtime", feature = "binlog"))]
+pub mod checkpoint;
+#[cfg(feature = "binlog")]
+pub mod classify;
+#[cfg(feature = "client")]
+pub mod compression;
+#[cfg(all(feature = "tokio-runtime", feature = "json"))]
+pub mod config;
+#[cfg(feature = "client")]
 pub mod conn;
-mod protocol;
-mod protocol_binlog;
+#[cfg(feature = "client")]
+pub mod crash_dump;
+#[cfg(feature = "binlog")]
+pub mod dropped;
+#[cfg(feature = "json")]
+pub mod dry_run;
+#[cfg(feature = "encryption")]
+pub mod encryption;
+#[cfg(all(feature = "client", feature = "binlog"))]
+pub mod enrich;
+#[cfg(feature = "binlog")]
+pub mod event;
+#[cfg(feature = "client")]
+pub mod explain;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "binlog")]
+pub mod fixture;
+#[cfg(any(test, feature = "fuzzing"))]
+#[doc(hidden)]
+pub mod fuzzing;
+#[cfg(feature = "client")]
+pub mod generate;
+#[cfg(feature = "client")]
+pub mod gtid;
+#[cfg(feature = "binlog")]
+pub mod idempotency;
+#[cfg(feature = "binlog")]
+pub mod inspect;
+#[cfg(feature = "client")]
+pub mod interceptor;
+#[cfg(feature = "json")]
+pub mod json;
+#[cfg(feature = "json")]
+pub mod json_schema;
+#[cfg(feature = "binlog")]
+pub mod limits;
+pub mod metrics;
+#[cfg(feature = "binlog")]
+pub mod migration;
+#[cfg(feature = "client")]
+pub mod params;
+#[cfg(all(feature = "client", feature = "binlog"))]
+pub mod pipeline;
+#[cfg(feature = "client")]
+pub mod pool;
+#[cfg(feature = "client")]
+pub mod positions;
+pub mod prelude;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(feature = "redaction")]
+pub mod redaction;
+#[cfg(all(feature = "client", feature = "binlog"))]
+pub mod replay;
+#[cfg(feature = "client")]
+pub mod replica;
+#[cfg(feature = "binlog")]
+pub mod replication_filter;
+#[cfg(feature = "client")]
+pub mod retry;
+#[cfg(feature = "binlog")]
+pub mod routing;
+#[cfg(feature = "client")]
+pub mod runtime;
+#[cfg(feature = "binlog")]
+pub mod sampling;
+#[cfg(feature = "client")]
 mod scramble;
+#[cfg(feature = "client")]
+pub mod session;
+pub mod sink;
+pub mod slow;
+#[cfg(feature = "client")]
+pub mod statistics;
+#[cfg(all(feature = "client", feature = "json"))]
+pub mod status;
+#[cfg(feature = "client")]
+pub mod supervisor;
+#[cfg(feature = "binlog")]
+pub mod temporal;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "client")]
+pub mod tls;
+#[cfg(feature = "binlog")]
+pub mod transaction;
 mod util;
-mod value;
+#[cfg(feature = "client")]
+pub mod verify;
+#[cfg(feature = "binlog")]
+pub mod watermark;
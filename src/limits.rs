@@ -0,0 +1,265 @@
+// Size limits applied to column values while decoding rows images, so that a single large BLOB
+// does not produce an event that sinks with message size limits (e.g. Kafka defaults to 1MB)
+// would reject.
+
+use super::sink::blob::BlobStore;
+use super::util::{hex, unexpected_err};
+use super::value::{Row, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum OversizedValue {
+  /// Keep the first `max_size` bytes, as `Value::Truncated`.
+  Truncate,
+  /// Replace the value by its SHA-256 digest, as `Value::Digest`.
+  Digest,
+  /// Write the value to the configured `BlobStore`, and replace it by a `Value::Reference`.
+  Offload,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct ValueLimit {
+  max_size: usize,
+  policy: OversizedValue,
+}
+
+impl ValueLimit {
+  pub fn new(max_size: usize, policy: OversizedValue) -> Self {
+    Self { max_size, policy }
+  }
+
+  pub fn truncate(max_size: usize) -> Self {
+    Self::new(max_size, OversizedValue::Truncate)
+  }
+
+  pub fn digest(max_size: usize) -> Self {
+    Self::new(max_size, OversizedValue::Digest)
+  }
+
+  pub fn offload(max_size: usize) -> Self {
+    Self::new(max_size, OversizedValue::Offload)
+  }
+
+  pub fn max_size(&self) -> usize {
+    self.max_size
+  }
+
+  pub fn policy(&self) -> OversizedValue {
+    self.policy
+  }
+
+  // Returns true when the value went over the limit.
+  fn apply(
+    &self,
+    value: &mut Value,
+    blob_store: Option<&dyn BlobStore>,
+    schema: &str,
+    table: &str,
+  ) -> io::Result<bool> {
+    let bytes = match value {
+      Value::Bytes(bytes) if bytes.len() > self.max_size => std::mem::take(bytes),
+      _ => return Ok(false),
+    };
+
+    let size = bytes.len();
+    let sha256 = || {
+      let mut sha256 = [0; 32];
+      sha256.copy_from_slice(&Sha256::digest(&bytes));
+      sha256
+    };
+
+    *value = match self.policy {
+      OversizedValue::Truncate => {
+        let mut prefix = bytes;
+        prefix.truncate(self.max_size);
+        Value::Truncated { prefix, size }
+      }
+      OversizedValue::Digest => Value::Digest {
+        sha256: sha256(),
+        size,
+      },
+      OversizedValue::Offload => {
+        let blob_store =
+          blob_store.ok_or_else(|| unexpected_err("offloading values requires a blob store"))?;
+        let sha256 = sha256();
+        let key = format!("{}/{}/{}", schema, table, hex(&sha256));
+        let url = blob_store.put(&key, &bytes)?;
+        Value::Reference { url, sha256, size }
+      }
+    };
+    Ok(true)
+  }
+}
+
+/// Per column size limits. Columns are identified by their position in the table, since the
+/// column names are not part of the binlog unless `binlog_row_metadata=FULL`.
+#[derive(Clone, Debug, Default)]
+pub struct ValueLimits {
+  default: Option<ValueLimit>,
+  tables: HashMap<(String, String), HashMap<usize, ValueLimit>>,
+  blob_store: Option<Arc<dyn BlobStore>>,
+}
+
+impl ValueLimits {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Limit applied to every column without a specific limit.
+  pub fn default_limit(mut self, limit: ValueLimit) -> Self {
+    self.default = Some(limit);
+    self
+  }
+
+  pub fn column_limit(
+    mut self,
+    schema: impl Into<String>,
+    table: impl Into<String>,
+    column: usize,
+    limit: ValueLimit,
+  ) -> Self {
+    self
+      .tables
+      .entry((schema.into(), table.into()))
+      .or_default()
+      .insert(column, limit);
+    self
+  }
+
+  /// Store used by the `OversizedValue::Offload` limits. Blobs are keyed by
+  /// `{schema}/{table}/{sha256}`.
+  pub fn blob_store(mut self, blob_store: Arc<dyn BlobStore>) -> Self {
+    self.blob_store = Some(blob_store);
+    self
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.default.is_none() && self.tables.is_empty()
+  }
+
+  /// Applies the limits to the rows of `schema`.`table`, returns the number of values that went
+  /// over their limit. Fails when a value could not be offloaded.
+  pub fn apply<'a>(
+    &self,
+    schema: &str,
+    table: &str,
+    rows: impl IntoIterator<Item = &'a mut Row>,
+  ) -> io::Result<u64> {
+    if self.is_empty() {
+      return Ok(0);
+    }
+
+    let columns = self.tables.get(&(schema.to_string(), table.to_string()));
+
+    let mut limited = 0;
+    for row in rows {
+      for (i, value) in row.values_mut().iter_mut().enumerate() {
+        let limit = columns
+          .and_then(|columns| columns.get(&i))
+          .or(self.default.as_ref());
+        if let (Some(limit), Some(value)) = (limit, value) {
+          if limit.apply(value, self.blob_store.as_deref(), schema, table)? {
+            limited += 1;
+          }
+        }
+      }
+    }
+
+    Ok(limited)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use std::sync::Mutex;
+
+  #[derive(Debug, Default)]
+  struct MemoryBlobStore {
+    blobs: Mutex<HashMap<String, Vec<u8>>>,
+  }
+
+  impl BlobStore for MemoryBlobStore {
+    fn put(&self, key: &str, bytes: &[u8]) -> io::Result<String> {
+      self
+        .blobs
+        .lock()
+        .unwrap()
+        .insert(key.to_string(), bytes.to_vec());
+      Ok(format!("memory://{}", key))
+    }
+  }
+
+  fn row() -> Row {
+    Row::new(vec![
+      Some(Value::Int(1)),
+      Some(Value::Bytes(b"Charlie".to_vec())),
+      Some(Value::Bytes(b"River".to_vec())),
+      None,
+    ])
+  }
+
+  #[test]
+  fn truncates_values_over_the_default_limit() {
+    let limits = ValueLimits::new().default_limit(ValueLimit::truncate(5));
+    let mut rows = [row()];
+    assert_eq!(1, limits.apply("pets", "cats", rows.iter_mut()).unwrap());
+    assert_eq!(
+      Some(&Value::Truncated {
+        prefix: b"Charl".to_vec(),
+        size: 7
+      }),
+      rows[0].get(1)
+    );
+    assert_eq!(Some(&Value::Bytes(b"River".to_vec())), rows[0].get(2));
+  }
+
+  #[test]
+  fn column_limits_take_precedence() {
+    let limits = ValueLimits::new()
+      .default_limit(ValueLimit::truncate(1024))
+      .column_limit("pets", "cats", 2, ValueLimit::digest(4));
+
+    let mut rows = [row()];
+    assert_eq!(1, limits.apply("pets", "cats", rows.iter_mut()).unwrap());
+    match rows[0].get(2) {
+      Some(Value::Digest { size, .. }) => assert_eq!(5, *size),
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+
+    let mut rows = [row()];
+    assert_eq!(0, limits.apply("pets", "dogs", rows.iter_mut()).unwrap());
+  }
+
+  #[test]
+  fn offloads_values_to_the_blob_store() {
+    let blob_store = Arc::new(MemoryBlobStore::default());
+    let limits = ValueLimits::new()
+      .default_limit(ValueLimit::offload(5))
+      .blob_store(blob_store.clone());
+
+    let mut rows = [row()];
+    assert_eq!(1, limits.apply("pets", "cats", rows.iter_mut()).unwrap());
+    match rows[0].get(1) {
+      Some(Value::Reference { url, sha256, size }) => {
+        let key = format!("pets/cats/{}", hex(sha256));
+        assert_eq!(&format!("memory://{}", key), url);
+        assert_eq!(7, *size);
+        assert_eq!(
+          Some(&b"Charlie".to_vec()),
+          blob_store.blobs.lock().unwrap().get(&key)
+        );
+      }
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+  }
+
+  #[test]
+  fn fails_to_offload_without_blob_store() {
+    let limits = ValueLimits::new().default_limit(ValueLimit::offload(5));
+    assert!(limits.apply("pets", "cats", [row()].iter_mut()).is_err());
+  }
+}
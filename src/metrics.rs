@@ -0,0 +1,255 @@
+#[cfg(feature = "binlog")]
+use super::dropped::DroppedEvents;
+use super::slow::SlowOperation;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Counters describing what happened while decoding the binlog stream.
+///
+/// Cheap to share between tasks through an `Arc`, every counter is a relaxed atomic.
+#[derive(Debug, Default)]
+pub struct Metrics {
+  statement_events: AtomicU64,
+  oversized_transactions: AtomicU64,
+  oversized_transaction_bytes: AtomicU64,
+  incomplete_transactions: AtomicU64,
+  limited_values: AtomicU64,
+  shadow_table_events: AtomicU64,
+  migration_cut_overs: AtomicU64,
+  sampled_out_rows: AtomicU64,
+  encrypted_values: AtomicU64,
+  redacted_values: AtomicU64,
+  expired_events: AtomicU64,
+  schema_drifts: AtomicU64,
+  invalid_temporal_values: AtomicU64,
+  replication_lag_micros: AtomicU64,
+  #[cfg(feature = "binlog")]
+  dropped: DroppedEvents,
+}
+
+impl Metrics {
+  /// Number of DML statements surfaced as `ChangeEvent::Statement`. Anything but 0 means the
+  /// upstream is not running with `binlog_format=ROW`, and that row images are not available.
+  pub fn statement_events(&self) -> u64 {
+    self.statement_events.load(Ordering::Relaxed)
+  }
+
+  pub(crate) fn incr_statement_events(&self) -> u64 {
+    self.statement_events.fetch_add(1, Ordering::Relaxed)
+  }
+
+  /// Number of transactions that went over the grouping buffer size, and were streamed in chunks.
+  pub fn oversized_transactions(&self) -> u64 {
+    self.oversized_transactions.load(Ordering::Relaxed)
+  }
+
+  /// Total size of the changes of oversized transactions, in bytes.
+  pub fn oversized_transaction_bytes(&self) -> u64 {
+    self.oversized_transaction_bytes.load(Ordering::Relaxed)
+  }
+
+  pub(crate) fn incr_oversized_transactions(&self) {
+    self.oversized_transactions.fetch_add(1, Ordering::Relaxed);
+  }
+
+  pub(crate) fn add_oversized_transaction_bytes(&self, bytes: u64) {
+    self
+      .oversized_transaction_bytes
+      .fetch_add(bytes, Ordering::Relaxed);
+  }
+
+  /// Number of transactions dropped for lacking their END marker, e.g. after an event that failed
+  /// to decode was skipped, see `TransactionGrouper` and `MysqlSink`.
+  pub fn incomplete_transactions(&self) -> u64 {
+    self.incomplete_transactions.load(Ordering::Relaxed)
+  }
+
+  pub(crate) fn incr_incomplete_transactions(&self) {
+    self.incomplete_transactions.fetch_add(1, Ordering::Relaxed);
+  }
+
+  /// Number of column values that went over their size limit, see `ValueLimits`.
+  pub fn limited_values(&self) -> u64 {
+    self.limited_values.load(Ordering::Relaxed)
+  }
+
+  pub(crate) fn add_limited_values(&self, count: u64) {
+    self.limited_values.fetch_add(count, Ordering::Relaxed);
+  }
+
+  /// Number of changes to the shadow tables of online schema migrations that were dropped, see
+  /// `ShadowTablePolicy`.
+  pub fn shadow_table_events(&self) -> u64 {
+    self.shadow_table_events.load(Ordering::Relaxed)
+  }
+
+  pub(crate) fn incr_shadow_table_events(&self) {
+    self.shadow_table_events.fetch_add(1, Ordering::Relaxed);
+  }
+
+  /// Number of online schema migrations whose cut-over was seen, see `migration::cut_over`.
+  pub fn migration_cut_overs(&self) -> u64 {
+    self.migration_cut_overs.load(Ordering::Relaxed)
+  }
+
+  pub(crate) fn incr_migration_cut_overs(&self) {
+    self.migration_cut_overs.fetch_add(1, Ordering::Relaxed);
+  }
+
+  /// Number of rows dropped by the sampling of their table, see `Sampling`.
+  pub fn sampled_out_rows(&self) -> u64 {
+    self.sampled_out_rows.load(Ordering::Relaxed)
+  }
+
+  pub(crate) fn add_sampled_out_rows(&self, count: u64) {
+    self.sampled_out_rows.fetch_add(count, Ordering::Relaxed);
+  }
+
+  /// Number of column values encrypted, see `ColumnEncryption`.
+  pub fn encrypted_values(&self) -> u64 {
+    self.encrypted_values.load(Ordering::Relaxed)
+  }
+
+  #[cfg_attr(not(feature = "encryption"), allow(dead_code))]
+  pub(crate) fn add_encrypted_values(&self, count: u64) {
+    self.encrypted_values.fetch_add(count, Ordering::Relaxed);
+  }
+
+  /// Number of column values redacted, see `Redaction`.
+  pub fn redacted_values(&self) -> u64 {
+    self.redacted_values.load(Ordering::Relaxed)
+  }
+
+  #[cfg_attr(not(feature = "redaction"), allow(dead_code))]
+  pub(crate) fn add_redacted_values(&self, count: u64) {
+    self.redacted_values.fetch_add(count, Ordering::Relaxed);
+  }
+
+  /// Number of changes dropped for being older than the maximum event age, see
+  /// `EventDecoder::max_event_age`.
+  pub fn expired_events(&self) -> u64 {
+    self.expired_events.load(Ordering::Relaxed)
+  }
+
+  pub(crate) fn incr_expired_events(&self) {
+    self.expired_events.fetch_add(1, Ordering::Relaxed);
+  }
+
+  /// Number of times the number of columns of a table changed between two of its
+  /// TABLE_MAP_EVENTs, see `EventDecoder::emit_schema_drift`.
+  pub fn schema_drifts(&self) -> u64 {
+    self.schema_drifts.load(Ordering::Relaxed)
+  }
+
+  pub(crate) fn incr_schema_drifts(&self) {
+    self.schema_drifts.fetch_add(1, Ordering::Relaxed);
+  }
+
+  /// Number of zero dates and out of range temporal values found in rows events, see
+  /// `EventDecoder::invalid_temporals`. Only counted unless they are kept as parsed.
+  pub fn invalid_temporal_values(&self) -> u64 {
+    self.invalid_temporal_values.load(Ordering::Relaxed)
+  }
+
+  pub(crate) fn add_invalid_temporal_values(&self, count: u64) {
+    self
+      .invalid_temporal_values
+      .fetch_add(count, Ordering::Relaxed);
+  }
+
+  /// Time between the commit of the last transaction decoded, on the server it originated from,
+  /// and its decoding. Falls back to the timestamp of the GTID_EVENT before MYSQL 8.0.1, which can
+  /// be stamped by an intermediate server of the replication chain.
+  pub fn replication_lag(&self) -> Duration {
+    Duration::from_micros(self.replication_lag_micros.load(Ordering::Relaxed))
+  }
+
+  pub(crate) fn set_replication_lag(&self, lag: Duration) {
+    let micros = lag.as_micros().min(u128::from(u64::MAX)) as u64;
+    self.replication_lag_micros.store(micros, Ordering::Relaxed);
+  }
+
+  /// Changes dropped by the decoder and the filters of the pipeline, by table and reason.
+  #[cfg(feature = "binlog")]
+  pub fn dropped(&self) -> &DroppedEvents {
+    &self.dropped
+  }
+}
+
+/// Counters of a stage of a `pipeline::Pipeline`, relaxed atomics like `Metrics`.
+#[derive(Debug, Default)]
+pub struct StageMetrics {
+  queue_length: AtomicU64,
+  processed: AtomicU64,
+  latency_nanos: AtomicU64,
+  max_latency_nanos: AtomicU64,
+}
+
+impl StageMetrics {
+  /// Number of items waiting in the queue in front of the stage.
+  pub fn queue_length(&self) -> u64 {
+    self.queue_length.load(Ordering::Relaxed)
+  }
+
+  /// Number of items the stage is done with.
+  pub fn processed(&self) -> u64 {
+    self.processed.load(Ordering::Relaxed)
+  }
+
+  /// Average time the stage spent on an item, zero until it processed one.
+  pub fn average_latency(&self) -> Duration {
+    let latency = self.latency_nanos.load(Ordering::Relaxed);
+    latency
+      .checked_div(self.processed())
+      .map(Duration::from_nanos)
+      .unwrap_or_default()
+  }
+
+  pub fn max_latency(&self) -> Duration {
+    Duration::from_nanos(self.max_latency_nanos.load(Ordering::Relaxed))
+  }
+
+  pub(crate) fn incr_queue_length(&self) {
+    self.queue_length.fetch_add(1, Ordering::Relaxed);
+  }
+
+  pub(crate) fn decr_queue_length(&self) {
+    self.queue_length.fetch_sub(1, Ordering::Relaxed);
+  }
+
+  pub(crate) fn record(&self, latency: Duration) {
+    let nanos = latency.as_nanos().min(u128::from(u64::MAX)) as u64;
+    self.processed.fetch_add(1, Ordering::Relaxed);
+    self.latency_nanos.fetch_add(nanos, Ordering::Relaxed);
+    self.max_latency_nanos.fetch_max(nanos, Ordering::Relaxed);
+  }
+}
+
+/// Number of operations that went over their threshold, see `slow::SlowOperations`. Relaxed
+/// atomics like `Metrics`.
+#[derive(Debug, Default)]
+pub struct SlowOperationMetrics {
+  handshakes: AtomicU64,
+  queries: AtomicU64,
+  event_reads: AtomicU64,
+  sink_publishes: AtomicU64,
+}
+
+impl SlowOperationMetrics {
+  pub fn count(&self, operation: SlowOperation) -> u64 {
+    self.counter(operation).load(Ordering::Relaxed)
+  }
+
+  pub(crate) fn incr(&self, operation: SlowOperation) {
+    self.counter(operation).fetch_add(1, Ordering::Relaxed);
+  }
+
+  fn counter(&self, operation: SlowOperation) -> &AtomicU64 {
+    match operation {
+      SlowOperation::Handshake => &self.handshakes,
+      SlowOperation::Query => &self.queries,
+      SlowOperation::EventRead => &self.event_reads,
+      SlowOperation::SinkPublish => &self.sink_publishes,
+    }
+  }
+}
@@ -0,0 +1,207 @@
+// Awareness of online schema migrations run with gh-ost or pt-online-schema-change.
+//
+// Both tools create a shadow table with the new schema, copy the rows of the original table into
+// it, keep it in sync by replaying the changes of the original table (gh-ost tails the binlog,
+// pt-osc uses triggers), and finally swap the tables with a RENAME TABLE. Every change on the
+// shadow table duplicates a change that was already made to the original table, which would flood
+// sinks with bogus inserts for the duration of the migration.
+//
+// Shadow tables are recognized by the names the tools give them:
+//
+// | | gh-ost | pt-osc |
+// | --- | --- | --- |
+// | new table | `_tbl_gho` | `_tbl_new` |
+// | original table, once swapped out | `_tbl_del` | `_tbl_old` |
+// | heartbeat and state | `_tbl_ghc` | |
+
+use super::classify::{renames, TableName};
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum MigrationTool {
+  GhOst,
+  PtOnlineSchemaChange,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum ShadowKind {
+  /// The table rows are copied into, which replaces the original table at the cut-over.
+  Ghost,
+  /// The original table, once swapped out.
+  Old,
+  /// gh-ost's heartbeat and state table.
+  Changelog,
+}
+
+/// What `EventDecoder` does with the changes made to shadow tables.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum ShadowTablePolicy {
+  /// Changes to shadow tables are emitted as is.
+  #[default]
+  Keep,
+  /// Changes to the rows of shadow tables are dropped, as with `Suppress`, but the `ALTER TABLE`
+  /// of the ghost table is attributed to the logical table, its statement included, e.g. for
+  /// sinks that keep the schema of their tables in sync. The ghost rows are never emitted: they
+  /// duplicate the changes of the original table, in the new layout of the columns.
+  Remap,
+  /// Changes to shadow tables are dropped, since they only copy or replay changes of the original
+  /// table.
+  Suppress,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct ShadowTable<'a> {
+  table: &'a str,
+  tool: MigrationTool,
+  kind: ShadowKind,
+}
+
+impl<'a> ShadowTable<'a> {
+  /// Name of the logical table being migrated.
+  pub fn table_str(&self) -> &'a str {
+    self.table
+  }
+
+  pub fn tool(&self) -> MigrationTool {
+    self.tool
+  }
+
+  pub fn kind(&self) -> ShadowKind {
+    self.kind
+  }
+}
+
+/// Returns the shadow table `table` is, if its name follows the naming of gh-ost or pt-osc.
+pub fn shadow_table(table: &str) -> Option<ShadowTable<'_>> {
+  const SUFFIXES: [(&str, MigrationTool, ShadowKind); 5] = [
+    ("_gho", MigrationTool::GhOst, ShadowKind::Ghost),
+    ("_del", MigrationTool::GhOst, ShadowKind::Old),
+    ("_ghc", MigrationTool::GhOst, ShadowKind::Changelog),
+    (
+      "_new",
+      MigrationTool::PtOnlineSchemaChange,
+      ShadowKind::Ghost,
+    ),
+    ("_old", MigrationTool::PtOnlineSchemaChange, ShadowKind::Old),
+  ];
+
+  let name = table.strip_prefix('_')?;
+  SUFFIXES.iter().find_map(|(suffix, tool, kind)| {
+    let table = name
+      .strip_suffix(suffix)
+      .filter(|table| !table.is_empty())?;
+    Some(ShadowTable {
+      table,
+      tool: *tool,
+      kind: *kind,
+    })
+  })
+}
+
+/// Renames the table `from` to `to` in `sql`, wherever `from` is a whole identifier, quoted or
+/// not, e.g. `ALTER TABLE `pets`.`cats`` for `ALTER TABLE `pets`.`_cats_gho``.
+pub fn rename_table(sql: &str, from: &str, to: &str) -> String {
+  let is_identifier = |c: char| c.is_alphanumeric() || c == '_' || c == '$';
+  let mut renamed = String::with_capacity(sql.len());
+  let mut rest = sql;
+  while let Some(start) = rest.find(from) {
+    let end = start + from.len();
+    let before = rest[..start].chars().next_back();
+    let after = rest[end..].chars().next();
+    renamed.push_str(&rest[..start]);
+    if before.is_some_and(is_identifier) || after.is_some_and(is_identifier) {
+      renamed.push_str(from);
+    } else {
+      renamed.push_str(to);
+    }
+    rest = &rest[end..];
+  }
+  renamed.push_str(rest);
+  renamed
+}
+
+/// Detects the cut-over of a migration, i.e. a `RENAME TABLE` that swaps the original table with
+/// its ghost table. Returns the migrated table and the tool that ran the migration.
+pub fn cut_over(sql: &str) -> Option<(TableName, MigrationTool)> {
+  let renames = renames(sql)?;
+  renames.iter().find_map(|(from, to)| {
+    let ghost = shadow_table(from.table_str())?;
+    let swapped = ghost.kind() == ShadowKind::Ghost
+      && ghost.table_str() == to.table_str()
+      && from.schema_str() == to.schema_str();
+    // The original table must be moved out of the way in the same statement.
+    let moved_out = renames.iter().any(|(original, old)| {
+      original == to
+        && shadow_table(old.table_str())
+          .is_some_and(|old| old.kind() == ShadowKind::Old && old.table_str() == ghost.table_str())
+    });
+    if swapped && moved_out {
+      Some((to.clone(), ghost.tool()))
+    } else {
+      None
+    }
+  })
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn recognizes_shadow_tables() {
+    let ghost = shadow_table("_cats_gho").unwrap();
+    assert_eq!("cats", ghost.table_str());
+    assert_eq!(MigrationTool::GhOst, ghost.tool());
+    assert_eq!(ShadowKind::Ghost, ghost.kind());
+
+    let old = shadow_table("_big_cats_old").unwrap();
+    assert_eq!("big_cats", old.table_str());
+    assert_eq!(MigrationTool::PtOnlineSchemaChange, old.tool());
+    assert_eq!(ShadowKind::Old, old.kind());
+
+    assert_eq!(None, shadow_table("cats"));
+    assert_eq!(None, shadow_table("cats_new"));
+    assert_eq!(None, shadow_table("__new"));
+  }
+
+  #[test]
+  fn renames_tables_in_statements() {
+    assert_eq!(
+      "ALTER TABLE `pets`.`cats` ADD COLUMN _cats_gho_id INT",
+      rename_table(
+        "ALTER TABLE `pets`.`_cats_gho` ADD COLUMN _cats_gho_id INT",
+        "_cats_gho",
+        "cats"
+      )
+    );
+    assert_eq!(
+      "ALTER TABLE cats ENGINE=InnoDB",
+      rename_table("ALTER TABLE _cats_gho ENGINE=InnoDB", "_cats_gho", "cats")
+    );
+  }
+
+  #[test]
+  fn detects_cut_overs() {
+    assert_eq!(
+      Some((
+        TableName::new(Some("pets".into()), "cats"),
+        MigrationTool::GhOst
+      )),
+      cut_over(
+        "rename /* gh-ost */ table `pets`.`cats` to `pets`.`_cats_del`, \
+         `pets`.`_cats_gho` to `pets`.`cats`"
+      )
+    );
+    assert_eq!(
+      Some((
+        TableName::new(None, "cats"),
+        MigrationTool::PtOnlineSchemaChange
+      )),
+      cut_over("RENAME TABLE cats TO _cats_old, _cats_new TO cats")
+    );
+
+    // Not a swap.
+    assert_eq!(None, cut_over("RENAME TABLE _cats_new TO cats"));
+    assert_eq!(None, cut_over("RENAME TABLE cats TO dogs"));
+    assert_eq!(None, cut_over("ALTER TABLE cats RENAME TO dogs"));
+  }
+}
@@ -0,0 +1,255 @@
+// Parameters of text queries, see `Connection::query_with_params`: the `?` placeholders of a query
+// are replaced with the values as SQL literals, escaped the way `mysql_real_escape_string` does,
+// for the queries that are not worth a prepared statement.
+//
+// Placeholders are only looked for outside of string literals, quoted identifiers and comments.
+// Strings are escaped with backslashes, unless the session runs with the NO_BACKSLASH_ESCAPES
+// sql_mode, which the server reports in the status flags of every OK packet. Bytes that are not
+// UTF-8 are sent as hex literals.
+//
+// Escaping is only safe in the character sets where a backslash or a quote is never part of a
+// multibyte character. In gbk, big5, sjis or cp932, `E4 B8 AD 5C 27` (`中\'`) reads as two
+// characters and a quote, which ends the literal: `interpolate` refuses any character set but the
+// ones of `SAFE_CHARACTER_SETS`.
+
+use super::protocol::CharacterSet;
+use super::util::hex;
+use super::value::Value;
+
+/// Character sets of the connections whose queries can have their parameters interpolated.
+pub const SAFE_CHARACTER_SETS: &[CharacterSet] = &[
+  CharacterSet::UTF8,
+  CharacterSet::UTF8MB4,
+  CharacterSet::LATIN1,
+  CharacterSet::ASCII,
+  CharacterSet::BINARY,
+];
+
+/// Replaces the `?` placeholders of `sql` with `params`, in order, for a connection using
+/// `character_set`. Fails when their counts do not match, on a value that has no literal (see
+/// `literal`), and on a character set that is not one of `SAFE_CHARACTER_SETS`.
+pub fn interpolate(
+  sql: &str,
+  params: &[Value],
+  no_backslash_escapes: bool,
+  character_set: CharacterSet,
+) -> Result<String, String> {
+  if !SAFE_CHARACTER_SETS.contains(&character_set) {
+    return Err(format!(
+      "parameters can not be escaped safely in the {:?} character set, use a prepared statement",
+      character_set
+    ));
+  }
+  let given = params.len();
+  let mut query = String::with_capacity(sql.len() + given * 8);
+  let mut params = params.iter();
+  let mut placeholders = 0;
+  let mut chars = sql.char_indices().peekable();
+  // Start of the part of `sql` not copied to `query` yet.
+  let mut copied = 0;
+
+  while let Some((i, c)) = chars.next() {
+    match c {
+      '?' => {
+        placeholders += 1;
+        query.push_str(&sql[copied..i]);
+        copied = i + 1;
+        if let Some(value) = params.next() {
+          query.push_str(&literal(value, no_backslash_escapes)?);
+        }
+      }
+      '\'' | '"' | '`' => {
+        while let Some((_, next)) = chars.next() {
+          if next == '\\' && c != '`' && !no_backslash_escapes {
+            chars.next();
+          } else if next == c {
+            // A doubled quote is part of the literal.
+            match chars.peek() {
+              Some((_, after)) if *after == c => {
+                chars.next();
+              }
+              _ => break,
+            }
+          }
+        }
+      }
+      '#' => skip_line(&mut chars),
+      // `--` only starts a comment when followed by a space or a control character.
+      '-'
+        if sql[i..].starts_with("--")
+          && sql[i + 2..]
+            .chars()
+            .next()
+            .is_none_or(|c| c.is_whitespace() || c.is_control()) =>
+      {
+        skip_line(&mut chars)
+      }
+      '/' if sql[i..].starts_with("/*") => {
+        chars.next();
+        while let Some((j, _)) = chars.next() {
+          if sql[j..].starts_with("*/") {
+            chars.next();
+            break;
+          }
+        }
+      }
+      _ => {}
+    }
+  }
+  query.push_str(&sql[copied..]);
+
+  if placeholders != given {
+    return Err(format!(
+      "the query has {} placeholders, {} parameters were given",
+      placeholders, given
+    ));
+  }
+  Ok(query)
+}
+
+fn skip_line(chars: &mut impl Iterator<Item = (usize, char)>) {
+  for (_, c) in chars {
+    if c == '\n' {
+      break;
+    }
+  }
+}
+
+/// SQL literal of `value`: numbers as is, strings and temporal values quoted and escaped, bytes
+/// that are not UTF-8 in hex. Only safe in the `SAFE_CHARACTER_SETS`. Fails on the values that
+/// were not logged in full (e.g. `Value::Truncated`), and on the floats that MYSQL can not
+/// represent (NaN and infinities). TIMESTAMPs are formatted in UTC, see `Value::to_text`.
+pub fn literal(value: &Value, no_backslash_escapes: bool) -> Result<String, String> {
+  match value {
+    Value::Null => Ok("NULL".to_string()),
+    Value::Int(v) => Ok(v.to_string()),
+    Value::Uint(v) => Ok(v.to_string()),
+    Value::Float(v) if v.is_finite() => Ok(format!("{:e}", v)),
+    Value::Float(v) => Err(format!("{} has no SQL literal", v)),
+    Value::Bytes(bytes) => match std::str::from_utf8(bytes) {
+      Ok(text) => Ok(quote(text, no_backslash_escapes)),
+      Err(_) => Ok(format!("X'{}'", hex(bytes))),
+    },
+    Value::Date { .. } | Value::Time { .. } | Value::Timestamp { .. } => {
+      let text = value.to_text().unwrap_or_default();
+      Ok(quote(&String::from_utf8_lossy(&text), no_backslash_escapes))
+    }
+    Value::Truncated { .. }
+    | Value::Digest { .. }
+    | Value::Reference { .. }
+    | Value::Encrypted { .. } => Err(format!("{} was not logged in full", value)),
+  }
+}
+
+// Quotes `text` as `mysql_real_escape_string` does, or only doubles the quotes with
+// NO_BACKSLASH_ESCAPES, where backslashes are ordinary characters.
+fn quote(text: &str, no_backslash_escapes: bool) -> String {
+  let mut quoted = String::with_capacity(text.len() + 2);
+  quoted.push('\'');
+  for c in text.chars() {
+    match c {
+      '\'' if no_backslash_escapes => quoted.push_str("''"),
+      _ if no_backslash_escapes => quoted.push(c),
+      '\0' => quoted.push_str("\\0"),
+      '\n' => quoted.push_str("\\n"),
+      '\r' => quoted.push_str("\\r"),
+      '\x1a' => quoted.push_str("\\Z"),
+      '\\' | '\'' | '"' => {
+        quoted.push('\\');
+        quoted.push(c);
+      }
+      c => quoted.push(c),
+    }
+  }
+  quoted.push('\'');
+  quoted
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn replaces_placeholders_outside_of_literals_and_comments() {
+    let sql = "SELECT '?', \"it''s?\", `?`, ? /* ? */ FROM cats # ?\nWHERE id = ? -- ?\n AND 1--?";
+    assert_eq!(
+      Ok(
+        "SELECT '?', \"it''s?\", `?`, NULL /* ? */ FROM cats # ?\nWHERE id = 4 -- ?\n AND 1--7"
+          .to_string()
+      ),
+      interpolate(
+        sql,
+        &[Value::Null, Value::Uint(4), Value::Int(7)],
+        false,
+        CharacterSet::UTF8MB4
+      )
+    );
+    // Not a literal that ends at `\'` without backslash escapes.
+    assert_eq!(
+      Ok(r"SELECT 'C:\', 1".to_string()),
+      interpolate(
+        r"SELECT 'C:\', ?",
+        &[Value::Int(1)],
+        true,
+        CharacterSet::UTF8MB4
+      )
+    );
+    assert_eq!(
+      Err("the query has 2 placeholders, 1 parameters were given".to_string()),
+      interpolate(
+        "SELECT ?, ?",
+        &[Value::Int(1)],
+        false,
+        CharacterSet::UTF8MB4
+      )
+    );
+  }
+
+  #[test]
+  fn refuses_character_sets_where_escaping_is_unsafe() {
+    // 5C 27 escapes the quote, but gbk reads AD 5C as one character.
+    let params = [Value::Bytes("中'".as_bytes().to_vec())];
+    assert_eq!(
+      Ok(r"SELECT '中\''".to_string()),
+      interpolate("SELECT ?", &params, false, CharacterSet::UTF8MB4)
+    );
+    assert!(interpolate("SELECT ?", &params, false, CharacterSet::GBK).is_err());
+    assert!(interpolate("SELECT ?", &params, true, CharacterSet::SJIS).is_err());
+  }
+
+  #[test]
+  fn escapes_literals() {
+    let text = |s: &str| Value::Bytes(s.as_bytes().to_vec());
+    assert_eq!(
+      Ok(r#"'a\'b\"c\\d\0\n\Z'"#.to_string()),
+      literal(&text("a'b\"c\\d\0\n\x1a"), false)
+    );
+    assert_eq!(Ok(r"'a''b\c'".to_string()), literal(&text("a'b\\c"), true));
+    assert_eq!(
+      Ok("X'ff27'".to_string()),
+      literal(&Value::Bytes(vec![0xff, b'\'']), false)
+    );
+    assert_eq!(Ok("1.5e0".to_string()), literal(&Value::Float(1.5), false));
+    assert!(literal(&Value::Float(f64::NAN), false).is_err());
+    assert_eq!(
+      Ok("'2016-05-21 00:00:00'".to_string()),
+      literal(
+        &Value::Date {
+          year: 2016,
+          month: 5,
+          day: 21,
+          hour: 0,
+          minute: 0,
+          second: 0,
+          micro: 0,
+        },
+        false
+      )
+    );
+    let truncated = Value::Truncated {
+      prefix: b"Cha".to_vec(),
+      size: 7,
+    };
+    assert!(literal(&truncated, false).is_err());
+  }
+}
@@ -0,0 +1,1060 @@
+// The plumbing of the `main` binary as a library: reads the binlog from a connection, decodes it
+// into changes, runs them through filters and transforms, groups them by transaction and pushes
+// the groups onto a `futures::Sink`.
+//
+// The decoder, the transforms and the sink run concurrently, in the task that calls
+// `Pipeline::run`, and hand changes over through bounded queues: a slow sink fills the queues,
+// which then stops the reads from the binlog. Up to `transform_workers` changes go through the
+// transforms at once, which only pays off with async transforms (e.g. lookups over the network).
+// The order of the changes is kept throughout.
+//
+// Nothing is spawned: the stages, and the checkpointer of `PipelineBuilder::checkpointer`, are
+// futures of `Pipeline::run`, which ends once all of them did, and fails with the first of their
+// errors. Dropping it stops them all.
+//
+// With `PipelineBuilder::destinations`, every transaction is also routed to the destinations of
+// its changes, e.g. the sinks a fanning out sink hands them to, which ack it on their own
+// watermarks: a stalled destination then only holds back its own checkpoint.
+//
+// A panic of the decoder or of a transform is caught and handled like an event that fails to
+// decode, per the `UndecodablePolicy` of the pipeline: logged, with the raw bytes of the event when
+// it failed to decode, then failing the stream or skipping the event.
+
+#[cfg(feature = "tokio-runtime")]
+use super::checkpoint::Checkpointer;
+use super::conn::{Connection, DriverError, ReplicationOptions};
+use super::dropped::{table_of, DropReason, DroppedEvents};
+use super::enrich::Enricher;
+use super::event::{ChangeEvent, EventDecoder};
+use super::inspect::hexdump;
+use super::metrics::{Metrics, StageMetrics};
+use super::retry::{Retry, RetryPolicy};
+use super::slow::{SlowOperation, SlowOperations};
+use super::transaction::{ChunkKind, Group, TransactionGrouper};
+use super::util::error_chain;
+use super::watermark::{BinlogPosition, DestinationWatermarks, Watermarks};
+use futures::channel::{mpsc, oneshot};
+use futures::future::{self, BoxFuture, Future, FutureExt};
+use futures::io::{AsyncRead, AsyncWrite};
+use futures::select;
+use futures::sink::{Sink, SinkExt};
+use futures::stream::StreamExt;
+use std::any::Any;
+use std::collections::BTreeSet;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const DEFAULT_QUEUE_DEPTH: usize = 1024;
+// Added to the delay of delayed pipelines to get the `net_write_timeout` of their connection.
+const DELAY_WRITE_TIMEOUT_MARGIN: Duration = Duration::from_secs(60);
+// Raw bytes of the events that fail to decode are logged up to this many bytes.
+const MAX_LOGGED_EVENT_LEN: usize = 4096;
+// Offset of the first event of a binlog file, after its magic number.
+const BINLOG_FILE_START: u32 = 4;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PipelineError<E: std::error::Error + 'static> {
+  #[error("Failed to read the binlog")]
+  Driver(#[from] DriverError),
+  #[error("Failed to push changes onto the sink")]
+  Sink(#[source] E),
+  #[error("Panicked while processing an event: {0}")]
+  Panicked(String),
+  /// The checkpointer of `PipelineBuilder::checkpointer` failed to save the checkpoint, see
+  /// `checkpoint::CheckpointError`.
+  #[error("Failed to save the checkpoint")]
+  Checkpoint(#[source] Box<dyn std::error::Error + Send + Sync>),
+}
+
+pub type PipelineResult<T, E> = Result<T, PipelineError<E>>;
+
+type DriverResult<T> = Result<T, DriverError>;
+// Destination of a change, see `PipelineBuilder::destinations`.
+type Route = Box<dyn Fn(&ChangeEvent) -> Option<String> + Send + Sync>;
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum PipelineStage {
+  /// Reads and decodes the binlog. Has no queue, it reads from the connection.
+  Decode,
+  /// Filters and transforms changes.
+  Transform,
+  /// Groups changes by transaction and pushes the groups onto the sink.
+  Sink,
+}
+
+/// What a pipeline does with an event that fails to decode, or that the decoder or a transform
+/// panics on. The event is logged either way, with its raw bytes when it failed to decode.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum UndecodablePolicy {
+  /// Fails the stream, after dumping the last events read when the event failed to decode (see
+  /// `ConnectionOptions::crash_dump`).
+  #[default]
+  Fail,
+  /// Drops the event, counted as `DropReason::Undecodable`, and moves on to the next one. The
+  /// changes of the event are lost, and the decoder may be left with part of the event applied,
+  /// e.g. a transaction begun but never ended, which the grouper drops (see
+  /// `Metrics::incomplete_transactions`).
+  Skip,
+}
+
+/// Metrics of every stage of a pipeline, see `Pipeline::metrics`.
+#[derive(Debug, Default)]
+pub struct PipelineMetrics {
+  decode: StageMetrics,
+  transform: StageMetrics,
+  sink: StageMetrics,
+  in_flight_events: AtomicU64,
+  in_flight_bytes: AtomicU64,
+}
+
+impl PipelineMetrics {
+  pub fn stage(&self, stage: PipelineStage) -> &StageMetrics {
+    match stage {
+      PipelineStage::Decode => &self.decode,
+      PipelineStage::Transform => &self.transform,
+      PipelineStage::Sink => &self.sink,
+    }
+  }
+
+  /// Changes decoded but not pushed onto the sink yet, see `Pressure`.
+  pub fn pressure(&self) -> Pressure {
+    Pressure {
+      events: self.in_flight_events.load(Ordering::Relaxed),
+      bytes: self.in_flight_bytes.load(Ordering::Relaxed),
+    }
+  }
+
+  fn add_in_flight(&self, bytes: u64) {
+    self.in_flight_events.fetch_add(1, Ordering::Relaxed);
+    self.in_flight_bytes.fetch_add(bytes, Ordering::Relaxed);
+  }
+
+  fn remove_in_flight(&self, events: u64, bytes: u64) {
+    self.in_flight_events.fetch_sub(events, Ordering::Relaxed);
+    self.in_flight_bytes.fetch_sub(bytes, Ordering::Relaxed);
+  }
+}
+
+/// Changes held in memory by a pipeline: queued in front of a stage, going through the
+/// transforms, buffered by the transaction grouper until their transaction ends, or being pushed
+/// onto the sink. Transaction markers are not counted.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Pressure {
+  events: u64,
+  bytes: u64,
+}
+
+impl Pressure {
+  pub fn events(&self) -> u64 {
+    self.events
+  }
+
+  /// Approximate size of the changes, see `ChangeEvent::byte_size`.
+  pub fn bytes(&self) -> u64 {
+    self.bytes
+  }
+}
+
+// Size of `change` as counted by `Pressure`, `None` for transaction markers.
+fn in_flight_size(change: &ChangeEvent) -> Option<u64> {
+  match change {
+    ChangeEvent::Begin(_) | ChangeEvent::End(_) => None,
+    change => Some(change.byte_size() as u64),
+  }
+}
+
+enum Stage {
+  Filter(Box<dyn Fn(&ChangeEvent) -> bool + Send + Sync>),
+  Transform(Box<dyn Fn(ChangeEvent) -> Option<ChangeEvent> + Send + Sync>),
+  AsyncTransform(Box<dyn Fn(ChangeEvent) -> BoxFuture<'static, Option<ChangeEvent>> + Send + Sync>),
+}
+
+// Runs `change` through the stages, in the order they were added, and counts the changes the
+// filters drop in `dropped`. Transaction markers skip them, the grouper relies on every one of
+// them.
+async fn apply_stages(
+  stages: &[Stage],
+  dropped: &DroppedEvents,
+  change: ChangeEvent,
+) -> Option<ChangeEvent> {
+  if let ChangeEvent::Begin(_) | ChangeEvent::End(_) = change {
+    return Some(change);
+  }
+
+  let mut change = change;
+  for stage in stages {
+    change = match stage {
+      Stage::Filter(filter) if !filter(&change) => {
+        dropped.record(DropReason::Filter, &change);
+        return None;
+      }
+      Stage::Filter(_) => change,
+      Stage::Transform(transform) => transform(change)?,
+      Stage::AsyncTransform(transform) => transform(change).await?,
+    };
+  }
+  Some(change)
+}
+
+// Message of a panic, as given to `panic!`.
+fn panic_message(panic: &(dyn Any + Send)) -> String {
+  match panic.downcast_ref::<&str>() {
+    Some(message) => message.to_string(),
+    None => match panic.downcast_ref::<String>() {
+      Some(message) => message.clone(),
+      None => "panicked".to_string(),
+    },
+  }
+}
+
+// Hex dump of the raw bytes of an event, up to `MAX_LOGGED_EVENT_LEN`.
+fn logged_bytes(event: &[u8]) -> String {
+  match event.len() {
+    len if len > MAX_LOGGED_EVENT_LEN => format!(
+      "{}... ({} bytes)",
+      hexdump(&event[..MAX_LOGGED_EVENT_LEN]),
+      len
+    ),
+    _ => hexdump(event),
+  }
+}
+
+// How long to wait for an event logged at `timestamp` (seconds since the epoch) to be `delay` old.
+fn remaining_delay(timestamp: u32, delay: Duration) -> Option<Duration> {
+  if timestamp == 0 {
+    return None;
+  }
+  let due = UNIX_EPOCH + Duration::from_secs(u64::from(timestamp)) + delay;
+  due
+    .duration_since(SystemTime::now())
+    .ok()
+    .filter(|wait| *wait > Duration::from_secs(0))
+}
+
+// Registers `conn` as a replica and starts the dump from `file`:`position`.
+async fn start_dump<S: AsyncRead + AsyncWrite + Unpin>(
+  conn: &mut Connection<S>,
+  replication_opts: &ReplicationOptions,
+  delay: Option<Duration>,
+  file: String,
+  position: u32,
+) -> DriverResult<()> {
+  // The server stops writing to the connection while the events are held back, the dump must
+  // not time out meanwhile. The connection is dedicated to the pipeline, it is not restored.
+  if let Some(delay) = delay {
+    let timeout = delay.as_secs() + DELAY_WRITE_TIMEOUT_MARGIN.as_secs();
+    conn
+      .query(format!("SET SESSION net_write_timeout = {}", timeout))
+      .await?;
+  }
+  conn
+    .start_binlog_dump(replication_opts.clone(), file, position)
+    .await
+}
+
+// Reconnects to a source that shut down at `stopped_at`, and resumes the dump from the first binlog
+// file it started after. The connection is refused until the source is up again.
+async fn restart<S: AsyncRead + AsyncWrite + Unpin>(
+  policy: &RetryPolicy,
+  connect: &Connect<Connection<S>>,
+  replication_opts: &ReplicationOptions,
+  delay: Option<Duration>,
+  stopped_at: BinlogPosition,
+) -> DriverResult<Connection<S>> {
+  let mut attempt = 1;
+  loop {
+    let restarted = async {
+      let mut conn = connect().await?;
+      let next_file = conn
+        .binary_logs()
+        .await?
+        .into_iter()
+        .find(|file| file.as_str() > stopped_at.file_str());
+      let (file, position) = match next_file {
+        Some(file) => (file, BINLOG_FILE_START),
+        None => (stopped_at.file_str().to_string(), stopped_at.position()),
+      };
+      start_dump(&mut conn, replication_opts, delay, file, position).await?;
+      Ok(conn)
+    };
+    match restarted.await {
+      Err(err) if policy.allows(attempt) && Retry::of(&err) == Retry::Reconnect => {
+        policy.wait(attempt, &err).await;
+        attempt += 1;
+      }
+      result => return result,
+    }
+  }
+}
+
+/// Configuration of a pipeline, see `Pipeline::builder`. `S` is the source and `K` the sink, `()`
+/// until they are set.
+pub struct PipelineBuilder<S, K> {
+  source: S,
+  replication_opts: ReplicationOptions,
+  resume_from: Option<(String, u32)>,
+  max_events: Option<u64>,
+  delay: Option<Duration>,
+  decoder: Option<EventDecoder>,
+  stages: Vec<Stage>,
+  transform_workers: usize,
+  transform_queue_depth: usize,
+  sink_queue_depth: usize,
+  grouper: Option<TransactionGrouper>,
+  watermarks: Option<Watermarks>,
+  destinations: Option<(DestinationWatermarks, Route)>,
+  slow_operations: SlowOperations,
+  undecodable_policy: UndecodablePolicy,
+  reconnect: Option<(RetryPolicy, Connect<S>)>,
+  #[cfg(feature = "tokio-runtime")]
+  checkpointer: Option<Checkpointer>,
+  sink: K,
+}
+
+// Opens a new connection to the source, see `PipelineBuilder::reconnect_on_restart`.
+type Connect<S> = Box<dyn Fn() -> BoxFuture<'static, DriverResult<S>> + Send + Sync>;
+
+impl<S, K> PipelineBuilder<S, K> {
+  /// Reads the binlog from `conn`, a connection dedicated to the pipeline.
+  pub fn source<T>(self, conn: Connection<T>) -> PipelineBuilder<Connection<T>, K> {
+    PipelineBuilder {
+      source: conn,
+      replication_opts: self.replication_opts,
+      resume_from: self.resume_from,
+      max_events: self.max_events,
+      delay: self.delay,
+      decoder: self.decoder,
+      stages: self.stages,
+      transform_workers: self.transform_workers,
+      transform_queue_depth: self.transform_queue_depth,
+      sink_queue_depth: self.sink_queue_depth,
+      grouper: self.grouper,
+      watermarks: self.watermarks,
+      destinations: self.destinations,
+      slow_operations: self.slow_operations,
+      undecodable_policy: self.undecodable_policy,
+      reconnect: None,
+      #[cfg(feature = "tokio-runtime")]
+      checkpointer: self.checkpointer,
+      sink: self.sink,
+    }
+  }
+
+  pub fn replication_opts(mut self, replication_opts: impl Into<ReplicationOptions>) -> Self {
+    self.replication_opts = replication_opts.into();
+    self
+  }
+
+  /// Resumes the binlog from `file`:`position`, instead of the current position of the server.
+  pub fn resume_from(mut self, file: impl Into<String>, position: u32) -> Self {
+    self.resume_from = Some((file.into(), position));
+    self
+  }
+
+  /// Stops after reading `max_events` events from the binlog, e.g. for dry runs. The changes of the
+  /// transaction in progress then are not pushed, as on shutdown.
+  pub fn max_events(mut self, max_events: u64) -> Self {
+    self.max_events = Some(max_events);
+    self
+  }
+
+  /// Holds every event back until `delay` after it was logged, like a delayed replica
+  /// (`SOURCE_DELAY`), so that a destructive change can be caught before it reaches the sink. The
+  /// events not read yet stay in the binlog of the server, whose retention must outlast the delay.
+  /// The `net_write_timeout` of the connection is raised to match.
+  pub fn delay(mut self, delay: Duration) -> Self {
+    self.delay = Some(delay);
+    self
+  }
+
+  /// Decodes the binlog with `decoder`. Defaults to a decoder emitting transaction markers, so
+  /// that changes are grouped by transaction: without them, every change is its own
+  /// `Group::Event`.
+  pub fn decoder(mut self, decoder: EventDecoder) -> Self {
+    self.decoder = Some(decoder);
+    self
+  }
+
+  /// Drops the changes `filter` returns false for.
+  pub fn filter(mut self, filter: impl Fn(&ChangeEvent) -> bool + Send + Sync + 'static) -> Self {
+    self.stages.push(Stage::Filter(Box::new(filter)));
+    self
+  }
+
+  /// Replaces every change by the one `transform` returns, dropping it on `None`. Filters and
+  /// transforms run in the order they are added.
+  pub fn transform(
+    mut self,
+    transform: impl Fn(ChangeEvent) -> Option<ChangeEvent> + Send + Sync + 'static,
+  ) -> Self {
+    self.stages.push(Stage::Transform(Box::new(transform)));
+    self
+  }
+
+  /// Same as `transform`, for transforms that wait on IO.
+  pub fn async_transform<F>(
+    mut self,
+    transform: impl Fn(ChangeEvent) -> F + Send + Sync + 'static,
+  ) -> Self
+  where
+    F: Future<Output = Option<ChangeEvent>> + Send + 'static,
+  {
+    self
+      .stages
+      .push(Stage::AsyncTransform(Box::new(move |change| {
+        transform(change).boxed()
+      })));
+    self
+  }
+
+  /// Appends the values `enricher` looks up to the rows of its table, see `enrich::Enricher`.
+  pub fn enrich(self, enricher: Enricher) -> Self {
+    self.async_transform(move |change| enricher.enrich(change))
+  }
+
+  /// Number of changes filtered and transformed at once, 1 by default.
+  pub fn transform_workers(mut self, workers: usize) -> Self {
+    self.transform_workers = workers.max(1);
+    self
+  }
+
+  /// Number of changes queued in front of `stage` before the stages upstream wait, 1024 by
+  /// default. The decoder has no queue.
+  pub fn queue_depth(mut self, stage: PipelineStage, depth: usize) -> Self {
+    match stage {
+      PipelineStage::Decode => {}
+      PipelineStage::Transform => self.transform_queue_depth = depth,
+      PipelineStage::Sink => self.sink_queue_depth = depth,
+    }
+    self
+  }
+
+  /// Groups the changes with `grouper`, which defaults to the metrics of the decoder and a 64MB
+  /// buffer.
+  pub fn grouper(mut self, grouper: TransactionGrouper) -> Self {
+    self.grouper = Some(grouper);
+    self
+  }
+
+  /// Reports the position of every transaction once the sink accepted it.
+  pub fn watermarks(mut self, watermarks: Watermarks) -> Self {
+    self.watermarks = Some(watermarks);
+    self
+  }
+
+  /// Routes every transaction to the destinations `route` gives for its changes, on
+  /// `destinations`: the sink acks it on the watermarks of each of them once it is done with it,
+  /// the others skip it. Changes without a destination route nowhere.
+  pub fn destinations(
+    mut self,
+    destinations: DestinationWatermarks,
+    route: impl Fn(&ChangeEvent) -> Option<String> + Send + Sync + 'static,
+  ) -> Self {
+    self.destinations = Some((destinations, Box::new(route)));
+    self
+  }
+
+  /// Saves the checkpoints of the pipeline with `checkpointer` while it runs, and a last time once
+  /// its queues are drained. The pipeline acks the transactions onto the watermarks of the
+  /// checkpointer, in place of any set with `watermarks`.
+  #[cfg(feature = "tokio-runtime")]
+  pub fn checkpointer(mut self, checkpointer: Checkpointer) -> Self {
+    self.watermarks = Some(checkpointer.watermarks().clone());
+    self.checkpointer = Some(checkpointer);
+    self
+  }
+
+  /// Reports the groups of changes the sink was slower than `SlowOperation::SinkPublish` to accept.
+  pub fn slow_operations(mut self, slow_operations: SlowOperations) -> Self {
+    self.slow_operations = slow_operations;
+    self
+  }
+
+  /// What to do with the events that fail to decode, or that the decoder or a transform panics
+  /// on. Fails the stream by default.
+  pub fn undecodable_policy(mut self, policy: UndecodablePolicy) -> Self {
+    self.undecodable_policy = policy;
+    self
+  }
+
+  /// Pushes the groups of changes onto `sink`.
+  pub fn sink<T>(self, sink: T) -> PipelineBuilder<S, T> {
+    PipelineBuilder {
+      source: self.source,
+      replication_opts: self.replication_opts,
+      resume_from: self.resume_from,
+      max_events: self.max_events,
+      delay: self.delay,
+      decoder: self.decoder,
+      stages: self.stages,
+      transform_workers: self.transform_workers,
+      transform_queue_depth: self.transform_queue_depth,
+      sink_queue_depth: self.sink_queue_depth,
+      grouper: self.grouper,
+      watermarks: self.watermarks,
+      destinations: self.destinations,
+      slow_operations: self.slow_operations,
+      undecodable_policy: self.undecodable_policy,
+      reconnect: self.reconnect,
+      #[cfg(feature = "tokio-runtime")]
+      checkpointer: self.checkpointer,
+      sink,
+    }
+  }
+}
+
+impl<S, K> PipelineBuilder<Connection<S>, K> {
+  /// Follows the source across its restarts: when it shuts down (STOP_EVENT) and drops the
+  /// connection, opens a new one with `connect`, per `policy` while the source is still down, and
+  /// resumes from the binlog file it started once up. A `ChangeEvent::SourceRestart` marks the
+  /// restart in the stream. Without it, the pipeline fails once the connection is lost.
+  pub fn reconnect_on_restart<F>(
+    mut self,
+    policy: RetryPolicy,
+    connect: impl Fn() -> F + Send + Sync + 'static,
+  ) -> Self
+  where
+    F: Future<Output = DriverResult<Connection<S>>> + Send + 'static,
+  {
+    self.reconnect = Some((policy, Box::new(move || connect().boxed())));
+    self
+  }
+}
+
+impl<S, K> PipelineBuilder<Connection<S>, K>
+where
+  S: AsyncRead + AsyncWrite + Unpin,
+  K: Sink<Group> + Unpin,
+{
+  pub fn build(self) -> Pipeline<S, K> {
+    let mut decoder = self.decoder.unwrap_or_else(|| {
+      EventDecoder::new(Arc::new(Metrics::default())).emit_transaction_markers(true)
+    });
+    if let Some(ref watermarks) = self.watermarks {
+      decoder = decoder.watermarks(watermarks.clone());
+    }
+    let grouper = self
+      .grouper
+      .unwrap_or_else(|| TransactionGrouper::new(decoder.metrics().clone()));
+
+    Pipeline {
+      conn: self.source,
+      replication_opts: self.replication_opts,
+      resume_from: self.resume_from,
+      max_events: self.max_events,
+      delay: self.delay,
+      decoder,
+      stages: self.stages,
+      transform_workers: self.transform_workers,
+      transform_queue_depth: self.transform_queue_depth,
+      sink_queue_depth: self.sink_queue_depth,
+      metrics: Arc::new(PipelineMetrics::default()),
+      grouper,
+      watermarks: self.watermarks,
+      destinations: self.destinations,
+      slow_operations: self.slow_operations,
+      undecodable_policy: self.undecodable_policy,
+      reconnect: self.reconnect,
+      #[cfg(feature = "tokio-runtime")]
+      checkpointer: self.checkpointer,
+      sink: self.sink,
+    }
+  }
+}
+
+/// Source, decoder, filters, transforms, grouper and sink of changes, run by `Pipeline::run`.
+pub struct Pipeline<S, K> {
+  conn: Connection<S>,
+  replication_opts: ReplicationOptions,
+  resume_from: Option<(String, u32)>,
+  max_events: Option<u64>,
+  delay: Option<Duration>,
+  decoder: EventDecoder,
+  stages: Vec<Stage>,
+  transform_workers: usize,
+  transform_queue_depth: usize,
+  sink_queue_depth: usize,
+  metrics: Arc<PipelineMetrics>,
+  grouper: TransactionGrouper,
+  watermarks: Option<Watermarks>,
+  destinations: Option<(DestinationWatermarks, Route)>,
+  slow_operations: SlowOperations,
+  undecodable_policy: UndecodablePolicy,
+  reconnect: Option<(RetryPolicy, Connect<Connection<S>>)>,
+  #[cfg(feature = "tokio-runtime")]
+  checkpointer: Option<Checkpointer>,
+  sink: K,
+}
+
+impl Pipeline<(), ()> {
+  pub fn builder() -> PipelineBuilder<(), ()> {
+    PipelineBuilder {
+      source: (),
+      replication_opts: ReplicationOptions::default(),
+      resume_from: None,
+      max_events: None,
+      delay: None,
+      decoder: None,
+      stages: Vec::new(),
+      transform_workers: 1,
+      transform_queue_depth: DEFAULT_QUEUE_DEPTH,
+      sink_queue_depth: DEFAULT_QUEUE_DEPTH,
+      grouper: None,
+      watermarks: None,
+      destinations: None,
+      slow_operations: SlowOperations::default(),
+      undecodable_policy: UndecodablePolicy::default(),
+      reconnect: None,
+      #[cfg(feature = "tokio-runtime")]
+      checkpointer: None,
+      sink: (),
+    }
+  }
+}
+
+impl<S, K> Pipeline<S, K>
+where
+  S: AsyncRead + AsyncWrite + Unpin,
+  K: Sink<Group> + Unpin,
+  K::Error: std::error::Error + 'static,
+{
+  pub fn metrics(&self) -> &Arc<PipelineMetrics> {
+    &self.metrics
+  }
+
+  /// Changes held in memory by the pipeline. Keep a clone of `metrics` to follow it while the
+  /// pipeline runs.
+  pub fn pressure(&self) -> Pressure {
+    self.metrics.pressure()
+  }
+
+  /// Streams changes onto the sink until `shutdown` resolves or the server closes the stream, then
+  /// drains the queues and closes the sink. Changes of the transaction in progress at shutdown are
+  /// not pushed: resuming from the last position acked reads them again. Fails once the
+  /// checkpointer gives up on saving a checkpoint, see `Checkpointer::run`.
+  pub async fn run(mut self, shutdown: impl Future<Output = ()>) -> PipelineResult<(), K::Error> {
+    // The last checkpoint is saved once the queues are drained, the stream failed or not.
+    let (stopped_sender, stopped) = oneshot::channel::<()>();
+    let checkpoint = self.checkpoint(stopped.map(drop));
+    let stream = async move {
+      let result = self.stream(shutdown).await;
+      let _ = stopped_sender.send(());
+      Ok(result)
+    };
+    let checkpoint = async { checkpoint.await.map_err(PipelineError::Checkpoint) };
+    let (result, ()) = future::try_join(stream, checkpoint).await?;
+    result
+  }
+
+  // Saves the checkpoints of the checkpointer, if any, until `stopped` resolves.
+  #[cfg(feature = "tokio-runtime")]
+  fn checkpoint(
+    &mut self,
+    stopped: impl Future<Output = ()> + Send + 'static,
+  ) -> BoxFuture<'static, Result<(), Box<dyn std::error::Error + Send + Sync>>> {
+    match self.checkpointer.take() {
+      Some(checkpointer) => checkpointer.run(stopped).boxed(),
+      None => future::ok(()).boxed(),
+    }
+  }
+
+  #[cfg(not(feature = "tokio-runtime"))]
+  fn checkpoint(
+    &mut self,
+    _stopped: impl Future<Output = ()> + Send + 'static,
+  ) -> BoxFuture<'static, Result<(), Box<dyn std::error::Error + Send + Sync>>> {
+    future::ok(()).boxed()
+  }
+
+  async fn stream(self, shutdown: impl Future<Output = ()>) -> PipelineResult<(), K::Error> {
+    let Pipeline {
+      mut conn,
+      replication_opts,
+      resume_from,
+      max_events,
+      delay,
+      mut decoder,
+      stages,
+      transform_workers,
+      transform_queue_depth,
+      sink_queue_depth,
+      metrics,
+      mut grouper,
+      watermarks,
+      destinations,
+      slow_operations,
+      undecodable_policy,
+      reconnect,
+      mut sink,
+      ..
+    } = self;
+
+    let (file, position) = match resume_from {
+      Some(position) => position,
+      None => conn.master_status().await?,
+    };
+    start_dump(&mut conn, &replication_opts, delay, file, position).await?;
+    let shutdown = shutdown.fuse();
+    futures::pin_mut!(shutdown);
+
+    let (mut decoded_sender, decoded) = mpsc::channel(transform_queue_depth);
+    let (mut transformed_sender, mut transformed) = mpsc::channel(sink_queue_depth);
+    let (metrics, stages) = (&metrics, &stages);
+    let decoder_metrics = decoder.metrics().clone();
+    let dropped = decoder_metrics.dropped();
+
+    // Every stage drops the sender of its output queue once done, which ends the next stage.
+    let decode = async move {
+      let mut read = 0;
+      // End of the binlog file closed by a shutdown of the source.
+      let mut stopped_at = None;
+      loop {
+        if max_events.is_some_and(|max_events| read >= max_events) {
+          break;
+        }
+        let next = select! {
+          _ = shutdown => break,
+          next = conn.read_binlog_event().fuse() => next,
+        };
+        let (header, event) = match (next, stopped_at.take(), &reconnect) {
+          (Ok(Some(next)), _, _) => next,
+          // The source closes the connection once it has sent the STOP_EVENT.
+          (Ok(None), Some(stopped_at), Some((policy, connect)))
+          | (Err(DriverError::Io(_)), Some(stopped_at), Some((policy, connect)))
+          | (Err(DriverError::ConnectionResetByPeer), Some(stopped_at), Some((policy, connect))) => {
+            let restarted = select! {
+              _ = shutdown => break,
+              restarted = restart(policy, connect, &replication_opts, delay, stopped_at).fuse() => {
+                restarted?
+              }
+            };
+            conn = restarted;
+            continue;
+          }
+          (Ok(None), _, _) => break,
+          (Err(err), _, _) => return Err(err.into()),
+        };
+        read += 1;
+
+        // Artificial events, e.g. the ROTATE_EVENT at the beginning of the dump, have no timestamp.
+        if let Some(wait) = delay.and_then(|delay| remaining_delay(header.timestamp(), delay)) {
+          select! {
+            _ = shutdown => break,
+            _ = futures_timer::Delay::new(wait).fuse() => {}
+          }
+        }
+
+        let started = Instant::now();
+        let decoded = match panic::catch_unwind(AssertUnwindSafe(|| decoder.decode(&header, event)))
+        {
+          Ok(decoded) => decoded.map_err(|err| PipelineError::Driver(err.into())),
+          Err(panic) => Err(PipelineError::Panicked(panic_message(&*panic))),
+        };
+        let change = match decoded {
+          Ok(change) => change,
+          Err(err) => {
+            eprintln!(
+              "error: failed to decode the {} bytes event ending at {}:{}: {}\n{}",
+              header.event_size(),
+              conn.binlog_file(),
+              header.log_pos(),
+              error_chain(&err),
+              logged_bytes(conn.last_binlog_event())
+            );
+            match undecodable_policy {
+              UndecodablePolicy::Fail => {
+                conn.dump_crash();
+                return Err(err);
+              }
+              UndecodablePolicy::Skip => {
+                dropped.record_table("", "", DropReason::Undecodable, None);
+                continue;
+              }
+            }
+          }
+        };
+        metrics.decode.record(started.elapsed());
+        if let Some(ChangeEvent::SourceRestart { position }) = &change {
+          stopped_at = position.clone();
+        }
+        if let Some(change) = change {
+          if let Some(size) = in_flight_size(&change) {
+            metrics.add_in_flight(size);
+          }
+          metrics.transform.incr_queue_length();
+          if decoded_sender.send(change).await.is_err() {
+            break;
+          }
+        }
+      }
+      Ok::<_, PipelineError<K::Error>>(())
+    };
+
+    let transform = async move {
+      let mut changes = decoded
+        .map(|change| {
+          metrics.transform.decr_queue_length();
+          let size = in_flight_size(&change);
+          // To report the change if a transform panics on it.
+          let table = match stages.is_empty() {
+            true => None,
+            false => {
+              let (schema, table) = table_of(&change);
+              Some((schema.to_string(), table.to_string()))
+            }
+          };
+          let started = Instant::now();
+          let applied = AssertUnwindSafe(apply_stages(stages, dropped, change)).catch_unwind();
+          async move {
+            let applied = match applied.await {
+              Ok(change) => Ok(change),
+              Err(panic) => Err((table.unwrap_or_default(), panic_message(&*panic))),
+            };
+            metrics.transform.record(started.elapsed());
+            // Transforms change the size of the changes, or drop them.
+            if let Some(size) = size {
+              metrics.remove_in_flight(1, size);
+              if let Ok(Some(ref change)) = applied {
+                if let Some(size) = in_flight_size(change) {
+                  metrics.add_in_flight(size);
+                }
+              }
+            }
+            applied
+          }
+        })
+        .buffered(transform_workers);
+      while let Some(change) = changes.next().await {
+        let change = match change {
+          Ok(change) => change,
+          Err(((schema, table), message)) => {
+            eprintln!(
+              "error: a transform panicked on a change of {}.{}: {}",
+              schema, table, message
+            );
+            match undecodable_policy {
+              UndecodablePolicy::Fail => return Err(PipelineError::Panicked(message)),
+              UndecodablePolicy::Skip => {
+                dropped.record_table(&schema, &table, DropReason::Undecodable, None);
+                continue;
+              }
+            }
+          }
+        };
+        if let Some(change) = change {
+          metrics.sink.incr_queue_length();
+          if transformed_sender.send(change).await.is_err() {
+            break;
+          }
+        }
+      }
+      Ok(())
+    };
+
+    let push = async move {
+      // Destinations of the changes of the current transaction.
+      let mut routed = BTreeSet::new();
+      while let Some(change) = transformed.next().await {
+        metrics.sink.decr_queue_length();
+        let end = match &change {
+          ChangeEvent::End(metadata) => metadata.position().cloned(),
+          _ => None,
+        };
+        if let Some((destinations, route)) = &destinations {
+          routed.extend(route(&change));
+          // Before the sink gets the transaction, which it may ack right away.
+          if let Some(ref position) = end {
+            destinations.route(position, routed.iter().map(String::as_str));
+            routed.clear();
+          }
+        }
+        let group = match grouper.push(change) {
+          Some(group) => group,
+          None => {
+            // A transaction whose changes were all filtered out: there is nothing to push, but
+            // the stream can resume after it.
+            if let (Some(watermarks), Some(position)) = (&watermarks, end) {
+              watermarks.ack(&position);
+            }
+            continue;
+          }
+        };
+
+        let position = match &group {
+          Group::Transaction { metadata, .. } => metadata.position().cloned(),
+          Group::Chunk(chunk) if chunk.kind() == ChunkKind::End => {
+            chunk.metadata().position().cloned()
+          }
+          _ => None,
+        };
+        let events = match &group {
+          Group::Transaction { events, .. } => events.as_slice(),
+          Group::Chunk(chunk) => chunk.events(),
+          Group::Event(event) => std::slice::from_ref(event),
+        };
+        let pushed = events.iter().filter_map(in_flight_size);
+        let (pushed_events, pushed_bytes) = pushed.fold((0, 0), |(n, b), size| (n + 1, b + size));
+        let started = Instant::now();
+        sink.send(group).await.map_err(PipelineError::Sink)?;
+        metrics.remove_in_flight(pushed_events, pushed_bytes);
+        let elapsed = started.elapsed();
+        metrics.sink.record(elapsed);
+        slow_operations.observe(SlowOperation::SinkPublish, elapsed, || match position {
+          Some(ref position) => format!("transaction ending at {}", position),
+          None => "changes outside of a transaction".to_string(),
+        });
+        if let (Some(watermarks), Some(position)) = (&watermarks, position) {
+          watermarks.ack(&position);
+        }
+      }
+      sink.close().await.map_err(PipelineError::Sink)
+    };
+
+    futures::try_join!(decode, transform, push).map(drop)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::classify::Operation;
+  use crate::event::TransactionMetadata;
+  use futures::executor::block_on;
+  use std::time::Duration;
+
+  fn statement(sql: &str) -> ChangeEvent {
+    ChangeEvent::Statement {
+      schema: "pets".into(),
+      sql: sql.into(),
+      operation: Operation::Insert,
+      tables: Vec::new(),
+      time_zone: None,
+    }
+  }
+
+  fn sql(change: Option<ChangeEvent>) -> Option<String> {
+    match change? {
+      ChangeEvent::Statement { sql, .. } => Some(sql),
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+  }
+
+  #[test]
+  fn applies_stages_in_order() {
+    let builder = Pipeline::builder()
+      .filter(
+        |change| !matches!(change, ChangeEvent::Statement { sql, .. } if sql.contains("dogs")),
+      )
+      .transform(|change| match change {
+        ChangeEvent::Statement { sql, .. } if sql.contains("vets") => None,
+        ChangeEvent::Statement { sql, .. } => Some(statement(&sql.replace("cats", "felines"))),
+        change => Some(change),
+      })
+      .filter(
+        |change| !matches!(change, ChangeEvent::Statement { sql, .. } if sql.contains("cats")),
+      )
+      .async_transform(|change| async move {
+        match change {
+          ChangeEvent::Statement { sql, .. } => Some(statement(&sql.to_lowercase())),
+          change => Some(change),
+        }
+      });
+    let stages = builder.stages;
+    let dropped = DroppedEvents::default();
+
+    let apply = |sql_text: &str| {
+      sql(block_on(apply_stages(
+        &stages,
+        &dropped,
+        statement(sql_text),
+      )))
+    };
+    assert_eq!(
+      Some("insert into felines values (1)".to_string()),
+      apply("INSERT INTO cats VALUES (1)")
+    );
+    assert_eq!(None, apply("INSERT INTO dogs VALUES (1)"));
+    assert_eq!(None, apply("INSERT INTO vets VALUES (1)"));
+    // Transforms that drop changes are not filters.
+    assert_eq!(1, dropped.count("pets", "", DropReason::Filter));
+
+    // Markers are never filtered out.
+    let stages = vec![Stage::Filter(Box::new(|_| false))];
+    let begin = ChangeEvent::Begin(TransactionMetadata::default());
+    assert!(block_on(apply_stages(&stages, &dropped, begin)).is_some());
+  }
+
+  #[test]
+  fn catches_panicking_transforms() {
+    let stages = vec![Stage::Transform(Box::new(|change| match change {
+      ChangeEvent::Statement { ref sql, .. } if sql.contains("dogs") => panic!("no dogs: {}", sql),
+      change => Some(change),
+    }))];
+    let dropped = DroppedEvents::default();
+    let apply = |sql: &str| {
+      block_on(AssertUnwindSafe(apply_stages(&stages, &dropped, statement(sql))).catch_unwind())
+        .map_err(|panic| panic_message(&*panic))
+    };
+    assert!(matches!(apply("INSERT INTO cats VALUES (1)"), Ok(Some(_))));
+    assert_eq!(
+      Err("no dogs: INSERT INTO dogs VALUES (1)".to_string()),
+      apply("INSERT INTO dogs VALUES (1)").map(drop)
+    );
+
+    let event = vec![0xab; MAX_LOGGED_EVENT_LEN + 1];
+    assert!(logged_bytes(&event).ends_with(&format!("... ({} bytes)", event.len())));
+  }
+
+  #[test]
+  fn records_stage_metrics() {
+    let metrics = PipelineMetrics::default();
+    let sink = metrics.stage(PipelineStage::Sink);
+    assert_eq!(Duration::from_secs(0), sink.average_latency());
+
+    sink.incr_queue_length();
+    sink.incr_queue_length();
+    sink.decr_queue_length();
+    sink.record(Duration::from_millis(10));
+    sink.record(Duration::from_millis(30));
+    assert_eq!(1, sink.queue_length());
+    assert_eq!(2, sink.processed());
+    assert_eq!(Duration::from_millis(20), sink.average_latency());
+    assert_eq!(Duration::from_millis(30), sink.max_latency());
+    assert_eq!(0, metrics.stage(PipelineStage::Decode).processed());
+  }
+
+  #[test]
+  fn tracks_pressure() {
+    let metrics = PipelineMetrics::default();
+    let insert = statement("INSERT INTO cats VALUES (1)");
+    let size = in_flight_size(&insert).unwrap();
+    assert_eq!(
+      None,
+      in_flight_size(&ChangeEvent::End(TransactionMetadata::default()))
+    );
+
+    metrics.add_in_flight(size);
+    metrics.add_in_flight(size);
+    assert_eq!(2, metrics.pressure().events());
+    assert_eq!(2 * size, metrics.pressure().bytes());
+    metrics.remove_in_flight(2, 2 * size);
+    assert_eq!(Pressure::default(), metrics.pressure());
+  }
+
+  #[test]
+  fn delays_events() {
+    let delay = Duration::from_secs(3600);
+    let now = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .unwrap()
+      .as_secs() as u32;
+
+    let wait = remaining_delay(now - 600, delay).unwrap();
+    assert!(wait > Duration::from_secs(2990) && wait <= Duration::from_secs(3000));
+    assert_eq!(None, remaining_delay(now - 3601, delay));
+    // Artificial events.
+    assert_eq!(None, remaining_delay(0, delay));
+  }
+}
@@ -0,0 +1,459 @@
+// Pool of connections, for the snapshotting side of a pipeline to run SELECTs in parallel while
+// the binlog stream holds its own connection.
+//
+// Connections are opened on demand, up to `max_size`, and handed back to the pool when the
+// `PooledConnection` is dropped. Dropping can not wait on the server, so the session of a returned
+// connection is reset (COM_RESET_CONNECTION) when it is acquired next, which also proves it is
+// still alive; connections returned with `PooledConnection::release` are reset right away. Idle
+// connections that were reset are checked with COM_PING before they are handed out, and the ones
+// that fail either are dropped and replaced.
+//
+// Nothing runs in the background: `fill` opens connections until `min_idle` are idle, call it once
+// the pool is created and whenever convenient, e.g. between two chunks of a snapshot.
+
+use super::conn::{Connection, DriverError};
+use futures::channel::oneshot;
+use futures::future::{BoxFuture, Future, FutureExt};
+use futures::io::{AsyncRead, AsyncWrite};
+use futures::select;
+use std::collections::VecDeque;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const DEFAULT_MAX_SIZE: usize = 10;
+const DEFAULT_ACQUIRE_TIMEOUT: Duration = Duration::from_secs(30);
+
+type DriverResult<T> = Result<T, DriverError>;
+
+// Opens a new connection, see `Pool::new`.
+type Connect<S> = Box<dyn Fn() -> BoxFuture<'static, DriverResult<Connection<S>>> + Send + Sync>;
+
+/// Pool of connections to the same server. Clones share their connections.
+pub struct Pool<S> {
+  inner: Arc<Inner<S>>,
+}
+
+struct Inner<S> {
+  connect: Connect<S>,
+  max_size: usize,
+  min_idle: usize,
+  acquire_timeout: Duration,
+  state: Mutex<State<S>>,
+}
+
+struct State<S> {
+  idle: Vec<Idle<S>>,
+  // Connections open, idle or not, and being opened.
+  size: usize,
+  // Tasks waiting for a connection to be returned, woken one at a time.
+  waiters: VecDeque<oneshot::Sender<()>>,
+}
+
+struct Idle<S> {
+  conn: Connection<S>,
+  // Returned without a reset of its session.
+  dirty: bool,
+}
+
+impl<S> Clone for Pool<S> {
+  fn clone(&self) -> Self {
+    Self {
+      inner: self.inner.clone(),
+    }
+  }
+}
+
+impl<S> Pool<S>
+where
+  S: AsyncRead + AsyncWrite + Unpin,
+{
+  /// Creates a pool opening its connections with `connect`, e.g.
+  /// `Pool::new(move || Connection::connect(opts.clone()))`. No connection is opened until the
+  /// first `acquire` or `fill`.
+  pub fn new<F>(connect: impl Fn() -> F + Send + Sync + 'static) -> Self
+  where
+    F: Future<Output = DriverResult<Connection<S>>> + Send + 'static,
+  {
+    Self {
+      inner: Arc::new(Inner {
+        connect: Box::new(move || connect().boxed()),
+        max_size: DEFAULT_MAX_SIZE,
+        min_idle: 0,
+        acquire_timeout: DEFAULT_ACQUIRE_TIMEOUT,
+        state: Mutex::new(State {
+          idle: Vec::new(),
+          size: 0,
+          waiters: VecDeque::new(),
+        }),
+      }),
+    }
+  }
+
+  /// Maximum number of connections open at once, 10 by default.
+  pub fn max_size(mut self, max_size: usize) -> Self {
+    self.inner_mut().max_size = max_size.max(1);
+    self
+  }
+
+  /// Number of idle connections `fill` keeps open, none by default. Capped by `max_size`.
+  pub fn min_idle(mut self, min_idle: usize) -> Self {
+    self.inner_mut().min_idle = min_idle;
+    self
+  }
+
+  /// How long `acquire` waits for a connection when `max_size` are in use, 30 seconds by default.
+  pub fn acquire_timeout(mut self, acquire_timeout: Duration) -> Self {
+    self.inner_mut().acquire_timeout = acquire_timeout;
+    self
+  }
+
+  // The options are set before the pool is shared.
+  fn inner_mut(&mut self) -> &mut Inner<S> {
+    Arc::get_mut(&mut self.inner).expect("the pool is configured before it is cloned")
+  }
+
+  /// Number of connections open, in use or idle.
+  pub fn size(&self) -> usize {
+    self.inner.state.lock().unwrap().size
+  }
+
+  pub fn idle(&self) -> usize {
+    self.inner.state.lock().unwrap().idle.len()
+  }
+
+  /// Hands out an idle connection, after resetting or pinging it, or opens a new one. Waits for a
+  /// connection to be returned when `max_size` are in use, and fails with
+  /// `DriverError::PoolTimeout` after `acquire_timeout`.
+  pub async fn acquire(&self) -> DriverResult<PooledConnection<S>> {
+    let deadline = Instant::now() + self.inner.acquire_timeout;
+    loop {
+      let next = {
+        let mut state = self.inner.state.lock().unwrap();
+        if let Some(idle) = state.idle.pop() {
+          Next::Check(Box::new(idle))
+        } else if state.size < self.inner.max_size {
+          state.size += 1;
+          Next::Open
+        } else {
+          let (sender, receiver) = oneshot::channel();
+          state.waiters.push_back(sender);
+          Next::Wait(receiver)
+        }
+      };
+
+      match next {
+        Next::Check(idle) => {
+          let slot = Slot::new(&self.inner);
+          let Idle { mut conn, dirty } = *idle;
+          let checked = match dirty {
+            true => conn.reset_connection().await,
+            false => conn.ping().await,
+          };
+          match checked {
+            Ok(()) => return Ok(slot.pooled(conn)),
+            Err(err) => eprintln!("warning: dropping a connection of the pool: {}", err),
+          }
+        }
+        Next::Open => {
+          let slot = Slot::new(&self.inner);
+          return (self.inner.connect)().await.map(|conn| slot.pooled(conn));
+        }
+        Next::Wait(receiver) => {
+          let mut waiter = Waiter {
+            inner: &self.inner,
+            receiver,
+          };
+          let timeout = deadline.saturating_duration_since(Instant::now());
+          select! {
+            _ = (&mut waiter.receiver).fuse() => {}
+            _ = futures_timer::Delay::new(timeout).fuse() => {
+              return Err(DriverError::PoolTimeout(self.inner.acquire_timeout));
+            }
+          }
+        }
+      }
+    }
+  }
+
+  /// Opens connections until `min_idle` are idle, or `max_size` are open.
+  pub async fn fill(&self) -> DriverResult<()> {
+    loop {
+      {
+        let mut state = self.inner.state.lock().unwrap();
+        if state.idle.len() >= self.inner.min_idle || state.size >= self.inner.max_size {
+          return Ok(());
+        }
+        state.size += 1;
+      }
+      let slot = Slot::new(&self.inner);
+      slot.put_back((self.inner.connect)().await?);
+    }
+  }
+}
+
+enum Next<S> {
+  Check(Box<Idle<S>>),
+  Open,
+  Wait(oneshot::Receiver<()>),
+}
+
+impl<S> Inner<S> {
+  fn put_back(&self, conn: Connection<S>, dirty: bool) {
+    let mut state = self.state.lock().unwrap();
+    state.idle.push(Idle { conn, dirty });
+    state.wake_one();
+  }
+
+  // Forgets a connection that was dropped, or could not be opened.
+  fn discard(&self) {
+    let mut state = self.state.lock().unwrap();
+    state.size -= 1;
+    state.wake_one();
+  }
+}
+
+// Connection counted in the size of the pool, being opened or checked. Dropped before it is handed
+// out, e.g. when `acquire` fails or is cancelled, the pool forgets it.
+struct Slot<'a, S> {
+  // Taken once the connection is handed out.
+  inner: Option<&'a Arc<Inner<S>>>,
+}
+
+impl<'a, S> Slot<'a, S> {
+  fn new(inner: &'a Arc<Inner<S>>) -> Self {
+    Self { inner: Some(inner) }
+  }
+
+  fn pooled(mut self, conn: Connection<S>) -> PooledConnection<S> {
+    PooledConnection {
+      conn: Some(conn),
+      inner: self.inner.take().unwrap().clone(),
+    }
+  }
+
+  fn put_back(mut self, conn: Connection<S>) {
+    self.inner.take().unwrap().put_back(conn, false);
+  }
+}
+
+impl<S> Drop for Slot<'_, S> {
+  fn drop(&mut self) {
+    if let Some(inner) = self.inner.take() {
+      inner.discard();
+    }
+  }
+}
+
+// Task waiting for a connection to be returned. Dropped after it was woken, e.g. when `acquire`
+// times out or is cancelled at the same time, it passes the wakeup on to the next waiter.
+struct Waiter<'a, S> {
+  inner: &'a Inner<S>,
+  receiver: oneshot::Receiver<()>,
+}
+
+impl<S> Drop for Waiter<'_, S> {
+  fn drop(&mut self) {
+    self.receiver.close();
+    if let Ok(Some(())) = self.receiver.try_recv() {
+      self.inner.state.lock().unwrap().wake_one();
+    }
+  }
+}
+
+impl<S> State<S> {
+  fn wake_one(&mut self) {
+    // The waiters that timed out are gone.
+    while let Some(waiter) = self.waiters.pop_front() {
+      if waiter.send(()).is_ok() {
+        break;
+      }
+    }
+  }
+}
+
+/// Connection acquired from a `Pool`, returned to it when dropped.
+pub struct PooledConnection<S> {
+  // Taken on release.
+  conn: Option<Connection<S>>,
+  inner: Arc<Inner<S>>,
+}
+
+impl<S> PooledConnection<S>
+where
+  S: AsyncRead + AsyncWrite + Unpin,
+{
+  /// Resets the session of the connection and returns it to the pool, or drops it when the reset
+  /// fails.
+  pub async fn release(mut self) {
+    if let Some(mut conn) = self.conn.take() {
+      match conn.reset_connection().await {
+        Ok(()) => self.inner.put_back(conn, false),
+        Err(_) => self.inner.discard(),
+      }
+    }
+  }
+
+  /// Takes the connection out of the pool, e.g. after an error that left it in an unknown state.
+  /// The pool opens another one in its place.
+  pub fn detach(mut self) -> Connection<S> {
+    self.inner.discard();
+    self.conn.take().unwrap()
+  }
+}
+
+impl<S> Deref for PooledConnection<S> {
+  type Target = Connection<S>;
+
+  fn deref(&self) -> &Connection<S> {
+    self.conn.as_ref().unwrap()
+  }
+}
+
+impl<S> DerefMut for PooledConnection<S> {
+  fn deref_mut(&mut self) -> &mut Connection<S> {
+    self.conn.as_mut().unwrap()
+  }
+}
+
+impl<S> Drop for PooledConnection<S> {
+  fn drop(&mut self) {
+    if let Some(conn) = self.conn.take() {
+      self.inner.put_back(conn, true);
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::conn::ConnectionOptions;
+  use futures::executor::block_on;
+  use futures::io::Cursor;
+  use std::io;
+  use std::pin::Pin;
+  use std::sync::atomic::{AtomicUsize, Ordering};
+  use std::task::{Context, Poll};
+
+  const HANDSHAKE: &[u8] = include_bytes!("../fuzz/corpus/handshake/mysql_5_7");
+  const OK: &[u8] = b"\x00\x00\x00\x02\x00\x00\x00";
+
+  // Server answering the handshake, then every command with an OK packet.
+  struct OkServer {
+    input: Cursor<Vec<u8>>,
+  }
+
+  impl OkServer {
+    fn new(commands: usize) -> Self {
+      let mut input = Vec::new();
+      let mut packets = vec![(0, HANDSHAKE), (2, OK)];
+      packets.extend(std::iter::repeat_n((1, OK), commands));
+      for (sequence_id, payload) in packets {
+        input.extend_from_slice(&(payload.len() as u32).to_le_bytes()[..3]);
+        input.push(sequence_id);
+        input.extend_from_slice(payload);
+      }
+      Self {
+        input: Cursor::new(input),
+      }
+    }
+  }
+
+  impl AsyncRead for OkServer {
+    fn poll_read(
+      mut self: Pin<&mut Self>,
+      cx: &mut Context<'_>,
+      buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+      Pin::new(&mut self.input).poll_read(cx, buf)
+    }
+  }
+
+  impl AsyncWrite for OkServer {
+    fn poll_write(
+      self: Pin<&mut Self>,
+      _cx: &mut Context<'_>,
+      buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+      Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+      Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+      Poll::Ready(Ok(()))
+    }
+  }
+
+  // Pool of connections answering `commands` commands each, and the number of connections opened.
+  fn pool(commands: usize) -> (Pool<OkServer>, Arc<AtomicUsize>) {
+    let opened = Arc::new(AtomicUsize::new(0));
+    let counter = opened.clone();
+    let pool = Pool::new(move || {
+      counter.fetch_add(1, Ordering::Relaxed);
+      Connection::with_stream(OkServer::new(commands), ConnectionOptions::default())
+    });
+    (pool, opened)
+  }
+
+  #[test]
+  fn recycles_connections() {
+    let (pool, opened) = pool(2);
+    let pool = pool.max_size(1).acquire_timeout(Duration::from_millis(10));
+    block_on(async {
+      let conn = pool.acquire().await.unwrap();
+      assert!(matches!(
+        pool.acquire().await,
+        Err(DriverError::PoolTimeout(_))
+      ));
+      drop(conn);
+      assert_eq!(1, pool.idle());
+
+      // Reset, then released with a reset.
+      let conn = pool.acquire().await.unwrap();
+      conn.release().await;
+      assert_eq!((1, 1), (pool.size(), pool.idle()));
+      // The server has no answer left to the ping, the connection is replaced.
+      drop(pool.acquire().await.unwrap());
+    });
+    assert_eq!(2, opened.load(Ordering::Relaxed));
+  }
+
+  #[test]
+  fn fills_idle_connections() {
+    let (pool, opened) = pool(0);
+    let pool = pool.max_size(3).min_idle(5);
+    block_on(pool.fill()).unwrap();
+    assert_eq!((3, 3), (pool.size(), pool.idle()));
+    assert_eq!(3, opened.load(Ordering::Relaxed));
+  }
+
+  #[test]
+  fn forgets_cancelled_acquires() {
+    // Cancelled while opening a connection.
+    let opening: Pool<OkServer> =
+      Pool::new(futures::future::pending::<DriverResult<Connection<OkServer>>>).max_size(1);
+    block_on(async {
+      let mut acquire = Box::pin(opening.acquire());
+      assert!(futures::poll!(acquire.as_mut()).is_pending());
+    });
+    assert_eq!(0, opening.size());
+
+    // Cancelled once woken, the next waiter gets the connection.
+    let (pool, _) = pool(1);
+    let pool = pool.max_size(1);
+    block_on(async {
+      let conn = pool.acquire().await.unwrap();
+      let mut woken = Box::pin(pool.acquire());
+      let mut next = Box::pin(pool.acquire());
+      assert!(futures::poll!(woken.as_mut()).is_pending());
+      assert!(futures::poll!(next.as_mut()).is_pending());
+      drop(conn);
+      drop(woken);
+      assert!(futures::poll!(next.as_mut()).is_ready());
+    });
+    assert_eq!(1, pool.size());
+  }
+}
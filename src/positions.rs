@@ -0,0 +1,131 @@
+// Binlog coordinates of a server, and the rate at which it writes binlogs, e.g. to size the
+// retention (`binlog_expire_logs_seconds`) that a pipeline needs to survive an outage, or to check
+// that a replication user can read them before starting one.
+//
+// SHOW BINARY LOGS lists the binlog files with their sizes. The end of the last file is the
+// current position, as returned by SHOW MASTER STATUS, and the growth between two reads is the
+// number of bytes written after the previous position, across rotations.
+
+use super::conn::{Connection, DriverError};
+use futures::io::{AsyncRead, AsyncWrite};
+use std::time::Instant;
+
+type DriverResult<T> = Result<T, DriverError>;
+
+/// Binlog files of a server, and their sizes, at the time they were read.
+#[derive(Clone, Debug)]
+pub struct BinaryLogs {
+  // Names and sizes, from the oldest to the newest.
+  files: Vec<(String, u64)>,
+  read_at: Instant,
+}
+
+impl BinaryLogs {
+  pub async fn read<S: AsyncRead + AsyncWrite + Unpin>(
+    conn: &mut Connection<S>,
+  ) -> DriverResult<Self> {
+    let results = conn.query("SHOW BINARY LOGS").await?;
+    let files = results
+      .iter()
+      .map(|row| {
+        let name = row.get("Log_name").and_then(|value| value.as_str());
+        let size = row
+          .get("File_size")
+          .and_then(|value| value.as_str())
+          .and_then(|size| size.parse().ok());
+        match (name, size) {
+          (Some(name), Some(size)) => Ok((name.to_string(), size)),
+          _ => Err(DriverError::UnexpectedPacket),
+        }
+      })
+      .collect::<DriverResult<_>>()?;
+    Ok(Self::new(files, Instant::now()))
+  }
+
+  fn new(files: Vec<(String, u64)>, read_at: Instant) -> Self {
+    Self { files, read_at }
+  }
+
+  /// Names and sizes of the binlog files, from the oldest to the newest.
+  pub fn files(&self) -> &[(String, u64)] {
+    &self.files
+  }
+
+  /// Current binlog file and position, `None` when binary logging is disabled.
+  pub fn position(&self) -> Option<(&str, u64)> {
+    self.files.last().map(|(file, size)| (file.as_str(), *size))
+  }
+
+  /// Size of the binlogs retained by the server.
+  pub fn total_size(&self) -> u64 {
+    self.files.iter().map(|(_, size)| size).sum()
+  }
+
+  /// Number of bytes written since `previous` was read.
+  pub fn growth_since(&self, previous: &Self) -> u64 {
+    let (file, position) = match previous.position() {
+      Some(position) => position,
+      None => return self.total_size(),
+    };
+    // The previous file is only missing when it was purged in between, the remaining files were
+    // all written since.
+    match self.files.iter().position(|(name, _)| name == file) {
+      Some(index) => self.files[index..]
+        .iter()
+        .map(|(_, size)| size)
+        .sum::<u64>()
+        .saturating_sub(position),
+      None => self.total_size(),
+    }
+  }
+
+  /// Number of bytes written per second since `previous` was read.
+  pub fn rate_since(&self, previous: &Self) -> f64 {
+    let elapsed = self.read_at.saturating_duration_since(previous.read_at);
+    if elapsed.as_secs_f64() == 0.0 {
+      return 0.0;
+    }
+    self.growth_since(previous) as f64 / elapsed.as_secs_f64()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use std::time::Duration;
+
+  fn binary_logs(files: &[(&str, u64)], read_at: Instant) -> BinaryLogs {
+    let files = files
+      .iter()
+      .map(|(name, size)| (name.to_string(), *size))
+      .collect();
+    BinaryLogs::new(files, read_at)
+  }
+
+  #[test]
+  fn measures_the_growth_across_rotations() {
+    let start = Instant::now();
+    let previous = binary_logs(&[("bin.000001", 500), ("bin.000002", 100)], start);
+
+    let written = binary_logs(
+      &[("bin.000001", 500), ("bin.000002", 300)],
+      start + Duration::from_secs(2),
+    );
+    assert_eq!(Some(("bin.000002", 300)), written.position());
+    assert_eq!(800, written.total_size());
+    assert_eq!(200, written.growth_since(&previous));
+    assert_eq!(100.0, written.rate_since(&previous));
+
+    let rotated = binary_logs(
+      &[("bin.000002", 400), ("bin.000003", 50)],
+      start + Duration::from_secs(2),
+    );
+    assert_eq!(350, rotated.growth_since(&previous));
+
+    let purged = binary_logs(&[("bin.000004", 50)], start + Duration::from_secs(2));
+    assert_eq!(50, purged.growth_since(&previous));
+
+    assert_eq!(0.0, previous.rate_since(&previous));
+    assert_eq!(None, binary_logs(&[], start).position());
+  }
+}
@@ -0,0 +1,24 @@
+// The types most programs built on the crate start from, for a single `use`:
+//
+//   use tail_mysql::prelude::*;
+//
+// Each one is only re-exported with the features of its module. The sinks of the pipeline are
+// `futures::Sink`s, re-exported with `SinkExt` to push onto them, and `StreamExt` to read binlog
+// streams.
+
+#[cfg(feature = "client")]
+pub use crate::conn::{Connection, ConnectionOptions, DriverError, ReplicationOptions};
+#[cfg(feature = "binlog")]
+pub use crate::event::{ChangeEvent, EventDecoder};
+#[cfg(all(feature = "client", feature = "binlog"))]
+pub use crate::pipeline::{Pipeline, PipelineError};
+pub use crate::protocol_binlog::{BinlogEvent, EventHeader};
+#[cfg(feature = "sinks-kafka")]
+pub use crate::sink::kafka::KafkaSink;
+#[cfg(feature = "sinks-mysql")]
+pub use crate::sink::mysql::MysqlSink;
+pub use crate::value::Value;
+#[cfg(feature = "futures")]
+pub use futures::sink::{Sink, SinkExt};
+#[cfg(feature = "futures")]
+pub use futures::stream::StreamExt;
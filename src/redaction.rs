@@ -0,0 +1,276 @@
+// Redaction of personal information (emails, phone numbers, credit card numbers...) while decoding
+// rows images.
+//
+// Rules either redact whole columns, picked by their name, or the parts of text values that match
+// a pattern, whatever their column. Column names are only known with `binlog_row_metadata=FULL`,
+// see `TableMapEvent::column_names`: without it, only value rules apply.
+//
+// Detection is heuristic, and prone to both false positives (e.g. order numbers that look like
+// phone numbers) and false negatives. Columns known to hold sensitive values are better encrypted
+// or dropped.
+
+use super::value::{Row, Value};
+use regex::bytes::NoExpand;
+use regex::Regex;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Preset {
+  Email,
+  PhoneNumber,
+  /// Sequences of 13 to 19 digits, optionally grouped by spaces or dashes.
+  CreditCard,
+}
+
+impl Preset {
+  /// Pattern matched against the names of columns, case insensitive.
+  pub fn column_pattern(self) -> &'static str {
+    match self {
+      Preset::Email => r"(?i)e[-_]?mail",
+      Preset::PhoneNumber => r"(?i)phone|mobile|fax",
+      Preset::CreditCard => r"(?i)(credit|debit)_?card|card_?(number|num|no)\b|^(cc|pan)(_|$)",
+    }
+  }
+
+  /// Pattern matched against text values.
+  pub fn value_pattern(self) -> &'static str {
+    match self {
+      Preset::Email => r"[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}",
+      Preset::PhoneNumber => r"(\+\d{1,3}[ .-]?)?(\(\d{3}\)|\b\d{3})[ .-]?\d{3}[ .-]?\d{4}\b",
+      Preset::CreditCard => r"\b(\d[ -]?){12,18}\d\b",
+    }
+  }
+
+  /// Redacts the columns named after the preset, e.g. `email` or `contact_email`.
+  pub fn by_column_name(self) -> RedactionRule {
+    RedactionRule::ColumnName(Regex::new(self.column_pattern()).expect("invalid preset pattern"))
+  }
+
+  /// Redacts the parts of text values that look like the preset.
+  pub fn by_value(self) -> RedactionRule {
+    RedactionRule::Value(
+      regex::bytes::Regex::new(self.value_pattern()).expect("invalid preset pattern"),
+    )
+  }
+}
+
+#[derive(Clone, Debug)]
+pub enum RedactionRule {
+  /// Redacts the whole value of the columns whose name matches, whatever their type.
+  ColumnName(Regex),
+  /// Redacts the parts of string and blob values that match.
+  Value(regex::bytes::Regex),
+}
+
+impl RedactionRule {
+  pub fn column_name(pattern: &str) -> Result<Self, regex::Error> {
+    Ok(RedactionRule::ColumnName(Regex::new(pattern)?))
+  }
+
+  pub fn value(pattern: &str) -> Result<Self, regex::Error> {
+    Ok(RedactionRule::Value(regex::bytes::Regex::new(pattern)?))
+  }
+}
+
+/// Redaction rules, applied to every table unless overridden for a table.
+#[derive(Clone, Debug)]
+pub struct Redaction {
+  rules: Vec<RedactionRule>,
+  tables: HashMap<(String, String), Vec<RedactionRule>>,
+  mask: Vec<u8>,
+}
+
+impl Default for Redaction {
+  fn default() -> Self {
+    Self {
+      rules: Vec::new(),
+      tables: HashMap::new(),
+      mask: b"[REDACTED]".to_vec(),
+    }
+  }
+}
+
+impl Redaction {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Redacts by both the column name and the value patterns of every preset.
+  pub fn presets() -> Self {
+    [Preset::Email, Preset::PhoneNumber, Preset::CreditCard]
+      .iter()
+      .fold(Self::new(), |redaction, preset| {
+        redaction
+          .rule(preset.by_column_name())
+          .rule(preset.by_value())
+      })
+  }
+
+  pub fn rule(mut self, rule: RedactionRule) -> Self {
+    self.rules.push(rule);
+    self
+  }
+
+  /// Applies `rules` to `schema`.`table` instead of the default rules. No rules exempt the table
+  /// from redaction.
+  pub fn table(
+    mut self,
+    schema: impl Into<String>,
+    table: impl Into<String>,
+    rules: Vec<RedactionRule>,
+  ) -> Self {
+    self.tables.insert((schema.into(), table.into()), rules);
+    self
+  }
+
+  /// Replacement of redacted values, `[REDACTED]` by default.
+  pub fn mask(mut self, mask: impl Into<String>) -> Self {
+    self.mask = mask.into().into_bytes();
+    self
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.rules.is_empty() && self.tables.is_empty()
+  }
+
+  /// Redacts the rows of `schema`.`table`, whose columns are named `column_names` when known.
+  /// Returns the number of values redacted.
+  pub fn apply<'a>(
+    &self,
+    schema: &str,
+    table: &str,
+    column_names: &[String],
+    rows: impl IntoIterator<Item = &'a mut Row>,
+  ) -> u64 {
+    let rules = self
+      .tables
+      .get(&(schema.to_string(), table.to_string()))
+      .unwrap_or(&self.rules);
+    if rules.is_empty() {
+      return 0;
+    }
+
+    let redacted_columns: Vec<bool> = column_names
+      .iter()
+      .map(|name| {
+        rules.iter().any(|rule| match rule {
+          RedactionRule::ColumnName(pattern) => pattern.is_match(name),
+          RedactionRule::Value(_) => false,
+        })
+      })
+      .collect();
+
+    let mut redacted = 0;
+    for row in rows {
+      for (i, value) in row.values_mut().iter_mut().enumerate() {
+        let value = match value {
+          Some(Value::Null) | None => continue,
+          Some(value) => value,
+        };
+
+        if redacted_columns.get(i).copied().unwrap_or_default() {
+          *value = Value::Bytes(self.mask.clone());
+          redacted += 1;
+          continue;
+        }
+
+        if let Value::Bytes(bytes) = value {
+          let mut changed = false;
+          for rule in rules {
+            if let RedactionRule::Value(pattern) = rule {
+              if pattern.is_match(bytes) {
+                *bytes = pattern
+                  .replace_all(bytes, NoExpand(&self.mask))
+                  .into_owned();
+                changed = true;
+              }
+            }
+          }
+          if changed {
+            redacted += 1;
+          }
+        }
+      }
+    }
+
+    redacted
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn names() -> Vec<String> {
+    vec!["id".into(), "contact_email".into(), "notes".into()]
+  }
+
+  fn row(notes: &[u8]) -> Row {
+    Row::new(vec![
+      Some(Value::Int(1)),
+      Some(Value::Bytes(b"charlie@example.com".to_vec())),
+      Some(Value::Bytes(notes.to_vec())),
+    ])
+  }
+
+  fn notes(row: &Row) -> String {
+    match row.get(2) {
+      Some(Value::Bytes(bytes)) => String::from_utf8_lossy(bytes).into_owned(),
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+  }
+
+  #[test]
+  fn redacts_by_column_name_and_value() {
+    let redaction = Redaction::presets();
+    let mut rows = [row(
+      b"call +1 (613) 555-0199 or 613.555.0100, card 4111 1111 1111 1111, mail river@example.org",
+    )];
+    assert_eq!(
+      2,
+      redaction.apply("pets", "owners", &names(), rows.iter_mut())
+    );
+    assert_eq!(Some(&Value::Int(1)), rows[0].get(0));
+    assert_eq!(Some(&Value::Bytes(b"[REDACTED]".to_vec())), rows[0].get(1));
+    assert_eq!(
+      "call [REDACTED] or [REDACTED], card [REDACTED], mail [REDACTED]",
+      notes(&rows[0])
+    );
+
+    // Column names are unknown without binlog_row_metadata=FULL.
+    let mut rows = [row(b"Charlie")];
+    assert_eq!(1, redaction.apply("pets", "owners", &[], rows.iter_mut()));
+    assert_eq!("Charlie", notes(&rows[0]));
+  }
+
+  #[test]
+  fn overrides_rules_per_table() {
+    let redaction = Redaction::new()
+      .rule(Preset::Email.by_value())
+      .table(
+        "pets",
+        "vets",
+        vec![RedactionRule::value("Charlie").unwrap()],
+      )
+      .table("pets", "audit", Vec::new())
+      .mask("$1");
+
+    let mut rows = [row(b"Charlie")];
+    assert_eq!(
+      1,
+      redaction.apply("pets", "vets", &names(), rows.iter_mut())
+    );
+    assert_eq!("$1", notes(&rows[0]));
+    assert_eq!(
+      Some(&Value::Bytes(b"charlie@example.com".to_vec())),
+      rows[0].get(1)
+    );
+
+    let mut rows = [row(b"Charlie")];
+    assert_eq!(
+      0,
+      redaction.apply("pets", "audit", &names(), rows.iter_mut())
+    );
+    assert!(Redaction::new().is_empty());
+  }
+}
@@ -0,0 +1,310 @@
+// Replays the changes of a binlog file, e.g. to load test a sink with the traffic shape of a real
+// server: as fast as possible, at the pace they were logged, or N times faster.
+//
+// The pace follows the timestamps of the events, which MYSQL logs to the second: the changes of a
+// second are replayed in a burst. A `ReplayControl` pauses, resumes, steps through and speeds up
+// the replay from another task; the schedule starts over from the next change after each command,
+// rather than catching up with the time spent paused.
+
+use super::conn::{expand_transaction_payload, DriverError};
+use super::event::{ChangeEvent, EventDecoder};
+use super::protocol_binlog::{BinlogEvent, BinlogFile, EventHeader};
+use futures::channel::mpsc;
+use futures::future::FutureExt;
+use futures::select;
+use futures::stream::{self, Stream, StreamExt};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+type DriverResult<T> = Result<T, DriverError>;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReplaySpeed {
+  /// As fast as the consumer of the replay goes.
+  Unlimited,
+  /// At the pace the events were logged.
+  Realtime,
+  /// N times faster than the events were logged, e.g. `Multiplier(0.5)` for half the pace. N must
+  /// be positive.
+  Multiplier(f64),
+}
+
+enum Command {
+  Pause,
+  Resume,
+  Step,
+  Speed(ReplaySpeed),
+}
+
+/// Pauses, resumes or changes the speed of a `Replay`, see `Replay::control`. Commands sent once
+/// the replay is dropped are ignored.
+#[derive(Clone)]
+pub struct ReplayControl {
+  commands: mpsc::UnboundedSender<Command>,
+}
+
+impl ReplayControl {
+  /// Holds the changes that follow back until `resume` or `step`.
+  pub fn pause(&self) {
+    self.send(Command::Pause);
+  }
+
+  pub fn resume(&self) {
+    self.send(Command::Resume);
+  }
+
+  /// Lets one more change through while paused.
+  pub fn step(&self) {
+    self.send(Command::Step);
+  }
+
+  pub fn speed(&self, speed: ReplaySpeed) {
+    self.send(Command::Speed(speed));
+  }
+
+  fn send(&self, command: Command) {
+    let _ = self.commands.unbounded_send(command);
+  }
+}
+
+/// Changes of a binlog file, decoded and paced, see `Replay::into_stream`.
+pub struct Replay {
+  file: BinlogFile,
+  decoder: EventDecoder,
+  speed: ReplaySpeed,
+  control: ReplayControl,
+  commands: mpsc::UnboundedReceiver<Command>,
+  paused: bool,
+  // Changes let through while paused.
+  steps: u64,
+  // Timestamp of a change, and when it was replayed, which the changes that follow are scheduled
+  // from.
+  anchor: Option<(u32, Instant)>,
+  // Events of a compressed transaction.
+  payload_events: VecDeque<(EventHeader, BinlogEvent)>,
+}
+
+impl Replay {
+  /// Replays `file` with `decoder`, as fast as possible unless `speed` is set.
+  pub fn new(file: BinlogFile, decoder: EventDecoder) -> Self {
+    let (sender, commands) = mpsc::unbounded();
+    Self {
+      file,
+      decoder,
+      speed: ReplaySpeed::Unlimited,
+      control: ReplayControl { commands: sender },
+      commands,
+      paused: false,
+      steps: 0,
+      anchor: None,
+      payload_events: VecDeque::new(),
+    }
+  }
+
+  pub fn speed(mut self, speed: ReplaySpeed) -> Self {
+    self.speed = speed;
+    self
+  }
+
+  pub fn control(&self) -> ReplayControl {
+    self.control.clone()
+  }
+
+  /// Yields the changes of the file, until its end or its first error.
+  pub fn into_stream(self) -> impl Stream<Item = DriverResult<ChangeEvent>> {
+    stream::unfold(Some(self), |replay| async move {
+      let mut replay = replay?;
+      match replay.next_change().await {
+        Some(Ok(change)) => Some((Ok(change), Some(replay))),
+        Some(Err(err)) => Some((Err(err), None)),
+        None => None,
+      }
+    })
+  }
+
+  async fn next_change(&mut self) -> Option<DriverResult<ChangeEvent>> {
+    loop {
+      let (header, event) = match self.next_event()? {
+        Ok(event) => event,
+        Err(err) => return Some(Err(err)),
+      };
+      match self.decoder.decode(&header, event) {
+        Ok(Some(change)) => {
+          self.wait(header.timestamp()).await;
+          return Some(Ok(change));
+        }
+        Ok(None) => continue,
+        Err(err) => return Some(Err(err.into())),
+      }
+    }
+  }
+
+  fn next_event(&mut self) -> Option<DriverResult<(EventHeader, BinlogEvent)>> {
+    if let Some(event) = self.payload_events.pop_front() {
+      return Some(Ok(event));
+    }
+
+    let event = self.file.next()?.and_then(|packet| {
+      let header = packet.header();
+      Ok((header, packet.into_binlog_event()?))
+    });
+    match event {
+      Ok((header, BinlogEvent::TransactionPayload(payload))) => {
+        match expand_transaction_payload(&header, &payload) {
+          Ok(events) => self.payload_events = events,
+          Err(err) => return Some(Err(err)),
+        }
+        self.next_event()
+      }
+      event => Some(event.map_err(Into::into)),
+    }
+  }
+
+  // Waits until the change logged at `timestamp` is due, or let through while paused.
+  async fn wait(&mut self, timestamp: u32) {
+    loop {
+      while let Ok(Some(command)) = self.commands.try_next() {
+        self.apply(command);
+      }
+
+      if self.paused {
+        if self.steps > 0 {
+          self.steps -= 1;
+          return;
+        }
+        match self.commands.next().await {
+          Some(command) => self.apply(command),
+          None => return,
+        }
+        continue;
+      }
+
+      let wait = match self.remaining(timestamp, Instant::now()) {
+        Some(wait) => wait,
+        None => return,
+      };
+      select! {
+        _ = futures_timer::Delay::new(wait).fuse() => return,
+        command = self.commands.next() => match command {
+          Some(command) => self.apply(command),
+          None => return,
+        },
+      }
+    }
+  }
+
+  fn apply(&mut self, command: Command) {
+    match command {
+      Command::Pause => self.paused = true,
+      Command::Resume => self.paused = false,
+      Command::Step => self.steps += 1,
+      Command::Speed(speed) => self.speed = speed,
+    }
+    self.anchor = None;
+  }
+
+  // How long to wait for the change logged at `timestamp` to be due. The first change after a
+  // command is due right away.
+  fn remaining(&mut self, timestamp: u32, now: Instant) -> Option<Duration> {
+    let multiplier = match self.speed {
+      ReplaySpeed::Unlimited => return None,
+      ReplaySpeed::Realtime => 1.0,
+      ReplaySpeed::Multiplier(multiplier) => multiplier,
+    };
+    // Artificial events have no timestamp.
+    if timestamp == 0 {
+      return None;
+    }
+    let (anchor_timestamp, anchor_instant) = match self.anchor {
+      Some(anchor) => anchor,
+      None => {
+        self.anchor = Some((timestamp, now));
+        return None;
+      }
+    };
+
+    let logged_after = timestamp.saturating_sub(anchor_timestamp);
+    let due = anchor_instant + Duration::from_secs(u64::from(logged_after)).div_f64(multiplier);
+    due.checked_duration_since(now)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::metrics::Metrics;
+  use futures::executor::block_on;
+  use std::sync::Arc;
+
+  // TABLE_MAP_EVENT and WRITE_ROWS_EVENTV2 of the parser tests, logged at `timestamp`.
+  fn insert(timestamp: u32) -> Vec<u8> {
+    let table_map = b"\xfc\x5a\x5d\x5d\x13\x01\x00\x00\x00\x32\x00\x00\x00\x49\x01\x00\x00\x00\
+                      \x00\x2d\x0a\x00\x00\x00\x00\x01\x00\x04\x70\x65\x74\x73\x00\x04\x63\x61\
+                      \x74\x73\x00\x04\x03\x0f\x0f\x0a\x04\x58\x02\x58\x02\x00";
+    let insert = b"\xfc\x5a\x5d\x5d\x1e\x01\x00\x00\x00\x37\x00\x00\x00\x80\x01\x00\x00\x00\x00\
+                   \x2d\x0a\x00\x00\x00\x00\x01\x00\x02\x00\x04\xff\xf0\x04\x00\x00\x00\x07\x00\
+                   \x43\x68\x61\x72\x6c\x69\x65\x05\x00\x52\x69\x76\x65\x72\xb5\xc0\x0f";
+    let mut events = [&table_map[..], &insert[..]].concat();
+    events[..4].copy_from_slice(&timestamp.to_le_bytes());
+    events[50..54].copy_from_slice(&timestamp.to_le_bytes());
+    events
+  }
+
+  // Three inserts, logged a second apart.
+  fn replay() -> Replay {
+    let file = [&b"\xfebin"[..], &insert(100), &insert(101), &insert(102)].concat();
+    let decoder = EventDecoder::new(Arc::new(Metrics::default()));
+    Replay::new(BinlogFile::parse(file).unwrap(), decoder)
+  }
+
+  fn replay_all(replay: Replay) -> Duration {
+    let started = Instant::now();
+    let changes = block_on(replay.into_stream().collect::<Vec<_>>());
+    assert_eq!(3, changes.len());
+    assert!(changes.iter().all(|change| matches!(
+      change,
+      Ok(ChangeEvent::Insert { table, .. }) if table == "cats"
+    )));
+    started.elapsed()
+  }
+
+  #[test]
+  fn replays_at_speed() {
+    assert!(replay_all(replay()) < Duration::from_millis(100));
+    let elapsed = replay_all(replay().speed(ReplaySpeed::Multiplier(10.0)));
+    assert!(elapsed >= Duration::from_millis(200) && elapsed < Duration::from_secs(1));
+  }
+
+  #[test]
+  fn schedules_from_the_first_change() {
+    let mut replay = replay().speed(ReplaySpeed::Multiplier(2.0));
+    let now = Instant::now();
+    assert_eq!(None, replay.remaining(100, now));
+    assert_eq!(Some(Duration::from_secs(1)), replay.remaining(102, now));
+    // Changes logged earlier are due right away.
+    assert_eq!(Some(Duration::from_secs(0)), replay.remaining(99, now));
+    assert_eq!(None, replay.remaining(0, now));
+
+    replay.apply(Command::Speed(ReplaySpeed::Realtime));
+    assert_eq!(None, replay.remaining(102, now));
+    assert_eq!(Some(Duration::from_secs(1)), replay.remaining(103, now));
+  }
+
+  #[test]
+  fn pauses_and_steps() {
+    let replay = replay().speed(ReplaySpeed::Realtime);
+    let control = replay.control();
+    let mut changes = Box::pin(replay.into_stream());
+
+    control.pause();
+    control.step();
+    control.step();
+    assert!(block_on(changes.next()).unwrap().is_ok());
+    assert!(block_on(changes.next()).unwrap().is_ok());
+    // The rest as fast as possible.
+    control.speed(ReplaySpeed::Unlimited);
+    control.resume();
+    assert!(block_on(changes.next()).unwrap().is_ok());
+    assert!(block_on(changes.next()).is_none());
+  }
+}
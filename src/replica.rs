@@ -0,0 +1,96 @@
+// Consistent reads off a read replica, e.g. to load or verify the tables without loading the
+// primary, while the binlog of the primary is tailed.
+//
+// The handoff point is a binlog position of the primary, along with the set of GTIDs executed up
+// to it. The replica first waits until it executed the same GTIDs with
+// `WAIT_FOR_EXECUTED_GTID_SET`, then opens a consistent snapshot, which therefore contains every
+// change before the handoff point. The replica may have applied later transactions by the time the
+// snapshot is opened: these are both in the snapshot and in the stream resumed from the handoff
+// point, so consumers must apply them idempotently (e.g. as upserts).
+//
+// Requires `gtid_mode=ON` on both servers, the replica can not tell where it is otherwise.
+
+use super::conn::{Connection, DriverError};
+use super::session::SessionSettings;
+use super::value::Value;
+use futures::io::{AsyncRead, AsyncWrite};
+use std::time::Duration;
+
+type DriverResult<T> = Result<T, DriverError>;
+
+/// Position of the primary where the binlog stream takes over from the snapshot.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Handoff {
+  file: String,
+  position: u32,
+  gtid_set: String,
+}
+
+impl Handoff {
+  /// Current binlog position of `primary`, and the GTIDs it executed up to it.
+  pub async fn current<S: AsyncRead + AsyncWrite + Unpin>(
+    primary: &mut Connection<S>,
+  ) -> DriverResult<Self> {
+    let results = primary.query("SHOW MASTER STATUS").await?;
+    let status = results.first().ok_or(DriverError::ReplicationDisabled)?;
+
+    let file = status
+      .get("File")
+      .and_then(Value::as_str)
+      .ok_or(DriverError::UnexpectedPacket)?
+      .to_string();
+    let position = status
+      .get("Position")
+      .and_then(Value::as_u32)
+      .ok_or(DriverError::UnexpectedPacket)?;
+    // Server UUIDs are separated by a comma and a line break.
+    let gtid_set: String = status
+      .get("Executed_Gtid_Set")
+      .and_then(Value::as_str)
+      .unwrap_or_default()
+      .split_whitespace()
+      .collect();
+    if gtid_set.is_empty() {
+      return Err(DriverError::Unsupported(
+        "Reading from a replica with gtid_mode=OFF".to_string(),
+      ));
+    }
+
+    Ok(Self {
+      file,
+      position,
+      gtid_set,
+    })
+  }
+
+  /// Binlog file to resume the stream from, see `Connection::resume_binlog_stream`.
+  pub fn file_str(&self) -> &str {
+    self.file.as_str()
+  }
+
+  pub fn position(&self) -> u32 {
+    self.position
+  }
+
+  pub fn gtid_set_str(&self) -> &str {
+    self.gtid_set.as_str()
+  }
+}
+
+/// Waits for at most `timeout` until `replica` executed the transactions before `handoff`, then
+/// starts a read only transaction with a consistent snapshot. The queries sent to `replica` read
+/// the snapshot until `COMMIT`, and its session is left with `SessionSettings::snapshot`.
+pub async fn start_snapshot<S: AsyncRead + AsyncWrite + Unpin>(
+  replica: &mut Connection<S>,
+  handoff: &Handoff,
+  timeout: Duration,
+) -> DriverResult<()> {
+  replica
+    .wait_for_executed_gtid_set(handoff.gtid_set_str(), timeout)
+    .await?;
+
+  let mut session = SessionSettings::snapshot().apply(replica).await?;
+  session.start_snapshot().await?;
+  session.keep();
+  Ok(())
+}
@@ -0,0 +1,430 @@
+// Client-side equivalents of the replication filters of MYSQL replicas (`--replicate-do-db`,
+// `--replicate-ignore-db`, `--replicate-do-table`, `--replicate-ignore-table`,
+// `--replicate-wild-do-table` and `--replicate-wild-ignore-table`), with the same precedence rules,
+// so that the changes kept by a tailer configured like a replica are the ones the replica applies.
+//
+// The database options are checked first, and the table options only for the changes they let
+// through:
+//
+// - with do-db options, only the changes of the listed databases are kept, and ignore-db options
+//   are not checked. Otherwise the changes of the databases listed by ignore-db are dropped.
+// - the first table option matching a table decides, in the order do-table, ignore-table,
+//   wild-do-table, wild-ignore-table. A table matching none of them is dropped when there are any
+//   do-table or wild-do-table options, and kept otherwise.
+//
+// As on a replica, the database of a row change is the one of its table, but the database of a
+// statement is the default database of the session that executed it (`USE`), not the one of the
+// tables it touches: `USE pets; INSERT INTO zoo.cats ...` is checked against the database
+// options as a change of `pets`. A statement is kept when the first of its tables to match a
+// table option is to be kept. Statements that touch no table are only checked against the
+// database options.
+//
+// Database and table names are compared as they are, as with `lower_case_table_names=0`, while
+// wild patterns are matched without regard to case, like the server does. `--replicate-rewrite-db`
+// is not emulated, see `routing::TableRouter` instead.
+//
+// Beyond the options of replicas, `ignore_operation` drops some operations of the tables matching
+// a wild pattern, e.g. the updates and deletes of the tables streamed to an append-only sink. It
+// applies to the changes the options above kept. Statements are checked with their operation
+// against each of their tables, a REPLACE being neither an insert nor a delete.
+
+use super::classify::{Operation, TableName};
+use super::event::ChangeEvent;
+
+#[derive(Clone, Debug, Default)]
+pub struct ReplicationFilter {
+  do_dbs: Vec<String>,
+  ignore_dbs: Vec<String>,
+  do_tables: Vec<(String, String)>,
+  ignore_tables: Vec<(String, String)>,
+  wild_do_tables: Vec<String>,
+  wild_ignore_tables: Vec<String>,
+  ignored_operations: Vec<(String, Operation)>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Decision {
+  Keep,
+  Drop,
+}
+
+impl ReplicationFilter {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// `--replicate-do-db`, can be called more than once.
+  pub fn do_db(mut self, schema: impl Into<String>) -> Self {
+    self.do_dbs.push(schema.into());
+    self
+  }
+
+  /// `--replicate-ignore-db`, can be called more than once.
+  pub fn ignore_db(mut self, schema: impl Into<String>) -> Self {
+    self.ignore_dbs.push(schema.into());
+    self
+  }
+
+  /// `--replicate-do-table`, can be called more than once.
+  pub fn do_table(mut self, schema: impl Into<String>, table: impl Into<String>) -> Self {
+    self.do_tables.push((schema.into(), table.into()));
+    self
+  }
+
+  /// `--replicate-ignore-table`, can be called more than once.
+  pub fn ignore_table(mut self, schema: impl Into<String>, table: impl Into<String>) -> Self {
+    self.ignore_tables.push((schema.into(), table.into()));
+    self
+  }
+
+  /// `--replicate-wild-do-table`, a `LIKE` pattern of `schema.table`, e.g. `pets.%` or
+  /// `shop\_%.orders`. Can be called more than once.
+  pub fn wild_do_table(mut self, pattern: impl Into<String>) -> Self {
+    self.wild_do_tables.push(pattern.into());
+    self
+  }
+
+  /// `--replicate-wild-ignore-table`, see `wild_do_table`. Can be called more than once.
+  pub fn wild_ignore_table(mut self, pattern: impl Into<String>) -> Self {
+    self.wild_ignore_tables.push(pattern.into());
+    self
+  }
+
+  /// Drops the `operation` changes of the tables matching `pattern`, a `LIKE` pattern of
+  /// `schema.table` as with `wild_do_table`. Can be called more than once.
+  pub fn ignore_operation(mut self, pattern: impl Into<String>, operation: Operation) -> Self {
+    self.ignored_operations.push((pattern.into(), operation));
+    self
+  }
+
+  /// Adds the option named like the one of `mysqld`, without the leading dashes, e.g.
+  /// `("replicate-wild-ignore-table", "mysql.%")`, to reuse the filters of a replica. Tables are
+  /// given as `schema.table`. `ignore-operation` takes a wild pattern and the operations to drop,
+  /// e.g. `("ignore-operation", "shop.%:update,delete")`. Returns `None` for an unknown option, a
+  /// table without schema, or an unknown operation.
+  pub fn option(self, name: &str, value: &str) -> Option<Self> {
+    let table = || {
+      let mut parts = value.splitn(2, '.');
+      match (parts.next(), parts.next()) {
+        (Some(schema), Some(table)) if !schema.is_empty() && !table.is_empty() => {
+          Some((schema, table))
+        }
+        _ => None,
+      }
+    };
+    Some(match name.replace('_', "-").as_str() {
+      "replicate-do-db" => self.do_db(value),
+      "replicate-ignore-db" => self.ignore_db(value),
+      "replicate-do-table" => {
+        let (schema, table) = table()?;
+        self.do_table(schema, table)
+      }
+      "replicate-ignore-table" => {
+        let (schema, table) = table()?;
+        self.ignore_table(schema, table)
+      }
+      "replicate-wild-do-table" => {
+        table()?;
+        self.wild_do_table(value)
+      }
+      "replicate-wild-ignore-table" => {
+        table()?;
+        self.wild_ignore_table(value)
+      }
+      "ignore-operation" => {
+        let (pattern, operations) = value.rsplit_once(':')?;
+        if !pattern.contains('.') {
+          return None;
+        }
+        let mut filter = self;
+        for operation in operations.split(',') {
+          filter = filter.ignore_operation(pattern, parse_operation(operation.trim())?);
+        }
+        filter
+      }
+      _ => return None,
+    })
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.do_dbs.is_empty()
+      && self.ignore_dbs.is_empty()
+      && !self.has_table_options()
+      && self.ignored_operations.is_empty()
+  }
+
+  /// Returns true when a replica with these filters would apply `change`, and its operation is not
+  /// ignored. Transaction markers and restarts of the source are always kept.
+  pub fn accepts(&self, change: &ChangeEvent) -> bool {
+    let accepts_rows = |schema: &str, table: &str, operation| {
+      self.accepts_table(schema, table) && self.accepts_operation(schema, table, operation)
+    };
+    match change {
+      ChangeEvent::Insert { schema, table, .. } => accepts_rows(schema, table, Operation::Insert),
+      ChangeEvent::Update { schema, table, .. } => accepts_rows(schema, table, Operation::Update),
+      ChangeEvent::Delete { schema, table, .. } => accepts_rows(schema, table, Operation::Delete),
+      ChangeEvent::SchemaDrift { schema, table, .. } => self.accepts_table(schema, table),
+      ChangeEvent::Statement {
+        schema,
+        tables,
+        operation,
+        ..
+      } => {
+        self.accepts_statement(schema, tables)
+          && tables.iter().all(|table| {
+            let table_schema = table.schema_str().unwrap_or(schema);
+            self.accepts_operation(table_schema, table.table_str(), *operation)
+          })
+      }
+      ChangeEvent::Begin(_) | ChangeEvent::End(_) | ChangeEvent::SourceRestart { .. } => true,
+    }
+  }
+
+  /// Returns true when a replica with these filters would apply the row changes of
+  /// `schema`.`table`.
+  pub fn accepts_table(&self, schema: &str, table: &str) -> bool {
+    self.accepts_db(Some(schema))
+      && match self.table_decision(schema, table) {
+        Some(decision) => decision == Decision::Keep,
+        None => !self.has_do_table_options(),
+      }
+  }
+
+  /// Returns true when a replica with these filters would apply a statement executed with
+  /// `schema` as default database (empty when there was none) and touching `tables`.
+  pub fn accepts_statement(&self, schema: &str, tables: &[TableName]) -> bool {
+    let default_schema = Some(schema).filter(|schema| !schema.is_empty());
+    if !self.accepts_db(default_schema) {
+      return false;
+    }
+    if tables.is_empty() || !self.has_table_options() {
+      return true;
+    }
+    let decision = tables.iter().find_map(|table| {
+      let table_schema = table.schema_str().unwrap_or(schema);
+      self.table_decision(table_schema, table.table_str())
+    });
+    match decision {
+      Some(decision) => decision == Decision::Keep,
+      None => !self.has_do_table_options(),
+    }
+  }
+
+  /// Returns false when `operation` is ignored for `schema`.`table`.
+  pub fn accepts_operation(&self, schema: &str, table: &str, operation: Operation) -> bool {
+    if self.ignored_operations.is_empty() {
+      return true;
+    }
+    let name = format!("{}.{}", schema, table);
+    !self
+      .ignored_operations
+      .iter()
+      .any(|(pattern, ignored)| *ignored == operation && like(&name, pattern))
+  }
+
+  // A change with no database, e.g. a statement executed without `USE`, is dropped by do-db
+  // options and kept by ignore-db options.
+  fn accepts_db(&self, schema: Option<&str>) -> bool {
+    if !self.do_dbs.is_empty() {
+      return schema.is_some_and(|schema| self.do_dbs.iter().any(|db| db == schema));
+    }
+    !schema.is_some_and(|schema| self.ignore_dbs.iter().any(|db| db == schema))
+  }
+
+  fn table_decision(&self, schema: &str, table: &str) -> Option<Decision> {
+    let is_table = |(s, t): &(String, String)| s == schema && t == table;
+    if self.do_tables.iter().any(is_table) {
+      return Some(Decision::Keep);
+    }
+    if self.ignore_tables.iter().any(is_table) {
+      return Some(Decision::Drop);
+    }
+    let name = format!("{}.{}", schema, table);
+    if self
+      .wild_do_tables
+      .iter()
+      .any(|pattern| like(&name, pattern))
+    {
+      return Some(Decision::Keep);
+    }
+    if self
+      .wild_ignore_tables
+      .iter()
+      .any(|pattern| like(&name, pattern))
+    {
+      return Some(Decision::Drop);
+    }
+    None
+  }
+
+  fn has_table_options(&self) -> bool {
+    self.has_do_table_options()
+      || !self.ignore_tables.is_empty()
+      || !self.wild_ignore_tables.is_empty()
+  }
+
+  fn has_do_table_options(&self) -> bool {
+    !self.do_tables.is_empty() || !self.wild_do_tables.is_empty()
+  }
+}
+
+fn parse_operation(name: &str) -> Option<Operation> {
+  match name.to_ascii_lowercase().as_str() {
+    "insert" => Some(Operation::Insert),
+    "update" => Some(Operation::Update),
+    "delete" => Some(Operation::Delete),
+    "replace" => Some(Operation::Replace),
+    _ => None,
+  }
+}
+
+// Matches `value` against a `LIKE` pattern: `%` matches any number of characters, `_` a single
+// one, and `\` escapes them. Case insensitive.
+fn like(value: &str, pattern: &str) -> bool {
+  let value: Vec<char> = value.chars().flat_map(char::to_lowercase).collect();
+  let pattern: Vec<char> = pattern.chars().flat_map(char::to_lowercase).collect();
+  like_chars(&value, &pattern)
+}
+
+fn like_chars(value: &[char], pattern: &[char]) -> bool {
+  match pattern.split_first() {
+    None => value.is_empty(),
+    Some(('%', rest)) => (0..=value.len()).any(|i| like_chars(&value[i..], rest)),
+    Some(('_', rest)) => !value.is_empty() && like_chars(&value[1..], rest),
+    Some(('\\', rest)) if !rest.is_empty() => {
+      value.first() == rest.first() && like_chars(&value[1..], &rest[1..])
+    }
+    Some((c, rest)) => value.first() == Some(c) && like_chars(&value[1..], rest),
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn statement(schema: &str, tables: &[(Option<&str>, &str)]) -> ChangeEvent {
+    ChangeEvent::Statement {
+      schema: schema.to_string(),
+      sql: String::new(),
+      operation: crate::classify::Operation::Insert,
+      tables: tables
+        .iter()
+        .map(|(schema, table)| TableName::new(schema.map(Into::into), *table))
+        .collect(),
+      time_zone: None,
+    }
+  }
+
+  #[test]
+  fn checks_databases_before_tables() {
+    let filter = ReplicationFilter::new().do_db("pets").ignore_db("pets");
+    // ignore-db is not checked when there are do-db options.
+    assert!(filter.accepts_table("pets", "cats"));
+    assert!(!filter.accepts_table("zoo", "cats"));
+
+    let filter = ReplicationFilter::new()
+      .ignore_db("zoo")
+      .do_table("zoo", "cats");
+    assert!(!filter.accepts_table("zoo", "cats"));
+    assert!(!filter.accepts_table("pets", "cats"));
+  }
+
+  #[test]
+  fn checks_tables_in_order() {
+    let filter = ReplicationFilter::new()
+      .do_table("pets", "cats")
+      .ignore_table("pets", "cats")
+      .wild_do_table("pets.d%")
+      .wild_ignore_table("pets.%");
+    assert!(filter.accepts_table("pets", "cats"));
+    assert!(filter.accepts_table("pets", "dogs"));
+    assert!(!filter.accepts_table("pets", "birds"));
+    // Matches nothing, dropped because of the do options.
+    assert!(!filter.accepts_table("zoo", "cats"));
+
+    let filter = ReplicationFilter::new().wild_ignore_table("mysql.%");
+    assert!(!filter.accepts_table("mysql", "user"));
+    assert!(filter.accepts_table("pets", "cats"));
+  }
+
+  #[test]
+  fn checks_statements_against_the_default_database() {
+    let filter = ReplicationFilter::new().do_db("pets");
+    assert!(filter.accepts(&statement("pets", &[(Some("zoo"), "cats")])));
+    assert!(!filter.accepts(&statement("zoo", &[(Some("pets"), "cats")])));
+    assert!(!filter.accepts(&statement("", &[(Some("pets"), "cats")])));
+
+    let filter = ReplicationFilter::new()
+      .ignore_table("pets", "cats")
+      .wild_do_table("pets.%");
+    // The first table to match decides.
+    assert!(!filter.accepts(&statement("pets", &[(None, "cats"), (None, "dogs")])));
+    assert!(filter.accepts(&statement("pets", &[(None, "dogs"), (None, "cats")])));
+    assert!(!filter.accepts(&statement("zoo", &[(None, "dogs")])));
+    assert!(filter.accepts(&statement("zoo", &[])));
+  }
+
+  #[test]
+  fn ignores_operations_per_table() {
+    let filter = ReplicationFilter::new()
+      .wild_ignore_table("mysql.%")
+      .option("ignore-operation", "shop.%:update,DELETE")
+      .unwrap();
+    let insert = |schema: &str| ChangeEvent::Insert {
+      schema: schema.to_string(),
+      table: "orders".to_string(),
+      column_types: vec![],
+      rows: vec![],
+    };
+    let delete = |schema: &str| ChangeEvent::Delete {
+      schema: schema.to_string(),
+      table: "orders".to_string(),
+      column_types: vec![],
+      rows: vec![],
+    };
+    assert!(filter.accepts(&insert("shop")));
+    assert!(!filter.accepts(&delete("shop")));
+    assert!(filter.accepts(&delete("zoo")));
+    assert!(!filter.accepts(&insert("mysql")));
+
+    let mut update = statement("shop", &[(None, "orders")]);
+    if let ChangeEvent::Statement { operation, .. } = &mut update {
+      *operation = Operation::Update;
+    }
+    assert!(!filter.accepts(&update));
+    assert!(filter.accepts(&statement("shop", &[(None, "orders")])));
+
+    assert!(ReplicationFilter::new()
+      .option("ignore-operation", "shop.%:truncate")
+      .is_none());
+    assert!(ReplicationFilter::new()
+      .option("ignore-operation", "delete")
+      .is_none());
+  }
+
+  #[test]
+  fn matches_wild_patterns() {
+    assert!(like("pets.cats", "pets.%"));
+    assert!(like("Pets.Cats", "pets.c_ts"));
+    assert!(!like("pets.cats", "pets.c_t"));
+    assert!(like("shop_1.orders", "shop\\_%.orders"));
+    assert!(!like("shopx1.orders", "shop\\_%.orders"));
+  }
+
+  #[test]
+  fn parses_options() {
+    let filter = ReplicationFilter::new()
+      .option("replicate_do_table", "pets.cats")
+      .unwrap()
+      .option("replicate-wild-ignore-table", "mysql.%")
+      .unwrap();
+    assert!(filter.accepts_table("pets", "cats"));
+    assert!(!filter.accepts_table("pets", "dogs"));
+    assert!(ReplicationFilter::new()
+      .option("replicate-do-table", "cats")
+      .is_none());
+    assert!(ReplicationFilter::new()
+      .option("replicate-rewrite-db", "a->b")
+      .is_none());
+  }
+}
@@ -0,0 +1,182 @@
+// Retries of the errors that long running reads, e.g. snapshots or verifications, are expected to
+// hit now and then.
+//
+// Lock wait timeouts and deadlocks only roll back the statement (or the transaction, for
+// deadlocks) that failed, so the statement is sent again over the same connection, see
+// `ConnectionOptions::query_retry`. A lost connection ("MYSQL server has gone away", "Lost
+// connection to MYSQL server during query") can only be retried over a new connection, by whoever
+// knows how to open one, e.g. `verify::Verifier::retry`.
+//
+// Attempts are spaced by an exponential backoff. The delays are timers of their own thread, so
+// that retries work with any runtime.
+
+use super::conn::{DriverError, UpstreamError};
+use std::time::Duration;
+
+/// ER_LOCK_WAIT_TIMEOUT
+pub const LOCK_WAIT_TIMEOUT: u16 = 1205;
+/// ER_LOCK_DEADLOCK
+pub const LOCK_DEADLOCK: u16 = 1213;
+/// CR_SERVER_GONE_ERROR, returned by proxies that lost their backend connection.
+pub const SERVER_GONE_ERROR: u16 = 2006;
+/// CR_SERVER_LOST, returned by proxies that lost their backend connection.
+pub const SERVER_LOST: u16 = 2013;
+
+/// How a failure can be retried.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Retry {
+  /// The statement can be sent again over the same connection.
+  Statement,
+  /// The connection is unusable, the statement can be sent again over a new one.
+  Reconnect,
+  /// The failure is not transient.
+  Never,
+}
+
+impl Retry {
+  pub fn of(err: &DriverError) -> Self {
+    match err {
+      DriverError::UpstreamError(UpstreamError::ServerError { code, .. }) => match *code {
+        LOCK_WAIT_TIMEOUT | LOCK_DEADLOCK => Retry::Statement,
+        SERVER_GONE_ERROR | SERVER_LOST => Retry::Reconnect,
+        _ => Retry::Never,
+      },
+      DriverError::Io(_) | DriverError::ConnectionResetByPeer | DriverError::Timeout(..) => {
+        Retry::Reconnect
+      }
+      _ => Retry::Never,
+    }
+  }
+}
+
+/// Number of attempts and backoff between them. By default, 3 attempts spaced by 100ms, then
+/// 200ms.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+  max_attempts: u32,
+  initial_backoff: Duration,
+  max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+  fn default() -> Self {
+    Self {
+      max_attempts: 3,
+      initial_backoff: Duration::from_millis(100),
+      max_backoff: Duration::from_secs(10),
+    }
+  }
+}
+
+impl RetryPolicy {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Number of attempts, including the first one. 1 disables the retries.
+  pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+    self.max_attempts = max_attempts.max(1);
+    self
+  }
+
+  /// Delay before the first retry, doubled on every retry up to `max`.
+  pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+    self.initial_backoff = initial;
+    self.max_backoff = max.max(initial);
+    self
+  }
+
+  /// Whether another attempt follows attempt number `attempt` (starting at 1).
+  pub fn allows(&self, attempt: u32) -> bool {
+    attempt < self.max_attempts
+  }
+
+  /// Delay before the attempt that follows attempt number `attempt`.
+  pub fn backoff_after(&self, attempt: u32) -> Duration {
+    let exponent = attempt.saturating_sub(1).min(31);
+    self
+      .initial_backoff
+      .checked_mul(1 << exponent)
+      .map_or(self.max_backoff, |backoff| backoff.min(self.max_backoff))
+  }
+
+  // Waits before the attempt that follows attempt number `attempt`, warning about `err`.
+  pub(crate) async fn wait(&self, attempt: u32, err: &DriverError) {
+    let backoff = self.backoff_after(attempt);
+    eprintln!(
+      "warning: retrying in {:?} (attempt {} of {}) after: {}",
+      backoff,
+      attempt + 1,
+      self.max_attempts,
+      describe(err)
+    );
+    futures_timer::Delay::new(backoff).await;
+  }
+}
+
+// The message of server errors, rather than their generic description.
+fn describe(err: &DriverError) -> String {
+  match err {
+    DriverError::UpstreamError(err) => err.to_string(),
+    err => err.to_string(),
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use std::io;
+
+  fn server_error(code: u16) -> DriverError {
+    UpstreamError::ServerError {
+      code,
+      state: "HY000".to_string(),
+      message: "".to_string(),
+    }
+    .into()
+  }
+
+  #[test]
+  fn classifies_transient_errors() {
+    assert_eq!(
+      Retry::Statement,
+      Retry::of(&server_error(LOCK_WAIT_TIMEOUT))
+    );
+    assert_eq!(Retry::Statement, Retry::of(&server_error(LOCK_DEADLOCK)));
+    assert_eq!(Retry::Reconnect, Retry::of(&server_error(SERVER_LOST)));
+    assert_eq!(
+      Retry::Reconnect,
+      Retry::of(&DriverError::ConnectionResetByPeer)
+    );
+    assert_eq!(
+      Retry::Reconnect,
+      Retry::of(&io::Error::from(io::ErrorKind::BrokenPipe).into())
+    );
+    assert_eq!(
+      Retry::Reconnect,
+      Retry::of(&DriverError::Timeout(
+        "read a packet",
+        Duration::from_secs(1)
+      ))
+    );
+    // ER_DUP_ENTRY
+    assert_eq!(Retry::Never, Retry::of(&server_error(1062)));
+    assert_eq!(Retry::Never, Retry::of(&DriverError::UnexpectedPacket));
+  }
+
+  #[test]
+  fn backs_off_exponentially() {
+    let policy = RetryPolicy::new()
+      .max_attempts(5)
+      .backoff(Duration::from_millis(100), Duration::from_millis(500));
+
+    assert_eq!(Duration::from_millis(100), policy.backoff_after(1));
+    assert_eq!(Duration::from_millis(200), policy.backoff_after(2));
+    assert_eq!(Duration::from_millis(400), policy.backoff_after(3));
+    assert_eq!(Duration::from_millis(500), policy.backoff_after(4));
+    assert_eq!(Duration::from_millis(500), policy.backoff_after(100));
+    assert!(policy.allows(4));
+    assert!(!policy.allows(5));
+    assert!(!RetryPolicy::new().max_attempts(0).allows(1));
+  }
+}
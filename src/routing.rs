@@ -0,0 +1,171 @@
+// Stable table identities across renames.
+//
+// Sinks name their topics and tables after the schema and table of the changes, which breaks
+// consumers when a table is renamed. `TableRouter` maps physical tables to logical names, and
+// moves those names along with the tables when they are renamed with RENAME TABLE or
+// ALTER TABLE ... RENAME, so that the changes of a renamed table keep flowing to the same place.
+
+use super::classify::{renames, TableName};
+use super::migration;
+use std::collections::HashMap;
+
+type QualifiedName = (String, String);
+
+/// A table renamed by a DDL statement, as observed in the binlog.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Rename {
+  from: QualifiedName,
+  to: QualifiedName,
+  logical: Option<QualifiedName>,
+}
+
+impl Rename {
+  pub fn from(&self) -> (&str, &str) {
+    (&self.from.0, &self.from.1)
+  }
+
+  pub fn to(&self) -> (&str, &str) {
+    (&self.to.0, &self.to.1)
+  }
+
+  /// Logical name the table was routed to, when it had one.
+  pub fn logical(&self) -> Option<(&str, &str)> {
+    self
+      .logical
+      .as_ref()
+      .map(|(schema, table)| (schema.as_str(), table.as_str()))
+  }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct TableRouter {
+  routes: HashMap<QualifiedName, QualifiedName>,
+  follow_renames: bool,
+  history: Vec<Rename>,
+}
+
+impl TableRouter {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Emits the changes of `schema`.`table` as changes of `logical_schema`.`logical_table`.
+  pub fn route(
+    mut self,
+    schema: impl Into<String>,
+    table: impl Into<String>,
+    logical_schema: impl Into<String>,
+    logical_table: impl Into<String>,
+  ) -> Self {
+    self.routes.insert(
+      (schema.into(), table.into()),
+      (logical_schema.into(), logical_table.into()),
+    );
+    self
+  }
+
+  /// Keeps the name a table had before being renamed, even when it was not routed explicitly.
+  /// Otherwise only the routed tables keep their logical names across renames.
+  pub fn follow_renames(mut self, enabled: bool) -> Self {
+    self.follow_renames = enabled;
+    self
+  }
+
+  /// Renames observed so far, oldest first.
+  pub fn history(&self) -> &[Rename] {
+    self.history.as_slice()
+  }
+
+  /// Logical name of `schema`.`table`, `None` when the table is not routed.
+  pub fn resolve(&self, schema: &str, table: &str) -> Option<(&str, &str)> {
+    self
+      .routes
+      .get(&(schema.to_string(), table.to_string()))
+      .map(|(schema, table)| (schema.as_str(), table.as_str()))
+  }
+
+  /// Moves the logical names along with the tables renamed by `sql`, a DDL statement executed
+  /// against `schema`. Returns true when tables were renamed.
+  pub fn observe_ddl(&mut self, schema: &str, sql: &str) -> bool {
+    let renames = match renames(sql) {
+      Some(renames) => renames,
+      None => return false,
+    };
+
+    // The cut-over of an online schema migration swaps the table with a copy of itself, which
+    // keeps the identity of the table: routes stay where they are.
+    let cut_over = migration::cut_over(sql).is_some();
+
+    let qualify = |table: TableName| {
+      let table = table.or_schema(schema);
+      let schema = table.schema_str().unwrap_or_default().to_string();
+      (schema, table.table_str().to_string())
+    };
+    // Renames are applied left to right, like MYSQL does, so that swaps go through a temporary
+    // name.
+    for (from, to) in renames {
+      let (from, to) = (qualify(from), qualify(to));
+      if cut_over {
+        let logical = self.routes.get(&from).cloned();
+        self.history.push(Rename { from, to, logical });
+        continue;
+      }
+
+      let logical = self.routes.remove(&from);
+      let moved = match logical {
+        Some(ref logical) => Some(logical.clone()),
+        None if self.follow_renames => Some(from.clone()),
+        None => None,
+      };
+      if let Some(moved) = moved {
+        self.routes.insert(to.clone(), moved);
+      }
+      self.history.push(Rename { from, to, logical });
+    }
+    true
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn keeps_routes_across_renames() {
+    let mut router = TableRouter::new().route("pets", "cats", "pets", "felines");
+    assert_eq!(Some(("pets", "felines")), router.resolve("pets", "cats"));
+
+    assert!(router.observe_ddl("pets", "RENAME TABLE cats TO kittens"));
+    assert_eq!(None, router.resolve("pets", "cats"));
+    assert_eq!(Some(("pets", "felines")), router.resolve("pets", "kittens"));
+
+    assert!(router.observe_ddl("zoo", "ALTER TABLE pets.kittens RENAME TO zoo.cats"));
+    assert_eq!(Some(("pets", "felines")), router.resolve("zoo", "cats"));
+
+    // Unrouted tables keep their names.
+    assert!(router.observe_ddl("pets", "RENAME TABLE dogs TO puppies"));
+    assert_eq!(None, router.resolve("pets", "puppies"));
+
+    assert!(!router.observe_ddl("pets", "DROP TABLE puppies"));
+    assert_eq!(3, router.history().len());
+    assert_eq!(("pets", "kittens"), router.history()[1].from());
+    assert_eq!(Some(("pets", "felines")), router.history()[1].logical());
+  }
+
+  #[test]
+  fn follows_renames() {
+    let mut router = TableRouter::new().follow_renames(true);
+    router.observe_ddl("pets", "RENAME TABLE cats TO kittens, kittens TO felines");
+    assert_eq!(Some(("pets", "cats")), router.resolve("pets", "felines"));
+    assert_eq!(None, router.resolve("pets", "kittens"));
+
+    // Swapped with a ghost table by gh-ost.
+    router.observe_ddl(
+      "pets",
+      "RENAME TABLE felines TO _felines_del, _felines_gho TO felines",
+    );
+    assert_eq!(None, router.resolve("pets", "_felines_del"));
+    assert_eq!(Some(("pets", "cats")), router.resolve("pets", "felines"));
+    assert_eq!(4, router.history().len());
+  }
+}
@@ -0,0 +1,50 @@
+use crate::conn::{timeout, Connection, ConnectionOptions, DriverError};
+use ::async_std::net::ToSocketAddrs;
+use std::convert::TryInto;
+
+/// TCP stream of async-std, which already implements the `futures::io` traits.
+pub type TcpStream = ::async_std::net::TcpStream;
+
+/// Establish a connection to MYSQL, using async-std.
+pub async fn connect<O>(opts: O) -> Result<Connection<TcpStream>, DriverError>
+where
+  O: TryInto<ConnectionOptions>,
+  DriverError: From<O::Error>,
+{
+  let opts = opts.try_into()?;
+  let connect_timeout = opts.connect_timeout_duration();
+  timeout("connect", connect_timeout, open(opts)).await
+}
+
+async fn open(opts: ConnectionOptions) -> Result<Connection<TcpStream>, DriverError> {
+  if opts.socket_path().is_some() {
+    return Err(DriverError::Unsupported(
+      "Unix domain sockets with async-std".to_string(),
+    ));
+  }
+  let addr = match opts.socket_addr() {
+    Ok(addr) => addr,
+    Err(domain) => (domain, opts.port())
+      .to_socket_addrs()
+      .await?
+      .next()
+      .ok_or_else(|| DriverError::UnreachableHost(domain.to_string()))?,
+  };
+
+  let stream = TcpStream::connect(&addr).await?;
+  if let Some(nodelay) = opts.tcp_nodelay_enabled() {
+    stream.set_nodelay(nodelay)?;
+  }
+  // async-std does not expose the buffer sizes nor the keepalives of its sockets.
+  if opts.socket_buffer_sizes() != (None, None) {
+    return Err(DriverError::Unsupported(
+      "Setting the socket buffer sizes with async-std".to_string(),
+    ));
+  }
+  if opts.tcp_keepalive_idle().is_some() {
+    return Err(DriverError::Unsupported(
+      "TCP keepalives with async-std".to_string(),
+    ));
+  }
+  Connection::with_tls_stream(stream, opts).await
+}
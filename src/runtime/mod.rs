@@ -0,0 +1,9 @@
+// `Connection` only needs a stream implementing the `futures::io` traits, everything else is
+// runtime-agnostic. The adapters below open that stream with a given runtime, and are enabled by
+// the `tokio-runtime` (default) and `async-std-runtime` features. Any other runtime can use
+// `Connection::with_stream` (or `Connection::with_tls_stream`) directly.
+
+#[cfg(feature = "async-std-runtime")]
+pub mod async_std;
+#[cfg(feature = "tokio-runtime")]
+pub mod tokio;
@@ -0,0 +1,150 @@
+use crate::conn::{timeout, Connection, ConnectionOptions, DriverError};
+use futures::io::{AsyncRead, AsyncWrite};
+use pin_project::pin_project;
+use std::convert::TryInto;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Stream of tokio, usable by `Connection`. A TCP stream, or a Unix domain socket with
+/// `ConnectionOptions::socket`.
+pub type Stream = Compat<Socket>;
+
+/// Former name of `Stream`, from before Unix domain sockets.
+#[deprecated(note = "renamed to `Stream`, which may be a Unix domain socket")]
+pub type TcpStream = Stream;
+
+#[derive(Debug)]
+pub enum Socket {
+  Tcp(::tokio::net::TcpStream),
+  #[cfg(unix)]
+  Unix(::tokio::net::UnixStream),
+}
+
+/// Establish a connection to MYSQL, using tokio.
+pub async fn connect<O>(opts: O) -> Result<Connection<Stream>, DriverError>
+where
+  O: TryInto<ConnectionOptions>,
+  DriverError: From<O::Error>,
+{
+  let opts = opts.try_into()?;
+  let connect_timeout = opts.connect_timeout_duration();
+  timeout("connect", connect_timeout, open(opts)).await
+}
+
+async fn open(opts: ConnectionOptions) -> Result<Connection<Stream>, DriverError> {
+  if let Some(path) = opts.socket_path() {
+    #[cfg(unix)]
+    {
+      let stream = ::tokio::net::UnixStream::connect(path).await?;
+      return Connection::with_tls_stream(Compat(Socket::Unix(stream)), opts).await;
+    }
+    #[cfg(not(unix))]
+    return Err(DriverError::Unsupported(
+      "Unix domain sockets on this platform".to_string(),
+    ));
+  }
+
+  let addr = match opts.socket_addr() {
+    Ok(addr) => addr,
+    Err(domain) => ::tokio::net::lookup_host((domain, opts.port()))
+      .await?
+      .next()
+      .ok_or_else(|| DriverError::UnreachableHost(domain.to_string()))?,
+  };
+
+  let stream = ::tokio::net::TcpStream::connect(&addr).await?;
+  if let Some(nodelay) = opts.tcp_nodelay_enabled() {
+    stream.set_nodelay(nodelay)?;
+  }
+  if let Some(idle) = opts.tcp_keepalive_idle() {
+    stream.set_keepalive(Some(idle))?;
+  }
+  let (recv_buffer_size, send_buffer_size) = opts.socket_buffer_sizes();
+  if let Some(size) = recv_buffer_size {
+    stream.set_recv_buffer_size(size)?;
+  }
+  if let Some(size) = send_buffer_size {
+    stream.set_send_buffer_size(size)?;
+  }
+  Connection::with_tls_stream(Compat(Socket::Tcp(stream)), opts).await
+}
+
+/// Implements the `futures::io` traits for a tokio stream.
+#[pin_project]
+#[derive(Debug)]
+pub struct Compat<T>(#[pin] T);
+
+impl<T> Compat<T> {
+  pub fn new(inner: T) -> Self {
+    Self(inner)
+  }
+
+  pub fn into_inner(self) -> T {
+    self.0
+  }
+}
+
+impl<T: ::tokio::io::AsyncRead> AsyncRead for Compat<T> {
+  fn poll_read(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &mut [u8],
+  ) -> Poll<io::Result<usize>> {
+    self.project().0.poll_read(cx, buf)
+  }
+}
+
+impl<T: ::tokio::io::AsyncWrite> AsyncWrite for Compat<T> {
+  fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+    self.project().0.poll_write(cx, buf)
+  }
+
+  fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+    self.project().0.poll_flush(cx)
+  }
+
+  fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+    self.project().0.poll_shutdown(cx)
+  }
+}
+
+impl ::tokio::io::AsyncRead for Socket {
+  fn poll_read(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &mut [u8],
+  ) -> Poll<io::Result<usize>> {
+    match self.get_mut() {
+      Socket::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+      #[cfg(unix)]
+      Socket::Unix(stream) => Pin::new(stream).poll_read(cx, buf),
+    }
+  }
+}
+
+impl ::tokio::io::AsyncWrite for Socket {
+  fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+    match self.get_mut() {
+      Socket::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+      #[cfg(unix)]
+      Socket::Unix(stream) => Pin::new(stream).poll_write(cx, buf),
+    }
+  }
+
+  fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+    match self.get_mut() {
+      Socket::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+      #[cfg(unix)]
+      Socket::Unix(stream) => Pin::new(stream).poll_flush(cx),
+    }
+  }
+
+  fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+    match self.get_mut() {
+      Socket::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+      #[cfg(unix)]
+      Socket::Unix(stream) => Pin::new(stream).poll_shutdown(cx),
+    }
+  }
+}
@@ -0,0 +1,221 @@
+// Sampling of the rows of high-volume tables, to feed analytics or monitoring without the full
+// stream of changes.
+//
+// Rows are either sampled 1-in-N by hashing their key columns, so that every change of a given row
+// is consistently kept or dropped (and the same rows are picked on every run), or filtered by a
+// predicate on one of their columns.
+
+use super::value::{Row, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+/// Predicate on the value of a column. Columns that are not part of the row image never match.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ColumnPredicate {
+  Equals(Value),
+  OneOf(Vec<Value>),
+  IsNull,
+  IsNotNull,
+}
+
+impl ColumnPredicate {
+  pub fn matches(&self, value: Option<&Value>) -> bool {
+    let value = match value {
+      Some(value) => value,
+      None => return false,
+    };
+
+    match self {
+      Self::Equals(expected) => value == expected,
+      Self::OneOf(expected) => expected.contains(value),
+      Self::IsNull => *value == Value::Null,
+      Self::IsNotNull => *value != Value::Null,
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SampleRule {
+  /// Keeps 1 row in `n`, picked from the SHA-256 digest of the `key` columns (e.g. the primary
+  /// key).
+  OneIn { n: u64, key: Vec<usize> },
+  /// Keeps the rows whose `column` matches `predicate`.
+  Matching {
+    column: usize,
+    predicate: ColumnPredicate,
+  },
+}
+
+impl SampleRule {
+  /// Samples 1 row in `n` by its first column, which usually is the primary key.
+  pub fn one_in(n: u64) -> Self {
+    Self::OneIn { n, key: vec![0] }
+  }
+
+  pub fn matching(column: usize, predicate: ColumnPredicate) -> Self {
+    Self::Matching { column, predicate }
+  }
+
+  /// Returns true when `row` is sampled in.
+  pub fn keep(&self, row: &Row) -> bool {
+    match self {
+      Self::OneIn { n, key } => *n <= 1 || key_hash(row, key).is_multiple_of(*n),
+      Self::Matching { column, predicate } => predicate.matches(row.get(*column)),
+    }
+  }
+}
+
+/// Per table sampling rules. Tables without a rule are not sampled.
+#[derive(Clone, Debug, Default)]
+pub struct Sampling {
+  tables: HashMap<(String, String), SampleRule>,
+}
+
+impl Sampling {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn table(
+    mut self,
+    schema: impl Into<String>,
+    table: impl Into<String>,
+    rule: SampleRule,
+  ) -> Self {
+    self.tables.insert((schema.into(), table.into()), rule);
+    self
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.tables.is_empty()
+  }
+
+  pub fn rule(&self, schema: &str, table: &str) -> Option<&SampleRule> {
+    self.tables.get(&(schema.to_string(), table.to_string()))
+  }
+}
+
+// First 8 bytes of the SHA-256 digest of the key columns, which does not depend on the platform or
+// on the version of Rust, unlike `std::hash`.
+fn key_hash(row: &Row, key: &[usize]) -> u64 {
+  let mut sha256 = Sha256::new();
+  for column in key {
+    match row.get(*column) {
+      None | Some(Value::Null) => sha256.update([0]),
+      Some(Value::Bytes(bytes)) | Some(Value::Truncated { prefix: bytes, .. }) => {
+        sha256.update([1]);
+        sha256.update((bytes.len() as u64).to_le_bytes());
+        sha256.update(bytes);
+      }
+      // Signed and unsigned columns hash the same way.
+      Some(Value::Int(i)) => {
+        sha256.update([2]);
+        sha256.update(i128::from(*i).to_le_bytes());
+      }
+      Some(Value::Uint(u)) => {
+        sha256.update([2]);
+        sha256.update(i128::from(*u).to_le_bytes());
+      }
+      Some(Value::Float(f)) => {
+        sha256.update([3]);
+        sha256.update(f.to_bits().to_le_bytes());
+      }
+      Some(Value::Date {
+        year,
+        month,
+        day,
+        hour,
+        minute,
+        second,
+        micro,
+      }) => {
+        sha256.update([4]);
+        sha256.update(year.to_le_bytes());
+        sha256.update([*month, *day, *hour, *minute, *second]);
+        sha256.update(micro.to_le_bytes());
+      }
+      Some(Value::Timestamp { seconds, micro }) => {
+        sha256.update([5]);
+        sha256.update(seconds.to_le_bytes());
+        sha256.update(micro.to_le_bytes());
+      }
+      Some(Value::Time {
+        negative,
+        days,
+        hours,
+        minutes,
+        seconds,
+        micros,
+      }) => {
+        sha256.update([6, *negative as u8]);
+        sha256.update(days.to_le_bytes());
+        sha256.update([*hours, *minutes, *seconds]);
+        sha256.update(micros.to_le_bytes());
+      }
+      Some(Value::Digest { sha256: digest, .. })
+      | Some(Value::Reference { sha256: digest, .. }) => {
+        sha256.update([7]);
+        sha256.update(digest);
+      }
+      // Rows are sampled before their columns are encrypted, see `EventDecoder`.
+      Some(Value::Encrypted { ciphertext, .. }) => {
+        sha256.update([8]);
+        sha256.update(ciphertext);
+      }
+    }
+  }
+
+  let mut hash = [0; 8];
+  hash.copy_from_slice(&sha256.finalize()[..8]);
+  u64::from_le_bytes(hash)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn cat(id: i64, name: &[u8]) -> Row {
+    Row::new(vec![
+      Some(Value::Int(id)),
+      Some(Value::Bytes(name.to_vec())),
+      Some(Value::Null),
+    ])
+  }
+
+  #[test]
+  fn samples_one_in_n_rows() {
+    let rule = SampleRule::one_in(4);
+    let kept: Vec<i64> = (0..1_000)
+      .filter(|id| rule.keep(&cat(*id, b"Charlie")))
+      .collect();
+    assert!((200..300).contains(&kept.len()), "kept {}", kept.len());
+
+    // Sampling only depends on the key.
+    assert!(kept
+      .iter()
+      .all(|id| rule.keep(&cat(*id, b"River")) && rule.keep(&cat(*id, b""))));
+    let unsigned = Row::new(vec![Some(Value::Uint(kept[0] as u64))]);
+    assert!(rule.keep(&unsigned));
+
+    assert!(SampleRule::one_in(1).keep(&cat(1, b"Charlie")));
+  }
+
+  #[test]
+  fn samples_matching_rows() {
+    let rule = SampleRule::matching(1, ColumnPredicate::Equals(Value::Bytes(b"River".to_vec())));
+    assert!(rule.keep(&cat(1, b"River")));
+    assert!(!rule.keep(&cat(1, b"Charlie")));
+
+    let rule = SampleRule::matching(
+      0,
+      ColumnPredicate::OneOf(vec![Value::Int(1), Value::Int(2)]),
+    );
+    assert!(rule.keep(&cat(2, b"Charlie")));
+    assert!(!rule.keep(&cat(3, b"Charlie")));
+
+    assert!(SampleRule::matching(2, ColumnPredicate::IsNull).keep(&cat(1, b"")));
+    // Missing from the row image.
+    assert!(!SampleRule::matching(3, ColumnPredicate::IsNotNull).keep(&cat(1, b"")));
+    assert!(!SampleRule::matching(3, ColumnPredicate::IsNull).keep(&cat(1, b"")));
+  }
+}
@@ -0,0 +1,184 @@
+// Session variables of the connections that read for a long time, e.g. to snapshot or verify
+// tables, set for the lifetime of a guard.
+//
+// The previous values are saved in user variables, server side, by the same statement that sets
+// the new ones: MYSQL assigns all of them or none, so a failure leaves the session as it was rather
+// than half configured, and values are restored with their own type. Restoring is a query, which
+// `Drop` can not send: a guard dropped without `SessionGuard::restore` (e.g. on an error, or a
+// cancelled future) defers it to the next query of the connection.
+//
+// The previous values are saved under fixed names, so guards of the same connection must not be
+// nested.
+
+use super::conn::{Connection, DriverError};
+use super::util::quote_literal;
+use futures::io::{AsyncRead, AsyncWrite};
+use std::ops::{Deref, DerefMut};
+
+type DriverResult<T> = Result<T, DriverError>;
+
+/// Session variables, and the values they are set to. Names are not quoted, they are expected to
+/// be the names of system variables rather than input.
+#[derive(Clone, Debug, Default)]
+pub struct SessionSettings {
+  // Names, and SQL literals.
+  variables: Vec<(String, String)>,
+}
+
+impl SessionSettings {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Settings of snapshot connections: an hour to write to a client busy processing the previous
+  /// rows (`net_write_timeout`), a day idle between reads (`wait_timeout`), and REPEATABLE READ,
+  /// the only isolation level of consistent snapshots. `transaction_isolation` requires MYSQL
+  /// 5.7.20 or later.
+  pub fn snapshot() -> Self {
+    Self::new()
+      .integer("net_write_timeout", 3600)
+      .integer("wait_timeout", 86400)
+      .string("transaction_isolation", "REPEATABLE-READ")
+  }
+
+  pub fn integer(self, name: &str, value: u64) -> Self {
+    self.set(name, value.to_string())
+  }
+
+  pub fn string(self, name: &str, value: &str) -> Self {
+    self.set(name, quote_literal(value))
+  }
+
+  fn set(mut self, name: &str, literal: String) -> Self {
+    self.variables.retain(|(variable, _)| variable != name);
+    self.variables.push((name.to_string(), literal));
+    self
+  }
+
+  /// Sets the variables on `conn` until the guard is restored.
+  pub async fn apply<'a, S: AsyncRead + AsyncWrite + Unpin>(
+    &self,
+    conn: &'a mut Connection<S>,
+  ) -> DriverResult<SessionGuard<'a, S>> {
+    let mut restore = Vec::new();
+    if !self.variables.is_empty() {
+      conn.query(self.apply_sql()).await?;
+      restore.push(self.restore_sql());
+    }
+    Ok(SessionGuard { conn, restore })
+  }
+
+  fn apply_sql(&self) -> String {
+    let assignments: Vec<String> = self
+      .variables
+      .iter()
+      .map(|(name, literal)| {
+        format!(
+          "{} = @@SESSION.{}, SESSION {} = {}",
+          saved(name),
+          name,
+          name,
+          literal
+        )
+      })
+      .collect();
+    format!("SET {}", assignments.join(", "))
+  }
+
+  fn restore_sql(&self) -> String {
+    let assignments: Vec<String> = self
+      .variables
+      .iter()
+      .map(|(name, _)| format!("SESSION {} = {}", name, saved(name)))
+      .collect();
+    format!("SET {}", assignments.join(", "))
+  }
+}
+
+// User variable of the previous value of `name`.
+fn saved(name: &str) -> String {
+  format!("@tail_mysql_saved_{}", name)
+}
+
+/// Connection with session variables set by `SessionSettings::apply`.
+pub struct SessionGuard<'a, S: AsyncRead + AsyncWrite + Unpin> {
+  conn: &'a mut Connection<S>,
+  // Statements that restore the session, in order. Empty once restored.
+  restore: Vec<String>,
+}
+
+impl<'a, S: AsyncRead + AsyncWrite + Unpin> SessionGuard<'a, S> {
+  /// Starts a read only transaction with a consistent snapshot, committed when the guard is
+  /// restored.
+  pub async fn start_snapshot(&mut self) -> DriverResult<()> {
+    self
+      .conn
+      .query("START TRANSACTION WITH CONSISTENT SNAPSHOT, READ ONLY")
+      .await?;
+    self.restore.insert(0, "COMMIT".to_string());
+    Ok(())
+  }
+
+  /// Restores the variables, after committing the snapshot if any.
+  pub async fn restore(mut self) -> DriverResult<()> {
+    while !self.restore.is_empty() {
+      let sql = self.restore.remove(0);
+      self.conn.query(sql).await?;
+    }
+    Ok(())
+  }
+
+  /// Leaves the variables set for the lifetime of the connection, e.g. when it is dedicated to the
+  /// snapshot.
+  pub fn keep(mut self) {
+    self.restore.clear();
+  }
+}
+
+impl<'a, S: AsyncRead + AsyncWrite + Unpin> Deref for SessionGuard<'a, S> {
+  type Target = Connection<S>;
+
+  fn deref(&self) -> &Self::Target {
+    self.conn
+  }
+}
+
+impl<'a, S: AsyncRead + AsyncWrite + Unpin> DerefMut for SessionGuard<'a, S> {
+  fn deref_mut(&mut self) -> &mut Self::Target {
+    self.conn
+  }
+}
+
+impl<'a, S: AsyncRead + AsyncWrite + Unpin> Drop for SessionGuard<'a, S> {
+  fn drop(&mut self) {
+    for sql in self.restore.drain(..) {
+      self.conn.defer_query(sql);
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn builds_apply_and_restore_statements() {
+    let settings = SessionSettings::new()
+      .integer("net_write_timeout", 60)
+      .string("transaction_isolation", "READ-COMMITTED")
+      .integer("net_write_timeout", 3600);
+
+    assert_eq!(
+      "SET @tail_mysql_saved_transaction_isolation = @@SESSION.transaction_isolation, \
+       SESSION transaction_isolation = 'READ-COMMITTED', \
+       @tail_mysql_saved_net_write_timeout = @@SESSION.net_write_timeout, \
+       SESSION net_write_timeout = 3600",
+      settings.apply_sql()
+    );
+    assert_eq!(
+      "SET SESSION transaction_isolation = @tail_mysql_saved_transaction_isolation, \
+       SESSION net_write_timeout = @tail_mysql_saved_net_write_timeout",
+      settings.restore_sql()
+    );
+  }
+}
@@ -0,0 +1,261 @@
+// Tamper-evident history of the changes, for compliance.
+//
+// Events are appended to a file as JSON lines, one record per event:
+//
+//   {"seq":0,"prev":"000…0","event":{…},"hash":"…","signature":"…"}
+//
+// `hash` is the hex SHA-256 of `{prev}\n{seq}\n{event}`, where `event` is the JSON envelope of the
+// event (see `tail_mysql::json`) serialized with its keys sorted, and `prev` the hash of the
+// previous record (64 zeros for the first one). Editing, removing or reordering records breaks the
+// chain, which `verify` detects. When a signing key is configured, `signature` is the hex ed25519
+// signature of the hash, so that the log can not be rewritten as a whole without the key either.
+
+use crate::event::ChangeEvent;
+use crate::json::{self, TypeMapping};
+use crate::util::hex;
+use ed25519_dalek::{Signature, Signer, Verifier};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+
+pub use ed25519_dalek::{SigningKey, VerifyingKey};
+
+const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+#[derive(Debug, thiserror::Error)]
+pub enum AuditSinkError {
+  #[error("IO error")]
+  Io(#[from] io::Error),
+  #[error("Malformed audit record on line {0}")]
+  Malformed(u64),
+  #[error("Audit record {0} does not chain to the previous record")]
+  BrokenChain(u64),
+  #[error("Audit record {0} has an invalid signature")]
+  InvalidSignature(u64),
+}
+
+type AuditSinkResult<T> = Result<T, AuditSinkError>;
+
+pub struct AuditSink {
+  file: File,
+  next_seq: u64,
+  prev: String,
+  signing_key: Option<SigningKey>,
+  type_mapping: TypeMapping,
+}
+
+impl AuditSink {
+  /// Opens the log at `path`, creating it when missing. Records are appended after the existing
+  /// ones, which must form a valid chain.
+  pub fn open(path: impl AsRef<Path>) -> AuditSinkResult<Self> {
+    let path = path.as_ref();
+    let (next_seq, prev) = if path.exists() {
+      chain_head(BufReader::new(File::open(path)?), None)?
+    } else {
+      (0, GENESIS.to_string())
+    };
+
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    Ok(Self {
+      file,
+      next_seq,
+      prev,
+      signing_key: None,
+      type_mapping: TypeMapping::default(),
+    })
+  }
+
+  /// Signs the records with `signing_key`.
+  pub fn signing_key(mut self, signing_key: SigningKey) -> Self {
+    self.signing_key = Some(signing_key);
+    self
+  }
+
+  /// Representations of the column values in the recorded envelopes.
+  pub fn type_mapping(mut self, type_mapping: TypeMapping) -> Self {
+    self.type_mapping = type_mapping;
+    self
+  }
+
+  /// Sequence number of the next record, i.e. the number of records in the log.
+  pub fn next_seq(&self) -> u64 {
+    self.next_seq
+  }
+
+  /// Appends a record of `event`. Records are buffered by the OS, call `sync` to make sure they
+  /// are on disk.
+  pub fn append(&mut self, event: &ChangeEvent) -> AuditSinkResult<()> {
+    let event = json::to_json_with(event, &self.type_mapping);
+    let hash = record_hash(&self.prev, self.next_seq, &event);
+    let mut record = json!({
+      "seq": self.next_seq,
+      "prev": self.prev,
+      "event": event,
+      "hash": hash,
+    });
+    if let Some(ref signing_key) = self.signing_key {
+      record["signature"] = hex(&signing_key.sign(hash.as_bytes()).to_bytes()).into();
+    }
+
+    let mut line = record.to_string();
+    line.push('\n');
+    // Written at once, so that a crash never leaves half a record behind the previous ones.
+    self.file.write_all(line.as_bytes())?;
+
+    self.next_seq += 1;
+    self.prev = hash;
+    Ok(())
+  }
+
+  /// Flushes the appended records to disk.
+  pub fn sync(&self) -> AuditSinkResult<()> {
+    self.file.sync_data()?;
+    Ok(())
+  }
+}
+
+/// Verifies the chain of the log at `path`, and the signatures of its records when
+/// `verifying_key` is set. Returns the number of records.
+pub fn verify(
+  path: impl AsRef<Path>,
+  verifying_key: Option<&VerifyingKey>,
+) -> AuditSinkResult<u64> {
+  let file = File::open(path)?;
+  let (count, _) = chain_head(BufReader::new(file), verifying_key)?;
+  Ok(count)
+}
+
+// Walks the records, and returns the sequence number and `prev` of the next record.
+fn chain_head(
+  reader: impl BufRead,
+  verifying_key: Option<&VerifyingKey>,
+) -> AuditSinkResult<(u64, String)> {
+  let mut seq = 0;
+  let mut prev = GENESIS.to_string();
+
+  for (i, line) in reader.lines().enumerate() {
+    let line = line?;
+    let malformed = || AuditSinkError::Malformed(i as u64 + 1);
+    let record: serde_json::Value = serde_json::from_str(&line).map_err(|_| malformed())?;
+
+    let hash = record["hash"].as_str().ok_or_else(malformed)?;
+    let chained = record["seq"].as_u64() == Some(seq)
+      && record["prev"].as_str() == Some(prev.as_str())
+      && record_hash(&prev, seq, &record["event"]) == hash;
+    if !chained {
+      return Err(AuditSinkError::BrokenChain(seq));
+    }
+
+    if let Some(verifying_key) = verifying_key {
+      let signature = record["signature"]
+        .as_str()
+        .and_then(unhex)
+        .and_then(|bytes| Signature::from_slice(&bytes).ok())
+        .ok_or(AuditSinkError::InvalidSignature(seq))?;
+      verifying_key
+        .verify(hash.as_bytes(), &signature)
+        .map_err(|_| AuditSinkError::InvalidSignature(seq))?;
+    }
+
+    seq += 1;
+    prev = hash.to_string();
+  }
+
+  Ok((seq, prev))
+}
+
+fn record_hash(prev: &str, seq: u64, event: &serde_json::Value) -> String {
+  let mut sha256 = Sha256::new();
+  sha256.update(format!("{}\n{}\n{}", prev, seq, event).as_bytes());
+  hex(&sha256.finalize())
+}
+
+fn unhex(s: &str) -> Option<Vec<u8>> {
+  if !s.len().is_multiple_of(2) {
+    return None;
+  }
+  (0..s.len())
+    .step_by(2)
+    .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+    .collect()
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::protocol::ColumnType;
+  use crate::value::{Row, Value};
+  use std::fs;
+
+  fn insert(id: i64) -> ChangeEvent {
+    ChangeEvent::Insert {
+      schema: "pets".to_string(),
+      table: "cats".to_string(),
+      column_types: vec![ColumnType::MYSQL_TYPE_LONG],
+      rows: vec![Row::new(vec![Some(Value::Int(id))])],
+    }
+  }
+
+  fn path(name: &str) -> std::path::PathBuf {
+    let path =
+      std::env::temp_dir().join(format!("tail_mysql_audit_{}_{}", name, std::process::id()));
+    let _ = fs::remove_file(&path);
+    path
+  }
+
+  #[test]
+  fn chains_records_across_reopens() {
+    let path = path("chain");
+    let mut sink = AuditSink::open(&path).unwrap();
+    sink.append(&insert(1)).unwrap();
+    sink.append(&insert(2)).unwrap();
+    sink.sync().unwrap();
+
+    let mut sink = AuditSink::open(&path).unwrap();
+    assert_eq!(2, sink.next_seq());
+    sink.append(&insert(3)).unwrap();
+    assert_eq!(3, verify(&path, None).unwrap());
+
+    // Tampering with a record breaks the chain.
+    let log = fs::read_to_string(&path).unwrap();
+    fs::write(&path, log.replacen("cats", "dogs", 1)).unwrap();
+    match verify(&path, None) {
+      Err(AuditSinkError::BrokenChain(0)) => {}
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+
+    // So does dropping one.
+    let lines: Vec<&str> = log.lines().skip(1).collect();
+    fs::write(&path, lines.join("\n")).unwrap();
+    assert!(verify(&path, None).is_err());
+    assert!(AuditSink::open(&path).is_err());
+    fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn signs_records() {
+    let path = path("signed");
+    let signing_key = SigningKey::from_bytes(&[7; 32]);
+    let mut sink = AuditSink::open(&path)
+      .unwrap()
+      .signing_key(signing_key.clone());
+    sink.append(&insert(1)).unwrap();
+    sink
+      .append(&ChangeEvent::Begin(Default::default()))
+      .unwrap();
+
+    assert_eq!(
+      2,
+      verify(&path, Some(&signing_key.verifying_key())).unwrap()
+    );
+
+    let other_key = SigningKey::from_bytes(&[8; 32]).verifying_key();
+    match verify(&path, Some(&other_key)) {
+      Err(AuditSinkError::InvalidSignature(0)) => {}
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+    fs::remove_file(&path).unwrap();
+  }
+}
@@ -0,0 +1,187 @@
+// Object storage for column values too large to be sent inline, see `OversizedValue::Offload`.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Distinguishes the temporary files of concurrent writers within the process.
+static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Stores offloaded values, and returns the URL they can be retrieved from.
+///
+/// Called synchronously while decoding rows images, implementations backed by a remote store
+/// should keep a connection pool around rather than connecting on every call.
+pub trait BlobStore: fmt::Debug + Send + Sync {
+  /// Stores `bytes` under `key`. Keys are content addressed, storing the same key twice must be
+  /// a no-op.
+  fn put(&self, key: &str, bytes: &[u8]) -> io::Result<String>;
+}
+
+/// Stores blobs as files under a local directory (or a mounted bucket), and returns `file://`
+/// URLs.
+///
+/// Each `/` separated segment of the key is a directory, percent-encoded so that the schema and
+/// table names the keys are made of can never name a path outside of the root.
+#[derive(Debug)]
+pub struct FileBlobStore {
+  root: PathBuf,
+}
+
+impl FileBlobStore {
+  pub fn new(root: impl Into<PathBuf>) -> Self {
+    let root = root.into();
+    Self { root }
+  }
+}
+
+impl BlobStore for FileBlobStore {
+  fn put(&self, key: &str, bytes: &[u8]) -> io::Result<String> {
+    let mut path = self.root.clone();
+    for segment in key.split('/') {
+      if segment.is_empty() {
+        return Err(io::Error::new(
+          io::ErrorKind::InvalidInput,
+          format!("empty segment in blob key {:?}", key),
+        ));
+      }
+      path.push(encode_segment(segment));
+    }
+
+    if !path.exists() {
+      if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+      }
+
+      // Written to a temporary file first, so that readers never observe a partial blob. Named
+      // after the writer, concurrent writers of the same key each rename their own complete copy.
+      let tmp = path.with_file_name(format!(
+        ".{}.{}.{}.tmp",
+        path.file_name().unwrap().to_string_lossy(),
+        process::id(),
+        TMP_COUNTER.fetch_add(1, Ordering::Relaxed),
+      ));
+      if let Err(err) = fs::write(&tmp, bytes).and_then(|_| fs::rename(&tmp, &path)) {
+        let _ = fs::remove_file(&tmp);
+        return Err(err);
+      }
+    }
+
+    Ok(format!("file://{}", path.display()))
+  }
+}
+
+// Percent-encodes everything but ASCII alphanumerics, `-` and `_`: `.` included, so that no
+// segment is `.` or `..`, and no segment starts with the `.` of the temporary files.
+fn encode_segment(segment: &str) -> String {
+  let mut encoded = String::with_capacity(segment.len());
+  for byte in segment.bytes() {
+    if byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_' {
+      encoded.push(byte as char);
+    } else {
+      encoded.push_str(&format!("%{:02X}", byte));
+    }
+  }
+  encoded
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use std::path::Path;
+  use std::sync::Arc;
+  use std::thread;
+
+  fn root(name: &str) -> PathBuf {
+    let path =
+      std::env::temp_dir().join(format!("tail_mysql_blob_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&path);
+    path
+  }
+
+  fn path(url: &str) -> &Path {
+    Path::new(url.strip_prefix("file://").unwrap())
+  }
+
+  #[test]
+  fn stores_blobs_under_the_key() {
+    let root = root("key");
+    let store = FileBlobStore::new(&root);
+
+    let url = store.put("shop/cats/00ff", b"meow").unwrap();
+    assert_eq!(root.join("shop").join("cats").join("00ff"), path(&url));
+    assert_eq!(b"meow".to_vec(), fs::read(path(&url)).unwrap());
+    assert_eq!(url, store.put("shop/cats/00ff", b"meow").unwrap());
+
+    fs::remove_dir_all(&root).unwrap();
+  }
+
+  #[test]
+  fn keeps_traversal_names_under_the_root() {
+    let root = root("traversal");
+    let store = FileBlobStore::new(root.join("blobs"));
+
+    for key in &[
+      "../../cats/00ff",
+      "shop/../../00ff",
+      "./../cats/00ff",
+      "sh\\op/c.ts/00ff",
+    ] {
+      let url = store.put(key, b"meow").unwrap();
+      assert!(
+        path(&url).starts_with(root.join("blobs")),
+        "{} escaped to {}",
+        key,
+        url
+      );
+      let segments = path(&url)
+        .strip_prefix(root.join("blobs"))
+        .unwrap()
+        .components()
+        .count();
+      assert_eq!(key.split('/').count(), segments);
+    }
+    assert_eq!(
+      root
+        .join("blobs")
+        .join("%2E%2E")
+        .join("%2E%2E")
+        .join("cats")
+        .join("00ff"),
+      path(&store.put("../../cats/00ff", b"meow").unwrap())
+    );
+    assert!(store.put("/etc/00ff", b"meow").is_err());
+    assert!(store.put("shop//00ff", b"meow").is_err());
+
+    fs::remove_dir_all(&root).unwrap();
+  }
+
+  #[test]
+  fn stores_concurrent_puts_of_a_key() {
+    let root = root("concurrent");
+    let store = Arc::new(FileBlobStore::new(&root));
+    let bytes = vec![7; 1 << 20];
+
+    let writers = (0..8)
+      .map(|_| {
+        let store = store.clone();
+        let bytes = bytes.clone();
+        thread::spawn(move || store.put("shop/cats/00ff", &bytes).unwrap())
+      })
+      .collect::<Vec<_>>();
+    for writer in writers {
+      let url = writer.join().unwrap();
+      assert_eq!(bytes, fs::read(path(&url)).unwrap());
+    }
+
+    // Only the blob is left, no temporary file.
+    let entries = fs::read_dir(root.join("shop").join("cats"))
+      .unwrap()
+      .count();
+    assert_eq!(1, entries);
+
+    fs::remove_dir_all(&root).unwrap();
+  }
+}
@@ -0,0 +1,173 @@
+// Compression of the serialized payloads of sinks. JSON envelopes repeat their field names, schema
+// and table in every change, and compress several times over, which saves most of the egress of a
+// sink shipping them across regions or to a paid network.
+//
+// Payloads are compressed a batch at a time, joined by newlines (JSON lines), as one zstd frame or
+// gzip member: the larger the batch, the better the ratio. The `Content-Encoding` of the batch,
+// `zstd` or `gzip`, is the one of HTTP, for the sinks and consumers to agree on it.
+
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const DEFAULT_ZSTD_LEVEL: i32 = 3;
+const DEFAULT_GZIP_LEVEL: u32 = 6;
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum PayloadCompression {
+  /// zstd, at a level between 1 and 22.
+  Zstd(i32),
+  /// gzip, at a level between 0 and 9.
+  Gzip(u32),
+}
+
+impl PayloadCompression {
+  /// `Content-Encoding` of the compressed payloads.
+  pub fn encoding(self) -> &'static str {
+    match self {
+      PayloadCompression::Zstd(_) => "zstd",
+      PayloadCompression::Gzip(_) => "gzip",
+    }
+  }
+
+  /// Compresses `payloads`, each followed by a newline, and records the sizes in `metrics`.
+  pub fn compress<P: AsRef<[u8]>>(
+    self,
+    payloads: &[P],
+    metrics: &CompressionMetrics,
+  ) -> io::Result<Vec<u8>> {
+    let mut uncompressed = 0;
+    let compressed = match self {
+      PayloadCompression::Zstd(level) => {
+        let mut encoder = zstd::stream::Encoder::new(Vec::new(), level)?;
+        uncompressed = write_lines(&mut encoder, payloads)?;
+        encoder.finish()?
+      }
+      PayloadCompression::Gzip(level) => {
+        let level = flate2::Compression::new(level);
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), level);
+        uncompressed = write_lines(&mut encoder, payloads)?;
+        encoder.finish()?
+      }
+    };
+    metrics.record(uncompressed, compressed.len() as u64);
+    Ok(compressed)
+  }
+}
+
+// Returns the number of bytes written.
+fn write_lines<P: AsRef<[u8]>>(out: &mut impl Write, payloads: &[P]) -> io::Result<u64> {
+  let mut written = 0;
+  for payload in payloads {
+    out.write_all(payload.as_ref())?;
+    out.write_all(b"\n")?;
+    written += payload.as_ref().len() as u64 + 1;
+  }
+  Ok(written)
+}
+
+impl FromStr for PayloadCompression {
+  type Err = String;
+
+  /// Parses `zstd` or `gzip`, optionally followed by a level, e.g. `zstd:9`.
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (name, level) = match s.split_once(':') {
+      Some((name, level)) => (name, Some(level)),
+      None => (s, None),
+    };
+    let invalid_level = || format!("invalid compression level in `{}`", s);
+    match name.to_ascii_lowercase().as_str() {
+      "zstd" => match level.map(str::parse) {
+        None => Ok(PayloadCompression::Zstd(DEFAULT_ZSTD_LEVEL)),
+        Some(Ok(level @ 1..=22)) => Ok(PayloadCompression::Zstd(level)),
+        Some(_) => Err(invalid_level()),
+      },
+      "gzip" => match level.map(str::parse) {
+        None => Ok(PayloadCompression::Gzip(DEFAULT_GZIP_LEVEL)),
+        Some(Ok(level @ 0..=9)) => Ok(PayloadCompression::Gzip(level)),
+        Some(_) => Err(invalid_level()),
+      },
+      _ => Err(format!(
+        "unknown compression `{}`, expected zstd or gzip",
+        name
+      )),
+    }
+  }
+}
+
+/// Sizes of the payloads before and after compression.
+///
+/// Cheap to share between tasks through an `Arc`, every counter is a relaxed atomic.
+#[derive(Debug, Default)]
+pub struct CompressionMetrics {
+  uncompressed_bytes: AtomicU64,
+  compressed_bytes: AtomicU64,
+}
+
+impl CompressionMetrics {
+  pub fn uncompressed_bytes(&self) -> u64 {
+    self.uncompressed_bytes.load(Ordering::Relaxed)
+  }
+
+  pub fn compressed_bytes(&self) -> u64 {
+    self.compressed_bytes.load(Ordering::Relaxed)
+  }
+
+  /// Uncompressed size over compressed size, e.g. 8.0 when the payloads shrank 8 times. 1.0
+  /// before anything was compressed.
+  pub fn ratio(&self) -> f64 {
+    match self.compressed_bytes() {
+      0 => 1.0,
+      compressed => self.uncompressed_bytes() as f64 / compressed as f64,
+    }
+  }
+
+  fn record(&self, uncompressed: u64, compressed: u64) {
+    self
+      .uncompressed_bytes
+      .fetch_add(uncompressed, Ordering::Relaxed);
+    self
+      .compressed_bytes
+      .fetch_add(compressed, Ordering::Relaxed);
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use std::io::Read;
+
+  #[test]
+  fn compresses_batches_of_json_lines() {
+    let payload = r#"{"type":"insert","schema":"pets","table":"cats","rows":[{"0":1}]}"#;
+    let payloads = vec![payload; 100];
+
+    let metrics = CompressionMetrics::default();
+    let zstd = PayloadCompression::Zstd(3)
+      .compress(&payloads, &metrics)
+      .unwrap();
+    let lines = zstd::stream::decode_all(&zstd[..]).unwrap();
+    assert_eq!(format!("{}\n", payload).repeat(100).as_bytes(), &lines[..]);
+    assert_eq!(lines.len() as u64, metrics.uncompressed_bytes());
+    assert!(metrics.ratio() > 10.0);
+
+    let gzip = PayloadCompression::Gzip(6)
+      .compress(&payloads, &metrics)
+      .unwrap();
+    let mut lines = String::new();
+    flate2::read::GzDecoder::new(&gzip[..])
+      .read_to_string(&mut lines)
+      .unwrap();
+    assert_eq!(100, lines.lines().count());
+    assert_eq!((zstd.len() + gzip.len()) as u64, metrics.compressed_bytes());
+  }
+
+  #[test]
+  fn parses_algorithms_and_levels() {
+    assert_eq!(Ok(PayloadCompression::Zstd(3)), "zstd".parse());
+    assert_eq!(Ok(PayloadCompression::Zstd(19)), "ZSTD:19".parse());
+    assert_eq!(Ok(PayloadCompression::Gzip(6)), "gzip".parse());
+    assert!("gzip:10".parse::<PayloadCompression>().is_err());
+    assert!("lz4".parse::<PayloadCompression>().is_err());
+  }
+}
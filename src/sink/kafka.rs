@@ -0,0 +1,478 @@
+// Publishes change events to Kafka, as their JSON envelope (see `tail_mysql::json`).
+//
+// Rows events go to the topic of their table, keyed by `schema.table`, so that the changes of a
+// table are all published to the same partition and consumed in binlog order. Statements go to the
+// topic of every table they touch. Transaction markers are only published when a transaction
+// topic is configured.
+//
+// With a checkpoint topic, the position after every transaction is published to it, keyed by the
+// name of the pipeline, and the topic doubles as the checkpoint store of the pipeline (see
+// `KafkaCheckpointStore`). When the producer sets `transactional.id`, every binlog transaction is
+// published as a Kafka transaction, with its checkpoint: consumers reading committed messages never
+// see changes whose checkpoint is not saved, and the other way around. The topic should be
+// compacted, only the last checkpoint of a pipeline matters.
+//
+// With idempotency keys (see `tail_mysql::idempotency`), the messages of the changes carry the key
+// of their change in their envelope and in an `idempotency-key` header, for consumers to drop the
+// messages published again after a crash or a re-drive without parsing them.
+//
+// Kafka compresses the batches of messages of a partition itself, so that consumers decompress
+// them transparently: `set_compression` configures the producer for it, rather than compressing
+// the payloads of the sink (see `tail_mysql::sink::compression`).
+
+#[cfg(feature = "sinks-compression")]
+use super::compression::PayloadCompression;
+use super::naming::{NameTemplate, Namer, NamingError};
+#[cfg(feature = "tokio-runtime")]
+use crate::checkpoint::{CheckpointError, CheckpointStore};
+use crate::event::ChangeEvent;
+use crate::idempotency::IdempotencyKeys;
+use crate::json::{self, TypeMapping};
+use crate::watermark::BinlogPosition;
+#[cfg(feature = "tokio-runtime")]
+use futures::future::{self, BoxFuture};
+use rdkafka::consumer::{BaseConsumer, Consumer};
+use rdkafka::error::KafkaError;
+use rdkafka::message::{Header, OwnedHeaders};
+use rdkafka::producer::{BaseRecord, DefaultProducerContext, Producer, ThreadedProducer};
+use rdkafka::{Message as _, Offset, TopicPartitionList};
+use std::time::{Duration, Instant};
+
+pub use rdkafka::ClientConfig;
+
+#[derive(Debug, thiserror::Error)]
+pub enum KafkaSinkError {
+  #[error("Failed to name the topic")]
+  Naming(#[from] NamingError),
+  #[error("Failed to publish to Kafka")]
+  Kafka(#[from] KafkaError),
+  #[error("Invalid checkpoint `{0}` in Kafka")]
+  Checkpoint(String),
+}
+
+const TRANSACTION_TIMEOUT: Duration = Duration::from_secs(30);
+const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+type KafkaSinkResult<T> = Result<T, KafkaSinkError>;
+
+pub struct KafkaSink {
+  producer: ThreadedProducer<DefaultProducerContext>,
+  topics: Namer,
+  transaction_topic: Option<String>,
+  // Topic, and key of the checkpoints.
+  checkpoint_topic: Option<(String, String)>,
+  type_mapping: TypeMapping,
+  idempotency_keys: Option<IdempotencyKeys>,
+  transactional: bool,
+  in_transaction: bool,
+}
+
+#[derive(Debug, PartialEq)]
+struct Message {
+  topic: String,
+  key: String,
+  payload: String,
+  idempotency_key: Option<String>,
+}
+
+impl KafkaSink {
+  /// Creates a sink publishing with a producer created from `config`, which must at least set
+  /// `bootstrap.servers`. Topics are named after the source tables with `topics`. With
+  /// `transactional.id`, binlog transactions are published as Kafka transactions.
+  pub fn new(config: &ClientConfig, topics: NameTemplate) -> KafkaSinkResult<Self> {
+    let producer: ThreadedProducer<DefaultProducerContext> = config.create()?;
+    let transactional = config.get("transactional.id").is_some();
+    if transactional {
+      producer.init_transactions(TRANSACTION_TIMEOUT)?;
+    }
+    Ok(Self {
+      producer,
+      topics: Namer::new(topics),
+      transaction_topic: None,
+      checkpoint_topic: None,
+      type_mapping: TypeMapping::default(),
+      idempotency_keys: None,
+      transactional,
+      in_transaction: false,
+    })
+  }
+
+  /// Publishes `ChangeEvent::Begin` and `ChangeEvent::End` to `topic`, keyed by GTID.
+  pub fn transaction_topic(mut self, topic: impl Into<String>) -> Self {
+    self.transaction_topic = Some(topic.into());
+    self
+  }
+
+  /// Publishes the position after every `ChangeEvent::End` to `topic`, keyed by `name`, which
+  /// tells apart the pipelines sharing the topic. See `KafkaCheckpointStore` to resume from it.
+  pub fn checkpoint_topic(mut self, topic: impl Into<String>, name: impl Into<String>) -> Self {
+    self.checkpoint_topic = Some((topic.into(), name.into()));
+    self
+  }
+
+  /// Keys the changes of `source` (see `IdempotencyKeys::new`), in their envelopes and in an
+  /// `idempotency-key` header. Needs the transaction markers.
+  pub fn idempotency_keys(mut self, source: impl Into<String>) -> Self {
+    self.idempotency_keys = Some(IdempotencyKeys::new(source));
+    self
+  }
+
+  /// Representations of the column values in the published envelopes.
+  pub fn type_mapping(mut self, type_mapping: TypeMapping) -> Self {
+    self.type_mapping = type_mapping;
+    self
+  }
+
+  /// Queues `event` for publishing. Messages are sent in the background, call `flush` to wait
+  /// until they are acknowledged. With `transactional.id`, the Kafka transaction is committed
+  /// once `ChangeEvent::End` is queued, which blocks until its messages are acknowledged.
+  pub fn send(&mut self, event: &ChangeEvent) -> KafkaSinkResult<()> {
+    let messages = self.messages(event)?;
+    if self.transactional && !self.in_transaction && !messages.is_empty() {
+      self.producer.begin_transaction()?;
+      self.in_transaction = true;
+    }
+    for message in messages {
+      let mut record = BaseRecord::to(&message.topic)
+        .key(&message.key)
+        .payload(&message.payload);
+      if let Some(ref key) = message.idempotency_key {
+        record = record.headers(OwnedHeaders::new().insert(Header {
+          key: IDEMPOTENCY_KEY_HEADER,
+          value: Some(key),
+        }));
+      }
+      self.producer.send(record).map_err(|(err, _)| err)?;
+    }
+    if let ChangeEvent::End(_) = event {
+      if self.in_transaction {
+        self.in_transaction = false;
+        self.producer.commit_transaction(TRANSACTION_TIMEOUT)?;
+      }
+    }
+    Ok(())
+  }
+
+  /// Blocks until every queued message is acknowledged, or `timeout` expires.
+  pub fn flush(&self, timeout: Duration) -> KafkaSinkResult<()> {
+    self.producer.flush(timeout)?;
+    Ok(())
+  }
+
+  fn messages(&mut self, event: &ChangeEvent) -> KafkaSinkResult<Vec<Message>> {
+    let idempotency_key = self
+      .idempotency_keys
+      .as_mut()
+      .and_then(|keys| keys.next(event));
+    let tables: Vec<(&str, &str)> = match event {
+      ChangeEvent::Insert { schema, table, .. }
+      | ChangeEvent::Update { schema, table, .. }
+      | ChangeEvent::Delete { schema, table, .. }
+      | ChangeEvent::SchemaDrift { schema, table, .. } => vec![(schema, table)],
+      ChangeEvent::Statement { schema, tables, .. } => tables
+        .iter()
+        .map(|table| (table.schema_str().unwrap_or(schema), table.table_str()))
+        .collect(),
+      ChangeEvent::Begin(transaction) | ChangeEvent::End(transaction) => {
+        let mut messages = Vec::new();
+        if let Some(ref topic) = self.transaction_topic {
+          messages.push(Message {
+            topic: topic.clone(),
+            key: transaction.gtid().unwrap_or_default().to_string(),
+            payload: json::to_string_with(event, &self.type_mapping),
+            idempotency_key: None,
+          });
+        }
+        if let (ChangeEvent::End(_), Some((topic, name)), Some(position)) =
+          (event, &self.checkpoint_topic, transaction.position())
+        {
+          messages.push(Message {
+            topic: topic.clone(),
+            key: name.clone(),
+            payload: position.to_string(),
+            idempotency_key: None,
+          });
+        }
+        return Ok(messages);
+      }
+      // Published with the transaction markers, for the consumers that check for gaps.
+      ChangeEvent::SourceRestart { .. } => {
+        let topic = match self.transaction_topic {
+          Some(ref topic) => topic.clone(),
+          None => return Ok(Vec::new()),
+        };
+        return Ok(vec![Message {
+          topic,
+          key: String::new(),
+          payload: json::to_string_with(event, &self.type_mapping),
+          idempotency_key: None,
+        }]);
+      }
+    };
+
+    let payload = match idempotency_key {
+      Some(ref key) => json::to_json_with_key(event, &self.type_mapping, key).to_string(),
+      None => json::to_string_with(event, &self.type_mapping),
+    };
+    let mut messages = Vec::with_capacity(tables.len());
+    for (schema, table) in tables {
+      messages.push(Message {
+        topic: self.topics.resolve(schema, table)?.to_string(),
+        key: format!("{}.{}", schema, table),
+        payload: payload.clone(),
+        idempotency_key: idempotency_key.clone(),
+      });
+    }
+    Ok(messages)
+  }
+}
+
+/// Has the producer created from `config` compress its batches of messages with `compression`.
+/// The sizes of the batches are in the statistics of librdkafka (`statistics.interval.ms`),
+/// `batchsize` of the topics.
+#[cfg(feature = "sinks-compression")]
+pub fn set_compression(config: &mut ClientConfig, compression: PayloadCompression) {
+  let level = match compression {
+    PayloadCompression::Zstd(level) => level.to_string(),
+    PayloadCompression::Gzip(level) => level.to_string(),
+  };
+  config
+    .set("compression.type", compression.encoding())
+    .set("compression.level", level);
+}
+
+/// Checkpoints of a pipeline kept in a topic, as `KafkaSink::checkpoint_topic` publishes them.
+pub struct KafkaCheckpointStore {
+  config: ClientConfig,
+  producer: ThreadedProducer<DefaultProducerContext>,
+  topic: String,
+  name: String,
+  timeout: Duration,
+}
+
+impl KafkaCheckpointStore {
+  /// Keeps the checkpoints of `name` in `topic`, with clients created from `config`, which must
+  /// at least set `bootstrap.servers`.
+  pub fn new(
+    config: &ClientConfig,
+    topic: impl Into<String>,
+    name: impl Into<String>,
+  ) -> KafkaSinkResult<Self> {
+    // Saved outside of the transactions of the sink.
+    let mut config = config.clone();
+    config.remove("transactional.id");
+    Ok(Self {
+      producer: config.create()?,
+      config,
+      topic: topic.into(),
+      name: name.into(),
+      timeout: TRANSACTION_TIMEOUT,
+    })
+  }
+
+  /// Reads the topic from the start of its partitions to their end, and returns the last
+  /// checkpoint of the pipeline in it. Blocks, as `KafkaSink::flush`.
+  pub fn load_checkpoint(&self) -> KafkaSinkResult<Option<BinlogPosition>> {
+    let mut config = self.config.clone();
+    config
+      .set("group.id", format!("{}.checkpoint", self.name))
+      .set("enable.auto.commit", "false")
+      .set("enable.partition.eof", "true")
+      .set("isolation.level", "read_committed");
+    let consumer: BaseConsumer = config.create()?;
+    let metadata = consumer.fetch_metadata(Some(&self.topic), self.timeout)?;
+    let mut assignment = TopicPartitionList::new();
+    for topic in metadata.topics() {
+      for partition in topic.partitions() {
+        let (low, high) = consumer.fetch_watermarks(&self.topic, partition.id(), self.timeout)?;
+        if low < high {
+          assignment.add_partition_offset(&self.topic, partition.id(), Offset::Offset(low))?;
+        }
+      }
+    }
+    let mut remaining = assignment.count();
+    consumer.assign(&assignment)?;
+
+    let deadline = Instant::now() + self.timeout;
+    let mut checkpoint = None;
+    while remaining > 0 {
+      let timeout = deadline.saturating_duration_since(Instant::now());
+      match consumer.poll(timeout) {
+        Some(Ok(message)) if message.key() == Some(self.name.as_bytes()) => {
+          checkpoint = message.payload().map(<[u8]>::to_vec);
+        }
+        Some(Ok(_)) => {}
+        Some(Err(KafkaError::PartitionEOF(_))) => remaining -= 1,
+        Some(Err(err)) => return Err(err.into()),
+        None => return Err(KafkaError::NoMessageReceived.into()),
+      }
+    }
+    checkpoint
+      .map(|checkpoint| {
+        let checkpoint = String::from_utf8_lossy(&checkpoint);
+        checkpoint
+          .parse()
+          .map_err(|_| KafkaSinkError::Checkpoint(checkpoint.into_owned()))
+      })
+      .transpose()
+  }
+
+  /// Publishes `position` as the checkpoint of the pipeline, and blocks until it is acknowledged.
+  pub fn save_checkpoint(&self, position: &BinlogPosition) -> KafkaSinkResult<()> {
+    let position = position.to_string();
+    let record = BaseRecord::to(&self.topic)
+      .key(&self.name)
+      .payload(&position);
+    self.producer.send(record).map_err(|(err, _)| err)?;
+    self.producer.flush(self.timeout)?;
+    Ok(())
+  }
+}
+
+impl std::fmt::Debug for KafkaCheckpointStore {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("KafkaCheckpointStore")
+      .field("topic", &self.topic)
+      .field("name", &self.name)
+      .finish()
+  }
+}
+
+// The clients of rdkafka block, the checkpoints are far enough apart to block the task.
+#[cfg(feature = "tokio-runtime")]
+impl CheckpointStore for KafkaCheckpointStore {
+  fn load(&self) -> BoxFuture<'_, Result<Option<BinlogPosition>, CheckpointError>> {
+    Box::pin(future::ready(self.load_checkpoint().map_err(Into::into)))
+  }
+
+  fn save<'a>(
+    &'a self,
+    position: &'a BinlogPosition,
+  ) -> BoxFuture<'a, Result<(), CheckpointError>> {
+    Box::pin(future::ready(
+      self.save_checkpoint(position).map_err(Into::into),
+    ))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::classify::{Operation, TableName};
+  use crate::event::TransactionMetadata;
+  use crate::protocol::ColumnType;
+  use crate::value::{Row, Value};
+
+  fn sink() -> KafkaSink {
+    // Creating a producer does not connect to the brokers.
+    let mut config = ClientConfig::new();
+    config
+      .set("bootstrap.servers", "127.0.0.1:9092")
+      .set("log_level", "0");
+    let topics = NameTemplate::parse("cdc.{schema}.{table}").unwrap();
+    KafkaSink::new(&config, topics).unwrap()
+  }
+
+  #[test]
+  fn routes_events_to_table_topics() {
+    let mut sink = sink();
+    let insert = ChangeEvent::Insert {
+      schema: "pets".to_string(),
+      table: "cats".to_string(),
+      column_types: vec![ColumnType::MYSQL_TYPE_LONG],
+      rows: vec![Row::new(vec![Some(Value::Int(1))])],
+    };
+    assert_eq!(
+      vec![Message {
+        topic: "cdc.pets.cats".to_string(),
+        key: "pets.cats".to_string(),
+        payload: json::to_string(&insert),
+        idempotency_key: None,
+      }],
+      sink.messages(&insert).unwrap()
+    );
+
+    let statement = ChangeEvent::Statement {
+      schema: "pets".to_string(),
+      sql: "DELETE cats, dogs FROM cats JOIN zoo.dogs".to_string(),
+      operation: Operation::Delete,
+      tables: vec![
+        TableName::new(None, "cats"),
+        TableName::new(Some("zoo".to_string()), "dogs"),
+      ],
+      time_zone: None,
+    };
+    let topics: Vec<String> = sink
+      .messages(&statement)
+      .unwrap()
+      .into_iter()
+      .map(|message| message.topic)
+      .collect();
+    assert_eq!(vec!["cdc.pets.cats", "cdc.zoo.dogs"], topics);
+  }
+
+  #[test]
+  fn publishes_transaction_markers_when_configured() {
+    let begin = ChangeEvent::Begin(Default::default());
+    assert!(sink().messages(&begin).unwrap().is_empty());
+
+    let mut sink = sink().transaction_topic("cdc.transactions");
+    let messages = sink.messages(&begin).unwrap();
+    assert_eq!(1, messages.len());
+    assert_eq!("cdc.transactions", messages[0].topic);
+  }
+
+  #[test]
+  fn keys_changes_when_configured() {
+    let mut sink = sink().idempotency_keys("shop");
+    let mut begin = TransactionMetadata::default();
+    begin.set_start_position(BinlogPosition::new("mysql-bin.000002", 4));
+    assert!(sink
+      .messages(&ChangeEvent::Begin(begin))
+      .unwrap()
+      .is_empty());
+    let insert = ChangeEvent::Insert {
+      schema: "pets".to_string(),
+      table: "cats".to_string(),
+      column_types: vec![ColumnType::MYSQL_TYPE_LONG],
+      rows: vec![Row::new(vec![Some(Value::Int(1))])],
+    };
+    let messages = sink.messages(&insert).unwrap();
+    assert_eq!(
+      Some("shop:mysql-bin.000002:4:0"),
+      messages[0].idempotency_key.as_deref()
+    );
+    let envelope: serde_json::Value = serde_json::from_str(&messages[0].payload).unwrap();
+    assert_eq!("shop:mysql-bin.000002:4:0", envelope["idempotency_key"]);
+  }
+
+  #[cfg(feature = "sinks-compression")]
+  #[test]
+  fn compresses_batches_when_configured() {
+    let mut config = ClientConfig::new();
+    set_compression(&mut config, PayloadCompression::Zstd(9));
+    assert_eq!(Some("zstd"), config.get("compression.type"));
+    assert_eq!(Some("9"), config.get("compression.level"));
+  }
+
+  #[test]
+  fn publishes_checkpoints_when_configured() {
+    let mut end = TransactionMetadata::default();
+    end.set_position(BinlogPosition::new("mysql-bin.000002", 120));
+    let end = ChangeEvent::End(end);
+    assert!(sink().messages(&end).unwrap().is_empty());
+
+    let mut sink = sink().checkpoint_topic("cdc.checkpoints", "shop");
+    assert_eq!(
+      vec![Message {
+        topic: "cdc.checkpoints".to_string(),
+        key: "shop".to_string(),
+        payload: "mysql-bin.000002:120".to_string(),
+        idempotency_key: None,
+      }],
+      sink.messages(&end).unwrap()
+    );
+    let begin = ChangeEvent::Begin(Default::default());
+    assert!(sink.messages(&begin).unwrap().is_empty());
+  }
+}
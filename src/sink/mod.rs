@@ -0,0 +1,12 @@
+// Everything shared by the sinks that events are pushed onto.
+
+#[cfg(feature = "sinks-audit")]
+pub mod audit;
+pub mod blob;
+#[cfg(feature = "sinks-compression")]
+pub mod compression;
+#[cfg(feature = "sinks-kafka")]
+pub mod kafka;
+#[cfg(feature = "sinks-mysql")]
+pub mod mysql;
+pub mod naming;
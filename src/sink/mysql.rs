@@ -0,0 +1,1132 @@
+// Replays change events onto another MYSQL server, e.g. to keep a copy of the tables.
+//
+// Transactions are applied in parallel over several connections, like the multi-threaded replicas
+// of MYSQL (MTS) with WRITESET dependency tracking: a transaction only waits for the earlier
+// transactions that write the same rows, identified by their table and primary key. The changes of
+// a row are therefore committed in the order of the binlog, while unrelated transactions commit in
+// any order. Statements, and the changes of tables without a primary key, wait for every earlier
+// transaction and hold back the later ones.
+//
+// Only primary keys are tracked: transactions conflicting on a secondary unique key or through a
+// foreign key may be applied out of order, use a single connection for such tables.
+//
+// Transactions are only known with `EventDecoder::emit_transaction_markers`, every change event
+// is applied in its own transaction otherwise. A transaction whose END marker never came, e.g.
+// once an event that failed to decode was skipped, is dropped when the next one begins, as
+// `TransactionGrouper` does, rather than applied in part.
+//
+// Target rows may not look like the source rows did, e.g. when events are replayed again after a
+// failure, or when the target is also written to (active-active). How such conflicts are resolved
+// is configured per table, see `ConflictPolicy`.
+//
+// DDL statements (see `EventDecoder::emit_ddl`) are passed through as is when enabled with
+// `MysqlSink::apply_ddl`, so that the target tables follow the schema of the source tables. They
+// wait for every earlier transaction, and hold back the later ones until they are applied.
+
+use crate::classify::Operation;
+use crate::conn::{Connection, DriverError, UpstreamError};
+use crate::event::ChangeEvent;
+use crate::metrics::Metrics;
+use crate::util::{hex, quote_ident, quote_literal};
+use crate::value::{Row, Value};
+use crate::verify::{column_names, primary_key};
+use crate::watermark::{BinlogPosition, Watermarks};
+use futures::future::LocalBoxFuture;
+use futures::io::{AsyncRead, AsyncWrite};
+use futures::stream::{FuturesUnordered, StreamExt};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+#[derive(Debug, thiserror::Error)]
+pub enum MysqlSinkError {
+  #[error("Failed to apply the changes")]
+  Driver(#[from] DriverError),
+  #[error("Table {0}.{1} does not exist on the target")]
+  UnknownTable(String, String),
+  #[error("Rows of {0}.{1} have more columns than the target table")]
+  ColumnMismatch(String, String),
+  #[error("Column {2} of {0}.{1} was not logged in full, and can not be replayed")]
+  LossyValue(String, String, usize),
+  #[error("Rows image of {0}.{1} is missing column {2}")]
+  MissingColumn(String, String, String),
+  #[error("Conflict with the target rows while running `{0}`")]
+  Conflict(String),
+}
+
+type MysqlSinkResult<T> = Result<T, MysqlSinkError>;
+
+// Duplicate entry for a unique key.
+const ER_DUP_ENTRY: u16 = 1062;
+
+/// How changes are applied when the target rows do not look like the source rows did, e.g. when
+/// an insert is replayed twice, or when a row is also written to the target.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub enum ConflictPolicy {
+  /// The change wins: inserts replace existing rows, updates write the whole row when its image
+  /// is complete (`binlog_row_image=FULL`). Changes to missing rows are skipped otherwise.
+  #[default]
+  Overwrite,
+  /// The target wins: inserts of existing rows, and changes to missing rows, are skipped.
+  Ignore,
+  /// Fails with `MysqlSinkError::Conflict` on inserts of existing rows, and changes to missing
+  /// rows.
+  Error,
+  /// The row with the highest `version` wins, e.g. an `updated_at` or a counter column. Changes
+  /// to older versions are skipped, as are changes to missing rows. The version must be in the
+  /// rows images.
+  LastWriteWins { version: String },
+}
+
+// Columns of a target table, the positions of its primary key and version among them, and how
+// conflicts are handled.
+#[derive(Debug)]
+struct TableInfo {
+  columns: Vec<String>,
+  primary_key: Vec<usize>,
+  policy: ConflictPolicy,
+  version: Option<usize>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Statement {
+  Execute(String),
+  /// A SELECT that must return a row, a conflict otherwise.
+  ExpectRow(String),
+}
+
+// Rows written by a transaction.
+#[derive(Debug, PartialEq)]
+enum WriteSet {
+  Rows(Vec<String>),
+  /// Any row, which conflicts with every other transaction.
+  All,
+}
+
+// Tracks which transactions write the same rows.
+#[derive(Debug, Default)]
+struct Dependencies {
+  // Last transaction writing a row, while it is in flight.
+  last_writers: HashMap<String, u64>,
+  in_flight: HashMap<u64, Vec<String>>,
+  barrier: Option<u64>,
+}
+
+impl Dependencies {
+  // Registers transaction `seq`, and returns the transactions in flight it must wait for.
+  fn register(&mut self, seq: u64, write_set: WriteSet) -> Vec<u64> {
+    let keys = match write_set {
+      WriteSet::All => {
+        let dependencies = self.in_flight.keys().copied().collect();
+        self.barrier = Some(seq);
+        self.in_flight.insert(seq, Vec::new());
+        return dependencies;
+      }
+      WriteSet::Rows(keys) => keys,
+    };
+
+    let mut dependencies: Vec<u64> = self.barrier.into_iter().collect();
+    for key in &keys {
+      if let Some(writer) = self.last_writers.insert(key.clone(), seq) {
+        if writer != seq && !dependencies.contains(&writer) {
+          dependencies.push(writer);
+        }
+      }
+    }
+    self.in_flight.insert(seq, keys);
+    dependencies
+  }
+
+  fn complete(&mut self, seq: u64) {
+    for key in self.in_flight.remove(&seq).unwrap_or_default() {
+      if self.last_writers.get(&key) == Some(&seq) {
+        self.last_writers.remove(&key);
+      }
+    }
+    if self.barrier == Some(seq) {
+      self.barrier = None;
+    }
+  }
+
+  fn is_in_flight(&self, seq: u64) -> bool {
+    self.in_flight.contains_key(&seq)
+  }
+}
+
+struct Applied<S> {
+  conn: Connection<S>,
+  seq: u64,
+  position: Option<BinlogPosition>,
+  result: MysqlSinkResult<()>,
+}
+
+pub struct MysqlSink<S> {
+  idle: Vec<Connection<S>>,
+  in_flight: FuturesUnordered<LocalBoxFuture<'static, Applied<S>>>,
+  dependencies: Dependencies,
+  tables: HashMap<(String, String), Arc<TableInfo>>,
+  conflict_policy: ConflictPolicy,
+  table_conflict_policies: HashMap<(String, String), ConflictPolicy>,
+  apply_ddl: bool,
+  watermarks: Option<Watermarks>,
+  metrics: Arc<Metrics>,
+  transaction: Option<Vec<ChangeEvent>>,
+  next_seq: u64,
+}
+
+impl<S> MysqlSink<S>
+where
+  S: AsyncRead + AsyncWrite + Unpin + 'static,
+{
+  /// Creates a sink applying up to one transaction at once per connection to the target. The
+  /// tables of the target must have the same columns, in the same order, as the source tables.
+  pub async fn new(mut connections: Vec<Connection<S>>) -> MysqlSinkResult<Self> {
+    for conn in &mut connections {
+      // TIMESTAMP values are replayed in UTC, see `Value::to_text`.
+      conn.query("SET time_zone = '+00:00'").await?;
+      // Lists the generated invisible primary keys of MYSQL 8.0.30+ (`my_row_id`) in
+      // information_schema, the row images have them. Older servers do not know the variable.
+      let _ = conn
+        .query("SET SESSION show_gipk_in_create_table_and_information_schema = ON")
+        .await;
+    }
+
+    Ok(Self {
+      idle: connections,
+      in_flight: FuturesUnordered::new(),
+      dependencies: Dependencies::default(),
+      tables: HashMap::new(),
+      conflict_policy: ConflictPolicy::default(),
+      table_conflict_policies: HashMap::new(),
+      apply_ddl: false,
+      watermarks: None,
+      metrics: Arc::new(Metrics::default()),
+      transaction: None,
+      next_seq: 0,
+    })
+  }
+
+  /// How conflicts are handled, `ConflictPolicy::Overwrite` by default.
+  pub fn conflict_policy(mut self, policy: ConflictPolicy) -> Self {
+    self.conflict_policy = policy;
+    self
+  }
+
+  /// Handles the conflicts of `schema`.`table` with `policy`, instead of the default policy.
+  pub fn table_conflict_policy(
+    mut self,
+    schema: impl Into<String>,
+    table: impl Into<String>,
+    policy: ConflictPolicy,
+  ) -> Self {
+    self
+      .table_conflict_policies
+      .insert((schema.into(), table.into()), policy);
+    self
+  }
+
+  /// Applies DDL statements to the target, which must run a version of MYSQL that understands
+  /// the DDL of the source. They are skipped by default.
+  pub fn apply_ddl(mut self, enabled: bool) -> Self {
+    self.apply_ddl = enabled;
+    self
+  }
+
+  /// Acks the position of every transaction once it is committed, see
+  /// `EventDecoder::watermarks`.
+  pub fn watermarks(mut self, watermarks: Watermarks) -> Self {
+    self.watermarks = Some(watermarks);
+    self
+  }
+
+  /// Counts the transactions dropped for lacking their END marker in `metrics`, e.g. those of the
+  /// decoder, see `Metrics::incomplete_transactions`.
+  pub fn metrics(mut self, metrics: Arc<Metrics>) -> Self {
+    self.metrics = metrics;
+    self
+  }
+
+  /// Queues `event` to be applied, once its transaction ends. Waits for earlier transactions
+  /// when every connection is busy, or when they write the same rows. The sink can not be used
+  /// after an error.
+  ///
+  /// Takes the event, since it is buffered until its transaction ends.
+  pub async fn send(&mut self, event: ChangeEvent) -> MysqlSinkResult<()> {
+    match event {
+      ChangeEvent::Begin(_) => {
+        if self.transaction.replace(Vec::new()).is_some() {
+          self.metrics.incr_incomplete_transactions();
+        }
+        Ok(())
+      }
+      ChangeEvent::End(transaction) => {
+        let events = self.transaction.take().unwrap_or_default();
+        self.dispatch(events, transaction.position().cloned()).await
+      }
+      _ => match self.transaction {
+        Some(ref mut events) => {
+          events.push(event);
+          Ok(())
+        }
+        None => self.dispatch(vec![event], None).await,
+      },
+    }
+  }
+
+  /// Waits until every queued transaction is committed.
+  pub async fn flush(&mut self) -> MysqlSinkResult<()> {
+    while !self.in_flight.is_empty() {
+      self.complete_next().await?;
+    }
+    Ok(())
+  }
+
+  async fn dispatch(
+    &mut self,
+    events: Vec<ChangeEvent>,
+    position: Option<BinlogPosition>,
+  ) -> MysqlSinkResult<()> {
+    while self.idle.is_empty() {
+      self.complete_next().await?;
+    }
+    let mut conn = self.idle.pop().expect("no idle connection");
+
+    let prepared = self.prepare(&mut conn, &events).await;
+    let (statements, write_set) = match prepared {
+      Ok(prepared) => prepared,
+      Err(err) => {
+        self.idle.push(conn);
+        return Err(err);
+      }
+    };
+    let ddl = events.iter().any(is_ddl);
+    if statements.is_empty() {
+      self.idle.push(conn);
+      self.ack(position.as_ref());
+      if ddl {
+        // Skipped, the target tables may still have been changed in step.
+        self.tables.clear();
+      }
+      return Ok(());
+    }
+
+    let seq = self.next_seq;
+    self.next_seq += 1;
+    let dependencies = self.dependencies.register(seq, write_set);
+    while dependencies
+      .iter()
+      .any(|dependency| self.dependencies.is_in_flight(*dependency))
+    {
+      self.complete_next().await?;
+    }
+
+    self
+      .in_flight
+      .push(Box::pin(apply(conn, seq, position, statements)));
+    if ddl {
+      // The columns of the next changes are looked up once the DDL is applied.
+      self.flush().await?;
+      self.tables.clear();
+    }
+    Ok(())
+  }
+
+  async fn complete_next(&mut self) -> MysqlSinkResult<()> {
+    let applied = match self.in_flight.next().await {
+      Some(applied) => applied,
+      None => return Ok(()),
+    };
+    self.dependencies.complete(applied.seq);
+    self.idle.push(applied.conn);
+    applied.result?;
+    self.ack(applied.position.as_ref());
+    Ok(())
+  }
+
+  fn ack(&self, position: Option<&BinlogPosition>) {
+    if let (Some(watermarks), Some(position)) = (&self.watermarks, position) {
+      watermarks.ack(position);
+    }
+  }
+
+  async fn prepare(
+    &mut self,
+    conn: &mut Connection<S>,
+    events: &[ChangeEvent],
+  ) -> MysqlSinkResult<(Vec<Statement>, WriteSet)> {
+    let mut statements = Vec::new();
+    let mut keys = Vec::new();
+    let mut conflicts_with_all = false;
+
+    for event in events {
+      let (schema, table) = match event {
+        ChangeEvent::Insert { schema, table, .. }
+        | ChangeEvent::Update { schema, table, .. }
+        | ChangeEvent::Delete { schema, table, .. } => (schema, table),
+        ChangeEvent::Statement { .. } => {
+          if self.apply_ddl || !is_ddl(event) {
+            statements.extend(event_statements(None, event)?.0);
+            conflicts_with_all = true;
+          }
+          continue;
+        }
+        ChangeEvent::SchemaDrift { schema, table, .. } => {
+          self.tables.remove(&(schema.clone(), table.clone()));
+          continue;
+        }
+        ChangeEvent::Begin(_) | ChangeEvent::End(_) | ChangeEvent::SourceRestart { .. } => continue,
+      };
+
+      let table = self
+        .table_info(conn, schema, table, row_width(event))
+        .await?;
+      let (event_statements, write_set) = event_statements(Some(&table), event)?;
+      statements.extend(event_statements);
+      match write_set {
+        WriteSet::Rows(event_keys) => keys.extend(event_keys),
+        WriteSet::All => conflicts_with_all = true,
+      }
+    }
+
+    let write_set = if conflicts_with_all {
+      WriteSet::All
+    } else {
+      WriteSet::Rows(keys)
+    };
+    Ok((statements, write_set))
+  }
+
+  async fn table_info(
+    &mut self,
+    conn: &mut Connection<S>,
+    schema: &str,
+    table: &str,
+    row_width: usize,
+  ) -> MysqlSinkResult<Arc<TableInfo>> {
+    let name = (schema.to_string(), table.to_string());
+    // Rows wider than the cached columns come after a column was added, e.g. INSTANT.
+    if let Some(info) = self.tables.get(&name) {
+      if row_width <= info.columns.len() {
+        return Ok(info.clone());
+      }
+    }
+
+    let columns = column_names(conn, schema, table).await?;
+    if columns.is_empty() {
+      return Err(MysqlSinkError::UnknownTable(name.0, name.1));
+    }
+    let primary_key = primary_key(conn, schema, table)
+      .await?
+      .iter()
+      .filter_map(|key| columns.iter().position(|column| column == key))
+      .collect();
+
+    let policy = self
+      .table_conflict_policies
+      .get(&name)
+      .unwrap_or(&self.conflict_policy)
+      .clone();
+    let version = match policy {
+      ConflictPolicy::LastWriteWins { ref version } => {
+        let position = columns.iter().position(|column| column == version);
+        if position.is_none() {
+          return Err(MysqlSinkError::MissingColumn(
+            name.0,
+            name.1,
+            version.clone(),
+          ));
+        }
+        position
+      }
+      _ => None,
+    };
+
+    let info = Arc::new(TableInfo {
+      columns,
+      primary_key,
+      policy,
+      version,
+    });
+    self.tables.insert(name, info.clone());
+    Ok(info)
+  }
+}
+
+fn is_ddl(event: &ChangeEvent) -> bool {
+  matches!(
+    event,
+    ChangeEvent::Statement {
+      operation: Operation::Ddl,
+      ..
+    }
+  )
+}
+
+async fn apply<S: AsyncRead + AsyncWrite + Unpin>(
+  mut conn: Connection<S>,
+  seq: u64,
+  position: Option<BinlogPosition>,
+  statements: Vec<Statement>,
+) -> Applied<S> {
+  let mut result = conn.query("BEGIN").await.map(drop).map_err(Into::into);
+  for statement in &statements {
+    if result.is_err() {
+      break;
+    }
+    result = execute(&mut conn, statement).await;
+  }
+  result = match result {
+    Ok(()) => conn.query("COMMIT").await.map(drop).map_err(Into::into),
+    Err(err) => {
+      let _ = conn.query("ROLLBACK").await;
+      Err(err)
+    }
+  };
+
+  Applied {
+    conn,
+    seq,
+    position,
+    result,
+  }
+}
+
+async fn execute<S: AsyncRead + AsyncWrite + Unpin>(
+  conn: &mut Connection<S>,
+  statement: &Statement,
+) -> MysqlSinkResult<()> {
+  match statement {
+    Statement::Execute(sql) => match conn.query(sql).await {
+      Ok(_) => Ok(()),
+      Err(DriverError::UpstreamError(UpstreamError::ServerError { code, .. }))
+        if code == ER_DUP_ENTRY =>
+      {
+        Err(MysqlSinkError::Conflict(sql.clone()))
+      }
+      Err(err) => Err(err.into()),
+    },
+    Statement::ExpectRow(sql) => match conn.query(sql).await?.first() {
+      Some(_) => Ok(()),
+      None => Err(MysqlSinkError::Conflict(sql.clone())),
+    },
+  }
+}
+
+// SQL statements replaying `event`, and the rows they write. `table` is `None` for statements.
+fn event_statements(
+  table: Option<&TableInfo>,
+  event: &ChangeEvent,
+) -> MysqlSinkResult<(Vec<Statement>, WriteSet)> {
+  let (schema, table_name) = match event {
+    ChangeEvent::Insert { schema, table, .. }
+    | ChangeEvent::Update { schema, table, .. }
+    | ChangeEvent::Delete { schema, table, .. } => (schema.as_str(), table.as_str()),
+    ChangeEvent::Statement {
+      schema,
+      sql,
+      time_zone,
+      ..
+    } => {
+      let mut statements = vec![format!("USE {}", quote_ident(schema))];
+      match time_zone {
+        Some(time_zone) => {
+          statements.push(format!("SET time_zone = {}", quote_literal(time_zone)));
+          statements.push(sql.clone());
+          statements.push("SET time_zone = '+00:00'".to_string());
+        }
+        None => statements.push(sql.clone()),
+      }
+      let statements = statements.into_iter().map(Statement::Execute).collect();
+      return Ok((statements, WriteSet::All));
+    }
+    ChangeEvent::Begin(_)
+    | ChangeEvent::End(_)
+    | ChangeEvent::SchemaDrift { .. }
+    | ChangeEvent::SourceRestart { .. } => return Ok((Vec::new(), WriteSet::Rows(vec![]))),
+  };
+  let table = table.expect("missing table info");
+  let writer = RowWriter {
+    schema,
+    table: table_name,
+    info: table,
+  };
+
+  let mut statements = Vec::new();
+  let mut keys = Vec::new();
+  match event {
+    ChangeEvent::Insert { rows, .. } => {
+      for row in rows {
+        statements.push(writer.insert(row)?);
+        keys.extend(writer.key(row)?);
+      }
+    }
+    ChangeEvent::Update { rows, .. } => {
+      for (before, after) in rows {
+        statements.extend(writer.update(before, after)?);
+        keys.extend(writer.key(before)?);
+        keys.extend(writer.key(after)?);
+      }
+    }
+    ChangeEvent::Delete { rows, .. } => {
+      for row in rows {
+        statements.extend(writer.delete(row)?);
+        keys.extend(writer.key(row)?);
+      }
+    }
+    ChangeEvent::Statement { .. }
+    | ChangeEvent::Begin(_)
+    | ChangeEvent::End(_)
+    | ChangeEvent::SchemaDrift { .. }
+    | ChangeEvent::SourceRestart { .. } => {}
+  }
+
+  let write_set = if table.primary_key.is_empty() {
+    WriteSet::All
+  } else {
+    WriteSet::Rows(keys)
+  };
+  Ok((statements, write_set))
+}
+
+// Number of columns of the widest row image of `event`.
+fn row_width(event: &ChangeEvent) -> usize {
+  let images: Box<dyn Iterator<Item = &Row>> = match event {
+    ChangeEvent::Insert { rows, .. } | ChangeEvent::Delete { rows, .. } => Box::new(rows.iter()),
+    ChangeEvent::Update { rows, .. } => Box::new(
+      rows
+        .iter()
+        .flat_map(|(before, after)| std::iter::once(before).chain(std::iter::once(after))),
+    ),
+    _ => return 0,
+  };
+  images
+    .map(|row| row.values().len())
+    .max()
+    .unwrap_or_default()
+}
+
+struct RowWriter<'a> {
+  schema: &'a str,
+  table: &'a str,
+  info: &'a TableInfo,
+}
+
+impl<'a> RowWriter<'a> {
+  fn insert(&self, row: &Row) -> MysqlSinkResult<Statement> {
+    let values = self.values(row)?;
+    let columns: Vec<String> = values
+      .iter()
+      .map(|(column, _)| quote_ident(column))
+      .collect();
+    let literals: Vec<&str> = values.iter().map(|(_, literal)| literal.as_str()).collect();
+    let verb = match self.info.policy {
+      ConflictPolicy::Overwrite => "REPLACE",
+      ConflictPolicy::Ignore => "INSERT IGNORE",
+      ConflictPolicy::Error | ConflictPolicy::LastWriteWins { .. } => "INSERT",
+    };
+    let mut sql = format!(
+      "{} INTO {} ({}) VALUES ({})",
+      verb,
+      self.name(),
+      columns.join(", "),
+      literals.join(", ")
+    );
+
+    if let Some(version) = self.version_column() {
+      self.version(&values)?;
+      let newer = format!("VALUES({0}) >= {0}", quote_ident(version));
+      // Assignments are evaluated in order, the version is only bumped once the other columns
+      // compared against it.
+      let assignments: Vec<String> = values
+        .iter()
+        .map(|(column, _)| *column)
+        .filter(|column| *column != version)
+        .chain(std::iter::once(version))
+        .map(|column| {
+          format!(
+            "{0} = IF({1}, VALUES({0}), {0})",
+            quote_ident(column),
+            newer
+          )
+        })
+        .collect();
+      sql.push_str(" ON DUPLICATE KEY UPDATE ");
+      sql.push_str(&assignments.join(", "));
+    }
+    Ok(Statement::Execute(sql))
+  }
+
+  fn update(&self, before: &Row, after: &Row) -> MysqlSinkResult<Vec<Statement>> {
+    let values = self.values(after)?;
+    if values.is_empty() {
+      return Ok(Vec::new());
+    }
+
+    // The after image of `binlog_row_image=FULL` holds the whole row, which is written even when
+    // the target lost it.
+    let complete = values.len() == self.info.columns.len();
+    if self.info.policy == ConflictPolicy::Overwrite
+      && complete
+      && !self.info.primary_key.is_empty()
+    {
+      let mut statements = Vec::new();
+      if self.key(before)? != self.key(after)? {
+        statements.extend(self.delete(before)?);
+      }
+      statements.push(self.insert(after)?);
+      return Ok(statements);
+    }
+
+    let mut statements = Vec::new();
+    let mut predicate = self.predicate(before)?;
+    match self.version_column() {
+      Some(version) => {
+        predicate = format!(
+          "{} AND {} <= {}",
+          predicate,
+          quote_ident(version),
+          self.version(&values)?
+        );
+      }
+      None if self.info.policy == ConflictPolicy::Error => {
+        statements.push(self.expect_row(&predicate));
+      }
+      None => {}
+    }
+
+    let assignments: Vec<String> = values
+      .iter()
+      .map(|(column, literal)| format!("{} = {}", quote_ident(column), literal))
+      .collect();
+    statements.push(Statement::Execute(format!(
+      "UPDATE {} SET {} WHERE {}",
+      self.name(),
+      assignments.join(", "),
+      self.limit(predicate)
+    )));
+    Ok(statements)
+  }
+
+  fn delete(&self, row: &Row) -> MysqlSinkResult<Vec<Statement>> {
+    let mut statements = Vec::new();
+    let mut predicate = self.predicate(row)?;
+    match self.version_column() {
+      Some(version) => {
+        predicate = format!(
+          "{} AND {} <= {}",
+          predicate,
+          quote_ident(version),
+          self.version(&self.values(row)?)?
+        );
+      }
+      None if self.info.policy == ConflictPolicy::Error => {
+        statements.push(self.expect_row(&predicate));
+      }
+      None => {}
+    }
+
+    statements.push(Statement::Execute(format!(
+      "DELETE FROM {} WHERE {}",
+      self.name(),
+      self.limit(predicate)
+    )));
+    Ok(statements)
+  }
+
+  fn name(&self) -> String {
+    format!("{}.{}", quote_ident(self.schema), quote_ident(self.table))
+  }
+
+  // Locks the row until the transaction commits, fails when it is missing.
+  fn expect_row(&self, predicate: &str) -> Statement {
+    Statement::ExpectRow(format!(
+      "SELECT 1 FROM {} WHERE {} FOR UPDATE",
+      self.name(),
+      self.limit(predicate.to_string())
+    ))
+  }
+
+  fn version_column(&self) -> Option<&'a str> {
+    match self.info.policy {
+      ConflictPolicy::LastWriteWins { .. } => {
+        self.info.version.map(|i| self.info.columns[i].as_str())
+      }
+      _ => None,
+    }
+  }
+
+  // Literal of the version column among `values`.
+  fn version<'b>(&self, values: &'b [(&'a str, String)]) -> MysqlSinkResult<&'b str> {
+    let version = self.version_column().unwrap_or_default();
+    values
+      .iter()
+      .find(|(column, _)| *column == version)
+      .map(|(_, literal)| literal.as_str())
+      .ok_or_else(|| {
+        MysqlSinkError::MissingColumn(
+          self.schema.to_string(),
+          self.table.to_string(),
+          version.to_string(),
+        )
+      })
+  }
+
+  // Columns of the row image, with their value as SQL literals.
+  fn values(&self, row: &Row) -> MysqlSinkResult<Vec<(&'a str, String)>> {
+    let values = row.values();
+    if values.len() > self.info.columns.len() {
+      return Err(MysqlSinkError::ColumnMismatch(
+        self.schema.to_string(),
+        self.table.to_string(),
+      ));
+    }
+
+    let mut literals = Vec::new();
+    for (i, value) in values.iter().enumerate() {
+      if let Some(value) = value {
+        let literal = literal(value).ok_or_else(|| {
+          MysqlSinkError::LossyValue(self.schema.to_string(), self.table.to_string(), i)
+        })?;
+        literals.push((self.info.columns[i].as_str(), literal));
+      }
+    }
+    Ok(literals)
+  }
+
+  // Identifies `row` by its primary key, or by every column of its image when the table has
+  // none.
+  fn predicate(&self, row: &Row) -> MysqlSinkResult<String> {
+    let values = self.values(row)?;
+    if self.info.primary_key.is_empty() {
+      let conditions: Vec<String> = values
+        .iter()
+        .map(|(column, literal)| format!("{} <=> {}", quote_ident(column), literal))
+        .collect();
+      return Ok(conditions.join(" AND "));
+    }
+
+    let conditions = self
+      .info
+      .primary_key
+      .iter()
+      .map(|i| {
+        let column = self.info.columns[*i].as_str();
+        values
+          .iter()
+          .find(|(name, _)| *name == column)
+          .map(|(_, literal)| format!("{} = {}", quote_ident(column), literal))
+          .ok_or_else(|| {
+            MysqlSinkError::MissingColumn(
+              self.schema.to_string(),
+              self.table.to_string(),
+              column.to_string(),
+            )
+          })
+      })
+      .collect::<MysqlSinkResult<Vec<String>>>()?;
+    Ok(conditions.join(" AND "))
+  }
+
+  // Rows of tables without a primary key may have duplicates, only one of them is changed.
+  fn limit(&self, predicate: String) -> String {
+    if self.info.primary_key.is_empty() {
+      format!("{} LIMIT 1", predicate)
+    } else {
+      predicate
+    }
+  }
+
+  // Key of the row in write sets, `None` for tables without a primary key. After images of
+  // `binlog_row_image=MINIMAL` only have the primary key when it changed.
+  fn key(&self, row: &Row) -> MysqlSinkResult<Option<String>> {
+    if self.info.primary_key.is_empty() {
+      return Ok(None);
+    }
+
+    let mut key = format!("{}.{}", self.schema, self.table);
+    for i in &self.info.primary_key {
+      match row.get(*i) {
+        Some(value) => {
+          let literal = literal(value).ok_or_else(|| {
+            MysqlSinkError::LossyValue(self.schema.to_string(), self.table.to_string(), *i)
+          })?;
+          key.push(':');
+          key.push_str(&literal);
+        }
+        None => return Ok(None),
+      }
+    }
+    Ok(Some(key))
+  }
+}
+
+// SQL literal of a value, `None` when it was not logged in full.
+fn literal(value: &Value) -> Option<String> {
+  match value {
+    Value::Null => Some("NULL".to_string()),
+    Value::Int(v) => Some(v.to_string()),
+    Value::Uint(v) => Some(v.to_string()),
+    Value::Float(v) => Some(v.to_string()),
+    Value::Bytes(bytes) => Some(format!("X'{}'", hex(bytes))),
+    Value::Date { .. } | Value::Time { .. } | Value::Timestamp { .. } => {
+      let text = value.to_text()?;
+      Some(quote_literal(&String::from_utf8_lossy(&text)))
+    }
+    Value::Truncated { .. }
+    | Value::Digest { .. }
+    | Value::Reference { .. }
+    | Value::Encrypted { .. } => None,
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::protocol::ColumnType;
+  use futures::executor::block_on;
+
+  fn cats(policy: ConflictPolicy) -> TableInfo {
+    TableInfo {
+      columns: vec!["id".into(), "name".into(), "version".into()],
+      primary_key: vec![0],
+      version: match policy {
+        ConflictPolicy::LastWriteWins { .. } => Some(2),
+        _ => None,
+      },
+      policy,
+    }
+  }
+
+  fn cat(id: Option<i64>, name: Option<&str>, version: Option<i64>) -> Row {
+    Row::new(vec![
+      id.map(Value::Int),
+      name.map(|name| Value::Bytes(name.as_bytes().to_vec())),
+      version.map(Value::Int),
+    ])
+  }
+
+  fn insert(row: Row) -> ChangeEvent {
+    ChangeEvent::Insert {
+      schema: "pets".to_string(),
+      table: "cats".to_string(),
+      column_types: vec![ColumnType::MYSQL_TYPE_LONG; 3],
+      rows: vec![row],
+    }
+  }
+
+  fn update(before: Row, after: Row) -> ChangeEvent {
+    ChangeEvent::Update {
+      schema: "pets".to_string(),
+      table: "cats".to_string(),
+      column_types: vec![ColumnType::MYSQL_TYPE_LONG; 3],
+      rows: vec![(before, after)],
+    }
+  }
+
+  fn delete(row: Row) -> ChangeEvent {
+    ChangeEvent::Delete {
+      schema: "pets".to_string(),
+      table: "cats".to_string(),
+      column_types: vec![ColumnType::MYSQL_TYPE_LONG; 3],
+      rows: vec![row],
+    }
+  }
+
+  fn sql(table: &TableInfo, event: &ChangeEvent) -> Vec<String> {
+    event_statements(Some(table), event)
+      .unwrap()
+      .0
+      .into_iter()
+      .map(|statement| match statement {
+        Statement::Execute(sql) => sql,
+        Statement::ExpectRow(sql) => format!("expect {}", sql),
+      })
+      .collect()
+  }
+
+  #[test]
+  fn builds_statements() {
+    let table = cats(ConflictPolicy::Overwrite);
+    let charlie = insert(Row::new(vec![
+      Some(Value::Int(1)),
+      Some(Value::Bytes(b"Charlie".to_vec())),
+      Some(Value::Null),
+    ]));
+    assert_eq!(
+      (
+        vec![Statement::Execute(
+          "REPLACE INTO `pets`.`cats` (`id`, `name`, `version`) VALUES (1, X'436861726c6965', NULL)"
+            .to_string()
+        )],
+        WriteSet::Rows(vec!["pets.cats:1".to_string()])
+      ),
+      event_statements(Some(&table), &charlie).unwrap()
+    );
+
+    // binlog_row_image=MINIMAL, the primary key changes.
+    let renumber = update(cat(Some(1), None, None), cat(Some(2), Some("C"), None));
+    assert_eq!(
+      (
+        vec![Statement::Execute(
+          "UPDATE `pets`.`cats` SET `id` = 2, `name` = X'43' WHERE `id` = 1".to_string()
+        )],
+        WriteSet::Rows(vec!["pets.cats:1".to_string(), "pets.cats:2".to_string()])
+      ),
+      event_statements(Some(&table), &renumber).unwrap()
+    );
+
+    let heap = TableInfo {
+      primary_key: Vec::new(),
+      ..cats(ConflictPolicy::Overwrite)
+    };
+    assert_eq!(
+      (
+        vec![Statement::Execute(
+          "DELETE FROM `pets`.`cats` WHERE `id` <=> 1 LIMIT 1".to_string()
+        )],
+        WriteSet::All
+      ),
+      event_statements(Some(&heap), &delete(cat(Some(1), None, None))).unwrap()
+    );
+
+    let statement = ChangeEvent::Statement {
+      schema: "pets".to_string(),
+      sql: "DELETE FROM cats".to_string(),
+      operation: Operation::Delete,
+      tables: Vec::new(),
+      time_zone: None,
+    };
+    assert_eq!(WriteSet::All, event_statements(None, &statement).unwrap().1);
+
+    let missing_key = update(cat(None, Some("C"), None), cat(None, Some("D"), None));
+    assert!(matches!(
+      event_statements(Some(&table), &missing_key),
+      Err(MysqlSinkError::MissingColumn(..))
+    ));
+    let truncated = update(
+      cat(Some(1), None, None),
+      Row::new(vec![
+        None,
+        Some(Value::Truncated {
+          prefix: b"C".to_vec(),
+          size: 2,
+        }),
+      ]),
+    );
+    assert!(matches!(
+      event_statements(Some(&table), &truncated),
+      Err(MysqlSinkError::LossyValue(_, _, 1))
+    ));
+  }
+
+  #[test]
+  fn resolves_conflicts() {
+    let full_update = update(
+      cat(Some(1), Some("A"), Some(1)),
+      cat(Some(2), Some("B"), Some(2)),
+    );
+
+    let overwrite = cats(ConflictPolicy::Overwrite);
+    assert_eq!(
+      vec![
+        "DELETE FROM `pets`.`cats` WHERE `id` = 1",
+        "REPLACE INTO `pets`.`cats` (`id`, `name`, `version`) VALUES (2, X'42', 2)",
+      ],
+      sql(&overwrite, &full_update)
+    );
+
+    let ignore = cats(ConflictPolicy::Ignore);
+    assert_eq!(
+      vec!["INSERT IGNORE INTO `pets`.`cats` (`id`) VALUES (1)"],
+      sql(&ignore, &insert(cat(Some(1), None, None)))
+    );
+    assert_eq!(
+      vec!["UPDATE `pets`.`cats` SET `id` = 2, `name` = X'42', `version` = 2 WHERE `id` = 1"],
+      sql(&ignore, &full_update)
+    );
+
+    let error = cats(ConflictPolicy::Error);
+    assert_eq!(
+      vec!["INSERT INTO `pets`.`cats` (`id`) VALUES (1)"],
+      sql(&error, &insert(cat(Some(1), None, None)))
+    );
+    assert_eq!(
+      vec![
+        "expect SELECT 1 FROM `pets`.`cats` WHERE `id` = 1 FOR UPDATE",
+        "DELETE FROM `pets`.`cats` WHERE `id` = 1",
+      ],
+      sql(&error, &delete(cat(Some(1), None, None)))
+    );
+
+    let last_write_wins = cats(ConflictPolicy::LastWriteWins {
+      version: "version".to_string(),
+    });
+    assert_eq!(
+      vec![
+        "INSERT INTO `pets`.`cats` (`id`, `name`, `version`) VALUES (1, X'41', 1) \
+         ON DUPLICATE KEY UPDATE \
+         `id` = IF(VALUES(`version`) >= `version`, VALUES(`id`), `id`), \
+         `name` = IF(VALUES(`version`) >= `version`, VALUES(`name`), `name`), \
+         `version` = IF(VALUES(`version`) >= `version`, VALUES(`version`), `version`)"
+      ],
+      sql(&last_write_wins, &insert(cat(Some(1), Some("A"), Some(1))))
+    );
+    assert_eq!(
+      vec![
+        "UPDATE `pets`.`cats` SET `id` = 2, `name` = X'42', `version` = 2 \
+         WHERE `id` = 1 AND `version` <= 2"
+      ],
+      sql(&last_write_wins, &full_update)
+    );
+    assert_eq!(
+      vec!["DELETE FROM `pets`.`cats` WHERE `id` = 1 AND `version` <= 1"],
+      sql(&last_write_wins, &delete(cat(Some(1), None, Some(1))))
+    );
+    // The version must be logged.
+    let minimal = update(cat(Some(1), None, None), cat(None, Some("B"), None));
+    assert!(matches!(
+      event_statements(Some(&last_write_wins), &minimal),
+      Err(MysqlSinkError::MissingColumn(_, _, version)) if version == "version"
+    ));
+  }
+
+  #[test]
+  fn tracks_dependencies_by_row() {
+    let rows = |keys: &[&str]| WriteSet::Rows(keys.iter().map(|key| key.to_string()).collect());
+    let mut dependencies = Dependencies::default();
+
+    assert!(dependencies.register(0, rows(&["a", "b"])).is_empty());
+    assert!(dependencies.register(1, rows(&["c"])).is_empty());
+    assert_eq!(vec![0], dependencies.register(2, rows(&["b", "a"])));
+
+    // Only the last writer of a row is waited for.
+    dependencies.complete(1);
+    assert_eq!(vec![2], dependencies.register(3, rows(&["a", "c"])));
+
+    let mut barrier = dependencies.register(4, WriteSet::All);
+    barrier.sort_unstable();
+    assert_eq!(vec![0, 2, 3], barrier);
+    assert_eq!(vec![4], dependencies.register(5, rows(&["d"])));
+
+    for seq in 0..6 {
+      dependencies.complete(seq);
+    }
+    assert!(dependencies.register(6, rows(&["a"])).is_empty());
+    assert_eq!(1, dependencies.last_writers.len());
+  }
+
+  #[test]
+  fn drops_transactions_without_end_markers() {
+    let metrics = Arc::new(Metrics::default());
+    let sink = MysqlSink::<futures::io::Cursor<Vec<u8>>>::new(Vec::new());
+    let mut sink = block_on(sink).unwrap().metrics(metrics.clone());
+
+    block_on(async {
+      sink.send(ChangeEvent::Begin(Default::default())).await?;
+      sink.send(insert(cat(Some(1), Some("A"), None))).await?;
+      sink.send(ChangeEvent::Begin(Default::default())).await
+    })
+    .unwrap();
+    assert_eq!(Some(0), sink.transaction.as_ref().map(Vec::len));
+    assert_eq!(1, metrics.incomplete_transactions());
+  }
+}
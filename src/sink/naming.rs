@@ -0,0 +1,225 @@
+use std::collections::HashMap;
+
+#[derive(Debug, thiserror::Error)]
+pub enum NamingError {
+  #[error("Unknown placeholder `{{{0}}}` in name template, expected `{{schema}}` or `{{table}}`")]
+  UnknownPlaceholder(String),
+  #[error("Unterminated placeholder in name template `{0}`")]
+  UnterminatedPlaceholder(String),
+  #[error("Tables `{0}` and `{1}` both map to destination `{2}`")]
+  Conflict(String, String, String),
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Segment {
+  Literal(String),
+  Schema,
+  Table,
+}
+
+/// Template used to derive destination names (topics, tables, paths...) from a source table.
+///
+/// Placeholders are `{schema}` and `{table}`, e.g. `cdc.{schema}.{table}`.
+#[derive(Debug, Clone)]
+pub struct NameTemplate {
+  template: String,
+  segments: Vec<Segment>,
+  prefix: Option<String>,
+  lowercase: bool,
+  sanitize: bool,
+}
+
+impl NameTemplate {
+  pub fn parse(template: impl Into<String>) -> Result<Self, NamingError> {
+    let template = template.into();
+    let mut segments = Vec::new();
+    let mut rest = template.as_str();
+
+    while let Some(start) = rest.find('{') {
+      if start > 0 {
+        segments.push(Segment::Literal(rest[..start].to_string()));
+      }
+
+      let end = rest[start..]
+        .find('}')
+        .ok_or_else(|| NamingError::UnterminatedPlaceholder(template.clone()))?;
+
+      match &rest[start + 1..start + end] {
+        "schema" => segments.push(Segment::Schema),
+        "table" => segments.push(Segment::Table),
+        unknown => return Err(NamingError::UnknownPlaceholder(unknown.to_string())),
+      }
+
+      rest = &rest[start + end + 1..];
+    }
+
+    if !rest.is_empty() {
+      segments.push(Segment::Literal(rest.to_string()));
+    }
+
+    Ok(Self {
+      template,
+      segments,
+      prefix: None,
+      lowercase: false,
+      sanitize: false,
+    })
+  }
+
+  /// Prepends `prefix` to every rendered name, e.g. an environment or a team name.
+  pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+    self.prefix = Some(prefix.into());
+    self
+  }
+
+  pub fn lowercase(mut self, lowercase: bool) -> Self {
+    self.lowercase = lowercase;
+    self
+  }
+
+  /// Replaces every character outside of `[a-zA-Z0-9._-]` by `_`, which is what most brokers
+  /// accept (e.g. Kafka topic names).
+  pub fn sanitize(mut self, sanitize: bool) -> Self {
+    self.sanitize = sanitize;
+    self
+  }
+
+  pub fn template_str(&self) -> &str {
+    self.template.as_str()
+  }
+
+  pub fn render(&self, schema: &str, table: &str) -> String {
+    let mut name = self.prefix.clone().unwrap_or_default();
+    for segment in &self.segments {
+      match segment {
+        Segment::Literal(literal) => name.push_str(literal),
+        Segment::Schema => name.push_str(schema),
+        Segment::Table => name.push_str(table),
+      }
+    }
+
+    if self.lowercase {
+      name = name.to_lowercase();
+    }
+
+    if self.sanitize {
+      name = name
+        .chars()
+        .map(|c| match c {
+          'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '_' | '-' => c,
+          _ => '_',
+        })
+        .collect();
+    }
+
+    name
+  }
+}
+
+impl Default for NameTemplate {
+  fn default() -> Self {
+    Self::parse("{schema}.{table}").expect("default template is valid")
+  }
+}
+
+/// Resolves destination names for source tables, and detects when two distinct tables would end
+/// up in the same destination (e.g. `Cats` and `cats` once lowercased).
+#[derive(Debug)]
+pub struct Namer {
+  template: NameTemplate,
+  names: HashMap<(String, String), String>,
+  owners: HashMap<String, (String, String)>,
+}
+
+impl Namer {
+  pub fn new(template: NameTemplate) -> Self {
+    let names = HashMap::new();
+    let owners = HashMap::new();
+    Self {
+      template,
+      names,
+      owners,
+    }
+  }
+
+  pub fn template(&self) -> &NameTemplate {
+    &self.template
+  }
+
+  /// Returns the destination name of `schema.table`, failing when it is already used by another
+  /// table.
+  pub fn resolve(&mut self, schema: &str, table: &str) -> Result<&str, NamingError> {
+    let key = (schema.to_string(), table.to_string());
+    if !self.names.contains_key(&key) {
+      let name = self.template.render(schema, table);
+      if let Some((owner_schema, owner_table)) = self.owners.get(&name) {
+        return Err(NamingError::Conflict(
+          format!("{}.{}", owner_schema, owner_table),
+          format!("{}.{}", schema, table),
+          name,
+        ));
+      }
+
+      self.owners.insert(name.clone(), key.clone());
+      self.names.insert(key.clone(), name);
+    }
+
+    Ok(self.names[&key].as_str())
+  }
+
+  /// Resolves all of the given tables upfront, e.g. at startup with the tables of the source
+  /// schema, so that conflicts are reported before any event is published.
+  pub fn check<'a>(
+    &mut self,
+    tables: impl IntoIterator<Item = (&'a str, &'a str)>,
+  ) -> Result<(), NamingError> {
+    for (schema, table) in tables {
+      self.resolve(schema, table)?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn renders_templates() {
+    let template = NameTemplate::parse("cdc.{schema}.{table}").unwrap();
+    assert_eq!("cdc.pets.cats", template.render("pets", "cats"));
+
+    let template = NameTemplate::parse("{table}_changes")
+      .unwrap()
+      .with_prefix("prod.")
+      .lowercase(true)
+      .sanitize(true);
+    assert_eq!("prod.cat_s_changes", template.render("pets", "Cat$s"));
+  }
+
+  #[test]
+  fn rejects_invalid_templates() {
+    assert!(matches!(
+      NameTemplate::parse("{schema}.{tabel}"),
+      Err(NamingError::UnknownPlaceholder(_))
+    ));
+    assert!(matches!(
+      NameTemplate::parse("{schema}.{table"),
+      Err(NamingError::UnterminatedPlaceholder(_))
+    ));
+  }
+
+  #[test]
+  fn detects_conflicts() {
+    let mut namer = Namer::new(NameTemplate::default().lowercase(true));
+    assert_eq!("pets.cats", namer.resolve("pets", "cats").unwrap());
+    assert_eq!("pets.cats", namer.resolve("pets", "cats").unwrap());
+    assert!(matches!(
+      namer.resolve("pets", "Cats"),
+      Err(NamingError::Conflict(_, _, _))
+    ));
+    assert!(namer
+      .check(vec![("pets", "dogs"), ("Pets", "Dogs")])
+      .is_err());
+  }
+}
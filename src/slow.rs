@@ -0,0 +1,131 @@
+// Detection of operations that take longer than expected, to make stalls in production
+// diagnosable: every slow operation is counted, and reported on stderr with the query or the
+// binlog coordinates it was working on.
+
+use super::metrics::SlowOperationMetrics;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum SlowOperation {
+  /// Connecting and authenticating, see `Connection::with_stream`.
+  Handshake,
+  /// A text query, until its results are read.
+  Query,
+  /// Receiving and parsing a binlog event, from its first byte on. The time spent waiting for the
+  /// server to log the next event is not included.
+  EventRead,
+  /// Pushing a group of changes onto the sink of a `pipeline::Pipeline`.
+  SinkPublish,
+}
+
+impl SlowOperation {
+  fn index(self) -> usize {
+    match self {
+      SlowOperation::Handshake => 0,
+      SlowOperation::Query => 1,
+      SlowOperation::EventRead => 2,
+      SlowOperation::SinkPublish => 3,
+    }
+  }
+}
+
+// Queries are reported up to this many bytes.
+const MAX_REPORTED_QUERY_LEN: usize = 1024;
+
+/// Thresholds over which operations are reported as slow, none by default. Clones share their
+/// metrics, so that the same `SlowOperations` can be given to the connections and the pipeline.
+#[derive(Clone, Debug, Default)]
+pub struct SlowOperations {
+  thresholds: [Option<Duration>; 4],
+  metrics: Arc<SlowOperationMetrics>,
+}
+
+impl SlowOperations {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Reports `operation` whenever it takes longer than `threshold`.
+  pub fn threshold(mut self, operation: SlowOperation, threshold: Duration) -> Self {
+    self.thresholds[operation.index()] = Some(threshold);
+    self
+  }
+
+  pub fn metrics(&self) -> &Arc<SlowOperationMetrics> {
+    &self.metrics
+  }
+
+  // Reports `operation` when it took more than its threshold, `subject` describes what it was
+  // working on. Returns true when it was slow.
+  pub(crate) fn observe(
+    &self,
+    operation: SlowOperation,
+    elapsed: Duration,
+    subject: impl FnOnce() -> String,
+  ) -> bool {
+    match self.thresholds[operation.index()] {
+      Some(threshold) if elapsed > threshold => {
+        self.metrics.incr(operation);
+        eprintln!(
+          "warning: slow {:?} took {:?} (threshold {:?}): {}",
+          operation,
+          elapsed,
+          threshold,
+          subject()
+        );
+        true
+      }
+      _ => false,
+    }
+  }
+}
+
+// The query, cut at `MAX_REPORTED_QUERY_LEN` bytes.
+pub(crate) fn reported_query(query: &str) -> String {
+  if query.len() <= MAX_REPORTED_QUERY_LEN {
+    return query.to_string();
+  }
+  let mut end = MAX_REPORTED_QUERY_LEN;
+  while !query.is_char_boundary(end) {
+    end -= 1;
+  }
+  format!("{}...", &query[..end])
+}
+
+#[cfg(test)]
+mod test {
+  use super::{reported_query, SlowOperation, SlowOperations};
+  use std::time::Duration;
+
+  #[test]
+  fn counts_operations_over_their_threshold() {
+    let slow = SlowOperations::new().threshold(SlowOperation::Query, Duration::from_millis(100));
+    let shared = slow.clone();
+
+    assert!(!slow.observe(SlowOperation::Query, Duration::from_millis(50), String::new));
+    assert!(
+      slow.observe(SlowOperation::Query, Duration::from_millis(150), || {
+        "SELECT 1".to_string()
+      })
+    );
+    // No threshold.
+    assert!(!slow.observe(
+      SlowOperation::EventRead,
+      Duration::from_secs(60),
+      String::new
+    ));
+
+    assert_eq!(1, shared.metrics().count(SlowOperation::Query));
+    assert_eq!(0, shared.metrics().count(SlowOperation::EventRead));
+  }
+
+  #[test]
+  fn cuts_long_queries() {
+    assert_eq!("SELECT 1", reported_query("SELECT 1"));
+    let query = format!("SELECT '{}'", "é".repeat(1024));
+    let reported = reported_query(&query);
+    assert!(reported.len() <= 1024 + 3);
+    assert!(reported.ends_with("..."));
+  }
+}
@@ -0,0 +1,111 @@
+// Server status counters, as reported by COM_STATISTICS, the command behind `mysqladmin status`.
+// It is cheaper than a query to poll, but only returns a human readable string, e.g.
+// `Uptime: 5  Threads: 1  Questions: 2  Slow queries: 0  Opens: 33  Flush tables: 1  Open tables:
+// 26  Queries per second avg: 0.400`.
+//
+// https://dev.mysql.com/doc/dev/mysql-server/latest/page_protocol_com_statistics.html
+
+use std::time::Duration;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Statistics {
+  uptime: Duration,
+  threads: u64,
+  questions: u64,
+  slow_queries: u64,
+  opens: u64,
+  flush_tables: u64,
+  open_tables: u64,
+  queries_per_second: f64,
+}
+
+impl Statistics {
+  // `None` when a counter is missing, or is not a number.
+  pub(crate) fn parse(status: &str) -> Option<Self> {
+    // Counters are separated by two spaces.
+    let counters: Vec<(&str, &str)> = status
+      .split("  ")
+      .filter_map(|counter| counter.trim().split_once(": "))
+      .collect();
+    let counter = |name: &str| {
+      counters
+        .iter()
+        .find(|(counter, _)| *counter == name)
+        .map(|(_, value)| value.trim())
+    };
+    let integer = |name: &str| counter(name)?.parse::<u64>().ok();
+
+    Some(Self {
+      uptime: Duration::from_secs(integer("Uptime")?),
+      threads: integer("Threads")?,
+      questions: integer("Questions")?,
+      slow_queries: integer("Slow queries")?,
+      opens: integer("Opens")?,
+      flush_tables: integer("Flush tables")?,
+      open_tables: integer("Open tables")?,
+      queries_per_second: counter("Queries per second avg")?.parse().ok()?,
+    })
+  }
+
+  pub fn uptime(&self) -> Duration {
+    self.uptime
+  }
+
+  /// Number of connected clients.
+  pub fn threads(&self) -> u64 {
+    self.threads
+  }
+
+  /// Number of statements executed since the server started.
+  pub fn questions(&self) -> u64 {
+    self.questions
+  }
+
+  /// Number of queries that took more than `long_query_time`.
+  pub fn slow_queries(&self) -> u64 {
+    self.slow_queries
+  }
+
+  /// Number of tables opened since the server started.
+  pub fn opens(&self) -> u64 {
+    self.opens
+  }
+
+  pub fn flush_tables(&self) -> u64 {
+    self.flush_tables
+  }
+
+  pub fn open_tables(&self) -> u64 {
+    self.open_tables
+  }
+
+  /// Questions per second, on average since the server started.
+  pub fn queries_per_second(&self) -> f64 {
+    self.queries_per_second
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn parses_statistics() {
+    let statistics = Statistics::parse(
+      "Uptime: 5  Threads: 1  Questions: 2  Slow queries: 0  Opens: 33  Flush tables: 1  \
+       Open tables: 26  Queries per second avg: 0.400",
+    )
+    .unwrap();
+
+    assert_eq!(Duration::from_secs(5), statistics.uptime());
+    assert_eq!(1, statistics.threads());
+    assert_eq!(2, statistics.questions());
+    assert_eq!(0, statistics.slow_queries());
+    assert_eq!(33, statistics.opens());
+    assert_eq!(1, statistics.flush_tables());
+    assert_eq!(26, statistics.open_tables());
+    assert_eq!(0.4, statistics.queries_per_second());
+
+    assert_eq!(None, Statistics::parse("Uptime: 5  Threads: many"));
+  }
+}
@@ -0,0 +1,301 @@
+// Exit codes of the `main` binary, and the status file it keeps up to date, so that whoever runs it
+// (e.g. a process supervisor or a Kubernetes operator) can react to how it failed: fix the
+// credentials, enable binary logging, or start over from a snapshot once the position it resumes
+// from was purged, rather than restart it in a loop.
+//
+// The status file is replaced rather than written in place, so that readers never see a partial
+// document.
+
+use super::conn::{DriverError, UpstreamError};
+use serde_json::json;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// ER_DBACCESS_DENIED_ERROR
+const DB_ACCESS_DENIED: u16 = 1044;
+/// ER_ACCESS_DENIED_ERROR
+const ACCESS_DENIED: u16 = 1045;
+/// ER_SPECIFIC_ACCESS_DENIED_ERROR, e.g. without the REPLICATION SLAVE privilege.
+const SPECIFIC_ACCESS_DENIED: u16 = 1227;
+/// ER_NOT_SUPPORTED_AUTH_MODE
+const NOT_SUPPORTED_AUTH_MODE: u16 = 1251;
+/// ER_MASTER_FATAL_ERROR_READING_BINLOG, for a purged position (see `PURGED_MESSAGES`), but also
+/// e.g. a truncated binlog or another replica with the same server_id.
+const FATAL_ERROR_READING_BINLOG: u16 = 1236;
+/// Messages of `FATAL_ERROR_READING_BINLOG` when the position, or GTID set, to resume from was
+/// purged.
+const PURGED_MESSAGES: &[&str] = &[
+  "purged required binary logs",
+  "purged binary logs",
+  "Could not find first log file",
+];
+/// ER_NO_BINARY_LOGGING
+const NO_BINARY_LOGGING: u16 = 1381;
+
+/// Exit codes of the `main` binary, by failure mode.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum ExitCode {
+  Success,
+  /// Any failure without a code of its own, e.g. inconsistent tables or a lost connection.
+  Failure,
+  /// Invalid arguments, e.g. a malformed URL.
+  Config,
+  /// The server refused the credentials, or the user lacks a privilege.
+  Auth,
+  /// Binary logging is disabled on the server.
+  ReplicationDisabled,
+  /// The binlog position, or GTID set, to resume from is no longer in the binlogs of the server.
+  PositionPurged,
+  /// The server failed to send the binlog for any other reason, e.g. a truncated binlog.
+  Replication,
+  /// The sink failed to take changes.
+  Sink,
+}
+
+impl ExitCode {
+  pub fn of(err: &DriverError) -> Self {
+    match err {
+      DriverError::UpstreamError(UpstreamError::ServerError { code, message, .. }) => match *code {
+        DB_ACCESS_DENIED | ACCESS_DENIED | SPECIFIC_ACCESS_DENIED | NOT_SUPPORTED_AUTH_MODE => {
+          ExitCode::Auth
+        }
+        FATAL_ERROR_READING_BINLOG
+          if PURGED_MESSAGES
+            .iter()
+            .any(|purged| message.contains(purged)) =>
+        {
+          ExitCode::PositionPurged
+        }
+        FATAL_ERROR_READING_BINLOG => ExitCode::Replication,
+        NO_BINARY_LOGGING => ExitCode::ReplicationDisabled,
+        _ => ExitCode::Failure,
+      },
+      DriverError::ReplicationDisabled => ExitCode::ReplicationDisabled,
+      DriverError::ServerIdConflict(_)
+      | DriverError::InvalidUrl(_)
+      | DriverError::PublicKeyRetrievalDisabled => ExitCode::Config,
+      DriverError::GtidSetNotFound(_) | DriverError::InvalidBinlogPosition(..) => {
+        ExitCode::PositionPurged
+      }
+      _ => ExitCode::Failure,
+    }
+  }
+
+  /// Code the process exits with.
+  pub fn code(self) -> i32 {
+    match self {
+      ExitCode::Success => 0,
+      ExitCode::Failure => 1,
+      ExitCode::Config => 2,
+      ExitCode::Auth => 3,
+      ExitCode::ReplicationDisabled => 4,
+      ExitCode::PositionPurged => 5,
+      ExitCode::Sink => 6,
+      ExitCode::Replication => 7,
+    }
+  }
+
+  /// Name of the failure mode in the status file.
+  pub fn name(self) -> &'static str {
+    match self {
+      ExitCode::Success => "success",
+      ExitCode::Failure => "failure",
+      ExitCode::Config => "config_error",
+      ExitCode::Auth => "auth_failure",
+      ExitCode::ReplicationDisabled => "replication_disabled",
+      ExitCode::PositionPurged => "position_purged",
+      ExitCode::Sink => "sink_failure",
+      ExitCode::Replication => "replication_error",
+    }
+  }
+}
+
+/// How a command failed, and why.
+#[derive(Debug)]
+pub struct Failure {
+  pub exit_code: ExitCode,
+  pub message: String,
+}
+
+impl Failure {
+  pub fn new(exit_code: ExitCode, message: impl Into<String>) -> Self {
+    Self {
+      exit_code,
+      message: message.into(),
+    }
+  }
+
+  /// Failure of `context` (e.g. "Failed to connect to MYSQL") due to `err`, with the server error
+  /// if any.
+  pub fn driver(context: &str, err: &DriverError) -> Self {
+    let message = match std::error::Error::source(err) {
+      Some(source) => format!("{}: {}: {}", context, err, source),
+      None => format!("{}: {}", context, err),
+    };
+    Self::new(ExitCode::of(err), message)
+  }
+}
+
+/// State of a command in the status file.
+#[derive(Debug)]
+pub enum State<'a> {
+  Starting,
+  /// Connected, and streaming changes.
+  Streaming,
+  Stopped,
+  Failed(&'a Failure),
+}
+
+/// JSON document describing the state of a command, replaced on every update.
+#[derive(Clone, Debug)]
+pub struct StatusFile {
+  path: PathBuf,
+  command: String,
+}
+
+impl StatusFile {
+  pub fn new(path: impl AsRef<Path>, command: impl Into<String>) -> Self {
+    Self {
+      path: path.as_ref().to_path_buf(),
+      command: command.into(),
+    }
+  }
+
+  pub fn update(&self, state: &State) -> io::Result<()> {
+    let updated_at = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map_or(0, |elapsed| elapsed.as_secs());
+    let status = self.to_json(state, updated_at);
+
+    let mut temporary = self.path.clone().into_os_string();
+    temporary.push(".tmp");
+    fs::write(&temporary, format!("{}\n", status))?;
+    fs::rename(&temporary, &self.path)
+  }
+
+  fn to_json(&self, state: &State, updated_at: u64) -> serde_json::Value {
+    let mut status = json!({
+      "command": self.command,
+      "pid": std::process::id(),
+      "updated_at": updated_at,
+    });
+    let details = match state {
+      State::Starting => json!({ "state": "starting" }),
+      State::Streaming => json!({ "state": "streaming" }),
+      State::Stopped => json!({ "state": "stopped", "exit_code": 0 }),
+      State::Failed(failure) => json!({
+        "state": "failed",
+        "exit_code": failure.exit_code.code(),
+        "reason": failure.exit_code.name(),
+        "error": failure.message,
+      }),
+    };
+    if let (Some(status), serde_json::Value::Object(details)) = (status.as_object_mut(), details) {
+      status.extend(details);
+    }
+    status
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn server_error(code: u16) -> DriverError {
+    server_error_with_message(code, "")
+  }
+
+  fn server_error_with_message(code: u16, message: &str) -> DriverError {
+    UpstreamError::ServerError {
+      code,
+      state: "HY000".to_string(),
+      message: message.to_string(),
+    }
+    .into()
+  }
+
+  #[test]
+  fn classifies_failures() {
+    assert_eq!(ExitCode::Auth, ExitCode::of(&server_error(ACCESS_DENIED)));
+    assert_eq!(
+      ExitCode::PositionPurged,
+      ExitCode::of(&server_error_with_message(
+        FATAL_ERROR_READING_BINLOG,
+        "Cannot replicate because the master purged required binary logs."
+      ))
+    );
+    assert_eq!(
+      ExitCode::PositionPurged,
+      ExitCode::of(&server_error_with_message(
+        FATAL_ERROR_READING_BINLOG,
+        "Could not find first log file name in binary log index file"
+      ))
+    );
+    assert_eq!(
+      ExitCode::Replication,
+      ExitCode::of(&server_error_with_message(
+        FATAL_ERROR_READING_BINLOG,
+        "binlog truncated in the middle of event; consider out of disk space on master"
+      ))
+    );
+    assert_eq!(
+      ExitCode::ReplicationDisabled,
+      ExitCode::of(&DriverError::ReplicationDisabled)
+    );
+    assert_eq!(
+      ExitCode::PositionPurged,
+      ExitCode::of(&DriverError::InvalidBinlogPosition(
+        "mysql-bin.000001".to_string(),
+        4
+      ))
+    );
+    assert_eq!(
+      ExitCode::Config,
+      ExitCode::of(&DriverError::ServerIdConflict(1))
+    );
+    assert_eq!(
+      ExitCode::Config,
+      ExitCode::of(&DriverError::InvalidUrl(
+        "invalid ssl_mode `requried`".to_string()
+      ))
+    );
+    assert_eq!(ExitCode::Failure, ExitCode::of(&server_error(1062)));
+    assert_eq!(
+      ExitCode::Failure,
+      ExitCode::of(&DriverError::UnexpectedPacket)
+    );
+  }
+
+  #[test]
+  fn describes_the_state() {
+    let status = StatusFile::new("status.json", "stream");
+    let pid = std::process::id();
+
+    assert_eq!(
+      json!({
+        "command": "stream",
+        "pid": pid,
+        "updated_at": 42,
+        "state": "streaming",
+      }),
+      status.to_json(&State::Streaming, 42)
+    );
+
+    let failure = Failure::driver("Failed to connect to MYSQL", &server_error(ACCESS_DENIED));
+    assert_eq!(
+      json!({
+        "command": "stream",
+        "pid": pid,
+        "updated_at": 42,
+        "state": "failed",
+        "exit_code": 3,
+        "reason": "auth_failure",
+        "error": "Failed to connect to MYSQL: Failed due to server error: MYSQL returned error \
+                  1045 (HY000): ",
+      }),
+      status.to_json(&State::Failed(&failure), 42)
+    );
+  }
+}
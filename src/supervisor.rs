@@ -0,0 +1,414 @@
+// Runs the long-lived stages of a service, e.g. a pipeline per source, and restarts them when they
+// fail, per their `RestartPolicy`: a lost connection or a server restart is waited out, a stage
+// that keeps failing is escalated, failing the whole supervisor once its `RetryPolicy` runs out.
+//
+// Like a pipeline, the supervisor spawns nothing: its stages are futures of `Supervisor::run`,
+// which ends once all of them did. They are started with the `Shutdown` of the supervisor, which
+// resolves once the supervisor is told to stop, or once a stage failed for good, so that the other
+// stages drain their queues and save their checkpoints before the failure is returned.
+//
+// `Supervisor::health` reports the state of every stage while the supervisor runs, for health
+// checks and status pages.
+
+use super::conn::DriverError;
+use super::retry::{Retry, RetryPolicy};
+use super::util::error_chain;
+use futures::channel::oneshot;
+use futures::future::{self, BoxFuture, Future, FutureExt, Shared};
+use futures::select;
+use futures::stream::{FuturesUnordered, StreamExt};
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// A stage that ran this long before failing is healthy again, its failures are counted anew.
+const DEFAULT_HEALTHY_AFTER: Duration = Duration::from_secs(60);
+
+pub type StageError = Box<dyn Error + Send + Sync>;
+
+/// Resolves once the stages must stop, see `Supervisor::run`.
+pub type Shutdown = Shared<BoxFuture<'static, ()>>;
+
+type Start = Box<dyn FnMut(Shutdown) -> BoxFuture<'static, Result<(), StageError>> + Send>;
+
+/// When a stage is started again once it ended.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RestartPolicy {
+  /// Whether it failed or completed.
+  Always,
+  /// When it failed on an error worth retrying, a `DriverError` that `retry::Retry` retries. Any
+  /// other failure fails the supervisor.
+  OnTransient,
+  /// Never, a failure fails the supervisor.
+  Never,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SupervisorError {
+  #[error("Stage `{stage}` failed")]
+  Failed {
+    stage: String,
+    #[source]
+    source: StageError,
+  },
+  #[error("Stage `{stage}` failed {failures} times in a row")]
+  Escalated {
+    stage: String,
+    failures: u32,
+    #[source]
+    source: StageError,
+  },
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StageState {
+  Running,
+  /// Failed, waiting to be started again.
+  Restarting,
+  /// Completed, or stopped by the shutdown of the supervisor.
+  Stopped,
+  Failed,
+}
+
+/// State of a stage, see `Health::stages`.
+#[derive(Clone, Debug)]
+pub struct StageHealth {
+  name: String,
+  state: StageState,
+  restarts: u64,
+  last_error: Option<String>,
+}
+
+impl StageHealth {
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+
+  pub fn state(&self) -> StageState {
+    self.state
+  }
+
+  /// Number of times the stage was started again.
+  pub fn restarts(&self) -> u64 {
+    self.restarts
+  }
+
+  /// Message of the last error the stage failed with, followed by its sources, if any.
+  pub fn last_error(&self) -> Option<&str> {
+    self.last_error.as_deref()
+  }
+}
+
+/// States of the stages of a supervisor, updated while it runs.
+#[derive(Debug, Default)]
+pub struct Health {
+  stages: Mutex<Vec<StageHealth>>,
+}
+
+impl Health {
+  /// The stages, in the order they were added.
+  pub fn stages(&self) -> Vec<StageHealth> {
+    self.stages.lock().unwrap().clone()
+  }
+
+  /// Whether no stage is failing or waiting to restart.
+  pub fn is_healthy(&self) -> bool {
+    self
+      .stages
+      .lock()
+      .unwrap()
+      .iter()
+      .all(|stage| matches!(stage.state, StageState::Running | StageState::Stopped))
+  }
+
+  fn update(&self, index: usize, update: impl FnOnce(&mut StageHealth)) {
+    update(&mut self.stages.lock().unwrap()[index]);
+  }
+}
+
+struct Stage {
+  name: String,
+  policy: RestartPolicy,
+  start: Start,
+}
+
+/// Stages and how they are restarted, see `run`.
+pub struct Supervisor {
+  stages: Vec<Stage>,
+  retry: RetryPolicy,
+  healthy_after: Duration,
+  health: Arc<Health>,
+}
+
+impl Default for Supervisor {
+  fn default() -> Self {
+    Self {
+      stages: Vec::new(),
+      retry: RetryPolicy::new()
+        .max_attempts(5)
+        .backoff(Duration::from_secs(1), Duration::from_secs(30)),
+      healthy_after: DEFAULT_HEALTHY_AFTER,
+      health: Arc::default(),
+    }
+  }
+}
+
+impl Supervisor {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Runs the future returned by `start` as the stage `name`, and calls `start` again to restart
+  /// it per `policy`. The stage is expected to stop once its `Shutdown` resolves, e.g. by passing
+  /// it to `Pipeline::run`.
+  pub fn stage<F, T, E>(
+    mut self,
+    name: impl Into<String>,
+    policy: RestartPolicy,
+    mut start: F,
+  ) -> Self
+  where
+    F: FnMut(Shutdown) -> T + Send + 'static,
+    T: Future<Output = Result<(), E>> + Send + 'static,
+    E: Into<StageError>,
+  {
+    let name = name.into();
+    self.health.stages.lock().unwrap().push(StageHealth {
+      name: name.clone(),
+      state: StageState::Stopped,
+      restarts: 0,
+      last_error: None,
+    });
+    self.stages.push(Stage {
+      name,
+      policy,
+      start: Box::new(move |shutdown| {
+        start(shutdown)
+          .map(|result| result.map_err(Into::into))
+          .boxed()
+      }),
+    });
+    self
+  }
+
+  /// Failures in a row of a stage before they are escalated, and the backoff before restarting
+  /// it. By default, 5 failures, spaced by 1 second, doubled up to 30 seconds.
+  pub fn retry(mut self, policy: RetryPolicy) -> Self {
+    self.retry = policy;
+    self
+  }
+
+  /// How long a stage runs before its failures are counted anew, 1 minute by default.
+  pub fn healthy_after(mut self, duration: Duration) -> Self {
+    self.healthy_after = duration;
+    self
+  }
+
+  /// Keep a clone to follow the stages while the supervisor runs.
+  pub fn health(&self) -> &Arc<Health> {
+    &self.health
+  }
+
+  /// Runs the stages until they all stopped, restarting them per their policy. Once `shutdown`
+  /// resolves, or a stage failed for good, the stages are told to stop, and the first failure is
+  /// returned once they did.
+  pub async fn run(
+    self,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+  ) -> Result<(), SupervisorError> {
+    let (stop_sender, stop) = oneshot::channel::<()>();
+    let mut stop_sender = Some(stop_sender);
+    let shutdown: Shutdown = future::select(shutdown.boxed(), stop)
+      .map(drop)
+      .boxed()
+      .shared();
+
+    let (retry, healthy_after, health) = (self.retry, self.healthy_after, &self.health);
+    let mut stages: FuturesUnordered<_> = self
+      .stages
+      .into_iter()
+      .enumerate()
+      .map(|(index, stage)| supervise(index, stage, shutdown.clone(), retry, healthy_after, health))
+      .collect();
+
+    let mut failure = None;
+    while let Some(result) = stages.next().await {
+      if let Err(err) = result {
+        eprintln!("error: {}", describe(&err));
+        failure.get_or_insert(err);
+        if let Some(stop_sender) = stop_sender.take() {
+          let _ = stop_sender.send(());
+        }
+      }
+    }
+    failure.map_or(Ok(()), Err)
+  }
+}
+
+// Runs `stage` until it stopped for good.
+async fn supervise(
+  index: usize,
+  mut stage: Stage,
+  shutdown: Shutdown,
+  retry: RetryPolicy,
+  healthy_after: Duration,
+  health: &Health,
+) -> Result<(), SupervisorError> {
+  let mut failures = 0;
+  loop {
+    health.update(index, |health| health.state = StageState::Running);
+    let started_at = Instant::now();
+    let result = (stage.start)(shutdown.clone()).await;
+    let stopping = shutdown.peek().is_some();
+
+    let err = match result {
+      Ok(()) if stopping || stage.policy != RestartPolicy::Always => {
+        health.update(index, |health| health.state = StageState::Stopped);
+        return Ok(());
+      }
+      Ok(()) => None,
+      Err(err) => {
+        health.update(index, |health| {
+          health.state = StageState::Failed;
+          health.last_error = Some(error_chain(&*err));
+        });
+        let restart = match stage.policy {
+          RestartPolicy::Always => true,
+          RestartPolicy::OnTransient => is_transient(&*err),
+          RestartPolicy::Never => false,
+        };
+        if stopping || !restart {
+          return Err(SupervisorError::Failed {
+            stage: stage.name,
+            source: err,
+          });
+        }
+        if started_at.elapsed() >= healthy_after {
+          failures = 0;
+        }
+        failures += 1;
+        if !retry.allows(failures) {
+          return Err(SupervisorError::Escalated {
+            stage: stage.name,
+            failures,
+            source: err,
+          });
+        }
+        Some(err)
+      }
+    };
+
+    // Stages that complete are restarted after the shortest backoff.
+    let backoff = retry.backoff_after(failures.max(1));
+    if let Some(ref err) = err {
+      eprintln!(
+        "warning: restarting stage `{}` in {:?} after: {}",
+        stage.name, backoff, err
+      );
+    }
+    health.update(index, |health| health.state = StageState::Restarting);
+    select! {
+      _ = shutdown.clone().fuse() => {
+        health.update(index, |health| health.state = StageState::Stopped);
+        return Ok(());
+      }
+      _ = futures_timer::Delay::new(backoff).fuse() => {}
+    }
+    health.update(index, |health| health.restarts += 1);
+  }
+}
+
+// Whether a `DriverError` that is worth retrying caused `err`.
+fn is_transient(err: &(dyn Error + 'static)) -> bool {
+  let mut source = Some(err);
+  while let Some(err) = source {
+    if let Some(err) = err.downcast_ref::<DriverError>() {
+      return Retry::of(err) != Retry::Never;
+    }
+    source = err.source();
+  }
+  false
+}
+
+// `err`, followed by its source.
+fn describe(err: &SupervisorError) -> String {
+  match err {
+    SupervisorError::Failed { source, .. } | SupervisorError::Escalated { source, .. } => {
+      format!("{}: {}", err, source)
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use futures::executor::block_on;
+  use std::io;
+  use std::sync::atomic::{AtomicU32, Ordering};
+
+  fn fast_retry() -> RetryPolicy {
+    RetryPolicy::new()
+      .max_attempts(3)
+      .backoff(Duration::from_millis(1), Duration::from_millis(1))
+  }
+
+  fn lost_connection() -> StageError {
+    Box::new(DriverError::from(io::Error::from(
+      io::ErrorKind::BrokenPipe,
+    )))
+  }
+
+  #[test]
+  fn restarts_transient_failures_and_escalates_repeated_ones() {
+    let starts = Arc::new(AtomicU32::new(0));
+    let counted = starts.clone();
+    let supervisor = Supervisor::new()
+      .retry(fast_retry())
+      .stage("binlog", RestartPolicy::OnTransient, move |_| {
+        counted.fetch_add(1, Ordering::SeqCst);
+        future::ready(Err::<(), _>(lost_connection()))
+      })
+      .stage("idle", RestartPolicy::Never, |shutdown| {
+        shutdown.map(Ok::<(), StageError>)
+      });
+    let health = supervisor.health().clone();
+
+    match block_on(supervisor.run(future::pending())) {
+      Err(SupervisorError::Escalated {
+        stage, failures, ..
+      }) => {
+        assert_eq!("binlog", stage);
+        assert_eq!(3, failures);
+      }
+      other => panic!("expected an escalation, got {:?}", other),
+    }
+    assert_eq!(3, starts.load(Ordering::SeqCst));
+    let stages = health.stages();
+    assert_eq!(StageState::Failed, stages[0].state());
+    assert_eq!(2, stages[0].restarts());
+    assert_eq!(
+      Some("Failed due to IO error: broken pipe"),
+      stages[0].last_error()
+    );
+    // Told to stop once the first stage failed for good.
+    assert_eq!(StageState::Stopped, stages[1].state());
+    assert!(!health.is_healthy());
+  }
+
+  #[test]
+  fn fails_on_permanent_errors() {
+    let supervisor =
+      Supervisor::new()
+        .retry(fast_retry())
+        .stage("sink", RestartPolicy::OnTransient, |_| {
+          future::ready(Err::<(), StageError>("topic not found".into()))
+        });
+    match block_on(supervisor.run(future::pending())) {
+      Err(SupervisorError::Failed { stage, source }) => {
+        assert_eq!("sink", stage);
+        assert_eq!("topic not found", source.to_string());
+      }
+      other => panic!("expected a failure, got {:?}", other),
+    }
+  }
+}
@@ -0,0 +1,218 @@
+// Zero dates and out of range temporal values, which MYSQL logs as is when the sql_mode lets them
+// in (no NO_ZERO_DATE, NO_ZERO_IN_DATE or strict mode). Sinks with a stricter type system, e.g. a
+// date type of their own, would reject them.
+
+use super::protocol::ColumnType;
+use super::value::{Row, Value};
+use std::io;
+
+// Largest TIMESTAMP, 2038-01-19 03:14:07 UTC.
+const MAX_TIMESTAMP_SECONDS: u32 = i32::MAX as u32;
+// Largest TIME, 838:59:59.
+const MAX_TIME_HOURS: u64 = 838;
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum InvalidTemporalPolicy {
+  /// Keep the value as parsed, e.g. `Value::Date` with a zero month.
+  #[default]
+  Keep,
+  /// Replace the value by NULL.
+  Null,
+  /// Fail the decoding of the rows event.
+  Error,
+  /// Replace the value by its MYSQL text representation, e.g. `0000-00-00` for a DATE, as
+  /// `Value::Bytes`.
+  String,
+}
+
+impl InvalidTemporalPolicy {
+  /// Applies the policy to the invalid temporal values of the rows of `schema`.`table`, returns the
+  /// number of values found. Fails on the first of them with `InvalidTemporalPolicy::Error`.
+  pub fn apply<'a>(
+    &self,
+    schema: &str,
+    table: &str,
+    column_types: &[ColumnType],
+    rows: impl IntoIterator<Item = &'a mut Row>,
+  ) -> io::Result<u64> {
+    if *self == InvalidTemporalPolicy::Keep {
+      return Ok(0);
+    }
+
+    let mut invalid = 0;
+    for row in rows {
+      for (i, value) in row.values_mut().iter_mut().enumerate() {
+        let value = match value {
+          Some(value) if is_invalid(value) => value,
+          _ => continue,
+        };
+        invalid += 1;
+
+        *value = match self {
+          InvalidTemporalPolicy::Keep => continue,
+          InvalidTemporalPolicy::Null => Value::Null,
+          InvalidTemporalPolicy::String => Value::Bytes(to_text(value, column_types.get(i))),
+          InvalidTemporalPolicy::Error => {
+            return Err(io::Error::new(
+              io::ErrorKind::InvalidData,
+              format!(
+                "invalid temporal value `{}` in column {} of {}.{}",
+                String::from_utf8_lossy(&to_text(value, column_types.get(i))),
+                i,
+                schema,
+                table
+              ),
+            ))
+          }
+        };
+      }
+    }
+
+    Ok(invalid)
+  }
+}
+
+/// Returns true for the zero dates, the dates with a zero part (e.g. `2020-00-15`), and the dates,
+/// timestamps and times out of the range of their type.
+pub fn is_invalid(value: &Value) -> bool {
+  match *value {
+    Value::Date {
+      year,
+      month,
+      day,
+      hour,
+      minute,
+      second,
+      micro,
+    } => {
+      month == 0
+        || month > 12
+        || day == 0
+        || day > days_in_month(year, month)
+        || hour > 23
+        || minute > 59
+        || second > 59
+        || micro > 999_999
+    }
+    Value::Timestamp { seconds, micro } => {
+      (seconds == 0 && micro == 0) || seconds > MAX_TIMESTAMP_SECONDS || micro > 999_999
+    }
+    Value::Time {
+      days,
+      hours,
+      minutes,
+      seconds,
+      micros,
+      ..
+    } => {
+      u64::from(days) * 24 + u64::from(hours) > MAX_TIME_HOURS
+        || minutes > 59
+        || seconds > 59
+        || micros > 999_999
+    }
+    _ => false,
+  }
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+  match month {
+    2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => 29,
+    2 => 28,
+    4 | 6 | 9 | 11 => 30,
+    _ => 31,
+  }
+}
+
+// Text of the value as MYSQL prints it for the type of its column: the zero TIMESTAMP as a zero
+// date, and DATE columns without their time.
+fn to_text(value: &Value, column_type: Option<&ColumnType>) -> Vec<u8> {
+  let mut text = value.to_text().unwrap_or_default();
+  if let Some(ColumnType::MYSQL_TYPE_DATE) | Some(ColumnType::MYSQL_TYPE_NEWDATE) = column_type {
+    text.truncate("0000-00-00".len());
+  }
+  text
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn date(year: u16, month: u8, day: u8) -> Value {
+    Value::Date {
+      year,
+      month,
+      day,
+      hour: 0,
+      minute: 0,
+      second: 0,
+      micro: 0,
+    }
+  }
+
+  fn rows() -> Vec<Row> {
+    vec![Row::new(vec![
+      Some(Value::Int(1)),
+      Some(date(0, 0, 0)),
+      Some(Value::Timestamp {
+        seconds: 0,
+        micro: 0,
+      }),
+      Some(date(2020, 2, 29)),
+      Some(date(2019, 2, 29)),
+    ])]
+  }
+
+  const COLUMN_TYPES: &[ColumnType] = &[
+    ColumnType::MYSQL_TYPE_LONG,
+    ColumnType::MYSQL_TYPE_DATE,
+    ColumnType::MYSQL_TYPE_TIMESTAMP2,
+    ColumnType::MYSQL_TYPE_DATE,
+    ColumnType::MYSQL_TYPE_DATETIME2,
+  ];
+
+  #[test]
+  fn applies_policies_to_invalid_temporals() {
+    let apply = |policy: InvalidTemporalPolicy, rows: &mut Vec<Row>| {
+      policy.apply("pets", "cats", COLUMN_TYPES, rows.iter_mut())
+    };
+
+    let mut kept = rows();
+    assert_eq!(0, apply(InvalidTemporalPolicy::Keep, &mut kept).unwrap());
+    assert_eq!(rows(), kept);
+
+    let mut nulls = rows();
+    assert_eq!(3, apply(InvalidTemporalPolicy::Null, &mut nulls).unwrap());
+    assert_eq!(Some(&Value::Null), nulls[0].get(1));
+    assert_eq!(Some(&date(2020, 2, 29)), nulls[0].get(3));
+
+    let mut strings = rows();
+    assert_eq!(
+      3,
+      apply(InvalidTemporalPolicy::String, &mut strings).unwrap()
+    );
+    let text = |i| strings[0].get(i).and_then(Value::as_str);
+    assert_eq!(Some("0000-00-00"), text(1));
+    assert_eq!(Some("0000-00-00 00:00:00"), text(2));
+    assert_eq!(Some("2019-02-29 00:00:00"), text(4));
+
+    let err = apply(InvalidTemporalPolicy::Error, &mut rows()).unwrap_err();
+    assert_eq!(io::ErrorKind::InvalidData, err.kind());
+    assert!(err
+      .to_string()
+      .contains("`0000-00-00` in column 1 of pets.cats"));
+  }
+
+  #[test]
+  fn validates_times() {
+    let time = |hours: u8, days: u32| Value::Time {
+      negative: true,
+      days,
+      hours,
+      minutes: 59,
+      seconds: 59,
+      micros: 0,
+    };
+    assert!(!is_invalid(&time(22, 34)));
+    assert!(is_invalid(&time(23, 34)));
+  }
+}
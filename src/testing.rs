@@ -0,0 +1,152 @@
+// Fault injection, to exercise the reconnect and checkpoint logic against an unreliable network
+// or sink. Only compiled with the `testing` feature.
+//
+// Faults are drawn from a seeded PRNG, so that a failing run can be replayed with the same seed.
+
+use super::util::XorShift;
+use futures::stream::{self, Stream, StreamExt};
+use std::time::Duration;
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Fault {
+  /// Delay the delivery of the packet.
+  Latency(Duration),
+  /// Drop the connection. Every subsequent read fails.
+  Disconnect,
+  /// Deliver only the first N bytes of the packet payload.
+  Truncate(usize),
+}
+
+pub struct FaultInjector {
+  rng: XorShift,
+  latency: Duration,
+  latency_rate: f64,
+  disconnect_rate: f64,
+  truncate_rate: f64,
+  reorder_window: usize,
+  disconnected: bool,
+}
+
+impl FaultInjector {
+  /// Creates an injector that does not inject anything until configured.
+  pub fn new(seed: u64) -> Self {
+    Self {
+      rng: XorShift::new(seed),
+      latency: Duration::from_millis(0),
+      latency_rate: 0.0,
+      disconnect_rate: 0.0,
+      truncate_rate: 0.0,
+      reorder_window: 1,
+      disconnected: false,
+    }
+  }
+
+  /// Delays `rate` (0.0 to 1.0) of the packets by `latency`.
+  pub fn latency(mut self, latency: Duration, rate: f64) -> Self {
+    self.latency = latency;
+    self.latency_rate = rate;
+    self
+  }
+
+  /// Drops the connection on `rate` of the packets.
+  pub fn disconnects(mut self, rate: f64) -> Self {
+    self.disconnect_rate = rate;
+    self
+  }
+
+  /// Truncates `rate` of the packets at a random length.
+  pub fn truncated_packets(mut self, rate: f64) -> Self {
+    self.truncate_rate = rate;
+    self
+  }
+
+  /// Shuffles the items delivered by `FaultInjector::reorder` within windows of `window` items.
+  pub fn reorder_window(mut self, window: usize) -> Self {
+    self.reorder_window = window.max(1);
+    self
+  }
+
+  /// Draws the fault to inject on the next packet, if any.
+  pub fn next_fault(&mut self, payload_len: usize) -> Option<Fault> {
+    if self.disconnected || self.rng.chance(self.disconnect_rate) {
+      self.disconnected = true;
+      return Some(Fault::Disconnect);
+    }
+
+    if payload_len > 0 && self.rng.chance(self.truncate_rate) {
+      let len = self.rng.next_u64() as usize % payload_len;
+      return Some(Fault::Truncate(len));
+    }
+
+    if self.rng.chance(self.latency_rate) {
+      return Some(Fault::Latency(self.latency));
+    }
+
+    None
+  }
+
+  /// Shuffles the items of `stream`, e.g. the events delivered to a sink. Items are only moved
+  /// within windows of `reorder_window` consecutive items.
+  pub fn reorder<S>(&mut self, stream: S) -> impl Stream<Item = S::Item>
+  where
+    S: Stream,
+  {
+    let mut rng = XorShift::new(self.rng.next_u64());
+    stream
+      .chunks(self.reorder_window)
+      .flat_map(move |mut items| {
+        // Fisher-Yates
+        for i in (1..items.len()).rev() {
+          let j = rng.next_u64() as usize % (i + 1);
+          items.swap(i, j);
+        }
+        stream::iter(items)
+      })
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn does_not_inject_by_default() {
+    let mut faults = FaultInjector::new(42);
+    assert!((0..1000).all(|_| faults.next_fault(100).is_none()));
+  }
+
+  #[test]
+  fn disconnects_are_permanent() {
+    let mut faults = FaultInjector::new(42).disconnects(1.0);
+    assert_eq!(Some(Fault::Disconnect), faults.next_fault(100));
+
+    faults.disconnect_rate = 0.0;
+    assert_eq!(Some(Fault::Disconnect), faults.next_fault(100));
+  }
+
+  #[test]
+  fn truncates_within_the_payload() {
+    let mut faults = FaultInjector::new(42).truncated_packets(1.0);
+    for _ in 0..100 {
+      match faults.next_fault(10) {
+        Some(Fault::Truncate(len)) => assert!(len < 10),
+        unexpected => panic!("unexpected {:?}", unexpected),
+      }
+    }
+  }
+
+  #[test]
+  fn reorders_within_windows() {
+    let mut faults = FaultInjector::new(42).reorder_window(4);
+    let items: Vec<u32> =
+      futures::executor::block_on(faults.reorder(stream::iter(0..8_u32)).collect());
+
+    let mut first = items[..4].to_vec();
+    let mut second = items[4..].to_vec();
+    first.sort_unstable();
+    second.sort_unstable();
+    assert_eq!(vec![0, 1, 2, 3], first);
+    assert_eq!(vec![4, 5, 6, 7], second);
+    assert_ne!((0..8).collect::<Vec<u32>>(), items);
+  }
+}
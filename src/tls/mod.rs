@@ -0,0 +1,154 @@
+// TLS of the connections to MYSQL. The client asks for TLS with an SSLRequest packet, the first
+// fields of its handshake response, upgrades the stream, then sends the whole handshake response
+// over TLS.
+//
+// The TLS handshake itself is done by a `TlsConnector`: `rustls::RustlsConnector` with the
+// `tls-rustls` feature, or one wrapping any other TLS library, e.g. async-native-tls. Only the
+// streams opened by `Connection::connect`, the runtime adapters or `Connection::with_tls_stream`
+// can be upgraded.
+
+use futures::future::BoxFuture;
+use futures::io::{AsyncRead, AsyncWrite};
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+#[cfg(feature = "tls-rustls")]
+pub mod rustls;
+
+/// Whether connections use TLS, and what the TLS handshake verifies, as `--ssl-mode` of the MYSQL
+/// client.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Ord, PartialOrd)]
+pub enum SslMode {
+  /// Plain connections, the default.
+  #[default]
+  Disabled,
+  /// TLS when both the server and the client support it, plain connections otherwise.
+  Preferred,
+  /// TLS, without verifying the certificate of the server.
+  Required,
+  /// TLS, with a certificate of the server signed by the CA, see `ConnectionOptions::ssl_ca`.
+  VerifyCa,
+  /// As `VerifyCa`, with a certificate of the server issued for the host connected to.
+  VerifyIdentity,
+}
+
+impl SslMode {
+  // Connections fail rather than fall back to plain ones.
+  pub(crate) fn is_required(self) -> bool {
+    self >= SslMode::Required
+  }
+}
+
+/// Parses the names of `--ssl-mode`, e.g. `VERIFY_CA`, in any case.
+impl FromStr for SslMode {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_ascii_uppercase().replace('-', "_").as_str() {
+      "DISABLED" => Ok(SslMode::Disabled),
+      "PREFERRED" => Ok(SslMode::Preferred),
+      "REQUIRED" => Ok(SslMode::Required),
+      "VERIFY_CA" => Ok(SslMode::VerifyCa),
+      "VERIFY_IDENTITY" => Ok(SslMode::VerifyIdentity),
+      _ => Err(format!("unknown SSL mode `{}`", s)),
+    }
+  }
+}
+
+/// Stream of a connection, before or after its upgrade to TLS.
+pub trait TlsStream: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> TlsStream for T {}
+
+/// Performs the TLS handshake of the connections, see `ConnectionOptions::tls_connector`.
+pub trait TlsConnector: fmt::Debug + Send + Sync {
+  /// Upgrades `stream`, connected to `domain` (or the IP address of the server), to TLS. `mode` is
+  /// one of `Required`, `VerifyCa` or `VerifyIdentity` when the connection fails without TLS, and
+  /// `ca` the certificates to verify the server with, if given.
+  fn connect<'a>(
+    &'a self,
+    stream: Box<dyn TlsStream>,
+    domain: &'a str,
+    mode: SslMode,
+    ca: Option<&'a Path>,
+  ) -> BoxFuture<'a, io::Result<Box<dyn TlsStream>>>;
+}
+
+// Connector of the connections given none, see `ConnectionOptions::tls_connector`.
+pub(crate) fn default_connector() -> Option<Arc<dyn TlsConnector>> {
+  #[cfg(feature = "tls-rustls")]
+  return Some(Arc::new(self::rustls::RustlsConnector));
+  #[cfg(not(feature = "tls-rustls"))]
+  None
+}
+
+pub(crate) fn boxed<S: TlsStream + 'static>(stream: S) -> Box<dyn TlsStream> {
+  Box::new(stream)
+}
+
+// Stream of `Connection`.
+pub(crate) enum Transport<S> {
+  Plain(S),
+  Tls(Box<dyn TlsStream>),
+  // Between the SSLRequest packet and the end of the TLS handshake.
+  Upgrading,
+}
+
+impl<S> Transport<S> {
+  #[cfg(test)]
+  pub(crate) fn plain(&self) -> &S {
+    match self {
+      Transport::Plain(stream) => stream,
+      _ => panic!("not a plain stream"),
+    }
+  }
+}
+
+fn upgrading() -> io::Error {
+  io::Error::new(io::ErrorKind::NotConnected, "TLS handshake in progress")
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Transport<S> {
+  fn poll_read(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &mut [u8],
+  ) -> Poll<io::Result<usize>> {
+    match self.get_mut() {
+      Transport::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+      Transport::Tls(stream) => Pin::new(stream).poll_read(cx, buf),
+      Transport::Upgrading => Poll::Ready(Err(upgrading())),
+    }
+  }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Transport<S> {
+  fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+    match self.get_mut() {
+      Transport::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+      Transport::Tls(stream) => Pin::new(stream).poll_write(cx, buf),
+      Transport::Upgrading => Poll::Ready(Err(upgrading())),
+    }
+  }
+
+  fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+    match self.get_mut() {
+      Transport::Plain(stream) => Pin::new(stream).poll_flush(cx),
+      Transport::Tls(stream) => Pin::new(stream).poll_flush(cx),
+      Transport::Upgrading => Poll::Ready(Err(upgrading())),
+    }
+  }
+
+  fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+    match self.get_mut() {
+      Transport::Plain(stream) => Pin::new(stream).poll_close(cx),
+      Transport::Tls(stream) => Pin::new(stream).poll_close(cx),
+      Transport::Upgrading => Poll::Ready(Err(upgrading())),
+    }
+  }
+}
@@ -0,0 +1,153 @@
+// TLS connector of the `tls-rustls` feature. The certificate of the server is verified against the
+// CA given to `ConnectionOptions::ssl_ca`, or the Mozilla root certificates of webpki-roots.
+
+use super::{SslMode, TlsConnector, TlsStream};
+use ::rustls::{
+  Certificate, ClientConfig, RootCertStore, ServerCertVerified, ServerCertVerifier, TLSError,
+  WebPKIVerifier,
+};
+use futures::future::BoxFuture;
+use futures_rustls::webpki::{self, DNSNameRef};
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::Path;
+use std::sync::Arc;
+
+// Name sent to the servers connected to by IP address, whose certificates are not checked against
+// the host with `Required` and `VerifyCa`: webpki only verifies DNS names.
+const IP_ADDRESS_NAME: &str = "mysql.invalid";
+
+/// Verifies the certificate of the server as `SslMode` requires: not at all with `Required`, its
+/// chain of trust with `VerifyCa`, and its chain and name with `VerifyIdentity`.
+#[derive(Debug, Default)]
+pub struct RustlsConnector;
+
+impl TlsConnector for RustlsConnector {
+  fn connect<'a>(
+    &'a self,
+    stream: Box<dyn TlsStream>,
+    domain: &'a str,
+    mode: SslMode,
+    ca: Option<&'a Path>,
+  ) -> BoxFuture<'a, io::Result<Box<dyn TlsStream>>> {
+    Box::pin(async move {
+      let name = match DNSNameRef::try_from_ascii_str(domain) {
+        Ok(name) => name,
+        Err(_) if mode != SslMode::VerifyIdentity => {
+          DNSNameRef::try_from_ascii_str(IP_ADDRESS_NAME).map_err(invalid)?
+        }
+        Err(_) => {
+          return Err(invalid(format!(
+            "`{}` is not a DNS name, VERIFY_IDENTITY needs the host name of the certificate",
+            domain
+          )))
+        }
+      };
+      let connector = futures_rustls::TlsConnector::from(Arc::new(client_config(mode, ca)?));
+      let stream = connector.connect(name, stream).await?;
+      Ok(Box::new(stream) as Box<dyn TlsStream>)
+    })
+  }
+}
+
+fn client_config(mode: SslMode, ca: Option<&Path>) -> io::Result<ClientConfig> {
+  let mut config = ClientConfig::new();
+  match ca {
+    Some(path) => {
+      let mut pem = BufReader::new(File::open(path)?);
+      match config.root_store.add_pem_file(&mut pem) {
+        Ok((added, _)) if added > 0 => {}
+        _ => {
+          return Err(invalid(format!(
+            "no valid certificate in {}",
+            path.display()
+          )))
+        }
+      }
+    }
+    None => {
+      config
+        .root_store
+        .add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS);
+    }
+  }
+
+  match mode {
+    SslMode::VerifyIdentity => {}
+    SslMode::VerifyCa => config
+      .dangerous()
+      .set_certificate_verifier(Arc::new(CaVerifier)),
+    _ => config
+      .dangerous()
+      .set_certificate_verifier(Arc::new(NoVerifier)),
+  }
+  Ok(config)
+}
+
+fn invalid(err: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidInput, err)
+}
+
+// `SslMode::VerifyCa`: the chain of trust, whatever the name of the certificate.
+struct CaVerifier;
+
+impl ServerCertVerifier for CaVerifier {
+  fn verify_server_cert(
+    &self,
+    roots: &RootCertStore,
+    presented_certs: &[Certificate],
+    dns_name: DNSNameRef,
+    ocsp_response: &[u8],
+  ) -> Result<ServerCertVerified, TLSError> {
+    let verified =
+      WebPKIVerifier::new().verify_server_cert(roots, presented_certs, dns_name, ocsp_response);
+    match verified {
+      Err(TLSError::WebPKIError(webpki::Error::CertNotValidForName)) => {
+        Ok(ServerCertVerified::assertion())
+      }
+      verified => verified,
+    }
+  }
+}
+
+// `SslMode::Required`: encryption only.
+struct NoVerifier;
+
+impl ServerCertVerifier for NoVerifier {
+  fn verify_server_cert(
+    &self,
+    _roots: &RootCertStore,
+    _presented_certs: &[Certificate],
+    _dns_name: DNSNameRef,
+    _ocsp_response: &[u8],
+  ) -> Result<ServerCertVerified, TLSError> {
+    Ok(ServerCertVerified::assertion())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use futures::executor::block_on;
+  use futures::io::Cursor;
+
+  fn connect(domain: &str, mode: SslMode, ca: Option<&Path>) -> io::Result<Box<dyn TlsStream>> {
+    let stream = Box::new(Cursor::new(Vec::new()));
+    block_on(RustlsConnector.connect(stream, domain, mode, ca))
+  }
+
+  #[test]
+  fn verifies_identity_of_dns_names_only() {
+    let err = connect("127.0.0.1", SslMode::VerifyIdentity, None)
+      .err()
+      .unwrap();
+    assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+    assert!(err.to_string().contains("not a DNS name"));
+
+    let ca = Path::new("/nonexistent/ca.pem");
+    let err = connect("db.example.com", SslMode::VerifyCa, Some(ca))
+      .err()
+      .unwrap();
+    assert_eq!(io::ErrorKind::NotFound, err.kind());
+  }
+}
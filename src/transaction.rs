@@ -0,0 +1,457 @@
+use super::event::{ChangeEvent, TransactionMetadata};
+use super::metrics::Metrics;
+use futures::future;
+use futures::stream::{Stream, StreamExt};
+use std::fmt;
+use std::sync::Arc;
+
+// 64MB
+const DEFAULT_MAX_BUFFER_SIZE: usize = 64 * 1024 * 1024;
+// Size of a rows event in the binlog assumed to estimate the number of changes of a transaction
+// from its `transaction_length`. Low enough that most transactions get their whole buffer upfront.
+const ESTIMATED_EVENT_SIZE: u64 = 1024;
+// Most changes made room for upfront, whatever the length logged and the size of the chunks: the
+// buffer grows as the changes arrive past it.
+const MAX_ESTIMATED_EVENT_COUNT: u64 = 4096;
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum ChunkKind {
+  Begin,
+  Continue,
+  End,
+  /// The transaction was cut short, e.g. by a new BEGIN marker after an event that failed to
+  /// decode was skipped. Carries no changes, the chunks received so far must be discarded.
+  Aborted,
+}
+
+/// Part of a transaction that went over the grouping buffer size.
+#[derive(Debug)]
+pub struct TransactionChunk {
+  kind: ChunkKind,
+  index: u64,
+  metadata: TransactionMetadata,
+  events: Vec<ChangeEvent>,
+}
+
+impl TransactionChunk {
+  pub fn kind(&self) -> ChunkKind {
+    self.kind
+  }
+
+  /// Position of the chunk within its transaction, starting at 0.
+  pub fn index(&self) -> u64 {
+    self.index
+  }
+
+  /// Metadata of the transaction, as of the BEGIN marker (or END marker for the last chunk).
+  pub fn metadata(&self) -> &TransactionMetadata {
+    &self.metadata
+  }
+
+  pub fn events(&self) -> &[ChangeEvent] {
+    self.events.as_slice()
+  }
+
+  pub fn into_events(self) -> Vec<ChangeEvent> {
+    self.events
+  }
+}
+
+#[derive(Debug)]
+pub enum Group {
+  /// A complete transaction, buffered in memory.
+  Transaction {
+    metadata: TransactionMetadata,
+    events: Vec<ChangeEvent>,
+  },
+  /// Part of an oversized transaction. Consumers that need atomicity must hold on to the changes
+  /// until they receive the `ChunkKind::End` chunk, and drop them on a `ChunkKind::Aborted` one.
+  Chunk(TransactionChunk),
+  /// A change that happened outside of any transaction (e.g. with a non-transactional engine).
+  Event(ChangeEvent),
+}
+
+/// Formats a line for the transaction or chunk, e.g. `TRANSACTION 3e11fa47-...:23 (2 events)`,
+/// then a line per change, indented.
+impl fmt::Display for Group {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let events = match self {
+      Group::Transaction { metadata, events } => {
+        write!(f, "TRANSACTION {}", metadata)?;
+        events
+      }
+      Group::Chunk(chunk) => {
+        write!(
+          f,
+          "CHUNK {} ({:?}) {}",
+          chunk.index, chunk.kind, chunk.metadata
+        )?;
+        &chunk.events
+      }
+      Group::Event(event) => return write!(f, "{}", event),
+    };
+    for event in events {
+      write!(f, "\n  {}", event)?;
+    }
+    Ok(())
+  }
+}
+
+struct Buffer {
+  metadata: TransactionMetadata,
+  events: Vec<ChangeEvent>,
+  size: usize,
+  total_size: u64,
+  chunks: u64,
+  // Changes of the transaction so far, chunks included.
+  event_count: u64,
+}
+
+/// Groups the changes found between `ChangeEvent::Begin` and `ChangeEvent::End` markers, which
+/// means the decoder must be configured with `EventDecoder::emit_transaction_markers`.
+///
+/// At most `max_buffer_size` bytes of changes are buffered. Past that, the transaction is
+/// streamed in chunks instead of being held in memory until its END marker.
+///
+/// A transaction without its END marker, followed by the BEGIN marker of the next one, is never
+/// emitted as complete: its buffered changes are dropped and counted by
+/// `Metrics::incomplete_transactions`, and a `ChunkKind::Aborted` chunk follows the chunks already
+/// emitted, if any.
+///
+/// The changes filtered out upstream leave their transaction behind: the `event_count` of the
+/// groups only counts the changes that are left, and the transactions left without any change are
+/// skipped, unless `emit_empty_transactions` is enabled.
+pub struct TransactionGrouper {
+  max_buffer_size: usize,
+  emit_empty_transactions: bool,
+  metrics: Arc<Metrics>,
+  buffer: Option<Buffer>,
+}
+
+impl TransactionGrouper {
+  pub fn new(metrics: Arc<Metrics>) -> Self {
+    Self {
+      max_buffer_size: DEFAULT_MAX_BUFFER_SIZE,
+      emit_empty_transactions: false,
+      metrics,
+      buffer: None,
+    }
+  }
+
+  pub fn max_buffer_size(mut self, max_buffer_size: usize) -> Self {
+    self.max_buffer_size = max_buffer_size;
+    self
+  }
+
+  /// Emits the transactions without changes as empty `Group::Transaction`s, e.g. for sinks that
+  /// record the position of every transaction.
+  pub fn emit_empty_transactions(mut self, enabled: bool) -> Self {
+    self.emit_empty_transactions = enabled;
+    self
+  }
+
+  /// Pushes the next change of the stream, returns a group once one is ready.
+  pub fn push(&mut self, event: ChangeEvent) -> Option<Group> {
+    match event {
+      ChangeEvent::Begin(metadata) => {
+        let aborted = self.buffer.take().and_then(|buffer| self.abort(buffer));
+        let events = Vec::with_capacity(self.estimated_event_count(&metadata));
+        self.buffer = Some(Buffer {
+          metadata,
+          events,
+          size: 0,
+          total_size: 0,
+          chunks: 0,
+          event_count: 0,
+        });
+        aborted
+      }
+      ChangeEvent::End(metadata) => self
+        .buffer
+        .take()
+        .and_then(|buffer| self.flush(buffer, metadata)),
+      event => {
+        let buffer = match self.buffer {
+          Some(ref mut buffer) => buffer,
+          None => return Some(Group::Event(event)),
+        };
+
+        let size = event.byte_size();
+        buffer.size += size;
+        buffer.total_size += size as u64;
+        buffer.event_count += 1;
+        buffer.events.push(event);
+
+        if buffer.size <= self.max_buffer_size {
+          return None;
+        }
+
+        if buffer.chunks == 0 {
+          self.metrics.incr_oversized_transactions();
+        }
+
+        let kind = if buffer.chunks == 0 {
+          ChunkKind::Begin
+        } else {
+          ChunkKind::Continue
+        };
+        let mut metadata = buffer.metadata.clone();
+        metadata.set_event_count(buffer.event_count);
+        let chunk = TransactionChunk {
+          kind,
+          index: buffer.chunks,
+          metadata,
+          events: std::mem::take(&mut buffer.events),
+        };
+        buffer.chunks += 1;
+        buffer.size = 0;
+        Some(Group::Chunk(chunk))
+      }
+    }
+  }
+
+  // Number of changes to make room for in the buffer of a transaction, up to the size of a chunk
+  // and `MAX_ESTIMATED_EVENT_COUNT`. MYSQL 8 logs the length of the transaction before its changes.
+  fn estimated_event_count(&self, metadata: &TransactionMetadata) -> usize {
+    let length = metadata.transaction_length().unwrap_or_default();
+    let length = length.min(self.max_buffer_size as u64);
+    (length / ESTIMATED_EVENT_SIZE).min(MAX_ESTIMATED_EVENT_COUNT) as usize
+  }
+
+  // Drops the changes of a transaction that never got its END marker, rather than pass them off as
+  // a complete transaction. Returns the chunk that tells the consumers of its chunks to drop them.
+  fn abort(&self, buffer: Buffer) -> Option<Group> {
+    self.metrics.incr_incomplete_transactions();
+    if buffer.chunks == 0 {
+      return None;
+    }
+
+    let mut metadata = buffer.metadata;
+    metadata.set_event_count(buffer.event_count);
+    Some(Group::Chunk(TransactionChunk {
+      kind: ChunkKind::Aborted,
+      index: buffer.chunks,
+      metadata,
+      events: Vec::new(),
+    }))
+  }
+
+  // Returns `None` for a transaction left without changes, unless they are emitted.
+  fn flush(&self, buffer: Buffer, mut metadata: TransactionMetadata) -> Option<Group> {
+    metadata.set_event_count(buffer.event_count);
+    if buffer.chunks == 0 {
+      if buffer.events.is_empty() && !self.emit_empty_transactions {
+        return None;
+      }
+      return Some(Group::Transaction {
+        metadata,
+        events: buffer.events,
+      });
+    }
+
+    self
+      .metrics
+      .add_oversized_transaction_bytes(buffer.total_size);
+    Some(Group::Chunk(TransactionChunk {
+      kind: ChunkKind::End,
+      index: buffer.chunks,
+      metadata,
+      events: buffer.events,
+    }))
+  }
+}
+
+/// Groups a stream of changes by transaction, see `TransactionGrouper`.
+pub fn group<S>(stream: S, mut grouper: TransactionGrouper) -> impl Stream<Item = Group>
+where
+  S: Stream<Item = ChangeEvent>,
+{
+  stream.filter_map(move |event| future::ready(grouper.push(event)))
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::classify::Operation;
+
+  fn statement(sql: &str) -> ChangeEvent {
+    ChangeEvent::Statement {
+      schema: "pets".into(),
+      sql: sql.into(),
+      operation: Operation::Insert,
+      tables: Vec::new(),
+      time_zone: None,
+    }
+  }
+
+  #[test]
+  fn groups_transactions() {
+    let mut grouper = TransactionGrouper::new(Arc::new(Metrics::default()));
+    assert!(grouper
+      .push(ChangeEvent::Begin(TransactionMetadata::default()))
+      .is_none());
+    assert!(grouper
+      .push(statement("INSERT INTO cats VALUES (1)"))
+      .is_none());
+    assert!(grouper
+      .push(statement("INSERT INTO cats VALUES (2)"))
+      .is_none());
+    match grouper.push(ChangeEvent::End(TransactionMetadata::default())) {
+      Some(Group::Transaction { events, .. }) => assert_eq!(2, events.len()),
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+
+    match grouper.push(statement("INSERT INTO cats VALUES (3)")) {
+      Some(Group::Event(_)) => {}
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+  }
+
+  fn end(event_count: u64) -> ChangeEvent {
+    let mut metadata = TransactionMetadata::default();
+    metadata.set_event_count(event_count);
+    ChangeEvent::End(metadata)
+  }
+
+  #[test]
+  fn counts_the_changes_left_by_the_filters() {
+    let mut grouper = TransactionGrouper::new(Arc::new(Metrics::default()));
+    // Three changes were decoded, one was filtered out.
+    grouper.push(ChangeEvent::Begin(TransactionMetadata::default()));
+    grouper.push(statement("INSERT INTO cats VALUES (1)"));
+    grouper.push(statement("INSERT INTO cats VALUES (2)"));
+    match grouper.push(end(3)) {
+      Some(Group::Transaction { metadata, events }) => {
+        assert_eq!(2, events.len());
+        assert_eq!(2, metadata.event_count());
+      }
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+
+    // All of them were filtered out.
+    grouper.push(ChangeEvent::Begin(TransactionMetadata::default()));
+    assert!(grouper.push(end(3)).is_none());
+    grouper.push(ChangeEvent::Begin(TransactionMetadata::default()));
+    assert!(grouper
+      .push(ChangeEvent::Begin(TransactionMetadata::default()))
+      .is_none());
+
+    let mut grouper =
+      TransactionGrouper::new(Arc::new(Metrics::default())).emit_empty_transactions(true);
+    grouper.push(ChangeEvent::Begin(TransactionMetadata::default()));
+    match grouper.push(end(3)) {
+      Some(Group::Transaction { metadata, events }) => {
+        assert!(events.is_empty());
+        assert_eq!(0, metadata.event_count());
+      }
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+  }
+
+  #[test]
+  fn presizes_buffers_by_transaction_length() {
+    let mut grouper = TransactionGrouper::new(Arc::new(Metrics::default())).max_buffer_size(16384);
+    let mut metadata = TransactionMetadata::default();
+    metadata.set_transaction_length(8192);
+    grouper.push(ChangeEvent::Begin(metadata.clone()));
+    grouper.push(statement("INSERT INTO cats VALUES (1)"));
+    match grouper.push(end(1)) {
+      Some(Group::Transaction { events, .. }) => assert_eq!(8, events.capacity()),
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+
+    // Up to the size of a chunk.
+    metadata.set_transaction_length(1 << 30);
+    grouper.push(ChangeEvent::Begin(metadata.clone()));
+    grouper.push(statement("INSERT INTO cats VALUES (1)"));
+    match grouper.push(end(1)) {
+      Some(Group::Transaction { events, .. }) => assert_eq!(16, events.capacity()),
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+
+    // Up to a fixed count, whatever the size of a chunk.
+    let mut grouper =
+      TransactionGrouper::new(Arc::new(Metrics::default())).max_buffer_size(usize::MAX);
+    metadata.set_transaction_length(u64::MAX);
+    grouper.push(ChangeEvent::Begin(metadata));
+    grouper.push(statement("INSERT INTO cats VALUES (1)"));
+    match grouper.push(end(1)) {
+      Some(Group::Transaction { events, .. }) => assert_eq!(4096, events.capacity()),
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+  }
+
+  #[test]
+  fn streams_oversized_transactions_in_chunks() {
+    let metrics = Arc::new(Metrics::default());
+    let mut grouper = TransactionGrouper::new(metrics.clone()).max_buffer_size(40);
+    let sql = "INSERT INTO cats VALUES (1)";
+
+    let mut chunks = Vec::new();
+    chunks.extend(grouper.push(ChangeEvent::Begin(TransactionMetadata::default())));
+    for _ in 0..5 {
+      chunks.extend(grouper.push(statement(sql)));
+    }
+    chunks.extend(grouper.push(ChangeEvent::End(TransactionMetadata::default())));
+
+    let kinds: Vec<(ChunkKind, usize, u64)> = chunks
+      .into_iter()
+      .map(|group| match group {
+        Group::Chunk(chunk) => (
+          chunk.kind(),
+          chunk.events().len(),
+          chunk.metadata().event_count(),
+        ),
+        unexpected => panic!("unexpected {:?}", unexpected),
+      })
+      .collect();
+
+    assert_eq!(
+      vec![
+        (ChunkKind::Begin, 2, 2),
+        (ChunkKind::Continue, 2, 4),
+        (ChunkKind::End, 1, 5)
+      ],
+      kinds
+    );
+    assert_eq!(1, metrics.oversized_transactions());
+    assert_eq!(
+      5 * (4 + sql.len()) as u64,
+      metrics.oversized_transaction_bytes()
+    );
+  }
+
+  #[test]
+  fn drops_transactions_without_end_markers() {
+    let metrics = Arc::new(Metrics::default());
+    let mut grouper = TransactionGrouper::new(metrics.clone());
+    grouper.push(ChangeEvent::Begin(TransactionMetadata::default()));
+    grouper.push(statement("INSERT INTO cats VALUES (1)"));
+    assert!(grouper
+      .push(ChangeEvent::Begin(TransactionMetadata::default()))
+      .is_none());
+    grouper.push(statement("INSERT INTO cats VALUES (2)"));
+    match grouper.push(end(1)) {
+      Some(Group::Transaction { events, .. }) => assert_eq!(1, events.len()),
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+    assert_eq!(1, metrics.incomplete_transactions());
+
+    // The chunks already streamed are called off.
+    let mut grouper = TransactionGrouper::new(metrics.clone()).max_buffer_size(40);
+    grouper.push(ChangeEvent::Begin(TransactionMetadata::default()));
+    grouper.push(statement("INSERT INTO cats VALUES (1)"));
+    assert!(grouper
+      .push(statement("INSERT INTO cats VALUES (2)"))
+      .is_some());
+    grouper.push(statement("INSERT INTO cats VALUES (3)"));
+    match grouper.push(ChangeEvent::Begin(TransactionMetadata::default())) {
+      Some(Group::Chunk(chunk)) => {
+        assert_eq!(ChunkKind::Aborted, chunk.kind());
+        assert!(chunk.events().is_empty());
+      }
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+    assert_eq!(2, metrics.incomplete_transactions());
+  }
+}
@@ -1,19 +1,54 @@
-use std::io;
+pub use tail_mysql_core::util::{
+  hex, null_terminated_pos, server_version_triple, unexpected_eof, unexpected_err,
+};
 
-pub fn unexpected_eof<E>(e: E) -> io::Error
-where
-  E: Into<Box<dyn std::error::Error + Send + Sync>>,
-{
-  io::Error::new(io::ErrorKind::UnexpectedEof, e)
+/// Formats `err` followed by its sources, e.g. `Failed due to IO error: Connection refused`.
+pub fn error_chain(err: &dyn std::error::Error) -> String {
+  let mut message = err.to_string();
+  let mut source = err.source();
+  while let Some(err) = source {
+    message.push_str(": ");
+    message.push_str(&err.to_string());
+    source = err.source();
+  }
+  message
 }
 
-pub fn unexpected_err<E>(e: E) -> io::Error
-where
-  E: Into<Box<dyn std::error::Error + Send + Sync>>,
-{
-  io::Error::new(io::ErrorKind::Other, e)
+/// Quotes a MYSQL identifier, e.g. a schema, table or column name.
+pub fn quote_ident(ident: &str) -> String {
+  format!("`{}`", ident.replace('`', "``"))
 }
 
-pub fn null_terminated_pos(b: &[u8]) -> usize {
-  b.iter().position(|b| *b == 0x00).unwrap_or(b.len())
+/// Quotes a MYSQL string literal.
+pub fn quote_literal(literal: &str) -> String {
+  format!("'{}'", literal.replace('\\', "\\\\").replace('\'', "''"))
+}
+
+// https://en.wikipedia.org/wiki/Xorshift
+pub(crate) struct XorShift(u64);
+
+impl XorShift {
+  pub(crate) fn new(seed: u64) -> Self {
+    // The state must never be 0.
+    Self(seed.max(1))
+  }
+
+  pub(crate) fn next_u64(&mut self) -> u64 {
+    let mut x = self.0;
+    x ^= x << 13;
+    x ^= x >> 7;
+    x ^= x << 17;
+    self.0 = x;
+    x
+  }
+
+  // Slightly biased towards small values, which does not matter to fault injection or load
+  // generation. `n` must not be 0.
+  pub(crate) fn below(&mut self, n: u64) -> u64 {
+    self.next_u64() % n
+  }
+
+  pub(crate) fn chance(&mut self, rate: f64) -> bool {
+    rate > 0.0 && (self.next_u64() as f64 / u64::MAX as f64) < rate
+  }
 }
@@ -0,0 +1,688 @@
+// Consistency verification between MYSQL and a MYSQL compatible target, a la pt-table-checksum.
+//
+// Tables are split in chunks of `chunk_size` rows, walking the primary key on the source. Every
+// chunk is then checksummed on both sides with the same query (a BIT_XOR of the CRC32 of every
+// row), and the chunks whose row count or checksum differ are reported as drifted.
+//
+// Chunks are only cheap when their primary key range is scanned through the primary key: the plan
+// of the first chunk is checked with EXPLAIN, and full scans of large tables are warned about
+// before they start.
+//
+// With a target chunk time, the chunk size adapts to how long the source takes to checksum the
+// previous chunks, a la pt-table-checksum's --chunk-time, within the configured bounds and the
+// number of rows of average width that fit in the maximum chunk size in bytes.
+//
+// With a retry policy, the tables whose connections are lost are verified again over new
+// connections, from the chunk that was being checksummed.
+
+use super::conn::{Connection, DriverError};
+use super::retry::{Retry, RetryPolicy};
+use super::util::{quote_ident, quote_literal, unexpected_err};
+use super::value::Value;
+use futures::io::{AsyncRead, AsyncWrite};
+use futures::stream::{self, StreamExt};
+use std::fmt;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+type VerifyResult<T> = Result<T, DriverError>;
+
+/// Row count and checksum of a chunk of rows.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct Checksum {
+  rows: u64,
+  crc: u64,
+}
+
+impl Checksum {
+  pub fn rows(&self) -> u64 {
+    self.rows
+  }
+
+  pub fn crc(&self) -> u64 {
+    self.crc
+  }
+}
+
+#[derive(Debug)]
+pub struct ChunkReport {
+  lower: Option<Vec<String>>,
+  upper: Option<Vec<String>>,
+  source: Checksum,
+  target: Checksum,
+}
+
+impl ChunkReport {
+  /// Inclusive lower bound of the chunk primary key, `None` for the first chunk.
+  pub fn lower(&self) -> Option<&[String]> {
+    self.lower.as_deref()
+  }
+
+  /// Exclusive upper bound of the chunk primary key, `None` for the last chunk.
+  pub fn upper(&self) -> Option<&[String]> {
+    self.upper.as_deref()
+  }
+
+  pub fn source(&self) -> Checksum {
+    self.source
+  }
+
+  pub fn target(&self) -> Checksum {
+    self.target
+  }
+
+  pub fn is_consistent(&self) -> bool {
+    self.source == self.target
+  }
+}
+
+#[derive(Debug)]
+pub struct TableReport {
+  schema: String,
+  table: String,
+  chunks: Vec<ChunkReport>,
+}
+
+impl TableReport {
+  pub fn schema_str(&self) -> &str {
+    self.schema.as_str()
+  }
+
+  pub fn table_str(&self) -> &str {
+    self.table.as_str()
+  }
+
+  pub fn chunks(&self) -> &[ChunkReport] {
+    self.chunks.as_slice()
+  }
+
+  pub fn drifted_chunks(&self) -> impl Iterator<Item = &ChunkReport> {
+    self.chunks.iter().filter(|chunk| !chunk.is_consistent())
+  }
+
+  pub fn is_consistent(&self) -> bool {
+    self.drifted_chunks().next().is_none()
+  }
+}
+
+impl fmt::Display for TableReport {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let drifted = self.drifted_chunks().count();
+    write!(
+      f,
+      "{}.{}: {} chunks, {} drifted",
+      self.schema,
+      self.table,
+      self.chunks.len(),
+      drifted
+    )?;
+    for chunk in self.drifted_chunks() {
+      write!(
+        f,
+        "\n  [{:?}, {:?}): source rows={} crc={:x}, target rows={} crc={:x}",
+        chunk.lower,
+        chunk.upper,
+        chunk.source.rows,
+        chunk.source.crc,
+        chunk.target.rows,
+        chunk.target.crc
+      )?;
+    }
+    Ok(())
+  }
+}
+
+// Adapts the number of rows of the next chunk to the time the previous chunks took.
+#[derive(Clone, Copy, Debug)]
+struct ChunkSizer {
+  size: u64,
+  min: u64,
+  max: u64,
+  target: Option<Duration>,
+}
+
+impl ChunkSizer {
+  fn observe(&mut self, rows: u64, elapsed: Duration) {
+    let target = match self.target {
+      Some(target) if rows > 0 => target,
+      _ => return,
+    };
+
+    // Number of rows checksummed in `target` at the rate of the previous chunk.
+    let elapsed = elapsed.as_secs_f64().max(0.001);
+    let ideal = rows as f64 * target.as_secs_f64() / elapsed;
+    // Grows or shrinks by at most a factor of 2 per chunk, so that a single outlier (e.g. a cold
+    // buffer pool) does not swing it.
+    let size = ideal.clamp(self.size as f64 / 2.0, self.size as f64 * 2.0);
+    self.size = (size as u64).clamp(self.min, self.max);
+  }
+}
+
+pub struct Verifier {
+  chunk_size: u64,
+  min_chunk_size: u64,
+  max_chunk_size: u64,
+  max_chunk_bytes: Option<u64>,
+  chunk_time: Option<Duration>,
+  parallelism: usize,
+  full_scan_warning_rows: u64,
+  retry: Option<RetryPolicy>,
+}
+
+impl Default for Verifier {
+  fn default() -> Self {
+    Self {
+      chunk_size: 1000,
+      min_chunk_size: 100,
+      max_chunk_size: 100_000,
+      max_chunk_bytes: None,
+      chunk_time: None,
+      parallelism: 1,
+      full_scan_warning_rows: 1_000_000,
+      retry: None,
+    }
+  }
+}
+
+impl Verifier {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Number of rows of the chunks, or of the first chunk when the chunk size adapts to
+  /// `chunk_time`.
+  pub fn chunk_size(mut self, chunk_size: u64) -> Self {
+    self.chunk_size = chunk_size.max(1);
+    self
+  }
+
+  /// Adapts the chunk size so that checksumming a chunk takes about `chunk_time` on the source.
+  pub fn chunk_time(mut self, chunk_time: Duration) -> Self {
+    self.chunk_time = Some(chunk_time);
+    self
+  }
+
+  /// Bounds of the adaptive chunk size, 100 and 100k rows by default.
+  pub fn chunk_size_limits(mut self, min: u64, max: u64) -> Self {
+    self.min_chunk_size = min.max(1);
+    self.max_chunk_size = max.max(self.min_chunk_size);
+    self
+  }
+
+  /// Caps the chunk size to the number of rows of the table average width (from
+  /// information_schema) that fit in `bytes`, so that tables of wide rows get smaller chunks.
+  pub fn max_chunk_bytes(mut self, bytes: u64) -> Self {
+    self.max_chunk_bytes = Some(bytes);
+    self
+  }
+
+  /// Number of tables verified at once by `verify_tables`, 1 by default.
+  pub fn parallelism(mut self, parallelism: usize) -> Self {
+    self.parallelism = parallelism.max(1);
+    self
+  }
+
+  /// Estimated number of rows over which chunks that are not scanned through the primary key
+  /// are warned about, 1M by default.
+  pub fn full_scan_warning_rows(mut self, rows: u64) -> Self {
+    self.full_scan_warning_rows = rows;
+    self
+  }
+
+  /// Reconnects, per `policy`, when `verify_tables` loses the connections of a table. Attempts
+  /// are counted from the last chunk checksummed. Lock wait timeouts and deadlocks are retried by
+  /// the connections themselves, see `ConnectionOptions::query_retry`.
+  pub fn retry(mut self, policy: RetryPolicy) -> Self {
+    self.retry = Some(policy);
+    self
+  }
+
+  /// Checksums `schema`.`table` on both connections and compares them chunk by chunk. The table
+  /// must have a primary key.
+  pub async fn verify_table<S, T>(
+    &self,
+    source: &mut Connection<S>,
+    target: &mut Connection<T>,
+    schema: &str,
+    table: &str,
+  ) -> VerifyResult<TableReport>
+  where
+    S: AsyncRead + AsyncWrite + Unpin,
+    T: AsyncRead + AsyncWrite + Unpin,
+  {
+    let mut chunks = Vec::new();
+    self
+      .verify_chunks(source, target, schema, table, &mut chunks)
+      .await?;
+    Ok(TableReport {
+      schema: schema.to_string(),
+      table: table.to_string(),
+      chunks,
+    })
+  }
+
+  // Checksums the chunks of `schema`.`table` that follow `chunks`, and appends them to it.
+  async fn verify_chunks<S, T>(
+    &self,
+    source: &mut Connection<S>,
+    target: &mut Connection<T>,
+    schema: &str,
+    table: &str,
+    chunks: &mut Vec<ChunkReport>,
+  ) -> VerifyResult<()>
+  where
+    S: AsyncRead + AsyncWrite + Unpin,
+    T: AsyncRead + AsyncWrite + Unpin,
+  {
+    let mut lower = match chunks.last() {
+      Some(ChunkReport { upper: None, .. }) => return Ok(()),
+      Some(chunk) => chunk.upper.clone(),
+      None => None,
+    };
+    let columns = column_names(source, schema, table).await?;
+    let primary_key = primary_key(source, schema, table).await?;
+    if primary_key.is_empty() {
+      return Err(
+        unexpected_err(format!("{}.{} does not have a primary key", schema, table)).into(),
+      );
+    }
+
+    let mut sizer = self.chunk_sizer(source, schema, table).await?;
+    loop {
+      let upper = next_boundary(
+        source,
+        schema,
+        table,
+        &primary_key,
+        lower.as_deref(),
+        sizer.size,
+      )
+      .await?;
+
+      let sql = checksum_sql(
+        schema,
+        table,
+        &columns,
+        &primary_key,
+        lower.as_deref(),
+        upper.as_deref(),
+      );
+      if chunks.is_empty() {
+        self.check_plan(source, schema, table, &sql).await?;
+      }
+      let started_at = Instant::now();
+      let source_checksum = checksum(source, &sql).await?;
+      sizer.observe(source_checksum.rows, started_at.elapsed());
+      let target_checksum = checksum(target, &sql).await?;
+      chunks.push(ChunkReport {
+        lower: lower.clone(),
+        upper: upper.clone(),
+        source: source_checksum,
+        target: target_checksum,
+      });
+
+      match upper {
+        Some(upper) => lower = Some(upper),
+        None => break,
+      }
+    }
+
+    Ok(())
+  }
+
+  // Warns when the chunk query `sql` does not scan a range of the primary key, on tables large
+  // enough for it to matter.
+  async fn check_plan<S: AsyncRead + AsyncWrite + Unpin>(
+    &self,
+    conn: &mut Connection<S>,
+    schema: &str,
+    table: &str,
+    sql: &str,
+  ) -> VerifyResult<()> {
+    let plan = conn.explain(sql).await?;
+    let rows = plan.estimated_rows();
+    if rows < self.full_scan_warning_rows || plan.uses_key("PRIMARY") {
+      return Ok(());
+    }
+
+    if plan.is_full_scan() {
+      eprintln!(
+        "warning: chunks of {}.{} are full table scans of ~{} rows",
+        schema, table, rows
+      );
+    } else {
+      eprintln!(
+        "warning: chunks of {}.{} are not scanned through the primary key (~{} rows)",
+        schema, table, rows
+      );
+    }
+    Ok(())
+  }
+
+  /// Verifies `tables`, `parallelism` at once. Every table is verified over its own
+  /// connections, opened by `connect`. Reports are returned in the order of `tables`.
+  pub async fn verify_tables<C, F, S, T>(
+    &self,
+    tables: &[(String, String)],
+    connect: C,
+  ) -> Vec<VerifyResult<TableReport>>
+  where
+    C: Fn() -> F,
+    F: Future<Output = VerifyResult<(Connection<S>, Connection<T>)>>,
+    S: AsyncRead + AsyncWrite + Unpin,
+    T: AsyncRead + AsyncWrite + Unpin,
+  {
+    let connect = &connect;
+    stream::iter(tables)
+      .map(|(schema, table)| async move {
+        let mut chunks = Vec::new();
+        let mut attempt = 1;
+        loop {
+          let verified_chunks = chunks.len();
+          let verified = async {
+            let (mut source, mut target) = connect().await?;
+            self
+              .verify_chunks(&mut source, &mut target, schema, table, &mut chunks)
+              .await
+          };
+          let err = match verified.await {
+            Ok(()) => {
+              return Ok(TableReport {
+                schema: schema.to_string(),
+                table: table.to_string(),
+                chunks,
+              })
+            }
+            Err(err) => err,
+          };
+
+          if chunks.len() > verified_chunks {
+            attempt = 1;
+          }
+          match self.retry {
+            Some(policy) if policy.allows(attempt) && Retry::of(&err) == Retry::Reconnect => {
+              policy.wait(attempt, &err).await;
+              attempt += 1;
+            }
+            _ => return Err(err),
+          }
+        }
+      })
+      .buffered(self.parallelism)
+      .collect()
+      .await
+  }
+
+  async fn chunk_sizer<S: AsyncRead + AsyncWrite + Unpin>(
+    &self,
+    conn: &mut Connection<S>,
+    schema: &str,
+    table: &str,
+  ) -> VerifyResult<ChunkSizer> {
+    let mut max = self.max_chunk_size;
+    if let Some(max_chunk_bytes) = self.max_chunk_bytes {
+      let row_width = average_row_length(conn, schema, table).await?;
+      // Unknown, e.g. for tables that were never analyzed.
+      if let Some(rows) = max_chunk_bytes.checked_div(row_width) {
+        max = max.min(rows).max(self.min_chunk_size);
+      }
+    }
+
+    let (min, size) = match self.chunk_time {
+      Some(_) => (
+        self.min_chunk_size,
+        self.chunk_size.clamp(self.min_chunk_size, max),
+      ),
+      None => (1, self.chunk_size.min(max)),
+    };
+    Ok(ChunkSizer {
+      size,
+      min,
+      max,
+      target: self.chunk_time,
+    })
+  }
+}
+
+// Primary key of the first row of the next chunk, `None` when the current chunk is the last.
+async fn next_boundary<S: AsyncRead + AsyncWrite + Unpin>(
+  conn: &mut Connection<S>,
+  schema: &str,
+  table: &str,
+  primary_key: &[String],
+  lower: Option<&[String]>,
+  chunk_size: u64,
+) -> VerifyResult<Option<Vec<String>>> {
+  let key = column_list(primary_key);
+  let sql = format!(
+    "SELECT {} FROM {}.{} WHERE {} ORDER BY {} LIMIT 1 OFFSET {}",
+    key,
+    quote_ident(schema),
+    quote_ident(table),
+    range_predicate(primary_key, lower, None),
+    key,
+    chunk_size
+  );
+
+  let results = conn.query(sql).await?;
+  let boundary = results.first().map(|row| {
+    row
+      .values()
+      .iter()
+      .map(|value| value.as_str().unwrap_or("").to_string())
+      .collect()
+  });
+  Ok(boundary)
+}
+
+// Average width of the rows of the table in bytes, as estimated by the storage engine. 0 when
+// unknown.
+async fn average_row_length<S: AsyncRead + AsyncWrite + Unpin>(
+  conn: &mut Connection<S>,
+  schema: &str,
+  table: &str,
+) -> VerifyResult<u64> {
+  let sql = format!(
+    "SELECT AVG_ROW_LENGTH FROM information_schema.TABLES \
+     WHERE TABLE_SCHEMA = {} AND TABLE_NAME = {}",
+    quote_literal(schema),
+    quote_literal(table)
+  );
+  let lengths = strings(conn, &sql).await?;
+  Ok(
+    lengths
+      .first()
+      .and_then(|length| length.parse().ok())
+      .unwrap_or_default(),
+  )
+}
+
+pub(crate) async fn column_names<S: AsyncRead + AsyncWrite + Unpin>(
+  conn: &mut Connection<S>,
+  schema: &str,
+  table: &str,
+) -> VerifyResult<Vec<String>> {
+  let sql = format!(
+    "SELECT COLUMN_NAME FROM information_schema.COLUMNS \
+     WHERE TABLE_SCHEMA = {} AND TABLE_NAME = {} ORDER BY ORDINAL_POSITION",
+    quote_literal(schema),
+    quote_literal(table)
+  );
+  strings(conn, &sql).await
+}
+
+pub(crate) async fn primary_key<S: AsyncRead + AsyncWrite + Unpin>(
+  conn: &mut Connection<S>,
+  schema: &str,
+  table: &str,
+) -> VerifyResult<Vec<String>> {
+  let sql = format!(
+    "SELECT COLUMN_NAME FROM information_schema.KEY_COLUMN_USAGE \
+     WHERE TABLE_SCHEMA = {} AND TABLE_NAME = {} AND CONSTRAINT_NAME = 'PRIMARY' \
+     ORDER BY ORDINAL_POSITION",
+    quote_literal(schema),
+    quote_literal(table)
+  );
+  strings(conn, &sql).await
+}
+
+async fn strings<S: AsyncRead + AsyncWrite + Unpin>(
+  conn: &mut Connection<S>,
+  sql: &str,
+) -> VerifyResult<Vec<String>> {
+  let results = conn.query(sql).await?;
+  let strings = results
+    .iter()
+    .filter_map(|row| row.values().first().and_then(Value::as_str).map(Into::into))
+    .collect();
+  Ok(strings)
+}
+
+async fn checksum<S: AsyncRead + AsyncWrite + Unpin>(
+  conn: &mut Connection<S>,
+  sql: &str,
+) -> VerifyResult<Checksum> {
+  let results = conn.query(sql).await?;
+  let parse = |value: Option<&Value>| {
+    value
+      .and_then(Value::as_str)
+      .and_then(|value| value.parse::<u64>().ok())
+      .ok_or_else(|| unexpected_err("unexpected checksum result"))
+  };
+
+  let row = results
+    .first()
+    .ok_or_else(|| unexpected_err("unexpected empty checksum result"))?;
+  let values = row.values();
+  Ok(Checksum {
+    rows: parse(values.first())?,
+    crc: parse(values.get(1))?,
+  })
+}
+
+fn checksum_sql(
+  schema: &str,
+  table: &str,
+  columns: &[String],
+  primary_key: &[String],
+  lower: Option<&[String]>,
+  upper: Option<&[String]>,
+) -> String {
+  // NULLs are skipped by CONCAT_WS, so which columns are NULL is checksummed separately.
+  let nulls: Vec<String> = columns
+    .iter()
+    .map(|column| format!("ISNULL({})", quote_ident(column)))
+    .collect();
+  format!(
+    "SELECT COUNT(*), COALESCE(BIT_XOR(CAST(CRC32(CONCAT_WS('#', {}, CONCAT({}))) AS UNSIGNED)), 0) \
+     FROM {}.{} WHERE {}",
+    column_list(columns),
+    nulls.join(", "),
+    quote_ident(schema),
+    quote_ident(table),
+    range_predicate(primary_key, lower, upper)
+  )
+}
+
+fn range_predicate(
+  primary_key: &[String],
+  lower: Option<&[String]>,
+  upper: Option<&[String]>,
+) -> String {
+  let key = format!("({})", column_list(primary_key));
+  let tuple = |values: &[String]| {
+    let values: Vec<String> = values.iter().map(|value| quote_literal(value)).collect();
+    format!("({})", values.join(", "))
+  };
+
+  let mut predicates = Vec::new();
+  if let Some(lower) = lower {
+    predicates.push(format!("{} >= {}", key, tuple(lower)));
+  }
+  if let Some(upper) = upper {
+    predicates.push(format!("{} < {}", key, tuple(upper)));
+  }
+
+  if predicates.is_empty() {
+    "1 = 1".to_string()
+  } else {
+    predicates.join(" AND ")
+  }
+}
+
+fn column_list(columns: &[String]) -> String {
+  let columns: Vec<String> = columns.iter().map(|column| quote_ident(column)).collect();
+  columns.join(", ")
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn builds_chunk_checksum_query() {
+    let columns = vec!["id".to_string(), "name".to_string()];
+    let primary_key = vec!["id".to_string()];
+    let lower = vec!["1000".to_string()];
+
+    assert_eq!(
+      "SELECT COUNT(*), COALESCE(BIT_XOR(CAST(CRC32(CONCAT_WS('#', `id`, `name`, \
+       CONCAT(ISNULL(`id`), ISNULL(`name`)))) AS UNSIGNED)), 0) \
+       FROM `pets`.`cats` WHERE (`id`) >= ('1000')",
+      checksum_sql("pets", "cats", &columns, &primary_key, Some(&lower), None)
+    );
+  }
+
+  #[test]
+  fn adapts_chunk_size_to_chunk_time() {
+    let mut sizer = ChunkSizer {
+      size: 1000,
+      min: 100,
+      max: 10_000,
+      target: Some(Duration::from_millis(500)),
+    };
+
+    // Grows at most twofold per chunk.
+    sizer.observe(1000, Duration::from_millis(50));
+    assert_eq!(2000, sizer.size);
+    sizer.observe(2000, Duration::from_millis(400));
+    assert_eq!(2500, sizer.size);
+    sizer.observe(2500, Duration::from_secs(10));
+    assert_eq!(1250, sizer.size);
+
+    // Within the bounds.
+    for _ in 0..10 {
+      sizer.observe(sizer.size, Duration::from_secs(60));
+    }
+    assert_eq!(100, sizer.size);
+    for _ in 0..10 {
+      sizer.observe(sizer.size, Duration::from_millis(1));
+    }
+    assert_eq!(10_000, sizer.size);
+
+    // The last chunk has less rows.
+    sizer.observe(0, Duration::from_millis(1));
+    assert_eq!(10_000, sizer.size);
+
+    let mut fixed = ChunkSizer {
+      target: None,
+      ..sizer
+    };
+    fixed.observe(1, Duration::from_secs(60));
+    assert_eq!(10_000, fixed.size);
+  }
+
+  #[test]
+  fn quotes_identifiers_and_literals() {
+    assert_eq!("`we``ird`", quote_ident("we`ird"));
+    assert_eq!("'O''Malley\\\\'", quote_literal("O'Malley\\"));
+    assert_eq!(
+      "(`a`, `b`) >= ('1', 'x') AND (`a`, `b`) < ('2', 'y')",
+      range_predicate(
+        &["a".to_string(), "b".to_string()],
+        Some(&["1".to_string(), "x".to_string()]),
+        Some(&["2".to_string(), "y".to_string()])
+      )
+    );
+  }
+}
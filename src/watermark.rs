@@ -0,0 +1,302 @@
+// Progress of the changes through a pipeline, for orchestrators to gauge its health.
+//
+// Positions are reported as seen when the changes up to them are read from the stream, and as
+// acked once the sinks are done with them. The high watermark is the newest position seen, the low
+// watermark the oldest position seen but not acked yet: the gap between them is the work in flight,
+// and a low watermark that stops moving points at a stuck sink. The checkpoint is the newest
+// position acked with every position seen before it, which a stream can resume from without
+// skipping changes, see `checkpoint::Checkpointer`.
+//
+// `DestinationWatermarks` keeps watermarks per destination of the changes, e.g. per sink or per
+// group of tables a router splits them between, so that a stalled destination only holds back its
+// own checkpoint. A transaction is seen by the destinations its changes go to, and skipped by the
+// others, whose checkpoints move past it once they acked what they were sent before it.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::iter::FromIterator;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+/// Position in the binlog of the source, e.g. `mysql-bin.000042:1337`.
+///
+/// Positions are ordered by file, then by offset in the file, which holds as long as the names of
+/// the binlog files sort in the order they were created (as the default names do).
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct BinlogPosition {
+  file: String,
+  position: u32,
+}
+
+impl BinlogPosition {
+  pub fn new(file: impl Into<String>, position: u32) -> Self {
+    Self {
+      file: file.into(),
+      position,
+    }
+  }
+
+  pub fn file_str(&self) -> &str {
+    self.file.as_str()
+  }
+
+  pub fn position(&self) -> u32 {
+    self.position
+  }
+}
+
+impl fmt::Display for BinlogPosition {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}:{}", self.file, self.position)
+  }
+}
+
+/// Parses the `FILE:POSITION` form of `Display`.
+impl FromStr for BinlogPosition {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    s.rsplit_once(':')
+      .and_then(|(file, position)| Some(Self::new(file, position.parse().ok()?)))
+      .filter(|position| !position.file.is_empty())
+      .ok_or_else(|| format!("invalid binlog position `{}`, expected FILE:POSITION", s))
+  }
+}
+
+#[derive(Debug, Default)]
+struct State {
+  // Number of times every position was seen, and not acked yet.
+  pending: BTreeMap<BinlogPosition, u64>,
+  // Positions acked while an older one is still pending.
+  acked: BTreeSet<BinlogPosition>,
+  high: Option<BinlogPosition>,
+  checkpoint: Option<BinlogPosition>,
+}
+
+/// Low and high watermarks of a pipeline. Cheap to clone and to share between tasks, every clone
+/// updates the same watermarks.
+#[derive(Clone, Debug, Default)]
+pub struct Watermarks {
+  state: Arc<Mutex<State>>,
+}
+
+impl Watermarks {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Records that the changes up to `position` were read, and wait for the sinks. Positions seen
+  /// several times, e.g. once per sink, must be acked as many times.
+  pub fn seen(&self, position: BinlogPosition) {
+    let mut state = self.state.lock().unwrap();
+    if state.high.as_ref().is_none_or(|high| *high < position) {
+      state.high = Some(position.clone());
+    }
+    *state.pending.entry(position).or_default() += 1;
+  }
+
+  /// Records that a sink is done with the changes up to `position`. Positions may be acked in any
+  /// order.
+  pub fn ack(&self, position: &BinlogPosition) {
+    let mut state = self.state.lock().unwrap();
+    if let Some(count) = state.pending.get_mut(position) {
+      *count -= 1;
+      if *count == 0 {
+        state.pending.remove(position);
+        state.acked.insert(position.clone());
+        let acked = match state.pending.keys().next().cloned() {
+          Some(low) => {
+            let pending = state.acked.split_off(&low);
+            std::mem::replace(&mut state.acked, pending)
+          }
+          None => std::mem::take(&mut state.acked),
+        };
+        if let Some(newest) = acked.into_iter().next_back() {
+          state.checkpoint = Some(newest);
+        }
+      }
+    }
+  }
+
+  /// Records that the changes up to `position` do not concern the sinks of these watermarks, e.g.
+  /// a transaction without changes for them: the checkpoint moves past it once the positions seen
+  /// before it are acked.
+  pub fn skip(&self, position: BinlogPosition) {
+    self.seen(position.clone());
+    self.ack(&position);
+  }
+
+  /// Newest position acked along with every position seen before it, `None` until then.
+  pub fn checkpoint(&self) -> Option<BinlogPosition> {
+    self.state.lock().unwrap().checkpoint.clone()
+  }
+
+  /// Oldest position seen but not acked yet, `None` when the sinks caught up.
+  pub fn low(&self) -> Option<BinlogPosition> {
+    let state = self.state.lock().unwrap();
+    state.pending.keys().next().cloned()
+  }
+
+  /// Newest position seen.
+  pub fn high(&self) -> Option<BinlogPosition> {
+    self.state.lock().unwrap().high.clone()
+  }
+
+  /// Number of positions seen but not acked yet.
+  pub fn pending(&self) -> usize {
+    self.state.lock().unwrap().pending.len()
+  }
+}
+
+/// Watermarks of every destination of the changes. Cheap to clone and to share between tasks,
+/// every clone updates the same watermarks.
+#[derive(Clone, Debug, Default)]
+pub struct DestinationWatermarks {
+  destinations: Arc<Mutex<BTreeMap<String, Watermarks>>>,
+}
+
+impl DestinationWatermarks {
+  /// Watermarks of the destinations `names`.
+  pub fn new<'a>(names: impl IntoIterator<Item = &'a str>) -> Self {
+    let destinations = names
+      .into_iter()
+      .map(|name| (name.to_string(), Watermarks::new()))
+      .collect();
+    Self {
+      destinations: Arc::new(Mutex::new(destinations)),
+    }
+  }
+
+  /// Watermarks of the destination `name`, for its sink to ack the positions it is done with, or
+  /// for a `checkpoint::Checkpointer` to save its checkpoint. Destinations that were not named
+  /// upfront only see the transactions from their first one on.
+  pub fn destination(&self, name: &str) -> Watermarks {
+    let mut destinations = self.destinations.lock().unwrap();
+    destinations.entry(name.to_string()).or_default().clone()
+  }
+
+  /// Records that the transaction ending at `position` has changes for the destinations `names`,
+  /// which ack it once done with it, and none for the others, which skip it.
+  pub fn route<'a>(&self, position: &BinlogPosition, names: impl IntoIterator<Item = &'a str>) {
+    let names = BTreeSet::from_iter(names);
+    for name in &names {
+      self.destination(name);
+    }
+    let destinations = self.destinations.lock().unwrap();
+    for (name, watermarks) in destinations.iter() {
+      if names.contains(name.as_str()) {
+        watermarks.seen(position.clone());
+      } else {
+        watermarks.skip(position.clone());
+      }
+    }
+  }
+
+  /// Checkpoint of every destination.
+  pub fn checkpoints(&self) -> BTreeMap<String, Option<BinlogPosition>> {
+    let destinations = self.destinations.lock().unwrap();
+    destinations
+      .iter()
+      .map(|(name, watermarks)| (name.clone(), watermarks.checkpoint()))
+      .collect()
+  }
+
+  /// Oldest checkpoint of the destinations, which a stream feeding all of them can resume from.
+  /// `None` until every destination has one.
+  pub fn checkpoint(&self) -> Option<BinlogPosition> {
+    let checkpoints = self.checkpoints();
+    checkpoints
+      .into_values()
+      .try_fold(None, |oldest: Option<BinlogPosition>, checkpoint| {
+        let checkpoint = checkpoint?;
+        Some(Some(
+          oldest.map_or(checkpoint.clone(), |oldest| oldest.min(checkpoint)),
+        ))
+      })
+      .flatten()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn tracks_low_and_high_watermarks() {
+    let watermarks = Watermarks::new();
+    let position = |file: &str, position| BinlogPosition::new(file, position);
+    assert_eq!(None, watermarks.low());
+    assert_eq!(None, watermarks.high());
+
+    watermarks.seen(position("mysql-bin.000001", 400));
+    watermarks.seen(position("mysql-bin.000001", 800));
+    watermarks.seen(position("mysql-bin.000002", 120));
+    assert_eq!(Some(position("mysql-bin.000001", 400)), watermarks.low());
+    assert_eq!(Some(position("mysql-bin.000002", 120)), watermarks.high());
+
+    // Acked out of order, e.g. by a sink applying transactions in parallel.
+    watermarks.ack(&position("mysql-bin.000001", 800));
+    assert_eq!(Some(position("mysql-bin.000001", 400)), watermarks.low());
+    assert_eq!(None, watermarks.checkpoint());
+    watermarks.ack(&position("mysql-bin.000001", 400));
+    assert_eq!(Some(position("mysql-bin.000002", 120)), watermarks.low());
+    assert_eq!(
+      Some(position("mysql-bin.000001", 800)),
+      watermarks.checkpoint()
+    );
+    assert_eq!(1, watermarks.pending());
+
+    // Seen by two sinks.
+    let clone = watermarks.clone();
+    clone.seen(position("mysql-bin.000002", 120));
+    watermarks.ack(&position("mysql-bin.000002", 120));
+    assert_eq!(Some(position("mysql-bin.000002", 120)), clone.low());
+    clone.ack(&position("mysql-bin.000002", 120));
+    assert_eq!(None, watermarks.low());
+    assert_eq!(Some(position("mysql-bin.000002", 120)), watermarks.high());
+    assert_eq!(
+      Some(position("mysql-bin.000002", 120)),
+      watermarks.checkpoint()
+    );
+
+    assert_eq!(
+      "mysql-bin.000002:120",
+      position("mysql-bin.000002", 120).to_string()
+    );
+    assert_eq!(
+      Ok(position("mysql-bin.000002", 120)),
+      "mysql-bin.000002:120".parse()
+    );
+    assert!("mysql-bin.000002".parse::<BinlogPosition>().is_err());
+  }
+
+  #[test]
+  fn tracks_checkpoints_by_destination() {
+    let position = |position| BinlogPosition::new("mysql-bin.000001", position);
+    let destinations = DestinationWatermarks::new(vec!["orders", "users"]);
+    destinations.route(&position(400), vec!["orders"]);
+    destinations.route(&position(800), vec!["users"]);
+    destinations.route(&position(1200), vec!["orders", "users"]);
+
+    // The sink of orders is stalled on the transaction ending at 400, users keeps moving.
+    let users = destinations.destination("users");
+    users.ack(&position(800));
+    users.ack(&position(1200));
+    assert_eq!(Some(position(1200)), users.checkpoint());
+    assert_eq!(None, destinations.destination("orders").checkpoint());
+    assert_eq!(None, destinations.checkpoint());
+
+    let orders = destinations.destination("orders");
+    orders.ack(&position(400));
+    assert_eq!(Some(position(800)), orders.checkpoint());
+    assert_eq!(Some(position(800)), destinations.checkpoint());
+    orders.ack(&position(1200));
+    assert_eq!(
+      vec![
+        ("orders".to_string(), Some(position(1200))),
+        ("users".to_string(), Some(position(1200)))
+      ],
+      destinations.checkpoints().into_iter().collect::<Vec<_>>()
+    );
+  }
+}